@@ -0,0 +1,166 @@
+use bars_client::Aerodrome;
+
+use bars_config::{Aerodrome as Config, Edge, Grid, Lattice};
+
+use criterion::{
+	criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion,
+};
+
+const SIZES: [usize; 3] = [4, 16, 32];
+
+fn grid(size: usize) -> Grid {
+	Grid {
+		size,
+		children: 2,
+		..Grid::default()
+	}
+}
+
+/// Creates an aerodrome which can be changed without control, as there is no
+/// server to give it.
+fn offline(config: Config) -> Aerodrome {
+	let mut aerodrome = Aerodrome::new(config);
+	aerodrome.set_offline(true);
+	aerodrome
+}
+
+fn new(c: &mut Criterion) {
+	let mut group = c.benchmark_group("new");
+
+	for size in SIZES {
+		let config = grid(size).aerodrome();
+		group.bench_with_input(
+			BenchmarkId::from_parameter(size),
+			&config,
+			|b, config| {
+				b.iter_batched(|| config.clone(), Aerodrome::new, BatchSize::LargeInput)
+			},
+		);
+	}
+
+	group.finish();
+}
+
+fn take_pending(c: &mut Criterion) {
+	let mut group = c.benchmark_group("take_pending");
+
+	for size in SIZES {
+		let grid = grid(size);
+		let config = grid.aerodrome();
+		group.bench_with_input(
+			BenchmarkId::from_parameter(size),
+			&config,
+			|b, config| {
+				b.iter_batched_ref(
+					|| {
+						let mut aerodrome = offline(config.clone());
+						aerodrome.set_route(grid.corners()).unwrap();
+						aerodrome
+					},
+					|aerodrome| aerodrome.take_pending(),
+					BatchSize::LargeInput,
+				)
+			},
+		);
+	}
+
+	group.finish();
+}
+
+fn set_route(c: &mut Criterion) {
+	let mut group = c.benchmark_group("set_route");
+
+	for size in SIZES {
+		let grid = grid(size);
+		let config = grid.aerodrome();
+		group.bench_with_input(
+			BenchmarkId::from_parameter(size),
+			&config,
+			|b, config| {
+				b.iter_batched_ref(
+					|| offline(config.clone()),
+					|aerodrome| aerodrome.set_route(grid.corners()),
+					BatchSize::LargeInput,
+				)
+			},
+		);
+	}
+
+	group.finish();
+}
+
+/// Routes across lattices, where unlike grids there are many routes between
+/// the corners to choose from.
+fn set_route_lattice(c: &mut Criterion) {
+	let mut group = c.benchmark_group("set_route_lattice");
+
+	for size in SIZES {
+		let lattice = Lattice {
+			columns: size,
+			rows: size,
+			transparent: 0.0,
+			..Lattice::default()
+		};
+		let config = lattice.generate();
+		group.bench_with_input(
+			BenchmarkId::from_parameter(size),
+			&config,
+			|b, config| {
+				b.iter_batched_ref(
+					|| offline(config.clone()),
+					|aerodrome| aerodrome.set_route(lattice.corners()),
+					BatchSize::LargeInput,
+				)
+			},
+		);
+	}
+
+	group.finish();
+}
+
+/// Returns a grid whose first block, which the route between the corners
+/// passes through, drives the number of router edges.
+fn driving(edges: usize) -> (Grid, Config) {
+	let grid = grid(4);
+	let mut config = grid.aerodrome();
+
+	// the first edge is the only one of the first block, between its two nodes
+	let condition = config.profiles[0].edges[0].clone();
+	for _ in 1..edges {
+		config.edges.push(Edge {
+			id: format!("X{}", config.edges.len()),
+			name: None,
+		});
+		config.profiles[0].edges.push(condition.clone());
+	}
+
+	(grid, config)
+}
+
+fn edge_states(c: &mut Criterion) {
+	let mut group = c.benchmark_group("edge_states");
+
+	for edges in [1, 20] {
+		let (grid, config) = driving(edges);
+		let mut aerodrome = offline(config);
+		aerodrome.set_route(grid.corners()).unwrap();
+
+		group.bench_with_input(
+			BenchmarkId::from_parameter(edges),
+			&aerodrome,
+			|b, aerodrome| b.iter(|| aerodrome.edge_states()),
+		);
+	}
+
+	group.finish();
+}
+
+criterion_group!(
+	benches,
+	new,
+	take_pending,
+	set_route,
+	set_route_lattice,
+	edge_states
+);
+criterion_main!(benches);
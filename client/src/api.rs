@@ -97,6 +97,11 @@ pub extern "C" fn client_connection_state(ctx: &Context) -> ConnectionState {
 	ctx.ctx.connection_state()
 }
 
+#[no_mangle]
+pub extern "C" fn client_is_degraded(ctx: &Context) -> bool {
+	ctx.ctx.is_degraded()
+}
+
 #[no_mangle]
 pub extern "C" fn client_next_message(ctx: &mut Context) -> *const c_char {
 	if let Some(message) = ctx.ctx.next_message() {
@@ -196,8 +201,24 @@ pub extern "C" fn client_get_presets(
 }
 
 #[no_mangle]
-pub extern "C" fn client_apply_preset(screen: &mut Screen, i: usize) {
-	screen.screen.apply_preset(i);
+pub extern "C" fn client_apply_preset(
+	screen: &mut Screen,
+	i: usize,
+) -> *const c_char {
+	if let Err(err) = screen.screen.apply_preset(i) {
+		let string =
+			unsafe { CString::from_vec_unchecked(err.to_string().into_bytes()) };
+		let ptr = string.as_ptr();
+		screen.string = Some(string);
+		ptr
+	} else {
+		std::ptr::null()
+	}
+}
+
+#[no_mangle]
+pub extern "C" fn client_resend_scenery(screen: &mut Screen) {
+	screen.screen.resend_scenery();
 }
 
 #[no_mangle]
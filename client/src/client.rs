@@ -1,49 +1,532 @@
-use crate::ipc::{Channel, Downstream, Upstream};
-use crate::ActivityState;
-
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::ipc::{Capability, Channel, Downstream, Upstream};
+use crate::latency::{LatencyEstimator, LatencyStats};
+use crate::timers::DeadlineQueue;
+use crate::{ActivityState, ConflictStrategy};
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::time::{Duration, Instant};
 
 use bars_config::{
-	BlockCondition, BlockRoute, BlockState, EdgeCondition, EdgeState,
-	ElementCondition, NodeCondition, NodeState, ResetCondition,
+	BlockCondition, BlockDisplay, BlockRoute, BlockState, ConditionTerm,
+	CountdownCondition, EdgeCondition, EdgeState, ElementCondition,
+	HoldingPointLocation, Issue, Node, NodeCondition, NodeDisplay, NodeState,
+	Position, Positioned, Preset, Profile, Projectable, Ref, ResetCondition,
+	ResolvedPreset, Severity, StringKind, Widget,
 };
 
-use bars_protocol::{BlockState as IpcBlockState, Patch};
+use bars_protocol::{
+	epoch_millis, BlockState as IpcBlockState, Patch, LOCK_DURATION,
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use tracing::{debug, warn};
 
+const CLOCK_SMOOTHING: f64 = 0.125;
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+const SCENERY_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+const SCENERY_MAX_RETRIES: usize = 3;
+/// interval between pings, by default
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(5);
+/// time without any message from the server after which the connection is
+/// reported as degraded, by default
+const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(15);
+/// longest time spent sending the last messages when disconnecting
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// time after which a patch not echoed by the server is no longer awaited
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// tag of profiles for low visibility procedures
+const LVP_TAG: &str = "lvp";
+/// time without patches or aircraft after which a controlled aerodrome is
+/// reported as stale, by default
+const DEFAULT_STALE_THRESHOLD: Duration = Duration::from_secs(120);
+/// local changes kept to be undone
+const UNDO_LIMIT: usize = 20;
+
+type SceneryUpdate = (u64, HashMap<String, bool>);
+
+/// What was left unsent when disconnecting, the channel having failed or the
+/// time allowed having passed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DisconnectSummary {
+	/// aerodromes with changes which were not sent
+	pub unflushed: Vec<String>,
+	/// aerodromes which were not released, so are left for the server to time
+	/// out
+	pub unreleased: Vec<String>,
+	/// why sending stopped, if it did
+	pub error: Option<String>,
+}
+
+impl DisconnectSummary {
+	pub fn is_complete(&self) -> bool {
+		self.error.is_none()
+	}
+}
+
+/// How well the connection to the server is holding up, as seen from the
+/// client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectionHealth {
+	/// round trip time of the last ping answered, if any has been
+	pub latency: Option<Duration>,
+	/// time since any message was last received, or since connecting
+	pub since_received: Duration,
+	/// whether nothing has been heard for longer than the timeout, so that
+	/// control is suspended until something is
+	pub degraded: bool,
+}
+
 pub struct Client {
 	channel: Channel,
 	aerodromes: HashMap<String, Aerodrome>,
+	/// aerodromes tracked, including those without a config yet
+	tracked: BTreeSet<String>,
+	providers: Vec<String>,
+	locale: String,
+	conflict_strategy: ConflictStrategy,
+	stale_threshold: Option<Duration>,
+	max_downstream_hz: Option<u8>,
+	/// messages which may be processed before the budget is refilled
+	downstream_budget: f64,
+	budget_refilled: Instant,
+	/// shortest interval between patches sent, as negotiated with the server
+	patch_interval: Duration,
+	latency: LatencyEstimator,
+	/// sequence and time of the ping awaiting its pong
+	ping: Option<(u64, Instant)>,
+	ping_seq: u64,
+	next_ping: Instant,
+	ping_interval: Duration,
+	/// time without messages after which the connection is degraded
+	connection_timeout: Option<Duration>,
+	last_received: Instant,
+	degraded: bool,
+	/// aerodromes controlled when the connection degraded, to be controlled
+	/// again once it recovers
+	suspended: Vec<String>,
 }
 
 impl Client {
-	pub fn new(mut channel: Channel) -> Result<Self> {
-		channel.send(Upstream::Init)?;
+	/// Connects a client over the channel, which processes at most
+	/// `max_downstream_hz` messages a second, and advertises this to the
+	/// server, so that it may coalesce messages to suit.
+	pub fn new(
+		mut channel: Channel,
+		max_downstream_hz: Option<u8>,
+	) -> Result<Self> {
+		let max_downstream_hz = max_downstream_hz.filter(|hz| *hz > 0);
+
+		channel.send(Upstream::Init {
+			max_downstream_hz,
+			supports: Capability::SUPPORTED.into(),
+		})?;
 
 		Ok(Self {
 			channel,
 			aerodromes: HashMap::new(),
+			tracked: BTreeSet::new(),
+			providers: Vec::new(),
+			locale: String::new(),
+			conflict_strategy: ConflictStrategy::default(),
+			stale_threshold: Some(DEFAULT_STALE_THRESHOLD),
+			max_downstream_hz,
+			downstream_budget: max_downstream_hz.map_or(f64::INFINITY, f64::from),
+			budget_refilled: Instant::now(),
+			patch_interval: Duration::ZERO,
+			latency: LatencyEstimator::default(),
+			ping: None,
+			ping_seq: 0,
+			next_ping: Instant::now(),
+			ping_interval: DEFAULT_PING_INTERVAL,
+			connection_timeout: Some(DEFAULT_CONNECTION_TIMEOUT),
+			last_received: Instant::now(),
+			degraded: false,
+			suspended: Vec::new(),
 		})
 	}
 
-	pub fn disconnect(self) {}
+	/// Creates a client with no server, to which aerodromes are given by
+	/// [`Client::load_local`] rather than tracked.
+	///
+	/// Messages are answered in place as a server would, so changes are
+	/// confirmed within the tick they are sent, and everything else behaves as
+	/// it would online.
+	pub fn new_offline() -> Self {
+		Self::new(Channel::loopback(), None)
+			.expect("sending to a loopback never fails")
+	}
+
+	/// Returns whether the client has no server, having been created by
+	/// [`Client::new_offline`].
+	pub fn is_offline(&self) -> bool {
+		self.channel.is_loopback()
+	}
+
+	/// Loads an aerodrome of an offline client, tracking and controlling it,
+	/// or replacing it if it is already loaded.
+	///
+	/// Fails if the client has a server, whose aerodromes are its own, or if
+	/// the config is invalid.
+	pub fn load_local(&mut self, config: bars_config::Aerodrome) -> Result<()> {
+		if !self.is_offline() {
+			bail!("local aerodromes can only be loaded without a server");
+		}
+		if let Some(issue) = config_error(&config) {
+			bail!("invalid config: {issue}");
+		}
+
+		let icao = config.icao.clone();
+		if let Some(held) = self.aerodromes.get_mut(&icao) {
+			held.reconfigure(config);
+		} else {
+			let aerodrome = self.new_aerodrome(config);
+			self.aerodromes.insert(icao.clone(), aerodrome);
+		}
+
+		self.aerodromes.get_mut(&icao).unwrap().state = ActivityState::Controlling;
+		self.tracked.insert(icao);
+		Ok(())
+	}
+
+	/// Disconnects from the server, first sending any pending changes, then
+	/// giving up control of and untracking every aerodrome, and saying goodbye,
+	/// so that the server need not wait to notice.
+	///
+	/// Sending stops at the first failure, or once a second has passed, and
+	/// what was left unsent is returned.
+	pub fn disconnect(mut self) -> DisconnectSummary {
+		let deadline = Instant::now() + DISCONNECT_TIMEOUT;
+		let mut summary = DisconnectSummary::default();
+
+		if let Err(err) = self.channel.set_send_timeout(DISCONNECT_TIMEOUT) {
+			summary.error = Some(err.to_string());
+		}
+
+		let mut send = |channel: &mut Channel, message: Upstream| {
+			if summary.error.is_none() && Instant::now() >= deadline {
+				summary.error = Some("timed out".into());
+			}
+			if summary.error.is_some() {
+				return Err(())
+			}
+
+			channel.send(message).map_err(|err| {
+				summary.error = Some(err.to_string());
+			})
+		};
+
+		let mut unflushed = Vec::new();
+		let mut unreleased = Vec::new();
+
+		let icaos = (self.tracked.iter())
+			.chain(self.aerodromes.keys())
+			.cloned()
+			.collect::<BTreeSet<_>>();
+		for icao in icaos {
+			if let Some(mut aerodrome) = self.aerodromes.remove(&icao) {
+				// there will be no next patch to wait for
+				aerodrome.last_patch = None;
+
+				let (patch, scenery) = aerodrome.take_pending();
+				let mut messages = Vec::new();
+				if !patch.is_empty() {
+					messages.push(Upstream::Patch {
+						icao: icao.clone(),
+						patch: Box::new(patch),
+					});
+				}
+
+				let retransmission = aerodrome.take_scenery_retransmission();
+				for (seq, scenery) in retransmission.into_iter().chain(scenery) {
+					messages.push(Upstream::Scenery {
+						icao: icao.clone(),
+						scenery,
+						seq,
+					});
+				}
+
+				let flushed = (messages.into_iter())
+					.try_for_each(|message| send(&mut self.channel, message));
+				if flushed.is_err() {
+					unflushed.push(icao.clone());
+				}
+
+				let mut release = Vec::new();
+				if aerodrome.state == ActivityState::Controlling {
+					release.push(Upstream::Control {
+						icao: icao.clone(),
+						control: false,
+					});
+				}
+				for block in aerodrome.held_locks() {
+					release.push(Upstream::Release {
+						icao: icao.clone(),
+						block,
+					});
+				}
+
+				let released = (release.into_iter())
+					.try_for_each(|message| send(&mut self.channel, message));
+				if released.is_err() {
+					unreleased.push(icao.clone());
+				}
+			}
+
+			let message = Upstream::Track { icao, track: false };
+			let _ = send(&mut self.channel, message);
+		}
+
+		let _ = send(&mut self.channel, Upstream::Bye);
+
+		summary.unflushed = unflushed;
+		summary.unreleased = unreleased;
+		if let Err(err) = self.channel.close() {
+			summary.error.get_or_insert(err.to_string());
+		}
+
+		summary
+	}
+
+	/// Returns the shortest interval between patches sent for an aerodrome,
+	/// being that preferred by the server, within which changes are merged.
+	pub fn patch_interval(&self) -> Duration {
+		self.patch_interval
+	}
+
+	/// Returns the round trip time to the server, as measured by pings, or
+	/// `None` if there has been no reply.
+	///
+	/// Connected through a proxy, this is the time to the proxy, rather than
+	/// to the network; see [`Aerodrome::confirmation_latency`] for that.
+	pub fn latency(&self) -> Option<LatencyStats> {
+		self.latency.stats()
+	}
+
+	/// Returns how the connection is holding up, by the last ping answered and
+	/// the time since anything was received.
+	pub fn connection_health(&self) -> ConnectionHealth {
+		ConnectionHealth {
+			latency: self.latency.stats().map(|stats| stats.last),
+			since_received: self.last_received.elapsed(),
+			degraded: self.degraded,
+		}
+	}
+
+	/// Returns when the client next needs a tick, being the next ping, the
+	/// connection timing out, or the next reset timer of any aerodrome, so that
+	/// the host can wait until then rather than tick continually.
+	///
+	/// Messages from the server may arrive sooner, and are only processed by a
+	/// tick.
+	pub fn next_deadline(&self) -> Instant {
+		let timeout = (self.connection_timeout)
+			.filter(|_| !self.degraded)
+			.map(|timeout| self.last_received + timeout);
+
+		(self.aerodromes.values())
+			.filter_map(Aerodrome::next_deadline)
+			.chain(timeout)
+			.fold(self.next_ping, Instant::min)
+	}
+
+	/// Pings the server if it is time to, abandoning any ping unanswered by
+	/// then.
+	fn ping(&mut self) -> Result<()> {
+		let now = Instant::now();
+		if now < self.next_ping {
+			return Ok(())
+		}
+
+		self.ping_seq += 1;
+		self.ping = Some((self.ping_seq, now));
+		self.next_ping = now + self.ping_interval;
+		self.channel.send(Upstream::Ping { seq: self.ping_seq })
+	}
+
+	/// Records that a message was received, recovering the connection if it
+	/// had degraded, so that aerodromes controlled before are controlled again.
+	fn receive(&mut self, user_messages: &mut Vec<String>) {
+		self.last_received = Instant::now();
+
+		if !std::mem::take(&mut self.degraded) {
+			return
+		}
+
+		user_messages.push("connection to the server recovered".into());
+		for icao in std::mem::take(&mut self.suspended) {
+			if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
+				if aerodrome.state == ActivityState::Observing {
+					aerodrome.state = ActivityState::Controlling;
+				}
+			}
+		}
+	}
+
+	/// Degrades the connection if nothing has been received within the
+	/// timeout, observing rather than controlling every aerodrome until it
+	/// recovers, as changes would not reach the server.
+	fn check_connection(&mut self, user_messages: &mut Vec<String>) {
+		let Some(timeout) = self.connection_timeout else {
+			return
+		};
+		if self.degraded || self.last_received.elapsed() < timeout {
+			return
+		}
+
+		warn!("nothing received for {timeout:?}");
+		self.degraded = true;
+		user_messages.push(format!(
+			"nothing heard from the server for {} s, so control is suspended \
+			 until it is",
+			timeout.as_secs_f32(),
+		));
+
+		for (icao, aerodrome) in &mut self.aerodromes {
+			if aerodrome.state == ActivityState::Controlling {
+				aerodrome.state = ActivityState::Observing;
+				self.suspended.push(icao.clone());
+			}
+		}
+	}
+
+	/// Refills the budget of messages to process at the advertised rate, up to
+	/// a second's worth, so that a burst is spread over several ticks.
+	fn refill_budget(&mut self) {
+		let now = Instant::now();
+		let elapsed = now - std::mem::replace(&mut self.budget_refilled, now);
+
+		self.downstream_budget = match self.max_downstream_hz {
+			Some(hz) => (self.downstream_budget
+				+ elapsed.as_secs_f64() * f64::from(hz))
+			.min(f64::from(hz)),
+			None => f64::INFINITY,
+		};
+	}
 
 	pub fn tick(&mut self) -> Result<Vec<String>> {
 		let mut user_messages = Vec::new();
 
-		while let Some(message) = self.channel.recv()? {
+		// messages beyond the budget are left in the channel for later ticks
+		self.refill_budget();
+		self.receive_messages(&mut user_messages)?;
+
+		self.ping()?;
+		self.check_connection(&mut user_messages);
+
+		for (icao, aerodrome) in &mut self.aerodromes {
+			aerodrome.tick();
+
+			for block in aerodrome.take_lock_requests() {
+				self.channel.send(Upstream::Acquire {
+					icao: icao.clone(),
+					block,
+				})?;
+			}
+
+			if aerodrome.take_resync_request() {
+				self.channel.send(Upstream::Resync { icao: icao.clone() })?;
+			}
+
+			let (patch, scenery) = aerodrome.take_pending();
+
+			if !patch.is_empty() {
+				self.channel.send(Upstream::Patch {
+					icao: icao.clone(),
+					patch: Box::new(patch),
+				})?;
+			}
+
+			let retransmission = aerodrome.take_scenery_retransmission();
+			for (seq, scenery) in retransmission.into_iter().chain(scenery) {
+				self.channel.send(Upstream::Scenery {
+					icao: icao.clone(),
+					scenery,
+					seq,
+				})?;
+			}
+
+			user_messages.extend(
+				aerodrome
+					.take_messages()
+					.into_iter()
+					.map(|message| format!("{icao}: {message}")),
+			);
+		}
+
+		// a loopback answers at once, so its replies are taken in the same tick
+		if self.is_offline() {
+			self.receive_messages(&mut user_messages)?;
+		}
+
+		Ok(user_messages)
+	}
+
+	/// Creates an aerodrome with the settings of the client.
+	fn new_aerodrome(&self, config: bars_config::Aerodrome) -> Aerodrome {
+		let mut aerodrome = Aerodrome::new(config);
+		aerodrome.providers = self.providers.clone();
+		aerodrome.locale = self.locale.clone();
+		aerodrome.conflict_strategy = self.conflict_strategy;
+		aerodrome.stale_threshold = self.stale_threshold;
+		aerodrome.patch_interval = self.patch_interval;
+		aerodrome
+	}
+
+	/// Processes messages from the server, as far as the budget allows.
+	fn receive_messages(
+		&mut self,
+		user_messages: &mut Vec<String>,
+	) -> Result<()> {
+		while self.downstream_budget >= 1.0 {
+			let Some(message) = self.channel.recv()? else {
+				break
+			};
+			self.downstream_budget -= 1.0;
+			self.receive(user_messages);
+
 			match message {
-				Downstream::Config { data } => {
+				Downstream::Config { data, hash } => {
 					let aerodrome = bars_config::Aerodrome::decode(&data)?;
 
-					self
-						.aerodromes
-						.entry(aerodrome.icao.clone())
-						.or_insert_with(|| Aerodrome::new(aerodrome));
+					if let Some(issue) = config_error(&aerodrome) {
+						warn!("rejecting config: {issue}");
+						user_messages.push(format!("invalid config: {issue}"));
+						continue
+					}
+
+					let icao = aerodrome.icao.clone();
+					let decoded = aerodrome.config_hash();
+
+					// the bytes are the server's, so are used even if they decode
+					// differently here
+					if let Some(hash) = hash.filter(|hash| *hash != decoded) {
+						warn!(
+							"decoded config hash {decoded} differs from server hash {hash}"
+						);
+						user_messages.push(format!(
+							"{icao}: config decoded differently from the server's, so this \
+							 plugin may be out of date"
+						));
+					}
+
+					// a different config from that held is newer, so replaces it
+					if let Some(held) = self.aerodromes.get_mut(&icao) {
+						if held.config.config_hash() != decoded {
+							debug!("replacing config for {icao} with {decoded}");
+							user_messages
+								.push(format!("{icao}: config changed to {}", &decoded[..12],));
+							held.reconfigure(aerodrome);
+						}
+
+						continue
+					}
+
+					let aerodrome = self.new_aerodrome(aerodrome);
+					self.aerodromes.entry(icao).or_insert(aerodrome);
 				},
 				Downstream::Control { icao, control } => {
 					if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
@@ -54,14 +537,26 @@ impl Client {
 						};
 					}
 				},
-				Downstream::Patch { icao, patch } => {
+				Downstream::Patch {
+					icao,
+					patch,
+					seq,
+					first_seq,
+					snapshot,
+				} => {
 					if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
-						aerodrome.apply_patch(patch);
+						aerodrome.receive_patch(*patch, seq, first_seq, snapshot);
+						aerodrome.receive_data();
 					}
 				},
-				Downstream::Aircraft { icao, aircraft } => {
+				Downstream::Aircraft {
+					icao,
+					aircraft,
+					stands,
+				} => {
 					if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
-						aerodrome.aircraft = HashSet::from_iter(aircraft);
+						aerodrome.set_aircraft(aircraft, stands);
+						aerodrome.receive_data();
 					}
 				},
 				Downstream::Error {
@@ -71,42 +566,93 @@ impl Client {
 				} => {
 					user_messages.push(format!(
 						"server: {icao}: {}",
-						message.as_ref().map(|s| s.as_str()).unwrap_or("error"),
+						message.as_deref().unwrap_or("error"),
 					));
 
 					if disconnect {
 						self.set_tracking(icao, false)?;
 					}
 				},
-			}
-		}
-
-		for (icao, aerodrome) in &mut self.aerodromes {
-			aerodrome.tick();
-
-			let (patch, scenery) = aerodrome.take_pending();
-
-			if !patch.is_empty() {
-				self.channel.send(Upstream::Patch {
-					icao: icao.clone(),
-					patch,
-				})?;
-			}
+				Downstream::Time {
+					icao,
+					server_epoch_millis,
+				} => {
+					if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
+						aerodrome.update_clock(server_epoch_millis);
+					}
+				},
+				Downstream::Lock {
+					icao,
+					block,
+					owner,
+					granted,
+				} => {
+					if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
+						aerodrome.handle_lock(&block, &owner, granted);
+					}
+				},
+				Downstream::SceneryAck { icao, seq } => {
+					if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
+						aerodrome.handle_scenery_ack(seq);
+					}
+				},
+				Downstream::Weather {
+					icao,
+					visibility_m,
+					rvr_m,
+					lvp,
+					raw,
+				} => {
+					if let Some(aerodrome) = self.aerodromes.get_mut(&icao) {
+						let received = aerodrome.now();
+						aerodrome.set_weather(WeatherReport {
+							visibility_m,
+							rvr_m,
+							lvp,
+							raw,
+							received,
+						});
+					}
+				},
+				Downstream::Pong { seq } => {
+					if let Some((_, sent)) = self.ping.take_if(|(ping, _)| *ping == seq) {
+						self.latency.record(sent.elapsed());
+					}
+				},
+				Downstream::InitAck {
+					min_patch_interval_ms,
+				} => {
+					self.patch_interval =
+						Duration::from_millis(min_patch_interval_ms.unwrap_or(0).into());
 
-			if !scenery.is_empty() {
-				self.channel.send(Upstream::Scenery {
-					icao: icao.clone(),
-					scenery,
-				})?;
+					for aerodrome in self.aerodromes.values_mut() {
+						aerodrome.patch_interval = self.patch_interval;
+					}
+				},
 			}
 		}
 
-		Ok(user_messages)
+		Ok(())
 	}
 
 	pub fn set_tracking(&mut self, icao: String, track: bool) -> Result<()> {
-		if !track {
-			self.aerodromes.remove(&icao);
+		if track && self.is_offline() && !self.aerodromes.contains_key(&icao) {
+			bail!("{icao} is not loaded, and there is no server to track it from");
+		}
+
+		if track {
+			self.tracked.insert(icao.clone());
+		} else {
+			self.tracked.remove(&icao);
+
+			if let Some(aerodrome) = self.aerodromes.remove(&icao) {
+				for block in aerodrome.held_locks() {
+					self.channel.send(Upstream::Release {
+						icao: icao.clone(),
+						block,
+					})?;
+				}
+			}
 		}
 
 		self.channel.send(Upstream::Track { icao, track })
@@ -121,6 +667,56 @@ impl Client {
 		}
 	}
 
+	/// Sets the scenery providers to send elements for, in every aerodrome.
+	pub fn set_providers(&mut self, providers: &[&str]) {
+		self.providers = providers.iter().map(|p| p.to_string()).collect();
+
+		for aerodrome in self.aerodromes.values_mut() {
+			aerodrome.set_providers(providers);
+		}
+	}
+
+	/// Sets the locale of the names returned by every aerodrome.
+	pub fn set_locale(&mut self, locale: &str) {
+		self.locale = locale.to_string();
+
+		for aerodrome in self.aerodromes.values_mut() {
+			aerodrome.set_locale(locale);
+		}
+	}
+
+	/// Sets how every aerodrome applies patches which conflict with local
+	/// changes.
+	pub fn set_conflict_strategy(&mut self, strategy: ConflictStrategy) {
+		self.conflict_strategy = strategy;
+
+		for aerodrome in self.aerodromes.values_mut() {
+			aerodrome.set_conflict_strategy(strategy);
+		}
+	}
+
+	/// Sets how long every aerodrome may go without patches or aircraft while
+	/// controlled before it is reported as stale, or `None` to never report.
+	pub fn set_stale_threshold(&mut self, threshold: Option<Duration>) {
+		self.stale_threshold = threshold;
+
+		for aerodrome in self.aerodromes.values_mut() {
+			aerodrome.set_stale_threshold(threshold);
+		}
+	}
+
+	/// Sets the interval between pings, from the next.
+	pub fn set_ping_interval(&mut self, interval: Duration) {
+		self.ping_interval = interval;
+		self.next_ping = self.next_ping.min(Instant::now() + interval);
+	}
+
+	/// Sets how long the client may go without receiving anything before the
+	/// connection is degraded, or `None` to never degrade it.
+	pub fn set_connection_timeout(&mut self, timeout: Option<Duration>) {
+		self.connection_timeout = timeout;
+	}
+
 	pub fn aerodrome(&self, icao: &String) -> Option<&Aerodrome> {
 		self.aerodromes.get(icao)
 	}
@@ -130,6 +726,18 @@ impl Client {
 	}
 }
 
+/// Returns the first error in a config, if any, as the rest of the client
+/// indexes freely and so must refuse bad refs.
+fn config_error(aerodrome: &bars_config::Aerodrome) -> Option<Issue> {
+	(aerodrome.validate().into_iter())
+		.find(|issue| issue.severity() == Severity::Error)
+}
+
+struct LockRequest {
+	sent: Instant,
+	previous: BlockState,
+}
+
 #[derive(Clone)]
 struct State<T> {
 	current: T,
@@ -140,57 +748,360 @@ impl<T> State<T> {
 	fn state(&self) -> &T {
 		self.pending.as_ref().unwrap_or(&self.current)
 	}
+
+	fn get(&self, layer: Layer) -> &T {
+		match layer {
+			Layer::Current => &self.current,
+			Layer::Pending => self.state(),
+		}
+	}
 }
 
-pub struct Aerodrome {
-	config: bars_config::Aerodrome,
-	state: ActivityState,
+/// Which states of nodes and blocks are read when evaluating what is lit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layer {
+	/// as last confirmed by the server
+	Current,
+	/// with local changes not yet confirmed, as shown
+	Pending,
+}
 
-	profile: usize,
+/// How a node or edge is shown, distinguishing changes made locally which
+/// the server is yet to confirm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayState {
+	Off,
+	On,
+	/// on once the server confirms a change
+	PendingOn,
+	/// off once the server confirms a change
+	PendingOff,
+}
 
-	node_ids: HashMap<String, usize>,
-	block_ids: HashMap<String, usize>,
+impl DisplayState {
+	fn new(current: bool, pending: bool) -> Self {
+		match (current, pending) {
+			(false, false) => Self::Off,
+			(true, true) => Self::On,
+			(false, true) => Self::PendingOn,
+			(true, false) => Self::PendingOff,
+		}
+	}
 
-	node_conns: Vec<[Vec<(usize, bool)>; 2]>,
-	node_blocks: Vec<[usize; 2]>,
-	children: HashMap<usize, Vec<usize>>,
+	/// Returns whether the node or edge is shown lit, as once confirmed.
+	pub fn is_on(self) -> bool {
+		matches!(self, Self::On | Self::PendingOn)
+	}
+}
 
-	nodes: Vec<State<bool>>,
-	blocks: Vec<State<BlockState>>,
+/// The routes through a routed block, as needed by the router edges it drives.
+struct RouterEval {
+	/// pairs of nodes without children, from the entry to the exit of the
+	/// block, which the route may take
+	routes: Vec<(usize, usize)>,
+}
 
-	aircraft: HashSet<String>,
+/// A node or block, by index, which may have a reset timer.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimerEntity {
+	Node(usize),
+	Block(usize),
+}
 
-	pending_patch: Patch,
-	pending_nodes: Vec<usize>,
-	previous_edges: Vec<bool>,
-	node_dependencies: Vec<Vec<usize>>,
-	edge_dependencies: Vec<Vec<usize>>,
+/// What armed a reset timer.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum TimerOrigin {
+	/// a change made here, resetting after the time of the profile
+	Profile,
+	/// a change made here, resetting after a time given instead of the
+	/// profile's, or a timer since extended
+	Override,
+	/// a change made by another client, as patched by the server
+	Remote,
+}
 
-	node_timers: Vec<(usize, Instant)>,
-	block_timers: Vec<(usize, Instant)>,
+impl TimerOrigin {
+	fn of(override_secs: Option<u32>) -> Self {
+		match override_secs {
+			Some(_) => Self::Override,
+			None => Self::Profile,
+		}
+	}
 }
 
-impl Aerodrome {
-	fn new(config: bars_config::Aerodrome) -> Self {
-		let mut this = Self {
-			config,
-			state: ActivityState::None,
-			profile: 0,
-			node_ids: HashMap::new(),
-			block_ids: HashMap::new(),
-			node_conns: Vec::new(),
-			node_blocks: Vec::new(),
-			children: HashMap::new(),
-			nodes: Vec::new(),
-			blocks: Vec::new(),
-			aircraft: HashSet::new(),
-			pending_patch: Default::default(),
+/// A running reset timer, from [`Aerodrome::timers`].
+#[derive(Clone, Copy, Debug)]
+pub struct TimerInfo {
+	pub entity: TimerEntity,
+	pub deadline: Instant,
+	pub origin: TimerOrigin,
+}
+
+/// Why a change to an aerodrome was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlError {
+	/// the aerodrome is only observed, or tracked, here, so the server would
+	/// reject the change
+	NotControlling(ActivityState),
+}
+
+impl Display for ControlError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NotControlling(ActivityState::Observing) => {
+				f.write_str("only observing the aerodrome")
+			},
+			Self::NotControlling(_) => f.write_str("not controlling the aerodrome"),
+		}
+	}
+}
+
+impl Error for ControlError {}
+
+/// How the blocks of a route are set, for [`Aerodrome::set_route_with`].
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum RouteActivation {
+	/// every block at once
+	#[default]
+	Immediate,
+	/// the first blocks from the origin, setting each of the rest as one ahead
+	/// of it clears, or the route is advanced
+	Progressive { ahead: u8 },
+}
+
+/// A route being followed, from [`Aerodrome::active_routes`].
+#[derive(Clone, Debug)]
+pub struct RouteInfo {
+	pub id: usize,
+	/// blocks set, from the origin
+	pub active: Vec<usize>,
+	/// blocks yet to be set, from the origin
+	pub planned: Vec<usize>,
+}
+
+/// A preset of the current profile, from [`Aerodrome::presets`].
+#[derive(Clone, Debug)]
+pub struct PresetInfo {
+	pub name: String,
+	/// captured here rather than from the config
+	pub local: bool,
+}
+
+/// The latest conditions at an aerodrome, from [`Aerodrome::weather`].
+#[derive(Clone, Debug)]
+pub struct WeatherReport {
+	pub visibility_m: Option<u32>,
+	pub rvr_m: Option<u32>,
+	/// whether low visibility procedures are in force
+	pub lvp: Option<bool>,
+	/// the report the conditions are from, such as a METAR
+	pub raw: Option<String>,
+	pub received: Instant,
+}
+
+/// A node or block, by index, which can be selected to control it.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Selectable {
+	Node(usize),
+	Block(usize),
+}
+
+/// A route set by [`Aerodrome::set_route_with`].
+struct ActiveRoute {
+	id: usize,
+	/// origin and destination, as selected
+	ends: (usize, usize),
+	/// blocks set and the states they were set to, from the origin
+	active: Vec<(usize, BlockState)>,
+	/// blocks yet to be set and the states to set them to, from the origin
+	planned: VecDeque<(usize, BlockState)>,
+	/// blocks to keep set while any are planned
+	ahead: usize,
+}
+
+/// A local change which may be undone, with the states of the nodes and
+/// blocks it changed before and after.
+struct Change {
+	nodes: Vec<(usize, bool, bool)>,
+	blocks: Vec<(usize, BlockState, BlockState)>,
+}
+
+/// Progress of a request for a snapshot of the state of an aerodrome, after
+/// patches from the server were found missing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Resync {
+	Synced,
+	Wanted,
+	Requested,
+}
+
+pub struct Aerodrome {
+	config: bars_config::Aerodrome,
+	state: ActivityState,
+
+	profile: usize,
+
+	node_ids: HashMap<String, usize>,
+	block_ids: HashMap<String, usize>,
+	edge_ids: HashMap<String, usize>,
+
+	node_conns: Vec<[Vec<(usize, bool)>; 2]>,
+	node_blocks: Vec<[usize; 2]>,
+	/// descendants without children of each node with any, at any depth
+	descendants: HashMap<usize, Vec<usize>>,
+
+	nodes: Vec<State<bool>>,
+	blocks: Vec<State<BlockState>>,
+	/// sticky router nodes held on since they were last lit, until a route
+	/// passes through them
+	latched: Vec<bool>,
+	/// states forced on edges regardless of their conditions, until cleared or
+	/// the profile changes
+	edge_overrides: Vec<Option<bool>>,
+
+	aircraft: HashSet<String>,
+	/// callsign of the aircraft on each stand, from servers which send stands
+	stand_occupants: HashMap<String, String>,
+	/// scenery providers to send elements for, or all if empty
+	providers: Vec<String>,
+	/// locale of the names returned, or the default names if empty
+	locale: String,
+	conflict_strategy: ConflictStrategy,
+	/// whether clearing a block of a route also clears the blocks behind it
+	release_trailing: bool,
+	/// whether changes are allowed without control, as when driven offline
+	offline: bool,
+	/// time without data after which the aerodrome is reported as stale
+	stale_threshold: Option<Duration>,
+	/// when the last patch or aircraft was received, or the aerodrome created
+	last_data: Instant,
+	/// whether the aerodrome has been reported as stale since the last data
+	stale: bool,
+	/// shortest interval between patches taken, within which changes are
+	/// merged
+	patch_interval: Duration,
+	last_patch: Option<Instant>,
+	/// sequence of the last patch received, from servers which number them
+	last_seq: Option<u64>,
+	resync: Resync,
+	/// times at which changes to nodes and blocks were taken to be sent, by
+	/// index, until echoed by the server
+	unconfirmed_nodes: HashMap<usize, Instant>,
+	unconfirmed_blocks: HashMap<usize, Instant>,
+	confirmation_latency: LatencyEstimator,
+	weather: Option<WeatherReport>,
+	/// profile suited to the conditions, if not the current one
+	suggested_profile: Option<usize>,
+	routes: Vec<ActiveRoute>,
+	/// local changes to be undone, the latest last, until the profile changes
+	history: VecDeque<Change>,
+	next_route_id: usize,
+	/// presets captured here, by profile
+	local_presets: HashMap<usize, Vec<Preset>>,
+
+	pending_patch: Patch,
+	pending_nodes: Vec<usize>,
+	previous_edges: Vec<bool>,
+	previous_blocks: Vec<BlockState>,
+	node_dependencies: Vec<Vec<usize>>,
+	edge_dependencies: Vec<Vec<usize>>,
+	block_dependencies: Vec<Vec<usize>>,
+	/// edges reading the state of each node and block under the profile
+	node_edges: Vec<Vec<usize>>,
+	block_edges: Vec<Vec<usize>>,
+	/// blocks whose routes are evaluated for each edge under the profile
+	routed_blocks: Vec<Vec<usize>>,
+	/// nodes and blocks changed since the edges were last evaluated, so that
+	/// only the edges reading them are evaluated again
+	changed_nodes: HashSet<usize>,
+	changed_blocks: HashSet<usize>,
+	/// whether every edge is to be evaluated again, as when the profile changes
+	edges_stale: bool,
+
+	/// reset timers, in the order of their deadlines
+	timers: DeadlineQueue<TimerEntity, TimerOrigin>,
+
+	clock_offset: Option<f64>,
+	/// time simulated beyond the local clock, when running offline
+	time_offset: Duration,
+
+	locks: HashMap<usize, Instant>,
+	lock_requests: HashMap<usize, LockRequest>,
+	pending_lock_requests: Vec<usize>,
+	messages: Vec<String>,
+
+	scenery_seq: u64,
+	unacked_scenery: BTreeMap<u64, (Instant, HashSet<usize>)>,
+	scenery_failures: usize,
+	full_scenery: bool,
+}
+
+impl Aerodrome {
+	pub fn new(config: bars_config::Aerodrome) -> Self {
+		let mut this = Self {
+			config,
+			state: ActivityState::None,
+			profile: 0,
+			node_ids: HashMap::new(),
+			block_ids: HashMap::new(),
+			edge_ids: HashMap::new(),
+			node_conns: Vec::new(),
+			node_blocks: Vec::new(),
+			descendants: HashMap::new(),
+			nodes: Vec::new(),
+			blocks: Vec::new(),
+			latched: Vec::new(),
+			edge_overrides: Vec::new(),
+			aircraft: HashSet::new(),
+			stand_occupants: HashMap::new(),
+			providers: Vec::new(),
+			locale: String::new(),
+			conflict_strategy: ConflictStrategy::default(),
+			release_trailing: false,
+			offline: false,
+			stale_threshold: Some(DEFAULT_STALE_THRESHOLD),
+			last_data: Instant::now(),
+			stale: false,
+			patch_interval: Duration::ZERO,
+			last_patch: None,
+			last_seq: None,
+			resync: Resync::Synced,
+			unconfirmed_nodes: HashMap::new(),
+			unconfirmed_blocks: HashMap::new(),
+			confirmation_latency: LatencyEstimator::default(),
+			weather: None,
+			suggested_profile: None,
+			routes: Vec::new(),
+			history: VecDeque::new(),
+			next_route_id: 0,
+			local_presets: HashMap::new(),
+			pending_patch: Default::default(),
 			previous_edges: Vec::new(),
+			previous_blocks: Vec::new(),
 			pending_nodes: Vec::new(),
 			node_dependencies: Vec::new(),
 			edge_dependencies: Vec::new(),
-			node_timers: Vec::new(),
-			block_timers: Vec::new(),
+			block_dependencies: Vec::new(),
+			node_edges: Vec::new(),
+			block_edges: Vec::new(),
+			routed_blocks: Vec::new(),
+			changed_nodes: HashSet::new(),
+			changed_blocks: HashSet::new(),
+			edges_stale: false,
+			timers: DeadlineQueue::default(),
+			clock_offset: None,
+			time_offset: Duration::ZERO,
+			locks: HashMap::new(),
+			lock_requests: HashMap::new(),
+			pending_lock_requests: Vec::new(),
+			messages: Vec::new(),
+			// acknowledgements are broadcast to every client tracking the
+			// aerodrome, so sequences start at a time-based offset to keep those
+			// of different clients apart
+			scenery_seq: epoch_millis() << 20,
+			unacked_scenery: BTreeMap::new(),
+			scenery_failures: 0,
+			full_scenery: false,
 		};
 
 		let mut borders = vec![0; this.config.nodes.len()];
@@ -199,11 +1110,23 @@ impl Aerodrome {
 			.resize(this.config.nodes.len(), [Vec::new(), Vec::new()]);
 		this.node_blocks.resize(this.config.nodes.len(), [0; 2]);
 
+		let parents = (this.config.nodes.iter())
+			.filter_map(|node| node.parent.map(|parent| parent.0))
+			.collect::<HashSet<_>>();
+
 		for (i, node) in this.config.nodes.iter().enumerate() {
 			this.node_ids.insert(node.id.clone(), i);
 
-			if let Some(parent) = node.parent {
-				this.children.entry(parent.0).or_default().push(i);
+			if parents.contains(&i) {
+				continue
+			}
+
+			// bounded, as a cycle of parents, which validation rejects, has no top
+			let mut ancestor = node.parent;
+			for _ in 0..this.config.nodes.len() {
+				let Some(parent) = ancestor else { break };
+				this.descendants.entry(parent.0).or_default().push(i);
+				ancestor = this.config.nodes.get(parent.0).and_then(|node| node.parent);
 			}
 		}
 
@@ -240,18 +1163,49 @@ impl Aerodrome {
 			}
 		}
 
+		for (i, edge) in this.config.edges.iter().enumerate() {
+			this.edge_ids.insert(edge.id.clone(), i);
+		}
+
+		// descendants are in the blocks of the node at the top of their hierarchy
+		for i in 0..this.config.nodes.len() {
+			let root = this.config.root_node(i.into()).0;
+			if root != i && root < this.node_blocks.len() {
+				this.node_blocks[i] = this.node_blocks[root];
+			}
+		}
+
 		this
 			.node_dependencies
 			.resize(this.config.nodes.len(), Vec::new());
 		this
 			.edge_dependencies
 			.resize(this.config.edges.len(), Vec::new());
+		this
+			.block_dependencies
+			.resize(this.config.blocks.len(), Vec::new());
 
 		for (i, element) in this.config.elements.iter().enumerate() {
-			match element.condition {
+			match &element.condition {
 				ElementCondition::Fixed(_) => (),
 				ElementCondition::Node(node) => this.node_dependencies[node.0].push(i),
 				ElementCondition::Edge(edge) => this.edge_dependencies[edge.0].push(i),
+				ElementCondition::Expression(expression) => {
+					for term in expression.terms() {
+						let dependencies = match term {
+							ConditionTerm::Node(node) => &mut this.node_dependencies[node.0],
+							ConditionTerm::Edge(edge) => &mut this.edge_dependencies[edge.0],
+							ConditionTerm::Block(block, _) => {
+								&mut this.block_dependencies[block.0]
+							},
+						};
+
+						// terms may repeat within an expression
+						if dependencies.last() != Some(&i) {
+							dependencies.push(i);
+						}
+					}
+				},
 			}
 		}
 
@@ -260,6 +1214,201 @@ impl Aerodrome {
 		this
 	}
 
+	/// Replaces the config, as when the server sends a corrected one, carrying
+	/// the states of nodes, blocks and edges over to those of the same ids,
+	/// along with their timers, routes, locks and local presets.
+	///
+	/// The profile is kept by id, or is the first if it no longer exists, and
+	/// anything new to the config takes its default state in the profile. All
+	/// scenery is sent again, as the elements and their conditions may have
+	/// changed.
+	fn reconfigure(&mut self, config: bars_config::Aerodrome) {
+		let old = std::mem::replace(self, Self::new(config));
+
+		self.state = old.state;
+		self.aircraft = old.aircraft;
+		self.stand_occupants = old.stand_occupants;
+		self.providers = old.providers;
+		self.locale = old.locale;
+		self.conflict_strategy = old.conflict_strategy;
+		self.release_trailing = old.release_trailing;
+		self.offline = old.offline;
+		self.stale_threshold = old.stale_threshold;
+		self.last_data = old.last_data;
+		self.stale = old.stale;
+		self.patch_interval = old.patch_interval;
+		self.last_patch = old.last_patch;
+		self.last_seq = old.last_seq;
+		self.resync = old.resync;
+		self.confirmation_latency = old.confirmation_latency;
+		self.weather = old.weather;
+		self.next_route_id = old.next_route_id;
+		self.clock_offset = old.clock_offset;
+		self.time_offset = old.time_offset;
+		self.messages = old.messages;
+		self.scenery_seq = old.scenery_seq;
+
+		let profile = &old.config.profiles[old.profile].id;
+		match self.config.profiles.iter().position(|p| &p.id == profile) {
+			Some(i) => self.profile = i,
+			None => {
+				warn!("profile {profile} is not in the new config");
+				self.messages.push(format!(
+					"profile {profile} was removed from the config, so the first \
+					 profile is used"
+				));
+			},
+		}
+
+		self.set_default_state(false);
+
+		let nodes = remap_ids(&old.config.nodes, &self.node_ids, |node| &node.id);
+		let blocks = remap_ids(&old.config.blocks, &self.block_ids, |b| &b.id);
+		let edges = remap_ids(&old.config.edges, &self.edge_ids, |edge| &edge.id);
+
+		let node = |node: Ref<Node>| nodes[node.0].map(Ref::from);
+		let block_state = |state: BlockState| match state {
+			BlockState::Route((a, b)) => {
+				Some(BlockState::Route((node(a)?, node(b)?)))
+			},
+			state => Some(state),
+		};
+
+		for (i, state) in old.nodes.into_iter().enumerate() {
+			if let Some(j) = nodes[i] {
+				self.nodes[j] = state;
+				self.latched[j] = old.latched[i];
+			}
+		}
+
+		for (i, state) in old.blocks.into_iter().enumerate() {
+			if let Some(j) = blocks[i] {
+				self.blocks[j] = State {
+					current: block_state(state.current).unwrap_or(BlockState::Clear),
+					pending: state.pending.and_then(block_state),
+				};
+			}
+		}
+
+		for (i, state) in old.edge_overrides.into_iter().enumerate() {
+			if let Some(j) = edges[i] {
+				self.edge_overrides[j] = state;
+			}
+		}
+
+		for (entity, deadline, origin) in old.timers.iter() {
+			let entity = match entity {
+				TimerEntity::Node(i) => nodes[i].map(TimerEntity::Node),
+				TimerEntity::Block(i) => blocks[i].map(TimerEntity::Block),
+			};
+			if let Some(entity) = entity {
+				self.timers.arm(entity, deadline, *origin);
+			}
+		}
+
+		self.unconfirmed_nodes = (old.unconfirmed_nodes.into_iter())
+			.filter_map(|(i, sent)| Some((nodes[i]?, sent)))
+			.collect();
+		self.unconfirmed_blocks = (old.unconfirmed_blocks.into_iter())
+			.filter_map(|(i, sent)| Some((blocks[i]?, sent)))
+			.collect();
+
+		// a route missing any of its blocks can no longer be followed
+		let set = |(block, state): (usize, BlockState)| {
+			Some((blocks[block]?, block_state(state)?))
+		};
+		self.routes = (old.routes.into_iter())
+			.filter_map(|route| {
+				Some(ActiveRoute {
+					id: route.id,
+					ends: (nodes[route.ends.0]?, nodes[route.ends.1]?),
+					active: route.active.into_iter().map(set).collect::<Option<_>>()?,
+					planned: route.planned.into_iter().map(set).collect::<Option<_>>()?,
+					ahead: route.ahead,
+				})
+			})
+			.collect();
+
+		self.locks = (old.locks.into_iter())
+			.filter_map(|(i, expiry)| Some((blocks[i]?, expiry)))
+			.collect();
+		self.lock_requests = (old.lock_requests.into_iter())
+			.filter_map(|(i, request)| {
+				Some((
+					blocks[i]?,
+					LockRequest {
+						sent: request.sent,
+						previous: block_state(request.previous)?,
+					},
+				))
+			})
+			.collect();
+		self.pending_lock_requests = (old.pending_lock_requests.into_iter())
+			.filter_map(|i| blocks[i])
+			.collect();
+
+		for (i, presets) in old.local_presets {
+			let profile = &old.config.profiles[i];
+			let Some(j) =
+				(self.config.profiles.iter()).position(|p| p.id == profile.id)
+			else {
+				continue
+			};
+
+			// presets included are those of the profile, so are kept by name
+			let included = &self.config.profiles[j].presets;
+			let include = |preset: Ref<Preset>| {
+				let name = &profile.presets.get(preset.0)?.name;
+				(included.iter())
+					.position(|p| &p.name == name)
+					.map(Ref::from)
+			};
+
+			let presets = (presets.into_iter())
+				.map(|preset| Preset {
+					name: preset.name,
+					includes: preset.includes.into_iter().filter_map(include).collect(),
+					nodes: (preset.nodes.into_iter())
+						.filter_map(|(n, state)| Some((node(n)?, state)))
+						.collect(),
+					blocks: (preset.blocks.into_iter())
+						.filter_map(|(b, state)| {
+							Some((Ref::from(blocks[b.0]?), block_state(state)?))
+						})
+						.collect(),
+				})
+				.collect();
+			self.local_presets.insert(j, presets);
+		}
+
+		let mut patch = old.pending_patch;
+		if (patch.profile.as_ref())
+			.is_some_and(|id| self.config.profiles.iter().all(|p| &p.id != id))
+		{
+			patch.profile = None;
+		}
+		patch.nodes.retain(|id, _| self.node_ids.contains_key(id));
+		patch
+			.node_deadlines
+			.retain(|id, _| self.node_ids.contains_key(id));
+		patch.blocks.retain(|id, state| {
+			self.block_ids.contains_key(id)
+				&& self.bs_ipc_to_conf(state.clone()).is_some()
+		});
+		patch
+			.block_deadlines
+			.retain(|id, _| patch.blocks.contains_key(id));
+		patch.edges.retain(|id, _| self.edge_ids.contains_key(id));
+		self.pending_patch = patch;
+
+		self.update_latches();
+		self.update_routes();
+
+		self.previous_edges = self.edge_states();
+		self.previous_blocks = self.block_states();
+		self.full_scenery = true;
+	}
+
 	fn bs_ipc_to_conf(&self, state: IpcBlockState) -> Option<BlockState> {
 		Some(match state {
 			IpcBlockState::Clear => BlockState::Clear,
@@ -282,14 +1431,149 @@ impl Aerodrome {
 		}
 	}
 
-	fn apply_patch(&mut self, patch: Patch) {
+	fn update_clock(&mut self, server_epoch_millis: u64) {
+		let sample = server_epoch_millis as f64 - epoch_millis() as f64;
+
+		self.clock_offset = Some(match self.clock_offset {
+			Some(offset) => offset + (sample - offset) * CLOCK_SMOOTHING,
+			None => sample,
+		});
+	}
+
+	fn now(&self) -> Instant {
+		Instant::now() + self.time_offset
+	}
+
+	/// Estimates the current server time, falling back to the local clock if
+	/// the server has not provided its time.
+	fn server_now(&self) -> f64 {
+		epoch_millis() as f64 + self.clock_offset.unwrap_or_default()
+	}
+
+	/// Returns the estimated server time in milliseconds since the epoch, for
+	/// drawing in step with other clients.
+	pub fn server_millis(&self) -> u64 {
+		self.server_now() as u64
+	}
+
+	fn deadline_to_server(&self, deadline: Instant) -> u64 {
+		let remaining = deadline.saturating_duration_since(self.now());
+		(self.server_now() + remaining.as_millis() as f64) as u64
+	}
+
+	fn deadline_from_server(&self, deadline: u64) -> Instant {
+		let remaining = (deadline as f64 - self.server_now()).max(0.0);
+		self.now() + Duration::from_millis(remaining as u64)
+	}
+
+	/// Applies a patch from the server, in order if the server numbers them.
+	///
+	/// Patches older than the last received are dropped, and a snapshot is
+	/// requested if any were skipped, as their changes are then lost. The
+	/// patch skipping them is still applied, being newer than what is held.
+	fn receive_patch(
+		&mut self,
+		patch: Patch,
+		seq: Option<u64>,
+		first_seq: Option<u64>,
+		snapshot: bool,
+	) {
+		if let (Some(seq), Some(last)) = (seq, self.last_seq) {
+			if snapshot {
+				// one as of the last patch holds nothing new, unless some were lost
+				if seq < last || (seq == last && self.resync == Resync::Synced) {
+					debug!("ignoring snapshot {seq}, having received patch {last}");
+					return
+				}
+			} else if seq <= last {
+				warn!("dropping patch {seq}, having received patch {last}");
+				return
+			} else if first_seq.unwrap_or(seq) > last + 1 {
+				warn!("patches after {last} were missed, so requesting a snapshot");
+				self.resync = Resync::Wanted;
+			}
+		}
+
+		if seq.is_some() {
+			self.last_seq = seq;
+		}
+
+		if snapshot {
+			self.resync = Resync::Synced;
+			self.apply_snapshot(patch);
+		} else {
+			self.apply_patch(patch);
+		}
+	}
+
+	/// Replaces the state with a snapshot of that of the server.
+	///
+	/// Local changes which the snapshot contradicts are discarded whatever the
+	/// conflict strategy, as patches of the server which would have settled
+	/// them may have been lost. Overrides missing from the snapshot are
+	/// cleared, and all scenery is sent again, as for a change of profile.
+	fn apply_snapshot(&mut self, mut patch: Patch) {
+		for (id, state) in &patch.nodes {
+			if let Some(i) = self.node_ids.get(id).copied() {
+				if self.nodes[i]
+					.pending
+					.is_some_and(|pending| pending != *state)
+				{
+					self.discard_pending_node(i);
+				}
+			}
+		}
+
+		for (id, state) in &patch.blocks {
+			if let Some(i) = self.block_ids.get(id).copied() {
+				let state = self.bs_ipc_to_conf(state.clone());
+				if self.blocks[i]
+					.pending
+					.is_some_and(|pending| Some(pending) != state)
+				{
+					self.discard_pending_block(i);
+				}
+			}
+		}
+
+		for id in self.edge_ids.keys() {
+			patch.edges.entry(id.clone()).or_insert(None);
+		}
+
+		self.apply_patch(patch);
+
+		self.edges_stale = true;
+		self.resend_scenery();
+	}
+
+	fn discard_pending_node(&mut self, node: usize) {
+		let id = &self.config.nodes[node].id;
+		self.nodes[node].pending = None;
+		self.unconfirmed_nodes.remove(&node);
+		self.pending_patch.nodes.remove(id);
+		self.pending_patch.node_deadlines.remove(id);
+	}
+
+	fn discard_pending_block(&mut self, block: usize) {
+		let id = &self.config.blocks[block].id;
+		self.blocks[block].pending = None;
+		self.unconfirmed_blocks.remove(&block);
+		self.pending_patch.blocks.remove(id);
+		self.pending_patch.block_deadlines.remove(id);
+	}
+
+	fn apply_patch(&mut self, mut patch: Patch) {
 		if let Some(profile) = patch.profile {
 			if let Some(i) = self.config.profiles.iter().position(|p| p.id == profile)
 			{
 				self.profile = i;
+				self.update_edge_dependencies();
 
-				self.node_timers.clear();
-				self.block_timers.clear();
+				self.timers.clear();
+				self.routes.clear();
+				self.history.clear();
+				self.latched.fill(false);
+				self.edge_overrides.fill(None);
 			} else {
 				warn!("requested to set unknown profile");
 			}
@@ -297,11 +1581,33 @@ impl Aerodrome {
 
 		for (id, state) in patch.nodes {
 			if let Some(i) = self.node_ids.get(&id).copied() {
+				self.changed_nodes.insert(i);
 				self.nodes[i].current = state;
 				if self.nodes[i].pending == Some(state) {
 					self.nodes[i].pending = None;
+					if let Some(sent) = self.unconfirmed_nodes.remove(&i) {
+						let latency = self.now().saturating_duration_since(sent);
+						self.confirmation_latency.record(latency);
+					}
+					continue
+				}
+
+				if let Some(pending) = self.nodes[i].pending {
+					let remote = if state { "on" } else { "off" };
+					let local = if pending { "on" } else { "off" };
+					if !self.keep_local_change("node", &id, remote, local) {
+						self.discard_pending_node(i);
+					}
+				}
+
+				// the timer follows the server, replacing that of any local change
+				if let Some(Some(deadline)) = patch.node_deadlines.remove(&id) {
+					let deadline = self.deadline_from_server(deadline);
+					self
+						.timers
+						.arm(TimerEntity::Node(i), deadline, TimerOrigin::Remote);
 				} else {
-					self.node_timers.retain(|(node, _)| node != &i);
+					self.timers.cancel(TimerEntity::Node(i));
 				}
 			}
 		}
@@ -312,82 +1618,511 @@ impl Aerodrome {
 					continue
 				};
 
+				self.changed_blocks.insert(i);
 				self.blocks[i].current = state;
 				if self.blocks[i].pending == Some(state) {
 					self.blocks[i].pending = None;
+					if let Some(sent) = self.unconfirmed_blocks.remove(&i) {
+						let latency = self.now().saturating_duration_since(sent);
+						self.confirmation_latency.record(latency);
+					}
+					continue
+				}
+
+				if let Some(pending) = self.blocks[i].pending {
+					let remote = self.describe_block_state(state);
+					let local = self.describe_block_state(pending);
+					if !self.keep_local_change("block", &id, &remote, &local) {
+						self.discard_pending_block(i);
+					}
+				}
+
+				if let Some(Some(deadline)) = patch.block_deadlines.remove(&id) {
+					let deadline = self.deadline_from_server(deadline);
+					self
+						.timers
+						.arm(TimerEntity::Block(i), deadline, TimerOrigin::Remote);
 				} else {
-					self.block_timers.retain(|(block, _)| block != &i);
+					self.timers.cancel(TimerEntity::Block(i));
 				}
 			}
 		}
+
+		for (id, state) in patch.edges {
+			// a local override yet to be sent supersedes that of the server
+			if self.pending_patch.edges.contains_key(&id) {
+				continue
+			}
+
+			if let Some(i) = self.edge_ids.get(&id).copied() {
+				self.edges_stale |= self.edge_overrides[i] != state;
+				self.edge_overrides[i] = state;
+			}
+		}
+
+		self.update_latches();
+		self.update_routes();
+	}
+
+	/// Resolves a patch conflicting with a local change by the strategy,
+	/// returning whether the local change is kept.
+	fn keep_local_change(
+		&mut self,
+		kind: &str,
+		id: &str,
+		remote: &str,
+		local: &str,
+	) -> bool {
+		match self.conflict_strategy {
+			ConflictStrategy::LocalWins => true,
+			ConflictStrategy::ServerWins => false,
+			ConflictStrategy::ServerWinsNotify => {
+				// patches do not say who made them, so neither can the message
+				self.messages.push(format!(
+					"{kind} {id} was changed elsewhere to {remote}, discarding the change \
+					 to {local}"
+				));
+				false
+			},
+		}
+	}
+
+	fn describe_block_state(&self, state: BlockState) -> String {
+		match state {
+			BlockState::Clear => "clear".into(),
+			BlockState::Relax => "relax".into(),
+			BlockState::Route((a, b)) => format!(
+				"route {} to {}",
+				self.config.nodes[a.0].id, self.config.nodes[b.0].id
+			),
+		}
 	}
 
 	fn tick(&mut self) {
-		let now = Instant::now();
+		let now = self.now();
+		self.check_stale();
+		self.update_suggested_profile();
+
+		// resets are left to the controller, whose changes are then patched in,
+		// so timers due without control are kept until it is regained
+		if self.check_control().is_ok() {
+			for entity in self.timers.take_due(now) {
+				match entity {
+					TimerEntity::Node(node) => self.set_direct_node(node, true, None),
+					TimerEntity::Block(block) => {
+						self.set_blocks(block, BlockState::Clear, false, None)
+					},
+				}
+			}
+		}
+
+		let awaited = |sent: &mut Instant| {
+			now.saturating_duration_since(*sent) < CONFIRMATION_TIMEOUT
+		};
+		self.unconfirmed_nodes.retain(|_, sent| awaited(sent));
+		self.unconfirmed_blocks.retain(|_, sent| awaited(sent));
+
+		self.lock_requests.retain(|block, request| {
+			let waiting = now.saturating_duration_since(request.sent) < LOCK_TIMEOUT;
+			if !waiting {
+				debug!("lock request for block {block} timed out");
+			}
+
+			waiting
+		});
+	}
+
+	fn take_lock_requests(&mut self) -> Vec<String> {
+		std::mem::take(&mut self.pending_lock_requests)
+			.into_iter()
+			.map(|block| self.config.blocks[block].id.clone())
+			.collect()
+	}
 
-		while self.node_timers.first().map(|(_, time)| time < &now) == Some(true) {
-			let (node, _) = self.node_timers.remove(0);
-			self.set_node(node, true);
+	/// Returns whether a snapshot is wanted from the server, noting that it has
+	/// been requested.
+	fn take_resync_request(&mut self) -> bool {
+		if self.resync != Resync::Wanted {
+			return false
 		}
 
-		while self.block_timers.first().map(|(_, time)| time < &now) == Some(true) {
-			let (block, _) = self.block_timers.remove(0);
-			self.set_block(block, BlockState::Clear);
+		self.resync = Resync::Requested;
+		true
+	}
+
+	fn take_messages(&mut self) -> Vec<String> {
+		std::mem::take(&mut self.messages)
+	}
+
+	fn held_locks(&self) -> Vec<String> {
+		let now = self.now();
+		self
+			.locks
+			.iter()
+			.filter(|(_, expiry)| **expiry > now)
+			.map(|(block, _)| self.config.blocks[*block].id.clone())
+			.collect()
+	}
+
+	/// Requests a lock on a block which is about to be changed, unless one is
+	/// already held or requested.
+	///
+	/// The change is made optimistically, and reverted if the lock is denied.
+	/// If the server does not respond, the change stands.
+	fn acquire_lock(&mut self, block: usize) {
+		let now = self.now();
+		if self.locks.get(&block).is_some_and(|expiry| *expiry > now)
+			|| self.lock_requests.contains_key(&block)
+		{
+			return
 		}
+
+		self.lock_requests.insert(
+			block,
+			LockRequest {
+				sent: now,
+				previous: *self.blocks[block].state(),
+			},
+		);
+		self.pending_lock_requests.push(block);
 	}
 
-	fn take_pending(&mut self) -> (Patch, HashMap<String, bool>) {
-		let next_edges = self.calculate_edges();
+	fn handle_lock(&mut self, block: &str, owner: &str, granted: bool) {
+		let Some(i) = self.block_ids.get(block).copied() else {
+			return
+		};
 
-		let patch = std::mem::take(&mut self.pending_patch);
-		let nodes = std::mem::take(&mut self.pending_nodes);
-		let mut scenery = HashMap::new();
-
-		if patch.profile.is_some() {
-			for element in &self.config.elements {
-				scenery.insert(
-					element.id.clone(),
-					match element.condition {
-						ElementCondition::Fixed(state) => state,
-						ElementCondition::Edge(edge) => next_edges[edge.0],
-						ElementCondition::Node(node) => *self.nodes[node.0].state(),
-					},
-				);
+		let Some(request) = self.lock_requests.remove(&i) else {
+			return
+		};
+
+		if granted {
+			// expire early so that the lock is never assumed to be held after the
+			// server has released it
+			self.locks.insert(i, request.sent + LOCK_DURATION / 2);
+		} else {
+			self.locks.remove(&i);
+			self.set_block_state(i, request.previous, None);
+			self
+				.messages
+				.push(format!("block {block} is locked by {owner}"));
+		}
+	}
+
+	pub fn take_pending(&mut self) -> (Patch, Option<SceneryUpdate>) {
+		let next_edges = self.changed_edge_states();
+
+		// changes within the patch interval are kept for the next patch, as is
+		// the scenery of a profile change, which goes with its patch
+		let now = self.now();
+		let due = (self.last_patch).is_none_or(|sent| {
+			now.saturating_duration_since(sent) >= self.patch_interval
+		});
+		let patch = if due {
+			std::mem::take(&mut self.pending_patch)
+		} else {
+			Patch::default()
+		};
+		if !patch.is_empty() {
+			self.last_patch = Some(now);
+
+			for id in patch.nodes.keys() {
+				if let Some(i) = self.node_ids.get(id) {
+					self.unconfirmed_nodes.insert(*i, now);
+				}
+			}
+			for id in patch.blocks.keys() {
+				if let Some(i) = self.block_ids.get(id) {
+					self.unconfirmed_blocks.insert(*i, now);
+				}
 			}
+		}
+
+		let nodes = std::mem::take(&mut self.pending_nodes);
+		let full = std::mem::take(&mut self.full_scenery);
+		let mut elements = HashSet::new();
+
+		if patch.profile.is_some() || full {
+			elements.extend(0..self.config.elements.len());
 		} else {
 			for i in nodes {
-				for element in &self.node_dependencies[i] {
-					scenery.insert(
-						self.config.elements[*element].id.clone(),
-						*self.nodes[i].state(),
-					);
-				}
+				elements.extend(&self.node_dependencies[i]);
 			}
 
 			for (i, (prev, next)) in
 				next_edges.iter().zip(&self.previous_edges).enumerate()
 			{
 				if prev != next {
-					for element in &self.edge_dependencies[i] {
-						scenery.insert(self.config.elements[*element].id.clone(), *next);
-					}
+					elements.extend(&self.edge_dependencies[i]);
+				}
+			}
+
+			for (i, (prev, next)) in
+				self.previous_blocks.iter().zip(&self.blocks).enumerate()
+			{
+				if prev != next.state() {
+					elements.extend(&self.block_dependencies[i]);
 				}
 			}
 		}
 
 		self.previous_edges = next_edges;
+		self.previous_blocks = self.block_states();
+
+		if full {
+			// the snapshot supersedes any outstanding sends, so they need not be
+			// retransmitted
+
+			self.unacked_scenery.clear();
+			self.scenery_failures = 0;
+		}
 
-		(patch, scenery)
+		(patch, self.record_scenery(elements))
 	}
 
-	fn calculate_edges(&self) -> Vec<bool> {
-		(0..self.config.edges.len())
-			.map(|i| self.edge_state(i))
-			.collect()
+	pub fn element_state(&self, element: usize) -> bool {
+		match &self.config.elements[element].condition {
+			ElementCondition::Fixed(state) => *state,
+			ElementCondition::Edge(edge) => self.previous_edges[edge.0],
+			ElementCondition::Node(node) => *self.nodes[node.0].state(),
+			ElementCondition::Expression(expression) => {
+				expression.evaluate(&|term| match *term {
+					ConditionTerm::Node(node) => *self.nodes[node.0].state(),
+					ConditionTerm::Edge(edge) => self.previous_edges[edge.0],
+					ConditionTerm::Block(block, predicate) => {
+						predicate.matches(self.blocks[block.0].state())
+					},
+				})
+			},
+		}
 	}
 
-	fn set_default_state(&mut self, patch: bool) {
-		self.nodes = Vec::with_capacity(self.config.nodes.len());
+	fn record_scenery(
+		&mut self,
+		mut elements: HashSet<usize>,
+	) -> Option<SceneryUpdate> {
+		elements.retain(|i| self.config.elements[*i].is_for(&self.providers));
+		if elements.is_empty() {
+			return None
+		}
+
+		// elements sharing an id have the same condition, so may be merged
+		let scenery = elements
+			.iter()
+			.filter_map(|i| {
+				let id = self.config.elements[*i].provider_id(&self.providers)?;
+				Some((id.to_owned(), self.element_state(*i)))
+			})
+			.collect();
+
+		self.scenery_seq += 1;
+		let now = self.now();
+		self
+			.unacked_scenery
+			.insert(self.scenery_seq, (now, elements));
+
+		Some((self.scenery_seq, scenery))
+	}
+
+	fn handle_scenery_ack(&mut self, seq: u64) {
+		let unacked = self.unacked_scenery.len();
+		self.unacked_scenery.retain(|seq_, _| *seq_ > seq);
+
+		if self.unacked_scenery.len() < unacked {
+			self.scenery_failures = 0;
+		}
+	}
+
+	/// Retransmits unacknowledged scenery once the oldest send has timed out.
+	///
+	/// All outstanding sends are merged into one, carrying the current states
+	/// of their elements rather than the states originally sent.
+	fn take_scenery_retransmission(&mut self) -> Option<SceneryUpdate> {
+		let (_, (sent, _)) = self.unacked_scenery.first_key_value()?;
+		if self.now().saturating_duration_since(*sent) < SCENERY_ACK_TIMEOUT {
+			return None
+		}
+
+		let elements = std::mem::take(&mut self.unacked_scenery)
+			.into_values()
+			.flat_map(|(_, elements)| elements)
+			.collect();
+
+		self.scenery_failures += 1;
+		debug!("retransmitting scenery (attempt {})", self.scenery_failures);
+
+		if self.scenery_failures == SCENERY_MAX_RETRIES {
+			self.messages.push(
+				"scenery is not being acknowledged; the simulator link may be \
+				 degraded, so consider resending all scenery"
+					.into(),
+			);
+		}
+
+		self.record_scenery(elements)
+	}
+
+	/// Sets the scenery providers to send elements for, sending elements by the
+	/// ids known to them. With no providers, all elements are sent by their
+	/// full ids.
+	pub fn set_providers(&mut self, providers: &[&str]) {
+		self.providers = providers.iter().map(|p| p.to_string()).collect();
+		self.resend_scenery();
+	}
+
+	/// Sends the states of all elements with the next pending changes.
+	///
+	/// Any outstanding unacknowledged sends are abandoned in favour of the
+	/// snapshot, which is itself retransmitted if unacknowledged.
+	pub fn resend_scenery(&mut self) {
+		self.full_scenery = true;
+	}
+
+	/// Returns the state of every edge, evaluating each routed block once
+	/// rather than once for each edge it drives.
+	pub fn edge_states(&self) -> Vec<bool> {
+		let evals = (0..self.blocks.len())
+			.map(|block| self.router_eval(block, Layer::Pending))
+			.collect::<Vec<_>>();
+
+		(0..self.config.edges.len())
+			.map(|i| self.edge_state_with(i, Some(&evals), Layer::Pending))
+			.collect()
+	}
+
+	/// Returns the state of every edge as [`Aerodrome::edge_states`] does, but
+	/// evaluating only the edges reading nodes or blocks changed since the
+	/// edges were last taken, and keeping the states last taken of the rest.
+	fn changed_edge_states(&mut self) -> Vec<bool> {
+		let Some(edges) = self.take_stale_edges() else {
+			return self.edge_states()
+		};
+
+		let mut evals = Vec::new();
+		evals.resize_with(self.blocks.len(), || None);
+		for edge in &edges {
+			for block in &self.routed_blocks[*edge] {
+				if evals[*block].is_none() {
+					evals[*block] = self.router_eval(*block, Layer::Pending);
+				}
+			}
+		}
+
+		let mut states = self.previous_edges.clone();
+		for edge in edges {
+			states[edge] = self.edge_state_with(edge, Some(&evals), Layer::Pending);
+		}
+
+		states
+	}
+
+	/// Returns the edges reading nodes or blocks changed since the edges were
+	/// last taken, or none if every edge must be evaluated again.
+	fn take_stale_edges(&mut self) -> Option<HashSet<usize>> {
+		let nodes = std::mem::take(&mut self.changed_nodes);
+		let blocks = std::mem::take(&mut self.changed_blocks);
+		if std::mem::take(&mut self.edges_stale) {
+			return None
+		}
+
+		let mut edges = HashSet::<usize>::new();
+		for node in nodes {
+			edges.extend(&self.node_edges[node]);
+		}
+
+		for block in &blocks {
+			edges.extend(&self.block_edges[*block]);
+		}
+
+		// routes are narrowed by those of the blocks beside the nodes routed
+		// between, whichever they are
+		for (i, state) in self.blocks.iter().enumerate() {
+			if let BlockState::Route((a, b)) = state.state() {
+				if (self.node_blocks[a.0].iter().chain(&self.node_blocks[b.0]))
+					.any(|block| blocks.contains(block))
+				{
+					edges.extend(&self.block_edges[i]);
+				}
+			}
+		}
+
+		Some(edges)
+	}
+
+	/// Finds the nodes and blocks read by each edge under the profile, and
+	/// has every edge evaluated again.
+	fn update_edge_dependencies(&mut self) {
+		let profile = &self.config.profiles[self.profile];
+		self.node_edges = vec![Vec::new(); self.config.nodes.len()];
+		self.block_edges = vec![Vec::new(); self.config.blocks.len()];
+		self.routed_blocks = vec![Vec::new(); self.config.edges.len()];
+
+		for (i, condition) in profile.edges.iter().enumerate() {
+			let mut nodes = Vec::new();
+			let mut blocks = Vec::new();
+			let mut conditions = vec![condition];
+
+			// an edge of an expression reads what that edge does, though one
+			// driven by an expression itself is never evaluated within it
+			if let EdgeCondition::Expression { expression } = condition {
+				for term in expression.terms() {
+					match term {
+						ConditionTerm::Node(node) => nodes.push(node.0),
+						ConditionTerm::Edge(edge) => {
+							conditions.push(&profile.edges[edge.0])
+						},
+						ConditionTerm::Block(block, _) => blocks.push(block.0),
+					}
+				}
+			}
+
+			for condition in conditions {
+				match condition {
+					EdgeCondition::Direct { nodes: expression } => {
+						nodes.extend(expression.nodes().map(|node| node.0));
+					},
+					EdgeCondition::Router { block, .. } => {
+						blocks.push(block.0);
+						self.routed_blocks[i].push(block.0);
+					},
+					EdgeCondition::Fixed { .. } | EdgeCondition::Expression { .. } => (),
+				}
+			}
+
+			// router nodes are lit by the blocks either side of them
+			for node in &nodes {
+				if let NodeCondition::Router { .. } = profile.nodes[*node] {
+					blocks.extend(self.node_blocks[*node]);
+				}
+			}
+
+			nodes.sort_unstable();
+			nodes.dedup();
+			blocks.sort_unstable();
+			blocks.dedup();
+			self.routed_blocks[i].sort_unstable();
+			self.routed_blocks[i].dedup();
+
+			for node in nodes {
+				self.node_edges[node].push(i);
+			}
+
+			for block in blocks {
+				self.block_edges[block].push(i);
+			}
+		}
+
+		self.edges_stale = true;
+	}
+
+	fn block_states(&self) -> Vec<BlockState> {
+		self.blocks.iter().map(|block| *block.state()).collect()
+	}
+
+	fn set_default_state(&mut self, patch: bool) {
+		self.update_edge_dependencies();
+
+		self.nodes = Vec::with_capacity(self.config.nodes.len());
 		self.blocks = vec![
 			State {
 				current: BlockState::Clear,
@@ -407,6 +2142,10 @@ impl Aerodrome {
 			});
 		}
 
+		self.latched = vec![false; self.nodes.len()];
+		self.edge_overrides = vec![None; self.config.edges.len()];
+		self.update_latches();
+
 		if patch {
 			self.pending_patch.nodes =
 				HashMap::from_iter(self.nodes.iter().enumerate().map(
@@ -421,135 +2160,813 @@ impl Aerodrome {
 					)
 				}),
 			);
+			self.pending_patch.node_deadlines.clear();
+			self.pending_patch.block_deadlines.clear();
+			self.pending_patch.edges.clear();
 		} else {
-			self.previous_edges = self.calculate_edges();
+			self.previous_edges = self.edge_states();
+			self.previous_blocks = self.block_states();
 		}
 
-		self.node_timers.clear();
-		self.block_timers.clear();
+		self.timers.clear();
+		self.routes.clear();
+		self.history.clear();
 	}
 
-	fn set_node_state(&mut self, node: usize, state: bool) {
+	fn set_node_state(
+		&mut self,
+		node: usize,
+		state: bool,
+		override_secs: Option<u32>,
+	) {
 		self.nodes[node].pending = Some(state);
+		self.changed_nodes.insert(node);
 		self
 			.pending_patch
 			.nodes
 			.insert(self.config.nodes[node].id.clone(), state);
 		self.pending_nodes.push(node);
 
-		self.node_timers.retain(|(node_, _)| node_ != &node);
+		self.timers.cancel(TimerEntity::Node(node));
+
+		let mut server_deadline = None;
 
 		if !state {
 			if let NodeCondition::Direct {
 				reset: ResetCondition::TimeSecs(secs),
 			} = self.config.profiles[self.profile].nodes[node]
 			{
-				let deadline = Instant::now() + Duration::from_secs(secs as u64);
-				self.node_timers.push((node, deadline));
+				let origin = TimerOrigin::of(override_secs);
+				let secs = override_secs.unwrap_or(secs);
+				let deadline = self.now() + Duration::from_secs(secs as u64);
+				self.timers.arm(TimerEntity::Node(node), deadline, origin);
+				server_deadline = Some(self.deadline_to_server(deadline));
 			}
 		}
+
+		self
+			.pending_patch
+			.node_deadlines
+			.insert(self.config.nodes[node].id.clone(), server_deadline);
 	}
 
-	fn set_block_state(&mut self, block: usize, state: BlockState) {
+	fn set_block_state(
+		&mut self,
+		block: usize,
+		state: BlockState,
+		override_secs: Option<u32>,
+	) {
 		self.blocks[block].pending = Some(state);
+		self.changed_blocks.insert(block);
 		self.pending_patch.blocks.insert(
 			self.config.blocks[block].id.clone(),
 			self.bs_conf_to_ipc(&state),
 		);
 
-		self.block_timers.retain(|(block_, _)| block_ != &block);
+		self.timers.cancel(TimerEntity::Block(block));
+
+		let mut server_deadline = None;
 
 		if state != BlockState::Clear {
 			if let BlockCondition {
 				reset: ResetCondition::TimeSecs(secs),
 			} = self.config.profiles[self.profile].blocks[block]
 			{
-				let deadline = Instant::now() + Duration::from_secs(secs as u64);
-				self.block_timers.push((block, deadline));
+				let origin = TimerOrigin::of(override_secs);
+				let secs = override_secs.unwrap_or(secs);
+				let deadline = self.now() + Duration::from_secs(secs as u64);
+				self.timers.arm(TimerEntity::Block(block), deadline, origin);
+				server_deadline = Some(self.deadline_to_server(deadline));
+			}
+		}
+
+		self
+			.pending_patch
+			.block_deadlines
+			.insert(self.config.blocks[block].id.clone(), server_deadline);
+
+		self.update_latches();
+	}
+
+	pub fn state(&self) -> ActivityState {
+		self.state
+	}
+
+	/// Returns whether the state of the aerodrome may be changed here, being
+	/// while controlling it, or at any time if offline.
+	///
+	/// Changes are refused otherwise, as the server would reject them.
+	pub fn check_control(&self) -> Result<(), ControlError> {
+		if self.offline || self.state == ActivityState::Controlling {
+			Ok(())
+		} else {
+			Err(ControlError::NotControlling(self.state))
+		}
+	}
+
+	/// Sets whether the state may be changed without control of the aerodrome,
+	/// as when it is driven offline, without a server.
+	pub fn set_offline(&mut self, offline: bool) {
+		self.offline = offline;
+	}
+
+	pub fn profile(&self) -> usize {
+		self.profile
+	}
+
+	pub fn set_profile(&mut self, i: usize) -> Result<(), ControlError> {
+		self.check_control()?;
+		if i >= self.config.profiles.len() {
+			return Ok(())
+		}
+
+		self.profile = i;
+		self.pending_patch.profile = Some(self.config.profiles[i].id.clone());
+		self.set_default_state(true);
+		Ok(())
+	}
+
+	/// Applies a preset of the current profile, by its index in
+	/// [`Aerodrome::presets`].
+	pub fn apply_preset(&mut self, i: usize) -> Result<(), ControlError> {
+		self.check_control()?;
+
+		let profile = &self.config.profiles[self.profile];
+		let preset = match i.checked_sub(profile.presets.len()) {
+			None => profile.resolve_preset(i),
+			Some(j) => match self.local_presets.get(&self.profile) {
+				Some(presets) if j < presets.len() => profile.resolve(&presets[j]),
+				_ => return Ok(()),
+			},
+		};
+
+		self.undoable(|this| this.apply_resolved_preset(preset));
+		Ok(())
+	}
+
+	/// Applies a preset which need not be one of the profile, such as one from
+	/// [`Aerodrome::capture_preset`].
+	pub fn apply_custom_preset(
+		&mut self,
+		preset: &Preset,
+	) -> Result<(), ControlError> {
+		self.check_control()?;
+
+		let preset = self.config.profiles[self.profile].resolve(preset);
+		self.undoable(|this| this.apply_resolved_preset(preset));
+		Ok(())
+	}
+
+	/// Sets the nodes and blocks of a preset as if each were set by hand, so
+	/// that blocks drag those beyond fixed off nodes with them and timers are
+	/// armed, merging into changes not yet sent.
+	fn apply_resolved_preset(&mut self, preset: ResolvedPreset) {
+		for (node, state) in preset.nodes {
+			if node.0 < self.nodes.len() {
+				self.set_node_state(node.0, state == NodeState::On, None);
+			}
+		}
+
+		for (block, state) in preset.blocks {
+			if block.0 < self.blocks.len() {
+				self.set_blocks(block.0, state, true, None);
+			}
+		}
+
+		self.update_latches();
+	}
+
+	pub fn set_locale(&mut self, locale: &str) {
+		self.locale = locale.to_string();
+	}
+
+	pub fn set_conflict_strategy(&mut self, strategy: ConflictStrategy) {
+		self.conflict_strategy = strategy;
+	}
+
+	/// Sets whether clearing a block of a route, by hand or by its reset,
+	/// also clears the blocks of the route behind it, so that the route is
+	/// shortened from behind as it is followed. The blocks ahead are kept.
+	///
+	/// Blocks cleared by other clients are left to those clients.
+	pub fn set_release_trailing(&mut self, release: bool) {
+		self.release_trailing = release;
+	}
+
+	/// Sets how long the aerodrome may go without patches or aircraft while
+	/// controlled before it is reported as stale, or `None` to never report.
+	pub fn set_stale_threshold(&mut self, threshold: Option<Duration>) {
+		self.stale_threshold = threshold;
+	}
+
+	/// Returns the time since the last patch or aircraft was received, or
+	/// since the aerodrome was created if none have been.
+	pub fn data_age(&self) -> Duration {
+		self.now().saturating_duration_since(self.last_data)
+	}
+
+	/// Returns the time taken for changes sent to the server to be echoed
+	/// back, or `None` if none have been.
+	pub fn confirmation_latency(&self) -> Option<LatencyStats> {
+		self.confirmation_latency.stats()
+	}
+
+	/// Records that data for the aerodrome was received, reporting recovery
+	/// if it had been reported as stale.
+	fn receive_data(&mut self) {
+		self.last_data = self.now();
+
+		if std::mem::take(&mut self.stale) {
+			self.messages.push("receiving data again".into());
+		}
+	}
+
+	/// Returns the latest conditions received, if any have been.
+	pub fn weather(&self) -> Option<&WeatherReport> {
+		self.weather.as_ref()
+	}
+
+	fn set_weather(&mut self, report: WeatherReport) {
+		self.weather = Some(report);
+		self.update_suggested_profile();
+	}
+
+	/// Returns a profile suited to the conditions, if the current one is not.
+	///
+	/// Profiles tagged `lvp` suit low visibility procedures being in force,
+	/// and others suit them not being. Nothing is suggested without a report
+	/// saying whether they are, and the profile is never changed.
+	pub fn suggested_profile(&self) -> Option<usize> {
+		self.suggested_profile
+	}
+
+	/// Updates the suggested profile, adding a message when it changes to
+	/// another profile.
+	fn update_suggested_profile(&mut self) {
+		let is_lvp = |profile: &Profile| profile.tags.iter().any(|t| t == LVP_TAG);
+		let profiles = &self.config.profiles;
+
+		let suggestion = match self.weather.as_ref().and_then(|w| w.lvp) {
+			Some(lvp) if is_lvp(&profiles[self.profile]) != lvp => {
+				profiles.iter().position(|profile| is_lvp(profile) == lvp)
+			},
+			_ => None,
+		};
+
+		if suggestion == self.suggested_profile {
+			return
+		}
+
+		self.suggested_profile = suggestion;
+		if let Some(i) = suggestion {
+			let lvp = is_lvp(&self.config.profiles[i]);
+			let message = format!(
+				"low visibility procedures are {}in force, so consider profile {}",
+				if lvp { "" } else { "no longer " },
+				self.profile_name(i),
+			);
+			self.messages.push(message);
+		}
+	}
+
+	/// Reports the aerodrome as stale once, if it is controlled and has gone
+	/// without data for longer than the threshold, and requests a snapshot.
+	fn check_stale(&mut self) {
+		let Some(threshold) = self.stale_threshold else {
+			return
+		};
+
+		let age = self.data_age();
+		if self.stale
+			|| self.state != ActivityState::Controlling
+			|| age <= threshold
+		{
+			return
+		}
+
+		self.stale = true;
+		self.resync = Resync::Wanted;
+		self.messages.push(format!(
+			"no data received for {} s, so states shown may be out of date; \
+			 requesting them again",
+			age.as_secs()
+		));
+	}
+
+	/// Returns the name of a profile in the locale.
+	pub fn profile_name(&self, i: usize) -> &str {
+		let id = &self.config.profiles[i].id;
+		self
+			.config
+			.localized_name(StringKind::Profile, id, &self.locale)
+	}
+
+	/// Returns the name of a preset of the current profile in the locale.
+	pub fn preset_name(&self, i: usize) -> &str {
+		let name = &self.config.profiles[self.profile].presets[i].name;
+		self
+			.config
+			.localized_name(StringKind::Preset, name, &self.locale)
+	}
+
+	/// Returns the presets of the current profile, those of the config in the
+	/// locale followed by those captured here.
+	pub fn presets(&self) -> Vec<PresetInfo> {
+		let profile = &self.config.profiles[self.profile];
+		let config = (0..profile.presets.len()).map(|i| PresetInfo {
+			name: self.preset_name(i).into(),
+			local: false,
+		});
+		let local = (self.local_presets.get(&self.profile).into_iter())
+			.flatten()
+			.map(|preset| PresetInfo {
+				name: preset.name.clone(),
+				local: true,
+			});
+
+		config.chain(local).collect()
+	}
+
+	/// Returns a preset of the current state of every directly controlled node
+	/// and every block under the current profile.
+	pub fn capture_preset(&self, name: &str) -> Preset {
+		let profile = &self.config.profiles[self.profile];
+		let nodes = (0..self.nodes.len())
+			.filter(|i| matches!(profile.nodes[*i], NodeCondition::Direct { .. }))
+			.map(|i| {
+				let state = match self.nodes[i].state() {
+					true => NodeState::On,
+					false => NodeState::Off,
+				};
+				(i.into(), state)
+			})
+			.collect();
+		let blocks = (0..self.blocks.len())
+			.map(|i| (i.into(), *self.blocks[i].state()))
+			.collect();
+
+		Preset {
+			name: name.into(),
+			includes: Vec::new(),
+			nodes,
+			blocks,
+		}
+	}
+
+	/// Captures the current state as a preset of the current profile, listed
+	/// after those of the config, replacing any captured preset of the same
+	/// name. Returns the index of the preset in [`Aerodrome::presets`].
+	pub fn save_preset(&mut self, name: &str) -> usize {
+		let preset = self.capture_preset(name);
+		let offset = self.config.profiles[self.profile].presets.len();
+		let presets = self.local_presets.entry(self.profile).or_default();
+
+		match presets.iter().position(|p| p.name == name) {
+			Some(j) => {
+				presets[j] = preset;
+				offset + j
+			},
+			None => {
+				presets.push(preset);
+				offset + presets.len() - 1
+			},
+		}
+	}
+
+	/// Removes a preset captured by [`Aerodrome::save_preset`], by its index in
+	/// [`Aerodrome::presets`], returning whether there was one. The presets of
+	/// the config cannot be removed.
+	pub fn remove_preset(&mut self, i: usize) -> bool {
+		let offset = self.config.profiles[self.profile].presets.len();
+		let Some(presets) = self.local_presets.get_mut(&self.profile) else {
+			return false
+		};
+
+		match i.checked_sub(offset) {
+			Some(j) if j < presets.len() => {
+				presets.remove(j);
+				true
+			},
+			_ => false,
+		}
+	}
+
+	/// Returns the label of a holding point in the locale.
+	pub fn holding_point_label(&self, i: usize) -> &str {
+		let id = &self.config.holding_points[i].id;
+		self
+			.config
+			.localized_name(StringKind::HoldingPoint, id, &self.locale)
+	}
+
+	/// Returns the tooltip of a node's target with its placeholders filled.
+	///
+	/// `{label}` is the name of the node in the locale, `{state}` is `on` or
+	/// `off`, and `{reset}` is the reset time of the node in the profile, as in
+	/// `45 s`, or `never`. Unknown placeholders are left as they are.
+	pub fn node_tooltip(&self, node: usize, tooltip: &str) -> String {
+		let reset = match self.config.profiles[self.profile].nodes[node] {
+			NodeCondition::Direct { reset } => reset,
+			_ => ResetCondition::None,
+		};
+
+		fill_placeholders(tooltip, |name| match name {
+			"label" => Some(
+				(self.config)
+					.localized_name(
+						StringKind::Node,
+						&self.config.nodes[node].id,
+						&self.locale,
+					)
+					.into(),
+			),
+			"state" => Some(if self.node_state(node) { "on" } else { "off" }.into()),
+			"reset" => Some(reset_text(reset)),
+			_ => None,
+		})
+	}
+
+	/// Returns the tooltip of a block's target with its placeholders filled.
+	///
+	/// `{label}` is the id of the block, `{state}` is `clear`, `relax` or
+	/// `route`, and `{reset}` is as for [`Aerodrome::node_tooltip`].
+	pub fn block_tooltip(&self, block: usize, tooltip: &str) -> String {
+		let reset = self.config.profiles[self.profile].blocks[block].reset;
+
+		fill_placeholders(tooltip, |name| match name {
+			"label" => Some(self.config.blocks[block].id.clone()),
+			"state" => Some(
+				match self.block_state(block) {
+					BlockState::Clear => "clear",
+					BlockState::Relax => "relax",
+					BlockState::Route(_) => "route",
+				}
+				.into(),
+			),
+			"reset" => Some(reset_text(reset)),
+			_ => None,
+		})
+	}
+
+	pub fn config(&self) -> &bars_config::Aerodrome {
+		&self.config
+	}
+
+	pub fn is_pilot_enabled(&self, callsign: &str) -> bool {
+		self.aircraft.contains(callsign)
+	}
+
+	/// Replaces the aircraft at the aerodrome, with the stands of those which
+	/// have them by callsign.
+	fn set_aircraft(
+		&mut self,
+		aircraft: Vec<String>,
+		stands: HashMap<String, String>,
+	) {
+		self.aircraft = HashSet::from_iter(aircraft);
+
+		// of aircraft sharing a stand, the first by callsign is its occupant
+		let mut stands = Vec::from_iter(stands);
+		stands.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+		self.stand_occupants = (stands.into_iter())
+			.map(|(callsign, stand)| (stand, callsign))
+			.collect();
+	}
+
+	/// Returns the callsign of the aircraft on a stand, if any is known to be.
+	pub fn stand_occupant(&self, stand: &str) -> Option<&str> {
+		self.stand_occupants.get(stand).map(String::as_str)
+	}
+
+	/// Returns whether any stand which a block leads on to is occupied.
+	pub fn block_occupied(&self, block: usize) -> bool {
+		self.occupied_stand(block).is_some()
+	}
+
+	/// Returns a stand which a block leads on to and its occupant, if any is
+	/// occupied, the first by name if several are.
+	///
+	/// Stands belong to blocks as for [`bars_config::Aerodrome::stand_block`],
+	/// so a stand matched by a pattern of the block is only its own if no other
+	/// block matches it better.
+	fn occupied_stand(&self, block: usize) -> Option<(&str, &str)> {
+		let block = Ref::from(block);
+		(self.stand_occupants.iter())
+			.filter(|(stand, _)| self.config.stand_block(stand) == Some(block))
+			.map(|(stand, callsign)| (stand.as_str(), callsign.as_str()))
+			.min()
+	}
+
+	/// Warns that a block is routed on to an occupied stand, which is allowed,
+	/// as the aircraft may be about to leave, but is worth a second look.
+	fn warn_occupied(&mut self, block: usize) {
+		if let Some((stand, callsign)) = self.occupied_stand(block) {
+			let message = format!(
+				"block {} is routed on to stand {stand}, which {callsign} occupies",
+				self.config.blocks[block].id,
+			);
+			self.messages.push(message);
+		}
+	}
+
+	/// Advances the clock without waiting, firing any timers which fall due.
+	///
+	/// This is for driving the aerodrome offline, as in a simulation; the time
+	/// sent to the server is unaffected.
+	pub fn advance(&mut self, duration: Duration) {
+		self.time_offset += duration;
+		self.tick();
+	}
+
+	pub fn node_state(&self, node: usize) -> bool {
+		self.node_state_in(node, Layer::Pending)
+	}
+
+	/// Returns how a node is shown, being pending if it is lit differently by
+	/// the states the server has confirmed than by those set locally.
+	pub fn node_display_state(&self, node: usize) -> DisplayState {
+		DisplayState::new(
+			self.node_state_in(node, Layer::Current),
+			self.node_state_in(node, Layer::Pending),
+		)
+	}
+
+	fn node_state_in(&self, node: usize, layer: Layer) -> bool {
+		match self.config.profiles[self.profile].nodes[node] {
+			NodeCondition::Fixed { state } => state == NodeState::On,
+			NodeCondition::Direct { .. } => *self.nodes[node].get(layer),
+			NodeCondition::Router { sticky } => {
+				self.router_node_lit(node, layer) || (sticky && self.latched[node])
+			},
+		}
+	}
+
+	/// Returns whether a router node is lit by the blocks either side of it,
+	/// being any which is clear or routed elsewhere.
+	fn router_node_lit(&self, node: usize, layer: Layer) -> bool {
+		self.node_blocks[node].iter().any(|block| {
+			match self.blocks[*block].get(layer) {
+				BlockState::Clear => true,
+				BlockState::Relax => false,
+				BlockState::Route(_) => !self.routed_through(node, *block, layer),
+			}
+		})
+	}
+
+	/// Returns whether the block is routed through the node.
+	fn routed_through(&self, node: usize, block: usize, layer: Layer) -> bool {
+		match self.blocks[block].get(layer) {
+			BlockState::Route((a, b)) => {
+				self.config.is_within(node.into(), *a)
+					|| self.config.is_within(node.into(), *b)
+			},
+			_ => false,
+		}
+	}
+
+	/// Latches sticky router nodes which are lit, and releases those which a
+	/// route passes through, for when the states of blocks have changed.
+	///
+	/// A sticky node so stays lit once lit, as when the block beyond it is
+	/// relaxed after being cleared, until it is routed through.
+	fn update_latches(&mut self) {
+		for node in 0..self.nodes.len() {
+			let NodeCondition::Router { sticky: true } =
+				self.config.profiles[self.profile].nodes[node]
+			else {
+				continue
+			};
+
+			let latched = if (self.node_blocks[node].iter())
+				.any(|block| self.routed_through(node, *block, Layer::Pending))
+			{
+				false
+			} else if self.router_node_lit(node, Layer::Pending) {
+				true
+			} else {
+				continue
+			};
+
+			if self.latched[node] != latched {
+				self.latched[node] = latched;
+				self.changed_nodes.insert(node);
 			}
 		}
 	}
 
-	pub fn state(&self) -> ActivityState {
-		self.state
+	pub fn block_state(&self, block: usize) -> BlockState {
+		*self.blocks[block].state()
+	}
+
+	/// Returns the time left before a node resets, or `None` if no reset is
+	/// due.
+	///
+	/// The timer of a local change is kept once the server confirms it, but
+	/// follows the server if it patches another state, and all timers stop when
+	/// the profile changes.
+	pub fn node_timer(&self, node: usize) -> Option<Duration> {
+		self.timer_remaining(TimerEntity::Node(node))
+	}
+
+	/// Returns the time left before a block resets, or `None` if no reset is
+	/// due, kept as for [`Aerodrome::node_timer`].
+	pub fn block_timer(&self, block: usize) -> Option<Duration> {
+		self.timer_remaining(TimerEntity::Block(block))
+	}
+
+	fn timer_remaining(&self, entity: TimerEntity) -> Option<Duration> {
+		let (deadline, _) = self.timers.get(entity)?;
+		Some(deadline.saturating_duration_since(self.now()))
+	}
+
+	/// Returns when the next reset timer is due, by the local clock, or `None`
+	/// if none are running, so that ticks need not be more frequent.
+	///
+	/// Without control, resets are left to the controller, so no timer is due.
+	pub fn next_deadline(&self) -> Option<Instant> {
+		self.check_control().ok()?;
+		let deadline = self.timers.next()?;
+
+		// deadlines are of simulated time, which runs ahead of the local clock
+		Some(Instant::now() + deadline.saturating_duration_since(self.now()))
 	}
 
-	pub fn profile(&self) -> usize {
-		self.profile
+	/// Returns the running reset timers, in the order they are due.
+	pub fn timers(&self) -> Vec<TimerInfo> {
+		(self.timers.iter())
+			.map(|(entity, deadline, origin)| TimerInfo {
+				entity,
+				deadline,
+				origin: *origin,
+			})
+			.collect()
 	}
 
-	pub fn set_profile(&mut self, i: usize) {
-		if i >= self.config.profiles.len() {
-			return
-		}
+	/// Delays the reset of a node or block by the extra time, returning
+	/// whether it had a timer to extend.
+	///
+	/// The new deadline is sent with the current state, so that other
+	/// clients reset at the same time.
+	pub fn extend_timer(
+		&mut self,
+		entity: TimerEntity,
+		extra: Duration,
+	) -> Result<bool, ControlError> {
+		self.check_control()?;
+
+		let Some((deadline, _)) = self.timers.get(entity) else {
+			return Ok(false)
+		};
+		let deadline = deadline + extra;
 
-		self.profile = i;
-		self.pending_patch.profile = Some(self.config.profiles[i].id.clone());
-		self.set_default_state(true);
+		// no longer the time of the profile, so as if overridden
+		self.timers.arm(entity, deadline, TimerOrigin::Override);
+		self.send_deadline(entity, Some(deadline));
+		Ok(true)
 	}
 
-	pub fn apply_preset(&mut self, i: usize) {
-		if i >= self.config.profiles[self.profile].presets.len() {
-			return
+	/// Cancels the reset of a node or block, leaving it in its current state
+	/// until next changed, returning whether it had a timer to cancel.
+	///
+	/// Only the running timer is cancelled, and any later change arms a timer
+	/// as usual.
+	pub fn cancel_timer(
+		&mut self,
+		entity: TimerEntity,
+	) -> Result<bool, ControlError> {
+		self.check_control()?;
+
+		if self.timers.cancel(entity).is_none() {
+			return Ok(false)
 		}
 
-		let preset = &self.config.profiles[self.profile].presets[i];
-		let mut nodes = HashMap::new();
-		let mut blocks = HashMap::new();
+		self.send_deadline(entity, None);
+		Ok(true)
+	}
 
-		for (node, state) in &preset.nodes {
-			if node.0 < self.nodes.len() {
-				let state = *state == NodeState::On;
-				self.nodes[node.0].pending = Some(state);
-				nodes.insert(self.config.nodes[node.0].id.clone(), state);
-			}
+	/// Queues the state of a node or block to be sent again with a new reset
+	/// deadline, which the server only accepts alongside a state.
+	fn send_deadline(&mut self, entity: TimerEntity, deadline: Option<Instant>) {
+		let deadline = deadline.map(|deadline| self.deadline_to_server(deadline));
+
+		match entity {
+			TimerEntity::Node(i) => {
+				let id = self.config.nodes[i].id.clone();
+				let state = *self.nodes[i].state();
+				self.pending_patch.nodes.insert(id.clone(), state);
+				self.pending_patch.node_deadlines.insert(id, deadline);
+			},
+			TimerEntity::Block(i) => {
+				let id = self.config.blocks[i].id.clone();
+				let state = self.bs_conf_to_ipc(self.blocks[i].state());
+				self.pending_patch.blocks.insert(id.clone(), state);
+				self.pending_patch.block_deadlines.insert(id, deadline);
+			},
 		}
+	}
 
-		for (block, state) in &preset.blocks {
-			if block.0 < self.blocks.len() {
-				self.blocks[block.0].pending = Some(*state);
-				blocks.insert(
-					self.config.blocks[block.0].id.clone(),
-					self.bs_conf_to_ipc(state),
-				);
-			}
+	/// Returns the time left before the node or block of a countdown resets,
+	/// as for [`Aerodrome::node_timer`] or [`Aerodrome::block_timer`].
+	pub fn countdown(&self, condition: &CountdownCondition) -> Option<Duration> {
+		match *condition {
+			CountdownCondition::Node(node) => self.node_timer(node.0),
+			CountdownCondition::Block(block) => self.block_timer(block.0),
 		}
-
-		self.pending_patch.nodes = nodes;
-		self.pending_nodes = preset.nodes.iter().map(|(i, _)| i.0).collect();
-		self.pending_patch.blocks = blocks;
-
-		self.node_timers.clear();
-		self.block_timers.clear();
 	}
 
-	pub fn config(&self) -> &bars_config::Aerodrome {
-		&self.config
-	}
+	/// Returns the whole reset time of the node or block of a countdown in the
+	/// profile, against which the time left is shown, if a reset is due.
+	///
+	/// A timer patched in by the server may run for longer, or be running with
+	/// no reset in the profile, in which case the time left is taken as whole.
+	pub fn countdown_total(
+		&self,
+		condition: &CountdownCondition,
+	) -> Option<Duration> {
+		let remaining = self.countdown(condition)?;
+
+		let profile = &self.config.profiles[self.profile];
+		let reset = match *condition {
+			CountdownCondition::Node(node) => match profile.nodes.get(node.0)? {
+				NodeCondition::Direct { reset } => *reset,
+				_ => ResetCondition::None,
+			},
+			CountdownCondition::Block(block) => profile.blocks.get(block.0)?.reset,
+		};
 
-	pub fn is_pilot_enabled(&self, callsign: &str) -> bool {
-		self.aircraft.contains(callsign)
+		Some(match reset {
+			ResetCondition::TimeSecs(secs) => {
+				Duration::from_secs(secs.into()).max(remaining)
+			},
+			ResetCondition::None => remaining,
+		})
 	}
 
-	pub fn node_state(&self, node: usize) -> bool {
-		match self.config.profiles[self.profile].nodes[node] {
-			NodeCondition::Fixed { state } => state == NodeState::On,
-			NodeCondition::Direct { .. } => *self.nodes[node].state(),
-			NodeCondition::Router { .. } => {
-				let blocks = &self.node_blocks[node];
-				blocks
-					.iter()
-					.any(|block| match self.blocks[*block].state() {
-						BlockState::Clear => true,
-						BlockState::Relax => false,
-						BlockState::Route((a, b)) => a.0 != node && b.0 != node,
-					})
+	/// Returns the text of a text widget, or `None` if the widget is not text
+	/// or is hidden by its condition.
+	///
+	/// A widget with a condition is shown while its node is on or its block is
+	/// routed, with placeholders filled as in the tooltips of that node or
+	/// block.
+	pub fn widget_text<T: Projectable>(
+		&self,
+		widget: &Widget<T>,
+	) -> Option<String> {
+		let Widget::Text {
+			text, condition, ..
+		} = widget
+		else {
+			return None
+		};
+
+		match *condition {
+			None => Some(text.clone()),
+			Some(CountdownCondition::Node(node)) => {
+				(self.node_state(node.0)).then(|| self.node_tooltip(node.0, text))
+			},
+			Some(CountdownCondition::Block(block)) => {
+				matches!(self.block_state(block.0), BlockState::Route(_))
+					.then(|| self.block_tooltip(block.0, text))
 			},
 		}
 	}
 
-	fn route_candidates(&self, block: usize) -> Vec<(usize, usize)> {
-		let BlockState::Route((ap, bp)) = *self.blocks[block].state() else {
+	/// Returns the holding points passed by the routes set through blocks.
+	///
+	/// A holding point on an edge is passed if the edge is lit by a routed
+	/// block, and one between two nodes if a block is routed between them.
+	pub fn route_holding_points(&self) -> Vec<usize> {
+		let root = |node: Ref<Node>| self.config.root_node(node).0;
+		let routed = |block: usize| match *self.blocks[block].state() {
+			BlockState::Route((a, b)) => Some((a.0, b.0)),
+			_ => None,
+		};
+
+		(self.config.holding_points.iter().enumerate())
+			.filter(|(_, holding_point)| match holding_point.location {
+				HoldingPointLocation::Edge(edge) => {
+					self.edge_state(edge.0)
+						&& (self.config.blocks.iter().enumerate()).any(|(i, block)| {
+							routed(i).is_some() && block.edges.contains(&edge)
+						})
+				},
+				HoldingPointLocation::Nodes(a, b) => {
+					let (a, b) = (root(a), root(b));
+					(0..self.blocks.len())
+						.filter_map(routed)
+						.any(|route| route == (a, b) || route == (b, a))
+				},
+			})
+			.map(|(i, _)| i)
+			.collect()
+	}
+
+	fn route_candidates(
+		&self,
+		block: usize,
+		layer: Layer,
+	) -> Vec<(usize, usize)> {
+		let BlockState::Route((ap, bp)) = *self.blocks[block].get(layer) else {
 			return vec![]
 		};
 		let (ap, bp) = (ap.0, bp.0);
@@ -558,17 +2975,17 @@ impl Aerodrome {
 
 		let ao = vec![ap];
 		let bo = vec![bp];
-		let ac = self.children.get(&ap).unwrap_or(&ao);
-		let bc = self.children.get(&bp).unwrap_or(&bo);
+		let ac = self.descendants.get(&ap).unwrap_or(&ao);
+		let bc = self.descendants.get(&bp).unwrap_or(&bo);
 
 		let non_routes = &self.config.blocks[block].non_routes;
 
 		for a in ac.iter().copied() {
 			for b in bc.iter().copied() {
-				if !non_routes.contains(&BlockRoute {
-					from: a.into(),
-					to: b.into(),
-				}) {
+				if !non_routes
+					.iter()
+					.any(|route| self.route_covers(route, a, b))
+				{
 					routes.push((a, b));
 				}
 			}
@@ -577,12 +2994,157 @@ impl Aerodrome {
 		routes
 	}
 
+	/// Evaluates the routes through a block, if it is routed, as shared by
+	/// every router edge the block drives.
+	///
+	/// The route may take any pair of the nodes routed between, or of their
+	/// descendants, which is not a non-route of the block. Where an adjacent
+	/// block is routed through either node, only the pairs which meet a route
+	/// it may take remain, unless none would.
+	fn router_eval(&self, block: usize, layer: Layer) -> Option<RouterEval> {
+		let BlockState::Route((entry, exit)) = *self.blocks[block].get(layer)
+		else {
+			return None
+		};
+
+		let mut routes = self.route_candidates(block, layer);
+
+		for (node, end) in [(entry.0, 0), (exit.0, 1)] {
+			let Some(leaves) = self.adjacent_leaves(block, node, layer) else {
+				continue
+			};
+
+			let met = (routes.iter().copied())
+				.filter(|route| leaves.contains(&[route.0, route.1][end]))
+				.collect::<Vec<_>>();
+
+			// a route which cannot meet that of the adjacent block is not ruled
+			// out by it, as the blocks are not routed as one
+			if !met.is_empty() {
+				routes = met;
+			}
+		}
+
+		Some(RouterEval { routes })
+	}
+
+	/// Returns the nodes without children by which the routes of the block
+	/// beside another at a node may pass through it, if that block is routed
+	/// through the node.
+	fn adjacent_leaves(
+		&self,
+		block: usize,
+		node: usize,
+		layer: Layer,
+	) -> Option<HashSet<usize>> {
+		let adjacent =
+			(self.node_blocks[node].into_iter()).find(|b| *b != block)?;
+		let BlockState::Route((a, b)) = *self.blocks[adjacent].get(layer) else {
+			return None
+		};
+
+		// routes of either block may be between descendants of the node
+		let root = self.config.root_node(node.into());
+		let candidates = self.route_candidates(adjacent, layer).into_iter();
+		if self.config.root_node(a) == root {
+			Some(candidates.map(|route| route.0).collect())
+		} else if self.config.root_node(b) == root {
+			Some(candidates.map(|route| route.1).collect())
+		} else {
+			None
+		}
+	}
+
+	/// Returns whether a route between two nodes, or between any of their
+	/// ancestors, includes the route between the nodes.
+	fn route_covers(&self, route: &BlockRoute, a: usize, b: usize) -> bool {
+		self.config.is_within(a.into(), route.from)
+			&& self.config.is_within(b.into(), route.to)
+	}
+
 	pub fn edge_state(&self, edge: usize) -> bool {
+		self.edge_state_with(edge, None, Layer::Pending)
+	}
+
+	/// Returns how an edge is shown, being pending if it is lit differently by
+	/// the states the server has confirmed than by those set locally.
+	pub fn edge_display_state(&self, edge: usize) -> DisplayState {
+		DisplayState::new(
+			self.edge_state_with(edge, None, Layer::Current),
+			self.edge_state_with(edge, None, Layer::Pending),
+		)
+	}
+
+	/// Returns the state forced on an edge, if it is overridden.
+	pub fn edge_override(&self, edge: usize) -> Option<bool> {
+		self.edge_overrides[edge]
+	}
+
+	/// Returns the overridden edges with their forced states.
+	pub fn edge_overrides(&self) -> Vec<(usize, bool)> {
+		(self.edge_overrides.iter().enumerate())
+			.filter_map(|(i, state)| Some((i, (*state)?)))
+			.collect()
+	}
+
+	/// Forces an edge on or off regardless of its condition, or clears the
+	/// override if none. Overrides are cleared when the profile changes.
+	pub fn set_edge_override(
+		&mut self,
+		edge: usize,
+		state: Option<bool>,
+	) -> Result<(), ControlError> {
+		self.check_control()?;
+		if edge >= self.edge_overrides.len() {
+			return Ok(())
+		}
+
+		// edges driven by expressions may read the edge, so all are evaluated
+		self.edges_stale = true;
+		self.edge_overrides[edge] = state;
+		self
+			.pending_patch
+			.edges
+			.insert(self.config.edges[edge].id.clone(), state);
+		Ok(())
+	}
+
+	/// Returns the state of an edge, using the evaluations of the blocks from
+	/// [`Aerodrome::router_eval`] if given, which must be of the same layer, or
+	/// evaluating its block if not.
+	fn edge_state_with(
+		&self,
+		edge: usize,
+		evals: Option<&[Option<RouterEval>]>,
+		layer: Layer,
+	) -> bool {
+		if let Some(state) = self.edge_overrides[edge] {
+			return state
+		}
+
 		match &self.config.profiles[self.profile].edges[edge] {
 			EdgeCondition::Fixed { state } => *state == EdgeState::On,
+			EdgeCondition::Expression { expression } => {
+				expression.evaluate(&|term| match *term {
+					ConditionTerm::Node(node) => self.node_state_in(node.0, layer),
+					ConditionTerm::Edge(other) => {
+						// nested expressions are invalid, and would recurse without end,
+						// though an overridden one is not evaluated
+						let nested = self.edge_overrides[other.0].is_none()
+							&& matches!(
+								self.config.profiles[self.profile].edges[other.0],
+								EdgeCondition::Expression { .. },
+							);
+						!nested && self.edge_state_with(other.0, evals, layer)
+					},
+					ConditionTerm::Block(block, predicate) => {
+						predicate.matches(self.blocks[block.0].get(layer))
+					},
+				})
+			},
 			EdgeCondition::Direct { nodes } => {
 				nodes.evaluate(&|node| {
-					if self.node_state(node.0) {
+					if self.node_state_in(node.0, layer) {
 						NodeState::On
 					} else {
 						NodeState::Off
@@ -590,84 +3152,94 @@ impl Aerodrome {
 				}) == EdgeState::On
 			},
 			EdgeCondition::Router { block, ref routes } => {
-				match *self.blocks[block.0].state() {
+				match *self.blocks[block.0].get(layer) {
 					BlockState::Clear => false,
 					BlockState::Relax => true,
-					BlockState::Route((ap, bp)) => {
-						let (ap, bp) = (ap.0, bp.0);
-
-						let cands = self.route_candidates(block.0);
-						match cands.len() {
-							0 => return false,
-							1 => {
-								let (a, b) = cands[0];
-								return routes.contains(&BlockRoute {
-									from: a.into(),
-									to: b.into(),
-								})
+					BlockState::Route(_) => {
+						let evaluated;
+						let eval = match evals {
+							Some(evals) => evals[block.0].as_ref(),
+							None => {
+								evaluated = self.router_eval(block.0, layer);
+								evaluated.as_ref()
 							},
-							_ => (),
-						}
-
-						// this implementation works for the most common cases only; it does
-						// not support the specification in full
-
-						let mut matches = (HashSet::new(), HashSet::new());
-
-						//let ao = vec![ap];
-						//let ac = self.children.get(&ap).unwrap_or(&ao);
-						for BlockRoute { from: a, to: b } in routes.iter().copied() {
-							//let (a, b) = if ac.contains(&a) { (a, b) } else { (b, a) };
-
-							matches.0.insert(a.0);
-							matches.1.insert(b.0);
-						}
-
-						let mut cands = (
-							HashSet::<usize>::from_iter(cands.iter().map(|r| r.0)),
-							HashSet::<usize>::from_iter(cands.iter().map(|r| r.1)),
-						);
-
-						for (parent, cands) in [(ap, &mut cands.0), (bp, &mut cands.1)] {
-							let [b1, b2] = self.node_blocks[parent];
-							let adjacent = if b1 != block.0 { b1 } else { b2 };
-
-							match *self.blocks[adjacent].state() {
-								BlockState::Clear => (),
-								BlockState::Relax => cands.clear(),
-								BlockState::Route((a, b)) => {
-									let points = self.route_candidates(adjacent).into_iter();
-
-									if a.0 == parent {
-										*cands = HashSet::from_iter(points.map(|r| r.0));
-									} else if b.0 == parent {
-										*cands = HashSet::from_iter(points.map(|r| r.1));
-									}
-								},
-							}
-						}
+						};
+						let Some(RouterEval { routes: taken }) = eval else {
+							return false
+						};
 
-						cands.0.is_subset(&matches.0) && cands.1.is_subset(&matches.1)
+						// lit only if on the route whichever it takes, as the edges of
+						// any route not taken would lead astray
+						!taken.is_empty()
+							&& taken.iter().all(|(a, b)| {
+								routes.iter().any(|route| self.route_covers(route, *a, *b))
+							})
 					},
 				}
 			},
 		}
 	}
 
-	pub fn set_block(&mut self, block: usize, state: BlockState) {
-		if block >= self.blocks.len() {
-			return
+	/// Sets the state of a block.
+	///
+	/// Routing a block on to an occupied stand is allowed, but leaves a message
+	/// warning of the occupant.
+	pub fn set_block(
+		&mut self,
+		block: usize,
+		state: BlockState,
+	) -> Result<(), ControlError> {
+		self.set_block_timed(block, state, None)
+	}
+
+	/// Sets a block as for [`Aerodrome::set_block`], resetting after the
+	/// override rather than the time of the profile if given.
+	///
+	/// The override only changes when a reset is due, so a block without a
+	/// timed reset in the profile is never given one.
+	pub fn set_block_timed(
+		&mut self,
+		block: usize,
+		state: BlockState,
+		override_secs: Option<u32>,
+	) -> Result<(), ControlError> {
+		self.check_control()?;
+		if block < self.blocks.len() {
+			self.undoable(|this| this.set_blocks(block, state, true, override_secs));
+
+			if matches!(state, BlockState::Route(_)) {
+				self.warn_occupied(block);
+			}
 		}
 
+		Ok(())
+	}
+
+	fn set_blocks(
+		&mut self,
+		block: usize,
+		state: BlockState,
+		lock: bool,
+		override_secs: Option<u32>,
+	) {
 		let mut blocks = vec![block];
 		let mut visited = HashSet::new();
+		let mut entries = Vec::new();
 
 		while let Some(block) = blocks.pop() {
 			if !visited.insert(block) {
 				continue
 			}
 
-			self.set_block_state(block, state);
+			if lock {
+				self.acquire_lock(block);
+			}
+
+			if let BlockState::Route((entry, _)) = *self.blocks[block].state() {
+				entries.push(entry.0);
+			}
+
+			self.set_block_state(block, state, override_secs);
 
 			blocks.extend(
 				self.config.blocks[block]
@@ -682,9 +3254,81 @@ impl Aerodrome {
 					.flat_map(|node| self.node_blocks[node.0]),
 			);
 		}
+
+		if self.release_trailing && state == BlockState::Clear {
+			for entry in entries {
+				self.clear_trailing_blocks(entry, lock, &mut visited);
+			}
+		}
+
+		self.update_routes();
+	}
+
+	/// Clears the blocks of a route behind the node where it entered a block
+	/// just cleared, following the route back to its origin.
+	///
+	/// The routes of blocks are reconstructed from their states, each block
+	/// being entered at the node where the block behind it was left.
+	fn clear_trailing_blocks(
+		&mut self,
+		entry: usize,
+		lock: bool,
+		visited: &mut HashSet<usize>,
+	) {
+		let mut node = entry;
+
+		while let Some((block, entry)) = self.node_blocks[node]
+			.into_iter()
+			.find_map(|block| match *self.blocks[block].state() {
+				BlockState::Route((entry, exit))
+					if exit.0 == node && !visited.contains(&block) =>
+				{
+					Some((block, entry.0))
+				},
+				_ => None,
+			}) {
+			visited.insert(block);
+
+			if lock {
+				self.acquire_lock(block);
+			}
+
+			self.set_block_state(block, BlockState::Clear, None);
+			node = entry;
+		}
 	}
 
-	pub fn set_route(&mut self, (orgn, dest): (usize, usize)) {
+	pub fn set_route(
+		&mut self,
+		route: (usize, usize),
+	) -> Result<(), ControlError> {
+		self.set_route_with(route, RouteActivation::Immediate)?;
+		Ok(())
+	}
+
+	/// Routes between two router nodes, setting the blocks of the route as the
+	/// activation says, and returns the id of the route if one was found.
+	///
+	/// Earlier routes give up the blocks which the new route passes through,
+	/// and no longer set any blocks they planned beyond them.
+	///
+	/// Selecting the ends of a route already set again, in either order,
+	/// clears the route instead, and returns `None`. Blocks of the route since
+	/// changed by others are left as they are.
+	pub fn set_route_with(
+		&mut self,
+		route: (usize, usize),
+		activation: RouteActivation,
+	) -> Result<Option<usize>, ControlError> {
+		self.check_control()?;
+		Ok(self.undoable(|this| this.route(route, activation)))
+	}
+
+	fn route(
+		&mut self,
+		(orgn, dest): (usize, usize),
+		activation: RouteActivation,
+	) -> Option<usize> {
 		if !matches!(
 			self.config.profiles[self.profile].nodes[orgn],
 			NodeCondition::Router { .. }
@@ -692,11 +3336,23 @@ impl Aerodrome {
 			self.config.profiles[self.profile].nodes[dest],
 			NodeCondition::Router { .. }
 		) {
-			return
+			return None
 		}
 
-		// todo: if orgn/dest are in same block, and the same route is currently
-		// selected, clear the block.
+		if let Some(i) = (self.routes.iter()).position(|route| {
+			route.ends == (orgn, dest) || route.ends == (dest, orgn)
+		}) {
+			let route = self.routes.remove(i);
+			for (block, state) in route.active {
+				if *self.blocks[block].state() == state {
+					self.acquire_lock(block);
+					self.set_block_state(block, BlockState::Clear, None);
+				}
+			}
+
+			self.update_routes();
+			return None
+		}
 
 		let mut nodes = VecDeque::from([(orgn, false, 0), (orgn, true, 0)]);
 		let mut visited = HashSet::from([(orgn, false), (orgn, true)]);
@@ -732,7 +3388,7 @@ impl Aerodrome {
 
 						if i > 1000 {
 							warn!("overflow {chain:?} {visited:?} {nodes:?}");
-							return
+							return None
 						}
 					}
 
@@ -743,7 +3399,7 @@ impl Aerodrome {
 					}
 				} else {
 					debug!("routing error");
-					return
+					return None
 				}
 			}
 
@@ -764,38 +3420,483 @@ impl Aerodrome {
 			}
 		}
 
-		if let Some(list) = list {
-			if list[..list.len() - 1]
-				.iter()
-				.any(|key| revisited.contains(key))
-			{
-				debug!("routing error");
-				return
-			}
+		let list = list?;
+		if list[..list.len() - 1]
+			.iter()
+			.any(|key| revisited.contains(key))
+		{
+			debug!("routing error");
+			return None
+		}
 
-			for pair in list.windows(2) {
+		// the list runs back from the destination
+		let mut planned = (list.windows(2).rev())
+			.map(|pair| {
 				let [(node2, _), (node1, direction1)] = pair else {
 					unreachable!()
 				};
 
 				let block = self.node_blocks[*node1][*direction1 as usize];
-				self.set_block_state(
-					block,
-					BlockState::Route(((*node1).into(), (*node2).into())),
-				);
+				(block, BlockState::Route(((*node1).into(), (*node2).into())))
+			})
+			.collect::<VecDeque<_>>();
+
+		// a route set otherwise, as by another client, is cleared as if set here
+		if !planned.is_empty()
+			&& (planned.iter())
+				.all(|(block, state)| same_route(self.blocks[*block].state(), state))
+		{
+			for (block, _) in planned {
+				self.acquire_lock(block);
+				self.set_block_state(block, BlockState::Clear, None);
+			}
+
+			self.update_routes();
+			return None
+		}
+
+		// the new route takes over the blocks it shares with earlier routes
+		let shared = |block: &usize| planned.iter().any(|(b, _)| b == block);
+		for route in &mut self.routes {
+			route.active.retain(|(block, _)| !shared(block));
+			if route.planned.iter().any(|(block, _)| shared(block)) {
+				route.planned.clear();
+			}
+		}
+
+		let ahead = match activation {
+			RouteActivation::Immediate => planned.len(),
+			RouteActivation::Progressive { ahead } => (ahead as usize).max(1),
+		};
+
+		let mut active = Vec::new();
+		while active.len() < ahead {
+			let Some((block, state)) = planned.pop_front() else {
+				break
+			};
+
+			self.acquire_lock(block);
+			self.set_block_state(block, state, None);
+			active.push((block, state));
+		}
+
+		for (block, _) in active.iter().chain(&planned) {
+			self.warn_occupied(*block);
+		}
+
+		let id = self.next_route_id;
+		self.next_route_id += 1;
+		self.routes.push(ActiveRoute {
+			id,
+			ends: (orgn, dest),
+			active,
+			planned,
+			ahead,
+		});
+
+		self.update_routes();
+		Some(id)
+	}
+
+	/// Returns the routes being followed, with the blocks of each still set
+	/// and those yet to be set.
+	pub fn active_routes(&self) -> Vec<RouteInfo> {
+		(self.routes.iter())
+			.map(|route| RouteInfo {
+				id: route.id,
+				active: route.active.iter().map(|(block, _)| *block).collect(),
+				planned: route.planned.iter().map(|(block, _)| *block).collect(),
+			})
+			.collect()
+	}
+
+	/// Sets the next planned block of a route, returning whether there was one
+	/// to set.
+	///
+	/// If trailing blocks are released, the first block still set is cleared
+	/// in its place, so that as many blocks stay set.
+	pub fn advance_route(&mut self, id: usize) -> Result<bool, ControlError> {
+		self.check_control()?;
+
+		let Some(i) = self.routes.iter().position(|route| route.id == id) else {
+			return Ok(false)
+		};
+
+		if self.routes[i].planned.is_empty() {
+			return Ok(false)
+		}
+
+		Ok(match self.routes[i].active.first() {
+			Some(&(block, _)) if self.release_trailing => {
+				self.set_blocks(block, BlockState::Clear, true, None);
+				true
+			},
+			_ => {
+				let advanced = self.activate_next(i);
+				self.update_routes();
+				advanced
+			},
+		})
+	}
+
+	/// Makes a local change, keeping what it changed to be undone.
+	fn undoable<T>(&mut self, change: impl FnOnce(&mut Self) -> T) -> T {
+		let nodes = (self.nodes.iter())
+			.map(|node| *node.state())
+			.collect::<Vec<_>>();
+		let blocks = self.block_states();
+		let result = change(self);
+
+		let change = Change {
+			nodes: (nodes.into_iter().enumerate())
+				.filter_map(|(i, prior)| {
+					let state = *self.nodes[i].state();
+					(state != prior).then_some((i, prior, state))
+				})
+				.collect(),
+			blocks: (blocks.into_iter().enumerate())
+				.filter_map(|(i, prior)| {
+					let state = *self.blocks[i].state();
+					(state != prior).then_some((i, prior, state))
+				})
+				.collect(),
+		};
+
+		if !change.nodes.is_empty() || !change.blocks.is_empty() {
+			if self.history.len() == UNDO_LIMIT {
+				self.history.pop_front();
 			}
+
+			self.history.push_back(change);
 		}
+
+		result
 	}
 
-	pub fn set_node(&mut self, node: usize, state: bool) {
-		if node >= self.nodes.len() {
-			return
+	/// Undoes the latest local change to nodes, blocks, routes or presets,
+	/// setting what it changed back as a new change, and returns whether it
+	/// was undone.
+	///
+	/// A change is forgotten without being undone if anything it changed has
+	/// changed since, as when the server has set it otherwise, though not by
+	/// the server confirming it. Changes before the profile last changed are
+	/// forgotten. Routes through the blocks set back are abandoned, so that a
+	/// route is undone as a whole.
+	pub fn undo(&mut self) -> Result<bool, ControlError> {
+		self.check_control()?;
+
+		let Some(change) = self.history.pop_back() else {
+			return Ok(false)
+		};
+
+		if (change.nodes.iter())
+			.any(|(i, _, state)| self.nodes[*i].state() != state)
+			|| (change.blocks.iter())
+				.any(|(i, _, state)| self.blocks[*i].state() != state)
+		{
+			return Ok(false)
+		}
+
+		for (node, prior, _) in change.nodes {
+			self.set_node_state(node, prior, None);
+		}
+
+		let blocks = (change.blocks.iter())
+			.map(|(i, ..)| *i)
+			.collect::<HashSet<_>>();
+		(self.routes).retain(|route| {
+			!route.active.iter().any(|(block, _)| blocks.contains(block))
+		});
+
+		for (block, prior, _) in change.blocks {
+			self.acquire_lock(block);
+			self.set_block_state(block, prior, None);
+		}
+
+		self.update_routes();
+		Ok(true)
+	}
+
+	/// Discards the planned blocks of a route, returning whether it had any.
+	/// The blocks already set are kept.
+	pub fn cancel_route(&mut self, id: usize) -> Result<bool, ControlError> {
+		self.check_control()?;
+
+		let Some(i) = self.routes.iter().position(|route| route.id == id) else {
+			return Ok(false)
+		};
+
+		let planned = !self.routes[i].planned.is_empty();
+		self.routes[i].planned.clear();
+		self.update_routes();
+		Ok(planned)
+	}
+
+	/// Sets the next planned block of a route, returning whether it was set.
+	fn activate_next(&mut self, i: usize) -> bool {
+		let Some((block, state)) = self.routes[i].planned.pop_front() else {
+			return false
+		};
+
+		// a block changed since the route was planned cannot be taken over, nor
+		// can the route continue past it
+		let current = *self.blocks[block].state();
+		if current != BlockState::Clear && current != state {
+			self.routes[i].planned.clear();
+			return false
+		}
+
+		if current != state {
+			self.acquire_lock(block);
+			self.set_block_state(block, state, None);
+		}
+
+		self.routes[i].active.push((block, state));
+		true
+	}
+
+	/// Forgets the blocks of routes which have since changed, setting planned
+	/// blocks in place of those cleared, and forgets routes with none left.
+	fn update_routes(&mut self) {
+		for i in 0..self.routes.len() {
+			let blocks = &self.blocks;
+			(self.routes[i].active)
+				.retain(|(block, state)| blocks[*block].state() == state);
+
+			while self.routes[i].active.len() < self.routes[i].ahead
+				&& self.activate_next(i)
+			{}
+		}
+
+		self.routes.retain(|route| !route.active.is_empty());
+	}
+
+	pub fn set_node(
+		&mut self,
+		node: usize,
+		state: bool,
+	) -> Result<(), ControlError> {
+		self.set_node_timed(node, state, None)
+	}
+
+	/// Sets a directly controlled node as for [`Aerodrome::set_node`],
+	/// resetting after the override rather than the time of the profile if
+	/// given.
+	///
+	/// The override only changes when a reset is due, so a node without a
+	/// timed reset in the profile is never given one.
+	pub fn set_node_timed(
+		&mut self,
+		node: usize,
+		state: bool,
+		override_secs: Option<u32>,
+	) -> Result<(), ControlError> {
+		self.check_control()?;
+		if node < self.nodes.len() {
+			self.undoable(|this| this.set_direct_node(node, state, override_secs));
 		}
 
+		Ok(())
+	}
+
+	fn set_direct_node(
+		&mut self,
+		node: usize,
+		state: bool,
+		override_secs: Option<u32>,
+	) {
 		if let NodeCondition::Direct { .. } =
 			self.config.profiles[self.profile].nodes[node]
 		{
-			self.set_node_state(node, state);
+			self.set_node_state(node, state, override_secs);
+		}
+	}
+
+	/// Returns the nodes and blocks controllable under the profile, being
+	/// directly controlled nodes, router nodes without parents and every
+	/// block, in the order they are selected by keyboard.
+	///
+	/// Those with an input order come first, by that order, followed by the
+	/// rest from left to right and then top to bottom, by the centres of their
+	/// targets on the geo map if there is one or else on the first map. Any
+	/// left over are ordered by id.
+	pub fn selection_order(&self) -> Vec<Selectable> {
+		let profile = &self.config.profiles[self.profile];
+		let nodes = (0..self.config.nodes.len())
+			.filter(|i| match profile.nodes[*i] {
+				NodeCondition::Fixed { .. } => false,
+				NodeCondition::Direct { .. } => true,
+				NodeCondition::Router { .. } => self.config.nodes[*i].parent.is_none(),
+			})
+			.map(Selectable::Node);
+		let blocks = (0..self.config.blocks.len()).map(Selectable::Block);
+
+		let mut order = nodes
+			.chain(blocks)
+			.map(|entity| {
+				let (id, input_order) = match entity {
+					Selectable::Node(i) => {
+						let node = &self.config.nodes[i];
+						(&node.id, node.input_order)
+					},
+					Selectable::Block(i) => {
+						let block = &self.config.blocks[i];
+						(&block.id, block.input_order)
+					},
+				};
+
+				(entity, input_order, self.selection_position(entity), id)
+			})
+			.collect::<Vec<_>>();
+
+		order.sort_by(|(a, a_order, a_pos, a_id), (b, b_order, b_pos, b_id)| {
+			(a_order.is_none().cmp(&b_order.is_none()))
+				.then(a_order.cmp(b_order))
+				.then(a_pos.is_none().cmp(&b_pos.is_none()))
+				.then_with(|| match (a_pos, b_pos) {
+					(Some([ax, ay]), Some([bx, by])) => {
+						ax.total_cmp(bx).then(ay.total_cmp(by))
+					},
+					_ => Ordering::Equal,
+				})
+				.then(a_id.cmp(b_id))
+				.then(a.cmp(b))
+		});
+
+		order.into_iter().map(|(entity, ..)| entity).collect()
+	}
+
+	/// Returns the centre of the target of a node or block, or of its paths if
+	/// it has no target, with y increasing downwards.
+	fn selection_position(&self, entity: Selectable) -> Option<[f32; 2]> {
+		fn centre<'a, T: Positioned + 'a>(
+			points: impl Iterator<Item = &'a T>,
+		) -> Option<[f32; 2]> {
+			let mut min = [f32::INFINITY; 2];
+			let mut max = [f32::NEG_INFINITY; 2];
+			for point in points {
+				let [x, y] = point.position().xy();
+				min = [min[0].min(x), min[1].min(y)];
+				max = [max[0].max(x), max[1].max(y)];
+			}
+
+			(min[0] <= max[0] && min[1] <= max[1])
+				.then(|| [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0])
+		}
+
+		fn position<T: Positioned>(
+			nodes: &[NodeDisplay<T>],
+			blocks: &[BlockDisplay<T>],
+			entity: Selectable,
+		) -> Option<[f32; 2]> {
+			match entity {
+				Selectable::Node(i) => {
+					let node = nodes.get(i)?;
+					centre(node.target.polygons.iter().flatten())
+						.or_else(|| centre(node.paths().flat_map(|path| &path.points)))
+				},
+				Selectable::Block(i) => {
+					centre(blocks.get(i)?.target.polygons.iter().flatten())
+				},
+			}
+		}
+
+		match (&self.config.geo_map, self.config.maps.first()) {
+			// latitude increases upwards
+			(Some(map), _) => {
+				position(&map.nodes, &map.blocks, entity).map(|[lon, lat]| [lon, -lat])
+			},
+			(None, Some(map)) => position(&map.nodes, &map.blocks, entity),
+			(None, None) => None,
+		}
+	}
+
+	/// Returns the next node or block in the selection order after the one
+	/// given, or before it if backwards, wrapping around at the ends.
+	///
+	/// Without one given, or if it cannot be selected, the first is returned,
+	/// or the last if backwards.
+	pub fn next_selectable(
+		&self,
+		after: Option<Selectable>,
+		backwards: bool,
+	) -> Option<Selectable> {
+		let order = self.selection_order();
+		let len = order.len();
+		let current =
+			after.and_then(|after| order.iter().position(|e| *e == after));
+
+		let next = match (current, backwards) {
+			(Some(i), false) => (i + 1) % len,
+			(Some(i), true) => (i + len - 1) % len,
+			(None, false) => 0,
+			(None, true) => len.checked_sub(1)?,
+		};
+
+		order.get(next).copied()
+	}
+}
+
+fn reset_text(reset: ResetCondition) -> String {
+	match reset {
+		ResetCondition::None => "never".into(),
+		ResetCondition::TimeSecs(secs) => format!("{secs} s"),
+	}
+}
+
+/// Replaces each `{name}` in the text with its value, if it has one.
+fn fill_placeholders(
+	text: &str,
+	value: impl Fn(&str) -> Option<String>,
+) -> String {
+	let mut filled = String::with_capacity(text.len());
+	let mut rest = text;
+
+	while let Some(start) = rest.find('{') {
+		filled.push_str(&rest[..start]);
+		rest = &rest[start..];
+
+		let replaced = rest
+			.find('}')
+			.and_then(|end| Some((value(&rest[1..end])?, end)));
+		match replaced {
+			Some((value, end)) => {
+				filled.push_str(&value);
+				rest = &rest[end + 1..];
+			},
+			None => {
+				filled.push('{');
+				rest = &rest[1..];
+			},
 		}
 	}
+
+	filled.push_str(rest);
+	filled
+}
+
+/// Returns whether two block states are the same, or route between the same
+/// nodes in opposite directions.
+fn same_route(a: &BlockState, b: &BlockState) -> bool {
+	match (a, b) {
+		(BlockState::Route((a1, a2)), BlockState::Route((b1, b2))) => {
+			(a1, a2) == (b1, b2) || (a1, a2) == (b2, b1)
+		},
+		(a, b) => a == b,
+	}
+}
+
+/// Finds the index in the new entities of the id of each of the old ones.
+fn remap_ids<T>(
+	old: &[T],
+	ids: &HashMap<String, usize>,
+	id: impl Fn(&T) -> &String,
+) -> Vec<Option<usize>> {
+	old
+		.iter()
+		.map(|entity| ids.get(id(entity)).copied())
+		.collect()
 }
+
+#[cfg(test)]
+mod tests;
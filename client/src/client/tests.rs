@@ -0,0 +1,3162 @@
+use super::*;
+
+use crate::ipc::{mpsc_pair, ServerChannel};
+
+use bars_config::{
+	Aerodrome as Config, Block, BlockPredicate, ConditionConjunction,
+	ConditionExpression, Edge, Element, HoldingPoint, Lattice, LocaleTable, Map,
+	NodeConjunction, NodeExpression, Point, SyntheticRng, Target,
+};
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use BlockState::{Clear, Relax};
+
+/// Pairs of nodes by index, as routes are given.
+type Routes<'a> = &'a [(usize, usize)];
+
+fn block_routes(routes: Routes) -> Vec<BlockRoute> {
+	(routes.iter())
+		.map(|(from, to)| BlockRoute {
+			from: (*from).into(),
+			to: (*to).into(),
+		})
+		.collect()
+}
+
+/// Builds an aerodrome with one profile of the conditions given, of nodes each
+/// given by its parent if any and condition, and of edges each given by its
+/// condition.
+fn build(
+	nodes: &[(Option<usize>, NodeCondition)],
+	blocks: Vec<Block>,
+	edges: Vec<EdgeCondition>,
+) -> Aerodrome {
+	let profile = Profile {
+		id: "default".into(),
+		name: "Default".into(),
+		tags: Vec::new(),
+		nodes: nodes.iter().map(|(_, condition)| *condition).collect(),
+		blocks: vec![
+			BlockCondition {
+				reset: ResetCondition::None,
+			};
+			blocks.len()
+		],
+		edges,
+		presets: Vec::new(),
+	};
+
+	offline(Config {
+		icao: "ZZZZ".into(),
+		elements: Vec::new(),
+		nodes: (nodes.iter().enumerate())
+			.map(|(i, (parent, _))| Node {
+				id: format!("N{i}"),
+				scratchpad: None,
+				parent: parent.map(Ref::from),
+				input_order: None,
+			})
+			.collect(),
+		edges: (0..profile.edges.len())
+			.map(|i| Edge {
+				id: format!("X{i}"),
+				name: None,
+			})
+			.collect(),
+		blocks,
+		holding_points: Vec::new(),
+		profiles: vec![profile],
+		strings: None,
+		geo_map: None,
+		maps: Vec::new(),
+		styles: Vec::new(),
+	})
+}
+
+/// Builds an aerodrome of router nodes, each given by its parent if any, and
+/// of blocks, each given by its nodes and non-routes, driving router edges,
+/// each given by its block and routes.
+fn routed(
+	parents: &[Option<usize>],
+	blocks: &[(&[usize], Routes)],
+	edges: &[(usize, Routes)],
+) -> Aerodrome {
+	let router = NodeCondition::Router { sticky: false };
+
+	build(
+		&(parents.iter())
+			.map(|parent| (*parent, router))
+			.collect::<Vec<_>>(),
+		(blocks.iter().enumerate())
+			.map(|(i, (nodes, non_routes))| Block {
+				id: format!("B{i}"),
+				nodes: nodes.iter().copied().map(Ref::from).collect(),
+				edges: Vec::new(),
+				non_routes: block_routes(non_routes),
+				stands: Vec::new(),
+				input_order: None,
+			})
+			.collect(),
+		(edges.iter())
+			.map(|(block, routes)| EdgeCondition::Router {
+				block: (*block).into(),
+				routes: block_routes(routes),
+			})
+			.collect(),
+	)
+}
+
+/// Creates an aerodrome which can be changed without control, as there is no
+/// server to give it.
+fn offline(config: Config) -> Aerodrome {
+	let mut aerodrome = Aerodrome::new(config);
+	aerodrome.set_offline(true);
+	aerodrome
+}
+
+fn route(a: usize, b: usize) -> BlockState {
+	BlockState::Route((a.into(), b.into()))
+}
+
+/// Sets the blocks of a fresh aerodrome to each row of states in turn,
+/// checking the state of every edge, whether each block is evaluated alone or
+/// all together.
+fn check_edges(
+	build: impl Fn() -> Aerodrome,
+	table: &[(&[BlockState], &[bool])],
+) {
+	for (states, expected) in table {
+		let mut aerodrome = build();
+		for (block, state) in states.iter().enumerate() {
+			aerodrome.set_block(block, *state).unwrap();
+		}
+
+		let alone = (0..expected.len())
+			.map(|edge| aerodrome.edge_state(edge))
+			.collect::<Vec<_>>();
+		assert_eq!(&alone, expected, "evaluated alone with {states:?}");
+		assert_eq!(
+			&aerodrome.edge_states(),
+			expected,
+			"evaluated together with {states:?}",
+		);
+	}
+}
+
+#[test]
+fn router_edges_of_a_chain() {
+	// E0 - B0 - P - B1 - Q - B2 - E3, where P has children p1 and p2 and Q has
+	// q1 and q2, which B0 and B2 each only route one of
+	let (e0, p, q, e3, p1, p2, q1, q2) = (0, 1, 2, 3, 4, 5, 6, 7);
+	let build = || {
+		routed(
+			&[None, None, None, None, Some(p), Some(p), Some(q), Some(q)],
+			&[
+				(&[e0, p], &[(e0, p2)]),
+				(&[p, q], &[]),
+				(&[q, e3], &[(q2, e3)]),
+			],
+			&[
+				(0, &[(e0, p1)]),
+				(0, &[(e0, p2)]),
+				(1, &[(p1, q1)]),
+				(1, &[(p2, q2)]),
+				(2, &[(q1, e3)]),
+				(2, &[(q2, e3)]),
+			],
+		)
+	};
+
+	check_edges(
+		build,
+		&[
+			(&[Clear, Clear, Clear], &[false; 6]),
+			(
+				&[Clear, Relax, Clear],
+				&[false, false, true, true, false, false],
+			),
+			// either child of each parent may be taken, so no edge is certain
+			(&[Clear, route(p, q), Clear], &[false; 6]),
+			// B0 only reaches p1, but q is still open
+			(
+				&[route(e0, p), route(p, q), Clear],
+				&[true, false, false, false, false, false],
+			),
+			(
+				&[Clear, route(p, q), route(q, e3)],
+				&[false, false, false, false, true, false],
+			),
+			// the routes either side narrow B1 to p1 and q1 as a pair, never
+			// lighting p1 to q2 or p2 to q2
+			(
+				&[route(e0, p), route(p, q), route(q, e3)],
+				&[true, false, true, false, true, false],
+			),
+			// the routes of edges are directed
+			(&[route(p, e0), route(q, p), route(e3, q)], &[false; 6]),
+		],
+	);
+}
+
+#[test]
+fn router_edges_of_a_block_with_three_boundaries() {
+	let build = || {
+		routed(
+			&[None, None, None],
+			&[(&[0, 1, 2], &[])],
+			&[
+				(0, &[(0, 1), (1, 0)]),
+				(0, &[(0, 2), (2, 0)]),
+				(0, &[(1, 2), (2, 1)]),
+			],
+		)
+	};
+
+	check_edges(
+		build,
+		&[
+			(&[Clear], &[false, false, false]),
+			(&[Relax], &[true, true, true]),
+			(&[route(0, 1)], &[true, false, false]),
+			(&[route(1, 0)], &[true, false, false]),
+			(&[route(2, 0)], &[false, true, false]),
+			(&[route(1, 2)], &[false, false, true]),
+			(&[route(2, 1)], &[false, false, true]),
+		],
+	);
+}
+
+#[test]
+fn router_edges_with_asymmetric_non_routes() {
+	// P has children p1 and p2, and the block may not be routed from p2 to Q,
+	// though it may from Q to p2
+	let (p, q, p1, p2) = (0, 1, 2, 3);
+	let build = || {
+		routed(
+			&[None, None, Some(p), Some(p)],
+			&[(&[p, q], &[(p2, q)])],
+			&[(0, &[(p1, q), (q, p1)]), (0, &[(p2, q), (q, p2)])],
+		)
+	};
+
+	check_edges(
+		build,
+		&[
+			(&[Clear], &[false, false]),
+			(&[Relax], &[true, true]),
+			(&[route(p, q)], &[true, false]),
+			(&[route(q, p)], &[false, false]),
+		],
+	);
+}
+
+#[test]
+fn router_edges_of_a_three_level_hierarchy() {
+	// E - B0 - P - B1 - X, where P has children c1 and c2, c1 has children g1
+	// and g2, and c2 has the child g3
+	let (e, p, x, c1, c2, g1, g2, g3) = (0, 1, 2, 3, 4, 5, 6, 7);
+	let parents = [
+		None,
+		None,
+		None,
+		Some(p),
+		Some(p),
+		Some(c1),
+		Some(c1),
+		Some(c2),
+	];
+	let build = |b0: Routes, b1: Routes| {
+		routed(
+			&parents,
+			&[(&[e, p], b0), (&[p, x], b1)],
+			&[
+				(0, &[(e, g1)]),
+				(0, &[(e, g2)]),
+				(0, &[(e, g3)]),
+				// routes to intermediate levels cover their descendants
+				(0, &[(e, c1)]),
+				(0, &[(e, p)]),
+				(1, &[(g3, x)]),
+				(1, &[(c1, x)]),
+			],
+		)
+	};
+
+	let table: &[(Routes, Routes, &[BlockState], &[bool])] = &[
+		// any grandchild may be taken, so only the route to P is certain
+		(
+			&[],
+			&[],
+			&[route(e, p), Clear],
+			&[false, false, false, false, true, false, false],
+		),
+		// ruling out c2 leaves both children of c1
+		(
+			&[(e, c2)],
+			&[],
+			&[route(e, p), Clear],
+			&[false, false, false, true, true, false, false],
+		),
+		// and ruling out g2 too leaves g1 alone
+		(
+			&[(e, c2), (e, g2)],
+			&[],
+			&[route(e, p), Clear],
+			&[true, false, false, true, true, false, false],
+		),
+		// the block beyond only leaves g3, through c2, which narrows both
+		(
+			&[],
+			&[(c1, x)],
+			&[route(e, p), route(p, x)],
+			&[false, false, true, false, true, true, false],
+		),
+		(
+			&[],
+			&[(c2, x)],
+			&[route(e, p), route(p, x)],
+			&[false, false, false, true, true, false, true],
+		),
+	];
+
+	for (b0, b1, states, expected) in table {
+		check_edges(|| build(b0, b1), &[(states, expected)]);
+	}
+
+	// grandchildren are in the blocks of the top of their hierarchy
+	let aerodrome = build(&[], &[]);
+	for node in [c1, c2, g1, g2, g3] {
+		assert_eq!(aerodrome.node_blocks[node], aerodrome.node_blocks[p]);
+	}
+	let mut leaves = aerodrome.descendants[&p].clone();
+	leaves.sort();
+	assert_eq!(leaves, [g1, g2, g3]);
+	assert_eq!(aerodrome.descendants[&c1], [g1, g2]);
+
+	// and routes are found through them
+	let mut aerodrome = build(&[], &[]);
+	aerodrome.set_route((e, x)).unwrap();
+	assert_eq!(aerodrome.block_state(0), route(e, p));
+	assert_eq!(aerodrome.block_state(1), route(p, x));
+}
+
+fn direct_edge(positive: &[usize], negative: &[usize]) -> EdgeCondition {
+	let nodes = |nodes: &[usize]| nodes.iter().copied().map(Ref::from).collect();
+
+	EdgeCondition::Direct {
+		nodes: NodeExpression {
+			disjunction: vec![NodeConjunction {
+				positive: nodes(positive),
+				negative: nodes(negative),
+			}],
+		},
+	}
+}
+
+#[test]
+fn changed_edges_only_evaluate_those_reading_changes() {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::None,
+	};
+	let mut aerodrome = build(
+		&[(None, direct); 4],
+		Vec::new(),
+		vec![
+			direct_edge(&[0], &[]),
+			direct_edge(&[1], &[]),
+			direct_edge(&[0], &[2]),
+			EdgeCondition::Fixed {
+				state: EdgeState::On,
+			},
+			EdgeCondition::Expression {
+				expression: ConditionExpression {
+					disjunction: vec![ConditionConjunction {
+						positive: vec![ConditionTerm::Node(3.into())],
+						negative: vec![ConditionTerm::Edge(1.into())],
+					}],
+				},
+			},
+		],
+	);
+	aerodrome.take_pending();
+
+	// the edges last taken are kept up to date, as they would be once the
+	// stale edges were evaluated
+	let stale = |aerodrome: &mut Aerodrome| {
+		let mut edges =
+			(aerodrome.take_stale_edges().unwrap().into_iter()).collect::<Vec<_>>();
+		edges.sort();
+		aerodrome.previous_edges = aerodrome.edge_states();
+		edges
+	};
+
+	assert!(stale(&mut aerodrome).is_empty());
+
+	aerodrome.set_node(0, true).unwrap();
+	assert_eq!(stale(&mut aerodrome), [0, 2]);
+
+	// an expression reads the nodes of the edges it reads
+	aerodrome.set_node(1, true).unwrap();
+	assert_eq!(stale(&mut aerodrome), [1, 4]);
+
+	aerodrome.set_node(2, true).unwrap();
+	aerodrome.set_node(3, true).unwrap();
+	assert_eq!(stale(&mut aerodrome), [2, 4]);
+
+	// an override may be read by any expression, so every edge is evaluated
+	aerodrome.set_edge_override(1, Some(false)).unwrap();
+	assert_eq!(aerodrome.take_stale_edges(), None);
+	aerodrome.previous_edges = aerodrome.edge_states();
+
+	aerodrome.set_node(0, false).unwrap();
+	let states = aerodrome.changed_edge_states();
+	assert_eq!(states, [false, false, false, true, true]);
+	assert_eq!(states, aerodrome.edge_states());
+}
+
+#[test]
+fn mixed_expressions_follow_nodes_edges_and_blocks() {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::None,
+	};
+	let router = NodeCondition::Router { sticky: false };
+	let block = |predicate| ConditionTerm::Block(0.into(), predicate);
+
+	// N0 & block:B0=route | edge:X1 & block:B0=relax | block:B0=N2>N1
+	let expression = ConditionExpression {
+		disjunction: vec![
+			ConditionConjunction {
+				positive: vec![
+					ConditionTerm::Node(0.into()),
+					block(BlockPredicate::AnyRoute),
+				],
+				negative: Vec::new(),
+			},
+			ConditionConjunction {
+				positive: vec![
+					ConditionTerm::Edge(1.into()),
+					block(BlockPredicate::Relax),
+				],
+				negative: Vec::new(),
+			},
+			ConditionConjunction {
+				positive: vec![block(BlockPredicate::Route((2.into(), 1.into())))],
+				negative: Vec::new(),
+			},
+		],
+	};
+	let mut aerodrome = build(
+		&[
+			(None, direct),
+			(None, router),
+			(None, router),
+			(None, direct),
+		],
+		vec![Block {
+			id: "B0".into(),
+			nodes: vec![1.into(), 2.into()],
+			edges: Vec::new(),
+			non_routes: Vec::new(),
+			stands: Vec::new(),
+			input_order: None,
+		}],
+		vec![
+			EdgeCondition::Expression { expression },
+			direct_edge(&[3], &[]),
+		],
+	);
+	aerodrome.take_pending();
+	aerodrome.previous_edges = aerodrome.edge_states();
+	assert!(!aerodrome.edge_state(0));
+
+	type Step = fn(&mut Aerodrome) -> Result<(), ControlError>;
+	let steps: [(&str, Step, bool); 9] = [
+		("N0 on, B0 clear", |a| a.set_node(0, true), false),
+		("N0 on, B0 routed", |a| a.set_route((1, 2)), true),
+		("N0 off, B0 routed", |a| a.set_node(0, false), false),
+		("X1 on, B0 routed", |a| a.set_node(3, true), false),
+		("X1 on, B0 relaxed", |a| a.set_block(0, Relax), true),
+		("X1 off, B0 relaxed", |a| a.set_node(3, false), false),
+		("B0 routed back", |a| a.set_route((2, 1)), true),
+		("B0 routed forward", |a| a.set_block(0, route(1, 2)), false),
+		(
+			"X1 forced on, B0 relaxed",
+			|a| {
+				a.set_edge_override(1, Some(true))?;
+				a.set_block(0, Relax)
+			},
+			true,
+		),
+	];
+
+	for (step, change, expected) in steps {
+		change(&mut aerodrome).unwrap();
+		assert_eq!(aerodrome.edge_state(0), expected, "{step}");
+
+		// evaluating only the edges reading changes agrees
+		let states = aerodrome.changed_edge_states();
+		assert_eq!(states, aerodrome.edge_states(), "{step}");
+		aerodrome.previous_edges = states;
+	}
+}
+
+#[test]
+fn changed_edges_match_every_edge_evaluated() {
+	for seed in 0..4 {
+		let config = Lattice {
+			columns: 4,
+			rows: 4,
+			points: None,
+			seed,
+			..Lattice::default()
+		}
+		.generate();
+
+		let mut rng = SyntheticRng::new(seed);
+		let mut aerodrome = offline(config.clone());
+
+		for step in 0..500 {
+			let nodes = config.nodes.len();
+			let result = match rng.below(8) {
+				0 | 1 => aerodrome.set_node(rng.below(nodes), rng.chance(0.5)),
+				2 | 3 => {
+					let i = rng.below(config.blocks.len());
+					let block = &config.blocks[i];
+					let state = match rng.below(3) {
+						0 => BlockState::Clear,
+						1 => BlockState::Relax,
+						_ => {
+							let a = block.nodes[rng.below(block.nodes.len())];
+							let b = block.nodes[rng.below(block.nodes.len())];
+							BlockState::Route((a, b))
+						},
+					};
+					aerodrome.set_block(i, state)
+				},
+				4 => aerodrome.set_route((rng.below(nodes), rng.below(nodes))),
+				5 => {
+					let state = [None, Some(false), Some(true)][rng.below(3)];
+					aerodrome.set_edge_override(rng.below(config.edges.len()), state)
+				},
+				6 => aerodrome.set_profile(rng.below(config.profiles.len())),
+				_ => {
+					aerodrome.advance(Duration::from_secs(rng.below(120) as u64));
+					Ok(())
+				},
+			};
+			result.unwrap();
+
+			if rng.chance(0.5) {
+				aerodrome.take_pending();
+				assert_eq!(
+					aerodrome.previous_edges,
+					aerodrome.edge_states(),
+					"seed {seed}, step {step}",
+				);
+			}
+		}
+	}
+}
+
+#[test]
+fn shared_router_evals_match_each_edge_evaluated_alone() {
+	// edge_state evaluates the block of each edge anew, as every edge did
+	// before blocks were evaluated once for all the edges they drive
+	for seed in 0..4 {
+		let config = Lattice {
+			columns: 4,
+			rows: 3,
+			children: 2,
+			points: None,
+			seed,
+			..Lattice::default()
+		}
+		.generate();
+
+		let mut rng = SyntheticRng::new(seed);
+		let mut aerodrome = offline(config.clone());
+		let mut routed = 0;
+
+		for step in 0..300 {
+			let i = rng.below(config.blocks.len());
+			let block = &config.blocks[i];
+			let state = match rng.below(4) {
+				0 => BlockState::Clear,
+				1 => BlockState::Relax,
+				_ => {
+					let a = block.nodes[rng.below(block.nodes.len())];
+					let b = block.nodes[rng.below(block.nodes.len())];
+					BlockState::Route((a, b))
+				},
+			};
+			aerodrome.set_block(i, state).unwrap();
+			if rng.chance(0.05) {
+				aerodrome
+					.set_profile(rng.below(config.profiles.len()))
+					.unwrap();
+			}
+
+			let shared = aerodrome.edge_states();
+			let alone = (0..config.edges.len())
+				.map(|edge| aerodrome.edge_state(edge))
+				.collect::<Vec<_>>();
+			assert_eq!(shared, alone, "seed {seed}, step {step}");
+			routed += shared.iter().filter(|lit| **lit).count();
+		}
+
+		// which is not only so for edges all off
+		assert!(routed > 0, "seed {seed}");
+	}
+}
+
+/// Routes a block between two nodes, as a config to be given to a client.
+fn two_nodes() -> Config {
+	routed(&[None, None], &[(&[0, 1], &[])], &[]).config
+}
+
+type Connection = (
+	Client,
+	UnboundedReceiver<Upstream>,
+	UnboundedSender<Downstream>,
+);
+
+/// Connects a client to a server answered by hand, which gives it the config
+/// and control of the aerodrome, and acknowledges the scenery first sent.
+fn connect(config: Config) -> Connection {
+	connect_all(vec![config])
+}
+
+/// Connects a client as for [`connect`], to a server of every aerodrome given.
+fn connect_all(configs: Vec<Config>) -> Connection {
+	let (channel, ServerChannel::Mpsc { mut rx, tx }) = mpsc_pair() else {
+		unreachable!("channels are paired over mpsc");
+	};
+	let mut client = Client::new(channel, None).unwrap();
+
+	for config in configs {
+		let icao = config.icao.clone();
+		let data = config.encode().unwrap();
+		tx.send(Downstream::Config { data, hash: None }).unwrap();
+		tx.send(Downstream::Control {
+			icao,
+			control: true,
+		})
+		.unwrap();
+	}
+	client.tick().unwrap();
+
+	while let Ok(message) = rx.try_recv() {
+		if let Upstream::Scenery { icao, seq, .. } = message {
+			tx.send(Downstream::SceneryAck { icao, seq }).unwrap();
+		}
+	}
+	client.tick().unwrap();
+	while rx.try_recv().is_ok() {}
+
+	(client, rx, tx)
+}
+
+/// Returns the blocks for which locks were requested since last taken.
+fn acquired(rx: &mut UnboundedReceiver<Upstream>) -> Vec<String> {
+	std::iter::from_fn(|| rx.try_recv().ok())
+		.filter_map(|message| match message {
+			Upstream::Acquire { block, .. } => Some(block),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Returns the scenery sent since last taken, with the sequence of each.
+fn sceneries(
+	rx: &mut UnboundedReceiver<Upstream>,
+) -> Vec<(HashMap<String, bool>, u64)> {
+	std::iter::from_fn(|| rx.try_recv().ok())
+		.filter_map(|message| match message {
+			Upstream::Scenery { scenery, seq, .. } => Some((scenery, seq)),
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn granted_locks_are_held() {
+	let mut client = Client::new_offline();
+	client.load_local(two_nodes()).unwrap();
+
+	let icao = "ZZZZ".to_string();
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_block(0, route(0, 1)).unwrap();
+	assert!(aerodrome.lock_requests.contains_key(&0));
+
+	// the loopback grants the lock within the tick
+	assert!(client.tick().unwrap().is_empty());
+
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert!(aerodrome.lock_requests.is_empty());
+	assert_eq!(aerodrome.held_locks(), ["B0"]);
+	assert_eq!(aerodrome.block_state(0), route(0, 1));
+}
+
+#[test]
+fn local_aerodromes_are_only_loaded_without_a_server() {
+	let icao = "ZZZZ".to_string();
+
+	let mut client = Client::new_offline();
+	assert!(client.is_offline());
+	client.load_local(two_lights()).unwrap();
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	assert_eq!(aerodrome.state(), ActivityState::Controlling);
+
+	// changes are confirmed within the tick they are sent
+	aerodrome.set_node(0, true).unwrap();
+	client.tick().unwrap();
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert_eq!(aerodrome.node_display_state(0), DisplayState::On);
+
+	// a client with a server keeps its aerodromes as the server gave them
+	let (mut client, _rx, _tx) = connect(two_nodes());
+	assert!(!client.is_offline());
+	let error = client.load_local(two_lights()).unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"local aerodromes can only be loaded without a server",
+	);
+
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert_eq!(aerodrome.config().blocks.len(), 1);
+	assert!(aerodrome.config().elements.is_empty());
+}
+
+#[test]
+fn denied_locks_roll_back_the_change() {
+	let (mut client, mut rx, tx) = connect(two_nodes());
+
+	let icao = "ZZZZ".to_string();
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_block(0, route(0, 1)).unwrap();
+	client.tick().unwrap();
+	assert_eq!(acquired(&mut rx), ["B0"]);
+
+	tx.send(Downstream::Lock {
+		icao: icao.clone(),
+		block: "B0".into(),
+		owner: "OTHER".into(),
+		granted: false,
+	})
+	.unwrap();
+	let messages = client.tick().unwrap();
+	assert_eq!(messages, ["ZZZZ: block B0 is locked by OTHER"]);
+
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert!(aerodrome.held_locks().is_empty());
+	assert_eq!(aerodrome.block_state(0), Clear);
+}
+
+#[test]
+fn unanswered_lock_requests_time_out_and_the_change_stands() {
+	let (mut client, mut rx, tx) = connect(two_nodes());
+
+	let icao = "ZZZZ".to_string();
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_block(0, route(0, 1)).unwrap();
+	client.tick().unwrap();
+	assert_eq!(acquired(&mut rx), ["B0"]);
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.advance(LOCK_TIMEOUT);
+	assert!(aerodrome.lock_requests.is_empty());
+
+	// an answer after the timeout is too late to roll back the change
+	tx.send(Downstream::Lock {
+		icao: icao.clone(),
+		block: "B0".into(),
+		owner: "OTHER".into(),
+		granted: false,
+	})
+	.unwrap();
+	assert!(client.tick().unwrap().is_empty());
+
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert!(aerodrome.held_locks().is_empty());
+	assert_eq!(aerodrome.block_state(0), route(0, 1));
+
+	// a later change asks again, as no lock was held
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_block(0, Relax).unwrap();
+	client.tick().unwrap();
+	assert_eq!(acquired(&mut rx), ["B0"]);
+}
+
+/// Lights an element for each of two direct nodes, as a config to be given to
+/// a client.
+fn two_lights() -> Config {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::None,
+	};
+	let mut config =
+		build(&[(None, direct), (None, direct)], vec![], vec![]).config;
+	config.elements = (0..2)
+		.map(|i| Element {
+			id: format!("E{i}"),
+			condition: ElementCondition::Node(i.into()),
+		})
+		.collect();
+	config
+}
+
+fn lights(states: &[(&str, bool)]) -> HashMap<String, bool> {
+	(states.iter())
+		.map(|(id, state)| (id.to_string(), *state))
+		.collect()
+}
+
+#[test]
+fn lost_scenery_is_retransmitted_merged() {
+	let (mut client, mut rx, tx) = connect(two_lights());
+	let icao = "ZZZZ".to_string();
+
+	// none of these are acknowledged, the first having been lost
+	for (node, state) in [(0, true), (1, true), (0, false)] {
+		let aerodrome = client.aerodrome_mut(&icao).unwrap();
+		aerodrome.set_node(node, state).unwrap();
+		client.tick().unwrap();
+	}
+	let sent = sceneries(&mut rx);
+	assert_eq!(
+		sent
+			.iter()
+			.map(|(scenery, _)| scenery.clone())
+			.collect::<Vec<_>>(),
+		[
+			lights(&[("E0", true)]),
+			lights(&[("E1", true)]),
+			lights(&[("E0", false)]),
+		],
+	);
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.advance(SCENERY_ACK_TIMEOUT);
+	client.tick().unwrap();
+
+	// the states now, not those first sent, are sent once for all three
+	let resent = sceneries(&mut rx);
+	assert_eq!(resent.len(), 1);
+	let (scenery, seq) = resent[0].clone();
+	assert_eq!(scenery, lights(&[("E0", false), ("E1", true)]));
+	assert!(sent.iter().all(|(_, sent)| *sent < seq));
+
+	// acknowledging the retransmission settles everything before it
+	tx.send(Downstream::SceneryAck {
+		icao: icao.clone(),
+		seq,
+	})
+	.unwrap();
+	client.tick().unwrap();
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	assert!(aerodrome.unacked_scenery.is_empty());
+	aerodrome.advance(SCENERY_ACK_TIMEOUT);
+	client.tick().unwrap();
+	assert!(sceneries(&mut rx).is_empty());
+}
+
+#[test]
+fn late_acks_of_older_scenery_leave_newer_to_be_retransmitted() {
+	let (mut client, mut rx, tx) = connect(two_lights());
+	let icao = "ZZZZ".to_string();
+
+	for node in 0..2 {
+		let aerodrome = client.aerodrome_mut(&icao).unwrap();
+		aerodrome.set_node(node, true).unwrap();
+		client.tick().unwrap();
+	}
+	let sent = sceneries(&mut rx);
+
+	tx.send(Downstream::SceneryAck {
+		icao: icao.clone(),
+		seq: sent[0].1,
+	})
+	.unwrap();
+	client.tick().unwrap();
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.advance(SCENERY_ACK_TIMEOUT);
+	client.tick().unwrap();
+
+	let resent = sceneries(&mut rx);
+	assert_eq!(resent.len(), 1);
+	assert_eq!(resent[0].0, lights(&[("E1", true)]));
+}
+
+/// Lights the first node by an element for each of two providers, and the
+/// second by one for all.
+fn two_providers() -> Config {
+	let mut config = two_lights();
+	config.elements = [("msfs:E0", 0), ("xp:E0", 0), ("E1", 1)]
+		.map(|(id, node)| Element {
+			id: id.into(),
+			condition: ElementCondition::Node(node.into()),
+		})
+		.into();
+	config
+}
+
+#[test]
+fn scenery_is_sent_for_the_providers() {
+	let (mut client, mut rx, _tx) = connect(two_providers());
+	let icao = "ZZZZ".to_string();
+
+	// changing providers sends everything again, by the ids they know
+	client.set_providers(&["xp"]);
+	client.tick().unwrap();
+	let sent = sceneries(&mut rx);
+	assert_eq!(sent.len(), 1);
+	assert_eq!(sent[0].0, lights(&[("E0", false), ("E1", false)]));
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_node(0, true).unwrap();
+	client.tick().unwrap();
+	assert_eq!(sceneries(&mut rx)[0].0, lights(&[("E0", true)]));
+
+	// elements for only other providers are not sent at all
+	client.set_providers(&["p3d"]);
+	client.tick().unwrap();
+	assert_eq!(sceneries(&mut rx)[0].0, lights(&[("E1", false)]));
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_node(0, false).unwrap();
+	client.tick().unwrap();
+	assert!(sceneries(&mut rx).is_empty());
+
+	// and without providers, everything is sent by its full id
+	client.set_providers(&[]);
+	client.tick().unwrap();
+	assert_eq!(
+		sceneries(&mut rx)[0].0,
+		lights(&[("msfs:E0", false), ("xp:E0", false), ("E1", false)]),
+	);
+}
+
+#[test]
+fn repeatedly_lost_scenery_reports_the_link_degraded() {
+	let (mut client, mut rx, _tx) = connect(two_lights());
+	let icao = "ZZZZ".to_string();
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_node(0, true).unwrap();
+	client.tick().unwrap();
+	assert_eq!(sceneries(&mut rx).len(), 1);
+
+	for retry in 1..=SCENERY_MAX_RETRIES {
+		let aerodrome = client.aerodrome_mut(&icao).unwrap();
+		aerodrome.advance(SCENERY_ACK_TIMEOUT);
+		let messages = client.tick().unwrap();
+
+		let resent = sceneries(&mut rx);
+		assert_eq!(resent.len(), 1, "retry {retry}");
+		assert_eq!(resent[0].0, lights(&[("E0", true)]), "retry {retry}");
+		assert_eq!(
+			messages.iter().any(|message| message.contains("degraded")),
+			retry == SCENERY_MAX_RETRIES,
+			"retry {retry}",
+		);
+	}
+
+	// a full resend supersedes what is outstanding
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.resend_scenery();
+	client.tick().unwrap();
+	let resent = sceneries(&mut rx);
+	assert_eq!(resent.len(), 1);
+	assert_eq!(resent[0].0, lights(&[("E0", true), ("E1", false)]));
+
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert_eq!(aerodrome.unacked_scenery.len(), 1);
+	assert_eq!(aerodrome.scenery_failures, 0);
+}
+
+/// Builds an aerodrome of a direct node and a block between two router nodes,
+/// each resetting after a minute, under the conflict strategy.
+fn timed(strategy: ConflictStrategy) -> Aerodrome {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::TimeSecs(60),
+	};
+	let router = NodeCondition::Router { sticky: false };
+	let block = Block {
+		id: "B0".into(),
+		nodes: vec![1.into(), 2.into()],
+		edges: Vec::new(),
+		non_routes: Vec::new(),
+		stands: Vec::new(),
+		input_order: None,
+	};
+
+	let nodes = [(None, direct), (None, router), (None, router)];
+	let mut config = build(&nodes, vec![block], Vec::new()).config;
+	config.profiles[0].blocks[0].reset = ResetCondition::TimeSecs(60);
+
+	let mut aerodrome = offline(config);
+	aerodrome.set_conflict_strategy(strategy);
+	aerodrome
+}
+
+#[test]
+fn conflicting_node_patches_follow_the_strategy() {
+	use ConflictStrategy::*;
+
+	for strategy in [ServerWins, LocalWins, ServerWinsNotify] {
+		let mut aerodrome = timed(strategy);
+		aerodrome.set_node(0, false).unwrap();
+		assert!(aerodrome.node_timer(0).is_some());
+
+		let patch = Patch {
+			nodes: [("N0".into(), true)].into(),
+			..Patch::default()
+		};
+		aerodrome.receive_patch(patch, None, None, false);
+
+		let local = strategy == LocalWins;
+		assert_eq!(aerodrome.node_state(0), !local, "{strategy:?}");
+		assert!(aerodrome.nodes[0].current, "{strategy:?}");
+		if !local {
+			assert_eq!(aerodrome.node_timer(0), None, "{strategy:?}");
+		}
+
+		let (patch, _) = aerodrome.take_pending();
+		assert_eq!(patch.nodes.contains_key("N0"), local, "{strategy:?}");
+
+		let messages = aerodrome.take_messages();
+		if strategy == ServerWinsNotify {
+			assert_eq!(
+				messages,
+				["node N0 was changed elsewhere to on, discarding the change to off"]
+			);
+		} else {
+			assert!(messages.is_empty(), "{strategy:?}");
+		}
+	}
+}
+
+#[test]
+fn conflicting_block_patches_follow_the_strategy() {
+	use ConflictStrategy::*;
+
+	for strategy in [ServerWins, LocalWins, ServerWinsNotify] {
+		let mut aerodrome = timed(strategy);
+		aerodrome.set_block(0, route(1, 2)).unwrap();
+		assert!(aerodrome.block_timer(0).is_some());
+
+		let patch = Patch {
+			blocks: [("B0".into(), aerodrome.bs_conf_to_ipc(&Relax))].into(),
+			..Patch::default()
+		};
+		aerodrome.receive_patch(patch, None, None, false);
+
+		let local = strategy == LocalWins;
+		let expected = if local { route(1, 2) } else { Relax };
+		assert_eq!(aerodrome.block_state(0), expected, "{strategy:?}");
+		assert_eq!(aerodrome.blocks[0].current, Relax, "{strategy:?}");
+		if !local {
+			assert_eq!(aerodrome.block_timer(0), None, "{strategy:?}");
+		}
+
+		let (patch, _) = aerodrome.take_pending();
+		assert_eq!(patch.blocks.contains_key("B0"), local, "{strategy:?}");
+
+		let messages = aerodrome.take_messages();
+		if strategy == ServerWinsNotify {
+			assert_eq!(
+				messages,
+				[
+					"block B0 was changed elsewhere to relax, discarding the change to \
+				 route N1 to N2"
+				]
+			);
+		} else {
+			assert!(messages.is_empty(), "{strategy:?}");
+		}
+	}
+}
+
+#[test]
+fn patches_matching_local_changes_are_not_conflicts() {
+	let mut aerodrome = timed(ConflictStrategy::ServerWinsNotify);
+	aerodrome.set_node(0, true).unwrap();
+	aerodrome.set_block(0, route(1, 2)).unwrap();
+
+	let patch = Patch {
+		nodes: [("N0".into(), true)].into(),
+		blocks: [("B0".into(), aerodrome.bs_conf_to_ipc(&route(1, 2)))].into(),
+		..Patch::default()
+	};
+	aerodrome.receive_patch(patch, None, None, false);
+
+	assert!(aerodrome.node_state(0));
+	assert_eq!(aerodrome.block_state(0), route(1, 2));
+	assert_eq!(aerodrome.nodes[0].pending, None);
+	assert_eq!(aerodrome.blocks[0].pending, None);
+	assert!(aerodrome.take_messages().is_empty());
+}
+
+/// Builds an aerodrome of a direct node lighting edge `X0`, and a block
+/// between two router nodes whose route from `N1` to `N2` lights edge `X1`.
+fn displayed(strategy: ConflictStrategy) -> Aerodrome {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::None,
+	};
+	let router = NodeCondition::Router { sticky: false };
+	let block = Block {
+		id: "B0".into(),
+		nodes: vec![1.into(), 2.into()],
+		edges: Vec::new(),
+		non_routes: Vec::new(),
+		stands: Vec::new(),
+		input_order: None,
+	};
+
+	let mut aerodrome = build(
+		&[(None, direct), (None, router), (None, router)],
+		vec![block],
+		vec![
+			direct_edge(&[0], &[]),
+			EdgeCondition::Router {
+				block: 0.into(),
+				routes: block_routes(&[(1, 2)]),
+			},
+		],
+	);
+	aerodrome.set_conflict_strategy(strategy);
+	aerodrome
+}
+
+#[test]
+fn display_states_are_pending_until_confirmed() {
+	use DisplayState::*;
+
+	let mut aerodrome = displayed(ConflictStrategy::ServerWins);
+	// of N0, X0 and X1, then of the stop bar N1, lit while not routed through
+	let states = |aerodrome: &Aerodrome| {
+		(
+			[
+				aerodrome.node_display_state(0),
+				aerodrome.edge_display_state(0),
+				aerodrome.edge_display_state(1),
+			],
+			aerodrome.node_display_state(1),
+		)
+	};
+	assert_eq!(states(&aerodrome), ([Off; 3], On));
+
+	aerodrome.set_node(0, true).unwrap();
+	aerodrome.set_route((1, 2)).unwrap();
+	assert_eq!(states(&aerodrome), ([PendingOn; 3], PendingOff));
+	assert!(states(&aerodrome).0.into_iter().all(DisplayState::is_on));
+	assert!(!states(&aerodrome).1.is_on());
+
+	// the server confirming the changes shows them plainly
+	let patch = Patch {
+		nodes: [("N0".into(), true)].into(),
+		blocks: [("B0".into(), aerodrome.bs_conf_to_ipc(&route(1, 2)))].into(),
+		..Patch::default()
+	};
+	aerodrome.receive_patch(patch, None, None, false);
+	assert_eq!(states(&aerodrome), ([On; 3], Off));
+
+	aerodrome.set_node(0, false).unwrap();
+	aerodrome.set_block(0, Clear).unwrap();
+	assert_eq!(states(&aerodrome), ([PendingOff; 3], PendingOn));
+
+	let patch = Patch {
+		nodes: [("N0".into(), false)].into(),
+		blocks: [("B0".into(), aerodrome.bs_conf_to_ipc(&Clear))].into(),
+		..Patch::default()
+	};
+	aerodrome.receive_patch(patch, None, None, false);
+	assert_eq!(states(&aerodrome), ([Off; 3], On));
+}
+
+#[test]
+fn display_states_follow_the_server_overriding_pending_changes() {
+	use ConflictStrategy::*;
+	use DisplayState::*;
+
+	for strategy in [ServerWins, LocalWins] {
+		let mut aerodrome = displayed(strategy);
+		aerodrome.set_node(0, true).unwrap();
+		aerodrome.set_route((1, 2)).unwrap();
+
+		// the server sets both otherwise than set locally, as they were
+		let patch = Patch {
+			nodes: [("N0".into(), false)].into(),
+			blocks: [("B0".into(), aerodrome.bs_conf_to_ipc(&Clear))].into(),
+			..Patch::default()
+		};
+		aerodrome.receive_patch(patch, None, None, false);
+
+		// the server's states are shown, unless the local changes are kept to
+		// be sent again, when they are still pending
+		let expected = if strategy == LocalWins {
+			PendingOn
+		} else {
+			Off
+		};
+		assert_eq!(aerodrome.node_display_state(0), expected, "{strategy:?}");
+		assert_eq!(aerodrome.edge_display_state(0), expected, "{strategy:?}");
+		assert_eq!(aerodrome.edge_display_state(1), expected, "{strategy:?}");
+
+		// and a server turning on what was set off locally shows it on
+		let mut aerodrome = displayed(strategy);
+		aerodrome.receive_patch(node_patch(true), None, None, false);
+		aerodrome.set_node(0, false).unwrap();
+		assert_eq!(aerodrome.edge_display_state(0), PendingOff, "{strategy:?}");
+
+		aerodrome.receive_patch(node_patch(true), None, None, false);
+		let expected = if strategy == LocalWins {
+			PendingOff
+		} else {
+			On
+		};
+		assert_eq!(aerodrome.node_display_state(0), expected, "{strategy:?}");
+		assert_eq!(aerodrome.edge_display_state(0), expected, "{strategy:?}");
+	}
+}
+
+#[test]
+fn observers_send_no_changes() {
+	let (mut client, mut rx, tx) = connect(two_lights());
+	let icao = "ZZZZ".to_string();
+
+	tx.send(Downstream::Control {
+		icao: icao.clone(),
+		control: false,
+	})
+	.unwrap();
+	client.tick().unwrap();
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	assert_eq!(aerodrome.state(), ActivityState::Observing);
+	assert_eq!(
+		aerodrome.set_node(0, true),
+		Err(ControlError::NotControlling(ActivityState::Observing)),
+	);
+	assert!(!aerodrome.node_state(0));
+
+	client.tick().unwrap();
+	let sent = std::iter::from_fn(|| rx.try_recv().ok())
+		.filter(|message| {
+			matches!(message, Upstream::Patch { .. } | Upstream::Scenery { .. })
+		})
+		.count();
+	assert_eq!(sent, 0);
+}
+
+#[test]
+fn changes_are_refused_without_control() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+	aerodrome.set_offline(false);
+	aerodrome.take_pending();
+
+	for state in [ActivityState::None, ActivityState::Observing] {
+		aerodrome.state = state;
+		let refused = Err(ControlError::NotControlling(state));
+
+		assert_eq!(aerodrome.set_node(0, false), refused);
+		assert_eq!(aerodrome.set_block(0, route(1, 2)), refused);
+		assert_eq!(aerodrome.set_route((1, 2)), refused);
+		assert_eq!(aerodrome.set_profile(0), refused);
+		assert_eq!(aerodrome.apply_preset(0), refused);
+
+		assert!(aerodrome.node_state(0));
+		assert_eq!(aerodrome.block_state(0), Clear);
+
+		let (patch, scenery) = aerodrome.take_pending();
+		assert!(patch.is_empty(), "{state:?}");
+		assert!(scenery.is_none(), "{state:?}");
+	}
+
+	aerodrome.state = ActivityState::Controlling;
+	assert_eq!(aerodrome.set_node(0, false), Ok(()));
+	assert!(!aerodrome.take_pending().0.is_empty());
+}
+
+#[test]
+fn offline_aerodromes_change_without_control() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+	assert_eq!(aerodrome.state(), ActivityState::None);
+
+	assert_eq!(aerodrome.set_node(0, false), Ok(()));
+	assert_eq!(aerodrome.set_block(0, route(1, 2)), Ok(()));
+	assert!(!aerodrome.node_state(0));
+	assert_eq!(aerodrome.block_state(0), route(1, 2));
+}
+
+#[test]
+fn disconnecting_errors_drop_observed_aerodromes() {
+	let (mut client, mut rx, tx) = connect(two_lights());
+	let icao = "ZZZZ".to_string();
+
+	tx.send(Downstream::Control {
+		icao: icao.clone(),
+		control: false,
+	})
+	.unwrap();
+	tx.send(Downstream::Error {
+		icao: icao.clone(),
+		message: Some("kicked".into()),
+		disconnect: true,
+	})
+	.unwrap();
+
+	let messages = client.tick().unwrap();
+	assert_eq!(messages, ["server: ZZZZ: kicked"]);
+	assert!(client.aerodrome(&icao).is_none());
+
+	let untracked = std::iter::from_fn(|| rx.try_recv().ok()).any(|message| {
+		matches!(message, Upstream::Track { icao: ref i, track: false } if *i == icao)
+	});
+	assert!(untracked);
+}
+
+fn node_patch(state: bool) -> Patch {
+	Patch {
+		nodes: [("N0".into(), state)].into(),
+		..Patch::default()
+	}
+}
+
+#[test]
+fn gaps_in_patches_request_a_snapshot() {
+	let (mut client, mut rx, tx) = connect(two_lights());
+	let icao = "ZZZZ".to_string();
+
+	let resyncs = |rx: &mut UnboundedReceiver<Upstream>| {
+		std::iter::from_fn(|| rx.try_recv().ok())
+			.filter(|message| matches!(message, Upstream::Resync { .. }))
+			.count()
+	};
+	let patch = |state, seq, first_seq| Downstream::Patch {
+		icao: icao.clone(),
+		patch: Box::new(node_patch(state)),
+		seq: Some(seq),
+		first_seq,
+		snapshot: false,
+	};
+
+	tx.send(patch(true, 1, None)).unwrap();
+	client.tick().unwrap();
+	assert_eq!(resyncs(&mut rx), 0);
+
+	// several merged into one are not missing
+	tx.send(patch(false, 4, Some(2))).unwrap();
+	client.tick().unwrap();
+	assert_eq!(resyncs(&mut rx), 0);
+
+	// the patch after a gap is still applied, being newer
+	tx.send(patch(true, 6, None)).unwrap();
+	client.tick().unwrap();
+	assert_eq!(resyncs(&mut rx), 1);
+	assert!(client.aerodrome(&icao).unwrap().node_state(0));
+
+	// and asked for only once
+	client.tick().unwrap();
+	assert_eq!(resyncs(&mut rx), 0);
+}
+
+#[test]
+fn old_and_duplicate_patches_are_dropped() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+
+	aerodrome.receive_patch(node_patch(false), Some(2), None, false);
+	assert!(!aerodrome.node_state(0));
+
+	for seq in [2, 1] {
+		aerodrome.receive_patch(node_patch(true), Some(seq), None, false);
+		assert!(!aerodrome.node_state(0), "seq {seq}");
+	}
+	assert!(!aerodrome.take_resync_request());
+
+	// servers which do not number patches have them applied as they come
+	aerodrome.receive_patch(node_patch(true), None, None, false);
+	assert!(aerodrome.node_state(0));
+}
+
+#[test]
+fn snapshots_replace_pending_changes() {
+	let mut aerodrome = timed(ConflictStrategy::LocalWins);
+	aerodrome.receive_patch(Patch::default(), Some(1), None, false);
+	aerodrome.set_node(0, false).unwrap();
+	aerodrome.set_block(0, route(1, 2)).unwrap();
+	aerodrome.take_pending();
+
+	// a patch which conflicts is kept under the strategy
+	aerodrome.receive_patch(node_patch(true), Some(2), None, false);
+	assert!(!aerodrome.node_state(0));
+
+	// but a snapshot discards it, keeping local changes it agrees with
+	let snapshot = Patch {
+		nodes: [("N0".into(), true)].into(),
+		blocks: [("B0".into(), aerodrome.bs_conf_to_ipc(&route(1, 2)))].into(),
+		..Patch::default()
+	};
+	aerodrome.receive_patch(snapshot, Some(3), None, true);
+
+	assert!(aerodrome.node_state(0));
+	assert_eq!(aerodrome.nodes[0].pending, None);
+	assert_eq!(aerodrome.block_state(0), route(1, 2));
+	assert_eq!(aerodrome.blocks[0].pending, None);
+
+	// and every element is sent again
+	assert!(aerodrome.full_scenery);
+	assert!(aerodrome.take_messages().is_empty());
+}
+
+#[test]
+fn snapshots_clear_missing_overrides() {
+	let mut aerodrome = routed(&[None, None], &[(&[0, 1], &[])], &[(0, &[])]);
+	let patch = Patch {
+		edges: [("X0".into(), Some(true))].into(),
+		..Patch::default()
+	};
+	aerodrome.receive_patch(patch, Some(1), None, false);
+	assert!(aerodrome.edge_state(0));
+
+	aerodrome.receive_patch(Patch::default(), Some(2), None, true);
+	assert!(!aerodrome.edge_state(0));
+}
+
+#[test]
+fn snapshots_as_of_the_last_patch_apply_only_if_requested() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+	aerodrome.receive_patch(node_patch(false), Some(1), None, false);
+
+	aerodrome.receive_patch(node_patch(true), Some(1), None, true);
+	assert!(!aerodrome.node_state(0));
+
+	aerodrome.receive_patch(node_patch(false), Some(3), None, false);
+	assert!(aerodrome.take_resync_request());
+	aerodrome.receive_patch(node_patch(true), Some(3), None, true);
+	assert!(aerodrome.node_state(0));
+}
+
+/// Asserts that a timer has at most the time left, and less than a second
+/// less, as real time passes while testing.
+fn assert_left(timer: Option<Duration>, secs: u64) {
+	let timer = timer.expect("a timer should be running");
+	let secs = Duration::from_secs(secs);
+	assert!(timer <= secs, "{timer:?} left of {secs:?}");
+	assert!(
+		timer > secs - Duration::from_secs(1),
+		"{timer:?} left of {secs:?}"
+	);
+}
+
+#[test]
+fn timers_count_down_to_the_reset() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+	let node = CountdownCondition::Node(0.into());
+	let block = CountdownCondition::Block(0.into());
+
+	assert_eq!(aerodrome.node_timer(0), None);
+	assert_eq!(aerodrome.block_timer(0), None);
+	assert_eq!(aerodrome.countdown(&node), None);
+	assert_eq!(aerodrome.countdown_total(&block), None);
+
+	// nodes reset on, so only turning one off arms its timer
+	aerodrome.set_node(0, true).unwrap();
+	assert_eq!(aerodrome.node_timer(0), None);
+
+	aerodrome.set_node(0, false).unwrap();
+	aerodrome.set_block(0, route(1, 2)).unwrap();
+	aerodrome.advance(Duration::from_secs(20));
+
+	assert_left(aerodrome.node_timer(0), 40);
+	assert_left(aerodrome.block_timer(0), 40);
+	assert_left(aerodrome.countdown(&node), 40);
+	assert_left(aerodrome.countdown(&block), 40);
+	assert_eq!(
+		aerodrome.countdown_total(&node),
+		Some(Duration::from_secs(60)),
+	);
+
+	aerodrome.advance(Duration::from_secs(41));
+	assert_eq!(aerodrome.node_timer(0), None);
+	assert_eq!(aerodrome.countdown(&block), None);
+	assert!(aerodrome.node_state(0));
+	assert_eq!(aerodrome.block_state(0), Clear);
+}
+
+#[test]
+fn timers_follow_patches() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+
+	// a patch confirming the change keeps its timer
+	aerodrome.set_node(0, false).unwrap();
+	aerodrome.receive_patch(node_patch(false), None, None, false);
+	assert_left(aerodrome.node_timer(0), 60);
+
+	// one contradicting it cancels the timer
+	aerodrome.receive_patch(node_patch(true), None, None, false);
+	assert_eq!(aerodrome.node_timer(0), None);
+
+	// and one with a deadline runs to that instead
+	let mut patch = node_patch(false);
+	let deadline = epoch_millis() + 30_000;
+	patch.node_deadlines.insert("N0".into(), Some(deadline));
+	aerodrome.receive_patch(patch, None, None, false);
+	assert_left(aerodrome.node_timer(0), 30);
+
+	let node = CountdownCondition::Node(0.into());
+	assert_eq!(
+		aerodrome.countdown_total(&node),
+		Some(Duration::from_secs(60)),
+	);
+}
+
+fn timer_origin(
+	aerodrome: &Aerodrome,
+	entity: TimerEntity,
+) -> Option<TimerOrigin> {
+	(aerodrome.timers().into_iter())
+		.find(|timer| timer.entity == entity)
+		.map(|timer| timer.origin)
+}
+
+#[test]
+fn reset_overrides_replace_the_time_of_the_profile() {
+	let node = TimerEntity::Node(0);
+	let block = TimerEntity::Block(0);
+
+	// shorter than the minute of the profile
+	let mut aerodrome = timed(ConflictStrategy::default());
+	aerodrome.set_node_timed(0, false, Some(30)).unwrap();
+	aerodrome.set_block_timed(0, route(1, 2), Some(15)).unwrap();
+	assert_left(aerodrome.node_timer(0), 30);
+	assert_left(aerodrome.block_timer(0), 15);
+	assert_eq!(timer_origin(&aerodrome, node), Some(TimerOrigin::Override));
+	assert_eq!(timer_origin(&aerodrome, block), Some(TimerOrigin::Override));
+
+	aerodrome.advance(Duration::from_secs(16));
+	assert_eq!(aerodrome.block_state(0), Clear);
+	assert!(!aerodrome.node_state(0));
+	aerodrome.advance(Duration::from_secs(15));
+	assert!(aerodrome.node_state(0));
+
+	// longer than it
+	let mut aerodrome = timed(ConflictStrategy::default());
+	aerodrome.set_node_timed(0, false, Some(120)).unwrap();
+	aerodrome.set_block_timed(0, route(1, 2), Some(90)).unwrap();
+	aerodrome.advance(Duration::from_secs(61));
+	assert!(!aerodrome.node_state(0));
+	assert_eq!(aerodrome.block_state(0), route(1, 2));
+	assert_left(aerodrome.node_timer(0), 59);
+	assert_left(aerodrome.block_timer(0), 29);
+
+	aerodrome.advance(Duration::from_secs(30));
+	assert_eq!(aerodrome.block_state(0), Clear);
+	aerodrome.advance(Duration::from_secs(30));
+	assert!(aerodrome.node_state(0));
+
+	// and without one, the profile's
+	let mut aerodrome = timed(ConflictStrategy::default());
+	aerodrome.set_node_timed(0, false, None).unwrap();
+	assert_left(aerodrome.node_timer(0), 60);
+	assert_eq!(timer_origin(&aerodrome, node), Some(TimerOrigin::Profile));
+}
+
+#[test]
+fn reset_overrides_never_add_a_reset() {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::None,
+	};
+	let router = NodeCondition::Router { sticky: false };
+	let block = Block {
+		id: "B0".into(),
+		nodes: vec![1.into(), 2.into()],
+		edges: Vec::new(),
+		non_routes: Vec::new(),
+		stands: Vec::new(),
+		input_order: None,
+	};
+	let nodes = [(None, direct), (None, router), (None, router)];
+	let mut aerodrome = build(&nodes, vec![block], Vec::new());
+
+	aerodrome.set_node_timed(0, false, Some(30)).unwrap();
+	aerodrome.set_block_timed(0, route(1, 2), Some(30)).unwrap();
+	assert_eq!(aerodrome.node_timer(0), None);
+	assert_eq!(aerodrome.block_timer(0), None);
+	assert!(aerodrome.timers().is_empty());
+
+	aerodrome.advance(Duration::from_secs(31));
+	assert!(!aerodrome.node_state(0));
+	assert_eq!(aerodrome.block_state(0), route(1, 2));
+}
+
+#[test]
+fn extended_timers_fire_later() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+	let node = TimerEntity::Node(0);
+
+	assert_eq!(
+		aerodrome.extend_timer(node, Duration::from_secs(30)),
+		Ok(false)
+	);
+
+	aerodrome.set_node(0, false).unwrap();
+	aerodrome.take_pending();
+	aerodrome.advance(Duration::from_secs(50));
+	assert_eq!(
+		aerodrome.extend_timer(node, Duration::from_secs(30)),
+		Ok(true)
+	);
+	assert_left(aerodrome.node_timer(0), 40);
+	assert_eq!(timer_origin(&aerodrome, node), Some(TimerOrigin::Override));
+
+	// the new deadline is sent with the state, for others to reset with it
+	let (patch, _) = aerodrome.take_pending();
+	let deadline = patch.node_deadlines["N0"].unwrap();
+	let left = deadline.saturating_sub(epoch_millis());
+	assert!((39_000..=40_000).contains(&left), "{left} ms left");
+
+	// not when the profile's minute is up, but once extended by half of it
+	aerodrome.advance(Duration::from_secs(11));
+	assert!(!aerodrome.node_state(0));
+	aerodrome.advance(Duration::from_secs(30));
+	assert!(aerodrome.node_state(0));
+	assert_eq!(aerodrome.node_timer(0), None);
+}
+
+#[test]
+fn cancelled_timers_stay_until_changed_again() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+	let block = TimerEntity::Block(0);
+
+	assert_eq!(aerodrome.cancel_timer(block), Ok(false));
+
+	aerodrome.set_block(0, route(1, 2)).unwrap();
+	aerodrome.take_pending();
+	assert_eq!(aerodrome.cancel_timer(block), Ok(true));
+	assert_eq!(aerodrome.block_timer(0), None);
+
+	let (patch, _) = aerodrome.take_pending();
+	assert_eq!(patch.block_deadlines.get("B0"), Some(&None));
+
+	// the block stays routed long after it would have reset
+	aerodrome.advance(Duration::from_secs(600));
+	assert_eq!(aerodrome.block_state(0), route(1, 2));
+
+	// until restored by hand, and a later change arms a timer as usual
+	aerodrome.set_block(0, Clear).unwrap();
+	aerodrome.set_block(0, route(2, 1)).unwrap();
+	assert_left(aerodrome.block_timer(0), 60);
+	assert_eq!(timer_origin(&aerodrome, block), Some(TimerOrigin::Profile));
+	aerodrome.advance(Duration::from_secs(61));
+	assert_eq!(aerodrome.block_state(0), Clear);
+}
+
+/// Builds a chain of four blocks between five router nodes, each block
+/// resetting after a minute, releasing the blocks behind those cleared if
+/// asked.
+fn chain(release_trailing: bool) -> Aerodrome {
+	chain_with(release_trailing, RouteActivation::Immediate).0
+}
+
+/// Routes the length of a chain of four blocks, each resetting after a
+/// minute, returning the aerodrome and the id of the route.
+fn chain_with(
+	release_trailing: bool,
+	activation: RouteActivation,
+) -> (Aerodrome, usize) {
+	let blocks: &[(&[usize], Routes)] = &[
+		(&[0, 1], &[]),
+		(&[1, 2], &[]),
+		(&[2, 3], &[]),
+		(&[3, 4], &[]),
+	];
+	let mut config = routed(&[None; 5], blocks, &[]).config;
+	for block in &mut config.profiles[0].blocks {
+		block.reset = ResetCondition::TimeSecs(60);
+	}
+
+	let mut aerodrome = offline(config);
+	aerodrome.set_release_trailing(release_trailing);
+	let id = aerodrome.set_route_with((0, 4), activation).unwrap();
+	(aerodrome, id.unwrap())
+}
+
+#[test]
+fn clearing_a_block_releases_those_behind() {
+	let nodes = |aerodrome: &Aerodrome| {
+		(0..5)
+			.map(|node| aerodrome.node_state(node))
+			.collect::<Vec<_>>()
+	};
+
+	let mut aerodrome = chain(true);
+	let routes = [route(0, 1), route(1, 2), route(2, 3), route(3, 4)];
+	assert_eq!(aerodrome.block_states(), routes);
+	assert_eq!(nodes(&aerodrome), [false; 5]);
+
+	aerodrome.set_block(2, Clear).unwrap();
+	assert_eq!(aerodrome.block_states(), [Clear, Clear, Clear, route(3, 4)]);
+	// and the stopbars behind light again
+	assert_eq!(nodes(&aerodrome), [true, true, true, true, false]);
+
+	// not unless asked
+	let mut aerodrome = chain(false);
+	aerodrome.set_block(2, Clear).unwrap();
+	assert_eq!(
+		aerodrome.block_states(),
+		[route(0, 1), route(1, 2), Clear, route(3, 4)],
+	);
+}
+
+#[test]
+fn timers_release_the_blocks_behind() {
+	let mut aerodrome = chain(true);
+
+	// the second block resets first
+	for block in [0, 2, 3] {
+		let extended = aerodrome
+			.extend_timer(TimerEntity::Block(block), Duration::from_secs(60));
+		assert_eq!(extended, Ok(true));
+	}
+	aerodrome.advance(Duration::from_secs(61));
+	assert_eq!(
+		aerodrome.block_states(),
+		[Clear, Clear, route(2, 3), route(3, 4)],
+	);
+	assert_eq!(aerodrome.block_timer(0), None);
+	assert_left(aerodrome.block_timer(2), 59);
+}
+
+#[test]
+fn blocks_cleared_out_of_order_release_only_those_behind() {
+	let mut aerodrome = chain(true);
+
+	aerodrome.set_block(1, Clear).unwrap();
+	assert_eq!(
+		aerodrome.block_states(),
+		[Clear, Clear, route(2, 3), route(3, 4)],
+	);
+
+	// the blocks ahead are left, so the last cleared releases the rest
+	aerodrome.set_block(3, Clear).unwrap();
+	assert_eq!(aerodrome.block_states(), [Clear; 4]);
+
+	// and clearing the first releases nothing but it
+	let mut aerodrome = chain(true);
+	aerodrome.set_block(0, Clear).unwrap();
+	assert_eq!(
+		aerodrome.block_states(),
+		[Clear, route(1, 2), route(2, 3), route(3, 4)],
+	);
+
+	// nor does relaxing rather than clearing
+	let mut aerodrome = chain(true);
+	aerodrome.set_block(2, Relax).unwrap();
+	assert_eq!(
+		aerodrome.block_states(),
+		[route(0, 1), route(1, 2), Relax, route(3, 4)],
+	);
+}
+
+fn route_blocks(aerodrome: &Aerodrome) -> Vec<(Vec<usize>, Vec<usize>)> {
+	(aerodrome.active_routes().into_iter())
+		.map(|route| (route.active, route.planned))
+		.collect()
+}
+
+#[test]
+fn progressive_routes_set_blocks_as_advanced() {
+	let progressive = RouteActivation::Progressive { ahead: 2 };
+	let (mut aerodrome, id) = chain_with(false, progressive);
+	assert_eq!(
+		aerodrome.block_states(),
+		[route(0, 1), route(1, 2), Clear, Clear],
+	);
+	assert_eq!(route_blocks(&aerodrome), [(vec![0, 1], vec![2, 3])]);
+
+	// the leading block clearing sets the next in its place
+	aerodrome.set_block(0, Clear).unwrap();
+	assert_eq!(
+		aerodrome.block_states(),
+		[Clear, route(1, 2), route(2, 3), Clear],
+	);
+	assert_eq!(route_blocks(&aerodrome), [(vec![1, 2], vec![3])]);
+
+	// and advancing sets it ahead of its time
+	assert_eq!(aerodrome.advance_route(id), Ok(true));
+	assert_eq!(
+		aerodrome.block_states(),
+		[Clear, route(1, 2), route(2, 3), route(3, 4)],
+	);
+	assert_eq!(route_blocks(&aerodrome), [(vec![1, 2, 3], vec![])]);
+
+	// until there are none left
+	assert_eq!(aerodrome.advance_route(id), Ok(false));
+	assert_eq!(aerodrome.advance_route(id + 1), Ok(false));
+
+	// when trailing blocks are released, as many stay set
+	let (mut aerodrome, id) = chain_with(true, progressive);
+	assert_eq!(aerodrome.advance_route(id), Ok(true));
+	assert_eq!(
+		aerodrome.block_states(),
+		[Clear, route(1, 2), route(2, 3), Clear],
+	);
+	assert_eq!(route_blocks(&aerodrome), [(vec![1, 2], vec![3])]);
+}
+
+#[test]
+fn cancelled_routes_keep_only_the_blocks_set() {
+	let progressive = RouteActivation::Progressive { ahead: 1 };
+	let (mut aerodrome, id) = chain_with(false, progressive);
+	assert_eq!(aerodrome.advance_route(id), Ok(true));
+
+	assert_eq!(route_blocks(&aerodrome), [(vec![0, 1], vec![2, 3])]);
+
+	assert_eq!(aerodrome.cancel_route(id), Ok(true));
+	assert_eq!(
+		aerodrome.block_states(),
+		[route(0, 1), route(1, 2), Clear, Clear],
+	);
+	assert_eq!(route_blocks(&aerodrome), [(vec![0, 1], vec![])]);
+	assert_eq!(aerodrome.cancel_route(id), Ok(false));
+	assert_eq!(aerodrome.advance_route(id), Ok(false));
+
+	// so clearing the blocks set sets no more, and ends the route
+	aerodrome.set_block(0, Clear).unwrap();
+	aerodrome.set_block(1, Clear).unwrap();
+	assert_eq!(aerodrome.block_states(), [Clear; 4]);
+	assert_eq!(route_blocks(&aerodrome), []);
+}
+
+/// Asserts that milliseconds are within those a test might take of another.
+fn assert_millis(millis: f64, expected: f64) {
+	assert!(
+		(millis - expected).abs() < 250.0,
+		"{millis} ms, not {expected} ms",
+	);
+}
+
+#[test]
+fn clocks_follow_the_server_time() {
+	let (mut client, _rx, tx) =
+		connect(timed(ConflictStrategy::default()).config);
+	let icao = "ZZZZ".to_string();
+	let time = |ahead: u64| Downstream::Time {
+		icao: icao.clone(),
+		server_epoch_millis: epoch_millis() + ahead,
+	};
+
+	// without the server time, deadlines are of the local clock
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	assert_eq!(aerodrome.clock_offset, None);
+	assert_millis(aerodrome.server_now(), epoch_millis() as f64);
+
+	aerodrome.set_node(0, false).unwrap();
+	let deadline = aerodrome.pending_patch.node_deadlines["N0"].unwrap();
+	assert_millis(deadline as f64, (epoch_millis() + 60_000) as f64);
+
+	let deadline = aerodrome.deadline_from_server(epoch_millis() + 30_000);
+	assert_left(Some(deadline - aerodrome.now()), 30);
+
+	// the first time taken is the offset
+	tx.send(time(10_000)).unwrap();
+	client.tick().unwrap();
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert_millis(aerodrome.clock_offset.unwrap(), 10_000.0);
+	assert_millis(
+		aerodrome.server_millis() as f64,
+		(epoch_millis() + 10_000) as f64,
+	);
+
+	// and later times move it only some of the way
+	tx.send(time(20_000)).unwrap();
+	client.tick().unwrap();
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	let offset = 10_000.0 + 10_000.0 * CLOCK_SMOOTHING;
+	assert_millis(aerodrome.clock_offset.unwrap(), offset);
+
+	// so deadlines are translated by the offset both ways
+	let server = aerodrome.server_now() as u64;
+	let deadline = aerodrome.deadline_from_server(server + 30_000);
+	assert_left(Some(deadline - aerodrome.now()), 30);
+
+	let deadline = aerodrome.now() + Duration::from_secs(45);
+	assert_millis(
+		aerodrome.deadline_to_server(deadline) as f64,
+		epoch_millis() as f64 + offset + 45_000.0,
+	);
+}
+
+#[test]
+fn timers_stop_when_the_profile_changes() {
+	let mut aerodrome = timed(ConflictStrategy::default());
+	aerodrome.set_node(0, false).unwrap();
+	aerodrome.set_block(0, route(1, 2)).unwrap();
+
+	aerodrome.set_profile(0).unwrap();
+	assert_eq!(aerodrome.node_timer(0), None);
+	assert_eq!(aerodrome.block_timer(0), None);
+
+	aerodrome.set_node(0, false).unwrap();
+	let patch = Patch {
+		profile: Some("default".into()),
+		..Patch::default()
+	};
+	aerodrome.receive_patch(patch, None, None, false);
+	assert_eq!(aerodrome.node_timer(0), None);
+}
+
+/// Builds an aerodrome of direct nodes, each resetting after the seconds
+/// given once turned off.
+fn resetting_after(secs: &[u32]) -> Aerodrome {
+	let nodes = (secs.iter())
+		.map(|secs| {
+			(
+				None,
+				NodeCondition::Direct {
+					reset: ResetCondition::TimeSecs(*secs),
+				},
+			)
+		})
+		.collect::<Vec<_>>();
+	build(&nodes, Vec::new(), Vec::new())
+}
+
+fn timer_order(aerodrome: &Aerodrome) -> Vec<TimerEntity> {
+	(aerodrome.timers().iter())
+		.map(|timer| timer.entity)
+		.collect()
+}
+
+#[test]
+fn timers_armed_out_of_order_reset_in_order() {
+	use TimerEntity::Node;
+
+	let mut aerodrome = resetting_after(&[60, 10, 30]);
+	for node in 0..3 {
+		aerodrome.set_node(node, false).unwrap();
+	}
+
+	assert_eq!(timer_order(&aerodrome), [Node(1), Node(2), Node(0)]);
+
+	aerodrome.advance(Duration::from_secs(11));
+	assert_eq!(
+		(0..3)
+			.map(|node| aerodrome.node_state(node))
+			.collect::<Vec<_>>(),
+		[false, true, false],
+	);
+
+	aerodrome.advance(Duration::from_secs(20));
+	assert_eq!(
+		(0..3)
+			.map(|node| aerodrome.node_state(node))
+			.collect::<Vec<_>>(),
+		[false, true, true],
+	);
+	assert_eq!(timer_order(&aerodrome), [Node(0)]);
+}
+
+#[test]
+fn cancelled_timers_leave_the_rest_in_order() {
+	use TimerEntity::Node;
+
+	let mut aerodrome = resetting_after(&[10, 20, 30, 40]);
+	for node in 0..4 {
+		aerodrome.set_node(node, false).unwrap();
+	}
+
+	assert_eq!(aerodrome.cancel_timer(Node(0)), Ok(true));
+	assert_eq!(aerodrome.cancel_timer(Node(0)), Ok(false));
+
+	// turning a node on cancels its timer as well
+	aerodrome.set_node(2, true).unwrap();
+	assert_eq!(timer_order(&aerodrome), [Node(1), Node(3)]);
+
+	aerodrome.advance(Duration::from_secs(21));
+	assert_eq!(
+		(0..4)
+			.map(|node| aerodrome.node_state(node))
+			.collect::<Vec<_>>(),
+		[false, true, true, false],
+	);
+	assert_eq!(timer_order(&aerodrome), [Node(3)]);
+}
+
+#[test]
+fn next_deadline_is_that_of_the_earliest_timer() {
+	let mut aerodrome = resetting_after(&[60, 10]);
+	assert_eq!(aerodrome.next_deadline(), None);
+
+	aerodrome.set_node(0, false).unwrap();
+	let first = aerodrome.next_deadline().unwrap();
+	aerodrome.set_node(1, false).unwrap();
+	let next = aerodrome.next_deadline().unwrap();
+	assert!(next < first);
+
+	aerodrome.cancel_timer(TimerEntity::Node(1)).unwrap();
+	aerodrome.cancel_timer(TimerEntity::Node(0)).unwrap();
+	assert_eq!(aerodrome.next_deadline(), None);
+}
+
+#[test]
+fn timers_due_without_control_are_kept_until_it_is_regained() {
+	let mut aerodrome = resetting_after(&[10]);
+	aerodrome.set_offline(false);
+	aerodrome.state = ActivityState::Controlling;
+	aerodrome.set_node(0, false).unwrap();
+
+	aerodrome.state = ActivityState::Observing;
+	aerodrome.advance(Duration::from_secs(11));
+	assert!(!aerodrome.node_state(0));
+	assert_eq!(aerodrome.node_timer(0), Some(Duration::ZERO));
+
+	// the host need not wake for a reset it cannot make
+	assert_eq!(aerodrome.next_deadline(), None);
+
+	aerodrome.state = ActivityState::Controlling;
+	assert!(aerodrome.next_deadline().is_some());
+	aerodrome.advance(Duration::ZERO);
+	assert!(aerodrome.node_state(0));
+	assert_eq!(aerodrome.node_timer(0), None);
+	assert_eq!(aerodrome.next_deadline(), None);
+}
+
+/// Builds an aerodrome of a block leading on to a range of stands, and one
+/// leading on to a stand within that range and a glob of others.
+fn stands() -> Aerodrome {
+	let router = NodeCondition::Router { sticky: false };
+	let block = |i: usize, stands: &[&str]| Block {
+		id: format!("B{i}"),
+		nodes: vec![i.into(), (i + 1).into()],
+		edges: Vec::new(),
+		non_routes: Vec::new(),
+		stands: stands.iter().map(|stand| stand.to_string()).collect(),
+		input_order: None,
+	};
+
+	build(
+		&[(None, router); 3],
+		vec![block(0, &["A1-A12"]), block(1, &["A5", "B*"])],
+		Vec::new(),
+	)
+}
+
+fn park(aerodrome: &mut Aerodrome, pilots: &[(&str, Option<&str>)]) {
+	let aircraft = pilots.iter().map(|(callsign, _)| callsign.to_string());
+	let stands = (pilots.iter())
+		.filter_map(|(callsign, stand)| {
+			Some((callsign.to_string(), (*stand)?.to_string()))
+		})
+		.collect();
+	aerodrome.set_aircraft(aircraft.collect(), stands);
+}
+
+#[test]
+fn pilots_decode_from_callsigns_or_objects() {
+	let pilots = serde_json::from_str::<Vec<bars_protocol::Pilot>>(
+		r#"["AAA1", {"callsign": "BBB2", "stand": "A7"}, {"callsign": "CCC3"}]"#,
+	)
+	.unwrap();
+
+	let pilot = |callsign: &str, stand: Option<&str>| bars_protocol::Pilot {
+		callsign: callsign.into(),
+		stand: stand.map(Into::into),
+	};
+	assert_eq!(
+		pilots,
+		[
+			pilot("AAA1", None),
+			pilot("BBB2", Some("A7")),
+			pilot("CCC3", None),
+		]
+	);
+}
+
+#[test]
+fn stands_have_occupants_of_the_aircraft_on_them() {
+	let mut aerodrome = stands();
+	park(
+		&mut aerodrome,
+		&[("AAA1", None), ("BBB2", Some("A7")), ("CCC3", Some("A7"))],
+	);
+
+	// of two on one stand, the first by callsign is its occupant
+	assert_eq!(aerodrome.stand_occupant("A7"), Some("BBB2"));
+	assert_eq!(aerodrome.stand_occupant("A8"), None);
+
+	// aircraft without stands are still enabled
+	assert!(aerodrome.is_pilot_enabled("AAA1"));
+	assert!(aerodrome.is_pilot_enabled("CCC3"));
+	assert!(!aerodrome.is_pilot_enabled("DDD4"));
+}
+
+#[test]
+fn blocks_are_occupied_by_their_own_stands() {
+	let table: &[(&str, [bool; 2])] = &[
+		// within the range of the first block
+		("A7", [true, false]),
+		// within the range, but exactly a stand of the second block
+		("A5", [false, true]),
+		// by the glob of the second block
+		("B12", [false, true]),
+		// outside the range, which does not match leading zeros
+		("A13", [false, false]),
+		("A01", [false, false]),
+	];
+
+	for (stand, occupied) in table {
+		let mut aerodrome = stands();
+		park(&mut aerodrome, &[("AAA1", Some(stand))]);
+
+		let actual = [aerodrome.block_occupied(0), aerodrome.block_occupied(1)];
+		assert_eq!(&actual, occupied, "{stand}");
+	}
+}
+
+#[test]
+fn routing_on_to_occupied_stands_warns() {
+	let mut aerodrome = stands();
+	park(&mut aerodrome, &[("AAA1", Some("A7"))]);
+
+	aerodrome.set_block(1, route(1, 2)).unwrap();
+	assert!(aerodrome.take_messages().is_empty());
+
+	// the route is still set
+	aerodrome.set_block(0, route(0, 1)).unwrap();
+	assert_eq!(aerodrome.block_state(0), route(0, 1));
+	assert_eq!(
+		aerodrome.take_messages(),
+		["block B0 is routed on to stand A7, which AAA1 occupies"]
+	);
+
+	// and only routing warns
+	aerodrome.set_block(0, Relax).unwrap();
+	assert!(aerodrome.take_messages().is_empty());
+}
+
+/// Names the profile, a preset, a node and a holding point of a config in
+/// French and German.
+fn two_locales() -> Config {
+	let mut config = two_nodes();
+	config.profiles[0].presets.push(Preset {
+		name: "Open".into(),
+		includes: Vec::new(),
+		nodes: Vec::new(),
+		blocks: Vec::new(),
+	});
+	config.holding_points.push(HoldingPoint {
+		id: "A1".into(),
+		label: "Alpha 1".into(),
+		location: HoldingPointLocation::Nodes(0.into(), 1.into()),
+		position: None,
+	});
+
+	let strings = [
+		("profile.default", "fr", "Par défaut"),
+		("profile.default", "de", "Standard"),
+		("preset.Open", "fr", "Ouvert"),
+		("node.N0", "de", "Barre 0"),
+		("holding_point.A1", "fr", "Alpha un"),
+	];
+	let mut table = LocaleTable::default();
+	for (key, locale, string) in strings {
+		let strings = table.strings.entry(key.into()).or_default();
+		strings.insert(locale.into(), string.into());
+	}
+	config.strings = Some(table);
+	config
+}
+
+#[test]
+fn listings_follow_the_locale() {
+	let mut client = Client::new_offline();
+	client.set_locale("fr-FR");
+	client.load_local(two_locales()).unwrap();
+
+	let icao = "ZZZZ".to_string();
+	let names = |client: &Client| {
+		let aerodrome = client.aerodrome(&icao).unwrap();
+		let presets = aerodrome.presets();
+		[
+			aerodrome.profile_name(0).to_string(),
+			presets[0].name.clone(),
+			aerodrome.holding_point_label(0).to_string(),
+			aerodrome.node_tooltip(0, "{label}"),
+		]
+	};
+
+	// aerodromes loaded take the locale of the client
+	assert_eq!(names(&client), ["Par défaut", "Ouvert", "Alpha un", "N0"]);
+
+	// and follow it as it changes, falling back to the default names
+	client.set_locale("de");
+	assert_eq!(names(&client), ["Standard", "Open", "Alpha 1", "Barre 0"]);
+
+	client.set_locale("");
+	assert_eq!(names(&client), ["Default", "Open", "Alpha 1", "N0"]);
+}
+
+#[test]
+fn tooltips_fill_their_placeholders() {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::TimeSecs(45),
+	};
+	let block = Block {
+		id: "B0".into(),
+		nodes: vec![0.into()],
+		edges: Vec::new(),
+		non_routes: Vec::new(),
+		stands: Vec::new(),
+		input_order: None,
+	};
+	let mut aerodrome = build(&[(None, direct)], vec![block], Vec::new());
+
+	let node = "{label} stopbar {state} — resets {reset}";
+	assert_eq!(
+		aerodrome.node_tooltip(0, node),
+		"N0 stopbar on — resets 45 s",
+	);
+	aerodrome.set_node(0, false).unwrap();
+	assert_eq!(
+		aerodrome.node_tooltip(0, node),
+		"N0 stopbar off — resets 45 s",
+	);
+
+	let block = "{label}: {state}, resets {reset}";
+	assert_eq!(aerodrome.block_tooltip(0, block), "B0: clear, resets never");
+	aerodrome.set_block(0, Relax).unwrap();
+	assert_eq!(aerodrome.block_tooltip(0, block), "B0: relax, resets never");
+	aerodrome.set_block(0, route(0, 0)).unwrap();
+	assert_eq!(aerodrome.block_tooltip(0, block), "B0: route, resets never");
+
+	// anything else is left as it was
+	for text in ["{unknown} {label", "{{label}}", "}{", "plain", ""] {
+		let filled = text.replace("{label}", "N0");
+		assert_eq!(aerodrome.node_tooltip(0, text), filled, "{text}");
+	}
+}
+
+/// Builds an aerodrome of directly controlled nodes N0, N4 and N5, a router
+/// node N1 with a child N2, a fixed node N3 and blocks B0 and B1.
+fn selectable() -> Config {
+	let direct = NodeCondition::Direct {
+		reset: ResetCondition::None,
+	};
+	let router = NodeCondition::Router { sticky: false };
+	let fixed = NodeCondition::Fixed {
+		state: NodeState::On,
+	};
+
+	let blocks = (0..2)
+		.map(|i| Block {
+			id: format!("B{i}"),
+			nodes: Vec::new(),
+			edges: Vec::new(),
+			non_routes: Vec::new(),
+			stands: Vec::new(),
+			input_order: None,
+		})
+		.collect();
+
+	build(
+		&[
+			(None, direct),
+			(None, router),
+			(Some(1), router),
+			(None, fixed),
+			(None, direct),
+			(None, direct),
+		],
+		blocks,
+		Vec::new(),
+	)
+	.config
+}
+
+fn target(x: f32, y: f32) -> Target<Point> {
+	Target {
+		polygons: vec![vec![Point { x, y }]],
+		..Default::default()
+	}
+}
+
+#[test]
+fn selection_follows_input_order_then_position_then_id() {
+	use Selectable::{Block as B, Node as N};
+
+	// fixed nodes and router nodes with parents cannot be selected
+	let config = selectable();
+	let aerodrome = offline(config.clone());
+	assert_eq!(
+		aerodrome.selection_order(),
+		[B(0), B(1), N(0), N(1), N(4), N(5)],
+	);
+
+	// those on the map are from left to right, then top to bottom
+	let mut map = Map::default();
+	map.nodes.resize_with(6, Default::default);
+	map.blocks.resize_with(2, Default::default);
+	map.nodes[0].target = target(10.0, 0.0);
+	map.nodes[1].target = target(0.0, 10.0);
+	map.nodes[3].target = target(-10.0, 0.0);
+	map.nodes[4].target = target(0.0, 0.0);
+	map.blocks[0].target = target(5.0, 5.0);
+
+	let mut config = Config {
+		maps: vec![map],
+		..config
+	};
+	let aerodrome = offline(config.clone());
+	assert_eq!(
+		aerodrome.selection_order(),
+		[N(4), N(1), B(0), N(0), B(1), N(5)],
+	);
+
+	// but after those given an order
+	config.nodes[5].input_order = Some(0);
+	config.blocks[1].input_order = Some(1);
+	config.nodes[0].input_order = Some(2);
+
+	// and a profile which fixes a node
+	let mut profile = config.profiles[0].clone();
+	profile.id = "fixed".into();
+	profile.nodes[0] = NodeCondition::Fixed {
+		state: NodeState::Off,
+	};
+	config.profiles.push(profile);
+
+	let mut aerodrome = offline(config);
+	let order = [N(5), B(1), N(0), N(4), N(1), B(0)];
+	assert_eq!(aerodrome.selection_order(), order);
+
+	// which is cycled through either way
+	assert_eq!(aerodrome.next_selectable(None, false), Some(N(5)));
+	assert_eq!(aerodrome.next_selectable(None, true), Some(B(0)));
+	assert_eq!(aerodrome.next_selectable(Some(N(0)), false), Some(N(4)));
+	assert_eq!(aerodrome.next_selectable(Some(N(0)), true), Some(B(1)));
+	assert_eq!(aerodrome.next_selectable(Some(B(0)), false), Some(N(5)));
+	assert_eq!(aerodrome.next_selectable(Some(N(5)), true), Some(B(0)));
+	assert_eq!(aerodrome.next_selectable(Some(N(3)), false), Some(N(5)));
+
+	// nodes fixed by the profile drop out
+	aerodrome.set_profile(1).unwrap();
+	assert_eq!(aerodrome.selection_order(), [N(5), B(1), N(4), N(1), B(0)],);
+	assert_eq!(aerodrome.next_selectable(Some(N(0)), false), Some(N(5)));
+}
+
+fn node_states(aerodrome: &Aerodrome) -> Vec<bool> {
+	(0..aerodrome.nodes.len())
+		.map(|node| aerodrome.node_state(node))
+		.collect()
+}
+
+#[test]
+fn presets_merge_with_manual_changes_and_arm_timers() {
+	let mut config = timed(ConflictStrategy::default()).config;
+	config.profiles[0].presets.push(Preset {
+		name: "Departure".into(),
+		includes: Vec::new(),
+		nodes: Vec::new(),
+		blocks: vec![(0.into(), route(1, 2))],
+	});
+	let mut aerodrome = offline(config);
+	aerodrome.take_pending();
+
+	// a stop bar set by hand, then a preset, within the same tick
+	aerodrome.set_node(0, false).unwrap();
+	aerodrome.apply_preset(0).unwrap();
+
+	let (patch, _) = aerodrome.take_pending();
+	assert_eq!(patch.nodes, HashMap::from([("N0".into(), false)]));
+	assert_eq!(
+		patch.blocks,
+		HashMap::from([("B0".into(), aerodrome.bs_conf_to_ipc(&route(1, 2)))]),
+	);
+
+	// the block routed by the preset resets as if routed by hand, and the
+	// timer of the node it does not mention is kept
+	let timer = aerodrome.block_timer(0).unwrap();
+	assert!(timer > Duration::from_secs(59), "{timer:?}");
+	assert!(aerodrome.node_timer(0).is_some());
+
+	aerodrome.advance(Duration::from_secs(30));
+	assert_eq!(aerodrome.block_state(0), route(1, 2));
+
+	aerodrome.advance(Duration::from_secs(31));
+	assert_eq!(aerodrome.block_state(0), Clear);
+	assert_eq!(aerodrome.block_timer(0), None);
+
+	let (patch, _) = aerodrome.take_pending();
+	assert_eq!(patch.blocks["B0"], aerodrome.bs_conf_to_ipc(&Clear));
+}
+
+#[test]
+fn captured_presets_restore_the_state_captured() {
+	let mut config = selectable();
+	config.profiles[0].presets.push(Preset {
+		name: "Open".into(),
+		includes: Vec::new(),
+		nodes: Vec::new(),
+		blocks: Vec::new(),
+	});
+
+	let mut aerodrome = offline(config);
+	aerodrome.set_node(4, true).unwrap();
+	aerodrome.set_block(1, Relax).unwrap();
+	let nodes = node_states(&aerodrome);
+	let blocks = aerodrome.block_states();
+
+	// only directly controlled nodes are captured
+	let preset = aerodrome.capture_preset("Inspection");
+	let captured = (preset.nodes.iter())
+		.map(|(node, state)| (node.0, *state))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		captured,
+		[(0, NodeState::Off), (4, NodeState::On), (5, NodeState::Off)],
+	);
+	assert_eq!(preset.blocks.len(), 2);
+
+	assert_eq!(aerodrome.save_preset("Inspection"), 1);
+	let listed = (aerodrome.presets().into_iter())
+		.map(|preset| (preset.name, preset.local))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		listed,
+		[("Open".into(), false), ("Inspection".into(), true)],
+	);
+
+	let mutate = |aerodrome: &mut Aerodrome| {
+		aerodrome.set_node(0, true).unwrap();
+		aerodrome.set_node(4, false).unwrap();
+		aerodrome.set_block(0, Relax).unwrap();
+		aerodrome.set_block(1, Clear).unwrap();
+		assert_ne!(node_states(aerodrome), nodes);
+		assert_ne!(aerodrome.block_states(), blocks);
+	};
+
+	mutate(&mut aerodrome);
+	aerodrome.apply_preset(1).unwrap();
+	assert_eq!(node_states(&aerodrome), nodes);
+	assert_eq!(aerodrome.block_states(), blocks);
+
+	// as does the preset itself
+	mutate(&mut aerodrome);
+	aerodrome.apply_custom_preset(&preset).unwrap();
+	assert_eq!(node_states(&aerodrome), nodes);
+	assert_eq!(aerodrome.block_states(), blocks);
+
+	// capturing again under the name replaces it
+	mutate(&mut aerodrome);
+	assert_eq!(aerodrome.save_preset("Inspection"), 1);
+	aerodrome.apply_preset(0).unwrap();
+	aerodrome.apply_preset(1).unwrap();
+	assert!(!aerodrome.node_state(4));
+	assert_eq!(aerodrome.block_state(0), Relax);
+
+	assert!(!aerodrome.remove_preset(0));
+	assert!(aerodrome.remove_preset(1));
+	assert_eq!(aerodrome.presets().len(), 1);
+}
+
+/// Builds a chain of two blocks through a router node N1, sticky or not, with
+/// a second profile of the same conditions.
+fn through(sticky: bool) -> Aerodrome {
+	let blocks: &[(&[usize], Routes)] = &[(&[0, 1], &[]), (&[1, 2], &[])];
+	let mut config = routed(&[None; 3], blocks, &[]).config;
+	config.profiles[0].nodes[1] = NodeCondition::Router { sticky };
+
+	let mut profile = config.profiles[0].clone();
+	profile.id = "other".into();
+	config.profiles.push(profile);
+
+	offline(config)
+}
+
+/// A change, with whether the node is lit after it when not sticky and sticky.
+type StickyStep = (&'static str, fn(&mut Aerodrome), bool, bool);
+
+#[test]
+fn sticky_nodes_stay_lit_until_routed_through() {
+	fn relax(aerodrome: &mut Aerodrome) {
+		aerodrome.set_block(0, Relax).unwrap();
+		aerodrome.set_block(1, Relax).unwrap();
+	}
+
+	fn select(aerodrome: &mut Aerodrome) {
+		aerodrome.set_route((0, 2)).unwrap();
+	}
+
+	fn clear(aerodrome: &mut Aerodrome) {
+		aerodrome.set_block(1, Clear).unwrap();
+	}
+
+	fn receive_relaxed_profile(aerodrome: &mut Aerodrome) {
+		let patch = Patch {
+			profile: Some("other".into()),
+			blocks: (0..2)
+				.map(|i| (format!("B{i}"), IpcBlockState::Relax))
+				.collect(),
+			..Patch::default()
+		};
+		aerodrome.receive_patch(patch, None, None, false);
+	}
+
+	fn set_profile(aerodrome: &mut Aerodrome) {
+		aerodrome.set_profile(0).unwrap();
+	}
+
+	let steps: &[StickyStep] = &[
+		("initially", |_| {}, true, true),
+		("relaxed once lit", relax, false, true),
+		("routed through", select, false, false),
+		("relaxed once routed", relax, false, false),
+		("cleared", clear, true, true),
+		("relaxed once cleared", relax, false, true),
+		("routed again", select, false, false),
+		("route cleared", select, true, true),
+		("relaxed once the route cleared", relax, false, true),
+		(
+			"profile received relaxed",
+			receive_relaxed_profile,
+			false,
+			false,
+		),
+		("profile set", set_profile, true, true),
+		("relaxed once the profile set", relax, false, true),
+	];
+
+	let mut aerodromes = [through(false), through(true)];
+	for (step, change, plain, sticky) in steps {
+		for aerodrome in &mut aerodromes {
+			change(aerodrome);
+		}
+
+		let lit = aerodromes
+			.each_ref()
+			.map(|aerodrome| aerodrome.node_state(1));
+		assert_eq!(lit, [*plain, *sticky], "{step}");
+	}
+}
+
+/// Returns the aerodromes for which snapshots were requested since last taken.
+fn resyncs(rx: &mut UnboundedReceiver<Upstream>) -> Vec<String> {
+	std::iter::from_fn(|| rx.try_recv().ok())
+		.filter_map(|message| match message {
+			Upstream::Resync { icao } => Some(icao),
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn silent_aerodromes_are_reported_stale_alone() {
+	let mut other = two_nodes();
+	other.icao = "YYYY".into();
+	let (mut client, mut rx, tx) = connect_all(vec![two_nodes(), other]);
+	client.set_stale_threshold(Some(Duration::from_secs(60)));
+
+	let [zzzz, yyyy] = ["ZZZZ", "YYYY"].map(String::from);
+	let advance = |client: &mut Client, secs: u64| {
+		for icao in [&zzzz, &yyyy] {
+			let aerodrome = client.aerodrome_mut(icao).unwrap();
+			aerodrome.time_offset += Duration::from_secs(secs);
+		}
+	};
+	let data_age = |client: &Client, icao: &String| {
+		client.aerodrome(icao).unwrap().data_age().as_secs()
+	};
+
+	// only aircraft and patches are data, not the time nor pongs
+	advance(&mut client, 30);
+	tx.send(Downstream::Aircraft {
+		icao: zzzz.clone(),
+		aircraft: Vec::new(),
+		stands: HashMap::new(),
+	})
+	.unwrap();
+	tx.send(Downstream::Time {
+		icao: yyyy.clone(),
+		server_epoch_millis: epoch_millis(),
+	})
+	.unwrap();
+	tx.send(Downstream::Pong { seq: 1 }).unwrap();
+	assert!(client.tick().unwrap().is_empty());
+	assert_eq!(data_age(&client, &zzzz), 0);
+	assert_eq!(data_age(&client, &yyyy), 30);
+
+	advance(&mut client, 31);
+	let messages = client.tick().unwrap();
+	assert_eq!(messages.len(), 1);
+	assert!(
+		messages[0].starts_with("YYYY: no data received for 61 s"),
+		"{messages:?}",
+	);
+	assert_eq!(resyncs(&mut rx), ["YYYY"]);
+	assert_eq!(data_age(&client, &zzzz), 31);
+
+	// once
+	assert!(client.tick().unwrap().is_empty());
+	assert!(resyncs(&mut rx).is_empty());
+
+	tx.send(Downstream::Patch {
+		icao: yyyy.clone(),
+		patch: Box::default(),
+		seq: None,
+		first_seq: None,
+		snapshot: false,
+	})
+	.unwrap();
+	assert_eq!(client.tick().unwrap(), ["YYYY: receiving data again"]);
+	assert_eq!(data_age(&client, &yyyy), 0);
+}
+
+#[test]
+fn weather_suggests_profiles_for_the_procedures_in_force() {
+	let mut config = two_nodes();
+	let mut lvp = config.profiles[0].clone();
+	lvp.id = "lvp".into();
+	lvp.name = "Low visibility".into();
+	lvp.tags = vec!["lvp".into()];
+	config.profiles.push(lvp);
+
+	let (mut client, _rx, tx) = connect(config);
+	let icao = "ZZZZ".to_string();
+	let weather = |lvp: Option<bool>| Downstream::Weather {
+		icao: icao.clone(),
+		visibility_m: Some(400),
+		rvr_m: None,
+		lvp,
+		raw: None,
+	};
+
+	// nothing without being told whether they are in force
+	tx.send(weather(None)).unwrap();
+	assert!(client.tick().unwrap().is_empty());
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	assert_eq!(aerodrome.weather().unwrap().visibility_m, Some(400));
+	assert_eq!(aerodrome.suggested_profile(), None);
+
+	tx.send(weather(Some(true))).unwrap();
+	assert_eq!(
+		client.tick().unwrap(),
+		[
+			"ZZZZ: low visibility procedures are in force, so consider profile \
+			 Low visibility"
+		],
+	);
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	assert_eq!(aerodrome.suggested_profile(), Some(1));
+	assert_eq!(aerodrome.profile(), 0);
+
+	// which is cleared by taking it
+	aerodrome.set_profile(1).unwrap();
+	assert!(client.tick().unwrap().is_empty());
+	assert_eq!(client.aerodrome(&icao).unwrap().suggested_profile(), None);
+
+	// or by the procedures ending
+	tx.send(weather(Some(false))).unwrap();
+	assert_eq!(
+		client.tick().unwrap(),
+		[
+			"ZZZZ: low visibility procedures are no longer in force, so consider \
+			 profile Default"
+		],
+	);
+	assert_eq!(
+		client.aerodrome(&icao).unwrap().suggested_profile(),
+		Some(0)
+	);
+
+	tx.send(weather(Some(true))).unwrap();
+	assert!(client.tick().unwrap().is_empty());
+	assert_eq!(client.aerodrome(&icao).unwrap().suggested_profile(), None);
+}
+
+/// Returns the patches sent since last taken.
+fn patches(rx: &mut UnboundedReceiver<Upstream>) -> Vec<Patch> {
+	std::iter::from_fn(|| rx.try_recv().ok())
+		.filter_map(|message| match message {
+			Upstream::Patch { patch, .. } => Some(*patch),
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn patches_are_merged_within_the_interval_acknowledged() {
+	let (channel, ServerChannel::Mpsc { mut rx, tx }) = mpsc_pair() else {
+		unreachable!("channels are paired over mpsc");
+	};
+	let mut client = Client::new(channel, Some(20)).unwrap();
+	let Ok(Upstream::Init {
+		max_downstream_hz,
+		supports,
+	}) = rx.try_recv()
+	else {
+		panic!("the client should introduce itself first");
+	};
+	assert_eq!(max_downstream_hz, Some(20));
+	assert_eq!(supports, Capability::SUPPORTED);
+
+	tx.send(Downstream::InitAck {
+		min_patch_interval_ms: Some(500),
+	})
+	.unwrap();
+	client.tick().unwrap();
+	assert_eq!(client.patch_interval(), Duration::from_millis(500));
+
+	// which the aerodromes held follow
+	let (mut client, mut rx, tx) = connect(two_nodes());
+	tx.send(Downstream::InitAck {
+		min_patch_interval_ms: Some(500),
+	})
+	.unwrap();
+	client.tick().unwrap();
+
+	let icao = "ZZZZ".to_string();
+	let mut set = |client: &mut Client, state| {
+		client
+			.aerodrome_mut(&icao)
+			.unwrap()
+			.set_block(0, state)
+			.unwrap();
+		client.tick().unwrap();
+		patches(&mut rx)
+	};
+
+	assert_eq!(set(&mut client, route(0, 1)).len(), 1);
+	assert!(set(&mut client, Relax).is_empty());
+	assert!(set(&mut client, Clear).is_empty());
+
+	// the changes since are sent together once the interval has passed
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.time_offset += Duration::from_millis(500);
+	let sent = set(&mut client, Relax);
+	assert_eq!(sent.len(), 1);
+	assert!(matches!(sent[0].blocks["B0"], IpcBlockState::Relax));
+
+	// without a preference, every change is sent at once
+	tx.send(Downstream::InitAck {
+		min_patch_interval_ms: None,
+	})
+	.unwrap();
+	client.tick().unwrap();
+	assert_eq!(client.patch_interval(), Duration::ZERO);
+	assert_eq!(set(&mut client, Clear).len(), 1);
+	assert_eq!(set(&mut client, Relax).len(), 1);
+}
+
+/// Asserts that a latency measured is of a delay injected, allowing for the
+/// time a test takes otherwise.
+fn assert_delayed(latency: Duration, millis: u64) {
+	let delay = Duration::from_millis(millis);
+	assert!(latency >= delay, "{latency:?} for a delay of {delay:?}");
+	assert!(
+		latency < delay + Duration::from_millis(150),
+		"{latency:?} for a delay of {delay:?}",
+	);
+}
+
+#[test]
+fn latency_is_measured_through_delays() {
+	let (mut client, mut rx, tx) = connect(two_nodes());
+	client.set_ping_interval(Duration::ZERO);
+	let icao = "ZZZZ".to_string();
+	assert_eq!(client.latency(), None);
+
+	for (i, millis) in [40, 80].into_iter().enumerate() {
+		client.tick().unwrap();
+		let pings = std::iter::from_fn(|| rx.try_recv().ok())
+			.filter_map(|message| match message {
+				Upstream::Ping { seq } => Some(seq),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		// each tick pings again, abandoning those before
+		let seq = *pings.last().expect("a ping should be sent");
+
+		std::thread::sleep(Duration::from_millis(millis));
+		tx.send(Downstream::Pong { seq }).unwrap();
+
+		// as are the patches echoed
+		let aerodrome = client.aerodrome_mut(&icao).unwrap();
+		let state = if i == 0 { route(0, 1) } else { Clear };
+		aerodrome.set_block(0, state).unwrap();
+		client.tick().unwrap();
+		let [patch] = &patches(&mut rx)[..] else {
+			panic!("one patch should be sent");
+		};
+
+		std::thread::sleep(Duration::from_millis(millis));
+		tx.send(Downstream::Patch {
+			icao: icao.clone(),
+			patch: Box::new(patch.clone()),
+			seq: None,
+			first_seq: None,
+			snapshot: false,
+		})
+		.unwrap();
+		client.tick().unwrap();
+	}
+
+	let latency = client.latency().unwrap();
+	assert_delayed(latency.min, 40);
+	assert_delayed(latency.max, 80);
+	assert_delayed(latency.last, 80);
+	assert!(latency.min < latency.smoothed && latency.smoothed < latency.max);
+
+	let aerodrome = client.aerodrome(&icao).unwrap();
+	let latency = aerodrome.confirmation_latency().unwrap();
+	assert_delayed(latency.min, 40);
+	assert_delayed(latency.max, 80);
+	assert!(latency.min < latency.smoothed && latency.smoothed < latency.max);
+}
+
+#[test]
+fn config_hashes_are_checked_when_the_server_sends_them() {
+	let config = two_nodes();
+	let hash = config.config_hash();
+	let receive = |hash: Option<&str>| {
+		let (channel, ServerChannel::Mpsc { tx, rx: _rx }) = mpsc_pair() else {
+			unreachable!("channels are paired over mpsc");
+		};
+		let mut client = Client::new(channel, None).unwrap();
+
+		let data = config.encode().unwrap();
+		let hash = hash.map(String::from);
+		tx.send(Downstream::Config { data, hash }).unwrap();
+		let messages = client.tick().unwrap();
+
+		// the config of the server is kept either way
+		let aerodrome = client.aerodrome(&"ZZZZ".into()).unwrap();
+		assert_eq!(aerodrome.config().config_hash(), config.config_hash());
+		messages
+	};
+
+	assert!(receive(Some(&hash)).is_empty());
+	assert!(receive(None).is_empty());
+	assert_eq!(
+		receive(Some("0123")),
+		[
+			"ZZZZ: config decoded differently from the server's, so this plugin \
+			 may be out of date"
+		],
+	);
+}
+
+#[test]
+fn disconnecting_flushes_changes_then_releases_everything() {
+	let (mut client, mut rx, tx) = connect(two_nodes());
+	let icao = "ZZZZ".to_string();
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_block(0, route(0, 1)).unwrap();
+	client.tick().unwrap();
+	tx.send(Downstream::Lock {
+		icao: icao.clone(),
+		block: "B0".into(),
+		owner: "SELF".into(),
+		granted: true,
+	})
+	.unwrap();
+	client.tick().unwrap();
+	client.set_tracking("YYYY".into(), true).unwrap();
+	while rx.try_recv().is_ok() {}
+
+	// a change not yet sent
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	aerodrome.set_block(0, Relax).unwrap();
+
+	let summary = client.disconnect();
+	assert_eq!(summary, DisconnectSummary::default());
+	assert!(summary.is_complete());
+
+	let sent = std::iter::from_fn(|| rx.try_recv().ok())
+		.map(|message| match message {
+			Upstream::Patch { icao, patch } => {
+				assert!(matches!(patch.blocks["B0"], IpcBlockState::Relax));
+				format!("patch {icao}")
+			},
+			Upstream::Control { icao, control } => {
+				format!("control {icao} {control}")
+			},
+			Upstream::Release { icao, block } => format!("release {icao} {block}"),
+			Upstream::Track { icao, track } => format!("track {icao} {track}"),
+			Upstream::Bye => "bye".into(),
+			message => panic!("unexpected {message:?}"),
+		})
+		.collect::<Vec<_>>();
+	assert_eq!(
+		sent,
+		[
+			"track YYYY false",
+			"patch ZZZZ",
+			"control ZZZZ false",
+			"release ZZZZ B0",
+			"track ZZZZ false",
+			"bye",
+		],
+	);
+}
+
+#[test]
+fn edge_overrides_are_sent_and_received() {
+	let config = routed(&[None, None], &[(&[0, 1], &[])], &[(0, &[(0, 1)])]);
+	let (mut client, mut rx, tx) = connect(config.config);
+	let icao = "ZZZZ".to_string();
+
+	let mut set = |client: &mut Client, state| {
+		let aerodrome = client.aerodrome_mut(&icao).unwrap();
+		aerodrome.set_edge_override(0, state).unwrap();
+		client.tick().unwrap();
+
+		let sent = patches(&mut rx);
+		assert_eq!(sent.len(), 1);
+		assert_eq!(sent[0].edges, [("X0".into(), state)].into());
+	};
+	let edge = |client: &Client| {
+		let aerodrome = client.aerodrome(&icao).unwrap();
+		(aerodrome.edge_state(0), aerodrome.edge_override(0))
+	};
+
+	// forced on though the block is clear
+	assert_eq!(edge(&client), (false, None));
+	set(&mut client, Some(true));
+	assert_eq!(edge(&client), (true, Some(true)));
+	assert_eq!(
+		client.aerodrome(&icao).unwrap().edge_overrides(),
+		[(0, true)],
+	);
+
+	set(&mut client, None);
+	assert_eq!(edge(&client), (false, None));
+
+	let receive = |client: &mut Client, patch: Patch| {
+		tx.send(Downstream::Patch {
+			icao: icao.clone(),
+			patch: Box::new(patch),
+			seq: None,
+			first_seq: None,
+			snapshot: false,
+		})
+		.unwrap();
+		client.tick().unwrap();
+	};
+	let overridden = |state| Patch {
+		edges: [("X0".into(), state)].into(),
+		..Patch::default()
+	};
+
+	receive(&mut client, overridden(Some(true)));
+	assert_eq!(edge(&client), (true, Some(true)));
+	receive(&mut client, overridden(None));
+	assert_eq!(edge(&client), (false, None));
+
+	// and cleared by the profile changing
+	receive(&mut client, overridden(Some(true)));
+	receive(
+		&mut client,
+		Patch {
+			profile: Some("default".into()),
+			..Patch::default()
+		},
+	);
+	assert_eq!(edge(&client), (false, None));
+}
+
+#[test]
+fn silent_connections_suspend_control_until_heard_from() {
+	let (mut client, mut rx, tx) = connect(two_nodes());
+	client.set_ping_interval(Duration::from_millis(20));
+	client.set_connection_timeout(Some(Duration::from_millis(250)));
+	let icao = "ZZZZ".to_string();
+
+	let pings = |rx: &mut UnboundedReceiver<Upstream>| {
+		std::iter::from_fn(|| rx.try_recv().ok())
+			.filter_map(|message| match message {
+				Upstream::Ping { seq } => Some(seq),
+				_ => None,
+			})
+			.collect::<Vec<_>>()
+	};
+	let state = |client: &Client| client.aerodrome(&icao).unwrap().state();
+
+	// pings are sent at the interval, from the next
+	client.tick().unwrap();
+	assert!(pings(&mut rx).is_empty());
+	for _ in 0..2 {
+		std::thread::sleep(Duration::from_millis(25));
+		client.tick().unwrap();
+		assert_eq!(pings(&mut rx).len(), 1);
+		client.tick().unwrap();
+		assert!(pings(&mut rx).is_empty());
+	}
+
+	let health = client.connection_health();
+	assert!(!health.degraded);
+	assert_eq!(health.latency, None);
+
+	std::thread::sleep(Duration::from_millis(250));
+	assert_eq!(
+		client.tick().unwrap(),
+		[
+			"nothing heard from the server for 0.25 s, so control is suspended \
+			 until it is"
+		],
+	);
+	let health = client.connection_health();
+	assert!(health.degraded);
+	assert!(health.since_received >= Duration::from_millis(250));
+	assert_eq!(state(&client), ActivityState::Observing);
+
+	let aerodrome = client.aerodrome_mut(&icao).unwrap();
+	assert!(aerodrome.set_block(0, route(0, 1)).is_err());
+
+	// once
+	assert!(client.tick().unwrap().is_empty());
+
+	// anything heard recovers it
+	let seq = *pings(&mut rx).last().unwrap();
+	tx.send(Downstream::Pong { seq }).unwrap();
+	assert_eq!(
+		client.tick().unwrap(),
+		["connection to the server recovered"],
+	);
+	let health = client.connection_health();
+	assert!(!health.degraded);
+	assert!(health.since_received < Duration::from_millis(250));
+	assert!(health.latency.is_some());
+	assert_eq!(state(&client), ActivityState::Controlling);
+
+	// unless there is no timeout
+	client.set_connection_timeout(None);
+	std::thread::sleep(Duration::from_millis(250));
+	assert!(client.tick().unwrap().is_empty());
+	assert!(!client.connection_health().degraded);
+}
+
+#[test]
+fn subsets_behave_as_the_original_for_the_profiles_kept() {
+	let mut config = Lattice {
+		columns: 4,
+		rows: 3,
+		children: 2,
+		transparent: 0.3,
+		seed: 1,
+		..Lattice::default()
+	}
+	.generate();
+
+	// a child fixed in every profile, which nothing refers to, is still kept
+	// for the routes through its parent
+	let child = config.nodes.iter().position(|n| n.id == "N0.1").unwrap();
+	for profile in &mut config.profiles {
+		profile.nodes[child] = NodeCondition::Fixed {
+			state: NodeState::On,
+		};
+		for condition in &mut profile.edges {
+			if let EdgeCondition::Router { routes, .. } = condition {
+				routes.retain(|route| route.from.0 != child && route.to.0 != child);
+			}
+		}
+	}
+
+	// unlike a node of its own, or an edge without an element
+	config.nodes.push(Node {
+		id: "SPARE".into(),
+		scratchpad: None,
+		parent: None,
+		input_order: None,
+	});
+	config.elements.retain(|element| element.id != "L2");
+	for profile in &mut config.profiles {
+		profile.nodes.push(NodeCondition::Fixed {
+			state: NodeState::Off,
+		});
+		profile.edges[2] = EdgeCondition::Fixed {
+			state: EdgeState::On,
+		};
+	}
+
+	let subset = config.subset(&["profile0", "profile2"], true).unwrap();
+	let issues = subset.validate();
+	assert!(issues.is_empty(), "{issues:?}");
+	let kept = (subset.nodes.iter().map(|node| &node.id))
+		.chain(subset.edges.iter().map(|edge| &edge.id))
+		.filter(|id| ["N0.1", "SPARE", "X2"].contains(&id.as_str()))
+		.collect::<Vec<_>>();
+	assert_eq!(kept, ["N0.1"]);
+
+	// the nodes and edges of the subset, by their index in the original
+	let index = |ids: Vec<&String>, of: Vec<&String>| {
+		(ids.into_iter())
+			.map(|id| of.iter().position(|other| *other == id).unwrap())
+			.collect::<Vec<_>>()
+	};
+	let nodes = index(
+		subset.nodes.iter().map(|node| &node.id).collect(),
+		config.nodes.iter().map(|node| &node.id).collect(),
+	);
+	let edges = index(
+		subset.edges.iter().map(|edge| &edge.id).collect(),
+		config.edges.iter().map(|edge| &edge.id).collect(),
+	);
+	let original_state = |state: BlockState| match state {
+		BlockState::Route((a, b)) => route(nodes[a.0], nodes[b.0]),
+		state => state,
+	};
+
+	for (kept, profile) in [(0, 0), (1, 2)] {
+		let mut original = offline(config.clone());
+		original.set_profile(profile).unwrap();
+		let mut trimmed = offline(subset.clone());
+		trimmed.set_profile(kept).unwrap();
+
+		let mut rng = SyntheticRng::new(profile as u64);
+		for step in 0..200 {
+			let node = rng.below(nodes.len());
+			match rng.below(4) {
+				0 => {
+					let on = rng.chance(0.5);
+					trimmed.set_node(node, on).unwrap();
+					original.set_node(nodes[node], on).unwrap();
+				},
+				1 => {
+					let other = rng.below(nodes.len());
+					trimmed.set_route((node, other)).unwrap();
+					original.set_route((nodes[node], nodes[other])).unwrap();
+				},
+				2 => {
+					let block = rng.below(subset.blocks.len());
+					let state = if rng.chance(0.5) { Clear } else { Relax };
+					trimmed.set_block(block, state).unwrap();
+					original.set_block(block, state).unwrap();
+				},
+				_ => {
+					let preset = rng.below(trimmed.presets().len());
+					trimmed.apply_preset(preset).unwrap();
+					original.apply_preset(preset).unwrap();
+				},
+			}
+
+			for (i, node) in nodes.iter().enumerate() {
+				let state = trimmed.node_state(i);
+				assert_eq!(state, original.node_state(*node), "step {step}: node {i}");
+			}
+			for (i, edge) in edges.iter().enumerate() {
+				let state = trimmed.edge_state(i);
+				assert_eq!(state, original.edge_state(*edge), "step {step}: edge {i}");
+			}
+			let blocks = trimmed.block_states().into_iter().map(original_state);
+			assert!(
+				blocks.eq(original.block_states()),
+				"step {step}: blocks differ",
+			);
+		}
+	}
+}
@@ -1,6 +1,10 @@
+use crate::ipc::{Framing, DEFAULT_MAX_MESSAGE_SIZE};
+use crate::ConflictStrategy;
+
 use bars_config::{Aerodrome, Config, Loadable};
 
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Result;
 
@@ -18,6 +22,10 @@ fn default_server() -> String {
 	"https://v2.stopbars.com/".into()
 }
 
+fn default_max_message_size() -> usize {
+	DEFAULT_MAX_MESSAGE_SIZE
+}
+
 #[derive(Default, Deserialize, Serialize)]
 pub struct LocalConfig {
 	pub token: Option<String>,
@@ -25,6 +33,28 @@ pub struct LocalConfig {
 	pub port: u16,
 	#[serde(default = "default_server")]
 	pub server: String,
+	#[serde(default = "default_max_message_size")]
+	pub max_message_size: usize,
+	#[serde(default)]
+	pub raw_framing: bool,
+	#[serde(default)]
+	pub conflict_strategy: ConflictStrategy,
+	/// seconds without data before a controlled aerodrome is reported as
+	/// stale, with 0 never reporting, or the client's default if unset
+	#[serde(default)]
+	pub stale_data_secs: Option<u64>,
+	/// most messages a second for the client to process, with 0 or unset for
+	/// no limit
+	#[serde(default)]
+	pub max_downstream_hz: Option<u8>,
+	/// seconds between pings of the server, or the client's default if unset
+	#[serde(default)]
+	pub ping_interval_secs: Option<u64>,
+	/// seconds without any message before the connection is reported as
+	/// degraded and control suspended, with 0 never reporting, or the client's
+	/// default if unset
+	#[serde(default)]
+	pub connection_timeout_secs: Option<u64>,
 }
 
 impl LocalConfig {
@@ -37,6 +67,31 @@ impl LocalConfig {
 			Ok(Self::default())
 		}
 	}
+
+	/// Returns the stale threshold to set on the client, if not the default.
+	pub fn stale_threshold(&self) -> Option<Option<Duration>> {
+		let secs = self.stale_data_secs?;
+		Some((secs > 0).then(|| Duration::from_secs(secs)))
+	}
+
+	/// Returns the ping interval to set on the client, if not the default.
+	pub fn ping_interval(&self) -> Option<Duration> {
+		self.ping_interval_secs.map(Duration::from_secs)
+	}
+
+	/// Returns the connection timeout to set on the client, if not the
+	/// default.
+	pub fn connection_timeout(&self) -> Option<Option<Duration>> {
+		let secs = self.connection_timeout_secs?;
+		Some((secs > 0).then(|| Duration::from_secs(secs)))
+	}
+
+	pub fn framing(&self) -> Framing {
+		Framing {
+			max_message_size: self.max_message_size,
+			raw: self.raw_framing,
+		}
+	}
 }
 
 #[derive(Default, Deserialize, Serialize)]
@@ -1,6 +1,7 @@
 use crate::client::Client;
 use crate::config::{ConfigMapping, LocalConfig};
 use crate::ipc::Channel;
+#[cfg(windows)]
 use crate::screen::Screen;
 use crate::server::{ConnectOptions, Server};
 use crate::ConnectionState;
@@ -182,9 +183,27 @@ impl Context {
 		}
 	}
 
-	fn create_client(&mut self, channel: Channel) -> Option<()> {
-		match Client::new(channel) {
+	fn create_client(
+		&mut self,
+		channel: Channel,
+		config: Option<&LocalConfig>,
+	) -> Option<()> {
+		let max_downstream_hz = config.and_then(|config| config.max_downstream_hz);
+
+		match Client::new(channel, max_downstream_hz) {
 			Ok(mut client) => {
+				if let Some(config) = config {
+					client.set_conflict_strategy(config.conflict_strategy);
+					if let Some(threshold) = config.stale_threshold() {
+						client.set_stale_threshold(threshold);
+					}
+					if let Some(interval) = config.ping_interval() {
+						client.set_ping_interval(interval);
+					}
+					if let Some(timeout) = config.connection_timeout() {
+						client.set_connection_timeout(timeout);
+					}
+				}
 				for tracked in &self.tracked {
 					let _ = client.set_tracking(tracked.clone(), true);
 				}
@@ -215,21 +234,22 @@ impl Context {
 			return
 		};
 
-		let Some(token) = config.token else {
+		let Some(token) = config.token.clone() else {
 			self.add_message("unauthenticated".into());
 			return
 		};
 
 		let options = ConnectOptions {
-			server: config.server,
+			server: config.server.clone(),
 			token,
 			port: config.port,
+			framing: config.framing(),
 			callsign: callsign.into(),
 			controlling,
 		};
 
 		if let Some(channel) = self.create_server(Some(options)) {
-			if self.create_client(channel).is_some() {
+			if self.create_client(channel, Some(&config)).is_some() {
 				self.state = ConnectionState::ConnectedDirect;
 			}
 		}
@@ -248,9 +268,9 @@ impl Context {
 			return
 		};
 
-		match Channel::connect(config.port) {
+		match Channel::connect(config.port, config.framing()) {
 			Ok(channel) => {
-				if self.create_client(channel).is_some() {
+				if self.create_client(channel, Some(&config)).is_some() {
 					self.state = ConnectionState::ConnectedProxy;
 				}
 			},
@@ -274,8 +294,12 @@ impl Context {
 
 		self.state = ConnectionState::Poisoned;
 
+		// the local config is optional here, only changing the settings of the
+		// client
+		let config = self.load_config();
+
 		if let Some(channel) = self.create_server(None) {
-			if self.create_client(channel).is_some() {
+			if self.create_client(channel, config.as_ref()).is_some() {
 				self.state = ConnectionState::ConnectedLocal;
 			}
 		}
@@ -285,12 +309,19 @@ impl Context {
 	pub fn disconnect(&mut self) {
 		self.state = ConnectionState::Disconnected;
 
-		if let Some(server) = self.server.take() {
-			server.stop();
+		// the client says goodbye through the server, so goes first
+		if let Some(client) = self.client.take() {
+			let summary = client.disconnect();
+			if let Some(err) = &summary.error {
+				warn!(
+					"disconnect incomplete ({err}): unflushed {:?}, unreleased {:?}",
+					summary.unflushed, summary.unreleased,
+				);
+			}
 		}
 
-		if let Some(client) = self.client.take() {
-			client.disconnect();
+		if let Some(server) = self.server.take() {
+			server.stop();
 		}
 	}
 
@@ -299,6 +330,14 @@ impl Context {
 		self.state
 	}
 
+	/// Returns whether the client has heard nothing from the server for too
+	/// long, so that control is suspended.
+	#[instrument(level = "trace", skip(self))]
+	pub fn is_degraded(&self) -> bool {
+		(self.client.as_ref())
+			.is_some_and(|client| client.connection_health().degraded)
+	}
+
 	#[instrument(level = "trace", skip(self))]
 	pub fn next_message(&mut self) -> Option<String> {
 		self.messages.pop_front()
@@ -308,6 +347,7 @@ impl Context {
 		self.messages.push_back(message)
 	}
 
+	#[cfg(windows)]
 	pub fn create_screen(&mut self, geo: bool) -> Screen<'_> {
 		Screen::new(self, geo)
 	}
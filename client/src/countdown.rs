@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use bars_config::{CountdownFormat, Projectable, Ref, Style, Widget};
+
+/// How a countdown widget is drawn at one moment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountdownDisplay {
+	/// the remaining time as text, which is empty for a bar
+	pub text: String,
+	/// the remaining part of the reset time, from one down to zero
+	pub fraction: f32,
+	/// whether the remaining time is below the warning threshold
+	pub warning: bool,
+	pub style: Option<Ref<Style>>,
+}
+
+impl CountdownDisplay {
+	/// Returns how a countdown widget is drawn with the time remaining of the
+	/// whole reset time, as from [`Aerodrome::countdown`] and
+	/// [`Aerodrome::countdown_total`], or `None` if the widget is not a
+	/// countdown.
+	///
+	/// Whole seconds are rounded up, so that zero is only shown once the time
+	/// has run out.
+	///
+	/// [`Aerodrome::countdown`]: crate::Aerodrome::countdown
+	/// [`Aerodrome::countdown_total`]: crate::Aerodrome::countdown_total
+	pub fn new<T: Projectable>(
+		widget: &Widget<T>,
+		remaining: Duration,
+		total: Duration,
+	) -> Option<Self> {
+		let Widget::Countdown {
+			format,
+			warn_below_secs,
+			normal_style,
+			warn_style,
+			..
+		} = widget
+		else {
+			return None
+		};
+
+		let secs = remaining.as_millis().div_ceil(1000);
+		let text = match format {
+			CountdownFormat::MinutesSeconds => {
+				format!("{:02}:{:02}", secs / 60, secs % 60)
+			},
+			CountdownFormat::Seconds => secs.to_string(),
+			CountdownFormat::Bar => String::new(),
+		};
+
+		let fraction = if total.is_zero() {
+			0.0
+		} else {
+			(remaining.as_secs_f32() / total.as_secs_f32()).clamp(0.0, 1.0)
+		};
+
+		let warning = warn_below_secs
+			.is_some_and(|secs| remaining < Duration::from_secs(secs.into()));
+		let style = if warning {
+			warn_style.or(*normal_style)
+		} else {
+			*normal_style
+		};
+
+		Some(Self {
+			text,
+			fraction,
+			warning,
+			style,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bars_config::{CountdownCondition, Point};
+
+	fn countdown(
+		format: CountdownFormat,
+		warn_below_secs: Option<u16>,
+		warn_style: Option<usize>,
+	) -> Widget<Point> {
+		Widget::Countdown {
+			position: Point::default(),
+			size: 12.0,
+			condition: CountdownCondition::Node(0.into()),
+			format,
+			warn_below_secs,
+			normal_style: Some(1.into()),
+			warn_style: warn_style.map(Into::into),
+		}
+	}
+
+	fn display(widget: &Widget<Point>, remaining_ms: u64) -> CountdownDisplay {
+		let remaining = Duration::from_millis(remaining_ms);
+		CountdownDisplay::new(widget, remaining, Duration::from_secs(60)).unwrap()
+	}
+
+	#[test]
+	fn warnings_start_below_the_threshold() {
+		let widget = countdown(CountdownFormat::MinutesSeconds, Some(10), Some(2));
+
+		for (remaining_ms, warning) in [
+			(60_000, false),
+			(10_001, false),
+			(10_000, false),
+			(9_999, true),
+			(0, true),
+		] {
+			let shown = display(&widget, remaining_ms);
+			assert_eq!(shown.warning, warning, "{remaining_ms} ms");
+			assert_eq!(shown.style, Some(if warning { 2 } else { 1 }.into()));
+		}
+
+		// warnings without their own style keep the normal one
+		let widget = countdown(CountdownFormat::MinutesSeconds, Some(10), None);
+		let shown = display(&widget, 5_000);
+		assert!(shown.warning);
+		assert_eq!(shown.style, Some(1.into()));
+
+		// and without a threshold there is never a warning
+		let widget = countdown(CountdownFormat::MinutesSeconds, None, Some(2));
+		assert!(!display(&widget, 0).warning);
+	}
+
+	#[test]
+	fn text_rounds_up_to_whole_seconds() {
+		let table = [
+			(CountdownFormat::MinutesSeconds, 90_000, "01:30"),
+			(CountdownFormat::MinutesSeconds, 89_001, "01:30"),
+			(CountdownFormat::MinutesSeconds, 600_000, "10:00"),
+			(CountdownFormat::MinutesSeconds, 1, "00:01"),
+			(CountdownFormat::MinutesSeconds, 0, "00:00"),
+			(CountdownFormat::Seconds, 90_000, "90"),
+			(CountdownFormat::Seconds, 9_500, "10"),
+			(CountdownFormat::Seconds, 0, "0"),
+			(CountdownFormat::Bar, 30_000, ""),
+		];
+
+		for (format, remaining_ms, text) in table {
+			let widget = countdown(format, None, None);
+			assert_eq!(display(&widget, remaining_ms).text, text, "{format:?}");
+		}
+	}
+
+	#[test]
+	fn bars_shrink_with_the_remaining_part() {
+		let widget = countdown(CountdownFormat::Bar, None, None);
+		assert_eq!(display(&widget, 60_000).fraction, 1.0);
+		assert_eq!(display(&widget, 15_000).fraction, 0.25);
+		assert_eq!(display(&widget, 0).fraction, 0.0);
+
+		let zero = CountdownDisplay::new(&widget, Duration::ZERO, Duration::ZERO);
+		assert_eq!(zero.unwrap().fraction, 0.0);
+
+		let label = Widget::Label {
+			position: Point::default(),
+			size: 12.0,
+			holding_point: 0.into(),
+		};
+		assert_eq!(
+			CountdownDisplay::new(&label, Duration::ZERO, Duration::ZERO),
+			None,
+		);
+	}
+}
@@ -1,16 +1,22 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::fmt::{self, Debug, Formatter};
-use std::io::{ErrorKind, Write};
-use std::net::{Ipv4Addr, TcpStream};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, TcpStream};
+use std::time::Duration;
 
-use bars_protocol::Patch;
+use bars_protocol::{
+	decode_envelope_header, encode_envelope, Patch, ENVELOPE_HEADER_SIZE,
+};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 
-use ::bincode::config::{legacy, Configuration, Fixint, LittleEndian};
+use ::bincode::config::{legacy, Configuration, Fixint, Limit, LittleEndian};
+use ::bincode::error::DecodeError;
 use bincode::serde as bincode;
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -19,13 +25,265 @@ use tokio::net::TcpStream as AsyncTcpStream;
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-use tracing::trace;
+use tracing::{debug, trace};
 
-const BINCODE_CONFIG: Configuration<LittleEndian, Fixint> = legacy();
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 0x100_0000;
+
+// the limit bounds what is allocated for a string by its length before it is
+// read, as a corrupt length would otherwise be allocated in full
+const BINCODE_CONFIG: Configuration<
+	LittleEndian,
+	Fixint,
+	Limit<DEFAULT_MAX_MESSAGE_SIZE>,
+> = legacy().with_limit();
+
+const RAW_HEADER_SIZE: usize = 4;
+const VARIANT_SIZE: usize = 4;
+const READ_CHUNK_SIZE: usize = 0x1000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Framing {
+	pub max_message_size: usize,
+	/// Frame messages in the raw length-prefixed encoding used by earlier
+	/// versions, rather than in envelopes.
+	pub raw: bool,
+}
+
+impl Framing {
+	fn header_size(&self) -> usize {
+		if self.raw {
+			RAW_HEADER_SIZE
+		} else {
+			ENVELOPE_HEADER_SIZE
+		}
+	}
+
+	fn decode_header(&self, data: &[u8]) -> Option<(Option<u16>, usize)> {
+		if self.raw {
+			let header = data.first_chunk::<RAW_HEADER_SIZE>()?;
+			Some((None, u32::from_le_bytes(*header) as usize))
+		} else {
+			decode_envelope_header(data).map(|(tag, size)| (Some(tag), size))
+		}
+	}
+
+	fn check_size(&self, size: usize) -> Result<(), FrameError> {
+		if size > self.max_message_size {
+			Err(FrameError::Oversized {
+				size,
+				max: self.max_message_size,
+			})
+		} else {
+			Ok(())
+		}
+	}
+
+	fn encode<T: Message>(&self, message: &T) -> Result<Vec<u8>> {
+		let data = bincode::encode_to_vec(message, BINCODE_CONFIG)?;
+
+		if self.raw {
+			let size = u32::try_from(data.len())?;
+			Ok([&size.to_le_bytes()[..], &data].concat())
+		} else {
+			// the variant index leads the bincode encoding, and is moved into the
+			// envelope tag so that it can be read without decoding the body
+
+			let (variant, body) = data.split_at(VARIANT_SIZE);
+			let tag = u32::from_le_bytes(variant.try_into()?);
+			let tag = u16::try_from(tag)?;
+
+			encode_envelope(tag, body).ok_or_else(|| anyhow!("oversized message"))
+		}
+	}
+
+	/// Decodes a message body, returning `None` if the message is of an
+	/// unknown kind and should be skipped.
+	fn decode<T: Message>(
+		&self,
+		tag: Option<u16>,
+		body: &[u8],
+	) -> Result<Option<T>, FrameError> {
+		// missing data is zeroed, as it may be fields appended since an older
+		// peer, which then decode as absent
+		let (mut data, exact) = match tag {
+			Some(tag) if tag >= T::TAGS => {
+				debug!("skipping message with unknown tag {tag}");
+				return Ok(None)
+			},
+			// trailing data is permitted, as it may hold fields appended by a
+			// newer peer
+			Some(tag) => ([&(tag as u32).to_le_bytes()[..], body].concat(), false),
+			None => (body.to_vec(), true),
+		};
+
+		loop {
+			match bincode::decode_from_slice(&data, BINCODE_CONFIG) {
+				Ok((_, read)) if exact && read < body.len() => {
+					return Err(FrameError::Corrupt(DecodeError::Other(
+						"trailing bytes in frame",
+					)))
+				},
+				Ok((message, _)) => return Ok(Some(message)),
+				Err(DecodeError::UnexpectedEnd { additional })
+					if data.len() + additional <= self.max_message_size =>
+				{
+					data.resize(data.len() + additional, 0);
+				},
+				Err(err) => return Err(FrameError::Corrupt(err)),
+			}
+		}
+	}
+}
+
+impl Default for Framing {
+	fn default() -> Self {
+		Self {
+			max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+			raw: false,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+	Oversized { size: usize, max: usize },
+	Corrupt(DecodeError),
+}
+
+impl Display for FrameError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Oversized { size, max } => {
+				write!(f, "oversized frame ({size} bytes, maximum {max})")
+			},
+			Self::Corrupt(err) => write!(f, "corrupt frame: {err}"),
+		}
+	}
+}
+
+impl Error for FrameError {}
+
+/// A message which can be sent over a channel.
+///
+/// Envelope tags are the variant indices of the message enum, so any new
+/// variants must be added at the end, and `TAGS` updated to match. Fields may
+/// also be appended to a variant, so long as they decode from zeroes as their
+/// absence, as with `Option`, `Vec` and `bool`, which is how they are read
+/// from peers without them.
+pub trait Message: Serialize + DeserializeOwned {
+	const TAGS: u16;
+}
+
+/// Reassembles frames from a byte stream which may deliver them in arbitrary
+/// fragments.
+///
+/// Incomplete frames are retained until later data completes them. There is
+/// no attempt at resynchronisation: an oversized or undecodable frame means
+/// that the stream can no longer be trusted, so any error returned is fatal
+/// and the connection must be dropped. Messages of unknown kinds are skipped
+/// and counted.
+pub struct FrameBuffer {
+	buf: Vec<u8>,
+	framing: Framing,
+	skipped: usize,
+}
+
+impl FrameBuffer {
+	pub fn new(framing: Framing) -> Self {
+		Self {
+			buf: Vec::new(),
+			framing,
+			skipped: 0,
+		}
+	}
+
+	pub fn framing(&self) -> &Framing {
+		&self.framing
+	}
+
+	pub fn skipped(&self) -> usize {
+		self.skipped
+	}
+
+	pub fn extend(&mut self, data: &[u8]) {
+		self.buf.extend_from_slice(data);
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.buf.is_empty()
+	}
+
+	pub fn next<T: Message>(&mut self) -> Result<Option<T>, FrameError> {
+		loop {
+			let Some((tag, size)) = self.framing.decode_header(&self.buf) else {
+				return Ok(None)
+			};
+
+			self.framing.check_size(size)?;
+
+			let header_size = self.framing.header_size();
+			if self.buf.len() < header_size + size {
+				return Ok(None)
+			}
+
+			let message = self.framing.decode(tag, &self.buf[header_size..][..size]);
+			self.buf.drain(..header_size + size);
+
+			match message? {
+				Some(message) => return Ok(Some(message)),
+				None => self.skipped += 1,
+			}
+		}
+	}
+}
+
+/// A feature which a client advertises support for when it connects, so that
+/// the server need not send what it would ignore.
+///
+/// Encoded as its index, so that capabilities unknown to a peer can be read
+/// and disregarded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "u16", into = "u16")]
+pub enum Capability {
+	/// adopting the patch interval of [`Downstream::InitAck`]
+	InitAck,
+	/// weather reports
+	Weather,
+	Unknown(u16),
+}
+
+impl Capability {
+	/// Capabilities of this client.
+	pub const SUPPORTED: [Self; 2] = [Self::InitAck, Self::Weather];
+}
+
+impl From<u16> for Capability {
+	fn from(value: u16) -> Self {
+		match value {
+			0 => Self::InitAck,
+			1 => Self::Weather,
+			value => Self::Unknown(value),
+		}
+	}
+}
+
+impl From<Capability> for u16 {
+	fn from(value: Capability) -> Self {
+		match value {
+			Capability::InitAck => 0,
+			Capability::Weather => 1,
+			Capability::Unknown(value) => value,
+		}
+	}
+}
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Upstream {
-	Init,
+	Init {
+		/// most messages a second which the client will process, or unlimited
+		max_downstream_hz: Option<u8>,
+		supports: Vec<Capability>,
+	},
 	Track {
 		icao: String,
 		track: bool,
@@ -36,14 +294,39 @@ pub enum Upstream {
 	},
 	Patch {
 		icao: String,
-		patch: Patch,
+		/// boxed, being much larger than any other message
+		patch: Box<Patch>,
 	},
 	Scenery {
 		icao: String,
 		scenery: HashMap<String, bool>,
+		seq: u64,
+	},
+	Acquire {
+		icao: String,
+		block: String,
+	},
+	Release {
+		icao: String,
+		block: String,
+	},
+	/// Request for a [`Downstream::Pong`], to measure the round trip.
+	Ping {
+		seq: u64,
+	},
+	/// Notice that the client is disconnecting, after which it sends nothing.
+	Bye,
+	/// Request for the full state of an aerodrome, as a snapshot, after
+	/// patches were found missing.
+	Resync {
+		icao: String,
 	},
 }
 
+impl Message for Upstream {
+	const TAGS: u16 = 10;
+}
+
 impl Upstream {
 	pub fn icao(&self) -> Option<&String> {
 		Some(match self {
@@ -51,6 +334,9 @@ impl Upstream {
 			Self::Control { icao, .. } => icao,
 			Self::Patch { icao, .. } => icao,
 			Self::Scenery { icao, .. } => icao,
+			Self::Acquire { icao, .. } => icao,
+			Self::Release { icao, .. } => icao,
+			Self::Resync { icao } => icao,
 			_ => return None,
 		})
 	}
@@ -60,6 +346,8 @@ impl Upstream {
 pub enum Downstream {
 	Config {
 		data: Vec<u8>,
+		/// config hash of the aerodrome encoded, from servers which send it
+		hash: Option<String>,
 	},
 	Control {
 		icao: String,
@@ -67,29 +355,81 @@ pub enum Downstream {
 	},
 	Patch {
 		icao: String,
-		patch: Patch,
+		/// boxed, being much larger than any other message
+		patch: Box<Patch>,
+		/// sequence of the patch among those of the aerodrome, or of the last
+		/// of several merged, from servers which number them
+		seq: Option<u64>,
+		/// sequence of the first of several patches merged, whose numbers up to
+		/// `seq` are therefore not missing
+		first_seq: Option<u64>,
+		/// whether the patch is the full state of the aerodrome, replacing
+		/// rather than adding to that held
+		snapshot: bool,
 	},
 	Aircraft {
 		icao: String,
 		aircraft: Vec<String>,
+		/// stand of each aircraft known to have one, by callsign, from servers
+		/// which send them
+		stands: HashMap<String, String>,
 	},
 	Error {
 		icao: String,
 		message: Option<String>,
 		disconnect: bool,
 	},
+	Time {
+		icao: String,
+		server_epoch_millis: u64,
+	},
+	Lock {
+		icao: String,
+		block: String,
+		owner: String,
+		granted: bool,
+	},
+	SceneryAck {
+		icao: String,
+		seq: u64,
+	},
+	Weather {
+		icao: String,
+		visibility_m: Option<u32>,
+		rvr_m: Option<u32>,
+		lvp: Option<bool>,
+		raw: Option<String>,
+	},
+	/// Reply to [`Upstream::Init`] from servers which know of it.
+	InitAck {
+		/// shortest interval between patches which the server wants from the
+		/// client, if any
+		min_patch_interval_ms: Option<u32>,
+	},
+	Pong {
+		seq: u64,
+	},
+}
+
+impl Message for Downstream {
+	const TAGS: u16 = 11;
 }
 
 impl Downstream {
 	pub fn icao(&self) -> Cow<'_, str> {
 		match self {
-			Self::Config { data } => bars_config::Aerodrome::decode(&data)
+			Self::Config { data, .. } => bars_config::Aerodrome::decode(data)
 				.map(|aerodrome| Cow::Owned(aerodrome.icao))
 				.unwrap_or(Cow::Borrowed("")),
+			Self::InitAck { .. } | Self::Pong { .. } => Cow::Borrowed(""),
 			Self::Control { icao, .. }
 			| Self::Patch { icao, .. }
 			| Self::Aircraft { icao, .. }
-			| Self::Error { icao, .. } => Cow::Borrowed(&icao),
+			| Self::Error { icao, .. }
+			| Self::Time { icao, .. }
+			| Self::Lock { icao, .. }
+			| Self::SceneryAck { icao, .. }
+			| Self::Weather { icao, .. } => Cow::Borrowed(icao),
 		}
 	}
 }
@@ -111,14 +451,61 @@ pub enum Channel {
 		rx: UnboundedReceiver<Downstream>,
 		tx: UnboundedSender<Upstream>,
 	},
-	Tcp(TcpStream),
+	Tcp {
+		stream: TcpStream,
+		frames: FrameBuffer,
+	},
+	/// No server, with messages answered in place as a server would, so that
+	/// aerodromes loaded locally behave as if online.
+	Loopback { replies: VecDeque<Downstream> },
 }
 
 impl Channel {
-	pub fn connect(port: u16) -> Result<Self> {
+	pub fn loopback() -> Self {
+		Self::Loopback {
+			replies: VecDeque::new(),
+		}
+	}
+
+	pub fn is_loopback(&self) -> bool {
+		matches!(self, Self::Loopback { .. })
+	}
+
+	pub fn connect(port: u16, framing: Framing) -> Result<Self> {
 		let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))?;
 		stream.set_nonblocking(true)?;
-		Ok(Self::Tcp(stream))
+		Ok(Self::Tcp {
+			stream,
+			frames: FrameBuffer::new(framing),
+		})
+	}
+
+	pub fn skipped(&self) -> usize {
+		match self {
+			Self::Mpsc { .. } | Self::Loopback { .. } => 0,
+			Self::Tcp { frames, .. } => frames.skipped(),
+		}
+	}
+
+	/// Makes sends wait for the peer, for up to the timeout each, rather than
+	/// fail if it is not reading, so that final messages are not dropped.
+	pub fn set_send_timeout(&mut self, timeout: Duration) -> Result<()> {
+		if let Self::Tcp { stream, .. } = self {
+			stream.set_nonblocking(false)?;
+			stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+		}
+
+		Ok(())
+	}
+
+	/// Closes the channel, once anything sent has been written.
+	pub fn close(self) -> Result<()> {
+		if let Self::Tcp { mut stream, .. } = self {
+			stream.flush()?;
+			stream.shutdown(Shutdown::Both)?;
+		}
+
+		Ok(())
 	}
 
 	pub fn send(&mut self, message: Upstream) -> Result<()> {
@@ -128,16 +515,31 @@ impl Channel {
 			Self::Mpsc { tx, .. } => {
 				tx.send(message)?;
 			},
-			Self::Tcp(stream) => {
-				let data = bincode::encode_to_vec(&message, BINCODE_CONFIG)?;
-				stream.write_all(&(data.len() as u32).to_le_bytes())?;
+			Self::Tcp { stream, frames } => {
+				let framing = frames.framing();
+
+				// peers framing raw messages predate the fields of init, and would
+				// refuse them as trailing data
+				let data = if framing.raw && matches!(message, Upstream::Init { .. }) {
+					[&(VARIANT_SIZE as u32).to_le_bytes()[..], &[0; VARIANT_SIZE]]
+						.concat()
+				} else {
+					framing.encode(&message)?
+				};
+
 				stream.write_all(&data)?;
 			},
+			Self::Loopback { replies } => replies.extend(loopback_reply(message)),
 		}
 
 		Ok(())
 	}
 
+	/// Receives the next message, if one is available, without blocking.
+	///
+	/// Any error is fatal to the channel; in particular, a framing error is
+	/// never recovered from, as the remainder of the stream cannot be reliably
+	/// interpreted.
 	pub fn recv(&mut self) -> Result<Option<Downstream>> {
 		match self {
 			Self::Mpsc { rx, .. } => match rx.try_recv() {
@@ -148,29 +550,73 @@ impl Channel {
 				Err(TryRecvError::Empty) => Ok(None),
 				Err(_) => bail!("disconnected"),
 			},
-			Self::Tcp(stream) => {
-				let mut buf = [0];
-				match stream.peek(&mut buf) {
-					Ok(0) => return Ok(None),
-					Ok(_) => (),
-					Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(None),
-					Err(err) => return Err(err.into()),
-				}
+			Self::Tcp { stream, frames } => {
+				let mut buf = [0; READ_CHUNK_SIZE];
+
+				let message = loop {
+					if let Some(message) = frames.next()? {
+						break message
+					}
+
+					match stream.read(&mut buf) {
+						Ok(0) if frames.is_empty() => bail!("disconnected"),
+						Ok(0) => bail!("disconnected mid-message"),
+						Ok(n) => frames.extend(&buf[..n]),
+						Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(None),
+						Err(err) if err.kind() == ErrorKind::Interrupted => (),
+						Err(err) => return Err(err.into()),
+					}
+				};
 
-				let message = bincode::decode_from_std_read(stream, BINCODE_CONFIG)?;
 				trace!("cch rx: {:?}", HideConfig(&message));
 				Ok(Some(message))
 			},
+			Self::Loopback { replies } => Ok(replies.pop_front()),
 		}
 	}
 }
 
+/// Answers a message as a server would if it were the only client, echoing
+/// changes and granting whatever is asked for.
+fn loopback_reply(message: Upstream) -> Option<Downstream> {
+	Some(match message {
+		Upstream::Init { .. } => Downstream::InitAck {
+			min_patch_interval_ms: None,
+		},
+		Upstream::Control { icao, control } => {
+			Downstream::Control { icao, control }
+		},
+		Upstream::Patch { icao, patch } => Downstream::Patch {
+			icao,
+			patch,
+			seq: None,
+			first_seq: None,
+			snapshot: false,
+		},
+		Upstream::Scenery { icao, seq, .. } => Downstream::SceneryAck { icao, seq },
+		Upstream::Acquire { icao, block } => Downstream::Lock {
+			icao,
+			block,
+			owner: String::new(),
+			granted: true,
+		},
+		Upstream::Ping { seq } => Downstream::Pong { seq },
+		Upstream::Track { .. }
+		| Upstream::Release { .. }
+		| Upstream::Resync { .. }
+		| Upstream::Bye => return None,
+	})
+}
+
 pub enum ServerChannel {
 	Mpsc {
 		rx: UnboundedReceiver<Upstream>,
 		tx: UnboundedSender<Downstream>,
 	},
-	Tcp(AsyncTcpStream),
+	Tcp {
+		stream: AsyncTcpStream,
+		framing: Framing,
+	},
 }
 
 impl ServerChannel {
@@ -191,10 +637,10 @@ impl ServerChannel {
 
 	async fn send_tcp<T: AsyncWriteExt + Unpin>(
 		tx: &mut T,
+		framing: &Framing,
 		message: Downstream,
 	) -> Result<()> {
-		let data = bincode::encode_to_vec(&message, BINCODE_CONFIG)?;
-		tx.write_all(&data).await?;
+		tx.write_all(&framing.encode(&message)?).await?;
 		Ok(())
 	}
 
@@ -215,14 +661,25 @@ impl ServerChannel {
 		}
 	}
 
-	async fn recv_tcp<T: AsyncReadExt + Unpin>(rx: &mut T) -> Result<Upstream> {
-		let n = rx.read_u32_le().await?;
-		if n > 0x100_0000 {
-			bail!("oversized packet");
-		} else {
-			let mut buf = vec![0; n as usize];
-			rx.read_exact(&mut buf).await?;
-			Ok(bincode::decode_from_slice(&buf, BINCODE_CONFIG)?.0)
+	async fn recv_tcp<T: AsyncReadExt + Unpin>(
+		rx: &mut T,
+		framing: &Framing,
+		skipped: &mut usize,
+	) -> Result<Upstream> {
+		loop {
+			let mut header = vec![0; framing.header_size()];
+			rx.read_exact(&mut header).await?;
+
+			let (tag, size) = framing.decode_header(&header).unwrap();
+			framing.check_size(size)?;
+
+			let mut body = vec![0; size];
+			rx.read_exact(&mut body).await?;
+
+			match framing.decode(tag, &body)? {
+				Some(message) => return Ok(message),
+				None => *skipped += 1,
+			}
 		}
 	}
 
@@ -232,11 +689,15 @@ impl ServerChannel {
 				ServerChannelReadHalf::Mpsc(rx),
 				ServerChannelWriteHalf::Mpsc(tx),
 			),
-			Self::Tcp(stream) => {
+			Self::Tcp { stream, framing } => {
 				let (rx, tx) = stream.into_split();
 				(
-					ServerChannelReadHalf::Tcp(rx),
-					ServerChannelWriteHalf::Tcp(tx),
+					ServerChannelReadHalf::Tcp {
+						rx,
+						framing,
+						skipped: 0,
+					},
+					ServerChannelWriteHalf::Tcp(tx, framing),
 				)
 			},
 		}
@@ -245,16 +706,24 @@ impl ServerChannel {
 
 pub enum ServerChannelReadHalf {
 	Mpsc(UnboundedReceiver<Upstream>),
-	Tcp(OwnedReadHalf),
+	Tcp {
+		rx: OwnedReadHalf,
+		framing: Framing,
+		skipped: usize,
+	},
 }
 
 impl ServerChannelReadHalf {
 	pub async fn recv(&mut self) -> Result<Upstream> {
 		let message = match self {
 			Self::Mpsc(rx) => ServerChannel::recv_mpsc(rx).await,
-			Self::Tcp(rx) => {
+			Self::Tcp {
+				rx,
+				framing,
+				skipped,
+			} => {
 				rx.readable().await?;
-				ServerChannel::recv_tcp(rx).await
+				ServerChannel::recv_tcp(rx, framing, skipped).await
 			},
 		}?;
 		trace!("sch rx: {message:?}");
@@ -264,7 +733,7 @@ impl ServerChannelReadHalf {
 
 pub enum ServerChannelWriteHalf {
 	Mpsc(UnboundedSender<Downstream>),
-	Tcp(OwnedWriteHalf),
+	Tcp(OwnedWriteHalf, Framing),
 }
 
 impl ServerChannelWriteHalf {
@@ -273,7 +742,9 @@ impl ServerChannelWriteHalf {
 
 		match self {
 			Self::Mpsc(tx) => ServerChannel::send_mpsc(tx, message).await,
-			Self::Tcp(tx) => ServerChannel::send_tcp(tx, message).await,
+			Self::Tcp(tx, framing) => {
+				ServerChannel::send_tcp(tx, framing, message).await
+			},
 		}
 	}
 }
@@ -287,3 +758,273 @@ pub fn mpsc_pair() -> (Channel, ServerChannel) {
 		ServerChannel::Mpsc { rx: urx, tx: dtx },
 	)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bars_config::SyntheticRng;
+
+	fn messages() -> Vec<Downstream> {
+		vec![
+			Downstream::Control {
+				icao: "EGLL".into(),
+				control: true,
+			},
+			Downstream::Patch {
+				icao: "EGLL".into(),
+				patch: Box::default(),
+				seq: Some(3),
+				first_seq: None,
+				snapshot: false,
+			},
+			Downstream::Error {
+				icao: "EGKK".into(),
+				message: Some("error".into()),
+				disconnect: false,
+			},
+			Downstream::Pong { seq: 7 },
+		]
+	}
+
+	fn framings() -> [Framing; 2] {
+		[
+			Framing::default(),
+			Framing {
+				raw: true,
+				..Framing::default()
+			},
+		]
+	}
+
+	fn encoded(framing: &Framing, messages: &[Downstream]) -> Vec<u8> {
+		(messages.iter())
+			.flat_map(|message| framing.encode(message).unwrap())
+			.collect()
+	}
+
+	/// Feeds the data to a buffer in chunks of the sizes given, in turn and
+	/// repeating, returning every message decoded or the first error.
+	fn feed(
+		framing: Framing,
+		data: &[u8],
+		mut chunk: impl FnMut() -> usize,
+	) -> Result<Vec<Downstream>, FrameError> {
+		let mut frames = FrameBuffer::new(framing);
+		let mut messages = Vec::new();
+		let mut rest = data;
+
+		while !rest.is_empty() {
+			let (next, after) = rest.split_at(chunk().clamp(1, rest.len()));
+			frames.extend(next);
+			rest = after;
+
+			while let Some(message) = frames.next()? {
+				messages.push(message);
+			}
+		}
+
+		Ok(messages)
+	}
+
+	fn debug(messages: &[Downstream]) -> Vec<String> {
+		messages.iter().map(|m| format!("{m:?}")).collect()
+	}
+
+	#[test]
+	fn frames_split_anywhere_are_reassembled() {
+		let messages = messages();
+
+		for framing in framings() {
+			let data = encoded(&framing, &messages);
+
+			for size in 1..=data.len() {
+				let decoded = feed(framing, &data, || size).unwrap();
+				assert_eq!(debug(&decoded), debug(&messages), "chunks of {size}");
+			}
+
+			let mut rng = SyntheticRng::new(0);
+			for _ in 0..250 {
+				let decoded = feed(framing, &data, || rng.below(16)).unwrap();
+				assert_eq!(debug(&decoded), debug(&messages));
+			}
+		}
+	}
+
+	#[test]
+	fn truncated_frames_wait_for_more() {
+		for framing in framings() {
+			let data = framing.encode(&messages()[1]).unwrap();
+
+			for len in 0..data.len() {
+				let mut frames = FrameBuffer::new(framing);
+				frames.extend(&data[..len]);
+				assert!(frames.next::<Downstream>().unwrap().is_none(), "{len}");
+
+				frames.extend(&data[len..]);
+				assert!(frames.next::<Downstream>().unwrap().is_some(), "{len}");
+				assert!(frames.is_empty());
+			}
+		}
+	}
+
+	#[test]
+	fn oversized_frames_are_refused_from_their_header() {
+		for framing in framings() {
+			let framing = Framing {
+				max_message_size: 0x100,
+				..framing
+			};
+
+			let mut data = framing.encode(&messages()[0]).unwrap();
+			let size = 0x101u32.to_le_bytes();
+			let at = framing.header_size() - size.len();
+			data[at..framing.header_size()].copy_from_slice(&size);
+
+			// the body is never waited for
+			let mut frames = FrameBuffer::new(framing);
+			frames.extend(&data[..framing.header_size()]);
+			assert!(matches!(
+				frames.next::<Downstream>(),
+				Err(FrameError::Oversized {
+					size: 0x101,
+					max: 0x100,
+				}),
+			));
+		}
+	}
+
+	#[test]
+	fn corrupt_frames_are_errors() {
+		for framing in framings() {
+			// control, ending with a bool
+			let mut data = framing.encode(&messages()[0]).unwrap();
+			*data.last_mut().unwrap() = 2;
+
+			let err = feed(framing, &data, || 1).unwrap_err();
+			assert!(matches!(err, FrameError::Corrupt(_)), "{err}");
+		}
+
+		// raw frames have no tag to skip a message of an unknown kind by
+		let framing = framings()[1];
+		let mut data = framing.encode(&messages()[0]).unwrap();
+		data[RAW_HEADER_SIZE..][..VARIANT_SIZE].fill(0xff);
+
+		let err = feed(framing, &data, || 1).unwrap_err();
+		assert!(matches!(err, FrameError::Corrupt(_)), "{err}");
+	}
+
+	#[test]
+	fn random_streams_never_panic() {
+		let mut rng = SyntheticRng::new(0);
+
+		for framing in framings() {
+			let framing = Framing {
+				max_message_size: 0x1000,
+				..framing
+			};
+			let valid = encoded(&framing, &messages());
+
+			for _ in 0..250 {
+				let mut data = match rng.below(3) {
+					// noise
+					0 => (0..rng.below(0x100))
+						.map(|_| rng.next_u64() as u8)
+						.collect(),
+					// valid messages cut short
+					1 => valid[..rng.below(valid.len())].to_vec(),
+					// valid messages with bytes changed
+					_ => valid.clone(),
+				};
+				for _ in 0..rng.below(4) {
+					if !data.is_empty() {
+						let i = rng.below(data.len());
+						data[i] = rng.next_u64() as u8;
+					}
+				}
+
+				let _ = feed(framing, &data, || rng.below(64));
+			}
+		}
+	}
+
+	#[test]
+	fn messages_of_unknown_kinds_are_skipped_and_counted() {
+		let messages = messages();
+		let framing = Framing::default();
+
+		let mut data = encoded(&framing, &messages[..2]);
+		data
+			.extend(encode_envelope(Downstream::TAGS, b"from a newer peer").unwrap());
+		data.extend(encoded(&framing, &messages[2..]));
+
+		let mut frames = FrameBuffer::new(framing);
+		frames.extend(&data);
+		let mut decoded = Vec::new();
+		while let Some(message) = frames.next::<Downstream>().unwrap() {
+			decoded.push(message);
+		}
+
+		assert_eq!(debug(&decoded), debug(&messages));
+		assert_eq!(frames.skipped(), 1);
+	}
+
+	#[test]
+	fn bodies_from_other_versions_decode() {
+		let framing = Framing::default();
+		let message = Downstream::Error {
+			icao: "EGLL".into(),
+			message: None,
+			disconnect: false,
+		};
+		let data = framing.encode(&message).unwrap();
+		let (tag, size) = decode_envelope_header(&data).unwrap();
+		let body = &data[ENVELOPE_HEADER_SIZE..];
+		assert_eq!(body.len(), size);
+
+		// fields appended since are ignored, and those missing from an older
+		// peer are absent
+		let newer = [body, b"appended"].concat();
+		let older = &body[..body.len() - 2];
+		for body in [&newer[..], older] {
+			let decoded = framing.decode::<Downstream>(Some(tag), body).unwrap();
+			assert_eq!(format!("{:?}", decoded.unwrap()), format!("{message:?}"));
+		}
+	}
+
+	#[test]
+	fn raw_frames_must_be_exact() {
+		let framing = framings()[1];
+		let data = framing.encode(&messages()[0]).unwrap();
+		let body = &data[RAW_HEADER_SIZE..];
+
+		assert!(framing.decode::<Downstream>(None, body).unwrap().is_some());
+		assert!(matches!(
+			framing.decode::<Downstream>(None, &[body, &[0]].concat()),
+			Err(FrameError::Corrupt(_)),
+		));
+	}
+
+	#[tokio::test]
+	async fn servers_skip_and_count_messages_of_unknown_kinds() {
+		let framing = Framing::default();
+		let ping = |seq| framing.encode(&Upstream::Ping { seq }).unwrap();
+
+		let data = [
+			ping(1),
+			encode_envelope(Upstream::TAGS, b"from a newer peer").unwrap(),
+			ping(2),
+		]
+		.concat();
+
+		let mut rx = data.as_slice();
+		let mut skipped = 0;
+		for expected in [1, 2] {
+			let message = ServerChannel::recv_tcp(&mut rx, &framing, &mut skipped)
+				.await
+				.unwrap();
+			assert!(matches!(message, Upstream::Ping { seq } if seq == expected));
+		}
+		assert_eq!(skipped, 1);
+	}
+}
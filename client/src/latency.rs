@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// weight of each new sample in the smoothed latency
+const SMOOTHING: f64 = 0.125;
+/// number of recent samples over which the extremes are taken
+const WINDOW: usize = 16;
+
+/// Summary of recently measured latencies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatencyStats {
+	/// exponentially weighted moving average of all samples
+	pub smoothed: Duration,
+	/// least of the recent samples
+	pub min: Duration,
+	/// greatest of the recent samples
+	pub max: Duration,
+	/// the most recent sample
+	pub last: Duration,
+}
+
+/// Estimator of latency from samples, which is cheap to update.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LatencyEstimator {
+	smoothed: Option<f64>,
+	recent: VecDeque<Duration>,
+}
+
+impl LatencyEstimator {
+	pub fn record(&mut self, sample: Duration) {
+		let secs = sample.as_secs_f64();
+		self.smoothed = Some(match self.smoothed {
+			Some(smoothed) => smoothed + (secs - smoothed) * SMOOTHING,
+			None => secs,
+		});
+
+		if self.recent.len() == WINDOW {
+			self.recent.pop_front();
+		}
+		self.recent.push_back(sample);
+	}
+
+	/// Returns the statistics of the samples, or `None` if there are none.
+	pub fn stats(&self) -> Option<LatencyStats> {
+		Some(LatencyStats {
+			smoothed: Duration::from_secs_f64(self.smoothed?),
+			min: *self.recent.iter().min()?,
+			max: *self.recent.iter().max()?,
+			last: *self.recent.back()?,
+		})
+	}
+}
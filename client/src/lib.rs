@@ -1,14 +1,32 @@
+// without the interface of the plugin, most of the crate is reached only by
+// tests and tools
+#![cfg_attr(not(windows), allow(dead_code))]
+
+#[cfg(windows)]
 mod api;
 mod client;
 mod config;
 mod context;
+mod countdown;
 mod ipc;
+mod latency;
+#[cfg(windows)]
 mod screen;
 mod server;
+mod timers;
 
 use serde::{Deserialize, Serialize};
 
+#[cfg(windows)]
 pub use api::*;
+#[doc(hidden)]
+pub use client::{
+	Aerodrome, Client, ConnectionHealth, ControlError, DisplayState, PresetInfo,
+	RouteActivation, RouteInfo, Selectable, TimerEntity, TimerInfo, TimerOrigin,
+	WeatherReport,
+};
+pub use countdown::CountdownDisplay;
+pub use latency::LatencyStats;
 
 #[derive(
 	Clone,
@@ -50,6 +68,32 @@ pub enum ActivityState {
 	Controlling,
 }
 
+/// How a patch from the server is applied to an entity with a local change
+/// the server has yet to confirm, and which differs from the patch.
+#[derive(
+	Clone,
+	Copy,
+	Debug,
+	Default,
+	Hash,
+	PartialEq,
+	Eq,
+	PartialOrd,
+	Ord,
+	Deserialize,
+	Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+	/// the local change is discarded, along with its reset timer
+	ServerWins,
+	/// the local change is kept until confirmed or superseded
+	LocalWins,
+	/// as [`ConflictStrategy::ServerWins`], with a message to the user
+	#[default]
+	ServerWinsNotify,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct ViewportGeo {
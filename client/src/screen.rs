@@ -1,4 +1,4 @@
-use crate::client::Aerodrome;
+use crate::client::{Aerodrome, ControlError};
 use crate::context::Context;
 use crate::{ActivityState, ClickType, ViewportGeo, ViewportNonGeo};
 
@@ -6,9 +6,10 @@ use std::fmt::Debug;
 use std::time::{Duration, Instant};
 
 use bars_config::{
-	BlockDisplay, BlockState, Color, EdgeCondition, EdgeDisplay, EdgeState,
-	FillStyle, Geo, GeoPoint, Map, NodeCondition, NodeDisplay, NodeState, Path,
-	Point, Projectable, StrokeCap, StrokeJoin, StrokeStyle, View,
+	Blink, BlockDisplay, BlockState, Color, EdgeCondition, EdgeDisplay,
+	EdgeState, FillStyle, Geo, GeoPoint, Map, NodeCondition, NodeDisplay,
+	NodeState, Path, Point, Projectable, StrokeCap, StrokeJoin, StrokeStyle,
+	View,
 };
 
 use tracing::{trace, warn};
@@ -30,6 +31,7 @@ struct Style {
 	brush: HBRUSH,
 	pen: HPEN,
 	filled: bool,
+	blink: Option<Blink>,
 }
 
 impl Style {
@@ -59,24 +61,55 @@ impl Style {
 
 		let pen = match style.stroke_style {
 			StrokeStyle::None => HPEN(Gdi::GetStockObject(Gdi::NULL_PEN).0),
-			StrokeStyle::Dash(dash) => Gdi::CreatePen(
-				match dash {
+			StrokeStyle::Dash(dash) => {
+				let dash = match dash {
 					0 => Gdi::PS_SOLID,
 					1 => Gdi::PS_DASH,
 					2 => Gdi::PS_DOT,
 					3 => Gdi::PS_DASHDOT,
 					4 => Gdi::PS_DASHDOTDOT,
 					_ => Gdi::PS_SOLID,
-				},
-				f32::from(style.stroke_width).ceil() as i32,
-				color(style.stroke_color),
-			),
+				};
+				let width = f32::from(style.stroke_width).ceil() as i32;
+
+				// pens of the default round caps and joins are kept cosmetic, so that
+				// thin lines are drawn as before
+				if style.stroke_cap == StrokeCap::ROUND
+					&& style.stroke_join == StrokeJoin::ROUND
+				{
+					Gdi::CreatePen(dash, width, color(style.stroke_color))
+				} else {
+					let cap = match style.stroke_cap {
+						StrokeCap::SQUARE => Gdi::PS_ENDCAP_SQUARE,
+						StrokeCap::BUTT => Gdi::PS_ENDCAP_FLAT,
+						_ => Gdi::PS_ENDCAP_ROUND,
+					};
+					let join = match style.stroke_join {
+						StrokeJoin::BEVEL => Gdi::PS_JOIN_BEVEL,
+						StrokeJoin::MITER => Gdi::PS_JOIN_MITER,
+						_ => Gdi::PS_JOIN_ROUND,
+					};
+					let brush = Gdi::LOGBRUSH {
+						lbStyle: Gdi::BS_SOLID,
+						lbColor: color(style.stroke_color),
+						lbHatch: 0,
+					};
+
+					Gdi::ExtCreatePen(
+						Gdi::PS_GEOMETRIC | dash | cap | join,
+						width as u32,
+						&brush,
+						None,
+					)
+				}
+			},
 		};
 
 		Self {
 			brush,
 			pen,
 			filled: style.fill_style != FillStyle::None,
+			blink: style.blink,
 		}
 	}
 
@@ -199,11 +232,8 @@ impl Screen<'_> {
 		self
 			.data()
 			.map(|aerodrome| {
-				aerodrome
-					.config()
-					.profiles
-					.iter()
-					.map(|profile| profile.name.clone())
+				(0..aerodrome.config().profiles.len())
+					.map(|i| aerodrome.profile_name(i).to_string())
 					.collect()
 			})
 			.unwrap_or(Vec::new())
@@ -225,10 +255,8 @@ impl Screen<'_> {
 		self
 			.data()
 			.map(|aerodrome| {
-				aerodrome.config().profiles[aerodrome.profile()]
-					.presets
-					.iter()
-					.map(|preset| preset.name.clone())
+				(aerodrome.presets().into_iter())
+					.map(|preset| preset.name)
 					.collect()
 			})
 			.unwrap_or(Vec::new())
@@ -236,8 +264,12 @@ impl Screen<'_> {
 
 	// bug: if profile changes between preset() and apply_preset(...), wrong
 	// preset will be applied
-	pub fn apply_preset(&mut self, i: usize) {
-		self.data_mut().map(|aerodrome| aerodrome.apply_preset(i));
+	pub fn apply_preset(&mut self, i: usize) -> Result<(), ControlError> {
+		(self.data_mut()).map_or(Ok(()), |aerodrome| aerodrome.apply_preset(i))
+	}
+
+	pub fn resend_scenery(&mut self) {
+		self.data_mut().map(|aerodrome| aerodrome.resend_scenery());
 	}
 
 	pub fn views(&self) -> Vec<String> {
@@ -248,7 +280,11 @@ impl Screen<'_> {
 					.config()
 					.maps
 					.iter()
-					.flat_map(|map| map.views.iter().map(|view| view.name.clone()))
+					.flat_map(|map| {
+						(map.views_or_default().iter())
+							.map(|view| view.name.clone())
+							.collect::<Vec<_>>()
+					})
 					.collect()
 			})
 			.unwrap_or(Vec::new())
@@ -265,12 +301,13 @@ impl Screen<'_> {
 		}
 	}
 
-	fn current_view(&self) -> Option<(&Map, &View)> {
+	fn current_view(&self) -> Option<(&Map, View)> {
 		self.view.zip(self.data()).and_then(|(view, aerodrome)| {
 			let mut ci = 0;
 			aerodrome.config().maps.iter().find_map(|map| {
-				ci += map.views.len();
-				(ci > view).then(|| (map, &map.views[view + ci - map.views.len()]))
+				let views = map.views_or_default();
+				ci += views.len();
+				(ci > view).then(|| (map, views[view + ci - views.len()].clone()))
 			})
 		})
 	}
@@ -312,6 +349,14 @@ impl Screen<'_> {
 		}
 
 		let style = &self.styles[path.style.0];
+		if let Some(blink) = &style.blink {
+			// in step with other clients by the server clock
+			let now = self.data().map(|aerodrome| aerodrome.server_millis());
+			if !blink.visible_at(now.unwrap_or_default()) {
+				return
+			}
+		}
+
 		style.apply(hdc);
 
 		let points = path
@@ -513,6 +558,7 @@ impl Screen<'_> {
 				stroke_join: StrokeJoin(0),
 				fill_style: FillStyle::Fill,
 				fill_color: map.background,
+				blink: None,
 			})
 			.apply(hdc);
 			let _ = Gdi::Rectangle(
@@ -540,19 +586,20 @@ impl Screen<'_> {
 		edges: impl Iterator<Item = &'a EdgeDisplay<T>>,
 		hdc: HDC,
 	) {
+		let edge_states = aerodrome.edge_states();
 		for (i, edge) in edges.enumerate() {
-			if let EdgeCondition::Fixed {
-				state: EdgeState::Off,
-			} = aerodrome.config().profiles[self.profile()].edges[i]
-			{
+			// edges fixed off are not drawn, unless overridden
+			let fixed_off = matches!(
+				aerodrome.config().profiles[self.profile()].edges[i],
+				EdgeCondition::Fixed {
+					state: EdgeState::Off,
+				},
+			);
+			if fixed_off && aerodrome.edge_override(i).is_none() {
 				continue
 			}
 
-			let display = if aerodrome.edge_state(i) {
-				&edge.on
-			} else {
-				&edge.off
-			};
+			let display = if edge_states[i] { &edge.on } else { &edge.off };
 
 			for path in display {
 				unsafe {
@@ -593,6 +640,21 @@ impl Screen<'_> {
 						self.draw_path(hdc, path);
 					}
 				}
+
+				// and the target itself, if the map gives it a style for this
+				if let Some(style) = node.target.active_style {
+					for polygon in &node.target.polygons {
+						let path = Path {
+							points: polygon.clone(),
+							style,
+							level: node.target.level,
+						};
+
+						unsafe {
+							self.draw_path(hdc, &path);
+						}
+					}
+				}
 			}
 		}
 	}
@@ -646,6 +708,7 @@ impl Screen<'_> {
 			return None
 		};
 
+		// clicks are only taken while controlling, so changes are not refused
 		match target {
 			Target::None => {
 				if geo {
@@ -659,12 +722,12 @@ impl Screen<'_> {
 					match data.config().profiles[data.profile()].nodes[id as usize] {
 						NodeCondition::Fixed { .. } => (),
 						NodeCondition::Direct { .. } => {
-							data.set_node(id as usize, !data.node_state(id as usize));
+							let _ = data.set_node(id as usize, !data.node_state(id as usize));
 						},
 						NodeCondition::Router { .. } => {
 							if let Some((node, at)) = selection {
 								if at.elapsed() < DESELECT_AFTER {
-									data.set_route((node, id as usize));
+									let _ = data.set_route((node, id as usize));
 								}
 							}
 
@@ -678,7 +741,7 @@ impl Screen<'_> {
 				}
 			},
 			Target::Block(id) => {
-				data.set_block(
+				let _ = data.set_block(
 					id as usize,
 					match click {
 						ClickType::Primary => BlockState::Clear,
@@ -1,6 +1,9 @@
 use crate::config::{ConfigManager, ConfigMapping};
-use crate::ipc::{Channel, Downstream, ServerChannel, Upstream};
+use crate::ipc::{
+	Capability, Channel, Downstream, Framing, ServerChannel, Upstream,
+};
 
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::net::Ipv4Addr;
 use std::sync::Arc;
@@ -9,7 +12,8 @@ use std::time::{Duration, Instant};
 
 use bars_config::Aerodrome;
 use bars_protocol::{
-	Downstream as NetDownstream, Patch, State, Upstream as NetUpstream,
+	epoch_millis, Downstream as NetDownstream, Patch, State,
+	Upstream as NetUpstream,
 };
 
 use anyhow::Result;
@@ -23,6 +27,7 @@ use tokio::sync::broadcast::Sender;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::error::TryRecvError;
 use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::time::{sleep_until, Instant as TokioInstant};
 
 use tokio_tungstenite::tungstenite::http::Uri;
 use tokio_tungstenite::tungstenite::Message;
@@ -32,11 +37,14 @@ use tracing::{debug, error, trace, warn};
 
 const SOCKET_POLL_TIMEOUT: Duration = Duration::from_millis(100);
 const STATE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// shortest interval between patches which clients are asked to keep to
+const MIN_PATCH_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct ConnectOptions {
 	pub server: String,
 	pub token: String,
 	pub port: u16,
+	pub framing: Framing,
 	pub callsign: String,
 	pub controlling: bool,
 }
@@ -129,7 +137,7 @@ impl Worker {
 		this.handle_stream(channel, tx.clone()).await?;
 
 		if let Some(options) = &connect {
-			this.bind(options.port, tx).await?;
+			this.bind(options.port, options.framing, tx).await?;
 		}
 
 		tokio::spawn(async move {
@@ -179,11 +187,24 @@ impl Worker {
 				},
 				Upstream::Patch { icao, patch } => {
 					debug!("patching {icao}");
-					aerodrome.patch(patch).await
+					aerodrome.patch(*patch).await
 				},
-				Upstream::Scenery { icao, scenery } => {
+				Upstream::Scenery { icao, scenery, seq } => {
 					debug!("updating {icao}");
-					aerodrome.scenery(scenery).await
+					aerodrome.scenery(scenery, seq).await
+				},
+				Upstream::Acquire { icao, block } => {
+					debug!("acquiring {block} at {icao}");
+					aerodrome.acquire(block).await
+				},
+				Upstream::Release { icao, block } => {
+					debug!("releasing {block} at {icao}");
+					aerodrome.release(block).await
+				},
+				Upstream::Resync { icao } => {
+					debug!("resyncing {icao}");
+					aerodrome.resync().await;
+					Ok(())
 				},
 				_ => Ok(()),
 			};
@@ -201,6 +222,7 @@ impl Worker {
 	async fn bind(
 		&self,
 		port: u16,
+		framing: Framing,
 		server_tx: UnboundedSender<Upstream>,
 	) -> Result<()> {
 		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
@@ -211,7 +233,7 @@ impl Worker {
 				if let Ok((stream, remote)) = listener.accept().await {
 					debug!("accepted {remote}");
 
-					let channel = ServerChannel::Tcp(stream);
+					let channel = ServerChannel::Tcp { stream, framing };
 					if let Err(err) =
 						state.handle_stream(channel, server_tx.clone()).await
 					{
@@ -233,13 +255,53 @@ impl Worker {
 		let mut ipc_rx = self.broadcast.subscribe();
 
 		let tracked = Arc::new(Mutex::new(HashSet::new()));
+		let init = Arc::new(Mutex::new(ClientInit::default()));
+		let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
 
 		{
 			let tracked = tracked.clone();
+			let init = init.clone();
 			let server_tx = server_tx.clone();
 
 			tokio::spawn(async move {
-				while let Ok(message) = ipc_rx.recv().await {
+				// patches held back to keep to the rate of the client, by aerodrome
+				let mut held = HashMap::<String, Downstream>::new();
+				let mut last_patch = None::<TokioInstant>;
+
+				loop {
+					let interval = init.lock().await.patch_interval();
+					let flush_at =
+						last_patch.map_or_else(TokioInstant::now, |sent| sent + interval);
+
+					let message = tokio::select! {
+						message = ipc_rx.recv() => match message {
+							Ok(message) => message,
+							Err(_) => break,
+						},
+						Some(reply) = reply_rx.recv() => {
+							if let Err(err) = stream_tx.send(reply).await {
+								debug!("{err}");
+								break
+							}
+							continue
+						},
+						_ = sleep_until(flush_at), if !held.is_empty() => {
+							let tracked = tracked.lock().await;
+							let mut res = Ok(());
+							for (icao, patch) in held.drain() {
+								if res.is_ok() && tracked.contains(&icao) {
+									res = stream_tx.send(patch).await;
+								}
+							}
+							if let Err(err) = res {
+								debug!("{err}");
+								break
+							}
+							last_patch = Some(TokioInstant::now());
+							continue
+						},
+					};
+
 					let mut tracked = tracked.lock().await;
 
 					let icao = message.icao();
@@ -247,6 +309,37 @@ impl Worker {
 						continue
 					}
 
+					let init = init.lock().await;
+					if matches!(message, Downstream::Weather { .. })
+						&& !init.supports.contains(&Capability::Weather)
+					{
+						continue
+					}
+
+					if let Downstream::Patch { icao, .. } = &message {
+						let now = TokioInstant::now();
+						if held.is_empty()
+							&& last_patch.is_none_or(|sent| now >= sent + interval)
+						{
+							last_patch = Some(now);
+							if let Err(err) = stream_tx.send(message).await {
+								debug!("{err}");
+								break
+							}
+						} else {
+							match held.entry(icao.clone()) {
+								Entry::Occupied(mut entry) => {
+									merge_held(entry.get_mut(), message)
+								},
+								Entry::Vacant(entry) => {
+									entry.insert(message);
+								},
+							}
+						}
+
+						continue
+					}
+
 					if let Downstream::Error {
 						icao,
 						disconnect: true,
@@ -286,7 +379,42 @@ impl Worker {
 				};
 
 				match &message {
-					Upstream::Init => continue,
+					Upstream::Init {
+						max_downstream_hz,
+						supports,
+					} => {
+						debug!("client init ({max_downstream_hz:?}, {supports:?})");
+
+						*init.lock().await = ClientInit {
+							max_downstream_hz: *max_downstream_hz,
+							supports: supports.iter().copied().collect(),
+						};
+
+						if supports.contains(&Capability::InitAck) {
+							let _ = reply_tx.send(Downstream::InitAck {
+								min_patch_interval_ms: Some(
+									MIN_PATCH_INTERVAL.as_millis() as u32
+								),
+							});
+						}
+
+						continue
+					},
+					Upstream::Ping { seq } => {
+						let _ = reply_tx.send(Downstream::Pong { seq: *seq });
+						continue
+					},
+					Upstream::Bye => {
+						debug!("client said goodbye");
+
+						// anything still tracked is released now, rather than once the
+						// stream is found to be closed
+						for icao in tracked.lock().await.drain() {
+							let _ = server_tx.send(Upstream::Track { icao, track: false });
+						}
+
+						break
+					},
 					Upstream::Track { icao, track } => {
 						let mut tracked = tracked.lock().await;
 
@@ -311,6 +439,52 @@ impl Worker {
 	}
 }
 
+/// Merges a patch into that held back for the same aerodrome, keeping the
+/// sequence of the first, so that the client does not take those between for
+/// missing. A snapshot replaces whatever was held, and remains a snapshot once
+/// patches are merged into it.
+fn merge_held(held: &mut Downstream, next: Downstream) {
+	let Downstream::Patch {
+		patch: next_patch,
+		seq: next_seq,
+		snapshot: false,
+		..
+	} = next
+	else {
+		*held = next;
+		return
+	};
+	let Downstream::Patch {
+		patch,
+		seq,
+		first_seq,
+		..
+	} = held
+	else {
+		unreachable!("only patches are held")
+	};
+
+	*first_seq = first_seq.or(*seq);
+	*seq = next_seq;
+	patch.apply_patch(*next_patch);
+}
+
+/// What a client advertised when it connected.
+#[derive(Default)]
+struct ClientInit {
+	max_downstream_hz: Option<u8>,
+	supports: HashSet<Capability>,
+}
+
+impl ClientInit {
+	/// Returns the shortest interval between patches sent to the client.
+	fn patch_interval(&self) -> Duration {
+		self.max_downstream_hz.map_or(Duration::ZERO, |hz| {
+			Duration::from_secs(1) / u32::from(hz.max(1))
+		})
+	}
+}
+
 #[derive(Clone)]
 struct AerodromeManager {
 	data: Arc<Mutex<AerodromeManagerData>>,
@@ -325,6 +499,9 @@ struct AerodromeManagerData {
 	controlling: bool,
 	trackers: usize,
 	state: Patch,
+	/// sequence of the last patch broadcast
+	seq: u64,
+	clock_offset: Option<i64>,
 	socket: Option<Arc<Mutex<WebSocketStream<MaybeTlsStream<TcpStream>>>>>,
 }
 
@@ -341,6 +518,8 @@ impl AerodromeManager {
 				controlling: false,
 				trackers: 0,
 				state: Patch::default(),
+				seq: 0,
+				clock_offset: None,
 				socket: None,
 			})),
 			server: options.as_ref().map(|options| {
@@ -384,6 +563,31 @@ impl AerodromeManager {
 		}
 	}
 
+	/// Merges a patch into the state held, and broadcasts it with the next
+	/// sequence.
+	fn broadcast_patch(&self, data: &mut AerodromeManagerData, patch: Patch) {
+		data.state.apply_patch(patch.clone());
+		data.seq += 1;
+		self.broadcast(Downstream::Patch {
+			icao: self.icao.clone(),
+			patch: Box::new(patch),
+			seq: Some(data.seq),
+			first_seq: None,
+			snapshot: false,
+		});
+	}
+
+	/// Broadcasts the state held as a snapshot, as of the last patch.
+	fn broadcast_snapshot(&self, data: &AerodromeManagerData) {
+		self.broadcast(Downstream::Patch {
+			icao: self.icao.clone(),
+			patch: Box::new(data.state.clone()),
+			seq: Some(data.seq),
+			first_seq: None,
+			snapshot: true,
+		});
+	}
+
 	async fn load_config(&self) -> Result<()> {
 		if self.data.lock().await.config.is_some() {
 			debug!("skipping config load");
@@ -416,20 +620,46 @@ impl AerodromeManager {
 		}
 	}
 
+	/// Warns clients if the config hash sent by the server differs from that
+	/// of the local config, in which case ids may no longer match.
+	fn check_config_hash(&self, config: &Aerodrome, expected: &str) {
+		let local = config.config_hash();
+		if local == expected {
+			return
+		}
+
+		warn!("local config hash {local} differs from server hash {expected}");
+		self.broadcast(Downstream::Error {
+			icao: self.icao.clone(),
+			message: Some(format!(
+				"local config ({}) differs from the server's ({}), so may be out \
+				 of date; update the config package",
+				short_hash(&local),
+				short_hash(expected),
+			)),
+			disconnect: false,
+		});
+	}
+
 	async fn sync_clients(&self) {
 		let data = self.data.lock().await;
 		if let Some(config) = &data.config {
 			self.broadcast(Downstream::Config {
 				data: config.encode().unwrap(),
+				hash: Some(config.config_hash()),
 			});
 			self.broadcast(Downstream::Control {
 				icao: self.icao.clone(),
 				control: data.controlling,
 			});
-			self.broadcast(Downstream::Patch {
-				icao: self.icao.clone(),
-				patch: data.state.clone(),
-			});
+			self.broadcast_snapshot(&data);
+
+			if let Some(offset) = data.clock_offset {
+				self.broadcast(Downstream::Time {
+					icao: self.icao.clone(),
+					server_epoch_millis: epoch_millis().saturating_add_signed(offset),
+				});
+			}
 		}
 	}
 
@@ -504,14 +734,19 @@ impl AerodromeManager {
 								},
 								state @ NetDownstream::InitialState { .. }
 								| state @ NetDownstream::SharedStateUpdate { .. } => {
-									let (patch, control) = match state {
+									let (patch, control, config_hash) = match state {
 										NetDownstream::InitialState {
 											connection_type,
 											patch,
+											config_hash,
 											..
-										} => (patch, Some(connection_type == "controller")),
+										} => (
+											patch,
+											Some(connection_type == "controller"),
+											config_hash,
+										),
 										NetDownstream::SharedStateUpdate { patch, .. } => {
-											(patch, None)
+											(patch, None, None)
 										},
 										_ => unreachable!(),
 									};
@@ -519,11 +754,13 @@ impl AerodromeManager {
 
 									let mut data = this.data.lock().await;
 
-									data.state.apply_patch(patch.clone());
-									this.broadcast(Downstream::Patch {
-										icao: this.icao.clone(),
-										patch,
-									});
+									if let Some((config, expected)) =
+										data.config.as_ref().zip(config_hash)
+									{
+										this.check_config_hash(config, &expected);
+									}
+
+									this.broadcast_patch(&mut data, patch);
 
 									if let Some(control) = control {
 										data.controlling = control;
@@ -535,6 +772,48 @@ impl AerodromeManager {
 
 									Ok(())
 								},
+								NetDownstream::Lock {
+									block,
+									owner,
+									granted,
+								} => {
+									this.broadcast(Downstream::Lock {
+										icao: this.icao.clone(),
+										block,
+										owner,
+										granted,
+									});
+
+									Ok(())
+								},
+								NetDownstream::Time {
+									server_epoch_millis,
+								} => {
+									this.data.lock().await.clock_offset =
+										Some(server_epoch_millis as i64 - epoch_millis() as i64);
+									this.broadcast(Downstream::Time {
+										icao: this.icao.clone(),
+										server_epoch_millis,
+									});
+
+									Ok(())
+								},
+								NetDownstream::Weather {
+									visibility_m,
+									rvr_m,
+									lvp,
+									raw,
+								} => {
+									this.broadcast(Downstream::Weather {
+										icao: this.icao.clone(),
+										visibility_m,
+										rvr_m,
+										lvp,
+										raw,
+									});
+
+									Ok(())
+								},
 								NetDownstream::StateUpdate { .. }
 								| NetDownstream::HeartbeatAck
 								| NetDownstream::ControllerConnect { .. }
@@ -570,7 +849,7 @@ impl AerodromeManager {
 							this
 								.disconnect_forced(
 									socket_arc,
-									format!("connection closed unexpectedly"),
+									"connection closed unexpectedly".to_string(),
 								)
 								.await;
 
@@ -595,9 +874,18 @@ impl AerodromeManager {
 									continue
 								};
 
+								let stands = (data.pilots.iter())
+									.filter_map(|pilot| {
+										Some((pilot.callsign.clone(), pilot.stand.clone()?))
+									})
+									.collect();
+
 								this.broadcast(Downstream::Aircraft {
 									icao: this.icao.clone(),
-									aircraft: data.pilots,
+									aircraft: (data.pilots.into_iter())
+										.map(|pilot| pilot.callsign)
+										.collect(),
+									stands,
 								});
 							}
 						},
@@ -713,24 +1001,69 @@ impl AerodromeManager {
 			let mut socket = socket.lock().await;
 			Self::send(&mut socket, &NetUpstream::SharedStateUpdate { patch }).await
 		} else {
-			data.state.apply_patch(patch.clone());
-			self.broadcast(Downstream::Patch {
-				icao: self.icao.clone(),
-				patch,
-			});
+			self.broadcast_patch(&mut data, patch);
 			Ok(())
 		}
 	}
 
-	async fn scenery(&self, scenery: HashMap<String, bool>) -> Result<()> {
+	/// Sends the state held to clients which found patches missing.
+	///
+	/// The snapshot is broadcast, but clients which are up to date ignore it.
+	async fn resync(&self) {
+		self.broadcast_snapshot(&*self.data.lock().await);
+	}
+
+	async fn scenery(
+		&self,
+		scenery: HashMap<String, bool>,
+		seq: u64,
+	) -> Result<()> {
 		if let Some(socket) = &self.data.lock().await.socket {
 			let mut socket = socket.lock().await;
 			for (object_id, state) in scenery {
 				let message = NetUpstream::StateUpdate { object_id, state };
 				Self::send(&mut socket, &message).await?;
 			}
+		} else if self.server.is_some() {
+			// not delivered, so the client is left to retransmit
+			return Ok(())
 		}
 
+		self.broadcast(Downstream::SceneryAck {
+			icao: self.icao.clone(),
+			seq,
+		});
+
 		Ok(())
 	}
+
+	async fn acquire(&self, block: String) -> Result<()> {
+		if self.server.is_none() {
+			self.broadcast(Downstream::Lock {
+				icao: self.icao.clone(),
+				block,
+				owner: "local".into(),
+				granted: true,
+			});
+		} else if let Some(socket) = &self.data.lock().await.socket {
+			let mut socket = socket.lock().await;
+			Self::send(&mut socket, &NetUpstream::Acquire { block }).await?;
+		}
+
+		Ok(())
+	}
+
+	async fn release(&self, block: String) -> Result<()> {
+		if let Some(socket) = &self.data.lock().await.socket {
+			let mut socket = socket.lock().await;
+			Self::send(&mut socket, &NetUpstream::Release { block }).await?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Shortens a config hash for display.
+fn short_hash(hash: &str) -> &str {
+	hash.get(..12).unwrap_or(hash)
 }
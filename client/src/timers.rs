@@ -0,0 +1,148 @@
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+use std::time::Instant;
+
+/// Deadlines of keys, each with a value, in the order they are due.
+///
+/// A key has at most one deadline, which arming it again replaces. Arming,
+/// cancelling and taking those due each take logarithmic time, whatever the
+/// order the deadlines were armed in.
+#[derive(Clone, Debug)]
+pub(crate) struct DeadlineQueue<K, V> {
+	order: BTreeSet<(Instant, K)>,
+	deadlines: HashMap<K, (Instant, V)>,
+}
+
+impl<K, V> Default for DeadlineQueue<K, V> {
+	fn default() -> Self {
+		Self {
+			order: BTreeSet::new(),
+			deadlines: HashMap::new(),
+		}
+	}
+}
+
+impl<K: Copy + Hash + Ord, V> DeadlineQueue<K, V> {
+	/// Arms the deadline of a key, replacing any it had.
+	pub fn arm(&mut self, key: K, deadline: Instant, value: V) {
+		self.cancel(key);
+		self.order.insert((deadline, key));
+		self.deadlines.insert(key, (deadline, value));
+	}
+
+	/// Cancels the deadline of a key, returning it if it had one.
+	pub fn cancel(&mut self, key: K) -> Option<(Instant, V)> {
+		let (deadline, value) = self.deadlines.remove(&key)?;
+		self.order.remove(&(deadline, key));
+		Some((deadline, value))
+	}
+
+	pub fn get(&self, key: K) -> Option<(Instant, &V)> {
+		(self.deadlines.get(&key)).map(|(deadline, value)| (*deadline, value))
+	}
+
+	/// Returns the earliest deadline, if any are armed.
+	pub fn next(&self) -> Option<Instant> {
+		self.order.first().map(|(deadline, _)| *deadline)
+	}
+
+	/// Removes the keys whose deadlines are before the instant, returning them
+	/// in the order they were due.
+	pub fn take_due(&mut self, now: Instant) -> Vec<K> {
+		let mut due = Vec::new();
+		while let Some(&(deadline, key)) = self.order.first() {
+			if deadline >= now {
+				break
+			}
+
+			self.order.pop_first();
+			self.deadlines.remove(&key);
+			due.push(key);
+		}
+		due
+	}
+
+	/// Returns each key with its deadline and value, in the order they are due.
+	pub fn iter(&self) -> impl Iterator<Item = (K, Instant, &V)> {
+		self
+			.order
+			.iter()
+			.map(|(deadline, key)| (*key, *deadline, &self.deadlines[key].1))
+	}
+
+	pub fn clear(&mut self) {
+		self.order.clear();
+		self.deadlines.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::time::Duration;
+
+	fn secs(base: Instant, secs: u64) -> Instant {
+		base + Duration::from_secs(secs)
+	}
+
+	#[test]
+	fn deadlines_are_due_in_order_whatever_the_order_armed() {
+		let base = Instant::now();
+		let mut queue = DeadlineQueue::default();
+		queue.arm('a', secs(base, 60), ());
+		queue.arm('b', secs(base, 10), ());
+		queue.arm('c', secs(base, 30), ());
+
+		assert_eq!(queue.next(), Some(secs(base, 10)));
+		assert_eq!(queue.iter().map(|(key, ..)| key).collect::<String>(), "bca");
+
+		assert_eq!(queue.take_due(secs(base, 10)), []);
+		assert_eq!(queue.take_due(secs(base, 31)), ['b', 'c']);
+		assert_eq!(queue.next(), Some(secs(base, 60)));
+		assert_eq!(queue.get('b'), None);
+	}
+
+	#[test]
+	fn cancelling_leaves_the_rest_in_order() {
+		let base = Instant::now();
+		let mut queue = DeadlineQueue::default();
+		for (key, at) in [('a', 20), ('b', 10), ('c', 40), ('d', 30)] {
+			queue.arm(key, secs(base, at), at);
+		}
+
+		assert_eq!(queue.cancel('b'), Some((secs(base, 10), 10)));
+		assert_eq!(queue.cancel('b'), None);
+		assert_eq!(queue.cancel('c'), Some((secs(base, 40), 40)));
+
+		assert_eq!(queue.next(), Some(secs(base, 20)));
+		assert_eq!(queue.take_due(secs(base, 60)), ['a', 'd']);
+		assert_eq!(queue.next(), None);
+	}
+
+	#[test]
+	fn arming_again_replaces_the_deadline() {
+		let base = Instant::now();
+		let mut queue = DeadlineQueue::default();
+		queue.arm('a', secs(base, 10), "first");
+		queue.arm('b', secs(base, 20), "other");
+		queue.arm('a', secs(base, 30), "second");
+
+		assert_eq!(queue.get('a'), Some((secs(base, 30), &"second")));
+		assert_eq!(queue.iter().count(), 2);
+		assert_eq!(queue.take_due(secs(base, 25)), ['b']);
+		assert_eq!(queue.take_due(secs(base, 35)), ['a']);
+	}
+
+	#[test]
+	fn nothing_is_next_once_cleared() {
+		let base = Instant::now();
+		let mut queue = DeadlineQueue::<char, ()>::default();
+		assert_eq!(queue.next(), None);
+
+		queue.arm('a', base, ());
+		queue.clear();
+		assert_eq!(queue.next(), None);
+		assert_eq!(queue.take_due(secs(base, 1)), []);
+	}
+}
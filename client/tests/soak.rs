@@ -0,0 +1,268 @@
+//! Soak test of the aerodrome state machine.
+//!
+//! Random operations are applied to aerodromes generated from lattices of
+//! blocks, and invariants checked after each. The operations are applied to
+//! twin aerodromes too, which must agree exactly, as nothing may depend on the
+//! order of iteration of hash maps.
+//!
+//! Edges are evaluated again only as what they read changes, so the elements
+//! lit by edges are checked against the edges evaluated in full.
+
+use std::time::Duration;
+
+use bars_client::Aerodrome;
+
+use bars_config::{
+	Aerodrome as Config, BlockState, ElementCondition, Lattice, NodeCondition,
+	SyntheticRng,
+};
+
+use bars_protocol::{BlockState as IpcBlockState, Patch};
+
+/// operations applied to each aerodrome in the full soak
+const OPERATIONS: usize = 10_000;
+/// aerodromes generated in the full soak, each from its own seed
+const SEEDS: u64 = 4;
+
+#[test]
+fn invariants_hold_under_random_operations() {
+	soak_seeds(1, OPERATIONS / 10);
+}
+
+/// Soaks every seed for the full number of operations, which takes some time
+/// without optimisation; run with `cargo test --release -- --ignored`.
+#[test]
+#[ignore = "slow without optimisation"]
+fn invariants_hold_under_many_random_operations() {
+	soak_seeds(SEEDS, OPERATIONS);
+}
+
+fn soak_seeds(seeds: u64, operations: usize) {
+	let mut failed = Vec::new();
+
+	for seed in 0..seeds {
+		let lattice = Lattice {
+			seed,
+			..Lattice::default()
+		};
+
+		if let Err(err) = soak(&lattice.generate(), seed, operations) {
+			failed.push(format!("seed {seed}: {err}"));
+		}
+	}
+
+	assert!(failed.is_empty(), "{}", failed.join("\n"));
+}
+
+#[derive(Debug)]
+enum Operation {
+	SetNode(usize, bool),
+	SetBlock(usize, BlockState),
+	SetRoute(usize, usize),
+	ApplyPreset(usize),
+	SetProfile(usize),
+	Advance(Duration),
+	TakePending,
+}
+
+impl Operation {
+	fn random(rng: &mut SyntheticRng, aerodrome: &Aerodrome) -> Self {
+		let config = aerodrome.config();
+		let nodes = config.nodes.len();
+
+		match rng.below(16) {
+			0..=3 => Self::SetNode(rng.below(nodes), rng.chance(0.5)),
+			4..=6 => {
+				let i = rng.below(config.blocks.len());
+				let block = &config.blocks[i];
+				let state = match rng.below(3) {
+					0 => BlockState::Clear,
+					1 => BlockState::Relax,
+					_ => {
+						let a = block.nodes[rng.below(block.nodes.len())];
+						let b = block.nodes[rng.below(block.nodes.len())];
+						BlockState::Route((a, b))
+					},
+				};
+				Self::SetBlock(i, state)
+			},
+			7..=9 => Self::SetRoute(rng.below(nodes), rng.below(nodes)),
+			10 => match aerodrome.presets().len() {
+				0 => Self::TakePending,
+				n => Self::ApplyPreset(rng.below(n)),
+			},
+			11 => Self::SetProfile(rng.below(config.profiles.len())),
+			12 => Self::Advance(Duration::from_secs(rng.below(120) as u64)),
+			_ => Self::TakePending,
+		}
+	}
+
+	fn apply(&self, aerodrome: &mut Aerodrome) -> Option<Patch> {
+		let result = match self {
+			Self::SetNode(node, state) => aerodrome.set_node(*node, *state),
+			Self::SetBlock(block, state) => aerodrome.set_block(*block, *state),
+			Self::SetRoute(from, to) => aerodrome.set_route((*from, *to)),
+			Self::ApplyPreset(i) => aerodrome.apply_preset(*i),
+			Self::SetProfile(i) => aerodrome.set_profile(*i),
+			Self::Advance(duration) => {
+				aerodrome.advance(*duration);
+				Ok(())
+			},
+			Self::TakePending => return Some(aerodrome.take_pending().0),
+		};
+
+		result.expect("the aerodrome is offline");
+		None
+	}
+}
+
+fn soak(config: &Config, seed: u64, operations: usize) -> Result<(), String> {
+	let mut rng = SyntheticRng::new(seed);
+	let mut aerodrome = Aerodrome::new(config.clone());
+	let mut twin = Aerodrome::new(config.clone());
+	aerodrome.set_offline(true);
+	twin.set_offline(true);
+
+	// everything sent, as the server would hold it
+	let mut sent = Patch::default();
+
+	for step in 0..operations {
+		let operation = Operation::random(&mut rng, &aerodrome);
+		let fail =
+			|message: String| format!("step {step} ({operation:?}): {message}");
+
+		let patch = operation.apply(&mut aerodrome);
+		operation.apply(&mut twin);
+
+		if let Some(patch) = patch {
+			sent.apply_patch(patch);
+			check_sent(&aerodrome, &sent).map_err(fail)?;
+			check_elements(&aerodrome).map_err(fail)?;
+		}
+
+		check_twins(&aerodrome, &twin).map_err(fail)?;
+	}
+
+	Ok(())
+}
+
+/// Checks that every change made has been sent, once the pending patch has
+/// been taken.
+fn check_sent(aerodrome: &Aerodrome, sent: &Patch) -> Result<(), String> {
+	let config = aerodrome.config();
+	let profile = &config.profiles[aerodrome.profile()];
+
+	if let Some(id) = &sent.profile {
+		if *id != profile.id {
+			return Err(format!("profile {} not sent, {id} was", profile.id))
+		}
+	}
+
+	for (i, node) in config.nodes.iter().enumerate() {
+		if !matches!(profile.nodes[i], NodeCondition::Direct { .. }) {
+			continue
+		}
+
+		let state = aerodrome.node_state(i);
+		if let Some(sent) = sent.nodes.get(&node.id) {
+			if *sent != state {
+				return Err(format!("node {} is {state} but {sent} was sent", node.id))
+			}
+		}
+	}
+
+	let ids = config.nodes.iter().map(|node| &node.id).collect::<Vec<_>>();
+	for (i, block) in config.blocks.iter().enumerate() {
+		let state = match aerodrome.block_state(i) {
+			BlockState::Clear => IpcBlockState::Clear,
+			BlockState::Relax => IpcBlockState::Relax,
+			BlockState::Route((a, b)) => {
+				IpcBlockState::Route((ids[a.0].clone(), ids[b.0].clone()))
+			},
+		};
+
+		let sent = sent.blocks.get(&block.id).unwrap_or(&IpcBlockState::Clear);
+		if *sent != state {
+			return Err(format!(
+				"block {} is {state:?} but {sent:?} was sent",
+				block.id,
+			))
+		}
+	}
+
+	Ok(())
+}
+
+/// Checks that the elements lit by edges agree with the edges evaluated in
+/// full, once the pending patch has been taken.
+fn check_elements(aerodrome: &Aerodrome) -> Result<(), String> {
+	let edges = aerodrome.edge_states();
+	let differ = (aerodrome.config().elements.iter().enumerate())
+		.filter(|(i, element)| match element.condition {
+			ElementCondition::Edge(edge) => {
+				aerodrome.element_state(*i) != edges[edge.0]
+			},
+			_ => false,
+		})
+		.map(|(_, element)| element.id.as_str())
+		.collect::<Vec<_>>();
+
+	if differ.is_empty() {
+		Ok(())
+	} else {
+		Err(format!(
+			"elements {} differ from their edges",
+			differ.join(", ")
+		))
+	}
+}
+
+/// Checks that twin aerodromes given the same operations agree on everything
+/// they light.
+fn check_twins(aerodrome: &Aerodrome, twin: &Aerodrome) -> Result<(), String> {
+	let config = aerodrome.config();
+
+	let edges = aerodrome.edge_states();
+	if edges != aerodrome.edge_states() {
+		return Err("edges differ between evaluations".into())
+	}
+
+	let edge_ids = config.edges.iter().map(|edge| &edge.id);
+	let differ = differing(&edges, &twin.edge_states(), edge_ids);
+	if !differ.is_empty() {
+		return Err(format!("edges {} differ from twin", differ.join(", ")))
+	}
+
+	let states = |aerodrome: &Aerodrome| {
+		(0..config.nodes.len())
+			.map(|i| aerodrome.node_state(i))
+			.collect::<Vec<_>>()
+	};
+	let node_ids = config.nodes.iter().map(|node| &node.id);
+	let differ = differing(&states(aerodrome), &states(twin), node_ids);
+	if !differ.is_empty() {
+		return Err(format!("nodes {} differ from twin", differ.join(", ")))
+	}
+
+	let blocks = (0..config.blocks.len())
+		.filter(|i| aerodrome.block_state(*i) != twin.block_state(*i))
+		.map(|i| config.blocks[i].id.as_str())
+		.collect::<Vec<_>>();
+	if !blocks.is_empty() {
+		return Err(format!("blocks {} differ from twin", blocks.join(", ")))
+	}
+
+	Ok(())
+}
+
+/// Returns the ids of the entities whose states differ.
+fn differing<'a>(
+	a: &[bool],
+	b: &[bool],
+	ids: impl Iterator<Item = &'a String>,
+) -> Vec<&'a str> {
+	(a.iter().zip(b).zip(ids))
+		.filter(|((a, b), _)| a != b)
+		.map(|(_, id)| id.as_str())
+		.collect()
+}
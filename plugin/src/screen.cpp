@@ -1,4 +1,5 @@
 #include "screen.hpp"
+#include "config.hpp"
 
 #include <algorithm>
 #include <cctype>
@@ -441,7 +442,11 @@ void Screen::OnFunctionCall(
 		break;
 
 	case TagFunctionType::SubmitSelectPreset:
-		client::client_apply_preset(screen_, function.data.payload);
+		if (auto error = client::client_apply_preset(screen_, function.data.payload))
+			plugin_->DisplayUserMessage(
+				PLUGIN_NAME, "Preset", error, true, true, false, false, false
+			);
+
 		break;
 
 	case TagFunctionType::OpenSelectView:
@@ -0,0 +1,142 @@
+use bars_config::{
+	Config, Geo, GeoMap, Grid, HitTarget, Lattice, Loadable, Maps,
+};
+
+use criterion::{
+	criterion_group, criterion_main, BenchmarkId, Criterion, Throughput,
+};
+
+fn decode(c: &mut Criterion) {
+	let mut group = c.benchmark_group("decode");
+
+	for size in [4, 16, 32] {
+		let grid = Grid {
+			size,
+			..Grid::default()
+		};
+		let config = Config {
+			name: None,
+			version: None,
+			aerodromes: vec![grid.aerodrome()],
+		};
+
+		let mut data = Vec::new();
+		config.save(&mut data).unwrap();
+
+		group.throughput(Throughput::Bytes(data.len() as u64));
+		group.bench_with_input(
+			BenchmarkId::from_parameter(size),
+			&data,
+			|b, data| b.iter(|| Config::load(&data[..]).unwrap()),
+		);
+	}
+
+	group.finish();
+}
+
+fn topsky(c: &mut Criterion) {
+	let mut group = c.benchmark_group("topsky");
+
+	// about 50k lines at the largest size
+	for size in [8, 16, 32] {
+		let text = Grid {
+			size,
+			..Grid::default()
+		}
+		.topsky();
+
+		group.throughput(Throughput::Elements(text.lines().count() as u64));
+		group.bench_with_input(
+			BenchmarkId::from_parameter(size),
+			&text,
+			|b, text| b.iter(|| Maps::load_topsky(text).unwrap()),
+		);
+	}
+
+	group.finish();
+}
+
+fn validate(c: &mut Criterion) {
+	let mut group = c.benchmark_group("validate");
+
+	for size in [8, 16, 32] {
+		let aerodrome = Lattice {
+			columns: size,
+			rows: size,
+			..Lattice::default()
+		}
+		.generate();
+
+		group.throughput(Throughput::Elements(aerodrome.nodes.len() as u64));
+		group.bench_with_input(
+			BenchmarkId::from_parameter(size),
+			&aerodrome,
+			|b, aerodrome| b.iter(|| aerodrome.validate()),
+		);
+	}
+
+	group.finish();
+}
+
+/// Finds what a position selects by checking every target, as the index is
+/// measured against.
+fn naive_hit(geo_map: &GeoMap, geo: Geo) -> Option<HitTarget> {
+	let node = geo_map
+		.nodes
+		.iter()
+		.position(|node| node.target.contains(geo))
+		.map(|i| HitTarget::Node(i.into()));
+
+	node.or_else(|| {
+		geo_map
+			.blocks
+			.iter()
+			.position(|block| block.target.contains(geo))
+			.map(|i| HitTarget::Block(i.into()))
+	})
+}
+
+fn hit(c: &mut Criterion) {
+	let mut group = c.benchmark_group("hit");
+
+	// about 5k targets, half nodes and half blocks
+	let grid = Grid {
+		size: 50,
+		..Grid::default()
+	};
+	let geo_map = grid.aerodrome().geo_map.unwrap();
+	let index = geo_map.build_index();
+
+	// a spread of positions over the grid, hitting nodes, blocks and nothing
+	let positions = (0..256)
+		.map(|i| Geo {
+			lat: 51.0 + (i / 16) as f32 * 0.0031,
+			lon: -0.5 + (i % 16) as f32 * 0.0031,
+		})
+		.collect::<Vec<_>>();
+
+	for geo in &positions {
+		assert_eq!(geo_map.hit(&index, *geo), naive_hit(&geo_map, *geo));
+	}
+
+	group.throughput(Throughput::Elements(positions.len() as u64));
+	group.bench_function("naive", |b| {
+		b.iter(|| {
+			(positions.iter())
+				.filter_map(|geo| naive_hit(&geo_map, *geo))
+				.count()
+		})
+	});
+	group.bench_function("index", |b| {
+		b.iter(|| {
+			(positions.iter())
+				.filter_map(|geo| geo_map.hit(&index, *geo))
+				.count()
+		})
+	});
+
+	group.finish();
+}
+
+criterion_group!(benches, decode, topsky, validate, hit);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+
+use bars_config::Aerodrome;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(aerodrome) = Aerodrome::decode(data) {
+		// anything that decodes must be safe to validate and re-encode
+		aerodrome.validate();
+		aerodrome.encode().unwrap();
+	}
+});
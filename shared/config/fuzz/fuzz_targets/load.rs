@@ -0,0 +1,13 @@
+#![no_main]
+
+use bars_config::{Config, Loadable, Maps};
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(config) = Config::load(data) {
+		config.validate();
+	}
+
+	let _ = Maps::load(data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use bars_config::Maps;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+	let _ = Maps::load_topsky(text);
+	let _ = Maps::load_topsky_lenient(text);
+});
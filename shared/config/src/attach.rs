@@ -0,0 +1,228 @@
+use crate::*;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A kind of entity which [`Maps`] name by id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapsEntity {
+	Node,
+	Edge,
+	Block,
+	HoldingPoint,
+}
+
+impl Display for MapsEntity {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		f.write_str(match self {
+			Self::Node => "node",
+			Self::Edge => "edge",
+			Self::Block => "block",
+			Self::HoldingPoint => "holding point",
+		})
+	}
+}
+
+/// An id in maps which is not in the aerodrome they were attached to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnmatchedId {
+	pub kind: MapsEntity,
+	pub id: String,
+}
+
+impl Display for UnmatchedId {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		write!(f, "{} {} is not in the aerodrome", self.kind, self.id)
+	}
+}
+
+/// What was left out of the display of an aerodrome by
+/// [`Aerodrome::attach_maps`].
+#[derive(Clone, Debug, Default)]
+pub struct AttachReport {
+	/// ids in the maps which matched nothing, whose displays were dropped
+	pub unmatched: Vec<UnmatchedId>,
+	/// nodes of the aerodrome with nothing drawn and no target in any of its
+	/// maps, once attached
+	pub undisplayed_nodes: Vec<Ref<Node>>,
+	/// edges of the aerodrome with nothing drawn in any of its maps, once
+	/// attached
+	pub undisplayed_edges: Vec<Ref<Edge>>,
+}
+
+impl AttachReport {
+	pub fn is_empty(&self) -> bool {
+		self.unmatched.is_empty()
+			&& self.undisplayed_nodes.is_empty()
+			&& self.undisplayed_edges.is_empty()
+	}
+
+	/// Describes each problem, naming entities by their ids in the aerodrome.
+	pub fn describe(&self, aerodrome: &Aerodrome) -> Vec<String> {
+		let nodes = (self.undisplayed_nodes.iter()).map(|node| {
+			let id = (aerodrome.nodes.get(node.0)).map_or("?", |node| &node.id);
+			format!("node {id} is not displayed")
+		});
+		let edges = (self.undisplayed_edges.iter()).map(|edge| {
+			let id = (aerodrome.edges.get(edge.0)).map_or("?", |edge| &edge.id);
+			format!("edge {id} is not displayed")
+		});
+
+		(self.unmatched.iter().map(ToString::to_string))
+			.chain(nodes)
+			.chain(edges)
+			.collect()
+	}
+}
+
+/// Why maps could not be attached to an aerodrome.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttachError {
+	/// an id listed more than once in the maps, so that which display is its
+	/// own is ambiguous
+	DuplicateId { kind: MapsEntity, id: String },
+	/// none of the ids in the maps are in the aerodrome, as if the maps were
+	/// of another
+	NoMatches,
+}
+
+impl Display for AttachError {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		match self {
+			Self::DuplicateId { kind, id } => {
+				write!(f, "{kind} {id} is listed more than once")
+			},
+			Self::NoMatches => f.write_str("no ids are in the aerodrome"),
+		}
+	}
+}
+
+impl Error for AttachError {}
+
+impl Aerodrome {
+	/// Attaches the display data of maps, matching the ids in the maps against
+	/// those of the entities of the aerodrome, and rebasing the displays and
+	/// widgets onto the aerodrome. The styles of the maps are appended to those
+	/// of the aerodrome.
+	///
+	/// Attaching more maps is deterministic: their maps are appended to those
+	/// already attached, and their geo map, if they have one, replaces the one
+	/// already attached. Attaching the same maps twice therefore draws the geo
+	/// map the same, in the second copy of the styles, but repeats each map.
+	///
+	/// Displays of ids which are not in the aerodrome are dropped, and reported
+	/// along with the nodes and edges which are left undisplayed. Nothing is
+	/// attached if an id is listed twice, or if no ids match.
+	pub fn attach_maps(
+		&mut self,
+		mut maps: Maps,
+	) -> Result<AttachReport, AttachError> {
+		let mut report = AttachReport::default();
+		let unmatched = &mut report.unmatched;
+
+		let rebase = Rebase {
+			offset: self.styles.len(),
+			nodes: match_ids(
+				MapsEntity::Node,
+				&maps.nodes,
+				self.nodes.iter().map(|node| &node.id),
+				unmatched,
+			)?,
+			edges: match_ids(
+				MapsEntity::Edge,
+				&maps.edges,
+				self.edges.iter().map(|edge| &edge.id),
+				unmatched,
+			)?,
+			blocks: match_ids(
+				MapsEntity::Block,
+				&maps.blocks,
+				self.blocks.iter().map(|block| &block.id),
+				unmatched,
+			)?,
+			holding_points: match_ids(
+				MapsEntity::HoldingPoint,
+				&maps.holding_points,
+				self.holding_points.iter().map(|hp| &hp.id),
+				unmatched,
+			)?,
+		};
+
+		let ids = maps.nodes.len()
+			+ maps.edges.len()
+			+ maps.blocks.len()
+			+ maps.holding_points.len();
+		if ids > 0 && report.unmatched.len() == ids {
+			return Err(AttachError::NoMatches)
+		}
+
+		self.styles.append(&mut maps.styles);
+
+		if let Some(geo_map) = maps.geo_map {
+			self.geo_map = Some(geo_map.rebase(&rebase));
+		}
+
+		self
+			.maps
+			.extend(maps.maps.into_iter().map(|map| map.rebase(&rebase)));
+
+		let geo_map = self.geo_map.as_ref();
+		report.undisplayed_nodes = (0..self.nodes.len())
+			.filter(|&i| {
+				!(geo_map.and_then(|map| map.nodes.get(i))).is_some_and(node_shown)
+					&& !(self.maps.iter())
+						.any(|map| map.nodes.get(i).is_some_and(node_shown))
+			})
+			.map(Ref::from)
+			.collect();
+		report.undisplayed_edges = (0..self.edges.len())
+			.filter(|&i| {
+				!(geo_map.and_then(|map| map.edges.get(i))).is_some_and(edge_shown)
+					&& !(self.maps.iter())
+						.any(|map| map.edges.get(i).is_some_and(edge_shown))
+			})
+			.map(Ref::from)
+			.collect();
+
+		Ok(report)
+	}
+}
+
+/// Finds the index in the maps of the id of each entity of the aerodrome,
+/// noting the ids in the maps which are not in the aerodrome.
+fn match_ids<'a>(
+	kind: MapsEntity,
+	source: &[String],
+	target: impl Iterator<Item = &'a String>,
+	unmatched: &mut Vec<UnmatchedId>,
+) -> Result<Vec<Option<usize>>, AttachError> {
+	let mut indices = HashMap::new();
+	for (i, id) in source.iter().enumerate() {
+		if indices.insert(id, i).is_some() {
+			return Err(AttachError::DuplicateId {
+				kind,
+				id: id.clone(),
+			})
+		}
+	}
+
+	let target = target.collect::<Vec<_>>();
+	let known = target.iter().copied().collect::<HashSet<_>>();
+	unmatched.extend((source.iter()).filter(|id| !known.contains(id)).map(
+		|id| UnmatchedId {
+			kind,
+			id: id.clone(),
+		},
+	));
+
+	Ok(target.iter().map(|id| indices.get(id).copied()).collect())
+}
+
+fn node_shown<T: Projectable>(display: &NodeDisplay<T>) -> bool {
+	display.paths().next().is_some() || has_target(&display.target)
+}
+
+fn edge_shown<T: Projectable>(display: &EdgeDisplay<T>) -> bool {
+	display.paths().next().is_some()
+}
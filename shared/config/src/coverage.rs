@@ -0,0 +1,244 @@
+use crate::*;
+
+/// Parts of the display of an entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayPart {
+	Off,
+	On,
+	Target,
+}
+
+impl DisplayPart {
+	fn name(self) -> &'static str {
+		match self {
+			Self::Off => "off drawing",
+			Self::On => "on drawing",
+			Self::Target => "target",
+		}
+	}
+}
+
+/// Something in a map which cannot be seen or clicked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CoverageGap {
+	Node {
+		node: Ref<Node>,
+		missing: Vec<DisplayPart>,
+	},
+	Edge {
+		edge: Ref<Edge>,
+		missing: Vec<DisplayPart>,
+	},
+	/// a block without a target
+	Block { block: Ref<Block> },
+	/// a countdown for an entity with nothing drawn
+	Widget {
+		widget: usize,
+		condition: CountdownCondition,
+	},
+}
+
+impl CoverageGap {
+	/// Describes the gap, naming entities by their ids in the aerodrome.
+	pub fn describe(&self, aerodrome: &Aerodrome) -> String {
+		let node = |node: &Ref<Node>| {
+			(aerodrome.nodes.get(node.0)).map_or("?", |node| node.id.as_str())
+		};
+		let block = |block: &Ref<Block>| {
+			(aerodrome.blocks.get(block.0)).map_or("?", |block| block.id.as_str())
+		};
+		let missing = |missing: &[DisplayPart]| {
+			(missing.iter())
+				.map(|part| part.name())
+				.collect::<Vec<_>>()
+				.join(" or ")
+		};
+
+		match self {
+			Self::Node {
+				node: i,
+				missing: parts,
+			} => {
+				format!("node {} has no {}", node(i), missing(parts))
+			},
+			Self::Edge {
+				edge,
+				missing: parts,
+			} => {
+				let id = (aerodrome.edges.get(edge.0)).map_or("?", |edge| &edge.id);
+				format!("edge {id} has no {}", missing(parts))
+			},
+			Self::Block { block: i } => format!("block {} has no target", block(i)),
+			Self::Widget { widget, condition } => {
+				let entity = match condition {
+					CountdownCondition::Node(i) => format!("node {}", node(i)),
+					CountdownCondition::Block(i) => format!("block {}", block(i)),
+				};
+				format!("widget {widget} counts down for {entity}, which is not drawn")
+			},
+		}
+	}
+}
+
+/// The gaps in one map of an aerodrome.
+#[derive(Clone, Debug)]
+pub struct MapCoverage {
+	/// `geo_map`, or `maps[i]`
+	pub map: String,
+	pub gaps: Vec<CoverageGap>,
+}
+
+/// Entities of an aerodrome which are not fully drawn in its maps.
+#[derive(Clone, Debug, Default)]
+pub struct CoverageReport {
+	/// every map, including those without gaps
+	pub maps: Vec<MapCoverage>,
+}
+
+impl CoverageReport {
+	pub fn gaps(&self) -> usize {
+		self.maps.iter().map(|map| map.gaps.len()).sum()
+	}
+
+	/// Removes the gaps of nodes and edges which are fixed in every profile,
+	/// which may rightly not be drawn.
+	pub fn exclude_fixed(&mut self, aerodrome: &Aerodrome) {
+		let fixed_node = |i: usize| {
+			(aerodrome.profiles.iter()).all(|profile| {
+				matches!(profile.nodes.get(i), Some(NodeCondition::Fixed { .. }))
+			})
+		};
+		let fixed_edge = |i: usize| {
+			(aerodrome.profiles.iter()).all(|profile| {
+				matches!(profile.edges.get(i), Some(EdgeCondition::Fixed { .. }))
+			})
+		};
+
+		for map in &mut self.maps {
+			map.gaps.retain(|gap| match gap {
+				CoverageGap::Node { node, .. } => !fixed_node(node.0),
+				CoverageGap::Edge { edge, .. } => !fixed_edge(edge.0),
+				_ => true,
+			});
+		}
+	}
+}
+
+impl Aerodrome {
+	/// Finds the entities which are missing parts of their display in each
+	/// map: nodes without off or on drawings or a target, edges without off or
+	/// on drawings, blocks without a target, and countdowns for entities with
+	/// nothing drawn.
+	///
+	/// Selected and pending drawings are optional, so are not reported.
+	pub fn display_coverage(&self) -> CoverageReport {
+		let mut report = CoverageReport::default();
+
+		if let Some(geo_map) = &self.geo_map {
+			report.maps.push(MapCoverage {
+				map: "geo_map".into(),
+				gaps: self.gaps(
+					&geo_map.nodes,
+					&geo_map.edges,
+					&geo_map.blocks,
+					&geo_map.widgets,
+				),
+			});
+		}
+
+		for (i, map) in self.maps.iter().enumerate() {
+			report.maps.push(MapCoverage {
+				map: format!("maps[{i}]"),
+				gaps: self.gaps(&map.nodes, &map.edges, &map.blocks, &map.widgets),
+			});
+		}
+
+		report
+	}
+
+	fn gaps<T: Projectable>(
+		&self,
+		nodes: &[NodeDisplay<T>],
+		edges: &[EdgeDisplay<T>],
+		blocks: &[BlockDisplay<T>],
+		widgets: &[Widget<T>],
+	) -> Vec<CoverageGap> {
+		let mut gaps = Vec::new();
+
+		// displays missing from the end of the map are missing every part
+		let missing = |parts: [(DisplayPart, bool); 3]| {
+			(parts.into_iter())
+				.filter(|(_, present)| !present)
+				.map(|(part, _)| part)
+				.collect::<Vec<_>>()
+		};
+
+		for i in 0..self.nodes.len() {
+			let display = nodes.get(i);
+			let missing = missing([
+				(DisplayPart::Off, display.is_some_and(|d| !d.off.is_empty())),
+				(DisplayPart::On, display.is_some_and(|d| !d.on.is_empty())),
+				(
+					DisplayPart::Target,
+					display.is_some_and(|d| has_target(&d.target)),
+				),
+			]);
+
+			if !missing.is_empty() {
+				gaps.push(CoverageGap::Node {
+					node: i.into(),
+					missing,
+				});
+			}
+		}
+
+		for i in 0..self.edges.len() {
+			let display = edges.get(i);
+			let missing = missing([
+				(DisplayPart::Off, display.is_some_and(|d| !d.off.is_empty())),
+				(DisplayPart::On, display.is_some_and(|d| !d.on.is_empty())),
+				(DisplayPart::Target, true),
+			]);
+
+			if !missing.is_empty() {
+				gaps.push(CoverageGap::Edge {
+					edge: i.into(),
+					missing,
+				});
+			}
+		}
+
+		for i in 0..self.blocks.len() {
+			if !blocks.get(i).is_some_and(|d| has_target(&d.target)) {
+				gaps.push(CoverageGap::Block { block: i.into() });
+			}
+		}
+
+		for (i, widget) in widgets.iter().enumerate() {
+			let Widget::Countdown { condition, .. } = widget else {
+				continue
+			};
+
+			let drawn = match condition {
+				CountdownCondition::Node(node) => (nodes.get(node.0))
+					.is_some_and(|d| d.paths().next().is_some() || has_target(&d.target)),
+				CountdownCondition::Block(block) => {
+					blocks.get(block.0).is_some_and(|d| has_target(&d.target))
+				},
+			};
+
+			if !drawn {
+				gaps.push(CoverageGap::Widget {
+					widget: i,
+					condition: *condition,
+				});
+			}
+		}
+
+		gaps
+	}
+}
+
+pub(crate) fn has_target<T: Projectable>(target: &Target<T>) -> bool {
+	target.polygons.iter().any(|polygon| polygon.len() >= 3)
+}
@@ -0,0 +1,264 @@
+use super::*;
+
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+use sha2::{Digest, Sha256};
+
+/// Largest useful dictionary, being the window of DEFLATE.
+pub const MAX_DICTIONARY_SIZE: usize = 0x8000;
+
+/// length of the runs of bytes compared between aerodromes
+const WINDOW: usize = 8;
+
+/// length of the dictionary id leading dictionary encodings
+const DICTIONARY_ID_SIZE: usize = 8;
+
+impl Config {
+	/// Builds a dictionary of content shared between the aerodromes, for
+	/// compressing them separately with [`Aerodrome::encode_with_dict`].
+	///
+	/// Runs of bytes found in the encodings of more than one aerodrome are
+	/// kept, ranked by how many aerodromes share them and how long they are,
+	/// with the best last, where they are cheapest to refer to. With fewer
+	/// than two aerodromes there is nothing shared, and the dictionary is
+	/// empty.
+	pub fn build_dictionary(&self) -> Result<Vec<u8>, EncodeError> {
+		if self.aerodromes.len() < 2 {
+			return Ok(Vec::new())
+		}
+
+		let encodings = self
+			.aerodromes
+			.iter()
+			.map(Aerodrome::encode)
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let window = |data: &[u8], i: usize| {
+			u64::from_le_bytes(data[i..i + WINDOW].try_into().unwrap())
+		};
+
+		// the number of aerodromes containing each window
+		let mut counts = HashMap::<u64, usize>::new();
+		for data in &encodings {
+			let windows = (0..data.len().saturating_sub(WINDOW - 1))
+				.map(|i| window(data, i))
+				.collect::<HashSet<_>>();
+			windows
+				.into_iter()
+				.for_each(|w| *counts.entry(w).or_default() += 1);
+		}
+
+		// maximal runs of shared windows, scored by the aerodromes they cover
+		let mut segments = HashMap::<&[u8], usize>::new();
+		for data in &encodings {
+			let windows = data.len().saturating_sub(WINDOW - 1);
+			let mut start = None;
+			let mut score = 0;
+
+			// one past the last window, to end any run reaching the end
+			for i in 0..=windows {
+				let count = (i < windows)
+					.then(|| counts[&window(data, i)])
+					.filter(|count| *count > 1);
+
+				match (count, start) {
+					(Some(count), None) => {
+						start = Some(i);
+						score = count;
+					},
+					(Some(count), Some(_)) => score += count,
+					(None, Some(from)) => {
+						*segments.entry(&data[from..i - 1 + WINDOW]).or_default() += score;
+						start = None;
+					},
+					(None, None) => (),
+				}
+			}
+		}
+
+		let mut segments = segments.into_iter().collect::<Vec<_>>();
+		segments.sort_by(|(a, a_score), (b, b_score)| {
+			b_score.cmp(a_score).then_with(|| a.cmp(b))
+		});
+
+		let mut size = 0;
+		let mut chosen = Vec::new();
+		for (segment, _) in segments {
+			let segment = &segment[..segment.len().min(MAX_DICTIONARY_SIZE - size)];
+			size += segment.len();
+			chosen.push(segment);
+
+			if size == MAX_DICTIONARY_SIZE {
+				break
+			}
+		}
+
+		Ok(chosen.into_iter().rev().flatten().copied().collect())
+	}
+}
+
+impl Aerodrome {
+	/// Encodes and compresses the aerodrome with a dictionary from
+	/// [`Config::build_dictionary`], which must be given again to decode it.
+	///
+	/// The encoding is led by an id of the dictionary, so that decoding with
+	/// the wrong one is an error. An empty dictionary is always valid, and
+	/// compresses as if without one.
+	pub fn encode_with_dict(&self, dict: &[u8]) -> Result<Vec<u8>, EncodeError> {
+		fn bincode_error(error: IoError) -> EncodeError {
+			EncodeError::Io {
+				inner: error,
+				index: 0,
+			}
+		}
+
+		let mut writer = DeflateEncoder::new(Vec::new(), Compression::best());
+
+		// prime the window with the dictionary, then discard its output, which
+		// ends on a byte boundary after the flush
+		writer.write_all(dict).map_err(bincode_error)?;
+		writer.flush().map_err(bincode_error)?;
+		let primed = writer.get_ref().len();
+
+		// encoded first, as many small writes to the encoder are slow
+		writer.write_all(&self.encode()?).map_err(bincode_error)?;
+		let compressed = writer.finish().map_err(bincode_error)?;
+
+		Ok([&dictionary_id(dict)[..], &compressed[primed..]].concat())
+	}
+
+	/// Decodes an aerodrome from [`Aerodrome::encode_with_dict`], with the same
+	/// dictionary.
+	pub fn decode_with_dict(
+		data: &[u8],
+		dict: &[u8],
+	) -> Result<Self, DecodeError> {
+		fn bincode_error(error: IoError) -> DecodeError {
+			DecodeError::Io {
+				inner: error,
+				additional: 0,
+			}
+		}
+
+		let Some((id, data)) = data.split_first_chunk::<DICTIONARY_ID_SIZE>()
+		else {
+			return Err(DecodeError::Other("missing dictionary id"))
+		};
+
+		if *id != dictionary_id(dict) {
+			return Err(DecodeError::Other("dictionary mismatch"))
+		}
+
+		// the decoder is primed by the dictionary as uncompressed blocks, which
+		// leave the stream on a byte boundary as the encoder's flush did
+		let mut stored =
+			Vec::with_capacity(dict.len() + dict.len() / 0xffff * 5 + 5);
+		for chunk in dict.chunks(0xffff) {
+			let len = chunk.len() as u16;
+			stored.push(0);
+			stored.extend_from_slice(&len.to_le_bytes());
+			stored.extend_from_slice(&(!len).to_le_bytes());
+			stored.extend_from_slice(chunk);
+		}
+
+		let mut reader = DeflateDecoder::new(Cursor::new(stored).chain(data));
+		std::io::copy(
+			&mut (&mut reader).take(dict.len() as u64),
+			&mut std::io::sink(),
+		)
+		.map_err(bincode_error)?;
+
+		bincode::decode_from_std_read_with_context(
+			&mut reader,
+			BINCODE_DECODE_CONFIG,
+			FormatVersion(Config::VERSION),
+		)
+	}
+}
+
+fn dictionary_id(dict: &[u8]) -> [u8; DICTIONARY_ID_SIZE] {
+	Sha256::digest(dict)[..DICTIONARY_ID_SIZE]
+		.try_into()
+		.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::fixture::{aerodrome, block};
+
+	fn round_trip(aerodrome: &Aerodrome, dict: &[u8]) -> Vec<u8> {
+		let data = aerodrome.encode_with_dict(dict).unwrap();
+		let decoded = Aerodrome::decode_with_dict(&data, dict).unwrap();
+		assert_eq!(decoded.encode().unwrap(), aerodrome.encode().unwrap());
+		data
+	}
+
+	#[test]
+	fn empty_dictionaries_always_work() {
+		let single = Config {
+			name: None,
+			version: None,
+			aerodromes: vec![aerodrome(3, vec![block("B0", &[0, 1], &[])])],
+		};
+		assert_eq!(single.build_dictionary().unwrap(), []);
+
+		// and compress as if without one
+		let data = round_trip(&single.aerodromes[0], &[]);
+		let plain = single.aerodromes[0].compressed_size().unwrap();
+		assert!(data.len().abs_diff(plain + DICTIONARY_ID_SIZE) <= 8);
+	}
+
+	#[test]
+	fn decoding_with_another_dictionary_fails() {
+		let aerodrome = aerodrome(3, Vec::new());
+		let data = aerodrome.encode_with_dict(b"N0N1N2").unwrap();
+
+		for dict in [&b""[..], b"N0N1N3"] {
+			let err = Aerodrome::decode_with_dict(&data, dict).unwrap_err();
+			assert!(matches!(err, DecodeError::Other("dictionary mismatch")));
+		}
+
+		let err = Aerodrome::decode_with_dict(&data[..4], b"N0N1N2").unwrap_err();
+		assert!(matches!(err, DecodeError::Other("missing dictionary id")));
+	}
+
+	#[cfg(feature = "testing")]
+	#[test]
+	fn dictionaries_shrink_configs_of_several_aerodromes() {
+		let aerodromes = (0..6)
+			.map(|i| {
+				let mut aerodrome = Lattice {
+					columns: 2 + i % 2,
+					rows: 2 + i / 3,
+					points: Some(4),
+					seed: i as u64,
+					..Default::default()
+				}
+				.generate();
+				aerodrome.icao = format!("ZZZ{}", char::from(b'A' + i as u8));
+				aerodrome
+			})
+			.collect();
+		let config = Config {
+			name: None,
+			version: None,
+			aerodromes,
+		};
+
+		let dict = config.build_dictionary().unwrap();
+		assert!(!dict.is_empty() && dict.len() <= MAX_DICTIONARY_SIZE);
+
+		let (mut plain, mut shared) = (0, 0);
+		for aerodrome in &config.aerodromes {
+			plain += aerodrome.compressed_size().unwrap();
+			shared += round_trip(aerodrome, &dict).len();
+		}
+
+		// the saving depends on the generator, so is only loosely asserted
+		println!("{plain} bytes alone, {shared} with a shared dictionary");
+		assert!(shared * 10 < plain * 9, "{shared} of {plain}");
+	}
+}
@@ -0,0 +1,945 @@
+use crate::*;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write};
+
+/// metres from a runway within which a holding point is taken to guard it
+const RUNWAY_RADIUS: f32 = 250.0;
+/// metres from the runway centreline at which a stop bar is placed at an entry
+/// without a holding point
+const HOLD_DISTANCE: f32 = 90.0;
+/// metres within which a holding point is taken to be at a runway entry
+const ENTRY_RADIUS: f32 = 150.0;
+/// metres from a taxiway within which a node is taken to lie on it
+const TAXIWAY_RADIUS: f32 = 40.0;
+/// metres between the nodes of a runway taken to be at the same crossing
+const CROSSING_RADIUS: f32 = 400.0;
+/// metres from a runway centreline within which a taxiway is taken to meet it
+const ENTRY_TOLERANCE: f32 = 30.0;
+/// length in metres of the bar drawn for each stop bar
+const BAR_LENGTH: f32 = 30.0;
+/// metres by which the targets drawn for blocks extend beyond their nodes
+const BLOCK_MARGIN: f32 = 40.0;
+
+#[derive(Debug)]
+pub struct EseLoadError {
+	pub message: String,
+	pub line: usize,
+}
+
+impl Display for EseLoadError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}: {}", self.line, self.message)
+	}
+}
+
+impl Error for EseLoadError {}
+
+/// The ground network of an aerodrome, as given in the `[GROUND]` section of
+/// a EuroScope sector extension (`.ese`) file.
+///
+/// The lines read, with coordinates in either the `N051.28.39.000` form of
+/// sector files or decimal degrees, are:
+///
+/// - `HOLD:ICAO:ID:LAT:LON[:RUNWAY]`, a holding point, optionally with the id
+///   of the runway it guards;
+/// - `RUNWAY:ICAO:ID:LAT:LON:LAT:LON`, a runway centreline between its
+///   thresholds;
+/// - `TAXI:ICAO:NAME:LAT:LON[:LAT:LON...]`, a taxiway centreline.
+///
+/// Other lines and sections, and lines for other aerodromes, are ignored, as
+/// are comments from `;`.
+#[derive(Clone, Debug, Default)]
+pub struct GroundNetwork {
+	pub holding_points: Vec<EseHoldingPoint>,
+	pub runways: Vec<EseRunway>,
+	pub taxiways: Vec<EseTaxiway>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EseHoldingPoint {
+	pub id: String,
+	pub runway: Option<String>,
+	pub position: Geo,
+}
+
+#[derive(Clone, Debug)]
+pub struct EseRunway {
+	pub id: String,
+	pub ends: [Geo; 2],
+}
+
+#[derive(Clone, Debug)]
+pub struct EseTaxiway {
+	pub name: String,
+	pub points: Vec<Geo>,
+}
+
+/// A note on part of a skeleton which needs human judgement.
+#[derive(Clone, Debug)]
+pub struct SkeletonNote {
+	/// path to the entity in the source, such as `nodes.A1`
+	pub location: String,
+	pub message: String,
+}
+
+impl Display for SkeletonNote {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.location, self.message)
+	}
+}
+
+/// A starting point for the source of an aerodrome, derived from its ground
+/// network.
+#[derive(Clone, Debug)]
+pub struct Skeleton {
+	pub source: AerodromeSource,
+	pub notes: Vec<SkeletonNote>,
+
+	plane: Plane,
+	/// position and direction of the bar of each node
+	bars: Vec<([f32; 2], [f32; 2])>,
+	/// path of each edge
+	paths: Vec<Vec<[f32; 2]>>,
+	/// indices of the nodes of each block
+	clusters: Vec<Vec<usize>>,
+}
+
+impl GroundNetwork {
+	/// Reads the ground network of aerodrome `icao` from the text of an ESE
+	/// file.
+	pub fn load_ese(text: &str, icao: &str) -> Result<Self, EseLoadError> {
+		let mut network = Self::default();
+		let mut ground = false;
+
+		for (i, line) in text.lines().enumerate() {
+			let error = |message: String| EseLoadError {
+				message,
+				line: i + 1,
+			};
+
+			let line = line.split(';').next().unwrap_or_default().trim();
+			if line.is_empty() {
+				continue
+			}
+
+			if line.starts_with('[') {
+				ground = line.eq_ignore_ascii_case("[GROUND]");
+				continue
+			}
+
+			let parts = line.split(':').map(str::trim).collect::<Vec<_>>();
+			if !ground || parts.len() < 3 || !parts[1].eq_ignore_ascii_case(icao) {
+				continue
+			}
+
+			let coords = |parts: &[&str]| {
+				parts
+					.chunks(2)
+					.map(|pair| match pair {
+						[lat, lon] => parse_geo(lat, lon).map_err(&error),
+						_ => Err(error("odd number of coordinates".into())),
+					})
+					.collect::<Result<Vec<_>, _>>()
+			};
+
+			match parts[0].to_ascii_uppercase().as_str() {
+				"HOLD" => {
+					let (position, runway) = match &parts[3..] {
+						[lat, lon] => (coords(&[lat, lon])?, None),
+						[lat, lon, runway] => (coords(&[lat, lon])?, Some(runway)),
+						_ => {
+							return Err(error("expected HOLD:ICAO:ID:LAT:LON[:RUNWAY]".into()))
+						},
+					};
+
+					network.holding_points.push(EseHoldingPoint {
+						id: parts[2].into(),
+						runway: runway.map(|runway| (*runway).into()),
+						position: position[0],
+					});
+				},
+				"RUNWAY" => {
+					let [a, b] = coords(&parts[3..])?[..] else {
+						return Err(error("expected RUNWAY:ICAO:ID:LAT:LON:LAT:LON".into()))
+					};
+
+					network.runways.push(EseRunway {
+						id: parts[2].into(),
+						ends: [a, b],
+					});
+				},
+				"TAXI" => {
+					let points = coords(&parts[3..])?;
+					if points.len() < 2 {
+						return Err(error("taxiway needs at least two points".into()))
+					}
+
+					network.taxiways.push(EseTaxiway {
+						name: parts[2].into(),
+						points,
+					});
+				},
+				_ => (),
+			}
+		}
+
+		Ok(network)
+	}
+
+	/// Derives a skeleton for aerodrome `icao` from the ground network.
+	///
+	/// Nodes are placed at holding points, and at each side of each runway
+	/// entry without one. Edges join consecutive nodes along each taxiway, and
+	/// a block is made for each cluster of nodes about a runway with nodes on
+	/// both sides of it. A single profile lights the edges from each node
+	/// while its stop bar is off. Everything which needs checking has a note.
+	pub fn skeleton(&self, icao: &str) -> Skeleton {
+		let origin = (self.runways.iter().map(|runway| runway.ends[0]))
+			.chain(self.holding_points.iter().map(|hold| hold.position))
+			.chain(self.taxiways.iter().map(|taxiway| taxiway.points[0]))
+			.next()
+			.unwrap_or(Geo { lat: 0.0, lon: 0.0 });
+		let plane = Plane::new(origin);
+
+		let runways = (self.runways.iter())
+			.map(|runway| runway.ends.map(|end| plane.project(end)))
+			.collect::<Vec<_>>();
+		let taxiways = (self.taxiways.iter())
+			.map(|taxiway| {
+				(taxiway.points.iter())
+					.map(|point| plane.project(*point))
+					.collect::<Vec<_>>()
+			})
+			.collect::<Vec<_>>();
+
+		let mut notes = Vec::new();
+		let mut note = |location: String, message: &str| {
+			notes.push(SkeletonNote {
+				location,
+				message: message.into(),
+			})
+		};
+
+		// each node, with its position and the runway it guards
+		let mut nodes = Vec::<(String, [f32; 2], Option<usize>)>::new();
+
+		for hold in &self.holding_points {
+			let id = unique(nodes.iter().map(|node| &node.0), &hold.id);
+			let position = plane.project(hold.position);
+			let runway = match &hold.runway {
+				Some(guarded) => {
+					let runway =
+						(self.runways.iter()).position(|runway| &runway.id == guarded);
+					if runway.is_none() {
+						note(format!("nodes.{id}"), "guards an unknown runway");
+					}
+					runway
+				},
+				None => nearest(&runways, position)
+					.filter(|(_, distance)| *distance <= RUNWAY_RADIUS)
+					.map(|(runway, _)| runway),
+			};
+
+			if hold.runway.is_none() && runway.is_none() {
+				note(
+					format!("nodes.{id}"),
+					"not near any runway, so may not need a stop bar",
+				);
+			}
+
+			nodes.push((id, position, runway));
+		}
+
+		// runway entries without holding points
+		for (t, taxiway) in taxiways.iter().enumerate() {
+			for (r, runway) in runways.iter().enumerate() {
+				for entry in entries(taxiway, runway) {
+					let direction = normalise(sub(runway[1], runway[0]));
+					let across = [-direction[1], direction[0]];
+
+					for side in [1.0, -1.0] {
+						// only where the taxiway continues to that side
+						let continues = taxiway.iter().any(|point| {
+							dot(sub(*point, entry), across) * side > HOLD_DISTANCE / 2.0
+						});
+						let position = add(entry, scale(across, side * HOLD_DISTANCE));
+						let guarded = nodes.iter().any(|(_, other, runway)| {
+							*runway == Some(r) && distance(*other, position) <= ENTRY_RADIUS
+						});
+						if !continues || guarded {
+							continue
+						}
+
+						let id = unique(
+							nodes.iter().map(|node| &node.0),
+							&format!("{}-{}", self.taxiways[t].name, self.runways[r].id),
+						);
+						note(
+							format!("nodes.{id}"),
+							"runway entry without a holding point; move the stop bar to where \
+							 it is painted",
+						);
+						nodes.push((id, position, Some(r)));
+					}
+				}
+			}
+		}
+
+		// edges between consecutive nodes along each taxiway
+		let mut edges = Vec::<(String, usize, usize, Vec<[f32; 2]>)>::new();
+
+		for (t, taxiway) in taxiways.iter().enumerate() {
+			let mut along = (nodes.iter().enumerate())
+				.filter_map(|(n, (_, position, _))| {
+					let (offset, distance) = locate(taxiway, *position);
+					(distance <= TAXIWAY_RADIUS).then_some((offset, n))
+				})
+				.collect::<Vec<_>>();
+			along.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+			let mut count = 0;
+
+			for pair in along.windows(2) {
+				let [(from_offset, from), (to_offset, to)] = [pair[0], pair[1]];
+				if from == to || edges.iter().any(|edge| (edge.1, edge.2) == (from, to))
+				{
+					continue
+				}
+
+				count += 1;
+				let id = unique(
+					edges.iter().map(|edge| &edge.0),
+					&format!("{}.{count}", self.taxiways[t].name),
+				);
+				let path = slice(taxiway, from_offset, to_offset);
+				edges.push((id, from, to, path));
+			}
+		}
+
+		for (n, (id, ..)) in nodes.iter().enumerate() {
+			if !edges.iter().any(|edge| edge.1 == n || edge.2 == n) {
+				note(
+					format!("nodes.{id}"),
+					"not joined to another node by a taxiway, so has no edges",
+				);
+			}
+		}
+
+		// blocks for the crossings of each runway
+		let mut clusters = Vec::new();
+
+		for (r, runway) in runways.iter().enumerate() {
+			let guarding = (0..nodes.len())
+				.filter(|n| nodes[*n].2 == Some(r))
+				.collect::<Vec<_>>();
+
+			for cluster in cluster(&guarding, |a, b| {
+				distance(nodes[a].1, nodes[b].1) <= CROSSING_RADIUS
+			}) {
+				let side = |n: &usize| {
+					cross(sub(runway[1], runway[0]), sub(nodes[*n].1, runway[0])) > 0.0
+				};
+				if cluster.iter().all(side) || !cluster.iter().any(side) {
+					continue
+				}
+
+				clusters.push(cluster);
+			}
+		}
+
+		let blocks = (clusters.iter().enumerate())
+			.map(|(i, cluster)| {
+				let runway = nodes[cluster[0]]
+					.2
+					.map_or("", |r| self.runways[r].id.as_str());
+				BlockSource {
+					id: format!("{runway}-{i}"),
+					nodes: cluster.iter().map(|n| nodes[*n].0.clone()).collect(),
+					edges: (edges.iter())
+						.filter(|edge| {
+							cluster.contains(&edge.1) && cluster.contains(&edge.2)
+						})
+						.map(|edge| edge.0.clone())
+						.collect(),
+					non_routes: Vec::new(),
+					stands: Vec::new(),
+					input_order: None,
+				}
+			})
+			.collect::<Vec<_>>();
+
+		for block in &blocks {
+			note(
+				format!("blocks.{}", block.id),
+				"check the nodes and edges of the crossing, and add any non-routes",
+			);
+		}
+
+		let profile = ProfileSource {
+			id: "default".into(),
+			name: "Default".into(),
+			tags: Vec::new(),
+			nodes: (nodes.iter())
+				.map(|(id, ..)| {
+					(id.clone(), NodeConditionSource::Direct { reset: None })
+				})
+				.collect(),
+			edges: (edges.iter())
+				.map(|(id, from, ..)| {
+					(
+						id.clone(),
+						EdgeConditionSource::Direct(vec![NodeConjunctionSource {
+							positive: Vec::new(),
+							negative: vec![nodes[*from].0.clone()],
+						}]),
+					)
+				})
+				.collect(),
+			blocks: BTreeMap::new(),
+			presets: Vec::new(),
+		};
+
+		if !edges.is_empty() {
+			note(
+				"profiles.default".into(),
+				"each edge is lit while the stop bar at its first node is off; check \
+				 the direction of travel of each",
+			);
+		}
+
+		let bars = (nodes.iter())
+			.map(|(_, position, _)| {
+				let direction = (taxiways.iter())
+					.filter_map(|taxiway| {
+						let (offset, distance) = locate(taxiway, *position);
+						(distance <= TAXIWAY_RADIUS).then(|| tangent(taxiway, offset))
+					})
+					.next()
+					.unwrap_or([1.0, 0.0]);
+				(*position, direction)
+			})
+			.collect();
+
+		let source = AerodromeSource {
+			icao: icao.into(),
+			elements: Vec::new(),
+			nodes: (nodes.iter())
+				.map(|(id, ..)| NodeSource {
+					id: id.clone(),
+					scratchpad: None,
+					parent: None,
+					input_order: None,
+				})
+				.collect(),
+			edges: (edges.iter())
+				.map(|(id, ..)| EdgeSource {
+					id: id.clone(),
+					name: None,
+				})
+				.collect(),
+			blocks,
+			holding_points: Vec::new(),
+			profiles: vec![profile],
+			strings: BTreeMap::new(),
+		};
+
+		Skeleton {
+			source,
+			notes,
+			plane,
+			bars,
+			paths: edges.into_iter().map(|edge| edge.3).collect(),
+			clusters,
+		}
+	}
+}
+
+impl Skeleton {
+	/// Draws the skeleton as a topsky geo map, with a bar across the taxiway
+	/// and a target for each node, lines along the taxiway for each edge, and
+	/// a target about the nodes of each block.
+	pub fn to_topsky(&self) -> String {
+		let mut topsky = String::new();
+
+		let _ = writeln!(topsky, "// skeleton of {}", self.source.icao);
+		topsky += "COLORDEF:off:64:64:64\n";
+		topsky += "COLORDEF:stopbar:255:0:0\n";
+		topsky += "COLORDEF:leadon:0:255:0\n";
+		topsky += "GEO\n";
+
+		let coords = |topsky: &mut String, points: &[[f32; 2]]| {
+			for point in points {
+				let geo = self.plane.unproject(*point);
+				let _ = writeln!(topsky, "COORD:{:.6}:{:.6}", geo.lat, geo.lon);
+			}
+		};
+
+		for (node, (position, direction)) in
+			self.source.nodes.iter().zip(&self.bars)
+		{
+			let across = scale([-direction[1], direction[0]], BAR_LENGTH / 2.0);
+			let bar = [sub(*position, across), add(*position, across)];
+
+			for (display, color) in [("OFF", "off"), ("ON", "stopbar")] {
+				let _ = writeln!(topsky, "NODE:{}:{display}", node.id);
+				let _ = writeln!(topsky, "COLOR:{color}\nSTYLE:solid:3");
+				coords(&mut topsky, &bar);
+				topsky += "COORDLINE\n";
+			}
+
+			let _ = writeln!(topsky, "NODE:{}:TARGET", node.id);
+			coords(&mut topsky, &square(*position, BAR_LENGTH / 2.0));
+			let _ = writeln!(topsky, "TOOLTIP:{}\nCOORDTARGET", node.id);
+		}
+
+		for (edge, path) in self.source.edges.iter().zip(&self.paths) {
+			for (display, color) in [("OFF", "off"), ("ON", "leadon")] {
+				let _ = writeln!(topsky, "EDGE:{}:{display}", edge.id);
+				let _ = writeln!(topsky, "COLOR:{color}\nSTYLE:solid:2");
+				coords(&mut topsky, path);
+				topsky += "COORDLINE\n";
+			}
+		}
+
+		for (block, cluster) in self.source.blocks.iter().zip(&self.clusters) {
+			let (mut min, mut max) = ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]);
+			for n in cluster {
+				let (position, _) = self.bars[*n];
+				min = [min[0].min(position[0]), min[1].min(position[1])];
+				max = [max[0].max(position[0]), max[1].max(position[1])];
+			}
+
+			let (min, max) =
+				(sub(min, [BLOCK_MARGIN; 2]), add(max, [BLOCK_MARGIN; 2]));
+			let _ = writeln!(topsky, "BLOCK:{}:TARGET", block.id);
+			coords(&mut topsky, &[min, [max[0], min[1]], max, [min[0], max[1]]]);
+			let _ = writeln!(topsky, "TOOLTIP:{}\nCOORDTARGET", block.id);
+		}
+
+		topsky
+	}
+}
+
+/// A local projection in metres, east and north of an origin, which is
+/// accurate enough over an aerodrome.
+#[derive(Clone, Copy, Debug)]
+struct Plane {
+	origin: Geo,
+	/// metres per degree of longitude
+	scale: f32,
+}
+
+impl Plane {
+	fn new(origin: Geo) -> Self {
+		Self {
+			origin,
+			scale: METRES_PER_DEGREE * origin.lat.to_radians().cos(),
+		}
+	}
+
+	fn project(&self, geo: Geo) -> [f32; 2] {
+		[
+			(geo.lon - self.origin.lon) * self.scale,
+			(geo.lat - self.origin.lat) * METRES_PER_DEGREE,
+		]
+	}
+
+	fn unproject(&self, point: [f32; 2]) -> Geo {
+		Geo {
+			lat: self.origin.lat + point[1] / METRES_PER_DEGREE,
+			lon: self.origin.lon + point[0] / self.scale,
+		}
+	}
+}
+
+fn parse_geo(lat: &str, lon: &str) -> Result<Geo, String> {
+	Ok(Geo {
+		lat: parse_angle(lat, 'N', 'S')?,
+		lon: parse_angle(lon, 'E', 'W')?,
+	})
+}
+
+/// Returns `id`, or it suffixed to be unlike any of the ids taken.
+fn unique<'a>(
+	taken: impl Iterator<Item = &'a String> + Clone,
+	id: &str,
+) -> String {
+	let free = |id: &str| !taken.clone().any(|taken| taken == id);
+	if free(id) {
+		return id.into()
+	}
+
+	(2..)
+		.map(|i| format!("{id}-{i}"))
+		.find(|id| free(id))
+		.unwrap()
+}
+
+/// Groups the items into clusters, each connected by `linked`.
+fn cluster(
+	items: &[usize],
+	linked: impl Fn(usize, usize) -> bool,
+) -> Vec<Vec<usize>> {
+	let mut clusters = Vec::<Vec<usize>>::new();
+
+	for &item in items {
+		let (joined, mut rest) =
+			clusters.into_iter().partition::<Vec<_>, _>(|cluster| {
+				cluster.iter().any(|other| linked(item, *other))
+			});
+
+		let mut merged = joined.into_iter().flatten().collect::<Vec<_>>();
+		merged.push(item);
+		merged.sort();
+		rest.push(merged);
+		clusters = rest;
+	}
+
+	clusters.sort();
+	clusters
+}
+
+/// Returns the index of and distance to the runway nearest to the point.
+fn nearest(runways: &[[[f32; 2]; 2]], point: [f32; 2]) -> Option<(usize, f32)> {
+	(runways.iter().enumerate())
+		.map(|(r, runway)| (r, locate(runway, point).1))
+		.min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Returns the points at which the taxiway meets the runway, either crossing
+/// it or ending on it.
+fn entries(taxiway: &[[f32; 2]], runway: &[[f32; 2]; 2]) -> Vec<[f32; 2]> {
+	let mut entries = Vec::new();
+
+	for segment in taxiway.windows(2) {
+		let (a, b) = (segment[0], segment[1]);
+		let (r, s) = (sub(b, a), sub(runway[1], runway[0]));
+		let denominator = cross(r, s);
+		if denominator == 0.0 {
+			continue
+		}
+
+		let t = cross(sub(runway[0], a), s) / denominator;
+		let u = cross(sub(runway[0], a), r) / denominator;
+		if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+			entries.push(add(a, scale(r, t)));
+		}
+	}
+
+	for end in [taxiway[0], taxiway[taxiway.len() - 1]] {
+		let (offset, distance) = locate(runway, end);
+		if distance <= ENTRY_TOLERANCE {
+			entries.push(slice(runway, offset, offset)[0]);
+		}
+	}
+
+	let mut unique = Vec::<[f32; 2]>::new();
+	for entry in entries {
+		if unique
+			.iter()
+			.all(|other| self::distance(*other, entry) > ENTRY_RADIUS)
+		{
+			unique.push(entry);
+		}
+	}
+	unique
+}
+
+/// Returns the distance along the path of the point on it nearest to the
+/// point given, and the distance between them.
+fn locate(path: &[[f32; 2]], point: [f32; 2]) -> (f32, f32) {
+	let mut start = 0.0;
+	let mut best = (0.0, f32::INFINITY);
+
+	for segment in path.windows(2) {
+		let (a, b) = (segment[0], segment[1]);
+		let length = distance(a, b);
+		let t = if length > 0.0 {
+			(dot(sub(point, a), sub(b, a)) / (length * length)).clamp(0.0, 1.0)
+		} else {
+			0.0
+		};
+
+		let distance = distance(add(a, scale(sub(b, a), t)), point);
+		if distance < best.1 {
+			best = (start + t * length, distance);
+		}
+		start += length;
+	}
+
+	best
+}
+
+/// Returns the part of the path between two distances along it.
+fn slice(path: &[[f32; 2]], from: f32, to: f32) -> Vec<[f32; 2]> {
+	let mut points = Vec::new();
+	let mut start = 0.0;
+
+	for segment in path.windows(2) {
+		let (a, b) = (segment[0], segment[1]);
+		let length = distance(a, b);
+		let end = start + length;
+		let at = |offset: f32| {
+			let t = if length > 0.0 {
+				(offset - start) / length
+			} else {
+				0.0
+			};
+			add(a, scale(sub(b, a), t))
+		};
+
+		if points.is_empty() && from <= end {
+			points.push(at(from));
+		}
+		if !points.is_empty() {
+			if to <= end {
+				points.push(at(to));
+				break
+			}
+			points.push(b);
+		}
+
+		start = end;
+	}
+
+	points
+}
+
+/// Returns the direction of the path at a distance along it.
+fn tangent(path: &[[f32; 2]], offset: f32) -> [f32; 2] {
+	let mut start = 0.0;
+	for segment in path.windows(2) {
+		let length = distance(segment[0], segment[1]);
+		if offset <= start + length {
+			return normalise(sub(segment[1], segment[0]))
+		}
+		start += length;
+	}
+
+	normalise(sub(path[path.len() - 1], path[path.len() - 2]))
+}
+
+fn square(centre: [f32; 2], half: f32) -> [[f32; 2]; 4] {
+	[[-half, -half], [half, -half], [half, half], [-half, half]]
+		.map(|corner| add(centre, corner))
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+	[a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+	[a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], k: f32) -> [f32; 2] {
+	[a[0] * k, a[1] * k]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+	a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+	a[0] * b[1] - a[1] * b[0]
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+	dot(sub(a, b), sub(a, b)).sqrt()
+}
+
+fn normalise(a: [f32; 2]) -> [f32; 2] {
+	let length = dot(a, a).sqrt();
+	if length > 0.0 {
+		scale(a, 1.0 / length)
+	} else {
+		[1.0, 0.0]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const EXCERPT: &str = include_str!("../tests/fixtures/egxx.ese");
+
+	fn skeleton() -> Skeleton {
+		GroundNetwork::load_ese(EXCERPT, "EGXX")
+			.unwrap()
+			.skeleton("EGXX")
+	}
+
+	fn ids<T>(items: &[T], id: impl Fn(&T) -> &str) -> Vec<&str> {
+		items.iter().map(id).collect()
+	}
+
+	fn near(a: Geo, b: Geo) -> bool {
+		(a.lat - b.lat).abs() < 1e-5 && (a.lon - b.lon).abs() < 1e-5
+	}
+
+	#[test]
+	fn only_the_ground_network_of_the_aerodrome_is_read() {
+		let network = GroundNetwork::load_ese(EXCERPT, "egxx").unwrap();
+
+		assert_eq!(
+			ids(&network.holding_points, |hold| &hold.id),
+			["A1", "A2", "C1", "S1", "X1"],
+		);
+		assert_eq!(ids(&network.runways, |runway| &runway.id), ["08R"]);
+		assert_eq!(ids(&network.taxiways, |taxiway| &taxiway.name), ["A", "B"]);
+
+		let [a1, .., c1, s1, _] = &network.holding_points[..] else {
+			unreachable!()
+		};
+		assert_eq!(a1.runway.as_deref(), Some("08R"));
+		assert_eq!(c1.runway, None);
+		assert!(near(
+			a1.position,
+			Geo {
+				lat: 51.0 + 8.0 / 60.0 + 57.089 / 3600.0,
+				lon: -(10.0 / 60.0 + 58.223 / 3600.0),
+			}
+		));
+		assert!(near(
+			s1.position,
+			Geo {
+				lat: 51.141017,
+				lon: -0.175679,
+			}
+		));
+
+		let network = GroundNetwork::load_ese(EXCERPT, "EGYY").unwrap();
+		assert_eq!(ids(&network.holding_points, |hold| &hold.id), ["Y1"]);
+		assert!(network.runways.is_empty());
+	}
+
+	#[test]
+	fn malformed_lines_are_errors_with_their_line() {
+		let error = |text: &str| {
+			let error = GroundNetwork::load_ese(text, "EGXX").unwrap_err();
+			(error.line, error.message)
+		};
+
+		let (line, message) =
+			error("[GROUND]\n\nRUNWAY:EGXX:08R:N051.09.00.000:W000.11.24.000\n");
+		assert_eq!(line, 3);
+		assert!(message.starts_with("expected RUNWAY"), "{message}");
+
+		let (line, message) = error("[GROUND]\nTAXI:EGXX:A:51.15:-0.19\n");
+		assert_eq!((line, &*message), (2, "taxiway needs at least two points"));
+
+		let (line, _) = error("[GROUND]\nHOLD:EGXX:A1:N091.00.00.000:W000.00.00\n");
+		assert_eq!(line, 2);
+
+		// nor are they read for other aerodromes
+		let text = "[GROUND]\nTAXI:EGYY:A:51.15:-0.19\n";
+		assert!(GroundNetwork::load_ese(text, "EGXX").is_ok());
+	}
+
+	#[test]
+	fn nodes_are_at_holds_and_unguarded_entries() {
+		let skeleton = skeleton();
+		let source = &skeleton.source;
+
+		assert_eq!(
+			ids(&source.nodes, |node| &node.id),
+			["A1", "A2", "C1", "S1", "X1", "B-08R"],
+		);
+
+		// the entry is on the side of the runway the taxiway comes from
+		let (position, direction) = skeleton.bars[5];
+		assert!((position[0] - 1500.0).abs() < 1.0, "{position:?}");
+		assert!((position[1] + HOLD_DISTANCE).abs() < 1.0, "{position:?}");
+
+		// bars are across the taxiways, or east-west if on none
+		assert!(direction[0].abs() < 1e-3, "{direction:?}");
+		assert_eq!(skeleton.bars[3].1, [1.0, 0.0]);
+	}
+
+	#[test]
+	fn edges_and_blocks_are_along_taxiways_and_across_runways() {
+		let source = skeleton().source;
+
+		assert_eq!(ids(&source.edges, |edge| &edge.id), ["A.1"]);
+
+		// only the crossing with nodes either side is a block
+		let [block] = &source.blocks[..] else {
+			panic!("{:?}", source.blocks);
+		};
+		assert_eq!(block.id, "08R-0");
+		assert_eq!(block.nodes, ["A1", "A2"]);
+		assert_eq!(block.edges, ["A.1"]);
+
+		let profile = &source.profiles[0];
+		assert_eq!(profile.nodes.len(), 6);
+		let EdgeConditionSource::Direct(conjunctions) = &profile.edges["A.1"]
+		else {
+			panic!("{:?}", profile.edges);
+		};
+		assert_eq!(conjunctions[0].negative, ["A1"]);
+		assert!(conjunctions[0].positive.is_empty());
+	}
+
+	#[test]
+	fn notes_are_on_what_needs_judgement() {
+		let skeleton = skeleton();
+		let notes = (skeleton.notes.iter())
+			.map(|note| (note.location.as_str(), note.message.as_str()))
+			.collect::<Vec<_>>();
+
+		let not_joined = "not joined to another node by a taxiway, so has no edges";
+		assert_eq!(notes, [
+			(
+				"nodes.S1",
+				"not near any runway, so may not need a stop bar"
+			),
+			("nodes.X1", "guards an unknown runway"),
+			(
+				"nodes.B-08R",
+				"runway entry without a holding point; move the stop bar to where it \
+				 is painted",
+			),
+			("nodes.C1", not_joined),
+			("nodes.S1", not_joined),
+			("nodes.X1", not_joined),
+			("nodes.B-08R", not_joined),
+			(
+				"blocks.08R-0",
+				"check the nodes and edges of the crossing, and add any non-routes",
+			),
+			(
+				"profiles.default",
+				"each edge is lit while the stop bar at its first node is off; check \
+				 the direction of travel of each",
+			),
+		]);
+	}
+
+	#[cfg(feature = "topsky")]
+	#[test]
+	fn topsky_draws_every_entity() {
+		let skeleton = skeleton();
+		let maps = Maps::load_topsky(&skeleton.to_topsky()).unwrap();
+		let geo_map = maps.geo_map.unwrap();
+
+		assert_eq!(maps.nodes, ids(&skeleton.source.nodes, |node| &node.id));
+		assert_eq!(maps.edges, ["A.1"]);
+		assert_eq!(maps.blocks, ["08R-0"]);
+
+		for node in &geo_map.nodes {
+			assert_eq!((node.off.len(), node.on.len()), (1, 1));
+			assert_eq!(node.target.polygons.len(), 1);
+		}
+		assert_eq!(geo_map.edges[0].off.len(), 1);
+		assert_eq!(geo_map.edges[0].on.len(), 1);
+
+		// the edge runs between the holding points, north along the taxiway
+		let path = &geo_map.edges[0].on[0].points;
+		let a1 = skeleton.plane.unproject(skeleton.bars[0].0);
+		let a2 = skeleton.plane.unproject(skeleton.bars[1].0);
+		assert!(near(path[0].geo, a1) && near(path[path.len() - 1].geo, a2));
+		assert_eq!(geo_map.blocks[0].tooltip.as_deref(), Some("08R-0"));
+	}
+}
@@ -0,0 +1,226 @@
+use crate::*;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// characters which may not appear in ids written in expressions
+const RESERVED: &[char] = &['|', '&', '!', '=', '>'];
+
+/// A term of an expression as written, naming entities by id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TermText<'a> {
+	Node(&'a str),
+	Edge(&'a str),
+	Block(&'a str, PredicateText<'a>),
+}
+
+/// A block predicate as written, naming nodes by id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PredicateText<'a> {
+	Clear,
+	Relax,
+	AnyRoute,
+	Route(&'a str, &'a str),
+}
+
+/// An error in the syntax of an expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpressionError {
+	pub message: String,
+	/// of the start of the offending part, from 1
+	pub column: usize,
+}
+
+impl Display for ExpressionError {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		write!(f, "column {}: {}", self.column, self.message)
+	}
+}
+
+impl Error for ExpressionError {}
+
+impl ConditionExpression {
+	/// Returns whether the expression holds, given whether each term does.
+	pub fn evaluate(&self, term: &impl Fn(&ConditionTerm) -> bool) -> bool {
+		self.disjunction.iter().any(|conjunction| {
+			conjunction.positive.iter().all(term)
+				&& !conjunction.negative.iter().any(term)
+		})
+	}
+
+	/// Returns every term of the expression, positive or negative.
+	pub fn terms(&self) -> impl Iterator<Item = &ConditionTerm> {
+		(self.disjunction.iter()).flat_map(|conjunction| {
+			conjunction.positive.iter().chain(&conjunction.negative)
+		})
+	}
+
+	/// Parses an expression written as terms joined by `&`, any of which may be
+	/// negated with `!`, and those conjunctions joined by `|`. `always` is a
+	/// conjunction of no terms, and `never` an expression of no conjunctions.
+	///
+	/// Terms are written as `node:ID`, or just `ID`, for a node being on,
+	/// `edge:ID` for an edge being on, and `block:ID=PREDICATE` for the state of
+	/// a block, where the predicate is `clear`, `relax`, `route` for any route,
+	/// or `FROM>TO` for the route between the parent nodes. Each term is passed
+	/// to `resolve`, which finds the entities named.
+	pub fn parse(
+		text: &str,
+		mut resolve: impl FnMut(TermText) -> ConditionTerm,
+	) -> Result<Self, ExpressionError> {
+		let column =
+			|part: &str| part.as_ptr() as usize - text.as_ptr() as usize + 1;
+		let error = |part: &str, message: String| ExpressionError {
+			message,
+			column: column(part),
+		};
+
+		if text.trim() == "never" {
+			return Ok(Self::default())
+		}
+
+		let mut expression = Self::default();
+		for conjunction in text.split('|') {
+			if conjunction.trim() == "always" {
+				expression.disjunction.push(ConditionConjunction::default());
+				continue
+			}
+
+			let mut parsed = ConditionConjunction::default();
+			for literal in conjunction.split('&') {
+				let literal = literal.trim();
+				if literal.is_empty() {
+					return Err(error(literal, "missing term".into()))
+				}
+
+				let (negated, term) = match literal.strip_prefix('!') {
+					Some(term) => (true, term.trim_start()),
+					None => (false, literal),
+				};
+
+				let term = parse_term(term).map_err(|message| error(term, message))?;
+				if negated {
+					parsed.negative.push(resolve(term));
+				} else {
+					parsed.positive.push(resolve(term));
+				}
+			}
+
+			expression.disjunction.push(parsed);
+		}
+
+		Ok(expression)
+	}
+
+	/// Writes the expression in the form read by [`ConditionExpression::parse`],
+	/// naming entities by their ids in the aerodrome.
+	pub fn to_text(&self, aerodrome: &Aerodrome) -> String {
+		if self.disjunction.is_empty() {
+			return "never".into()
+		}
+
+		let term = |term: &ConditionTerm| term_text(aerodrome, term);
+
+		(self.disjunction.iter())
+			.map(|conjunction| {
+				let positive = conjunction.positive.iter().map(term);
+				let negative =
+					(conjunction.negative.iter()).map(|t| format!("!{}", term(t)));
+				let terms = positive.chain(negative).collect::<Vec<_>>();
+
+				if terms.is_empty() {
+					"always".into()
+				} else {
+					terms.join(" & ")
+				}
+			})
+			.collect::<Vec<_>>()
+			.join(" | ")
+	}
+}
+
+fn parse_term(term: &str) -> Result<TermText<'_>, String> {
+	let id = |id: &str| {
+		if id.is_empty() {
+			Err("missing id".to_string())
+		} else if let Some(c) = id
+			.chars()
+			.find(|c| c.is_whitespace() || RESERVED.contains(c))
+		{
+			Err(format!("unexpected {c:?} in {id}"))
+		} else {
+			Ok(())
+		}
+	};
+
+	if ["always", "never"].contains(&term) {
+		Err(format!("{term} must stand alone"))
+	} else if let Some(edge) = term.strip_prefix("edge:") {
+		id(edge)?;
+		Ok(TermText::Edge(edge))
+	} else if let Some(block) = term.strip_prefix("block:") {
+		let Some((block, predicate)) = block.split_once('=') else {
+			return Err(format!("no predicate for block {block}"))
+		};
+
+		id(block)?;
+		let predicate = match predicate {
+			"clear" => PredicateText::Clear,
+			"relax" => PredicateText::Relax,
+			"route" => PredicateText::AnyRoute,
+			route => {
+				let Some((from, to)) = route.split_once('>') else {
+					return Err(format!("unknown block predicate {route}"))
+				};
+
+				id(from)?;
+				id(to)?;
+				PredicateText::Route(from, to)
+			},
+		};
+
+		Ok(TermText::Block(block, predicate))
+	} else {
+		let node = term.strip_prefix("node:").unwrap_or(term);
+		id(node)?;
+		Ok(TermText::Node(node))
+	}
+}
+
+fn term_text(aerodrome: &Aerodrome, term: &ConditionTerm) -> String {
+	let node = |node: &Ref<Node>| {
+		(aerodrome.nodes.get(node.0)).map_or("?", |node| node.id.as_str())
+	};
+
+	match term {
+		ConditionTerm::Node(i) => {
+			let id = node(i);
+			// ids which would read as something else are marked as nodes
+			let ambiguous = ["never", "always"].contains(&id)
+				|| ["node:", "edge:", "block:"]
+					.iter()
+					.any(|p| id.starts_with(p));
+			if ambiguous {
+				format!("node:{id}")
+			} else {
+				id.into()
+			}
+		},
+		ConditionTerm::Edge(edge) => {
+			let id = (aerodrome.edges.get(edge.0)).map_or("?", |edge| &edge.id);
+			format!("edge:{id}")
+		},
+		ConditionTerm::Block(block, predicate) => {
+			let id = (aerodrome.blocks.get(block.0)).map_or("?", |block| &block.id);
+			let predicate = match predicate {
+				BlockPredicate::Clear => "clear".into(),
+				BlockPredicate::Relax => "relax".into(),
+				BlockPredicate::AnyRoute => "route".into(),
+				BlockPredicate::Route((from, to)) => {
+					format!("{}>{}", node(from), node(to))
+				},
+			};
+			format!("block:{id}={predicate}")
+		},
+	}
+}
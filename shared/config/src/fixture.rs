@@ -0,0 +1,56 @@
+//! Small aerodromes built by hand, for tests.
+
+use super::*;
+
+pub fn node(id: &str) -> Node {
+	Node {
+		id: id.into(),
+		scratchpad: None,
+		parent: None,
+		input_order: None,
+	}
+}
+
+pub fn block(id: &str, nodes: &[usize], stands: &[&str]) -> Block {
+	Block {
+		id: id.into(),
+		nodes: nodes.iter().copied().map(Ref::from).collect(),
+		edges: Vec::new(),
+		non_routes: Vec::new(),
+		stands: stands.iter().map(|stand| stand.to_string()).collect(),
+		input_order: None,
+	}
+}
+
+/// Builds an aerodrome of router nodes `N0`, `N1` and so on, and of the
+/// blocks, with one profile in which no block resets.
+pub fn aerodrome(nodes: usize, blocks: Vec<Block>) -> Aerodrome {
+	let profile = Profile {
+		id: "default".into(),
+		name: "Default".into(),
+		tags: Vec::new(),
+		nodes: vec![NodeCondition::Router { sticky: false }; nodes],
+		edges: Vec::new(),
+		blocks: vec![
+			BlockCondition {
+				reset: ResetCondition::None,
+			};
+			blocks.len()
+		],
+		presets: Vec::new(),
+	};
+
+	Aerodrome {
+		icao: "ZZZZ".into(),
+		elements: Vec::new(),
+		nodes: (0..nodes).map(|i| node(&format!("N{i}"))).collect(),
+		edges: Vec::new(),
+		blocks,
+		holding_points: Vec::new(),
+		profiles: vec![profile],
+		strings: None,
+		geo_map: None,
+		maps: Vec::new(),
+		styles: Vec::new(),
+	}
+}
@@ -0,0 +1,971 @@
+use super::*;
+
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+
+// The content hash is documented as stable across crate versions, so the
+// canonical form written here must not change for data which has not. Each
+// value is written field by field in a fixed layout: integers little-endian at
+// a fixed width, lengths as u64, enum variants as explicitly numbered tags,
+// and floats by their bits with zeros and NaNs normalised. New fields must be
+// written so that existing data hashes as before, for instance by writing
+// nothing when they hold their default value.
+
+impl Config {
+	/// Returns a hash identifying the contents of the config.
+	///
+	/// The hash covers the structure rather than its encoding, so it does not
+	/// depend on the compression or encoding used, on the order of aerodromes
+	/// or other lists whose order has no meaning, or on how styles are shared
+	/// between paths. It is stable across crate versions for unchanged data.
+	pub fn content_hash(&self) -> [u8; 32] {
+		let mut hashes = self
+			.aerodromes
+			.iter()
+			.map(Aerodrome::content_hash)
+			.collect::<Vec<_>>();
+		hashes.sort();
+
+		let mut writer = Writer::new(&[]);
+		writer.bytes(b"bars-config\0");
+		self.name.write(&mut writer);
+		self.version.write(&mut writer);
+		writer.len(hashes.len());
+		hashes.iter().for_each(|hash| writer.bytes(hash));
+
+		writer.finish()
+	}
+}
+
+impl Aerodrome {
+	/// Returns a hash identifying the contents of the aerodrome, with the same
+	/// guarantees as [`Config::content_hash`].
+	pub fn content_hash(&self) -> [u8; 32] {
+		let mut writer = Writer::new(&self.styles);
+		writer.bytes(b"bars-aerodrome\0");
+
+		self.icao.write(&mut writer);
+		writer.set(&self.elements);
+		self.nodes.write(&mut writer);
+		self.edges.write(&mut writer);
+		self.blocks.write(&mut writer);
+		self.profiles.write(&mut writer);
+		self.geo_map.write(&mut writer);
+		self.maps.write(&mut writer);
+
+		// fields added since, each written after its own tag only when set
+		if !self.holding_points.is_empty() {
+			writer.tag(0);
+			self.holding_points.write(&mut writer);
+		}
+		if let Some(strings) = &self.strings {
+			writer.tag(1);
+			strings.write(&mut writer);
+		}
+
+		writer.finish()
+	}
+
+	/// Returns the content hash of the aerodrome in hex, as shown to users and
+	/// compared between client and server to check that their configs agree.
+	pub fn config_hash(&self) -> String {
+		(self.content_hash().iter())
+			.map(|byte| format!("{byte:02x}"))
+			.collect()
+	}
+}
+
+/// Accumulates the canonical form of an aerodrome.
+struct Writer<'a> {
+	buf: Vec<u8>,
+	/// styles of the aerodrome, written in place of refs to them
+	styles: &'a [Style],
+}
+
+impl<'a> Writer<'a> {
+	fn new(styles: &'a [Style]) -> Self {
+		Self {
+			buf: Vec::new(),
+			styles,
+		}
+	}
+
+	fn bytes(&mut self, bytes: &[u8]) {
+		self.buf.extend_from_slice(bytes);
+	}
+
+	fn tag(&mut self, tag: u8) {
+		self.buf.push(tag);
+	}
+
+	fn len(&mut self, len: usize) {
+		self.bytes(&(len as u64).to_le_bytes());
+	}
+
+	/// Writes a style by value, so that sharing and ordering of styles have no
+	/// effect.
+	fn style(&mut self, style: Ref<Style>) {
+		self.styles.get(style.0).cloned().write(self);
+	}
+
+	fn optional_style(&mut self, style: Option<Ref<Style>>) {
+		match style {
+			None => self.tag(0),
+			Some(style) => {
+				self.tag(1);
+				self.style(style);
+			},
+		}
+	}
+
+	/// Writes items in sorted order without duplicates, for lists whose order
+	/// and repetition have no meaning.
+	fn set<T: Canonical>(&mut self, items: &[T]) {
+		let mut items = items
+			.iter()
+			.map(|item| {
+				let mut writer = Writer::new(self.styles);
+				item.write(&mut writer);
+				writer.buf
+			})
+			.collect::<Vec<_>>();
+		items.sort();
+		items.dedup();
+
+		self.len(items.len());
+		items.iter().for_each(|item| self.bytes(item));
+	}
+
+	fn finish(self) -> [u8; 32] {
+		Sha256::digest(&self.buf).into()
+	}
+}
+
+trait Canonical {
+	fn write(&self, writer: &mut Writer);
+}
+
+impl Canonical for bool {
+	fn write(&self, writer: &mut Writer) {
+		writer.tag(*self as u8);
+	}
+}
+
+impl Canonical for u8 {
+	fn write(&self, writer: &mut Writer) {
+		writer.tag(*self);
+	}
+}
+
+impl Canonical for u16 {
+	fn write(&self, writer: &mut Writer) {
+		writer.bytes(&self.to_le_bytes());
+	}
+}
+
+impl Canonical for i8 {
+	fn write(&self, writer: &mut Writer) {
+		writer.bytes(&self.to_le_bytes());
+	}
+}
+
+impl Canonical for i32 {
+	fn write(&self, writer: &mut Writer) {
+		writer.bytes(&self.to_le_bytes());
+	}
+}
+
+impl Canonical for u32 {
+	fn write(&self, writer: &mut Writer) {
+		writer.bytes(&self.to_le_bytes());
+	}
+}
+
+impl Canonical for f32 {
+	fn write(&self, writer: &mut Writer) {
+		let value = if *self == 0.0 {
+			0.0
+		} else if self.is_nan() {
+			f32::NAN
+		} else {
+			*self
+		};
+
+		writer.bytes(&value.to_bits().to_le_bytes());
+	}
+}
+
+impl Canonical for String {
+	fn write(&self, writer: &mut Writer) {
+		writer.len(self.len());
+		writer.bytes(self.as_bytes());
+	}
+}
+
+impl<T> Canonical for Ref<T> {
+	fn write(&self, writer: &mut Writer) {
+		writer.bytes(&(self.0 as u64).to_le_bytes());
+	}
+}
+
+impl<T: Canonical> Canonical for Option<T> {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			None => writer.tag(0),
+			Some(value) => {
+				writer.tag(1);
+				value.write(writer);
+			},
+		}
+	}
+}
+
+impl<T: Canonical> Canonical for Vec<T> {
+	fn write(&self, writer: &mut Writer) {
+		writer.len(self.len());
+		self.iter().for_each(|item| item.write(writer));
+	}
+}
+
+impl<K: Canonical, V: Canonical> Canonical for BTreeMap<K, V> {
+	fn write(&self, writer: &mut Writer) {
+		writer.len(self.len());
+		self.iter().for_each(|(key, value)| {
+			key.write(writer);
+			value.write(writer);
+		});
+	}
+}
+
+impl<A: Canonical, B: Canonical> Canonical for (A, B) {
+	fn write(&self, writer: &mut Writer) {
+		self.0.write(writer);
+		self.1.write(writer);
+	}
+}
+
+impl Canonical for Element {
+	fn write(&self, writer: &mut Writer) {
+		self.id.write(writer);
+		self.condition.write(writer);
+	}
+}
+
+impl Canonical for ElementCondition {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Fixed(state) => {
+				writer.tag(0);
+				state.write(writer);
+			},
+			Self::Node(node) => {
+				writer.tag(1);
+				node.write(writer);
+			},
+			Self::Edge(edge) => {
+				writer.tag(2);
+				edge.write(writer);
+			},
+			Self::Expression(expression) => {
+				writer.tag(3);
+				expression.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for Node {
+	fn write(&self, writer: &mut Writer) {
+		self.id.write(writer);
+		self.scratchpad.write(writer);
+		self.parent.write(writer);
+
+		// added since, so written after a tag only when set
+		if let Some(order) = self.input_order {
+			writer.tag(0);
+			order.write(writer);
+		}
+	}
+}
+
+impl Canonical for Edge {
+	fn write(&self, writer: &mut Writer) {
+		self.id.write(writer);
+
+		if let Some(name) = &self.name {
+			writer.tag(0);
+			name.write(writer);
+		}
+	}
+}
+
+impl Canonical for Block {
+	fn write(&self, writer: &mut Writer) {
+		self.id.write(writer);
+		writer.set(&self.nodes);
+		writer.set(&self.edges);
+		writer.set(&self.non_routes);
+		writer.set(&self.stands);
+
+		if let Some(order) = self.input_order {
+			writer.tag(0);
+			order.write(writer);
+		}
+	}
+}
+
+impl Canonical for HoldingPoint {
+	fn write(&self, writer: &mut Writer) {
+		self.id.write(writer);
+		self.label.write(writer);
+		self.location.write(writer);
+		self.position.write(writer);
+	}
+}
+
+impl Canonical for LocaleTable {
+	fn write(&self, writer: &mut Writer) {
+		self.strings.write(writer);
+	}
+}
+
+impl Canonical for HoldingPointLocation {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Edge(edge) => {
+				writer.tag(0);
+				edge.write(writer);
+			},
+			Self::Nodes(a, b) => {
+				// the order of the nodes has no meaning
+				writer.tag(1);
+				a.min(b).write(writer);
+				a.max(b).write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for BlockRoute {
+	fn write(&self, writer: &mut Writer) {
+		self.from.write(writer);
+		self.to.write(writer);
+	}
+}
+
+impl Canonical for Profile {
+	fn write(&self, writer: &mut Writer) {
+		self.id.write(writer);
+		self.name.write(writer);
+		self.nodes.write(writer);
+		self.edges.write(writer);
+		self.blocks.write(writer);
+		self.presets.write(writer);
+
+		// added since, so written after a tag only when set
+		if !self.tags.is_empty() {
+			writer.tag(0);
+			writer.set(&self.tags);
+		}
+	}
+}
+
+impl Canonical for NodeCondition {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Fixed { state } => {
+				writer.tag(0);
+				state.write(writer);
+			},
+			Self::Direct { reset } => {
+				writer.tag(1);
+				reset.write(writer);
+			},
+			Self::Router { sticky } => {
+				writer.tag(2);
+				sticky.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for EdgeCondition {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Fixed { state } => {
+				writer.tag(0);
+				state.write(writer);
+			},
+			Self::Direct { nodes } => {
+				writer.tag(1);
+				nodes.write(writer);
+			},
+			Self::Router { block, routes } => {
+				writer.tag(2);
+				block.write(writer);
+				writer.set(routes);
+			},
+			Self::Expression { expression } => {
+				writer.tag(3);
+				expression.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for NodeExpression {
+	fn write(&self, writer: &mut Writer) {
+		writer.set(&self.disjunction);
+	}
+}
+
+impl Canonical for NodeConjunction {
+	fn write(&self, writer: &mut Writer) {
+		writer.set(&self.positive);
+		writer.set(&self.negative);
+	}
+}
+
+impl Canonical for ConditionExpression {
+	fn write(&self, writer: &mut Writer) {
+		writer.set(&self.disjunction);
+	}
+}
+
+impl Canonical for ConditionConjunction {
+	fn write(&self, writer: &mut Writer) {
+		writer.set(&self.positive);
+		writer.set(&self.negative);
+	}
+}
+
+impl Canonical for ConditionTerm {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Node(node) => {
+				writer.tag(0);
+				node.write(writer);
+			},
+			Self::Edge(edge) => {
+				writer.tag(1);
+				edge.write(writer);
+			},
+			Self::Block(block, predicate) => {
+				writer.tag(2);
+				block.write(writer);
+				predicate.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for BlockPredicate {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Clear => writer.tag(0),
+			Self::Relax => writer.tag(1),
+			Self::AnyRoute => writer.tag(2),
+			Self::Route(route) => {
+				writer.tag(3);
+				route.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for BlockCondition {
+	fn write(&self, writer: &mut Writer) {
+		self.reset.write(writer);
+	}
+}
+
+impl Canonical for ResetCondition {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::None => writer.tag(0),
+			Self::TimeSecs(secs) => {
+				writer.tag(1);
+				secs.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for Preset {
+	fn write(&self, writer: &mut Writer) {
+		self.name.write(writer);
+		writer.set(&self.nodes);
+		writer.set(&self.blocks);
+
+		if !self.includes.is_empty() {
+			writer.tag(0);
+			self.includes.write(writer);
+		}
+	}
+}
+
+impl Canonical for NodeState {
+	fn write(&self, writer: &mut Writer) {
+		writer.tag(match self {
+			Self::Off => 0,
+			Self::On => 1,
+		});
+	}
+}
+
+impl Canonical for EdgeState {
+	fn write(&self, writer: &mut Writer) {
+		writer.tag(match self {
+			Self::Off => 0,
+			Self::On => 1,
+		});
+	}
+}
+
+impl Canonical for BlockState {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Clear => writer.tag(0),
+			Self::Relax => writer.tag(1),
+			Self::Route(route) => {
+				writer.tag(2);
+				route.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for GeoMap {
+	fn write(&self, writer: &mut Writer) {
+		self.nodes.write(writer);
+		self.edges.write(writer);
+		self.blocks.write(writer);
+		self.widgets.write(writer);
+	}
+}
+
+impl Canonical for Map {
+	fn write(&self, writer: &mut Writer) {
+		self.background.write(writer);
+		self.base.write(writer);
+		self.nodes.write(writer);
+		self.edges.write(writer);
+		self.blocks.write(writer);
+		self.widgets.write(writer);
+		self.views.write(writer);
+	}
+}
+
+impl Canonical for View {
+	fn write(&self, writer: &mut Writer) {
+		self.name.write(writer);
+		self.bounds.min.write(writer);
+		self.bounds.max.write(writer);
+	}
+}
+
+impl<T: Projectable + Canonical> Canonical for Path<T> {
+	fn write(&self, writer: &mut Writer) {
+		self.points.write(writer);
+		writer.style(self.style);
+
+		// added since, so written after a tag only when set
+		if let Some(level) = self.level {
+			writer.tag(0);
+			level.write(writer);
+		}
+	}
+}
+
+impl<T: Projectable + Canonical> Canonical for Target<T> {
+	fn write(&self, writer: &mut Writer) {
+		self.polygons.write(writer);
+
+		// added since, so written after a tag only when set
+		if self.hover_style.is_some() || self.active_style.is_some() {
+			writer.tag(0);
+			writer.optional_style(self.hover_style);
+			writer.optional_style(self.active_style);
+		}
+
+		if let Some(level) = self.level {
+			writer.tag(1);
+			level.write(writer);
+		}
+	}
+}
+
+impl<T: Projectable + Canonical> Canonical for NodeDisplay<T> {
+	fn write(&self, writer: &mut Writer) {
+		self.off.write(writer);
+		self.on.write(writer);
+		self.selected.write(writer);
+		self.target.write(writer);
+
+		// added since, so written after a tag only when set
+		if let Some(tooltip) = &self.tooltip {
+			writer.tag(0);
+			tooltip.write(writer);
+		}
+	}
+}
+
+impl<T: Projectable + Canonical> Canonical for EdgeDisplay<T> {
+	fn write(&self, writer: &mut Writer) {
+		self.off.write(writer);
+		self.on.write(writer);
+		self.pending.write(writer);
+	}
+}
+
+impl<T: Projectable + Canonical> Canonical for BlockDisplay<T> {
+	fn write(&self, writer: &mut Writer) {
+		self.target.write(writer);
+
+		// added since, so written after a tag only when set
+		if let Some(tooltip) = &self.tooltip {
+			writer.tag(0);
+			tooltip.write(writer);
+		}
+	}
+}
+
+impl<T: Projectable + Canonical> Canonical for Widget<T> {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Countdown {
+				position,
+				size,
+				condition,
+				format,
+				warn_below_secs,
+				normal_style,
+				warn_style,
+			} => {
+				writer.tag(0);
+				position.write(writer);
+				size.write(writer);
+				condition.write(writer);
+
+				// added since, so written after a tag only when set
+				if *format != CountdownFormat::default()
+					|| warn_below_secs.is_some()
+					|| normal_style.is_some()
+					|| warn_style.is_some()
+				{
+					writer.tag(0);
+					format.write(writer);
+					warn_below_secs.write(writer);
+					writer.optional_style(*normal_style);
+					writer.optional_style(*warn_style);
+				}
+			},
+			Self::Label {
+				position,
+				size,
+				holding_point,
+			} => {
+				writer.tag(1);
+				position.write(writer);
+				size.write(writer);
+				holding_point.write(writer);
+			},
+			Self::Text {
+				position,
+				size,
+				anchor,
+				text,
+				color,
+				condition,
+			} => {
+				writer.tag(2);
+				position.write(writer);
+				size.write(writer);
+				anchor.write(writer);
+				text.write(writer);
+				color.write(writer);
+				condition.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for TextAnchor {
+	fn write(&self, writer: &mut Writer) {
+		writer.tag(match self {
+			Self::Centre => 0,
+			Self::Left => 1,
+			Self::Right => 2,
+			Self::Top => 3,
+			Self::Bottom => 4,
+			Self::TopLeft => 5,
+			Self::TopRight => 6,
+			Self::BottomLeft => 7,
+			Self::BottomRight => 8,
+		});
+	}
+}
+
+impl Canonical for CountdownFormat {
+	fn write(&self, writer: &mut Writer) {
+		writer.tag(match self {
+			Self::MinutesSeconds => 0,
+			Self::Seconds => 1,
+			Self::Bar => 2,
+		});
+	}
+}
+
+impl Canonical for CountdownCondition {
+	fn write(&self, writer: &mut Writer) {
+		match self {
+			Self::Node(node) => {
+				writer.tag(0);
+				node.write(writer);
+			},
+			Self::Block(block) => {
+				writer.tag(1);
+				block.write(writer);
+			},
+		}
+	}
+}
+
+impl Canonical for Point {
+	fn write(&self, writer: &mut Writer) {
+		self.x.write(writer);
+		self.y.write(writer);
+	}
+}
+
+impl Canonical for Geo {
+	fn write(&self, writer: &mut Writer) {
+		self.lat.write(writer);
+		self.lon.write(writer);
+	}
+}
+
+impl Canonical for GeoPoint {
+	fn write(&self, writer: &mut Writer) {
+		self.geo.write(writer);
+		self.offset.write(writer);
+	}
+}
+
+impl Canonical for Style {
+	fn write(&self, writer: &mut Writer) {
+		match self.stroke_style {
+			StrokeStyle::None => writer.tag(0),
+			StrokeStyle::Dash(dash) => {
+				writer.tag(1);
+				dash.write(writer);
+			},
+		}
+
+		f32::from(self.stroke_width).write(writer);
+		self.stroke_cap.0.write(writer);
+		self.stroke_join.0.write(writer);
+		self.stroke_color.write(writer);
+
+		match self.fill_style {
+			FillStyle::None => writer.tag(0),
+			FillStyle::Fill => writer.tag(1),
+			FillStyle::Hatch(hatch) => {
+				writer.tag(2);
+				hatch.write(writer);
+			},
+		}
+
+		self.fill_color.write(writer);
+
+		// added since, so written after a tag only when set
+		if let Some(blink) = &self.blink {
+			writer.tag(0);
+			blink.write(writer);
+		}
+	}
+}
+
+impl Canonical for Blink {
+	fn write(&self, writer: &mut Writer) {
+		self.period_ms.write(writer);
+		self.duty_percent.write(writer);
+		self.phase_ms.write(writer);
+	}
+}
+
+impl Canonical for Color {
+	fn write(&self, writer: &mut Writer) {
+		writer.bytes(&[self.r, self.g, self.b, self.a]);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::fixture::{aerodrome, block};
+
+	fn style(r: u8) -> Style {
+		Style {
+			stroke_style: StrokeStyle::Dash(0),
+			stroke_width: 2.0.into(),
+			stroke_cap: StrokeCap(0),
+			stroke_join: StrokeJoin(0),
+			stroke_color: Color {
+				r,
+				g: 0,
+				b: 0,
+				a: 0xff,
+			},
+			fill_style: FillStyle::None,
+			fill_color: Color::default(),
+			blink: None,
+		}
+	}
+
+	/// Returns an aerodrome of elements, a block and a styled geo map.
+	fn fixture() -> Aerodrome {
+		let mut aerodrome = aerodrome(3, vec![block("B1", &[0, 2], &["1"])]);
+		aerodrome.elements = vec![
+			Element {
+				id: "E0".into(),
+				condition: ElementCondition::Node(Ref::from(0)),
+			},
+			Element {
+				id: "msfs:E1".into(),
+				condition: ElementCondition::Fixed(true),
+			},
+		];
+
+		let path = |style: usize| Path {
+			points: vec![
+				GeoPoint {
+					geo: Geo {
+						lat: 51.5,
+						lon: -0.5,
+					},
+					offset: Point::default(),
+				},
+				GeoPoint {
+					geo: Geo {
+						lat: 51.6,
+						lon: -0.5,
+					},
+					offset: Point { x: 0.0, y: -2.0 },
+				},
+			],
+			style: Ref::from(style),
+			level: None,
+		};
+		aerodrome.geo_map = Some(GeoMap {
+			nodes: (0..3)
+				.map(|i| NodeDisplay {
+					on: vec![path(i % 2)],
+					..Default::default()
+				})
+				.collect(),
+			..Default::default()
+		});
+		aerodrome.styles = vec![style(0xff), style(0x80)];
+		aerodrome
+	}
+
+	fn config(aerodromes: Vec<Aerodrome>) -> Config {
+		Config {
+			name: Some("fixture".into()),
+			version: None,
+			aerodromes,
+		}
+	}
+
+	fn hex(hash: [u8; 32]) -> String {
+		hash.iter().map(|byte| format!("{byte:02x}")).collect()
+	}
+
+	#[test]
+	fn hashes_of_fixtures_are_pinned() {
+		// these must only change with the data, so a change here breaks the
+		// stability promised to users
+		let mut yyyy = aerodrome(1, Vec::new());
+		yyyy.icao = "YYYY".into();
+
+		assert_eq!(
+			fixture().config_hash(),
+			"e597838ad97462e0a80d5cbadb7aefe1d94acace24f2c234febe80638ccea513",
+		);
+		assert_eq!(
+			yyyy.config_hash(),
+			"581fb2493f5547c4020932e61fe22eafff250cff37e776b0ecff26e7ef3dd43f",
+		);
+		assert_eq!(
+			hex(config(vec![fixture(), yyyy]).content_hash()),
+			"ff4fec005b6d92006d227504256c564e6527741dc455acddd2e3084d6ddfe4fe",
+		);
+	}
+
+	#[test]
+	fn hashes_ignore_order_without_meaning() {
+		let mut yyyy = aerodrome(1, Vec::new());
+		yyyy.icao = "YYYY".into();
+
+		let hash = config(vec![fixture(), yyyy.clone()]).content_hash();
+		assert_eq!(config(vec![yyyy, fixture()]).content_hash(), hash);
+
+		let hash = fixture().content_hash();
+
+		let mut reordered = fixture();
+		reordered.elements.reverse();
+		reordered.blocks[0].nodes.reverse();
+		assert_eq!(reordered.content_hash(), hash);
+
+		// styles are written by value, however they are shared
+		let mut unshared = fixture();
+		unshared.styles.push(style(0xff));
+		let geo_map = unshared.geo_map.as_mut().unwrap();
+		geo_map.nodes[2].on[0].style = Ref::from(2);
+		assert_eq!(unshared.content_hash(), hash);
+
+		let mut swapped = fixture();
+		swapped.styles.reverse();
+		for node in &mut swapped.geo_map.as_mut().unwrap().nodes {
+			node.on[0].style = Ref::from(1 - node.on[0].style.0);
+		}
+		assert_eq!(swapped.content_hash(), hash);
+
+		// and zeros are the same whatever their sign
+		let mut negative = fixture();
+		let geo_map = negative.geo_map.as_mut().unwrap();
+		geo_map.nodes[0].on[0].points[0].offset.x = -0.0;
+		assert_eq!(negative.content_hash(), hash);
+	}
+
+	#[test]
+	fn hashes_change_with_the_data() {
+		let hash = fixture().content_hash();
+		let changed = |change: fn(&mut Aerodrome)| {
+			let mut aerodrome = fixture();
+			change(&mut aerodrome);
+			aerodrome.content_hash() != hash
+		};
+
+		assert!(changed(|aerodrome| aerodrome.icao = "ZZZY".into()));
+		assert!(changed(|aerodrome| aerodrome.nodes.reverse()));
+		assert!(changed(|aerodrome| aerodrome.blocks[0].stands.clear()));
+		assert!(changed(|aerodrome| aerodrome.styles[1].stroke_color.g = 1));
+		assert!(changed(|aerodrome| aerodrome.elements.truncate(1)));
+		assert!(changed(|aerodrome| {
+			let geo_map = aerodrome.geo_map.as_mut().unwrap();
+			geo_map.nodes[1].on[0].level = Some(0);
+		}));
+		assert!(changed(|aerodrome| {
+			aerodrome.profiles[0].nodes[1] = NodeCondition::Router { sticky: true };
+		}));
+
+		let config = config(vec![fixture()]);
+		let mut renamed = config.clone();
+		renamed.name = None;
+		assert_ne!(renamed.content_hash(), config.content_hash());
+	}
+}
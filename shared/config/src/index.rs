@@ -0,0 +1,486 @@
+use super::*;
+
+/// A position on a map which targets can be looked up by.
+pub trait Position: Copy + Debug {
+	/// Returns the position as planar coordinates.
+	fn xy(self) -> [f32; 2];
+}
+
+impl Position for Point {
+	fn xy(self) -> [f32; 2] {
+		[self.x, self.y]
+	}
+}
+
+impl Position for Geo {
+	fn xy(self) -> [f32; 2] {
+		[self.lon, self.lat]
+	}
+}
+
+/// A point of a path which lies at a position on its map.
+pub trait Positioned: Projectable {
+	type Position: Position;
+
+	/// Returns the position of the point, without any offset in pixels, which
+	/// depends on the scale it is drawn at.
+	fn position(&self) -> Self::Position;
+}
+
+impl Positioned for Point {
+	type Position = Self;
+
+	fn position(&self) -> Self {
+		*self
+	}
+}
+
+impl Positioned for GeoPoint {
+	type Position = Geo;
+
+	fn position(&self) -> Geo {
+		self.geo
+	}
+}
+
+impl<T: Positioned> Target<T> {
+	/// Returns whether any polygon of the target contains the position.
+	pub fn contains(&self, position: T::Position) -> bool {
+		(self.polygons.iter()).any(|polygon| polygon_contains(polygon, position))
+	}
+}
+
+fn polygon_contains<T: Positioned>(
+	polygon: &[T],
+	position: T::Position,
+) -> bool {
+	let [x, y] = position.xy().map(f64::from);
+	let mut inside = false;
+
+	for (i, a) in polygon.iter().enumerate() {
+		let b = &polygon[(i + 1) % polygon.len()];
+		let [ax, ay] = a.position().xy().map(f64::from);
+		let [bx, by] = b.position().xy().map(f64::from);
+
+		if (ay > y) != (by > y) && x < ax + (y - ay) / (by - ay) * (bx - ax) {
+			inside = !inside;
+		}
+	}
+
+	inside
+}
+
+/// What a target on a map selects.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum HitTarget {
+	Node(Ref<Node>),
+	Block(Ref<Block>),
+}
+
+/// A polygon of a target whose bounds contain a position, which may or may
+/// not contain the position itself.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct HitCandidate {
+	pub target: HitTarget,
+	/// index into the polygons of the target
+	pub polygon: usize,
+}
+
+/// A lookup of the targets of a map by position.
+///
+/// The bounds of each polygon of each target are binned into a uniform grid,
+/// so that a query need only check the few polygons in one cell. The index
+/// refers to the map it was built from by index, so is valid for as long as
+/// the map is unchanged.
+#[derive(Clone, Debug)]
+pub struct SpatialIndex<P: Position> {
+	entries: Vec<(HitCandidate, Bounds)>,
+	/// indices into the entries, in the order nodes then blocks
+	cells: Vec<Vec<u32>>,
+	bounds: Bounds,
+	columns: usize,
+	rows: usize,
+	_position: PhantomData<P>,
+}
+
+pub type GeoSpatialIndex = SpatialIndex<Geo>;
+
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+	min: [f32; 2],
+	max: [f32; 2],
+}
+
+impl Bounds {
+	fn of(points: impl Iterator<Item = [f32; 2]>) -> Option<Self> {
+		let mut bounds = Self {
+			min: [f32::INFINITY; 2],
+			max: [f32::NEG_INFINITY; 2],
+		};
+
+		for [x, y] in points {
+			bounds.min = [bounds.min[0].min(x), bounds.min[1].min(y)];
+			bounds.max = [bounds.max[0].max(x), bounds.max[1].max(y)];
+		}
+
+		(bounds.min.iter().chain(&bounds.max).all(|c| c.is_finite()))
+			.then_some(bounds)
+	}
+
+	fn contains(&self, [x, y]: [f32; 2]) -> bool {
+		(self.min[0]..=self.max[0]).contains(&x)
+			&& (self.min[1]..=self.max[1]).contains(&y)
+	}
+}
+
+impl<P: Position> SpatialIndex<P> {
+	fn build<T: Positioned<Position = P>>(
+		nodes: &[NodeDisplay<T>],
+		blocks: &[BlockDisplay<T>],
+	) -> Self {
+		let targets = (nodes.iter().enumerate())
+			.map(|(i, node)| (HitTarget::Node(i.into()), &node.target))
+			.chain(
+				(blocks.iter().enumerate())
+					.map(|(i, block)| (HitTarget::Block(i.into()), &block.target)),
+			);
+
+		let mut entries = Vec::new();
+		for (target, Target { polygons, .. }) in targets {
+			for (polygon, points) in polygons.iter().enumerate() {
+				// a polygon with non-finite points can contain nothing
+				let points = points.iter().map(|point| point.position().xy());
+				if let Some(bounds) = Bounds::of(points) {
+					entries.push((HitCandidate { target, polygon }, bounds));
+				}
+			}
+		}
+
+		let bounds = Bounds::of(
+			entries
+				.iter()
+				.flat_map(|(_, bounds)| [bounds.min, bounds.max]),
+		)
+		.unwrap_or(Bounds {
+			min: [0.0; 2],
+			max: [0.0; 2],
+		});
+
+		// about one cell for each polygon, in proportion to the bounds
+		let [width, height] = [0, 1].map(|i| bounds.max[i] - bounds.min[i]);
+		let side = (entries.len() as f32).sqrt().max(1.0);
+		let aspect = if width > 0.0 && height > 0.0 {
+			(width / height).sqrt()
+		} else {
+			1.0
+		};
+		let columns = (side * aspect).ceil().clamp(1.0, 4096.0) as usize;
+		let rows = (side / aspect).ceil().clamp(1.0, 4096.0) as usize;
+
+		let mut index = Self {
+			entries: Vec::new(),
+			cells: vec![Vec::new(); columns * rows],
+			bounds,
+			columns,
+			rows,
+			_position: PhantomData,
+		};
+
+		for (i, (_, bounds)) in entries.iter().enumerate() {
+			let [x0, y0] = index.cell(bounds.min);
+			let [x1, y1] = index.cell(bounds.max);
+			for y in y0..=y1 {
+				for x in x0..=x1 {
+					index.cells[y * columns + x].push(i as u32);
+				}
+			}
+		}
+
+		index.entries = entries;
+		index
+	}
+
+	/// Returns the cell containing the coordinates, clamped to the grid.
+	fn cell(&self, [x, y]: [f32; 2]) -> [usize; 2] {
+		let axis = |c: f32, min: f32, max: f32, len: usize| {
+			let scale = if max > min {
+				len as f32 / (max - min)
+			} else {
+				0.0
+			};
+			(((c - min) * scale) as usize).min(len - 1)
+		};
+
+		[
+			axis(x, self.bounds.min[0], self.bounds.max[0], self.columns),
+			axis(y, self.bounds.min[1], self.bounds.max[1], self.rows),
+		]
+	}
+
+	/// Returns the polygons whose bounds contain the position, those of nodes
+	/// before those of blocks.
+	pub fn query(&self, position: P) -> impl Iterator<Item = HitCandidate> + '_ {
+		let xy = position.xy();
+		let cell: &[u32] = if self.bounds.contains(xy) {
+			let [x, y] = self.cell(xy);
+			&self.cells[y * self.columns + x][..]
+		} else {
+			&[]
+		};
+
+		cell
+			.iter()
+			.map(|i| &self.entries[*i as usize])
+			.filter(move |(_, bounds)| bounds.contains(xy))
+			.map(|(candidate, _)| *candidate)
+	}
+
+	fn hit<T: Positioned<Position = P>>(
+		&self,
+		nodes: &[NodeDisplay<T>],
+		blocks: &[BlockDisplay<T>],
+		position: P,
+		level: Option<i8>,
+	) -> Option<HitTarget> {
+		let hits = || {
+			self.query(position).filter_map(|candidate| {
+				let target = match candidate.target {
+					HitTarget::Node(node) => &nodes.get(node.0)?.target,
+					HitTarget::Block(block) => &blocks.get(block.0)?.target,
+				};
+
+				let polygon = target.polygons.get(candidate.polygon)?;
+				polygon_contains(polygon, position).then_some((candidate, target))
+			})
+		};
+
+		// targets on the level selected are preferred to those beneath them
+		hits()
+			.find(|(_, target)| target.on_level(level))
+			.or_else(|| hits().next())
+			.map(|(candidate, _)| candidate.target)
+	}
+}
+
+impl GeoMap {
+	/// Builds an index of the targets of the map, for finding what a position
+	/// selects.
+	pub fn build_index(&self) -> GeoSpatialIndex {
+		SpatialIndex::build(&self.nodes, &self.blocks)
+	}
+
+	/// Returns what the position selects, using an index built from the map,
+	/// preferring nodes to the blocks drawn beneath them.
+	pub fn hit(&self, index: &GeoSpatialIndex, geo: Geo) -> Option<HitTarget> {
+		self.hit_on_level(index, geo, None)
+	}
+
+	/// Returns what the position selects as for [`GeoMap::hit`], preferring
+	/// targets on the level selected where targets overlap.
+	pub fn hit_on_level(
+		&self,
+		index: &GeoSpatialIndex,
+		geo: Geo,
+		level: Option<i8>,
+	) -> Option<HitTarget> {
+		index.hit(&self.nodes, &self.blocks, geo, level)
+	}
+}
+
+impl Map {
+	/// Builds an index of the targets of the map, for finding what a position
+	/// selects.
+	pub fn build_index(&self) -> SpatialIndex<Point> {
+		SpatialIndex::build(&self.nodes, &self.blocks)
+	}
+
+	/// Returns what the position selects, using an index built from the map,
+	/// preferring nodes to the blocks drawn beneath them.
+	pub fn hit(
+		&self,
+		index: &SpatialIndex<Point>,
+		point: Point,
+	) -> Option<HitTarget> {
+		self.hit_on_level(index, point, None)
+	}
+
+	/// Returns what the position selects as for [`Map::hit`], preferring
+	/// targets on the level selected where targets overlap.
+	pub fn hit_on_level(
+		&self,
+		index: &SpatialIndex<Point>,
+		point: Point,
+		level: Option<i8>,
+	) -> Option<HitTarget> {
+		index.hit(&self.nodes, &self.blocks, point, level)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A small generator of pseudo-random numbers, so that failures repeat.
+	struct Rng(u64);
+
+	impl Rng {
+		fn next(&mut self) -> f32 {
+			// xorshift64*
+			self.0 ^= self.0 >> 12;
+			self.0 ^= self.0 << 25;
+			self.0 ^= self.0 >> 27;
+			let bits = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 40;
+			bits as f32 / (1u64 << 24) as f32
+		}
+
+		fn range(&mut self, min: f32, max: f32) -> f32 {
+			min + self.next() * (max - min)
+		}
+
+		fn below(&mut self, n: usize) -> usize {
+			(self.next() * n as f32) as usize % n
+		}
+	}
+
+	/// Returns a target of a few star-shaped polygons about random centres,
+	/// some too short to contain anything, on a random level.
+	fn target<T: Positioned + Default>(
+		rng: &mut Rng,
+		radius: f32,
+		point: &impl Fn([f32; 2]) -> T,
+	) -> Target<T> {
+		let polygons = (0..rng.below(3))
+			.map(|_| {
+				let centre = [rng.range(0.0, 100.0), rng.range(0.0, 100.0)];
+				let mut angles = (0..rng.below(6))
+					.map(|_| rng.range(0.0, std::f32::consts::TAU))
+					.collect::<Vec<_>>();
+				angles.sort_by(f32::total_cmp);
+
+				(angles.into_iter())
+					.map(|angle| {
+						let r = rng.range(radius / 4.0, radius);
+						point([centre[0] + r * angle.cos(), centre[1] + r * angle.sin()])
+					})
+					.collect()
+			})
+			.collect();
+
+		Target {
+			polygons,
+			level: [None, Some(0), Some(1)][rng.below(3)],
+			..Default::default()
+		}
+	}
+
+	/// Returns what the position selects, checking every target in turn.
+	fn naive<T: Positioned>(
+		nodes: &[NodeDisplay<T>],
+		blocks: &[BlockDisplay<T>],
+		position: T::Position,
+		level: Option<i8>,
+	) -> Option<HitTarget> {
+		let targets = (nodes.iter().enumerate())
+			.map(|(i, node)| (HitTarget::Node(i.into()), &node.target))
+			.chain(
+				(blocks.iter().enumerate())
+					.map(|(i, block)| (HitTarget::Block(i.into()), &block.target)),
+			);
+		let hits = targets
+			.filter(|(_, target)| target.contains(position))
+			.collect::<Vec<_>>();
+
+		(hits.iter())
+			.find(|(_, target)| target.on_level(level))
+			.or(hits.first())
+			.map(|(hit, _)| *hit)
+	}
+
+	/// Checks the index of random targets against a naive scan, at random
+	/// positions and at the vertices of the polygons.
+	fn check<T: Positioned + Default>(seed: u64, point: impl Fn([f32; 2]) -> T) {
+		let mut rng = Rng(seed);
+		let nodes = (0..150)
+			.map(|_| NodeDisplay {
+				target: target(&mut rng, 6.0, &point),
+				..Default::default()
+			})
+			.collect::<Vec<_>>();
+		let blocks = (0..25)
+			.map(|_| BlockDisplay {
+				target: target(&mut rng, 25.0, &point),
+				tooltip: None,
+			})
+			.collect::<Vec<_>>();
+		let index = SpatialIndex::build(&nodes, &blocks);
+
+		let vertices = (nodes.iter().map(|node| &node.target))
+			.chain(blocks.iter().map(|block| &block.target))
+			.flat_map(|target| target.polygons.iter().flatten())
+			.map(|point| point.position())
+			.collect::<Vec<_>>();
+		let random = (0..1500)
+			.map(|_| point([rng.range(-10.0, 110.0), rng.range(-10.0, 110.0)]))
+			.map(|point| point.position())
+			.collect::<Vec<_>>();
+
+		let mut hit = 0;
+		for position in vertices.into_iter().chain(random) {
+			let candidates = index.query(position).collect::<Vec<_>>();
+
+			// every polygon containing the position is a candidate
+			let targets = (nodes.iter().enumerate())
+				.map(|(i, node)| (HitTarget::Node(i.into()), &node.target))
+				.chain(
+					(blocks.iter().enumerate())
+						.map(|(i, block)| (HitTarget::Block(i.into()), &block.target)),
+				);
+			for (target, Target { polygons, .. }) in targets {
+				for (polygon, points) in polygons.iter().enumerate() {
+					let candidate = HitCandidate { target, polygon };
+					if polygon_contains(points, position) {
+						assert!(candidates.contains(&candidate), "{position:?}");
+					}
+				}
+			}
+
+			for level in [None, Some(0), Some(1)] {
+				let expected = naive(&nodes, &blocks, position, level);
+				let actual = index.hit(&nodes, &blocks, position, level);
+				assert_eq!(actual, expected, "{position:?} on {level:?}");
+				hit += usize::from(actual.is_some());
+			}
+		}
+
+		// enough positions hit something for the comparison to mean anything
+		assert!(hit > 500, "only {hit} hits");
+	}
+
+	#[test]
+	fn geo_indices_agree_with_a_naive_scan() {
+		for seed in 1..=2 {
+			check(seed, |[lon, lat]| GeoPoint {
+				geo: Geo {
+					lat: 51.0 + lat / 1000.0,
+					lon: -1.0 + lon / 1000.0,
+				},
+				offset: Point::default(),
+			});
+		}
+	}
+
+	#[test]
+	fn map_indices_agree_with_a_naive_scan() {
+		for seed in 1..=2 {
+			check(seed, |[x, y]| Point { x, y });
+		}
+	}
+
+	#[test]
+	fn empty_maps_hit_nothing() {
+		let map = Map::default();
+		let index = map.build_index();
+		assert_eq!(index.query(Point::default()).count(), 0);
+		assert_eq!(map.hit(&index, Point { x: 1.0, y: 1.0 }), None);
+	}
+}
@@ -0,0 +1,255 @@
+use super::*;
+
+use std::io::{Seek, SeekFrom};
+
+/// The section leading a config saved with [`Config::save_indexed`], which is
+/// followed by a section for each aerodrome.
+#[derive(Decode, Encode)]
+struct ConfigIndex {
+	name: Option<String>,
+	version: Option<String>,
+	aerodromes: Vec<IndexEntry>,
+}
+
+#[derive(Decode, Encode)]
+struct IndexEntry {
+	icao: String,
+	/// of the section of the aerodrome, from the end of the index
+	offset: u64,
+	/// of the whole section, including its length and checksum
+	length: u64,
+}
+
+/// A config opened by [`Config::open`], from which aerodromes are loaded one
+/// at a time.
+///
+/// Configs saved with an index have only the aerodromes asked for decoded.
+/// Others are loaded in full when opened, so are no quicker to open, but can
+/// be used in the same way.
+pub struct ConfigHandle<R> {
+	name: Option<String>,
+	version: Option<String>,
+	contents: Contents<R>,
+}
+
+enum Contents<R> {
+	Indexed {
+		reader: R,
+		format: FormatVersion,
+		/// position of the first aerodrome in the reader
+		base: u64,
+		entries: Vec<IndexEntry>,
+	},
+	Loaded(Vec<Aerodrome>),
+}
+
+impl<R: Read + Seek> ConfigHandle<R> {
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	pub fn version(&self) -> Option<&str> {
+		self.version.as_deref()
+	}
+
+	/// Returns whether the config was saved with an index, so that aerodromes
+	/// are decoded only as they are loaded.
+	pub fn is_indexed(&self) -> bool {
+		matches!(self.contents, Contents::Indexed { .. })
+	}
+
+	/// Returns the ICAO codes of the aerodromes, in the order they were saved.
+	pub fn icaos(&self) -> Vec<&str> {
+		match &self.contents {
+			Contents::Indexed { entries, .. } => {
+				entries.iter().map(|entry| entry.icao.as_str()).collect()
+			},
+			Contents::Loaded(aerodromes) => (aerodromes.iter())
+				.map(|aerodrome| aerodrome.icao.as_str())
+				.collect(),
+		}
+	}
+
+	/// Loads the aerodrome with the ICAO code, if there is one, decoding only
+	/// that aerodrome if the config is indexed.
+	pub fn load_aerodrome(
+		&mut self,
+		icao: &str,
+	) -> Result<Option<Aerodrome>, LoadError> {
+		match &mut self.contents {
+			Contents::Indexed {
+				reader,
+				format,
+				base,
+				entries,
+			} => {
+				let Some(entry) = entries.iter().find(|entry| entry.icao == icao)
+				else {
+					return Ok(None)
+				};
+
+				let position =
+					base.checked_add(entry.offset).ok_or(LoadError::Corrupt)?;
+				reader.seek(SeekFrom::Start(position))?;
+				read_aerodrome(reader, entry, *format).map(Some)
+			},
+			Contents::Loaded(aerodromes) => Ok(
+				aerodromes
+					.iter()
+					.find(|aerodrome| aerodrome.icao == icao)
+					.cloned(),
+			),
+		}
+	}
+}
+
+impl Config {
+	/// Saves as for [`Loadable::save`], but with an index and each aerodrome
+	/// compressed separately, so that [`Config::open`] can load aerodromes
+	/// without decoding the rest.
+	///
+	/// The file is usually a little larger, as aerodromes do not share a
+	/// compression window, but can still be read by [`Loadable::load`].
+	pub fn save_indexed(
+		&self,
+		mut writer: impl Write,
+	) -> Result<(), EncodeError> {
+		let mut sections = Vec::new();
+		let mut entries = Vec::new();
+		let mut offset = 0;
+
+		for aerodrome in &self.aerodromes {
+			let mut section = Vec::new();
+			write_section(&mut section, &compress(&aerodrome.encode()?)?)?;
+
+			entries.push(IndexEntry {
+				icao: aerodrome.icao.clone(),
+				offset,
+				length: section.len() as u64,
+			});
+
+			offset += section.len() as u64;
+			sections.push(section);
+		}
+
+		let index = ConfigIndex {
+			name: self.name.clone(),
+			version: self.version.clone(),
+			aerodromes: entries,
+		};
+
+		writer.write_all(MAGIC).map_err(encode_io_error)?;
+		(writer.write_all(&(Self::VERSION | INDEXED_FLAG).to_be_bytes()))
+			.map_err(encode_io_error)?;
+		write_section(
+			&mut writer,
+			&compress(&bincode::encode_to_vec(&index, BINCODE_CONFIG)?)?,
+		)?;
+
+		for section in sections {
+			writer.write_all(&section).map_err(encode_io_error)?;
+		}
+
+		Ok(())
+	}
+
+	/// Opens a config to load its aerodromes one at a time, reading only the
+	/// header and index of a config saved with [`Config::save_indexed`].
+	///
+	/// Configs saved without an index are loaded in full.
+	pub fn open<R: Read + Seek>(
+		mut reader: R,
+	) -> Result<ConfigHandle<R>, LoadError> {
+		let start = reader.stream_position()?;
+		let version = read_version(&mut reader)?;
+
+		if version & INDEXED_FLAG == 0 {
+			reader.seek(SeekFrom::Start(start))?;
+			let config = Self::load(reader)?;
+
+			return Ok(ConfigHandle {
+				name: config.name,
+				version: config.version,
+				contents: Contents::Loaded(config.aerodromes),
+			})
+		}
+
+		let format = supported_version::<Self>(version & !INDEXED_FLAG)?;
+		let index = read_index(&mut reader, format)?;
+		let base = reader.stream_position()?;
+
+		Ok(ConfigHandle {
+			name: index.name,
+			version: index.version,
+			contents: Contents::Indexed {
+				reader,
+				format,
+				base,
+				entries: index.aerodromes,
+			},
+		})
+	}
+}
+
+fn read_index(
+	reader: impl Read,
+	format: FormatVersion,
+) -> Result<ConfigIndex, LoadError> {
+	// indices came after sections, so no earlier version has one
+	if format.0 < Config::CHECKSUM_VERSION {
+		return Err(LoadError::UnsupportedVersion {
+			version: format.0 | INDEXED_FLAG,
+			oldest: Config::CHECKSUM_VERSION,
+			newest: Config::VERSION,
+		})
+	}
+
+	Ok(decode_section(&read_section(reader)?, format)?)
+}
+
+fn read_aerodrome(
+	reader: impl Read,
+	entry: &IndexEntry,
+	format: FormatVersion,
+) -> Result<Aerodrome, LoadError> {
+	let payload = read_section(reader.take(entry.length))?;
+	if payload.len() as u64 + 8 != entry.length {
+		return Err(LoadError::Corrupt)
+	}
+
+	let aerodrome = decode_section::<Aerodrome>(&payload, format)?;
+	if aerodrome.icao != entry.icao {
+		return Err(LoadError::Corrupt)
+	}
+
+	Ok(aerodrome)
+}
+
+/// Reads the rest of a config saved with an index, after its header, reading
+/// the aerodromes in turn.
+pub(crate) fn load_indexed(
+	mut reader: impl Read,
+	format: FormatVersion,
+) -> Result<Config, LoadError> {
+	let index = read_index(&mut reader, format)?;
+
+	let mut aerodromes = Vec::new();
+	let mut offset = 0;
+	for entry in &index.aerodromes {
+		// sections are saved in the order of the index, with no gaps
+		if entry.offset != offset {
+			return Err(LoadError::Corrupt)
+		}
+
+		aerodromes.push(read_aerodrome(&mut reader, entry, format)?);
+		offset += entry.length;
+	}
+
+	expect_end(reader)?;
+
+	Ok(Config {
+		name: index.name,
+		version: index.version,
+		aerodromes,
+	})
+}
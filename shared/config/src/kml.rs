@@ -0,0 +1,620 @@
+use crate::*;
+
+use std::fmt::Write;
+
+/// colour of the outlines of targets, as drawn by render-map
+const TARGET_COLOR: Color = Color {
+	r: 0xff,
+	g: 0x00,
+	b: 0xff,
+	a: 0xff,
+};
+
+/// Names of the entities of a map, by index, for labelling placemarks.
+struct Names<'a> {
+	nodes: Vec<&'a str>,
+	edges: Vec<&'a str>,
+	blocks: Vec<&'a str>,
+	holding_points: Vec<&'a str>,
+}
+
+impl Aerodrome {
+	/// Exports the geo map of the aerodrome as a KML document, or returns
+	/// `None` if it has no geo map.
+	///
+	/// See [`Maps::to_kml`] for the contents of the document.
+	pub fn to_kml(&self) -> Option<String> {
+		let names = Names {
+			nodes: self.nodes.iter().map(|node| node.id.as_str()).collect(),
+			edges: self.edges.iter().map(|edge| edge.id.as_str()).collect(),
+			blocks: self.blocks.iter().map(|block| block.id.as_str()).collect(),
+			holding_points: (self.holding_points.iter())
+				.map(|holding_point| holding_point.label.as_str())
+				.collect(),
+		};
+
+		Some(kml(
+			&self.icao,
+			self.geo_map.as_ref()?,
+			&self.styles,
+			&names,
+		))
+	}
+}
+
+impl Maps {
+	/// Exports the geo map as a KML document named `name`, or returns `None` if
+	/// there is no geo map.
+	///
+	/// Paths are grouped into a folder for each display of nodes and edges,
+	/// and targets into one each for nodes and blocks, with a placemark named
+	/// for its entity. Paths with filled styles are polygons, and others are
+	/// lines, styled with the colours of the original. Widgets are placemarks
+	/// at their positions. Pixel offsets cannot be drawn, so points are placed
+	/// at their geo positions, with any offsets listed in the extended data of
+	/// the placemark, as is the level.
+	pub fn to_kml(&self, name: &str) -> Option<String> {
+		let names = Names {
+			nodes: self.nodes.iter().map(String::as_str).collect(),
+			edges: self.edges.iter().map(String::as_str).collect(),
+			blocks: self.blocks.iter().map(String::as_str).collect(),
+			holding_points: self.holding_points.iter().map(String::as_str).collect(),
+		};
+
+		Some(kml(name, self.geo_map.as_ref()?, &self.styles, &names))
+	}
+}
+
+fn kml(name: &str, map: &GeoMap, styles: &[Style], names: &Names) -> String {
+	let mut kml = String::new();
+
+	kml += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+	kml += "<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n";
+	kml += "<Document>\n";
+	let _ = writeln!(kml, "<name>{}</name>", escape(name));
+
+	for (i, style) in styles.iter().enumerate() {
+		let width = match style.stroke_style {
+			StrokeStyle::None => 0.0,
+			StrokeStyle::Dash(_) => f32::from(style.stroke_width),
+		};
+
+		let _ = writeln!(
+			kml,
+			"<Style id=\"style{i}\"><LineStyle><color>{}</color><width>{width}\
+			 </width></LineStyle><PolyStyle><color>{}</color><fill>{}</fill>\
+			 <outline>{}</outline></PolyStyle></Style>",
+			color(style.stroke_color),
+			color(style.fill_color),
+			u8::from(style.fill_style != FillStyle::None),
+			u8::from(style.stroke_style != StrokeStyle::None),
+		);
+	}
+
+	let _ = writeln!(
+		kml,
+		"<Style id=\"target\"><LineStyle><color>{}</color><width>1</width>\
+		 </LineStyle><PolyStyle><fill>0</fill></PolyStyle></Style>",
+		color(TARGET_COLOR),
+	);
+
+	type Paths<T> = fn(&T) -> &Vec<Path<GeoPoint>>;
+
+	let node_displays: [(_, Paths<NodeDisplay<GeoPoint>>); 3] = [
+		("node off", |node| &node.off),
+		("node on", |node| &node.on),
+		("node selected", |node| &node.selected),
+	];
+
+	for (folder, paths) in node_displays {
+		open_folder(&mut kml, folder);
+		for (i, node) in map.nodes.iter().enumerate() {
+			for path in paths(node) {
+				write_path(&mut kml, names.nodes.get(i).copied(), path, styles);
+			}
+		}
+		kml += "</Folder>\n";
+	}
+
+	let edge_displays: [(_, Paths<EdgeDisplay<GeoPoint>>); 3] = [
+		("edge off", |edge| &edge.off),
+		("edge on", |edge| &edge.on),
+		("edge pending", |edge| &edge.pending),
+	];
+
+	for (folder, paths) in edge_displays {
+		open_folder(&mut kml, folder);
+		for (i, edge) in map.edges.iter().enumerate() {
+			for path in paths(edge) {
+				write_path(&mut kml, names.edges.get(i).copied(), path, styles);
+			}
+		}
+		kml += "</Folder>\n";
+	}
+
+	open_folder(&mut kml, "node targets");
+	for (i, node) in map.nodes.iter().enumerate() {
+		let name = names.nodes.get(i).copied();
+		write_target(&mut kml, name, &node.target, node.tooltip.as_deref());
+	}
+	kml += "</Folder>\n";
+
+	open_folder(&mut kml, "block targets");
+	for (i, block) in map.blocks.iter().enumerate() {
+		let name = names.blocks.get(i).copied();
+		write_target(&mut kml, name, &block.target, block.tooltip.as_deref());
+	}
+	kml += "</Folder>\n";
+
+	open_folder(&mut kml, "widgets");
+	for widget in &map.widgets {
+		let (name, position) = match widget {
+			Widget::Countdown {
+				position,
+				condition,
+				..
+			} => {
+				let entity = match condition {
+					CountdownCondition::Node(node) => names.nodes.get(node.0),
+					CountdownCondition::Block(block) => names.blocks.get(block.0),
+				};
+				(format!("countdown {}", entity.unwrap_or(&"")), position)
+			},
+			Widget::Label {
+				position,
+				holding_point,
+				..
+			} => {
+				let label = names.holding_points.get(holding_point.0);
+				(format!("label {}", label.unwrap_or(&"")), position)
+			},
+			Widget::Text { position, text, .. } => (format!("text {text}"), position),
+		};
+
+		let _ = write!(kml, "<Placemark><name>{}</name>", escape(&name));
+		write_data(&mut kml, None, std::slice::from_ref(position));
+		let _ = writeln!(
+			kml,
+			"<Point><coordinates>{}</coordinates></Point></Placemark>",
+			coordinates(std::slice::from_ref(position)),
+		);
+	}
+	kml += "</Folder>\n";
+
+	kml += "</Document>\n";
+	kml += "</kml>\n";
+	kml
+}
+
+fn open_folder(kml: &mut String, name: &str) {
+	let _ = writeln!(kml, "<Folder><name>{name}</name>");
+}
+
+fn write_path(
+	kml: &mut String,
+	name: Option<&str>,
+	path: &Path<GeoPoint>,
+	styles: &[Style],
+) {
+	let filled = (styles.get(path.style.0))
+		.is_some_and(|style| style.fill_style != FillStyle::None);
+
+	// a line needs two points, and a polygon three
+	if path.points.len() < 2 + usize::from(filled) {
+		return
+	}
+
+	let _ = write!(
+		kml,
+		"<Placemark><name>{}</name><styleUrl>#style{}</styleUrl>",
+		escape(name.unwrap_or_default()),
+		path.style.0,
+	);
+	write_data(kml, path.level, &path.points);
+
+	if filled {
+		write_polygon(kml, &path.points);
+	} else {
+		let _ = write!(
+			kml,
+			"<LineString><coordinates>{}</coordinates></LineString>",
+			coordinates(&path.points),
+		);
+	}
+
+	*kml += "</Placemark>\n";
+}
+
+fn write_target(
+	kml: &mut String,
+	name: Option<&str>,
+	target: &Target<GeoPoint>,
+	tooltip: Option<&str>,
+) {
+	for polygon in &target.polygons {
+		if polygon.len() < 3 {
+			continue
+		}
+
+		let _ = write!(
+			kml,
+			"<Placemark><name>{}</name>",
+			escape(name.unwrap_or_default()),
+		);
+		if let Some(tooltip) = tooltip {
+			let _ = write!(kml, "<description>{}</description>", escape(tooltip));
+		}
+		*kml += "<styleUrl>#target</styleUrl>";
+		write_data(kml, target.level, polygon);
+		write_polygon(kml, polygon);
+		*kml += "</Placemark>\n";
+	}
+}
+
+fn write_polygon(kml: &mut String, points: &[GeoPoint]) {
+	// rings are closed by repeating the first point
+	let ring = points
+		.iter()
+		.chain(&points[..1])
+		.copied()
+		.collect::<Vec<_>>();
+
+	let _ = write!(
+		kml,
+		"<Polygon><outerBoundaryIs><LinearRing><coordinates>{}</coordinates>\
+		 </LinearRing></outerBoundaryIs></Polygon>",
+		coordinates(&ring),
+	);
+}
+
+/// Writes the level and pixel offsets of the points, if any, as extended
+/// data.
+fn write_data(kml: &mut String, level: Option<i8>, points: &[GeoPoint]) {
+	let offset = points
+		.iter()
+		.any(|point| point.offset.x != 0.0 || point.offset.y != 0.0);
+
+	if level.is_none() && !offset {
+		return
+	}
+
+	*kml += "<ExtendedData>";
+	if let Some(level) = level {
+		let _ = write!(kml, "<Data name=\"level\"><value>{level}</value></Data>");
+	}
+	if offset {
+		let offsets = points
+			.iter()
+			.map(|point| format!("{},{}", point.offset.x, point.offset.y))
+			.collect::<Vec<_>>();
+		let _ = write!(
+			kml,
+			"<Data name=\"offsets\"><value>{}</value></Data>",
+			offsets.join(" "),
+		);
+	}
+	*kml += "</ExtendedData>";
+}
+
+fn coordinates(points: &[GeoPoint]) -> String {
+	points
+		.iter()
+		.map(|point| format!("{},{}", point.geo.lon, point.geo.lat))
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Returns the colour in the `aabbggrr` form of KML.
+fn color(color: Color) -> String {
+	format!(
+		"{:02x}{:02x}{:02x}{:02x}",
+		color.a, color.b, color.g, color.r
+	)
+}
+
+fn escape(text: &str) -> String {
+	text
+		.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::collections::HashSet;
+
+	use roxmltree::{Document, Node};
+
+	const KML: &str = "http://www.opengis.net/kml/2.2";
+
+	fn point(lat: f32, lon: f32, x: f32) -> GeoPoint {
+		GeoPoint {
+			geo: Geo { lat, lon },
+			offset: Point { x, y: 0.0 },
+		}
+	}
+
+	fn square(lat: f32, lon: f32) -> Vec<GeoPoint> {
+		vec![
+			point(lat, lon, 0.0),
+			point(lat + 0.001, lon, 0.0),
+			point(lat + 0.001, lon + 0.001, 0.0),
+			point(lat, lon + 0.001, 0.0),
+		]
+	}
+
+	fn style(fill_style: FillStyle) -> Style {
+		Style {
+			stroke_style: StrokeStyle::Dash(0),
+			stroke_width: 2.0.into(),
+			stroke_cap: StrokeCap(0),
+			stroke_join: StrokeJoin(0),
+			stroke_color: Color {
+				r: 0x12,
+				g: 0x34,
+				b: 0x56,
+				a: 0xff,
+			},
+			fill_style,
+			fill_color: Color::default(),
+			blink: None,
+		}
+	}
+
+	/// Returns maps of every kind of placemark, with names to be escaped.
+	fn maps() -> Maps {
+		let line = Path {
+			points: vec![point(51.5, -0.5, 0.0), point(51.501, -0.5, 2.5)],
+			style: Ref::from(0),
+			level: Some(-1),
+		};
+		let fill = Path {
+			points: square(51.5, -0.5),
+			style: Ref::from(1),
+			level: None,
+		};
+
+		Maps {
+			nodes: vec!["A&B".into(), "<C>".into()],
+			edges: vec!["E\"1\"".into()],
+			blocks: vec!["B1".into()],
+			holding_points: vec!["HP".into()],
+			geo_map: Some(GeoMap {
+				nodes: vec![
+					NodeDisplay {
+						off: vec![line.clone()],
+						on: vec![fill.clone()],
+						target: Target {
+							polygons: vec![square(51.6, -0.6)],
+							level: Some(2),
+							..Default::default()
+						},
+						tooltip: Some("A & B".into()),
+						..Default::default()
+					},
+					NodeDisplay {
+						selected: vec![line.clone()],
+						..Default::default()
+					},
+				],
+				edges: vec![EdgeDisplay {
+					pending: vec![line, fill],
+					..Default::default()
+				}],
+				blocks: vec![BlockDisplay {
+					target: Target {
+						polygons: vec![square(51.7, -0.7)],
+						..Default::default()
+					},
+					tooltip: None,
+				}],
+				widgets: vec![
+					Widget::Countdown {
+						position: point(51.5, -0.5, 4.0),
+						size: 1.0,
+						condition: CountdownCondition::Block(Ref::from(0)),
+						format: CountdownFormat::default(),
+						warn_below_secs: None,
+						normal_style: None,
+						warn_style: None,
+					},
+					Widget::Label {
+						position: point(51.5, -0.5, 0.0),
+						size: 1.0,
+						holding_point: Ref::from(0),
+					},
+					Widget::Text {
+						position: point(51.5, -0.5, 0.0),
+						size: 1.0,
+						anchor: TextAnchor::default(),
+						text: "<stop>".into(),
+						color: Color::default(),
+						condition: None,
+					},
+				],
+			}),
+			maps: Vec::new(),
+			styles: vec![style(FillStyle::None), style(FillStyle::Fill)],
+		}
+	}
+
+	fn children<'a, 'i>(
+		node: Node<'a, 'i>,
+	) -> impl Iterator<Item = Node<'a, 'i>> {
+		node.children().filter(Node::is_element)
+	}
+
+	fn names<'a>(nodes: impl Iterator<Item = Node<'a, 'a>>) -> Vec<&'a str> {
+		nodes.map(|node| node.tag_name().name()).collect()
+	}
+
+	fn text<'a>(node: Node<'a, '_>, name: &str) -> &'a str {
+		let child = children(node).find(|child| child.has_tag_name(name));
+		child.and_then(|child| child.text()).unwrap_or_default()
+	}
+
+	/// Checks that coordinates are tuples of longitude then latitude, returning
+	/// how many there are.
+	fn check_coordinates(coordinates: &str) -> usize {
+		let tuples = coordinates.split(' ').collect::<Vec<_>>();
+		for tuple in &tuples {
+			let values = (tuple.split(','))
+				.map(|value| value.parse::<f32>().unwrap())
+				.collect::<Vec<_>>();
+			let [lon, lat] = values[..] else {
+				panic!("bad tuple {tuple:?}");
+			};
+			assert!((-180.0..=180.0).contains(&lon), "{tuple}");
+			assert!((-90.0..=90.0).contains(&lat), "{tuple}");
+		}
+		tuples.len()
+	}
+
+	fn check_geometry(geometry: Node) {
+		let coordinates = geometry
+			.descendants()
+			.find(|node| node.has_tag_name("coordinates"))
+			.and_then(|node| node.text())
+			.unwrap();
+		let count = check_coordinates(coordinates);
+
+		match geometry.tag_name().name() {
+			"Point" => assert_eq!(count, 1),
+			"LineString" => assert!(count >= 2),
+			"Polygon" => {
+				assert_eq!(names(children(geometry)), ["outerBoundaryIs"]);
+				let tuples = coordinates.split(' ').collect::<Vec<_>>();
+				assert!(count >= 4, "ring of {count}");
+				assert_eq!(tuples.first(), tuples.last(), "open ring");
+			},
+			other => panic!("unexpected geometry {other}"),
+		}
+	}
+
+	#[test]
+	fn documents_follow_the_schema() {
+		let kml = maps().to_kml("ZZ&Z").unwrap();
+		let document = Document::parse(&kml).unwrap();
+
+		let root = document.root_element();
+		assert_eq!(root.tag_name().namespace(), Some(KML));
+		assert_eq!(root.tag_name().name(), "kml");
+		assert!(document.descendants().all(|node| {
+			!node.is_element() || node.tag_name().namespace() == Some(KML)
+		}));
+
+		assert_eq!(names(children(root)), ["Document"]);
+		let document = children(root).next().unwrap();
+		assert_eq!(text(document, "name"), "ZZ&Z");
+
+		// styles come before the features of the document
+		let kinds = names(children(document));
+		let features = kinds.iter().position(|kind| *kind == "Folder").unwrap();
+		assert!(kinds[1..features].iter().all(|kind| *kind == "Style"));
+		assert!(kinds[features..].iter().all(|kind| *kind == "Folder"));
+
+		let mut ids = HashSet::new();
+		for style in children(document).filter(|node| node.has_tag_name("Style")) {
+			assert!(ids.insert(style.attribute("id").unwrap()));
+			for color in style
+				.descendants()
+				.filter(|node| node.has_tag_name("color"))
+			{
+				let color = color.text().unwrap();
+				assert_eq!(color.len(), 8, "{color}");
+				assert!(color.chars().all(|c| c.is_ascii_hexdigit()), "{color}");
+			}
+		}
+		assert_eq!(ids.len(), 3);
+
+		let folders = children(document)
+			.filter(|node| node.has_tag_name("Folder"))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			folders
+				.iter()
+				.map(|folder| text(*folder, "name"))
+				.collect::<Vec<_>>(),
+			[
+				"node off",
+				"node on",
+				"node selected",
+				"edge off",
+				"edge on",
+				"edge pending",
+				"node targets",
+				"block targets",
+				"widgets",
+			],
+		);
+
+		let mut placemarks = Vec::new();
+		for folder in &folders {
+			let kinds = names(children(*folder));
+			assert_eq!(kinds[0], "name");
+			assert!(kinds[1..].iter().all(|kind| *kind == "Placemark"));
+			placemarks.extend(children(*folder).skip(1));
+		}
+
+		// elements of placemarks are in the order of the schema, with one
+		// geometry each
+		const ORDER: [&str; 5] = [
+			"name",
+			"description",
+			"styleUrl",
+			"ExtendedData",
+			"geometry",
+		];
+		for placemark in &placemarks {
+			let kinds = names(children(*placemark))
+				.into_iter()
+				.map(|kind| match kind {
+					"Point" | "LineString" | "Polygon" => "geometry",
+					kind => kind,
+				})
+				.map(|kind| ORDER.iter().position(|order| *order == kind).unwrap())
+				.collect::<Vec<_>>();
+			assert!(kinds.windows(2).all(|pair| pair[0] < pair[1]), "{kinds:?}");
+			assert_eq!(kinds.last(), Some(&4));
+
+			check_geometry(children(*placemark).last().unwrap());
+
+			let url = text(*placemark, "styleUrl");
+			if !url.is_empty() {
+				assert!(ids.contains(url.strip_prefix('#').unwrap()), "{url}");
+			}
+		}
+
+		let names = placemarks
+			.iter()
+			.map(|placemark| text(*placemark, "name"))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			names,
+			[
+				"A&B",
+				"A&B",
+				"<C>",
+				"E\"1\"",
+				"E\"1\"",
+				"A&B",
+				"B1",
+				"countdown B1",
+				"label HP",
+				"text <stop>",
+			],
+		);
+		assert_eq!(text(placemarks[5], "description"), "A & B");
+
+		// the level and offsets of the line are kept as extended data
+		let data = placemarks[0]
+			.descendants()
+			.filter(|node| node.has_tag_name("Data"))
+			.map(|node| (node.attribute("name").unwrap(), text(node, "value")))
+			.collect::<Vec<_>>();
+		assert_eq!(data, [("level", "-1"), ("offsets", "0,0 2.5,0")]);
+	}
+}
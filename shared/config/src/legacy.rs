@@ -0,0 +1,422 @@
+//! Encoders pinned to older versions of the format, for testing that files
+//! saved by older versions still load.
+//!
+//! Each type is written as it was at the version given, leaving out what had
+//! yet to be added, so values saved at a version load as they would have then,
+//! with anything added since at its default.
+
+use super::*;
+
+use bincode::enc::Encoder;
+
+/// Encoding of a value as at an older version.
+trait EncodeAt {
+	fn encode_at<E: Encoder>(
+		&self,
+		version: FormatVersion,
+		encoder: &mut E,
+	) -> Result<(), EncodeError>;
+}
+
+impl<T: EncodeAt> EncodeAt for Vec<T> {
+	fn encode_at<E: Encoder>(
+		&self,
+		version: FormatVersion,
+		encoder: &mut E,
+	) -> Result<(), EncodeError> {
+		(self.len() as u64).encode(encoder)?;
+		for item in self {
+			item.encode_at(version, encoder)?;
+		}
+		Ok(())
+	}
+}
+
+impl<T: EncodeAt> EncodeAt for Option<T> {
+	fn encode_at<E: Encoder>(
+		&self,
+		version: FormatVersion,
+		encoder: &mut E,
+	) -> Result<(), EncodeError> {
+		match self {
+			Some(value) => {
+				1u8.encode(encoder)?;
+				value.encode_at(version, encoder)
+			},
+			None => 0u8.encode(encoder),
+		}
+	}
+}
+
+/// Encodes a field added in the versions given, as for
+/// [`FormatVersion::since`], if the version is not before them.
+fn encode_since<E: Encoder>(
+	value: &impl Encode,
+	versions: &[u16],
+	version: FormatVersion,
+	encoder: &mut E,
+) -> Result<(), EncodeError> {
+	if version.since(versions) {
+		value.encode(encoder)?;
+	}
+	Ok(())
+}
+
+/// Implements [`EncodeAt`] for a struct, encoding each field as at the version
+/// if it is given as `field: EncodeAt`, if the version is not before those
+/// given if it is given as `field: [VERSIONS]`, and as it is now otherwise.
+macro_rules! encode_at_fields {
+	($ty:ty { $( $field:ident $( : $how:tt )? ),* $(,)? }) => {
+		impl EncodeAt for $ty {
+			fn encode_at<E: Encoder>(
+				&self,
+				version: FormatVersion,
+				encoder: &mut E,
+			) -> Result<(), EncodeError> {
+				$( encode_at_fields!(@field self, version, encoder, $field $( : $how )?); )*
+				Ok(())
+			}
+		}
+	};
+	(@field $self:ident, $version:ident, $encoder:ident, $field:ident) => {
+		$self.$field.encode($encoder)?
+	};
+	(@field $self:ident, $version:ident, $encoder:ident, $field:ident : EncodeAt) => {
+		$self.$field.encode_at($version, $encoder)?
+	};
+	(@field $self:ident, $version:ident, $encoder:ident, $field:ident : [ $versions:expr ]) => {
+		encode_since(&$self.$field, &$versions, $version, $encoder)?
+	};
+}
+
+encode_at_fields!(Config {
+	name,
+	version,
+	aerodromes: EncodeAt,
+});
+
+encode_at_fields!(Aerodrome {
+	icao,
+	elements,
+	nodes: EncodeAt,
+	edges: EncodeAt,
+	blocks: EncodeAt,
+	holding_points: [[HOLDING_POINTS_VERSION]],
+	profiles: EncodeAt,
+	strings: [[STRINGS_VERSION]],
+	geo_map: EncodeAt,
+	maps: EncodeAt,
+	styles: EncodeAt,
+});
+
+encode_at_fields!(Node {
+	id,
+	scratchpad,
+	parent,
+	input_order: [[INPUT_ORDER_VERSION]],
+});
+
+encode_at_fields!(Edge {
+	id,
+	name: [[NAMED_EDGES_VERSION]],
+});
+
+encode_at_fields!(Block {
+	id,
+	nodes,
+	edges,
+	non_routes,
+	stands,
+	input_order: [[INPUT_ORDER_VERSION]],
+});
+
+encode_at_fields!(Profile {
+	id,
+	name,
+	tags: [[PROFILE_TAGS_VERSION]],
+	nodes,
+	edges,
+	blocks,
+	presets: EncodeAt,
+});
+
+encode_at_fields!(Preset {
+	name,
+	includes: [[PRESET_INCLUDES_VERSION]],
+	nodes,
+	blocks,
+});
+
+encode_at_fields!(Maps {
+	nodes,
+	edges,
+	blocks,
+	holding_points: [[map::MAPS_HOLDING_POINTS_VERSION]],
+	geo_map: EncodeAt,
+	maps: EncodeAt,
+	styles: EncodeAt,
+});
+
+encode_at_fields!(GeoMap {
+	nodes: EncodeAt,
+	edges: EncodeAt,
+	blocks: EncodeAt,
+	widgets: EncodeAt,
+});
+
+encode_at_fields!(Map {
+	background,
+	base: EncodeAt,
+	nodes: EncodeAt,
+	edges: EncodeAt,
+	blocks: EncodeAt,
+	widgets: EncodeAt,
+	views,
+});
+
+encode_at_fields!(Style {
+	stroke_style,
+	stroke_width: EncodeAt,
+	stroke_cap,
+	stroke_join,
+	stroke_color,
+	fill_style,
+	fill_color,
+	blink: [map::BLINK_VERSIONS],
+});
+
+impl EncodeAt for StrokeWidth {
+	fn encode_at<E: Encoder>(
+		&self,
+		version: FormatVersion,
+		encoder: &mut E,
+	) -> Result<(), EncodeError> {
+		if version.since(&map::SIXTEENTHS_VERSIONS) {
+			self.encode(encoder)
+		} else {
+			// eighths, rounded down and clamped
+			u8::try_from(self.0 / 2).unwrap_or(u8::MAX).encode(encoder)
+		}
+	}
+}
+
+/// Implements [`EncodeAt`] for each kind of point, as for `encode_at_fields`.
+macro_rules! encode_at_projectable {
+	($ty:ident { $( $field:ident $( : $how:tt )? ),* $(,)? }) => {
+		impl<T: Projectable + Encode> EncodeAt for $ty<T> {
+			fn encode_at<E: Encoder>(
+				&self,
+				version: FormatVersion,
+				encoder: &mut E,
+			) -> Result<(), EncodeError> {
+				$( encode_at_fields!(@field self, version, encoder, $field $( : $how )?); )*
+				Ok(())
+			}
+		}
+	};
+}
+
+encode_at_projectable!(Path {
+	points,
+	style,
+	level: [map::LEVELS_VERSIONS],
+});
+
+encode_at_projectable!(Target {
+	polygons,
+	hover_style: [map::TARGET_STYLES_VERSIONS],
+	active_style: [map::TARGET_STYLES_VERSIONS],
+	level: [map::LEVELS_VERSIONS],
+});
+
+encode_at_projectable!(NodeDisplay {
+	off: EncodeAt,
+	on: EncodeAt,
+	selected: EncodeAt,
+	target: EncodeAt,
+	tooltip: [map::TOOLTIPS_VERSIONS],
+});
+
+encode_at_projectable!(EdgeDisplay {
+	off: EncodeAt,
+	on: EncodeAt,
+	pending: EncodeAt,
+});
+
+encode_at_projectable!(BlockDisplay {
+	target: EncodeAt,
+	tooltip: [map::TOOLTIPS_VERSIONS],
+});
+
+impl<T: Projectable + Encode> EncodeAt for Widget<T> {
+	fn encode_at<E: Encoder>(
+		&self,
+		version: FormatVersion,
+		encoder: &mut E,
+	) -> Result<(), EncodeError> {
+		let Self::Countdown {
+			position,
+			size,
+			condition,
+			format,
+			warn_below_secs,
+			normal_style,
+			warn_style,
+		} = self
+		else {
+			// only countdowns have changed
+			return self.encode(encoder)
+		};
+
+		let versions = &map::COUNTDOWN_STYLES_VERSIONS;
+		0u32.encode(encoder)?;
+		position.encode(encoder)?;
+		size.encode(encoder)?;
+		condition.encode(encoder)?;
+		encode_since(format, versions, version, encoder)?;
+		encode_since(warn_below_secs, versions, version, encoder)?;
+		encode_since(normal_style, versions, version, encoder)?;
+		encode_since(warn_style, versions, version, encoder)
+	}
+}
+
+/// Saves as at the version, which must be one which can still be loaded, in
+/// the layout of the file at that version.
+fn save_at<T: Loadable + EncodeAt>(
+	value: &T,
+	version: u16,
+	mut writer: impl Write,
+) -> Result<(), EncodeError> {
+	if !(T::OLDEST_VERSION..=T::VERSION).contains(&version) {
+		return Err(EncodeError::Other("version cannot be loaded"))
+	}
+
+	let data =
+		bincode::encode_to_vec(At(value, FormatVersion(version)), BINCODE_CONFIG)?;
+	let payload = compress(&data)?;
+
+	writer.write_all(MAGIC).map_err(encode_io_error)?;
+	writer
+		.write_all(&version.to_be_bytes())
+		.map_err(encode_io_error)?;
+
+	// before checksums, the compressed contents ran to the end of the file
+	if version >= T::CHECKSUM_VERSION {
+		write_section(writer, &payload)
+	} else {
+		writer.write_all(&payload).map_err(encode_io_error)
+	}
+}
+
+/// A value encoded as at a version.
+struct At<'a, T>(&'a T, FormatVersion);
+
+impl<T: EncodeAt> Encode for At<'_, T> {
+	fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+		self.0.encode_at(self.1, encoder)
+	}
+}
+
+impl Config {
+	/// Saves the config as a file of an older version, for testing that such
+	/// files still load. Anything added since the version is left out.
+	pub fn save_at(
+		&self,
+		version: u16,
+		writer: impl Write,
+	) -> Result<(), EncodeError> {
+		save_at(self, version, writer)
+	}
+}
+
+impl Maps {
+	/// Saves the maps as a file of an older version, as for
+	/// [`Config::save_at`].
+	pub fn save_at(
+		&self,
+		version: u16,
+		writer: impl Write,
+	) -> Result<(), EncodeError> {
+		save_at(self, version, writer)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use proptest::prelude::*;
+
+	fn resave<T: Loadable + EncodeAt>(value: &T, version: u16) -> (Vec<u8>, T) {
+		let mut saved = Vec::new();
+		save_at(value, version, &mut saved).unwrap();
+		let loaded = T::load(saved.as_slice()).unwrap();
+
+		let mut resaved = Vec::new();
+		save_at(&loaded, version, &mut resaved).unwrap();
+		assert_eq!(saved, resaved, "version {version:#06x}");
+
+		(saved, loaded)
+	}
+
+	proptest! {
+		#[test]
+		fn configs_round_trip_at_every_version(config: Config) {
+			for version in Config::OLDEST_VERSION..=Config::VERSION {
+				resave(&config, version);
+			}
+		}
+
+		#[test]
+		fn maps_round_trip_at_every_version(maps: Maps) {
+			for version in Maps::OLDEST_VERSION..=Maps::VERSION {
+				resave(&maps, version);
+			}
+		}
+
+		#[test]
+		fn current_version_saves_as_save(config: Config, maps: Maps) {
+			let mut saved = Vec::new();
+			config.save(&mut saved).unwrap();
+			let mut saved_at = Vec::new();
+			config.save_at(Config::VERSION, &mut saved_at).unwrap();
+			prop_assert_eq!(saved, saved_at);
+
+			let mut saved = Vec::new();
+			maps.save(&mut saved).unwrap();
+			let mut saved_at = Vec::new();
+			maps.save_at(Maps::VERSION, &mut saved_at).unwrap();
+			prop_assert_eq!(saved, saved_at);
+		}
+
+		#[test]
+		fn oldest_config_loads_additions_as_defaults(config: Config) {
+			let (_, loaded) = resave(&config, Config::OLDEST_VERSION);
+
+			for aerodrome in &loaded.aerodromes {
+				prop_assert!(aerodrome.holding_points.is_empty());
+				prop_assert!(aerodrome.strings.is_none());
+				prop_assert!(aerodrome.nodes.iter().all(|n| n.input_order.is_none()));
+				prop_assert!(aerodrome.edges.iter().all(|e| e.name.is_none()));
+				prop_assert!(aerodrome.blocks.iter().all(|b| b.input_order.is_none()));
+
+				for profile in &aerodrome.profiles {
+					prop_assert!(profile.tags.is_empty());
+					prop_assert!(profile.presets.iter().all(|p| p.includes.is_empty()));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn versions_out_of_range_are_refused() {
+		let config = Config {
+			name: None,
+			version: None,
+			aerodromes: Vec::new(),
+		};
+		for version in [Config::OLDEST_VERSION - 1, Config::VERSION + 1] {
+			assert!(config.save_at(version, Vec::new()).is_err());
+		}
+	}
+}
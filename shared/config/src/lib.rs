@@ -1,15 +1,46 @@
+mod attach;
+mod coverage;
+mod dictionary;
+#[cfg(feature = "ese")]
+mod ese;
+mod expression;
+#[cfg(test)]
+mod fixture;
+mod hash;
+mod index;
+mod indexed;
+#[cfg(feature = "kml")]
+mod kml;
+#[cfg(feature = "testing")]
+mod legacy;
 mod map;
+mod preset;
+mod progress;
+mod projection;
+#[cfg(feature = "source")]
+mod source;
+mod stand;
+mod strings;
+mod subset;
+#[cfg(feature = "testing")]
+mod synthetic;
+#[cfg(feature = "testing")]
+mod testing;
 #[cfg(feature = "topsky")]
 mod topsky;
+mod validate;
 
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
-use std::io::{Error as IoError, Read, Write};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Write};
 use std::marker::PhantomData;
+use std::ops::ControlFlow;
 
-use bincode::config::Configuration as BincodeConfig;
+use bincode::config::{
+	Configuration as BincodeConfig, Limit, LittleEndian, Varint,
+};
 use bincode::error::{DecodeError, EncodeError};
 use bincode::{Decode, Encode};
 
@@ -17,62 +48,298 @@ use flate2::read::DeflateDecoder;
 use flate2::write::DeflateEncoder;
 use flate2::Compression;
 
+pub use attach::*;
+pub use coverage::*;
+pub use dictionary::*;
+#[cfg(feature = "ese")]
+pub use ese::*;
+pub use expression::*;
+pub use index::*;
+pub use indexed::*;
 pub use map::*;
+pub use preset::*;
+pub use progress::*;
+pub use projection::*;
+#[cfg(feature = "source")]
+pub use source::*;
+pub use stand::*;
+pub use strings::*;
+pub use subset::*;
+#[cfg(feature = "testing")]
+pub use synthetic::*;
+#[cfg(feature = "topsky")]
+pub use topsky::*;
+pub use validate::*;
 
 static MAGIC: &[u8] = b"\xffBARS\x13eu";
 
 const BINCODE_CONFIG: BincodeConfig = bincode::config::standard();
 
-pub trait Loadable: Decode<()> + Encode {
+/// Upper bound on memory claimed while decoding, so that corrupt length
+/// prefixes cause an error rather than a huge allocation.
+pub const DECODE_LIMIT: usize = 1 << 26;
+
+const BINCODE_DECODE_CONFIG: BincodeConfig<
+	LittleEndian,
+	Varint,
+	Limit<DECODE_LIMIT>,
+> = BINCODE_CONFIG.with_limit();
+
+/// Flag set in the version of a config saved with an index by
+/// [`Config::save_indexed`].
+pub const INDEXED_FLAG: u16 = 0x4000;
+
+/// Flag set in the versions of maps files, and clear in those of configs.
+pub const MAPS_FLAG: u16 = 0x8000;
+
+/// A file which can be saved and loaded, being the magic number, the version,
+/// and the compressed contents.
+///
+/// From [`Loadable::CHECKSUM_VERSION`], the contents are preceded by their
+/// compressed length and followed by a CRC-32 of them, so that truncated and
+/// corrupt files are told apart from those which fail to decode.
+pub trait Loadable: Decode<FormatVersion> + Encode {
 	const VERSION: u16;
+	/// oldest version which can be loaded, files from which are decoded as
+	/// they were encoded then
+	const OLDEST_VERSION: u16;
+	/// oldest version with a length and checksum around the contents
+	const CHECKSUM_VERSION: u16;
+
+	fn load(reader: impl Read) -> Result<Self, LoadError> {
+		Self::load_with_progress(reader, None, |_| ControlFlow::Continue(()))
+	}
 
-	fn load(mut reader: impl Read) -> Result<Self, DecodeError> {
-		fn bincode_error(error: IoError) -> DecodeError {
-			DecodeError::Io {
-				inner: error,
-				additional: 0,
-			}
-		}
+	/// Loads as for [`Loadable::load`], calling `progress` as the file is read,
+	/// every so many bytes rather than for each.
+	///
+	/// The size of the file, if given as `total`, is passed on so that the part
+	/// read can be shown. The load stops with [`LoadError::Cancelled`] as soon
+	/// as the callback returns [`ControlFlow::Break`].
+	fn load_with_progress(
+		reader: impl Read,
+		total: Option<u64>,
+		progress: impl FnMut(Progress) -> ControlFlow<()>,
+	) -> Result<Self, LoadError> {
+		let mut reader = ProgressReader::new(reader, total, progress);
+
+		reader.report(LoadPhase::Header)?;
+		let version = read_version(&mut reader).map_err(|err| reader.error(err))?;
+		let indexed = version & INDEXED_FLAG != 0;
+		let version = supported_version::<Self>(version & !INDEXED_FLAG)
+			.map_err(|err| reader.error(err))?;
+
+		reader.report(LoadPhase::Decode)?;
+		let loaded = if indexed {
+			Self::load_indexed(&mut reader, version)
+		} else if version.0 >= Self::CHECKSUM_VERSION {
+			read_section(&mut reader)
+				.and_then(|payload| Ok(decode_section(&payload, version)?))
+				.and_then(|loaded| expect_end(&mut reader).map(|_| loaded))
+		} else {
+			bincode::decode_from_std_read_with_context(
+				&mut DeflateDecoder::new(&mut reader),
+				BINCODE_DECODE_CONFIG,
+				version,
+			)
+			.map_err(LoadError::from)
+		};
 
-		let mut buf = vec![0; MAGIC.len()];
-		reader.read_exact(&mut buf).map_err(bincode_error)?;
+		let loaded = loaded.map_err(|err| reader.error(err))?;
+		reader.report(LoadPhase::Done)?;
 
-		if buf != MAGIC {
-			return Err(DecodeError::Other("invalid config file"))
-		}
+		Ok(loaded)
+	}
 
-		let mut buf = [0; 2];
-		reader.read_exact(&mut buf).map_err(bincode_error)?;
+	/// Decodes the rest of a file saved with an index, after its header, for
+	/// types which can be.
+	fn load_indexed(
+		_reader: impl Read,
+		version: FormatVersion,
+	) -> Result<Self, LoadError> {
+		Err(LoadError::UnsupportedVersion {
+			version: version.0 | INDEXED_FLAG,
+			oldest: Self::OLDEST_VERSION,
+			newest: Self::VERSION,
+		})
+	}
 
-		if buf != Self::VERSION.to_be_bytes() {
-			return Err(DecodeError::Other("unsupported config version"))
-		}
+	fn save(&self, mut writer: impl Write) -> Result<(), EncodeError> {
+		let payload = compress(&bincode::encode_to_vec(self, BINCODE_CONFIG)?)?;
 
-		let mut reader = DeflateDecoder::new(reader);
-		bincode::decode_from_std_read(&mut reader, BINCODE_CONFIG)
+		writer.write_all(MAGIC).map_err(encode_io_error)?;
+		(writer.write_all(&Self::VERSION.to_be_bytes()))
+			.map_err(encode_io_error)?;
+		write_section(writer, &payload)
 	}
+}
 
-	fn save(&self, mut writer: impl Write) -> Result<(), EncodeError> {
-		fn bincode_error(error: IoError) -> EncodeError {
-			EncodeError::Io {
-				inner: error,
-				index: 0,
-			}
+fn encode_io_error(error: IoError) -> EncodeError {
+	EncodeError::Io {
+		inner: error,
+		index: 0,
+	}
+}
+
+/// Compresses encoded data to be written as a section.
+fn compress(data: &[u8]) -> Result<Vec<u8>, EncodeError> {
+	let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+	encoder.write_all(data).map_err(encode_io_error)?;
+	encoder.finish().map_err(encode_io_error)
+}
+
+/// Writes compressed contents as a section, preceded by their length and
+/// followed by their checksum.
+fn write_section(
+	mut writer: impl Write,
+	payload: &[u8],
+) -> Result<(), EncodeError> {
+	let length = u32::try_from(payload.len())
+		.map_err(|_| EncodeError::Other("contents too large"))?;
+
+	let checksum = crc32fast::hash(payload);
+	for part in [&length.to_be_bytes(), payload, &checksum.to_be_bytes()] {
+		writer.write_all(part).map_err(encode_io_error)?;
+	}
+
+	Ok(())
+}
+
+/// Decompresses and decodes the contents of a section.
+fn decode_section<T: Decode<FormatVersion>>(
+	payload: &[u8],
+	version: FormatVersion,
+) -> Result<T, DecodeError> {
+	bincode::decode_from_std_read_with_context(
+		&mut DeflateDecoder::new(payload),
+		BINCODE_DECODE_CONFIG,
+		version,
+	)
+}
+
+/// The version of the file being decoded, as the context of decoding, for
+/// types whose encoding has changed between versions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatVersion(pub u16);
+
+impl FormatVersion {
+	/// Returns whether the file is at least as new as the version of its kind,
+	/// config or maps, among those given, in which something was changed.
+	pub fn since(self, versions: &[u16]) -> bool {
+		(versions.iter())
+			.filter(|version| *version & MAPS_FLAG == self.0 & MAPS_FLAG)
+			.all(|version| self.0 >= *version)
+	}
+}
+
+/// Decodes a field added in the versions given, as for
+/// [`FormatVersion::since`], or gives its default for files from before it.
+fn decode_since<T: Decode<FormatVersion> + Default>(
+	decoder: &mut impl bincode::de::Decoder<Context = FormatVersion>,
+	versions: &[u16],
+) -> Result<T, DecodeError> {
+	if decoder.context().since(versions) {
+		T::decode(decoder)
+	} else {
+		Ok(T::default())
+	}
+}
+
+fn supported_version<T: Loadable>(
+	version: u16,
+) -> Result<FormatVersion, LoadError> {
+	if (T::OLDEST_VERSION..=T::VERSION).contains(&version) {
+		Ok(FormatVersion(version))
+	} else {
+		Err(LoadError::UnsupportedVersion {
+			version,
+			oldest: T::OLDEST_VERSION,
+			newest: T::VERSION,
+		})
+	}
+}
+
+/// Reads into `buf` until it is full or the reader ends, returning the number
+/// of bytes read.
+fn read_up_to(mut reader: impl Read, buf: &mut [u8]) -> Result<usize, IoError> {
+	let mut read = 0;
+	while read < buf.len() {
+		match reader.read(&mut buf[read..]) {
+			Ok(0) => break,
+			Ok(n) => read += n,
+			Err(err) if err.kind() == IoErrorKind::Interrupted => (),
+			Err(err) => return Err(err),
 		}
+	}
 
-		writer.write_all(&MAGIC).map_err(bincode_error)?;
-		writer
-			.write_all(&Self::VERSION.to_be_bytes())
-			.map_err(bincode_error)?;
+	Ok(read)
+}
 
-		let mut writer = DeflateEncoder::new(writer, Compression::best());
-		bincode::encode_into_std_write(self, &mut writer, BINCODE_CONFIG)?;
+/// Reads the header of a config or maps file, returning the version of the
+/// file without decoding the rest.
+///
+/// Versions of maps files have the high bit set, so the kind of file can be
+/// told from its version.
+pub fn read_version(mut reader: impl Read) -> Result<u16, LoadError> {
+	let mut buf = vec![0; MAGIC.len()];
+	let read = read_up_to(&mut reader, &mut buf)?;
+
+	// a file cut short within the magic number is only truncated if what
+	// there is of it matches
+	if buf[..read] != MAGIC[..read] {
+		return Err(LoadError::InvalidMagic)
+	} else if read < MAGIC.len() {
+		return Err(LoadError::Truncated)
+	}
+
+	let mut buf = [0; 2];
+	if read_up_to(&mut reader, &mut buf)? < buf.len() {
+		return Err(LoadError::Truncated)
+	}
+
+	Ok(u16::from_be_bytes(buf))
+}
+
+/// Reads the compressed contents of a section, checking them against their
+/// length and checksum.
+fn read_section(mut reader: impl Read) -> Result<Vec<u8>, LoadError> {
+	let mut buf = [0; 4];
+	if read_up_to(&mut reader, &mut buf)? < buf.len() {
+		return Err(LoadError::Truncated)
+	}
 
+	// read as it arrives rather than allocated up front, as the length may be
+	// corrupt
+	let length = u32::from_be_bytes(buf) as usize;
+	let mut payload = Vec::new();
+	(&mut reader)
+		.take(length as u64 + 4)
+		.read_to_end(&mut payload)?;
+
+	if payload.len() < length + 4 {
+		return Err(LoadError::Truncated)
+	}
+
+	let checksum = payload.split_off(length);
+	if checksum != crc32fast::hash(&payload).to_be_bytes() {
+		return Err(LoadError::Corrupt)
+	}
+
+	Ok(payload)
+}
+
+/// Checks that nothing follows the last section.
+fn expect_end(reader: impl Read) -> Result<(), LoadError> {
+	if read_up_to(reader, &mut [0])? > 0 {
+		Err(LoadError::Corrupt)
+	} else {
 		Ok(())
 	}
 }
 
 #[derive(Clone, Debug, Decode, Encode)]
+#[bincode(decode_context = "FormatVersion")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Config {
 	pub name: Option<String>,
 	pub version: Option<String>,
@@ -81,10 +348,20 @@ pub struct Config {
 }
 
 impl Loadable for Config {
-	const VERSION: u16 = 0x0002;
+	const VERSION: u16 = 0x0010;
+	const OLDEST_VERSION: u16 = 0x0002;
+	const CHECKSUM_VERSION: u16 = 0x000e;
+
+	fn load_indexed(
+		reader: impl Read,
+		version: FormatVersion,
+	) -> Result<Self, LoadError> {
+		indexed::load_indexed(reader, version)
+	}
 }
 
-#[derive(Clone, Debug, Decode, Encode)]
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Aerodrome {
 	pub icao: String,
 
@@ -92,59 +369,190 @@ pub struct Aerodrome {
 	pub nodes: Vec<Node>,
 	pub edges: Vec<Edge>,
 	pub blocks: Vec<Block>,
+	pub holding_points: Vec<HoldingPoint>,
 
 	pub profiles: Vec<Profile>,
+	/// display strings in other languages
+	pub strings: Option<LocaleTable>,
 
 	pub geo_map: Option<GeoMap>,
 	pub maps: Vec<Map>,
 	pub styles: Vec<Style>,
 }
 
+/// first version of configs in which aerodromes have holding points
+pub(crate) const HOLDING_POINTS_VERSION: u16 = 0x0003;
+/// first version of configs in which aerodromes have display strings
+pub(crate) const STRINGS_VERSION: u16 = 0x0005;
+
+impl Decode<FormatVersion> for Aerodrome {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			icao: Decode::decode(decoder)?,
+			elements: Decode::decode(decoder)?,
+			nodes: Decode::decode(decoder)?,
+			edges: Decode::decode(decoder)?,
+			blocks: Decode::decode(decoder)?,
+			holding_points: decode_since(decoder, &[HOLDING_POINTS_VERSION])?,
+			profiles: Decode::decode(decoder)?,
+			strings: decode_since(decoder, &[STRINGS_VERSION])?,
+			geo_map: Decode::decode(decoder)?,
+			maps: Decode::decode(decoder)?,
+			styles: Decode::decode(decoder)?,
+		})
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(Aerodrome, FormatVersion);
+
 impl Aerodrome {
+	/// Decodes an aerodrome encoded by [`Aerodrome::encode`], at the current
+	/// version.
 	pub fn decode(serialised: &[u8]) -> Result<Self, DecodeError> {
-		Ok(bincode::decode_from_slice(serialised, BINCODE_CONFIG)?.0)
+		let version = FormatVersion(Config::VERSION);
+		Ok(
+			bincode::decode_from_slice_with_context(
+				serialised,
+				BINCODE_DECODE_CONFIG,
+				version,
+			)?
+			.0,
+		)
 	}
 
 	pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
 		bincode::encode_to_vec(self, BINCODE_CONFIG)
 	}
 
-	pub fn append_maps(&mut self, mut maps: Maps) {
-		let offset = self.styles.len();
-		self.styles.append(&mut maps.styles);
-
-		fn rebase<'a>(
-			source: Vec<String>,
-			target: impl Iterator<Item = &'a String>,
-		) -> Vec<Option<usize>> {
-			let map = source
-				.into_iter()
-				.enumerate()
-				.map(|(i, id)| (id, i))
-				.collect::<HashMap<_, _>>();
-			target.map(|id| map.get(id).copied()).collect()
+	/// Returns the encoded size of each field, which sum to the size of the
+	/// whole aerodrome.
+	pub fn encoded_sizes(
+		&self,
+	) -> Result<Vec<(&'static str, usize)>, EncodeError> {
+		fn size(value: &impl Encode) -> Result<usize, EncodeError> {
+			Ok(bincode::encode_to_vec(value, BINCODE_CONFIG)?.len())
+		}
+
+		Ok(vec![
+			("icao", size(&self.icao)?),
+			("elements", size(&self.elements)?),
+			("nodes", size(&self.nodes)?),
+			("edges", size(&self.edges)?),
+			("blocks", size(&self.blocks)?),
+			("holding_points", size(&self.holding_points)?),
+			("profiles", size(&self.profiles)?),
+			("strings", size(&self.strings)?),
+			("geo_map", size(&self.geo_map)?),
+			("maps", size(&self.maps)?),
+			("styles", size(&self.styles)?),
+		])
+	}
+
+	/// Returns the holding point with the id, if any.
+	pub fn holding_point(&self, id: &str) -> Option<Ref<HoldingPoint>> {
+		self
+			.holding_points
+			.iter()
+			.position(|holding_point| holding_point.id == id)
+			.map(Ref::from)
+	}
+
+	/// Returns the node at the top of the hierarchy of the node, which is the
+	/// node itself if it has no parent.
+	pub fn root_node(&self, node: Ref<Node>) -> Ref<Node> {
+		let mut root = node;
+
+		// bounded, as a cycle of parents, which validation rejects, has no top
+		for _ in 0..self.nodes.len() {
+			match self.nodes.get(root.0).and_then(|node| node.parent) {
+				Some(parent) => root = parent,
+				None => break,
+			}
 		}
 
-		let rebase = Rebase {
-			offset,
-			nodes: rebase(maps.nodes, self.nodes.iter().map(|node| &node.id)),
-			edges: rebase(maps.edges, self.edges.iter().map(|edge| &edge.id)),
-			blocks: rebase(maps.blocks, self.blocks.iter().map(|block| &block.id)),
-		};
+		root
+	}
+
+	/// Returns whether the node is the ancestor or descends from it, through
+	/// any number of parents.
+	pub fn is_within(&self, node: Ref<Node>, ancestor: Ref<Node>) -> bool {
+		let mut node = node;
+
+		for _ in 0..=self.nodes.len() {
+			if node == ancestor {
+				return true
+			}
 
-		if let Some(geo_map) = maps.geo_map {
-			self.geo_map = Some(geo_map.rebase(&rebase));
+			match self.nodes.get(node.0).and_then(|node| node.parent) {
+				Some(parent) => node = parent,
+				None => break,
+			}
 		}
 
+		false
+	}
+
+	/// Returns the index and scenery id of each element for the providers.
+	///
+	/// With no providers, elements are returned with their full ids. Otherwise
+	/// the ids are translated to those known to the providers, which may be
+	/// shared by several elements; validation ensures that these have the same
+	/// condition.
+	pub fn provider_elements<'a>(
+		&'a self,
+		providers: &'a [impl AsRef<str>],
+	) -> impl Iterator<Item = (usize, &'a str)> {
 		self
-			.maps
-			.extend(maps.maps.into_iter().map(|map| map.rebase(&rebase)));
+			.elements
+			.iter()
+			.enumerate()
+			.filter_map(|(i, element)| Some((i, element.provider_id(providers)?)))
+	}
+
+	/// Returns the size of the aerodrome once encoded and compressed.
+	pub fn compressed_size(&self) -> Result<usize, EncodeError> {
+		let mut writer = DeflateEncoder::new(Vec::new(), Compression::best());
+		bincode::encode_into_std_write(self, &mut writer, BINCODE_CONFIG)?;
+
+		let buf = writer
+			.finish()
+			.map_err(|inner| EncodeError::Io { inner, index: 0 })?;
+		Ok(buf.len())
 	}
 }
 
-#[derive(Debug, Decode, Encode)]
+/// An index into a list of the aerodrome.
+///
+/// Refs are encoded as `u32`, which under varint encoding is byte-for-byte the
+/// same as the `usize` encoding used previously, so existing configs decode
+/// unchanged. Encoding a ref above `u32::MAX` is an error rather than being
+/// truncated.
+#[derive(Debug)]
 pub struct Ref<T>(pub usize, PhantomData<T>);
 
+impl<T> Encode for Ref<T> {
+	fn encode<E: bincode::enc::Encoder>(
+		&self,
+		encoder: &mut E,
+	) -> Result<(), EncodeError> {
+		u32::try_from(self.0)
+			.map_err(|_| EncodeError::Other("ref out of range"))?
+			.encode(encoder)
+	}
+}
+
+impl<T, Context> Decode<Context> for Ref<T> {
+	fn decode<D: bincode::de::Decoder<Context = Context>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok((u32::decode(decoder)? as usize).into())
+	}
+}
+
+bincode::impl_borrow_decode!(Ref<T>, T);
+
 impl<T> Clone for Ref<T> {
 	fn clone(&self) -> Self {
 		*self
@@ -169,7 +577,7 @@ impl<T> Eq for Ref<T> {}
 
 impl<T> PartialOrd for Ref<T> {
 	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-		self.0.partial_cmp(&other.0)
+		Some(self.cmp(other))
 	}
 }
 
@@ -191,35 +599,127 @@ impl<T> From<Ref<T>> for usize {
 	}
 }
 
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Ref<T> {
+	fn serialize<S: serde::Serializer>(
+		&self,
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		serializer.serialize_u64(self.0 as u64)
+	}
+}
+
 #[derive(Clone, Debug, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Element {
+	/// scenery object id, optionally prefixed by a provider as in `msfs:OBJ1`
 	pub id: String,
 	pub condition: ElementCondition,
 }
 
+impl Element {
+	/// Returns the scenery provider the element is for, if it is not for all.
+	pub fn provider(&self) -> Option<&str> {
+		self.id.split_once(':').map(|(provider, _)| provider)
+	}
+
+	/// Returns the id without any provider prefix, as known to the provider.
+	pub fn local_id(&self) -> &str {
+		self.id.split_once(':').map_or(&self.id, |(_, id)| id)
+	}
+
+	/// Returns whether the element is for any of the providers, where an
+	/// element without a provider is for all, as is every element if no
+	/// providers are given.
+	pub fn is_for(&self, providers: &[impl AsRef<str>]) -> bool {
+		match self.provider() {
+			Some(provider) if !providers.is_empty() => {
+				providers.iter().any(|p| p.as_ref() == provider)
+			},
+			_ => true,
+		}
+	}
+
+	/// Returns the scenery id of the element for the providers, if it is for
+	/// any of them, being the id known to the providers if any are given.
+	pub fn provider_id(&self, providers: &[impl AsRef<str>]) -> Option<&str> {
+		if !self.is_for(providers) {
+			None
+		} else if providers.is_empty() {
+			Some(&self.id)
+		} else {
+			Some(self.local_id())
+		}
+	}
+}
+
 #[derive(
-	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
+	Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ElementCondition {
 	Fixed(bool),
 	Node(Ref<Node>),
 	Edge(Ref<Edge>),
+	Expression(ConditionExpression),
 }
 
-#[derive(Clone, Debug, Decode, Encode)]
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Node {
 	pub id: String,
 
 	pub scratchpad: Option<String>,
 	pub parent: Option<Ref<Node>>,
+	/// place in the keyboard selection order, before anything without one
+	pub input_order: Option<u16>,
 }
 
-#[derive(Clone, Debug, Decode, Encode)]
+/// first version of configs in which nodes and blocks have a place in the
+/// keyboard selection order
+pub(crate) const INPUT_ORDER_VERSION: u16 = 0x000b;
+
+impl Decode<FormatVersion> for Node {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			id: Decode::decode(decoder)?,
+			scratchpad: Decode::decode(decoder)?,
+			parent: Decode::decode(decoder)?,
+			input_order: decode_since(decoder, &[INPUT_ORDER_VERSION])?,
+		})
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(Node, FormatVersion);
+
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Edge {
 	pub id: String,
+	/// readable name, such as of the taxiway, if it differs from the id
+	pub name: Option<String>,
 }
 
-#[derive(Clone, Debug, Decode, Encode)]
+/// first version of configs in which edges have names
+pub(crate) const NAMED_EDGES_VERSION: u16 = 0x0010;
+
+impl Decode<FormatVersion> for Edge {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			id: Decode::decode(decoder)?,
+			name: decode_since(decoder, &[NAMED_EDGES_VERSION])?,
+		})
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(Edge, FormatVersion);
+
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Block {
 	pub id: String,
 
@@ -229,21 +729,65 @@ pub struct Block {
 	pub non_routes: Vec<BlockRoute>,
 
 	pub stands: Vec<String>,
+	/// place in the keyboard selection order, before anything without one
+	pub input_order: Option<u16>,
+}
+
+impl Decode<FormatVersion> for Block {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			id: Decode::decode(decoder)?,
+			nodes: Decode::decode(decoder)?,
+			edges: Decode::decode(decoder)?,
+			non_routes: Decode::decode(decoder)?,
+			stands: Decode::decode(decoder)?,
+			input_order: decode_since(decoder, &[INPUT_ORDER_VERSION])?,
+		})
+	}
 }
 
+bincode::impl_borrow_decode_with_context!(Block, FormatVersion);
+
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 /// child nodes only
 pub struct BlockRoute {
 	pub from: Ref<Node>,
 	pub to: Ref<Node>,
 }
 
+/// An intermediate holding point, such as one painted between two stopbars.
 #[derive(Clone, Debug, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HoldingPoint {
+	pub id: String,
+	pub label: String,
+
+	pub location: HoldingPointLocation,
+	pub position: Option<Geo>,
+}
+
+#[derive(
+	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum HoldingPointLocation {
+	Edge(Ref<Edge>),
+	/// between two nodes, in either order
+	Nodes(Ref<Node>, Ref<Node>),
+}
+
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Profile {
 	pub id: String,
 	pub name: String,
+	/// conditions the profile is for, such as `lvp` for low visibility
+	pub tags: Vec<String>,
 
 	pub nodes: Vec<NodeCondition>,
 	pub edges: Vec<EdgeCondition>,
@@ -252,9 +796,31 @@ pub struct Profile {
 	pub presets: Vec<Preset>,
 }
 
+/// first version of configs in which profiles have tags
+pub(crate) const PROFILE_TAGS_VERSION: u16 = 0x000c;
+
+impl Decode<FormatVersion> for Profile {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			id: Decode::decode(decoder)?,
+			name: Decode::decode(decoder)?,
+			tags: decode_since(decoder, &[PROFILE_TAGS_VERSION])?,
+			nodes: Decode::decode(decoder)?,
+			edges: Decode::decode(decoder)?,
+			blocks: Decode::decode(decoder)?,
+			presets: Decode::decode(decoder)?,
+		})
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(Profile, FormatVersion);
+
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum NodeCondition {
 	Fixed { state: NodeState },
 	Direct { reset: ResetCondition },
@@ -262,6 +828,7 @@ pub enum NodeCondition {
 }
 
 #[derive(Clone, Debug, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum EdgeCondition {
 	Fixed {
 		state: EdgeState,
@@ -273,9 +840,15 @@ pub enum EdgeCondition {
 		block: Ref<Block>,
 		routes: Vec<BlockRoute>,
 	},
+	/// on while the expression holds, which may not refer to edges which are
+	/// themselves driven by expressions
+	Expression {
+		expression: ConditionExpression,
+	},
 }
 
 #[derive(Clone, Debug, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NodeExpression {
 	pub disjunction: Vec<NodeConjunction>,
 }
@@ -295,9 +868,17 @@ impl NodeExpression {
 			EdgeState::Off
 		}
 	}
+
+	/// Returns every node of the expression, positive or negative.
+	pub fn nodes(&self) -> impl Iterator<Item = &Ref<Node>> {
+		(self.disjunction.iter()).flat_map(|conjunction| {
+			conjunction.positive.iter().chain(&conjunction.negative)
+		})
+	}
 }
 
 #[derive(Clone, Debug, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NodeConjunction {
 	pub positive: Vec<Ref<Node>>,
 	pub negative: Vec<Ref<Node>>,
@@ -316,9 +897,67 @@ impl NodeConjunction {
 	}
 }
 
+/// A condition on the states of nodes, edges and blocks, as a disjunction of
+/// conjunctions.
+#[derive(
+	Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConditionExpression {
+	pub disjunction: Vec<ConditionConjunction>,
+}
+
+#[derive(
+	Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConditionConjunction {
+	pub positive: Vec<ConditionTerm>,
+	pub negative: Vec<ConditionTerm>,
+}
+
+#[derive(
+	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ConditionTerm {
+	/// the node is on
+	Node(Ref<Node>),
+	/// the edge is on
+	Edge(Ref<Edge>),
+	Block(Ref<Block>, BlockPredicate),
+}
+
+/// A test of the state of a block.
+#[derive(
+	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum BlockPredicate {
+	Clear,
+	Relax,
+	/// any route is selected
+	AnyRoute,
+	/// the route between the parent nodes is selected, in that direction
+	Route((Ref<Node>, Ref<Node>)),
+}
+
+impl BlockPredicate {
+	pub fn matches(&self, state: &BlockState) -> bool {
+		match (self, state) {
+			(Self::Clear, BlockState::Clear) => true,
+			(Self::Relax, BlockState::Relax) => true,
+			(Self::AnyRoute, BlockState::Route(_)) => true,
+			(Self::Route(route), BlockState::Route(state)) => route == state,
+			_ => false,
+		}
+	}
+}
+
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BlockCondition {
 	pub reset: ResetCondition,
 }
@@ -326,22 +965,46 @@ pub struct BlockCondition {
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ResetCondition {
 	None,
 	TimeSecs(u32),
 }
 
-#[derive(Clone, Debug, Decode, Encode)]
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Preset {
 	pub name: String,
 
+	/// presets of the same profile applied first, later ones taking precedence
+	pub includes: Vec<Ref<Preset>>,
+
 	pub nodes: Vec<(Ref<Node>, NodeState)>,
 	pub blocks: Vec<(Ref<Block>, BlockState)>,
 }
 
+/// first version of configs in which presets include others
+pub(crate) const PRESET_INCLUDES_VERSION: u16 = 0x0004;
+
+impl Decode<FormatVersion> for Preset {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			name: Decode::decode(decoder)?,
+			includes: decode_since(decoder, &[PRESET_INCLUDES_VERSION])?,
+			nodes: Decode::decode(decoder)?,
+			blocks: Decode::decode(decoder)?,
+		})
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(Preset, FormatVersion);
+
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u8)]
 pub enum NodeState {
 	Off,
@@ -351,6 +1014,7 @@ pub enum NodeState {
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u8)]
 pub enum EdgeState {
 	Off,
@@ -360,9 +1024,241 @@ pub enum EdgeState {
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BlockState {
 	Clear,
 	Relax,
 	/// parent nodes
 	Route((Ref<Node>, Ref<Node>)),
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::path::{Path, PathBuf};
+
+	fn saved(value: &impl Loadable) -> Vec<u8> {
+		let mut data = Vec::new();
+		value.save(&mut data).unwrap();
+		data
+	}
+
+	fn files() -> [Vec<u8>; 2] {
+		let config = Config {
+			name: Some("test".into()),
+			version: Some("2025/01".into()),
+			aerodromes: Vec::new(),
+		};
+		let maps = Maps {
+			nodes: vec!["N0".into()],
+			edges: Vec::new(),
+			blocks: Vec::new(),
+			holding_points: Vec::new(),
+			geo_map: None,
+			maps: Vec::new(),
+			styles: Vec::new(),
+		};
+
+		[saved(&config), saved(&maps)]
+	}
+
+	fn load(data: &[u8]) -> Result<(), LoadError> {
+		if read_version(data)? & MAPS_FLAG == 0 {
+			Config::load(data).map(drop)
+		} else {
+			Maps::load(data).map(drop)
+		}
+	}
+
+	/// Returns the inputs found by fuzzing to have crashed the decoders, each
+	/// with its path.
+	fn fuzz_regressions() -> Vec<(PathBuf, Vec<u8>)> {
+		let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+		let mut dirs = vec![root.join("fuzz/regressions")];
+		let mut files = Vec::new();
+
+		while let Some(dir) = dirs.pop() {
+			for entry in std::fs::read_dir(dir).unwrap() {
+				let path = entry.unwrap().path();
+				if path.is_dir() {
+					dirs.push(path);
+				} else {
+					let data = std::fs::read(&path).unwrap();
+					files.push((path, data));
+				}
+			}
+		}
+
+		assert!(!files.is_empty());
+		files
+	}
+
+	#[test]
+	fn fuzz_regressions_are_errors() {
+		for (path, data) in fuzz_regressions() {
+			let path = path.display();
+			assert!(Aerodrome::decode(&data).is_err(), "{path}");
+			assert!(Config::load(data.as_slice()).is_err(), "{path}");
+			assert!(Maps::load(data.as_slice()).is_err(), "{path}");
+		}
+	}
+
+	fn encoded(value: impl Encode) -> Result<Vec<u8>, EncodeError> {
+		bincode::encode_to_vec(value, BINCODE_CONFIG)
+	}
+
+	#[test]
+	fn refs_encode_as_usizes_did() {
+		let values = [0, 1, 250, 251, 0xffff, 0x1_0000, u32::MAX as usize];
+		for value in values {
+			assert_eq!(
+				encoded(Ref::<Node>::from(value)).unwrap(),
+				encoded(value).unwrap(),
+				"{value}",
+			);
+		}
+
+		// as do the refs of an aerodrome, which decodes to the same
+		let mut aerodrome =
+			fixture::aerodrome(3, vec![fixture::block("B0", &[0, 300, 70_000], &[])]);
+		aerodrome.nodes[1].parent = Some(0.into());
+
+		let data = aerodrome.encode().unwrap();
+		assert_eq!(Aerodrome::decode(&data).unwrap().encode().unwrap(), data);
+		assert_eq!(
+			encoded(&aerodrome.blocks[0].nodes).unwrap(),
+			encoded(vec![0usize, 300, 70_000]).unwrap(),
+		);
+	}
+
+	#[cfg(target_pointer_width = "64")]
+	#[test]
+	fn refs_beyond_u32_are_refused() {
+		let beyond = u32::MAX as usize + 1;
+		assert!(encoded(Ref::<Node>::from(beyond)).is_err());
+
+		let aerodrome =
+			fixture::aerodrome(1, vec![fixture::block("B0", &[beyond], &[])]);
+		assert!(aerodrome.encode().is_err());
+	}
+
+	#[test]
+	fn files_cut_short_are_truncated() {
+		for data in files() {
+			load(&data).unwrap();
+
+			// within the magic, version, length, payload and checksum
+			for len in 0..data.len() {
+				let err = load(&data[..len]).unwrap_err();
+				assert!(matches!(err, LoadError::Truncated), "{len}: {err}");
+			}
+		}
+	}
+
+	#[test]
+	fn sections_cut_short_are_truncated() {
+		let payload = b"payload";
+		let mut section = Vec::new();
+		write_section(&mut section, payload).unwrap();
+
+		assert_eq!(read_section(section.as_slice()).unwrap(), payload);
+		for len in 0..section.len() {
+			let err = read_section(&section[..len]).unwrap_err();
+			assert!(matches!(err, LoadError::Truncated), "{len}: {err}");
+		}
+	}
+
+	#[test]
+	fn files_with_flipped_bits_are_corrupt() {
+		for data in files() {
+			let header = MAGIC.len() + 2;
+
+			for bit in header * 8..data.len() * 8 {
+				let mut data = data.clone();
+				data[bit / 8] ^= 1 << (bit % 8);
+
+				// a flipped length runs the section short or long of the file
+				let err = load(&data).unwrap_err();
+				assert!(
+					matches!(err, LoadError::Truncated | LoadError::Corrupt),
+					"{bit}: {err}",
+				);
+				if bit >= (header + 4) * 8 {
+					assert!(matches!(err, LoadError::Corrupt), "{bit}: {err}");
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn files_with_more_after_the_end_are_corrupt() {
+		expect_end(&[][..]).unwrap();
+
+		for mut data in files() {
+			data.push(0);
+			let err = load(&data).unwrap_err();
+			assert!(matches!(err, LoadError::Corrupt), "{err}");
+		}
+	}
+
+	/// Returns an aerodrome lighting one stop bar for two providers, as
+	/// `msfs:S1` and `xp:S1`, and another for all as `S2`.
+	fn two_providers() -> Aerodrome {
+		let mut aerodrome = fixture::aerodrome(2, Vec::new());
+		aerodrome.elements = [("msfs:S1", 0), ("xp:S1", 0), ("S2", 1)]
+			.map(|(id, node)| Element {
+				id: id.into(),
+				condition: ElementCondition::Node(Ref::from(node)),
+			})
+			.into();
+		aerodrome
+	}
+
+	#[test]
+	fn elements_are_translated_for_providers() {
+		let aerodrome = two_providers();
+		let elements = |providers: &'static [&str]| {
+			aerodrome.provider_elements(providers).collect::<Vec<_>>()
+		};
+
+		assert_eq!(elements(&["msfs"]), [(0, "S1"), (2, "S2")]);
+		assert_eq!(elements(&["xp"]), [(1, "S1"), (2, "S2")]);
+		assert_eq!(elements(&["msfs", "xp"]), [(0, "S1"), (1, "S1"), (2, "S2")]);
+		assert_eq!(elements(&["p3d"]), [(2, "S2")]);
+
+		// without providers, everything is sent by its full id
+		assert_eq!(elements(&[]), [(0, "msfs:S1"), (1, "xp:S1"), (2, "S2")]);
+
+		let element = &aerodrome.elements[1];
+		assert_eq!(element.provider(), Some("xp"));
+		assert_eq!(element.local_id(), "S1");
+		assert_eq!(aerodrome.elements[2].provider(), None);
+	}
+
+	#[test]
+	fn providers_must_agree_on_shared_elements() {
+		let mismatches = |aerodrome: &Aerodrome| {
+			(aerodrome.validate().into_iter())
+				.filter(|issue| issue.code() == "provider-condition-mismatch")
+				.map(|issue| (issue.location, issue.kind))
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(mismatches(&two_providers()), []);
+
+		let mut aerodrome = two_providers();
+		aerodrome.elements[1].condition = ElementCondition::Node(Ref::from(1));
+		assert_eq!(
+			mismatches(&aerodrome),
+			[(
+				"elements[1]".into(),
+				IssueKind::ProviderConditionMismatch { id: "S1".into() },
+			)]
+		);
+
+		// ids are only shared once their providers are removed
+		aerodrome.elements[1].id = "xp:S3".into();
+		assert_eq!(mismatches(&aerodrome), []);
+	}
+}
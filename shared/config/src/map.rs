@@ -1,18 +1,66 @@
 use super::*;
 
-#[derive(Clone, Debug, Decode, Encode)]
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use bincode::error::AllowedEnumVariants;
+
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Maps {
 	pub nodes: Vec<String>,
 	pub edges: Vec<String>,
 	pub blocks: Vec<String>,
+	pub holding_points: Vec<String>,
 
 	pub geo_map: Option<GeoMap>,
 	pub maps: Vec<Map>,
 	pub styles: Vec<Style>,
 }
 
+/// first version of maps with holding points
+pub(crate) const MAPS_HOLDING_POINTS_VERSION: u16 = 0x8003;
+
+impl Decode<FormatVersion> for Maps {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			nodes: Decode::decode(decoder)?,
+			edges: Decode::decode(decoder)?,
+			blocks: Decode::decode(decoder)?,
+			holding_points: decode_since(decoder, &[MAPS_HOLDING_POINTS_VERSION])?,
+			geo_map: Decode::decode(decoder)?,
+			maps: Decode::decode(decoder)?,
+			styles: Decode::decode(decoder)?,
+		})
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(Maps, FormatVersion);
+
+/// Implements borrowed decoding of a type generic over its points as owned
+/// decoding, as [`bincode::impl_borrow_decode_with_context`] does for others.
+macro_rules! impl_borrow_decode_projectable {
+	($ty:ident) => {
+		impl<'de, T: Projectable + Decode<FormatVersion>>
+			bincode::BorrowDecode<'de, FormatVersion> for $ty<T>
+		{
+			fn borrow_decode<
+				D: bincode::de::BorrowDecoder<'de, Context = FormatVersion>,
+			>(
+				decoder: &mut D,
+			) -> Result<Self, DecodeError> {
+				Decode::decode(decoder)
+			}
+		}
+	};
+}
+
 impl Loadable for Maps {
-	const VERSION: u16 = 0x8002;
+	const VERSION: u16 = 0x800b;
+	const OLDEST_VERSION: u16 = 0x8002;
+	const CHECKSUM_VERSION: u16 = 0x800a;
 }
 
 pub(crate) struct Rebase {
@@ -20,6 +68,7 @@ pub(crate) struct Rebase {
 	pub nodes: Vec<Option<usize>>,
 	pub edges: Vec<Option<usize>>,
 	pub blocks: Vec<Option<usize>>,
+	pub holding_points: Vec<Option<usize>>,
 }
 
 fn rebase_vec<T: Default>(
@@ -41,11 +90,25 @@ fn rebase_vec<T: Default>(
 		.collect()
 }
 
+fn levels<'a, T: Projectable + 'a>(
+	paths: impl Iterator<Item = &'a Path<T>>,
+	targets: impl Iterator<Item = &'a Target<T>>,
+) -> BTreeSet<i8> {
+	(paths.map(|path| path.level))
+		.chain(targets.map(|target| target.level))
+		.flatten()
+		.collect()
+}
+
 fn offset_paths<T: Projectable>(paths: &mut [Path<T>], offset: usize) {
-	paths.iter_mut().for_each(|path| path.style.0 += offset);
+	paths
+		.iter_mut()
+		.for_each(|path| path.style.0 = path.style.0.saturating_add(offset));
 }
 
 #[derive(Clone, Debug, Default, Decode, Encode)]
+#[bincode(decode_context = "FormatVersion")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct GeoMap {
 	pub nodes: Vec<NodeDisplay<GeoPoint>>,
 	pub edges: Vec<EdgeDisplay<GeoPoint>>,
@@ -54,11 +117,47 @@ pub struct GeoMap {
 }
 
 impl GeoMap {
+	/// Returns the levels of the paths and targets of the map.
+	pub fn levels(&self) -> BTreeSet<i8> {
+		levels(
+			(self.nodes.iter().flat_map(NodeDisplay::paths))
+				.chain(self.edges.iter().flat_map(EdgeDisplay::paths)),
+			(self.nodes.iter().map(|node| &node.target))
+				.chain(self.blocks.iter().map(|block| &block.target)),
+		)
+	}
+
+	/// Returns the bounds of the paths, targets and widget positions of the
+	/// map, ignoring the offsets of points, or none if it has none.
+	pub fn bounds(&self) -> Option<GeoBox> {
+		let points = (self.nodes.iter().flat_map(NodeDisplay::points))
+			.chain(self.edges.iter().flat_map(EdgeDisplay::points))
+			.chain(self.blocks.iter().flat_map(BlockDisplay::points))
+			.chain(self.widgets.iter().map(Widget::position));
+
+		let [min, max] = bounds(
+			points.map(|point| [point.geo.lon, point.geo.lat]),
+			GEO_EXTENT,
+		)?;
+		Some(GeoBox {
+			min: Geo {
+				lat: min[1],
+				lon: min[0],
+			},
+			max: Geo {
+				lat: max[1],
+				lon: max[0],
+			},
+		})
+	}
+
 	pub(crate) fn rebase(self, rebase: &Rebase) -> Self {
 		Self {
 			nodes: rebase_vec(self.nodes, &rebase.nodes, |d| d.offset(rebase.offset)),
 			edges: rebase_vec(self.edges, &rebase.edges, |d| d.offset(rebase.offset)),
-			blocks: rebase_vec(self.blocks, &rebase.blocks, |_| ()),
+			blocks: rebase_vec(self.blocks, &rebase.blocks, |d| {
+				d.offset(rebase.offset)
+			}),
 			widgets: self
 				.widgets
 				.into_iter()
@@ -69,6 +168,8 @@ impl GeoMap {
 }
 
 #[derive(Clone, Debug, Default, Decode, Encode)]
+#[bincode(decode_context = "FormatVersion")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Map {
 	pub background: Color,
 	pub base: Vec<Path<Point>>,
@@ -82,12 +183,78 @@ pub struct Map {
 }
 
 impl Map {
+	/// Returns the levels of the paths and targets of the map.
+	pub fn levels(&self) -> BTreeSet<i8> {
+		levels(
+			(self.base.iter())
+				.chain(self.nodes.iter().flat_map(NodeDisplay::paths))
+				.chain(self.edges.iter().flat_map(EdgeDisplay::paths)),
+			(self.nodes.iter().map(|node| &node.target))
+				.chain(self.blocks.iter().map(|block| &block.target)),
+		)
+	}
+
+	/// Returns the bounds of the paths, targets and widget positions of the
+	/// map, or none if it has none.
+	pub fn bounds(&self) -> Option<Box> {
+		let points = (self.base.iter().flat_map(|path| &path.points))
+			.chain(self.nodes.iter().flat_map(NodeDisplay::points))
+			.chain(self.edges.iter().flat_map(EdgeDisplay::points))
+			.chain(self.blocks.iter().flat_map(BlockDisplay::points))
+			.chain(self.widgets.iter().map(Widget::position));
+
+		let [min, max] =
+			bounds(points.map(|point| [point.x, point.y]), POINT_EXTENT)?;
+		Some(Box {
+			min: Point {
+				x: min[0],
+				y: min[1],
+			},
+			max: Point {
+				x: max[0],
+				y: max[1],
+			},
+		})
+	}
+
+	/// Returns the views of the map, or if it has none, a view named `All` of
+	/// its bounds with a margin, if it has any.
+	pub fn views_or_default(&self) -> Cow<'_, [View]> {
+		if !self.views.is_empty() {
+			return Cow::Borrowed(&self.views)
+		}
+
+		let Some(Box { min, max }) = self.bounds() else {
+			return Cow::Borrowed(&[])
+		};
+
+		let margin = Point {
+			x: (max.x - min.x) * VIEW_MARGIN,
+			y: (max.y - min.y) * VIEW_MARGIN,
+		};
+		Cow::Owned(vec![View {
+			name: "All".into(),
+			bounds: Box {
+				min: Point {
+					x: min.x - margin.x,
+					y: min.y - margin.y,
+				},
+				max: Point {
+					x: max.x + margin.x,
+					y: max.y + margin.y,
+				},
+			},
+		}])
+	}
+
 	pub(crate) fn rebase(mut self, rebase: &Rebase) -> Self {
 		offset_paths(&mut self.base, rebase.offset);
 		Self {
 			nodes: rebase_vec(self.nodes, &rebase.nodes, |d| d.offset(rebase.offset)),
 			edges: rebase_vec(self.edges, &rebase.edges, |d| d.offset(rebase.offset)),
-			blocks: rebase_vec(self.blocks, &rebase.blocks, |_| ()),
+			blocks: rebase_vec(self.blocks, &rebase.blocks, |d| {
+				d.offset(rebase.offset)
+			}),
 			widgets: self
 				.widgets
 				.into_iter()
@@ -99,83 +266,413 @@ impl Map {
 }
 
 #[derive(Clone, Debug, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct View {
 	pub name: String,
 	pub bounds: Box,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Box {
 	pub min: Point,
 	pub max: Point,
 }
 
-#[derive(Clone, Debug, Decode, Encode)]
+/// A box of latitude and longitude, as [`Box`] is of points.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GeoBox {
+	pub min: Geo,
+	pub max: Geo,
+}
+
+/// smallest width and height of the bounds of a map, in map units
+const POINT_EXTENT: f32 = 1.0;
+/// smallest width and height of the bounds of a geo map, in degrees
+const GEO_EXTENT: f32 = 0.001;
+/// part of the size of the bounds of a map added on each side of its default
+/// view
+const VIEW_MARGIN: f32 = 0.05;
+
+/// Returns the least and greatest of each coordinate of the finite points, or
+/// none if there are none, grown about their centre to at least the extent
+/// given so that no bounds are empty.
+fn bounds(
+	points: impl Iterator<Item = [f32; 2]>,
+	extent: f32,
+) -> Option<[[f32; 2]; 2]> {
+	let mut min = [f32::INFINITY; 2];
+	let mut max = [f32::NEG_INFINITY; 2];
+	for point in points.filter(|point| point.iter().all(|c| c.is_finite())) {
+		min = [min[0].min(point[0]), min[1].min(point[1])];
+		max = [max[0].max(point[0]), max[1].max(point[1])];
+	}
+
+	if min[0] > max[0] {
+		return None
+	}
+
+	for i in 0..2 {
+		let grow = (extent - (max[i] - min[i])) / 2.0;
+		if grow > 0.0 {
+			min[i] -= grow;
+			max[i] += grow;
+		}
+	}
+
+	Some([min, max])
+}
+
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Path<T: Projectable> {
 	pub points: Vec<T>,
 	pub style: Ref<Style>,
+	/// level of the aerodrome the path is on, as for a bridge, or all if none
+	pub level: Option<i8>,
 }
 
-#[derive(Clone, Debug, Default, Decode, Encode)]
+/// first versions of configs and maps in which paths and targets have levels
+pub(crate) const LEVELS_VERSIONS: [u16; 2] = [0x000a, 0x8008];
+
+impl<T: Projectable + Decode<FormatVersion>> Decode<FormatVersion> for Path<T> {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			points: Decode::decode(decoder)?,
+			style: Decode::decode(decoder)?,
+			level: decode_since(decoder, &LEVELS_VERSIONS)?,
+		})
+	}
+}
+
+impl_borrow_decode_projectable!(Path);
+
+impl<T: Projectable> Path<T> {
+	/// Returns whether the path is drawn with the level selected, where no
+	/// selected level draws every level.
+	pub fn on_level(&self, level: Option<i8>) -> bool {
+		on_level(self.level, level)
+	}
+}
+
+fn on_level(level: Option<i8>, selected: Option<i8>) -> bool {
+	level.is_none() || selected.is_none() || level == selected
+}
+
+#[derive(Clone, Debug, Default, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Target<T: Projectable> {
 	pub polygons: Vec<Vec<T>>,
+	/// style of the polygons while the pointer is over them
+	pub hover_style: Option<Ref<Style>>,
+	/// style of the polygons while they are selected
+	pub active_style: Option<Ref<Style>>,
+	/// level of the aerodrome the target is on, or all if none
+	pub level: Option<i8>,
 }
 
-#[derive(Clone, Debug, Default, Decode, Encode)]
+/// first versions of configs and maps in which targets have their own styles
+pub(crate) const TARGET_STYLES_VERSIONS: [u16; 2] = [0x0009, 0x8007];
+
+impl<T: Projectable + Decode<FormatVersion>> Decode<FormatVersion>
+	for Target<T>
+{
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			polygons: Decode::decode(decoder)?,
+			hover_style: decode_since(decoder, &TARGET_STYLES_VERSIONS)?,
+			active_style: decode_since(decoder, &TARGET_STYLES_VERSIONS)?,
+			level: decode_since(decoder, &LEVELS_VERSIONS)?,
+		})
+	}
+}
+
+impl_borrow_decode_projectable!(Target);
+
+impl<T: Projectable> Target<T> {
+	fn offset(&mut self, offset: usize) {
+		let styles = [&mut self.hover_style, &mut self.active_style];
+		for style in styles.into_iter().flatten() {
+			style.0 = style.0.saturating_add(offset);
+		}
+	}
+
+	/// Returns whether the target can be hit with the level selected, as for
+	/// [`Path::on_level`].
+	pub fn on_level(&self, level: Option<i8>) -> bool {
+		on_level(self.level, level)
+	}
+
+	/// Returns the styles of the target which are set.
+	pub fn styles(&self) -> impl Iterator<Item = Ref<Style>> {
+		self.hover_style.into_iter().chain(self.active_style)
+	}
+}
+
+#[derive(Clone, Debug, Default, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NodeDisplay<T: Projectable> {
 	pub off: Vec<Path<T>>,
 	pub on: Vec<Path<T>>,
 	pub selected: Vec<Path<T>>,
 
 	pub target: Target<T>,
+	/// text shown when the target is hovered, with placeholders in braces
+	pub tooltip: Option<String>,
+}
+
+/// first versions of configs and maps in which targets have tooltips
+pub(crate) const TOOLTIPS_VERSIONS: [u16; 2] = [0x0008, 0x8006];
+
+impl<T: Projectable + Decode<FormatVersion>> Decode<FormatVersion>
+	for NodeDisplay<T>
+{
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			off: Decode::decode(decoder)?,
+			on: Decode::decode(decoder)?,
+			selected: Decode::decode(decoder)?,
+			target: Decode::decode(decoder)?,
+			tooltip: decode_since(decoder, &TOOLTIPS_VERSIONS)?,
+		})
+	}
 }
 
+impl_borrow_decode_projectable!(NodeDisplay);
+
 impl<T: Projectable> NodeDisplay<T> {
 	fn offset(&mut self, offset: usize) {
 		offset_paths(&mut self.off, offset);
 		offset_paths(&mut self.on, offset);
 		offset_paths(&mut self.selected, offset);
+		self.target.offset(offset);
+	}
+
+	pub fn paths(&self) -> impl Iterator<Item = &Path<T>> {
+		self.off.iter().chain(&self.on).chain(&self.selected)
+	}
+
+	/// Returns the points of the paths and target.
+	pub fn points(&self) -> impl Iterator<Item = &T> {
+		(self.paths().flat_map(|path| &path.points))
+			.chain(self.target.polygons.iter().flatten())
+	}
+
+	/// Returns the paths drawn with the level selected.
+	pub fn paths_on_level(
+		&self,
+		level: Option<i8>,
+	) -> impl Iterator<Item = &Path<T>> {
+		self.paths().filter(move |path| path.on_level(level))
 	}
 }
 
-#[derive(Clone, Debug, Default, Decode, Encode)]
+#[derive(Clone, Debug, Default, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct EdgeDisplay<T: Projectable> {
 	pub off: Vec<Path<T>>,
 	pub on: Vec<Path<T>>,
 	pub pending: Vec<Path<T>>,
 }
 
+impl<T: Projectable + Decode<FormatVersion>> Decode<FormatVersion>
+	for EdgeDisplay<T>
+{
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			off: Decode::decode(decoder)?,
+			on: Decode::decode(decoder)?,
+			pending: Decode::decode(decoder)?,
+		})
+	}
+}
+
+impl_borrow_decode_projectable!(EdgeDisplay);
+
 impl<T: Projectable> EdgeDisplay<T> {
 	fn offset(&mut self, offset: usize) {
 		offset_paths(&mut self.off, offset);
 		offset_paths(&mut self.on, offset);
 		offset_paths(&mut self.pending, offset);
 	}
+
+	pub fn paths(&self) -> impl Iterator<Item = &Path<T>> {
+		self.off.iter().chain(&self.on).chain(&self.pending)
+	}
+
+	/// Returns the points of the paths.
+	pub fn points(&self) -> impl Iterator<Item = &T> {
+		self.paths().flat_map(|path| &path.points)
+	}
+
+	/// Returns the paths drawn with the level selected.
+	pub fn paths_on_level(
+		&self,
+		level: Option<i8>,
+	) -> impl Iterator<Item = &Path<T>> {
+		self.paths().filter(move |path| path.on_level(level))
+	}
 }
 
-#[derive(Clone, Debug, Default, Decode, Encode)]
+#[derive(Clone, Debug, Default, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BlockDisplay<T: Projectable> {
 	pub target: Target<T>,
+	/// text shown when the target is hovered, with placeholders in braces
+	pub tooltip: Option<String>,
 }
 
-#[derive(Clone, Debug, Decode, Encode)]
+impl<T: Projectable + Decode<FormatVersion>> Decode<FormatVersion>
+	for BlockDisplay<T>
+{
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			target: Decode::decode(decoder)?,
+			tooltip: decode_since(decoder, &TOOLTIPS_VERSIONS)?,
+		})
+	}
+}
+
+impl_borrow_decode_projectable!(BlockDisplay);
+
+impl<T: Projectable> BlockDisplay<T> {
+	fn offset(&mut self, offset: usize) {
+		self.target.offset(offset);
+	}
+
+	/// Returns the points of the target.
+	pub fn points(&self) -> impl Iterator<Item = &T> {
+		self.target.polygons.iter().flatten()
+	}
+}
+
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Widget<T: Projectable> {
 	Countdown {
 		position: T,
 		size: f32,
 		condition: CountdownCondition,
+		format: CountdownFormat,
+		/// remaining time below which the warning style applies
+		warn_below_secs: Option<u16>,
+		normal_style: Option<Ref<Style>>,
+		/// style when below the threshold, or the normal style if none
+		warn_style: Option<Ref<Style>>,
 	},
+	/// the label of a holding point
+	Label {
+		position: T,
+		size: f32,
+		holding_point: Ref<HoldingPoint>,
+	},
+	/// fixed text, with placeholders in braces filled as in the tooltip of the
+	/// entity of the condition, if there is one
+	Text {
+		position: T,
+		size: f32,
+		anchor: TextAnchor,
+		text: String,
+		color: Color,
+		/// shown only while the node is on, or the block is routed
+		condition: Option<CountdownCondition>,
+	},
+}
+
+/// first versions of configs and maps in which countdowns have formats and
+/// styles
+pub(crate) const COUNTDOWN_STYLES_VERSIONS: [u16; 2] = [0x0007, 0x8005];
+
+impl<T: Projectable + Decode<FormatVersion>> Decode<FormatVersion>
+	for Widget<T>
+{
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		let versions = &COUNTDOWN_STYLES_VERSIONS;
+		match u32::decode(decoder)? {
+			0 => Ok(Self::Countdown {
+				position: Decode::decode(decoder)?,
+				size: Decode::decode(decoder)?,
+				condition: Decode::decode(decoder)?,
+				format: decode_since(decoder, versions)?,
+				warn_below_secs: decode_since(decoder, versions)?,
+				normal_style: decode_since(decoder, versions)?,
+				warn_style: decode_since(decoder, versions)?,
+			}),
+			1 => Ok(Self::Label {
+				position: Decode::decode(decoder)?,
+				size: Decode::decode(decoder)?,
+				holding_point: Decode::decode(decoder)?,
+			}),
+			2 => Ok(Self::Text {
+				position: Decode::decode(decoder)?,
+				size: Decode::decode(decoder)?,
+				anchor: Decode::decode(decoder)?,
+				text: Decode::decode(decoder)?,
+				color: Decode::decode(decoder)?,
+				condition: Decode::decode(decoder)?,
+			}),
+			found => Err(DecodeError::UnexpectedVariant {
+				type_name: std::any::type_name::<Self>(),
+				allowed: &AllowedEnumVariants::Range { min: 0, max: 2 },
+				found,
+			}),
+		}
+	}
 }
 
+impl_borrow_decode_projectable!(Widget);
+
 impl<T: Projectable> Widget<T> {
+	pub fn position(&self) -> &T {
+		match self {
+			Self::Countdown { position, .. }
+			| Self::Label { position, .. }
+			| Self::Text { position, .. } => position,
+		}
+	}
+
 	fn rebase(mut self, rebase: &Rebase) -> Option<Self> {
-		let Self::Countdown { condition, .. } = &mut self;
-		match condition {
-			CountdownCondition::Node(i) => {
-				*i = rebase.nodes.iter().position(|j| *j == Some(i.0))?.into();
+		fn position<T>(rebase: &[Option<usize>], i: &mut Ref<T>) -> Option<()> {
+			*i = rebase.iter().position(|j| *j == Some(i.0))?.into();
+			Some(())
+		}
+
+		match &mut self {
+			Self::Countdown {
+				condition,
+				normal_style,
+				warn_style,
+				..
+			} => {
+				match condition {
+					CountdownCondition::Node(i) => position(&rebase.nodes, i)?,
+					CountdownCondition::Block(i) => position(&rebase.blocks, i)?,
+				}
+
+				for style in [normal_style, warn_style].into_iter().flatten() {
+					style.0 = style.0.saturating_add(rebase.offset);
+				}
+			},
+			Self::Label { holding_point, .. } => {
+				position(&rebase.holding_points, holding_point)?
 			},
-			CountdownCondition::Block(i) => {
-				*i = rebase.blocks.iter().position(|j| *j == Some(i.0))?.into();
+			Self::Text { condition, .. } => match condition {
+				Some(CountdownCondition::Node(i)) => position(&rebase.nodes, i)?,
+				Some(CountdownCondition::Block(i)) => position(&rebase.blocks, i)?,
+				None => (),
 			},
 		}
 
@@ -186,16 +683,47 @@ impl<T: Projectable> Widget<T> {
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CountdownCondition {
 	Node(Ref<Node>),
 	Block(Ref<Block>),
 }
 
+/// How the remaining time of a countdown is shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CountdownFormat {
+	/// minutes and seconds, as `01:30`
+	#[default]
+	MinutesSeconds,
+	/// whole seconds, as `90`
+	Seconds,
+	/// a bar shrinking with the remaining part of the reset time
+	Bar,
+}
+
+/// The point of a text widget at its position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TextAnchor {
+	#[default]
+	Centre,
+	Left,
+	Right,
+	Top,
+	Bottom,
+	TopLeft,
+	TopRight,
+	BottomLeft,
+	BottomRight,
+}
+
 pub trait Projectable: Clone + Debug {}
 
 #[derive(
 	Clone, Copy, Debug, Default, PartialEq, PartialOrd, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Point {
 	pub x: f32,
 	pub y: f32,
@@ -206,6 +734,7 @@ impl Projectable for Point {}
 #[derive(
 	Clone, Copy, Debug, Default, PartialEq, PartialOrd, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Geo {
 	pub lat: f32,
 	pub lon: f32,
@@ -213,9 +742,83 @@ pub struct Geo {
 
 impl Projectable for Geo {}
 
+/// Parses a latitude or longitude, with the hemisphere letters given, in
+/// decimal degrees or as degrees, minutes and seconds in the `N051.28.39.000`
+/// form of sector files, with the fraction of the seconds optional.
+#[cfg(any(feature = "ese", feature = "topsky"))]
+pub(crate) fn parse_angle(
+	text: &str,
+	positive: char,
+	negative: char,
+) -> Result<f32, String> {
+	let max = if positive == 'N' { 90.0 } else { 180.0 };
+	let angle = parse_signed_angle(text, positive, negative)?;
+
+	// which also refuses infinities and NaN, as decimals may be either
+	if !(-max..=max).contains(&angle) {
+		return Err(format!("{text} beyond {max} degrees"))
+	}
+
+	Ok(angle as f32)
+}
+
+/// Parses an angle for [`parse_angle`], whatever its magnitude.
+#[cfg(any(feature = "ese", feature = "topsky"))]
+fn parse_signed_angle(
+	text: &str,
+	positive: char,
+	negative: char,
+) -> Result<f64, String> {
+	let Some(hemisphere) = text.chars().next().filter(char::is_ascii_alphabetic)
+	else {
+		return text
+			.parse()
+			.map_err(|_| format!("invalid coordinate {text}"))
+	};
+
+	let sign = match hemisphere.to_ascii_uppercase() {
+		c if c == positive => 1.0,
+		c if c == negative => -1.0,
+		c => return Err(format!("expected {positive} or {negative}, not {c}")),
+	};
+
+	// seconds may be fractional, so are the rest after the minutes
+	let malformed = || format!("expected {positive}DDD.MM.SS[.SSS], not {text}");
+	let [degrees, minutes, seconds] =
+		text[1..].splitn(3, '.').collect::<Vec<_>>()[..]
+	else {
+		return Err(malformed())
+	};
+
+	let digits =
+		|part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+	let fractional = match seconds.split_once('.') {
+		Some((whole, fraction)) => digits(whole) && digits(fraction),
+		None => digits(seconds),
+	};
+	if !digits(degrees) || !digits(minutes) || !fractional {
+		return Err(malformed())
+	}
+
+	let parse = |part: &str| part.parse::<f64>().map_err(|err| err.to_string());
+	let (degrees, minutes, seconds) =
+		(parse(degrees)?, parse(minutes)?, parse(seconds)?);
+
+	if minutes >= 60.0 {
+		return Err(format!("minutes out of range in {text}"))
+	}
+
+	if seconds >= 60.0 {
+		return Err(format!("seconds out of range in {text}"))
+	}
+
+	Ok(sign * (degrees + minutes / 60.0 + seconds / 3600.0))
+}
+
 #[derive(
 	Clone, Copy, Debug, Default, PartialEq, PartialOrd, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct GeoPoint {
 	pub geo: Geo,
 	pub offset: Point,
@@ -223,9 +826,8 @@ pub struct GeoPoint {
 
 impl Projectable for GeoPoint {}
 
-#[derive(
-	Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
-)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Style {
 	pub stroke_style: StrokeStyle,
 	pub stroke_width: StrokeWidth,
@@ -235,11 +837,75 @@ pub struct Style {
 
 	pub fill_style: FillStyle,
 	pub fill_color: Color,
+
+	pub blink: Option<Blink>,
+}
+
+/// first versions of configs and maps in which styles blink
+pub(crate) const BLINK_VERSIONS: [u16; 2] = [0x0006, 0x8004];
+
+impl Decode<FormatVersion> for Style {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		Ok(Self {
+			stroke_style: Decode::decode(decoder)?,
+			stroke_width: Decode::decode(decoder)?,
+			stroke_cap: Decode::decode(decoder)?,
+			stroke_join: Decode::decode(decoder)?,
+			stroke_color: Decode::decode(decoder)?,
+			fill_style: Decode::decode(decoder)?,
+			fill_color: Decode::decode(decoder)?,
+			blink: decode_since(decoder, &BLINK_VERSIONS)?,
+		})
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(Style, FormatVersion);
+
+impl Style {
+	/// Returns whether paths in the style are drawn at the time, in
+	/// milliseconds since the epoch, which is always so unless it blinks.
+	pub fn visible_at(&self, t_millis: u64) -> bool {
+		self.blink.is_none_or(|blink| blink.visible_at(t_millis))
+	}
 }
 
+/// A repeating cycle of being drawn then hidden.
+///
+/// Cycles are aligned to the epoch, so that clients sharing a clock, as with
+/// the time from the server, blink in step.
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Blink {
+	pub period_ms: u16,
+	/// percentage of each period drawn, from its start
+	pub duty_percent: u8,
+	/// offset of the start of each period from the epoch
+	pub phase_ms: u16,
+}
+
+impl Blink {
+	/// Returns whether the style is drawn at the time, in milliseconds since
+	/// the epoch. A zero period never blinks.
+	pub fn visible_at(&self, t_millis: u64) -> bool {
+		let period = self.period_ms as u64;
+		if period == 0 {
+			return true
+		}
+
+		let elapsed =
+			(t_millis % period + period - self.phase_ms as u64 % period) % period;
+		elapsed * 100 < period * self.duty_percent as u64
+	}
+}
+
+#[derive(
+	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Color {
 	pub r: u8,
 	pub g: u8,
@@ -261,43 +927,377 @@ impl Default for Color {
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum StrokeStyle {
 	None,
 	Dash(i32),
 }
 
-#[derive(
-	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
-)]
-pub struct StrokeWidth(u8);
+/// A width in sixteenths of a pixel.
+///
+/// Widths convert exactly to and from `f32` when they are multiples of a
+/// sixteenth. Others are rounded to the nearest sixteenth, and clamped to
+/// between zero and [`StrokeWidth::MAX`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StrokeWidth(pub(crate) u16);
+
+impl StrokeWidth {
+	pub const MAX: Self = Self(u16::MAX);
+}
+
+/// first versions of configs and maps with widths in sixteenths, those before
+/// having them in eighths, as a `u8`
+pub(crate) const SIXTEENTHS_VERSIONS: [u16; 2] = [0x000d, 0x8009];
+
+impl Decode<FormatVersion> for StrokeWidth {
+	fn decode<D: bincode::de::Decoder<Context = FormatVersion>>(
+		decoder: &mut D,
+	) -> Result<Self, DecodeError> {
+		if decoder.context().since(&SIXTEENTHS_VERSIONS) {
+			Ok(Self(u16::decode(decoder)?))
+		} else {
+			Ok(Self(u16::from(u8::decode(decoder)?) * 2))
+		}
+	}
+}
+
+bincode::impl_borrow_decode_with_context!(StrokeWidth, FormatVersion);
 
 impl From<StrokeWidth> for f32 {
 	fn from(from: StrokeWidth) -> Self {
-		from.0 as f32 / 8.0
+		from.0 as f32 / 16.0
 	}
 }
 
 impl From<f32> for StrokeWidth {
 	fn from(from: f32) -> Self {
-		Self((8.0 * from).clamp(0.0, 255.0).round() as u8)
+		Self((16.0 * from).clamp(0.0, u16::MAX as f32).round() as u16)
 	}
 }
 
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StrokeCap(pub i32);
 
+// numbered in the order of the end caps of GDI pens
+impl StrokeCap {
+	pub const ROUND: Self = Self(0);
+	pub const SQUARE: Self = Self(1);
+	pub const BUTT: Self = Self(2);
+}
+
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StrokeJoin(pub i32);
 
+// numbered in the order of the joins of GDI pens
+impl StrokeJoin {
+	pub const ROUND: Self = Self(0);
+	pub const BEVEL: Self = Self(1);
+	pub const MITER: Self = Self(2);
+}
+
 #[derive(
 	Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Decode, Encode,
 )]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum FillStyle {
 	None,
 	Fill,
 	Hatch(i32),
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(any(feature = "ese", feature = "topsky"))]
+	#[test]
+	fn angles_convert_within_range() {
+		let table: &[(&str, char, Result<f32, ()>)] = &[
+			("0", 'N', Ok(0.0)),
+			("N000.00.00.000", 'N', Ok(0.0)),
+			("51.5", 'N', Ok(51.5)),
+			("-0.25", 'E', Ok(-0.25)),
+			("N051.30.00.000", 'N', Ok(51.5)),
+			("S051.30.00", 'N', Ok(-51.5)),
+			("n051.30.00", 'N', Ok(51.5)),
+			("W000.15.00.000", 'E', Ok(-0.25)),
+			("E000.00.36.000", 'E', Ok(0.01)),
+			// the bounds themselves
+			("90", 'N', Ok(90.0)),
+			("-90.0", 'N', Ok(-90.0)),
+			("N090.00.00.000", 'N', Ok(90.0)),
+			("S090.00.00.000", 'N', Ok(-90.0)),
+			("180", 'E', Ok(180.0)),
+			("W180.00.00.000", 'E', Ok(-180.0)),
+			// and beyond them, in either form
+			("90.001", 'N', Err(())),
+			("-91", 'N', Err(())),
+			("N091.00.00.000", 'N', Err(())),
+			("N090.00.00.001", 'N', Err(())),
+			("180.5", 'E', Err(())),
+			("E180.00.01.000", 'E', Err(())),
+			("inf", 'N', Err(())),
+			("-inf", 'E', Err(())),
+			("NaN", 'E', Err(())),
+			// the hemisphere of the other axis
+			("E051.30.00.000", 'N', Err(())),
+			("N000.15.00.000", 'E', Err(())),
+			// minutes and seconds of sixty
+			("N051.60.00.000", 'N', Err(())),
+			("N051.00.60.000", 'N', Err(())),
+			(
+				"N051.59.59.999",
+				'N',
+				Ok(51.0 + 59.0 / 60.0 + 59.999 / 3600.0),
+			),
+			// malformed
+			("N051.30", 'N', Err(())),
+			("N051.3x.00", 'N', Err(())),
+			("N051..00", 'N', Err(())),
+			("", 'N', Err(())),
+		];
+
+		for (text, positive, expected) in table {
+			let negative = if *positive == 'N' { 'S' } else { 'W' };
+			let angle = parse_angle(text, *positive, negative).map_err(drop);
+			match (angle, expected) {
+				(Ok(angle), Ok(expected)) => {
+					assert!((angle - expected).abs() < 1e-6, "{text}: {angle}");
+				},
+				(angle, expected) => assert_eq!(angle, *expected, "{text}"),
+			}
+		}
+	}
+
+	fn decode_width(bytes: &[u8], version: u16) -> StrokeWidth {
+		let (width, read) = bincode::decode_from_slice_with_context(
+			bytes,
+			BINCODE_CONFIG,
+			FormatVersion(version),
+		)
+		.unwrap();
+		assert_eq!(read, bytes.len());
+		width
+	}
+
+	#[test]
+	fn widths_in_eighths_before_sixteenths() {
+		for version in [0x0002, 0x0007, 0x000c, 0x8002, 0x8008] {
+			// a single byte of three eighths
+			assert_eq!(f32::from(decode_width(&[3], version)), 0.375);
+			assert_eq!(f32::from(decode_width(&[u8::MAX], version)), 31.875);
+		}
+	}
+
+	#[test]
+	fn widths_in_sixteenths_since() {
+		for version in [0x000d, Config::VERSION, 0x8009, Maps::VERSION] {
+			assert_eq!(f32::from(decode_width(&[3], version)), 0.1875);
+
+			let encoded = bincode::encode_to_vec(StrokeWidth::MAX, BINCODE_CONFIG);
+			assert_eq!(decode_width(&encoded.unwrap(), version), StrokeWidth::MAX);
+		}
+	}
+
+	#[test]
+	fn widths_round_trip() {
+		for sixteenths in [0, 1, 15, 16, 17, 255, 256, 4096, u16::MAX] {
+			let width = f32::from(sixteenths) / 16.0;
+			let stroke = StrokeWidth::from(width);
+			assert_eq!(f32::from(stroke), width);
+
+			let encoded = bincode::encode_to_vec(stroke, BINCODE_CONFIG).unwrap();
+			assert_eq!(decode_width(&encoded, Config::VERSION), stroke);
+		}
+
+		// others round to the nearest sixteenth
+		assert_eq!(f32::from(StrokeWidth::from(0.04)), 0.0625);
+		assert_eq!(f32::from(StrokeWidth::from(1.01)), 1.0);
+	}
+
+	#[test]
+	fn widths_clamped() {
+		let max = f32::from(StrokeWidth::MAX);
+		assert_eq!(max, 4095.9375);
+
+		for width in [max + 1.0, 1e9, f32::INFINITY] {
+			assert_eq!(StrokeWidth::from(width), StrokeWidth::MAX);
+		}
+		for width in [-1.0, -1e9, f32::NEG_INFINITY] {
+			assert_eq!(f32::from(StrokeWidth::from(width)), 0.0);
+		}
+	}
+
+	fn blink(period_ms: u16, duty_percent: u8, phase_ms: u16) -> Blink {
+		Blink {
+			period_ms,
+			duty_percent,
+			phase_ms,
+		}
+	}
+
+	#[test]
+	fn blinks_follow_the_phase() {
+		let table: &[(Blink, &[(u64, bool)])] = &[
+			// drawn for the first half of each second
+			(
+				blink(1000, 50, 0),
+				&[
+					(0, true),
+					(499, true),
+					(500, false),
+					(999, false),
+					(1000, true),
+				],
+			),
+			// from a quarter past
+			(
+				blink(1000, 50, 250),
+				&[
+					(0, false),
+					(249, false),
+					(250, true),
+					(749, true),
+					(750, false),
+				],
+			),
+			// phases of whole periods or more wrap
+			(
+				blink(1000, 50, 1250),
+				&[(0, false), (250, true), (750, false)],
+			),
+			(blink(500, 20, 500), &[(0, true), (99, true), (100, false)]),
+			// times long after the epoch
+			(
+				blink(700, 50, 100),
+				&[
+					(7_000_000_100, true),
+					(7_000_000_449, true),
+					(7_000_000_450, false),
+				],
+			),
+			(
+				blink(u16::MAX, 100, u16::MAX),
+				&[(0, true), (u64::MAX, true)],
+			),
+			// duties of none and all
+			(blink(1000, 0, 0), &[(0, false), (1, false), (999, false)]),
+			(
+				blink(1000, 100, 300),
+				&[(0, true), (299, true), (300, true)],
+			),
+			// and a period of zero never blinks
+			(blink(0, 0, 0), &[(0, true), (123_456, true)]),
+			(blink(0, 50, 250), &[(0, true), (u64::MAX, true)]),
+		];
+
+		for (blink, times) in table {
+			for (t, visible) in *times {
+				assert_eq!(blink.visible_at(*t), *visible, "{blink:?} at {t}");
+			}
+		}
+	}
+
+	fn style(blink: Option<Blink>) -> Style {
+		Style {
+			stroke_style: StrokeStyle::Dash(0),
+			stroke_width: StrokeWidth(0),
+			stroke_cap: StrokeCap::ROUND,
+			stroke_join: StrokeJoin::ROUND,
+			stroke_color: Color {
+				r: 255,
+				g: 0,
+				b: 0,
+				a: 255,
+			},
+			fill_style: FillStyle::None,
+			fill_color: Color {
+				r: 0,
+				g: 0,
+				b: 0,
+				a: 0,
+			},
+			blink,
+		}
+	}
+
+	#[test]
+	fn styles_only_blink_when_given() {
+		let steady = style(None);
+		assert!((0..2000).all(|t| steady.visible_at(t)));
+
+		let blinking = style(Some(blink(1000, 50, 0)));
+		assert!(blinking.visible_at(250));
+		assert!(!blinking.visible_at(750));
+	}
+
+	fn decode_style(bytes: &[u8], version: u16) -> Style {
+		let (style, read) = bincode::decode_from_slice_with_context(
+			bytes,
+			BINCODE_CONFIG,
+			FormatVersion(version),
+		)
+		.unwrap();
+		assert_eq!(read, bytes.len());
+		style
+	}
+
+	#[test]
+	fn styles_from_before_blinking_decode() {
+		let steady = style(None);
+		let encoded = bincode::encode_to_vec(&steady, BINCODE_CONFIG).unwrap();
+
+		// older styles end before the blink, whose absence is the last byte
+		let (older, [0]) = encoded.split_at(encoded.len() - 1) else {
+			panic!("{encoded:?} does not end with no blink");
+		};
+		for version in [0x0002, 0x0005, 0x8002, 0x8003] {
+			assert_eq!(decode_style(older, version), steady);
+		}
+		for version in [0x0006, Config::VERSION, 0x8004, Maps::VERSION] {
+			assert_eq!(decode_style(&encoded, version), steady);
+		}
+
+		let blinking = style(Some(blink(500, 50, 250)));
+		let encoded = bincode::encode_to_vec(&blinking, BINCODE_CONFIG).unwrap();
+		for version in [Config::VERSION, Maps::VERSION] {
+			assert_eq!(decode_style(&encoded, version), blinking);
+		}
+	}
+
+	#[test]
+	fn paths_from_before_levels_decode() {
+		let path = Path {
+			points: vec![Point { x: 1.0, y: 2.0 }, Point { x: 3.0, y: 4.0 }],
+			style: Ref::from(3),
+			level: None,
+		};
+		let encoded = bincode::encode_to_vec(&path, BINCODE_CONFIG).unwrap();
+
+		// older paths end before the level, whose absence is the last byte
+		let (older, [0]) = encoded.split_at(encoded.len() - 1) else {
+			panic!("{encoded:?} does not end with no level");
+		};
+		for (bytes, version) in
+			[(older, 0x0009), (older, 0x8007), (&encoded, 0x000a)]
+		{
+			let (decoded, read): (Path<Point>, _) =
+				bincode::decode_from_slice_with_context(
+					bytes,
+					BINCODE_CONFIG,
+					FormatVersion(version),
+				)
+				.unwrap();
+			assert_eq!(read, bytes.len());
+			assert_eq!(decoded.points, path.points);
+			assert_eq!(decoded.level, None);
+		}
+	}
+}
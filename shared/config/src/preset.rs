@@ -0,0 +1,262 @@
+use super::*;
+
+use std::collections::BTreeMap;
+
+/// The entries of a preset with its includes applied.
+#[derive(Clone, Debug, Default)]
+pub struct ResolvedPreset {
+	pub nodes: Vec<(Ref<Node>, NodeState)>,
+	pub blocks: Vec<(Ref<Block>, BlockState)>,
+}
+
+impl Profile {
+	/// Flattens a preset and its includes into one set of entries.
+	///
+	/// Includes are applied depth-first in order, each overriding entries for
+	/// the same node or block from those before it, and the preset's own entries
+	/// override all of them. A preset included more than once, as when two
+	/// includes share a base, is applied only where it is first reached, so that
+	/// it does not undo the includes between. Includes which are out of range or
+	/// would form a cycle are skipped.
+	pub fn resolve_preset(&self, i: usize) -> ResolvedPreset {
+		let mut nodes = BTreeMap::new();
+		let mut blocks = BTreeMap::new();
+		let mut applied = vec![false; self.presets.len()];
+		self.apply_preset(i, &mut applied, &mut nodes, &mut blocks);
+
+		ResolvedPreset {
+			nodes: nodes.into_iter().collect(),
+			blocks: blocks.into_iter().collect(),
+		}
+	}
+
+	/// Flattens a preset which need not be one of the profile, such as one
+	/// captured from the current state, into one set of entries, applying its
+	/// includes from the profile as for [`Profile::resolve_preset`].
+	pub fn resolve(&self, preset: &Preset) -> ResolvedPreset {
+		let mut nodes = BTreeMap::new();
+		let mut blocks = BTreeMap::new();
+		let mut applied = vec![false; self.presets.len()];
+
+		for include in &preset.includes {
+			self.apply_preset(include.0, &mut applied, &mut nodes, &mut blocks);
+		}
+
+		nodes.extend(preset.nodes.iter().copied());
+		blocks.extend(preset.blocks.iter().copied());
+
+		ResolvedPreset {
+			nodes: nodes.into_iter().collect(),
+			blocks: blocks.into_iter().collect(),
+		}
+	}
+
+	/// Applies a preset and its includes, depth-first, through a stack of the
+	/// presets being applied and how many of the includes of each have been,
+	/// as a chain of includes may be as long as there are presets.
+	fn apply_preset(
+		&self,
+		i: usize,
+		applied: &mut [bool],
+		nodes: &mut BTreeMap<Ref<Node>, NodeState>,
+		blocks: &mut BTreeMap<Ref<Block>, BlockState>,
+	) {
+		// marked before the includes, so that cycles are cut
+		let mut enter = |i: usize| {
+			(i < self.presets.len() && !std::mem::replace(&mut applied[i], true))
+				.then_some((i, 0))
+		};
+
+		let mut stack = Vec::from_iter(enter(i));
+		while let Some(&mut (i, ref mut next)) = stack.last_mut() {
+			let preset = &self.presets[i];
+
+			if let Some(include) = preset.includes.get(*next) {
+				*next += 1;
+				stack.extend(enter(include.0));
+			} else {
+				nodes.extend(preset.nodes.iter().copied());
+				blocks.extend(preset.blocks.iter().copied());
+				stack.pop();
+			}
+		}
+	}
+
+	/// Returns a cycle of includes through the preset, if there is one, as the
+	/// presets along it starting and ending with the preset.
+	///
+	/// The search is depth-first through a stack, as for applying presets.
+	pub(crate) fn preset_cycle(&self, i: usize) -> Option<Vec<usize>> {
+		let mut stack = vec![(i, 0)];
+		let mut visited = vec![false; self.presets.len()];
+
+		while let Some(&mut (j, ref mut next)) = stack.last_mut() {
+			let Some(include) = self.presets[j].includes.get(*next) else {
+				stack.pop();
+				continue
+			};
+			*next += 1;
+
+			let k = include.0;
+			if k == i {
+				let mut path = Vec::from_iter(stack.iter().map(|(j, _)| *j));
+				path.push(i);
+				return Some(path)
+			}
+
+			if k < self.presets.len() && !std::mem::replace(&mut visited[k], true) {
+				stack.push((k, 0));
+			}
+		}
+
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::fixture::{aerodrome, block};
+
+	use NodeState::{Off, On};
+
+	fn preset(name: &str, includes: &[usize]) -> Preset {
+		Preset {
+			name: name.into(),
+			includes: includes.iter().copied().map(Ref::from).collect(),
+			nodes: Vec::new(),
+			blocks: Vec::new(),
+		}
+	}
+
+	/// Builds a profile of presets each including the next, the last setting
+	/// a node, and including the first if `cycle` is set.
+	fn chain(len: usize, cycle: bool) -> Profile {
+		let mut profile = aerodrome(1, Vec::new()).profiles.remove(0);
+		profile.presets = (0..len)
+			.map(|i| preset(&format!("P{i}"), &[(i + 1) % len]))
+			.collect();
+		profile.presets[len - 1].nodes = vec![(0.into(), NodeState::On)];
+		if !cycle {
+			profile.presets[len - 1].includes.clear();
+		}
+		profile
+	}
+
+	#[test]
+	fn long_chains_of_includes_resolve() {
+		let profile = chain(100_000, false);
+		assert_eq!(profile.resolve_preset(0).nodes, [(0.into(), NodeState::On)]);
+		assert_eq!(profile.preset_cycle(0), None);
+	}
+
+	#[test]
+	fn long_cycles_of_includes_are_found() {
+		let len = 100_000;
+		let profile = chain(len, true);
+
+		let cycle = profile.preset_cycle(0).unwrap();
+		assert_eq!(cycle.len(), len + 1);
+		assert_eq!(cycle, Vec::from_iter((0..len).chain([0])));
+		assert_eq!(profile.resolve_preset(0).nodes.len(), 1);
+	}
+
+	fn nodes(entries: &[(usize, NodeState)]) -> Vec<(Ref<Node>, NodeState)> {
+		(entries.iter())
+			.map(|(node, state)| (Ref::from(*node), *state))
+			.collect()
+	}
+
+	/// Builds a profile of three nodes and a block, with the presets.
+	fn profile(presets: Vec<Preset>) -> Profile {
+		let blocks = vec![block("B0", &[0, 1], &[])];
+		let mut profile = aerodrome(3, blocks).profiles.remove(0);
+		profile.presets = presets;
+		profile
+	}
+
+	#[test]
+	fn shared_bases_of_diamonds_apply_once() {
+		let mut base = preset("Base", &[]);
+		base.nodes = nodes(&[(0, On), (1, On)]);
+		let mut left = preset("Left", &[0]);
+		left.nodes = nodes(&[(1, Off)]);
+		let mut right = preset("Right", &[0]);
+		right.nodes = nodes(&[(2, On)]);
+		let profile = profile(vec![base, left, right, preset("Top", &[1, 2])]);
+
+		// the base is not applied again through the right, undoing the left
+		let resolved = profile.resolve_preset(3);
+		assert_eq!(resolved.nodes, nodes(&[(0, On), (1, Off), (2, On)]));
+		assert_eq!(profile.preset_cycle(3), None);
+
+		// but each side alone applies it
+		assert_eq!(
+			profile.resolve_preset(2).nodes,
+			nodes(&[(0, On), (1, On), (2, On)]),
+		);
+	}
+
+	#[test]
+	fn later_entries_override_earlier() {
+		let mut departures = preset("27 departures", &[]);
+		departures.nodes = nodes(&[(0, On), (2, On)]);
+		departures.blocks = vec![(0.into(), BlockState::Relax)];
+		let mut arrivals = preset("27 arrivals", &[]);
+		arrivals.nodes = nodes(&[(0, Off), (1, On)]);
+		let mut full = preset("27 full", &[0, 1]);
+		full.nodes = nodes(&[(1, Off)]);
+		let profile = profile(vec![departures, arrivals, full]);
+
+		// later includes override earlier, and the preset's own entries both
+		let resolved = profile.resolve_preset(2);
+		assert_eq!(resolved.nodes, nodes(&[(0, Off), (1, Off), (2, On)]));
+		assert_eq!(resolved.blocks, [(0.into(), BlockState::Relax)]);
+
+		// a preset resolved by value applies its includes the same
+		let mut captured = preset("captured", &[1, 0]);
+		captured.nodes = nodes(&[(2, Off)]);
+		let resolved = profile.resolve(&captured);
+		assert_eq!(resolved.nodes, nodes(&[(0, On), (1, On), (2, Off)]));
+	}
+
+	#[test]
+	fn cycles_of_includes_are_rejected() {
+		let mut config = aerodrome(3, vec![block("B0", &[0, 1], &[])]);
+		config.profiles[0].presets = vec![
+			preset("A", &[1]),
+			preset("B", &[0]),
+			// including a cycle is not itself one
+			preset("C", &[0]),
+		];
+
+		let cycles = (config.validate().into_iter())
+			.filter(|issue| issue.code() == "preset-cycle")
+			.map(|issue| (issue.location, issue.kind))
+			.collect::<Vec<_>>();
+		let cycle = |presets: [&str; 3]| IssueKind::PresetCycle {
+			presets: presets.map(String::from).into(),
+		};
+		assert_eq!(
+			cycles,
+			[
+				(
+					"profiles[0].presets[0].includes".into(),
+					cycle(["A", "B", "A"])
+				),
+				(
+					"profiles[0].presets[1].includes".into(),
+					cycle(["B", "A", "B"])
+				),
+			]
+		);
+		assert!(cycles
+			.iter()
+			.all(|(_, kind)| kind.severity() == Severity::Error));
+
+		// and resolving them still ends
+		let profile = &config.profiles[0];
+		assert_eq!(profile.resolve_preset(2).nodes, []);
+	}
+}
@@ -0,0 +1,203 @@
+use crate::*;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Result as IoResult;
+use std::ops::ControlFlow;
+
+/// compressed bytes read between reports of progress
+const REPORT_INTERVAL: u64 = 64 * 1024;
+
+/// Stage of a load, as reported to a progress callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadPhase {
+	/// reading the magic number and version
+	Header,
+	/// decompressing and decoding the contents
+	Decode,
+	/// finished decoding
+	Done,
+}
+
+/// How far a load has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+	pub phase: LoadPhase,
+	/// compressed bytes read, including the header
+	pub consumed: u64,
+	/// size of the file, if known
+	pub total: Option<u64>,
+}
+
+impl Progress {
+	/// Returns the part of the file read, from 0 to 1, if its size is known.
+	pub fn fraction(&self) -> Option<f32> {
+		let total = self.total.filter(|total| *total > 0)?;
+		Some((self.consumed as f64 / total as f64).min(1.0) as f32)
+	}
+}
+
+/// An error from loading a config or maps file.
+#[derive(Debug)]
+pub enum LoadError {
+	/// the progress callback asked for the load to stop
+	Cancelled,
+	/// not a config or maps file at all
+	InvalidMagic,
+	UnsupportedVersion {
+		version: u16,
+		oldest: u16,
+		newest: u16,
+	},
+	/// the file ends before its contents do
+	Truncated,
+	/// the contents do not match their checksum
+	Corrupt,
+	Decode(DecodeError),
+}
+
+impl Display for LoadError {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		let kind = |version: u16| {
+			if version & MAPS_FLAG == 0 {
+				"config"
+			} else {
+				"maps"
+			}
+		};
+
+		match self {
+			Self::Cancelled => write!(f, "load cancelled"),
+			Self::InvalidMagic => write!(f, "not a config or maps file"),
+			Self::UnsupportedVersion {
+				version,
+				oldest,
+				newest,
+			} => {
+				if kind(*version) != kind(*newest) {
+					return write!(
+						f,
+						"expected a {} file, found {} version {version:#06x}",
+						kind(*newest),
+						kind(*version),
+					)
+				}
+
+				let problem = if version > newest { "newer" } else { "older" };
+				write!(
+					f,
+					"{} version {version:#06x} is {problem} than those supported, \
+					 {oldest:#06x} to {newest:#06x}",
+					kind(*version),
+				)
+			},
+			Self::Truncated => write!(f, "file is truncated"),
+			Self::Corrupt => write!(f, "file is corrupt (checksum mismatch)"),
+			Self::Decode(err) => write!(f, "{err}"),
+		}
+	}
+}
+
+impl Error for LoadError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::Decode(err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+impl From<DecodeError> for LoadError {
+	fn from(err: DecodeError) -> Self {
+		Self::Decode(err)
+	}
+}
+
+impl From<IoError> for LoadError {
+	fn from(err: IoError) -> Self {
+		Self::Decode(DecodeError::Io {
+			inner: err,
+			additional: 0,
+		})
+	}
+}
+
+/// A reader which counts the bytes read through it, reporting them to the
+/// callback every [`REPORT_INTERVAL`] bytes, and failing every read once the
+/// callback has asked to stop.
+pub(crate) struct ProgressReader<R, F> {
+	inner: R,
+	callback: F,
+	phase: LoadPhase,
+	consumed: u64,
+	reported: u64,
+	total: Option<u64>,
+	cancelled: bool,
+}
+
+impl<R: Read, F: FnMut(Progress) -> ControlFlow<()>> ProgressReader<R, F> {
+	pub fn new(inner: R, total: Option<u64>, callback: F) -> Self {
+		Self {
+			inner,
+			callback,
+			phase: LoadPhase::Header,
+			consumed: 0,
+			reported: 0,
+			total,
+			cancelled: false,
+		}
+	}
+
+	/// Reports the progress immediately, returning whether to continue.
+	pub fn report(&mut self, phase: LoadPhase) -> Result<(), LoadError> {
+		self.phase = phase;
+		self.reported = self.consumed;
+
+		let progress = Progress {
+			phase,
+			consumed: self.consumed,
+			total: self.total,
+		};
+
+		if (self.callback)(progress).is_break() {
+			self.cancelled = true;
+		}
+
+		if self.cancelled {
+			Err(LoadError::Cancelled)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Converts an error from reading through the reader, which is a
+	/// cancellation if the callback asked to stop.
+	pub fn error(&self, err: impl Into<LoadError>) -> LoadError {
+		if self.cancelled {
+			LoadError::Cancelled
+		} else {
+			err.into()
+		}
+	}
+}
+
+impl<R: Read, F: FnMut(Progress) -> ControlFlow<()>> Read
+	for ProgressReader<R, F>
+{
+	fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+		if self.cancelled {
+			return Err(IoError::other("load cancelled"))
+		}
+
+		let n = self.inner.read(buf)?;
+		self.consumed += n as u64;
+
+		if self.consumed - self.reported >= REPORT_INTERVAL
+			&& self.report(self.phase).is_err()
+		{
+			return Err(IoError::other("load cancelled"))
+		}
+
+		Ok(n)
+	}
+}
@@ -0,0 +1,298 @@
+use super::*;
+
+/// metres per degree of latitude
+pub(crate) const METRES_PER_DEGREE: f32 = 111_320.0;
+
+/// A projection of latitude and longitude onto the plane of a map, which is
+/// equirectangular about an origin, and so accurate enough over an aerodrome.
+///
+/// The y axis increases downwards, as in maps, so that north is up.
+#[derive(Clone, Copy, Debug)]
+pub struct Projection {
+	origin: Geo,
+	/// map units per degree of latitude and longitude
+	scale: [f32; 2],
+}
+
+impl Projection {
+	/// Returns the projection with the origin at the zero point, and the
+	/// number of map units per metre given.
+	pub fn new(origin: Geo, units_per_metre: f32) -> Self {
+		let scale = METRES_PER_DEGREE * units_per_metre;
+
+		Self {
+			origin,
+			scale: [scale * origin.lat.to_radians().cos(), scale],
+		}
+	}
+
+	pub fn origin(&self) -> Geo {
+		self.origin
+	}
+
+	pub fn project(&self, geo: Geo) -> Point {
+		Point {
+			x: (geo.lon - self.origin.lon) * self.scale[0],
+			y: (self.origin.lat - geo.lat) * self.scale[1],
+		}
+	}
+
+	/// Returns the position in the plane of the point, with its offset added
+	/// as though in map units.
+	pub fn project_point(&self, point: &GeoPoint) -> Point {
+		let Point { x, y } = self.project(point.geo);
+		Point {
+			x: x + point.offset.x,
+			y: y + point.offset.y,
+		}
+	}
+}
+
+impl GeoMap {
+	/// Returns the map projected onto a plane, with a view of all it draws.
+	pub fn project(&self, projection: &Projection) -> Map {
+		let project = |point: &GeoPoint| projection.project_point(point);
+
+		let mut map = Map {
+			nodes: (self.nodes.iter())
+				.map(|node| node.map_points(project))
+				.collect(),
+			edges: (self.edges.iter())
+				.map(|edge| edge.map_points(project))
+				.collect(),
+			blocks: (self.blocks.iter())
+				.map(|block| block.map_points(project))
+				.collect(),
+			widgets: (self.widgets.iter())
+				.map(|widget| widget.map_points(project))
+				.collect(),
+			..Map::default()
+		};
+
+		map.views = map.views_or_default().into_owned();
+		map
+	}
+}
+
+impl<T: Projectable> Path<T> {
+	pub(crate) fn map_points<U: Projectable>(
+		&self,
+		f: impl Fn(&T) -> U,
+	) -> Path<U> {
+		Path {
+			points: self.points.iter().map(f).collect(),
+			style: self.style,
+			level: self.level,
+		}
+	}
+}
+
+impl<T: Projectable> Target<T> {
+	pub(crate) fn map_points<U: Projectable>(
+		&self,
+		f: impl Fn(&T) -> U,
+	) -> Target<U> {
+		Target {
+			polygons: (self.polygons.iter())
+				.map(|polygon| polygon.iter().map(&f).collect())
+				.collect(),
+			hover_style: self.hover_style,
+			active_style: self.active_style,
+			level: self.level,
+		}
+	}
+}
+
+impl<T: Projectable> NodeDisplay<T> {
+	pub(crate) fn map_points<U: Projectable>(
+		&self,
+		f: impl Fn(&T) -> U,
+	) -> NodeDisplay<U> {
+		NodeDisplay {
+			off: self.off.iter().map(|path| path.map_points(&f)).collect(),
+			on: self.on.iter().map(|path| path.map_points(&f)).collect(),
+			selected: (self.selected.iter())
+				.map(|path| path.map_points(&f))
+				.collect(),
+			target: self.target.map_points(&f),
+			tooltip: self.tooltip.clone(),
+		}
+	}
+}
+
+impl<T: Projectable> EdgeDisplay<T> {
+	pub(crate) fn map_points<U: Projectable>(
+		&self,
+		f: impl Fn(&T) -> U,
+	) -> EdgeDisplay<U> {
+		EdgeDisplay {
+			off: self.off.iter().map(|path| path.map_points(&f)).collect(),
+			on: self.on.iter().map(|path| path.map_points(&f)).collect(),
+			pending: (self.pending.iter())
+				.map(|path| path.map_points(&f))
+				.collect(),
+		}
+	}
+}
+
+impl<T: Projectable> BlockDisplay<T> {
+	pub(crate) fn map_points<U: Projectable>(
+		&self,
+		f: impl Fn(&T) -> U,
+	) -> BlockDisplay<U> {
+		BlockDisplay {
+			target: self.target.map_points(f),
+			tooltip: self.tooltip.clone(),
+		}
+	}
+}
+
+impl<T: Projectable> Widget<T> {
+	pub(crate) fn map_points<U: Projectable>(
+		&self,
+		f: impl Fn(&T) -> U,
+	) -> Widget<U> {
+		match self.clone() {
+			Self::Countdown {
+				position,
+				size,
+				condition,
+				format,
+				warn_below_secs,
+				normal_style,
+				warn_style,
+			} => Widget::Countdown {
+				position: f(&position),
+				size,
+				condition,
+				format,
+				warn_below_secs,
+				normal_style,
+				warn_style,
+			},
+			Self::Label {
+				position,
+				size,
+				holding_point,
+			} => Widget::Label {
+				position: f(&position),
+				size,
+				holding_point,
+			},
+			Self::Text {
+				position,
+				size,
+				anchor,
+				text,
+				color,
+				condition,
+			} => Widget::Text {
+				position: f(&position),
+				size,
+				anchor,
+				text,
+				color,
+				condition,
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Returns the great-circle distance in metres between two points.
+	fn haversine(a: Geo, b: Geo) -> f64 {
+		// the mean radius of the earth, in metres
+		const RADIUS: f64 = 6_371_000.0;
+
+		let [lat_a, lon_a, lat_b, lon_b] =
+			[a.lat, a.lon, b.lat, b.lon].map(|degrees| (degrees as f64).to_radians());
+		let h = ((lat_b - lat_a) / 2.0).sin().powi(2)
+			+ lat_a.cos() * lat_b.cos() * ((lon_b - lon_a) / 2.0).sin().powi(2);
+		2.0 * RADIUS * h.sqrt().asin()
+	}
+
+	fn distance(a: Point, b: Point) -> f64 {
+		((a.x - b.x) as f64).hypot((a.y - b.y) as f64)
+	}
+
+	fn geo(lat: f32, lon: f32) -> Geo {
+		Geo { lat, lon }
+	}
+
+	#[test]
+	fn distances_are_preserved_at_mid_latitudes() {
+		// the ends and intersections of runways, at aerodromes from 40 to 60°
+		let aerodromes = [
+			// KJFK 04L/22R and 13R/31L
+			[
+				geo(40.6222, -73.7856),
+				geo(40.6518, -73.7633),
+				geo(40.6483, -73.8164),
+			],
+			// EGLL 09L/27R and the threshold of 09R
+			[
+				geo(51.4775, -0.4850),
+				geo(51.4777, -0.4333),
+				geo(51.4647, -0.4823),
+			],
+			// ENGM 01L/19R and the threshold of 01R
+			[
+				geo(60.1856, 11.0740),
+				geo(60.2163, 11.0914),
+				geo(60.1774, 11.1050),
+			],
+		];
+
+		for points in aerodromes {
+			let units_per_metre = 0.5;
+			let projection = Projection::new(points[0], units_per_metre);
+			let projected = points.map(|point| projection.project(point));
+			assert_eq!(projected[0], Point { x: 0.0, y: 0.0 });
+
+			for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+				let expected = haversine(points[i], points[j]);
+				let found =
+					distance(projected[i], projected[j]) / units_per_metre as f64;
+				let error = (found - expected).abs() / expected;
+				assert!(
+					error < 0.005,
+					"{:?} to {:?}: {found} m rather than {expected} m",
+					points[i],
+					points[j],
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn north_is_up_and_offsets_follow_projection() {
+		let projection = Projection::new(geo(51.0, -1.0), 1.0);
+
+		// one degree of latitude north is up, and of longitude east is right but
+		// shorter by the cosine of the latitude
+		let north = projection.project(geo(52.0, -1.0));
+		assert_eq!(north.x, 0.0);
+		assert!((north.y + METRES_PER_DEGREE).abs() < 1.0, "{north:?}");
+
+		let east = projection.project(geo(51.0, 0.0));
+		let expected = METRES_PER_DEGREE * 51f32.to_radians().cos();
+		assert!((east.x - expected).abs() < 1.0, "{east:?}");
+		assert_eq!(east.y, 0.0);
+
+		let point = GeoPoint {
+			geo: geo(51.0, 0.0),
+			offset: Point { x: 3.0, y: -4.0 },
+		};
+		let offset = projection.project_point(&point);
+		assert_eq!(
+			offset,
+			Point {
+				x: east.x + 3.0,
+				y: -4.0
+			}
+		);
+	}
+}
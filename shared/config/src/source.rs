@@ -0,0 +1,1088 @@
+use crate::*;
+
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// An aerodrome in a human-editable form, with entities referenced by id.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AerodromeSource {
+	pub icao: String,
+
+	#[serde(default)]
+	pub elements: Vec<ElementSource>,
+	#[serde(default)]
+	pub nodes: Vec<NodeSource>,
+	#[serde(default)]
+	pub edges: Vec<EdgeSource>,
+	#[serde(default)]
+	pub blocks: Vec<BlockSource>,
+	#[serde(default)]
+	pub holding_points: Vec<HoldingPointSource>,
+
+	#[serde(default)]
+	pub profiles: Vec<ProfileSource>,
+
+	/// display strings in other languages, by key and then locale
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub strings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ElementSource {
+	pub id: String,
+	#[serde(flatten)]
+	pub condition: ElementConditionSource,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElementConditionSource {
+	Fixed(bool),
+	Node(String),
+	Edge(String),
+	/// as read by [`ConditionExpression::parse`]
+	Expression(String),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NodeSource {
+	pub id: String,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub scratchpad: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub parent: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub input_order: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EdgeSource {
+	pub id: String,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BlockSource {
+	pub id: String,
+
+	/// parent nodes only
+	#[serde(default)]
+	pub nodes: Vec<String>,
+	#[serde(default)]
+	pub edges: Vec<String>,
+	/// child nodes only
+	#[serde(default)]
+	pub non_routes: Vec<(String, String)>,
+
+	#[serde(default)]
+	pub stands: Vec<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub input_order: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HoldingPointSource {
+	pub id: String,
+	pub label: String,
+	#[serde(flatten)]
+	pub location: HoldingPointLocationSource,
+
+	/// latitude and longitude
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub position: Option<(f32, f32)>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldingPointLocationSource {
+	Edge(String),
+	Nodes(String, String),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProfileSource {
+	pub id: String,
+	pub name: String,
+	/// conditions the profile is for, such as `lvp` for low visibility
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+
+	/// conditions for every node, by id
+	#[serde(default)]
+	pub nodes: BTreeMap<String, NodeConditionSource>,
+	/// conditions for every edge, by id
+	#[serde(default)]
+	pub edges: BTreeMap<String, EdgeConditionSource>,
+	/// conditions for blocks, by id, defaulting to no reset
+	#[serde(default)]
+	pub blocks: BTreeMap<String, BlockConditionSource>,
+
+	#[serde(default)]
+	pub presets: Vec<PresetSource>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeConditionSource {
+	Fixed(bool),
+	Direct {
+		/// seconds
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		reset: Option<u32>,
+	},
+	Router {
+		#[serde(default)]
+		sticky: bool,
+	},
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeConditionSource {
+	Fixed(bool),
+	/// disjunction of conjunctions
+	Direct(Vec<NodeConjunctionSource>),
+	Router {
+		block: String,
+		#[serde(default)]
+		routes: Vec<(String, String)>,
+	},
+	/// as read by [`ConditionExpression::parse`], which may not refer to edges
+	/// which are themselves driven by expressions
+	Expression(String),
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct NodeConjunctionSource {
+	#[serde(default)]
+	pub positive: Vec<String>,
+	#[serde(default)]
+	pub negative: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BlockConditionSource {
+	/// seconds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reset: Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PresetSource {
+	pub name: String,
+
+	/// names of presets of the profile to apply first, in order
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub includes: Vec<String>,
+
+	#[serde(default)]
+	pub nodes: BTreeMap<String, bool>,
+	#[serde(default)]
+	pub blocks: BTreeMap<String, BlockStateSource>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockStateSource {
+	Clear,
+	Relax,
+	Route(String, String),
+}
+
+#[derive(Debug)]
+pub struct SourceError {
+	/// path to the offending field, such as `blocks[0].nodes[1]`
+	pub location: String,
+	pub message: String,
+}
+
+impl Display for SourceError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.location, self.message)
+	}
+}
+
+impl Error for SourceError {}
+
+struct Resolver {
+	nodes: HashMap<String, usize>,
+	edges: HashMap<String, usize>,
+	blocks: HashMap<String, usize>,
+	errors: Vec<SourceError>,
+}
+
+impl Resolver {
+	fn error(&mut self, location: impl Into<String>, message: impl Into<String>) {
+		self.errors.push(SourceError {
+			location: location.into(),
+			message: message.into(),
+		});
+	}
+
+	fn index(
+		&mut self,
+		kind: &str,
+		location: impl Into<String>,
+		ids: impl Iterator<Item = String>,
+	) -> HashMap<String, usize> {
+		let location = location.into();
+		let mut map = HashMap::new();
+
+		for (i, id) in ids.enumerate() {
+			if map.insert(id.clone(), i).is_some() {
+				self
+					.error(format!("{location}[{i}]"), format!("duplicate {kind} {id}"));
+			}
+		}
+
+		map
+	}
+
+	fn resolve<T>(
+		&mut self,
+		kind: &str,
+		location: impl Into<String>,
+		id: &str,
+	) -> Ref<T> {
+		let map = match kind {
+			"node" => &self.nodes,
+			"edge" => &self.edges,
+			_ => &self.blocks,
+		};
+
+		match map.get(id) {
+			Some(i) => Ref::from(*i),
+			None => {
+				self.error(location, format!("unknown {kind} {id}"));
+				Ref::from(usize::MAX)
+			},
+		}
+	}
+
+	fn node(&mut self, location: impl Into<String>, id: &str) -> Ref<Node> {
+		self.resolve("node", location, id)
+	}
+
+	fn edge(&mut self, location: impl Into<String>, id: &str) -> Ref<Edge> {
+		self.resolve("edge", location, id)
+	}
+
+	fn block(&mut self, location: impl Into<String>, id: &str) -> Ref<Block> {
+		self.resolve("block", location, id)
+	}
+
+	fn expression(
+		&mut self,
+		location: String,
+		text: &str,
+	) -> ConditionExpression {
+		let parsed = ConditionExpression::parse(text, |term| match term {
+			TermText::Node(id) => ConditionTerm::Node(self.node(&location, id)),
+			TermText::Edge(id) => ConditionTerm::Edge(self.edge(&location, id)),
+			TermText::Block(id, predicate) => {
+				let block = self.block(&location, id);
+				let predicate = match predicate {
+					PredicateText::Clear => BlockPredicate::Clear,
+					PredicateText::Relax => BlockPredicate::Relax,
+					PredicateText::AnyRoute => BlockPredicate::AnyRoute,
+					PredicateText::Route(from, to) => BlockPredicate::Route((
+						self.node(&location, from),
+						self.node(&location, to),
+					)),
+				};
+				ConditionTerm::Block(block, predicate)
+			},
+		});
+
+		parsed.unwrap_or_else(|err| {
+			self.error(location, err.to_string());
+			ConditionExpression::default()
+		})
+	}
+
+	fn route(
+		&mut self,
+		location: String,
+		route: &(String, String),
+	) -> BlockRoute {
+		BlockRoute {
+			from: self.node(format!("{location}.0"), &route.0),
+			to: self.node(format!("{location}.1"), &route.1),
+		}
+	}
+
+	/// Orders conditions given by id to match `ids`, reporting unknown ids, and
+	/// missing ids if `required`.
+	fn order<'a, T>(
+		&mut self,
+		kind: &str,
+		location: &str,
+		ids: &HashMap<String, usize>,
+		conditions: &'a BTreeMap<String, T>,
+		required: bool,
+	) -> Vec<Option<(&'a str, &'a T)>> {
+		let mut ordered = vec![None; ids.len()];
+
+		for (id, condition) in conditions {
+			match ids.get(id) {
+				Some(i) => ordered[*i] = Some((id.as_str(), condition)),
+				None => {
+					self.error(format!("{location}.{id}"), format!("unknown {kind} {id}"))
+				},
+			}
+		}
+
+		if !required {
+			return ordered
+		}
+
+		let mut missing = ids
+			.iter()
+			.filter(|(_, i)| ordered[**i].is_none())
+			.map(|(id, _)| id.as_str())
+			.collect::<Vec<_>>();
+
+		if !missing.is_empty() {
+			missing.sort();
+			let message = format!("no conditions for {kind}s {}", missing.join(", "));
+			self.error(location, message);
+		}
+
+		ordered
+	}
+}
+
+fn reset(reset: Option<u32>) -> ResetCondition {
+	reset
+		.map(ResetCondition::TimeSecs)
+		.unwrap_or(ResetCondition::None)
+}
+
+fn node_state(state: bool) -> NodeState {
+	if state {
+		NodeState::On
+	} else {
+		NodeState::Off
+	}
+}
+
+impl AerodromeSource {
+	/// Resolves ids to produce an aerodrome without maps.
+	///
+	/// All errors are returned, rather than only the first.
+	pub fn compile(&self) -> Result<Aerodrome, Vec<SourceError>> {
+		let mut resolver = Resolver {
+			nodes: HashMap::new(),
+			edges: HashMap::new(),
+			blocks: HashMap::new(),
+			errors: Vec::new(),
+		};
+
+		resolver.index(
+			"element",
+			"elements",
+			self.elements.iter().map(|e| e.id.clone()),
+		);
+		resolver.nodes =
+			resolver.index("node", "nodes", self.nodes.iter().map(|n| n.id.clone()));
+		resolver.edges =
+			resolver.index("edge", "edges", self.edges.iter().map(|e| e.id.clone()));
+		resolver.blocks = resolver.index(
+			"block",
+			"blocks",
+			self.blocks.iter().map(|b| b.id.clone()),
+		);
+		resolver.index(
+			"holding point",
+			"holding_points",
+			self.holding_points.iter().map(|h| h.id.clone()),
+		);
+
+		let elements = self
+			.elements
+			.iter()
+			.enumerate()
+			.map(|(i, element)| {
+				let location = format!("elements[{i}]");
+				Element {
+					id: element.id.clone(),
+					condition: match &element.condition {
+						ElementConditionSource::Fixed(state) => {
+							ElementCondition::Fixed(*state)
+						},
+						ElementConditionSource::Node(id) => ElementCondition::Node(
+							resolver.node(format!("{location}.node"), id),
+						),
+						ElementConditionSource::Edge(id) => ElementCondition::Edge(
+							resolver.edge(format!("{location}.edge"), id),
+						),
+						ElementConditionSource::Expression(text) => {
+							ElementCondition::Expression(
+								resolver.expression(format!("{location}.expression"), text),
+							)
+						},
+					},
+				}
+			})
+			.collect();
+
+		let nodes = self
+			.nodes
+			.iter()
+			.enumerate()
+			.map(|(i, node)| Node {
+				id: node.id.clone(),
+				scratchpad: node.scratchpad.clone(),
+				parent: node
+					.parent
+					.as_ref()
+					.map(|id| resolver.node(format!("nodes[{i}].parent"), id)),
+				input_order: node.input_order,
+			})
+			.collect();
+
+		let edges = self
+			.edges
+			.iter()
+			.map(|edge| Edge {
+				id: edge.id.clone(),
+				name: edge.name.clone(),
+			})
+			.collect();
+
+		let blocks = self
+			.blocks
+			.iter()
+			.enumerate()
+			.map(|(i, block)| {
+				let location = format!("blocks[{i}]");
+				Block {
+					id: block.id.clone(),
+					nodes: (block.nodes.iter().enumerate())
+						.map(|(j, id)| resolver.node(format!("{location}.nodes[{j}]"), id))
+						.collect(),
+					edges: (block.edges.iter().enumerate())
+						.map(|(j, id)| resolver.edge(format!("{location}.edges[{j}]"), id))
+						.collect(),
+					non_routes: (block.non_routes.iter().enumerate())
+						.map(|(j, route)| {
+							resolver.route(format!("{location}.non_routes[{j}]"), route)
+						})
+						.collect(),
+					stands: block.stands.clone(),
+					input_order: block.input_order,
+				}
+			})
+			.collect();
+
+		let holding_points = self
+			.holding_points
+			.iter()
+			.enumerate()
+			.map(|(i, holding_point)| {
+				let location = format!("holding_points[{i}]");
+				HoldingPoint {
+					id: holding_point.id.clone(),
+					label: holding_point.label.clone(),
+					location: match &holding_point.location {
+						HoldingPointLocationSource::Edge(id) => HoldingPointLocation::Edge(
+							resolver.edge(format!("{location}.edge"), id),
+						),
+						HoldingPointLocationSource::Nodes(a, b) => {
+							HoldingPointLocation::Nodes(
+								resolver.node(format!("{location}.nodes.0"), a),
+								resolver.node(format!("{location}.nodes.1"), b),
+							)
+						},
+					},
+					position: holding_point.position.map(|(lat, lon)| Geo { lat, lon }),
+				}
+			})
+			.collect();
+
+		let profiles = self
+			.profiles
+			.iter()
+			.enumerate()
+			.map(|(i, profile)| {
+				profile.compile(&mut resolver, format!("profiles[{i}]"))
+			})
+			.collect();
+
+		if !resolver.errors.is_empty() {
+			return Err(resolver.errors)
+		}
+
+		Ok(Aerodrome {
+			icao: self.icao.clone(),
+			elements,
+			nodes,
+			edges,
+			blocks,
+			holding_points,
+			profiles,
+			strings: (!self.strings.is_empty()).then(|| LocaleTable {
+				strings: self.strings.clone(),
+			}),
+			geo_map: None,
+			maps: Vec::new(),
+			styles: Vec::new(),
+		})
+	}
+}
+
+impl ProfileSource {
+	fn compile(&self, resolver: &mut Resolver, location: String) -> Profile {
+		let node_ids = resolver.nodes.clone();
+		let nodes = resolver
+			.order(
+				"node",
+				&format!("{location}.nodes"),
+				&node_ids,
+				&self.nodes,
+				true,
+			)
+			.into_iter()
+			.map(
+				|condition| match condition.map(|(_, condition)| condition) {
+					Some(NodeConditionSource::Fixed(state)) => NodeCondition::Fixed {
+						state: node_state(*state),
+					},
+					Some(NodeConditionSource::Direct { reset: secs }) => {
+						NodeCondition::Direct {
+							reset: reset(*secs),
+						}
+					},
+					Some(NodeConditionSource::Router { sticky }) => {
+						NodeCondition::Router { sticky: *sticky }
+					},
+					None => NodeCondition::Fixed {
+						state: NodeState::Off,
+					},
+				},
+			)
+			.collect();
+
+		let edge_ids = resolver.edges.clone();
+		let edges = resolver
+			.order(
+				"edge",
+				&format!("{location}.edges"),
+				&edge_ids,
+				&self.edges,
+				true,
+			)
+			.into_iter()
+			.map(|condition| {
+				let Some((id, condition)) = condition else {
+					return EdgeCondition::Fixed {
+						state: EdgeState::Off,
+					}
+				};
+
+				let location = format!("{location}.edges.{id}");
+				match condition {
+					EdgeConditionSource::Fixed(state) => EdgeCondition::Fixed {
+						state: if *state {
+							EdgeState::On
+						} else {
+							EdgeState::Off
+						},
+					},
+					EdgeConditionSource::Direct(disjunction) => EdgeCondition::Direct {
+						nodes: NodeExpression {
+							disjunction: (disjunction.iter().enumerate())
+								.map(|(j, conjunction)| NodeConjunction {
+									positive: (conjunction.positive.iter())
+										.map(|id| {
+											resolver.node(format!("{location}[{j}].positive"), id)
+										})
+										.collect(),
+									negative: (conjunction.negative.iter())
+										.map(|id| {
+											resolver.node(format!("{location}[{j}].negative"), id)
+										})
+										.collect(),
+								})
+								.collect(),
+						},
+					},
+					EdgeConditionSource::Router { block, routes } => {
+						EdgeCondition::Router {
+							block: resolver.block(format!("{location}.block"), block),
+							routes: (routes.iter().enumerate())
+								.map(|(j, route)| {
+									resolver.route(format!("{location}.routes[{j}]"), route)
+								})
+								.collect(),
+						}
+					},
+					EdgeConditionSource::Expression(text) => EdgeCondition::Expression {
+						expression: resolver.expression(location, text),
+					},
+				}
+			})
+			.collect();
+
+		let block_ids = resolver.blocks.clone();
+		let blocks = resolver
+			.order(
+				"block",
+				&format!("{location}.blocks"),
+				&block_ids,
+				&self.blocks,
+				false,
+			)
+			.into_iter()
+			.map(|condition| BlockCondition {
+				reset: reset(condition.and_then(|(_, condition)| condition.reset)),
+			})
+			.collect();
+
+		let preset_names = resolver.index(
+			"preset",
+			format!("{location}.presets"),
+			self.presets.iter().map(|p| p.name.clone()),
+		);
+
+		let presets = self
+			.presets
+			.iter()
+			.enumerate()
+			.map(|(j, preset)| {
+				let location = format!("{location}.presets[{j}]");
+				Preset {
+					name: preset.name.clone(),
+					includes: (preset.includes.iter().enumerate())
+						.map(|(k, name)| match preset_names.get(name) {
+							Some(i) => Ref::from(*i),
+							None => {
+								resolver.error(
+									format!("{location}.includes[{k}]"),
+									format!("unknown preset {name}"),
+								);
+								Ref::from(usize::MAX)
+							},
+						})
+						.collect(),
+					nodes: (preset.nodes.iter())
+						.map(|(id, state)| {
+							(
+								resolver.node(format!("{location}.nodes.{id}"), id),
+								node_state(*state),
+							)
+						})
+						.collect(),
+					blocks: (preset.blocks.iter())
+						.map(|(id, state)| {
+							let location = format!("{location}.blocks.{id}");
+							let state = match state {
+								BlockStateSource::Clear => BlockState::Clear,
+								BlockStateSource::Relax => BlockState::Relax,
+								BlockStateSource::Route(from, to) => BlockState::Route((
+									resolver.node(format!("{location}.route.0"), from),
+									resolver.node(format!("{location}.route.1"), to),
+								)),
+							};
+
+							(resolver.block(location, id), state)
+						})
+						.collect(),
+				}
+			})
+			.collect();
+
+		Profile {
+			id: self.id.clone(),
+			name: self.name.clone(),
+			tags: self.tags.clone(),
+			nodes,
+			edges,
+			blocks,
+			presets,
+		}
+	}
+}
+
+/// Names entities by id for [`Aerodrome::to_source`], collecting refs which
+/// are out of bounds.
+struct Namer<'a> {
+	aerodrome: &'a Aerodrome,
+	errors: Vec<SourceError>,
+}
+
+impl Namer<'_> {
+	fn name<'a, T>(
+		&mut self,
+		kind: &str,
+		location: impl Into<String>,
+		ids: impl Fn(usize) -> Option<&'a str>,
+		target: Ref<T>,
+	) -> String {
+		match ids(target.0) {
+			Some(id) => id.into(),
+			None => {
+				self.errors.push(SourceError {
+					location: location.into(),
+					message: format!("{kind} {} out of bounds", target.0),
+				});
+				String::new()
+			},
+		}
+	}
+
+	fn node(&mut self, location: impl Into<String>, node: Ref<Node>) -> String {
+		let nodes = &self.aerodrome.nodes;
+		self.name("node", location, |i| Some(nodes.get(i)?.id.as_str()), node)
+	}
+
+	fn edge(&mut self, location: impl Into<String>, edge: Ref<Edge>) -> String {
+		let edges = &self.aerodrome.edges;
+		self.name("edge", location, |i| Some(edges.get(i)?.id.as_str()), edge)
+	}
+
+	fn block(
+		&mut self,
+		location: impl Into<String>,
+		block: Ref<Block>,
+	) -> String {
+		let blocks = &self.aerodrome.blocks;
+		self.name(
+			"block",
+			location,
+			|i| Some(blocks.get(i)?.id.as_str()),
+			block,
+		)
+	}
+
+	fn expression(
+		&mut self,
+		location: String,
+		expression: &ConditionExpression,
+	) -> String {
+		for term in expression.terms() {
+			match *term {
+				ConditionTerm::Node(node) => _ = self.node(&location, node),
+				ConditionTerm::Edge(edge) => _ = self.edge(&location, edge),
+				ConditionTerm::Block(block, predicate) => {
+					self.block(&location, block);
+					if let BlockPredicate::Route(route) = predicate {
+						self.node(&location, route.0);
+						self.node(&location, route.1);
+					}
+				},
+			}
+		}
+
+		expression.to_text(self.aerodrome)
+	}
+
+	fn route(
+		&mut self,
+		location: String,
+		route: &BlockRoute,
+	) -> (String, String) {
+		(
+			self.node(format!("{location}.0"), route.from),
+			self.node(format!("{location}.1"), route.to),
+		)
+	}
+}
+
+fn reset_secs(reset: ResetCondition) -> Option<u32> {
+	match reset {
+		ResetCondition::None => None,
+		ResetCondition::TimeSecs(secs) => Some(secs),
+	}
+}
+
+impl Aerodrome {
+	/// Writes the aerodrome in source form, naming entities by id, such that
+	/// compiling the source gives the same aerodrome without maps. The entries
+	/// of presets come back in order of id, with any repeats merged.
+	///
+	/// All refs out of bounds are returned as errors, rather than only the
+	/// first.
+	pub fn to_source(&self) -> Result<AerodromeSource, Vec<SourceError>> {
+		let mut namer = Namer {
+			aerodrome: self,
+			errors: Vec::new(),
+		};
+
+		let elements = (self.elements.iter().enumerate())
+			.map(|(i, element)| {
+				let location = format!("elements[{i}]");
+				ElementSource {
+					id: element.id.clone(),
+					condition: match &element.condition {
+						ElementCondition::Fixed(state) => {
+							ElementConditionSource::Fixed(*state)
+						},
+						ElementCondition::Node(node) => ElementConditionSource::Node(
+							namer.node(format!("{location}.node"), *node),
+						),
+						ElementCondition::Edge(edge) => ElementConditionSource::Edge(
+							namer.edge(format!("{location}.edge"), *edge),
+						),
+						ElementCondition::Expression(expression) => {
+							ElementConditionSource::Expression(
+								namer.expression(format!("{location}.expression"), expression),
+							)
+						},
+					},
+				}
+			})
+			.collect();
+
+		let nodes = (self.nodes.iter().enumerate())
+			.map(|(i, node)| NodeSource {
+				id: node.id.clone(),
+				scratchpad: node.scratchpad.clone(),
+				parent: (node.parent)
+					.map(|parent| namer.node(format!("nodes[{i}].parent"), parent)),
+				input_order: node.input_order,
+			})
+			.collect();
+
+		let edges = (self.edges.iter())
+			.map(|edge| EdgeSource {
+				id: edge.id.clone(),
+				name: edge.name.clone(),
+			})
+			.collect();
+
+		let blocks = (self.blocks.iter().enumerate())
+			.map(|(i, block)| {
+				let location = format!("blocks[{i}]");
+				BlockSource {
+					id: block.id.clone(),
+					nodes: (block.nodes.iter().enumerate())
+						.map(|(j, node)| {
+							namer.node(format!("{location}.nodes[{j}]"), *node)
+						})
+						.collect(),
+					edges: (block.edges.iter().enumerate())
+						.map(|(j, edge)| {
+							namer.edge(format!("{location}.edges[{j}]"), *edge)
+						})
+						.collect(),
+					non_routes: (block.non_routes.iter().enumerate())
+						.map(|(j, route)| {
+							namer.route(format!("{location}.non_routes[{j}]"), route)
+						})
+						.collect(),
+					stands: block.stands.clone(),
+					input_order: block.input_order,
+				}
+			})
+			.collect();
+
+		let holding_points = (self.holding_points.iter().enumerate())
+			.map(|(i, holding_point)| {
+				let location = format!("holding_points[{i}]");
+				HoldingPointSource {
+					id: holding_point.id.clone(),
+					label: holding_point.label.clone(),
+					location: match holding_point.location {
+						HoldingPointLocation::Edge(edge) => {
+							HoldingPointLocationSource::Edge(
+								namer.edge(format!("{location}.edge"), edge),
+							)
+						},
+						HoldingPointLocation::Nodes(a, b) => {
+							HoldingPointLocationSource::Nodes(
+								namer.node(format!("{location}.nodes.0"), a),
+								namer.node(format!("{location}.nodes.1"), b),
+							)
+						},
+					},
+					position: (holding_point.position).map(|geo| (geo.lat, geo.lon)),
+				}
+			})
+			.collect();
+
+		let profiles = (self.profiles.iter().enumerate())
+			.map(|(i, profile)| {
+				profile.to_source(&mut namer, format!("profiles[{i}]"))
+			})
+			.collect();
+
+		if !namer.errors.is_empty() {
+			return Err(namer.errors)
+		}
+
+		Ok(AerodromeSource {
+			icao: self.icao.clone(),
+			elements,
+			nodes,
+			edges,
+			blocks,
+			holding_points,
+			profiles,
+			strings: (self.strings.as_ref())
+				.map(|table| table.strings.clone())
+				.unwrap_or_default(),
+		})
+	}
+}
+
+impl Profile {
+	fn to_source(&self, namer: &mut Namer, location: String) -> ProfileSource {
+		let aerodrome = namer.aerodrome;
+
+		let counts = [
+			("node", aerodrome.nodes.len(), self.nodes.len()),
+			("edge", aerodrome.edges.len(), self.edges.len()),
+			("block", aerodrome.blocks.len(), self.blocks.len()),
+		];
+		for (kind, expected, found) in counts {
+			if expected != found {
+				namer.errors.push(SourceError {
+					location: format!("{location}.{kind}s"),
+					message: format!(
+						"expected {expected} {kind} conditions, found {found}"
+					),
+				});
+			}
+		}
+
+		let nodes = (aerodrome.nodes.iter().zip(&self.nodes))
+			.map(|(node, condition)| {
+				let condition = match *condition {
+					NodeCondition::Fixed { state } => {
+						NodeConditionSource::Fixed(state == NodeState::On)
+					},
+					NodeCondition::Direct { reset } => NodeConditionSource::Direct {
+						reset: reset_secs(reset),
+					},
+					NodeCondition::Router { sticky } => {
+						NodeConditionSource::Router { sticky }
+					},
+				};
+				(node.id.clone(), condition)
+			})
+			.collect();
+
+		let edges = (aerodrome.edges.iter().zip(&self.edges))
+			.map(|(edge, condition)| {
+				let location = format!("{location}.edges.{}", edge.id);
+				let condition = match condition {
+					EdgeCondition::Fixed { state } => {
+						EdgeConditionSource::Fixed(*state == EdgeState::On)
+					},
+					EdgeCondition::Direct { nodes } => EdgeConditionSource::Direct(
+						(nodes.disjunction.iter().enumerate())
+							.map(|(j, conjunction)| NodeConjunctionSource {
+								positive: (conjunction.positive.iter())
+									.map(|node| {
+										namer.node(format!("{location}[{j}].positive"), *node)
+									})
+									.collect(),
+								negative: (conjunction.negative.iter())
+									.map(|node| {
+										namer.node(format!("{location}[{j}].negative"), *node)
+									})
+									.collect(),
+							})
+							.collect(),
+					),
+					EdgeCondition::Router { block, routes } => {
+						EdgeConditionSource::Router {
+							block: namer.block(format!("{location}.block"), *block),
+							routes: (routes.iter().enumerate())
+								.map(|(j, route)| {
+									namer.route(format!("{location}.routes[{j}]"), route)
+								})
+								.collect(),
+						}
+					},
+					EdgeCondition::Expression { expression } => {
+						EdgeConditionSource::Expression(
+							namer.expression(location, expression),
+						)
+					},
+				};
+				(edge.id.clone(), condition)
+			})
+			.collect();
+
+		// blocks default to no reset, so only those with one are written
+		let blocks = (aerodrome.blocks.iter().zip(&self.blocks))
+			.filter_map(|(block, condition)| {
+				let reset = reset_secs(condition.reset)?;
+				Some((
+					block.id.clone(),
+					BlockConditionSource { reset: Some(reset) },
+				))
+			})
+			.collect();
+
+		// includes are written by name, so names must tell presets apart
+		let mut names = HashSet::new();
+		for (j, preset) in self.presets.iter().enumerate() {
+			if !names.insert(&preset.name) {
+				namer.errors.push(SourceError {
+					location: format!("{location}.presets[{j}]"),
+					message: format!("duplicate preset {}", preset.name),
+				});
+			}
+		}
+
+		let presets = (self.presets.iter().enumerate())
+			.map(|(j, preset)| {
+				let location = format!("{location}.presets[{j}]");
+				PresetSource {
+					name: preset.name.clone(),
+					includes: (preset.includes.iter().enumerate())
+						.map(|(k, include)| match self.presets.get(include.0) {
+							Some(included) => included.name.clone(),
+							None => {
+								namer.errors.push(SourceError {
+									location: format!("{location}.includes[{k}]"),
+									message: format!("preset {} out of bounds", include.0),
+								});
+								String::new()
+							},
+						})
+						.collect(),
+					nodes: (preset.nodes.iter().enumerate())
+						.map(|(k, (node, state))| {
+							let id = namer.node(format!("{location}.nodes[{k}]"), *node);
+							(id, *state == NodeState::On)
+						})
+						.collect(),
+					blocks: (preset.blocks.iter().enumerate())
+						.map(|(k, (block, state))| {
+							let location = format!("{location}.blocks[{k}]");
+							let state = match *state {
+								BlockState::Clear => BlockStateSource::Clear,
+								BlockState::Relax => BlockStateSource::Relax,
+								BlockState::Route((from, to)) => BlockStateSource::Route(
+									namer.node(format!("{location}.route.0"), from),
+									namer.node(format!("{location}.route.1"), to),
+								),
+							};
+							(namer.block(location, *block), state)
+						})
+						.collect(),
+				}
+			})
+			.collect();
+
+		ProfileSource {
+			id: self.id.clone(),
+			name: self.name.clone(),
+			tags: self.tags.clone(),
+			nodes,
+			edges,
+			blocks,
+			presets,
+		}
+	}
+}
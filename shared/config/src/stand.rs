@@ -0,0 +1,265 @@
+use super::*;
+
+/// How closely an entry in the stands of a block matches a stand.
+///
+/// Exact matches rank above any pattern, and longer patterns above shorter
+/// ones, so the best match decides which block a stand belongs to.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StandMatch {
+	/// matched by a range or glob, with the length of the pattern
+	Pattern(usize),
+	Exact,
+}
+
+impl Block {
+	/// Returns whether any entry in the stands of the block matches the stand.
+	///
+	/// Entries are stand names, numeric ranges such as `A1-A12` (matching `A1`
+	/// to `A12` but not `A01`), or globs in which `*` matches any characters and
+	/// `?` any one character.
+	pub fn matches_stand(&self, stand: &str) -> bool {
+		self.stand_match(stand).is_some()
+	}
+
+	/// Returns the best match for the stand among the stands of the block.
+	pub fn stand_match(&self, stand: &str) -> Option<StandMatch> {
+		self
+			.stands
+			.iter()
+			.filter_map(|entry| match_stand(entry, stand))
+			.max()
+	}
+}
+
+impl Aerodrome {
+	/// Returns the block a stand belongs to, being the block with the best
+	/// match for it, or `None` if no block or more than one has the best match.
+	pub fn stand_block(&self, stand: &str) -> Option<Ref<Block>> {
+		let mut best = None;
+		let mut ambiguous = false;
+
+		for (i, block) in self.blocks.iter().enumerate() {
+			let Some(rank) = block.stand_match(stand) else {
+				continue
+			};
+
+			match best {
+				Some((best_rank, _)) if rank < best_rank => (),
+				Some((best_rank, _)) if rank == best_rank => ambiguous = true,
+				_ => {
+					best = Some((rank, i));
+					ambiguous = false;
+				},
+			}
+		}
+
+		best.filter(|_| !ambiguous).map(|(_, i)| i.into())
+	}
+}
+
+fn match_stand(entry: &str, stand: &str) -> Option<StandMatch> {
+	if entry == stand {
+		return Some(StandMatch::Exact)
+	}
+
+	let matched = if let Some((prefix, range)) = parse_range(entry) {
+		split_number(stand).is_some_and(|(stand_prefix, number)| {
+			stand_prefix == prefix && range.contains(&number)
+		})
+	} else if is_glob(entry) {
+		glob(
+			&entry.chars().collect::<Vec<_>>(),
+			&stand.chars().collect::<Vec<_>>(),
+		)
+	} else {
+		false
+	};
+
+	matched.then_some(StandMatch::Pattern(entry.chars().count()))
+}
+
+/// Returns stands matched by an entry, for checking which other blocks match
+/// them: every stand of a range, up to a limit, or one example of a glob.
+pub(crate) fn stand_examples(entry: &str) -> Vec<String> {
+	if let Some((prefix, range)) = parse_range(entry) {
+		range.take(1000).map(|n| format!("{prefix}{n}")).collect()
+	} else if is_glob(entry) {
+		vec![entry.replace('*', "").replace('?', "0")]
+	} else {
+		vec![entry.to_owned()]
+	}
+}
+
+fn is_glob(entry: &str) -> bool {
+	entry.contains(['*', '?'])
+}
+
+/// Parses a range such as `A1-A12` or `A1-12` into its prefix and numbers.
+fn parse_range(entry: &str) -> Option<(&str, std::ops::RangeInclusive<u32>)> {
+	let (start, end) = entry.split_once(['-', '–'])?;
+	let (prefix, start) = split_number(start)?;
+	let (end_prefix, end) = split_number(end)?;
+
+	if (!end_prefix.is_empty() && end_prefix != prefix) || start > end {
+		return None
+	}
+
+	Some((prefix, start..=end))
+}
+
+/// Splits a stand into its prefix and trailing number, which must not have
+/// leading zeros.
+fn split_number(stand: &str) -> Option<(&str, u32)> {
+	let digits =
+		stand.len() - stand.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+	let (prefix, number) = stand.split_at(stand.len() - digits);
+
+	if number.is_empty() || (number.len() > 1 && number.starts_with('0')) {
+		return None
+	}
+
+	Some((prefix, number.parse().ok()?))
+}
+
+/// Matches a glob against text, in time linear in the product of their lengths
+/// at worst, however many `*`s the glob has.
+fn glob(pattern: &[char], text: &[char]) -> bool {
+	let (mut p, mut t) = (0, 0);
+	// the last `*` seen, and where in the text it was last tried up to
+	let mut star = None;
+
+	while t < text.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				star = Some((p, t));
+				p += 1;
+			},
+			Some(c) if *c == '?' || *c == text[t] => {
+				p += 1;
+				t += 1;
+			},
+			// a mismatch after a `*` is retried with it taking one more character
+			_ => match star {
+				Some((star_p, star_t)) => {
+					star = Some((star_p, star_t + 1));
+					p = star_p + 1;
+					t = star_t + 1;
+				},
+				None => return false,
+			},
+		}
+	}
+
+	pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::fixture::{aerodrome, block};
+
+	use std::time::{Duration, Instant};
+
+	fn matches(entry: &str, stand: &str) -> bool {
+		block("B0", &[], &[entry]).matches_stand(stand)
+	}
+
+	#[test]
+	fn ranges_match_numbers_within_them() {
+		let table = [
+			("A1-A12", "A1", true),
+			("A1-A12", "A12", true),
+			("A1-A12", "A7", true),
+			("A1-A12", "A13", false),
+			("A1-A12", "A0", false),
+			("A1-A12", "A01", false),
+			("A1-A12", "B7", false),
+			("A1-12", "A12", true),
+			("10-20", "15", true),
+			("10-20", "A15", false),
+			// backwards, or with another prefix at the end, is only a name
+			("A12-A1", "A7", false),
+			("A1-B12", "A7", false),
+			("A1-B12", "A1-B12", true),
+		];
+
+		for (entry, stand, expected) in table {
+			assert_eq!(matches(entry, stand), expected, "{entry} {stand}");
+		}
+	}
+
+	#[test]
+	fn globs_match_any_characters() {
+		let table = [
+			("5*", "5", true),
+			("5*", "512", true),
+			("5*", "45", false),
+			("*L", "12L", true),
+			("*L", "12R", false),
+			("?1", "A1", true),
+			("?1", "AA1", false),
+			("A*1*", "AB1C", true),
+			("A*1*", "AB2C", false),
+			("**", "", true),
+			("a*b*c", "aXbXbXc", true),
+			("a*b*c", "aXbXbXd", false),
+		];
+
+		for (entry, stand, expected) in table {
+			assert_eq!(matches(entry, stand), expected, "{entry} {stand}");
+		}
+	}
+
+	#[test]
+	fn globs_with_many_stars_match_quickly() {
+		let entry = "*a*a*a*a*a*a*a*a*a*a*a*a*b";
+		let stand = "a".repeat(200);
+
+		let start = Instant::now();
+		assert!(!matches(entry, &stand));
+		assert!(matches(entry, &(stand + "b")));
+		assert!(start.elapsed() < Duration::from_secs(1));
+	}
+
+	#[test]
+	fn stands_belong_to_the_best_match() {
+		let aerodrome = aerodrome(
+			4,
+			vec![
+				block("B0", &[0, 1], &["A1-A12"]),
+				block("B1", &[1, 2], &["A5", "B*"]),
+				block("B2", &[2, 3], &["B1*"]),
+				block("B3", &[0, 3], &["C?"]),
+				block("B4", &[0, 2], &["C*", "D1"]),
+			],
+		);
+
+		let table: [(&str, Option<usize>); 8] = [
+			("A7", Some(0)),
+			// exact above a range
+			("A5", Some(1)),
+			// a longer pattern above a shorter
+			("B12", Some(2)),
+			("B21", Some(1)),
+			("CCC", Some(4)),
+			("D1", Some(4)),
+			// patterns equally long are ambiguous
+			("C4", None),
+			("A13", None),
+		];
+
+		for (stand, expected) in table {
+			assert_eq!(
+				aerodrome.stand_block(stand),
+				expected.map(Ref::from),
+				"{stand}",
+			);
+		}
+
+		assert_eq!(
+			block("B0", &[], &["5*", "512"]).stand_match("512"),
+			Some(StandMatch::Exact),
+		);
+	}
+}
@@ -0,0 +1,235 @@
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Display strings of an aerodrome in other languages.
+///
+/// Strings are keyed by a [`StringKind`] and an id, as in `profile.DEP`, and
+/// then by locale, as in `fr` or `fr-CA`. Ids themselves are never translated.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Decode, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct LocaleTable {
+	pub strings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl LocaleTable {
+	/// Returns every locale with any strings.
+	pub fn locales(&self) -> BTreeSet<&str> {
+		(self.strings.values())
+			.flat_map(|strings| strings.keys().map(String::as_str))
+			.collect()
+	}
+
+	/// Returns the string for the key in the locale, or failing that in the
+	/// language of the locale, as `fr` for `fr-CA`.
+	pub fn get(&self, key: &str, locale: &str) -> Option<&str> {
+		let strings = self.strings.get(key)?;
+		strings
+			.get(locale)
+			.or_else(|| {
+				let (language, _) = locale.split_once(['-', '_'])?;
+				strings.get(language)
+			})
+			.map(String::as_str)
+	}
+}
+
+/// The kind of entity a display string is for.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StringKind {
+	Profile,
+	/// presets by name, shared by presets of the same name in any profile
+	Preset,
+	Node,
+	HoldingPoint,
+}
+
+impl StringKind {
+	pub const ALL: [Self; 4] =
+		[Self::Profile, Self::Preset, Self::Node, Self::HoldingPoint];
+
+	/// Returns the key of the strings for the entity with the id.
+	pub fn key(self, id: &str) -> String {
+		format!("{self}.{id}")
+	}
+
+	/// Splits a key into its kind and id.
+	pub fn parse_key(key: &str) -> Option<(Self, &str)> {
+		let (kind, id) = key.split_once('.')?;
+		let kind = Self::ALL.into_iter().find(|k| k.to_string() == kind)?;
+		Some((kind, id))
+	}
+}
+
+impl Display for StringKind {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		f.write_str(match self {
+			Self::Profile => "profile",
+			Self::Preset => "preset",
+			Self::Node => "node",
+			Self::HoldingPoint => "holding_point",
+		})
+	}
+}
+
+impl Aerodrome {
+	/// Returns the name of an entity in the locale, falling back to its default
+	/// name if it has no string in the locale.
+	///
+	/// The default names are those of profiles, the labels of holding points,
+	/// and otherwise the id itself.
+	pub fn localized_name<'a>(
+		&'a self,
+		kind: StringKind,
+		id: &'a str,
+		locale: &str,
+	) -> &'a str {
+		self
+			.strings
+			.as_ref()
+			.and_then(|strings| strings.get(&kind.key(id), locale))
+			.or_else(|| self.default_name(kind, id))
+			.unwrap_or(id)
+	}
+
+	/// Returns the name of an entity without localisation, or `None` if there
+	/// is no such entity.
+	pub(crate) fn default_name(
+		&self,
+		kind: StringKind,
+		id: &str,
+	) -> Option<&str> {
+		match kind {
+			StringKind::Profile => self
+				.profiles
+				.iter()
+				.find(|profile| profile.id == id)
+				.map(|profile| profile.name.as_str()),
+			StringKind::Preset => self
+				.profiles
+				.iter()
+				.flat_map(|profile| &profile.presets)
+				.find(|preset| preset.name == id)
+				.map(|preset| preset.name.as_str()),
+			StringKind::Node => self
+				.nodes
+				.iter()
+				.find(|node| node.id == id)
+				.map(|node| node.id.as_str()),
+			StringKind::HoldingPoint => self
+				.holding_points
+				.iter()
+				.find(|holding_point| holding_point.id == id)
+				.map(|holding_point| holding_point.label.as_str()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::fixture::aerodrome;
+
+	/// Returns an aerodrome with strings in French and German, and two for
+	/// entities which do not exist.
+	fn two_locales() -> Aerodrome {
+		let mut aerodrome = aerodrome(2, Vec::new());
+		aerodrome.holding_points.push(HoldingPoint {
+			id: "A1".into(),
+			label: "Alpha 1".into(),
+			location: HoldingPointLocation::Nodes(0.into(), 1.into()),
+			position: None,
+		});
+		aerodrome.profiles[0].presets.push(Preset {
+			name: "Open".into(),
+			includes: Vec::new(),
+			nodes: Vec::new(),
+			blocks: Vec::new(),
+		});
+
+		let strings = [
+			(
+				"profile.default",
+				&[("fr", "Par défaut"), ("de", "Standard")][..],
+			),
+			("preset.Open", &[("fr", "Ouvert")]),
+			("node.N0", &[("fr", "Barre 0"), ("fr-CA", "Barre zéro")]),
+			("holding_point.A1", &[("de", "Alfa 1")]),
+			("node.N9", &[("fr", "Barre 9")]),
+			("runway.27", &[("de", "Piste 27")]),
+		];
+		aerodrome.strings = Some(LocaleTable {
+			strings: (strings.into_iter())
+				.map(|(key, strings)| {
+					let strings = (strings.iter())
+						.map(|(locale, string)| (locale.to_string(), string.to_string()))
+						.collect();
+					(key.into(), strings)
+				})
+				.collect(),
+		});
+		aerodrome
+	}
+
+	#[test]
+	fn names_fall_back_to_the_language_then_the_default() {
+		let aerodrome = two_locales();
+		let name = |kind, id, locale| aerodrome.localized_name(kind, id, locale);
+
+		assert_eq!(name(StringKind::Profile, "default", "fr"), "Par défaut");
+		assert_eq!(name(StringKind::Profile, "default", "de"), "Standard");
+		assert_eq!(name(StringKind::Profile, "default", "fr-CA"), "Par défaut");
+		assert_eq!(name(StringKind::Profile, "default", "en"), "Default");
+		assert_eq!(name(StringKind::Profile, "default", ""), "Default");
+
+		// the locale is preferred to its language
+		assert_eq!(name(StringKind::Node, "N0", "fr-CA"), "Barre zéro");
+		assert_eq!(name(StringKind::Node, "N0", "fr_BE"), "Barre 0");
+		assert_eq!(name(StringKind::Node, "N1", "fr"), "N1");
+
+		assert_eq!(name(StringKind::Preset, "Open", "fr"), "Ouvert");
+		assert_eq!(name(StringKind::Preset, "Open", "de"), "Open");
+		assert_eq!(name(StringKind::HoldingPoint, "A1", "de"), "Alfa 1");
+		assert_eq!(name(StringKind::HoldingPoint, "A1", "fr"), "Alpha 1");
+		assert_eq!(name(StringKind::HoldingPoint, "B1", "fr"), "B1");
+
+		let strings = aerodrome.strings.as_ref().unwrap();
+		assert_eq!(Vec::from_iter(strings.locales()), ["de", "fr", "fr-CA"]);
+	}
+
+	#[test]
+	fn strings_of_missing_entities_dangle() {
+		let issues = (two_locales().validate().into_iter())
+			.filter(|issue| issue.code() == "dangling-string")
+			.map(|issue| (issue.severity(), issue.location))
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			issues,
+			[
+				(Severity::Warning, "strings[\"node.N9\"]".into()),
+				(Severity::Warning, "strings[\"runway.27\"]".into()),
+			]
+		);
+
+		assert_eq!(
+			StringKind::parse_key("holding_point.A1"),
+			Some((StringKind::HoldingPoint, "A1")),
+		);
+		assert_eq!(StringKind::parse_key("runway.27"), None);
+	}
+
+	#[test]
+	fn strings_survive_encoding() {
+		let aerodrome = two_locales();
+		let decoded = Aerodrome::decode(&aerodrome.encode().unwrap()).unwrap();
+		assert_eq!(decoded.strings, aerodrome.strings);
+
+		let mut without = two_locales();
+		without.strings = None;
+		let decoded = Aerodrome::decode(&without.encode().unwrap()).unwrap();
+		assert_eq!(decoded.strings, None);
+	}
+}
@@ -0,0 +1,457 @@
+use crate::*;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// An error from taking a subset of an aerodrome.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubsetError {
+	NoProfiles,
+	UnknownProfile(String),
+}
+
+impl Display for SubsetError {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		match self {
+			Self::NoProfiles => write!(f, "no profiles to keep"),
+			Self::UnknownProfile(id) => write!(f, "unknown profile {id}"),
+		}
+	}
+}
+
+impl Error for SubsetError {}
+
+impl Aerodrome {
+	/// Returns a copy of the aerodrome with only the profiles with the ids, in
+	/// their original order, along with their presets.
+	///
+	/// With `prune_fixed`, nodes and edges which are fixed in every profile
+	/// kept are also removed, unless anything else refers to them: an element,
+	/// a block, a holding point, a kept preset or condition, or a node kept in
+	/// the same hierarchy, whose routes may take any of its descendants. Refs
+	/// and the displays of the maps are rebased to match, and widgets for
+	/// removed entities dropped. Blocks have no fixed condition, so are always
+	/// kept.
+	pub fn subset(
+		&self,
+		profile_ids: &[&str],
+		prune_fixed: bool,
+	) -> Result<Aerodrome, SubsetError> {
+		if profile_ids.is_empty() {
+			return Err(SubsetError::NoProfiles)
+		}
+
+		if let Some(id) = (profile_ids.iter())
+			.find(|id| !self.profiles.iter().any(|profile| profile.id == **id))
+		{
+			return Err(SubsetError::UnknownProfile(id.to_string()))
+		}
+
+		let profiles = (self.profiles.iter())
+			.filter(|profile| profile_ids.contains(&profile.id.as_str()))
+			.collect::<Vec<_>>();
+
+		let (nodes, edges) = if prune_fixed {
+			self.kept_entities(&profiles)
+		} else {
+			(vec![true; self.nodes.len()], vec![true; self.edges.len()])
+		};
+
+		let renumber = Renumber {
+			nodes: renumber(&nodes),
+			edges: renumber(&edges),
+		};
+		let rebase = Rebase {
+			offset: 0,
+			nodes: kept(&nodes),
+			edges: kept(&edges),
+			blocks: (0..self.blocks.len()).map(Some).collect(),
+			holding_points: (0..self.holding_points.len()).map(Some).collect(),
+		};
+
+		let mut subset = Aerodrome {
+			icao: self.icao.clone(),
+			elements: (self.elements.iter())
+				.map(|element| renumber.element(element))
+				.collect(),
+			nodes: (self.nodes.iter().zip(&nodes))
+				.filter(|(_, kept)| **kept)
+				.map(|(node, _)| Node {
+					parent: node.parent.map(|parent| renumber.node(parent)),
+					..node.clone()
+				})
+				.collect(),
+			edges: (self.edges.iter().zip(&edges))
+				.filter(|(_, kept)| **kept)
+				.map(|(edge, _)| edge.clone())
+				.collect(),
+			blocks: (self.blocks.iter())
+				.map(|block| renumber.block(block))
+				.collect(),
+			holding_points: (self.holding_points.iter())
+				.map(|holding_point| renumber.holding_point(holding_point))
+				.collect(),
+			profiles: (profiles.iter())
+				.map(|profile| renumber.profile(profile, &nodes, &edges))
+				.collect(),
+			strings: self.strings.clone(),
+			geo_map: (self.geo_map.clone()).map(|geo_map| geo_map.rebase(&rebase)),
+			maps: (self.maps.iter())
+				.map(|map| map.clone().rebase(&rebase))
+				.collect(),
+			styles: self.styles.clone(),
+		};
+
+		// strings of removed profiles, presets and nodes would dangle
+		if let Some(mut strings) = subset.strings.take() {
+			let known = |aerodrome: &Aerodrome, key: &str| {
+				StringKind::parse_key(key)
+					.is_some_and(|(kind, id)| aerodrome.default_name(kind, id).is_some())
+			};
+
+			let removed = (strings.strings.keys())
+				.filter(|key| known(self, key) && !known(&subset, key))
+				.cloned()
+				.collect::<Vec<_>>();
+			for key in removed {
+				strings.strings.remove(&key);
+			}
+
+			subset.strings = Some(strings);
+		}
+
+		Ok(subset)
+	}
+
+	/// Returns whether each node and edge is kept when pruning to the
+	/// profiles.
+	fn kept_entities(&self, profiles: &[&Profile]) -> (Vec<bool>, Vec<bool>) {
+		let mut nodes = HashSet::new();
+		let mut edges = HashSet::new();
+
+		for (i, _) in self.nodes.iter().enumerate() {
+			let fixed = profiles.iter().all(|profile| {
+				matches!(profile.nodes.get(i), Some(NodeCondition::Fixed { .. }))
+			});
+			if !fixed {
+				nodes.insert(i);
+			}
+		}
+
+		for (i, _) in self.edges.iter().enumerate() {
+			let fixed = profiles.iter().all(|profile| {
+				matches!(profile.edges.get(i), Some(EdgeCondition::Fixed { .. }))
+			});
+			if !fixed {
+				edges.insert(i);
+			}
+		}
+
+		for element in &self.elements {
+			match &element.condition {
+				ElementCondition::Fixed(_) => (),
+				ElementCondition::Node(node) => {
+					nodes.insert(node.0);
+				},
+				ElementCondition::Edge(edge) => {
+					edges.insert(edge.0);
+				},
+				ElementCondition::Expression(expression) => {
+					expression_refs(expression, &mut nodes, &mut edges);
+				},
+			}
+		}
+
+		for block in &self.blocks {
+			nodes.extend(block.nodes.iter().map(|node| node.0));
+			edges.extend(block.edges.iter().map(|edge| edge.0));
+			for route in &block.non_routes {
+				nodes.extend([route.from.0, route.to.0]);
+			}
+		}
+
+		for holding_point in &self.holding_points {
+			match holding_point.location {
+				HoldingPointLocation::Edge(edge) => {
+					edges.insert(edge.0);
+				},
+				HoldingPointLocation::Nodes(a, b) => nodes.extend([a.0, b.0]),
+			}
+		}
+
+		for profile in profiles {
+			for condition in &profile.edges {
+				match condition {
+					EdgeCondition::Fixed { .. } => (),
+					EdgeCondition::Direct { nodes: expression } => {
+						for conjunction in &expression.disjunction {
+							let refs =
+								conjunction.positive.iter().chain(&conjunction.negative);
+							nodes.extend(refs.map(|node| node.0));
+						}
+					},
+					EdgeCondition::Router { routes, .. } => {
+						for route in routes {
+							nodes.extend([route.from.0, route.to.0]);
+						}
+					},
+					EdgeCondition::Expression { expression } => {
+						expression_refs(expression, &mut nodes, &mut edges);
+					},
+				}
+			}
+
+			for preset in &profile.presets {
+				nodes.extend(preset.nodes.iter().map(|(node, _)| node.0));
+				for (_, state) in &preset.blocks {
+					if let BlockState::Route((from, to)) = state {
+						nodes.extend([from.0, to.0]);
+					}
+				}
+			}
+		}
+
+		// the ancestors of kept nodes are needed for their hierarchy
+		for i in nodes.clone() {
+			let mut node = Ref::from(i);
+			while let Some(parent) = self.nodes.get(node.0).and_then(|n| n.parent) {
+				if !nodes.insert(parent.0) {
+					break
+				}
+				node = parent;
+			}
+		}
+
+		// as are the rest of their hierarchies, which routes through them take
+		let hierarchies = (0..self.nodes.len())
+			.filter(|i| nodes.contains(&self.root_node((*i).into()).0))
+			.collect::<Vec<_>>();
+		nodes.extend(hierarchies);
+
+		let kept = |kept: HashSet<usize>, len: usize| {
+			(0..len).map(|i| kept.contains(&i)).collect()
+		};
+
+		(kept(nodes, self.nodes.len()), kept(edges, self.edges.len()))
+	}
+}
+
+/// Adds the nodes and edges referred to by an expression.
+fn expression_refs(
+	expression: &ConditionExpression,
+	nodes: &mut HashSet<usize>,
+	edges: &mut HashSet<usize>,
+) {
+	for term in expression.terms() {
+		match term {
+			ConditionTerm::Node(node) => {
+				nodes.insert(node.0);
+			},
+			ConditionTerm::Edge(edge) => {
+				edges.insert(edge.0);
+			},
+			ConditionTerm::Block(_, BlockPredicate::Route((from, to))) => {
+				nodes.extend([from.0, to.0]);
+			},
+			ConditionTerm::Block(..) => (),
+		}
+	}
+}
+
+/// Returns the old index of each entity kept, by its new index.
+fn kept(kept: &[bool]) -> Vec<Option<usize>> {
+	(kept.iter().enumerate())
+		.filter(|(_, kept)| **kept)
+		.map(|(i, _)| Some(i))
+		.collect()
+}
+
+/// Returns the new index of each entity, by its old index, or `None` for
+/// those removed.
+fn renumber(kept: &[bool]) -> Vec<Option<usize>> {
+	let mut next = 0;
+	(kept.iter())
+		.map(|kept| {
+			kept.then(|| {
+				next += 1;
+				next - 1
+			})
+		})
+		.collect()
+}
+
+/// The new indices of the nodes and edges of a subset, by their old.
+///
+/// Kept entities only refer to kept nodes and edges, so refs to removed ones,
+/// or out of bounds, are left as they are.
+struct Renumber {
+	nodes: Vec<Option<usize>>,
+	edges: Vec<Option<usize>>,
+}
+
+impl Renumber {
+	fn node(&self, node: Ref<Node>) -> Ref<Node> {
+		self
+			.nodes
+			.get(node.0)
+			.copied()
+			.flatten()
+			.unwrap_or(node.0)
+			.into()
+	}
+
+	fn edge(&self, edge: Ref<Edge>) -> Ref<Edge> {
+		self
+			.edges
+			.get(edge.0)
+			.copied()
+			.flatten()
+			.unwrap_or(edge.0)
+			.into()
+	}
+
+	fn nodes(&self, nodes: &[Ref<Node>]) -> Vec<Ref<Node>> {
+		nodes.iter().map(|node| self.node(*node)).collect()
+	}
+
+	fn route(&self, route: &BlockRoute) -> BlockRoute {
+		BlockRoute {
+			from: self.node(route.from),
+			to: self.node(route.to),
+		}
+	}
+
+	fn element(&self, element: &Element) -> Element {
+		let condition = match &element.condition {
+			ElementCondition::Fixed(state) => ElementCondition::Fixed(*state),
+			ElementCondition::Node(node) => ElementCondition::Node(self.node(*node)),
+			ElementCondition::Edge(edge) => ElementCondition::Edge(self.edge(*edge)),
+			ElementCondition::Expression(expression) => {
+				ElementCondition::Expression(self.expression(expression))
+			},
+		};
+
+		Element {
+			id: element.id.clone(),
+			condition,
+		}
+	}
+
+	fn block(&self, block: &Block) -> Block {
+		Block {
+			nodes: self.nodes(&block.nodes),
+			edges: block.edges.iter().map(|edge| self.edge(*edge)).collect(),
+			non_routes: block.non_routes.iter().map(|r| self.route(r)).collect(),
+			..block.clone()
+		}
+	}
+
+	fn holding_point(&self, holding_point: &HoldingPoint) -> HoldingPoint {
+		let location = match holding_point.location {
+			HoldingPointLocation::Edge(edge) => {
+				HoldingPointLocation::Edge(self.edge(edge))
+			},
+			HoldingPointLocation::Nodes(a, b) => {
+				HoldingPointLocation::Nodes(self.node(a), self.node(b))
+			},
+		};
+
+		HoldingPoint {
+			location,
+			..holding_point.clone()
+		}
+	}
+
+	fn profile(
+		&self,
+		profile: &Profile,
+		nodes: &[bool],
+		edges: &[bool],
+	) -> Profile {
+		Profile {
+			id: profile.id.clone(),
+			name: profile.name.clone(),
+			tags: profile.tags.clone(),
+			nodes: (profile.nodes.iter().zip(nodes))
+				.filter(|(_, kept)| **kept)
+				.map(|(condition, _)| *condition)
+				.collect(),
+			edges: (profile.edges.iter().zip(edges))
+				.filter(|(_, kept)| **kept)
+				.map(|(condition, _)| self.edge_condition(condition))
+				.collect(),
+			blocks: profile.blocks.clone(),
+			presets: (profile.presets.iter())
+				.map(|preset| self.preset(preset))
+				.collect(),
+		}
+	}
+
+	fn edge_condition(&self, condition: &EdgeCondition) -> EdgeCondition {
+		match condition {
+			EdgeCondition::Fixed { state } => EdgeCondition::Fixed { state: *state },
+			EdgeCondition::Direct { nodes } => EdgeCondition::Direct {
+				nodes: NodeExpression {
+					disjunction: (nodes.disjunction.iter())
+						.map(|conjunction| NodeConjunction {
+							positive: self.nodes(&conjunction.positive),
+							negative: self.nodes(&conjunction.negative),
+						})
+						.collect(),
+				},
+			},
+			EdgeCondition::Router { block, routes } => EdgeCondition::Router {
+				block: *block,
+				routes: routes.iter().map(|route| self.route(route)).collect(),
+			},
+			EdgeCondition::Expression { expression } => EdgeCondition::Expression {
+				expression: self.expression(expression),
+			},
+		}
+	}
+
+	fn expression(
+		&self,
+		expression: &ConditionExpression,
+	) -> ConditionExpression {
+		let term = |term: &ConditionTerm| match *term {
+			ConditionTerm::Node(node) => ConditionTerm::Node(self.node(node)),
+			ConditionTerm::Edge(edge) => ConditionTerm::Edge(self.edge(edge)),
+			ConditionTerm::Block(block, BlockPredicate::Route((from, to))) => {
+				let route = (self.node(from), self.node(to));
+				ConditionTerm::Block(block, BlockPredicate::Route(route))
+			},
+			term => term,
+		};
+
+		ConditionExpression {
+			disjunction: (expression.disjunction.iter())
+				.map(|conjunction| ConditionConjunction {
+					positive: conjunction.positive.iter().map(term).collect(),
+					negative: conjunction.negative.iter().map(term).collect(),
+				})
+				.collect(),
+		}
+	}
+
+	fn preset(&self, preset: &Preset) -> Preset {
+		let block_state = |state: &BlockState| match state {
+			BlockState::Route((from, to)) => {
+				BlockState::Route((self.node(*from), self.node(*to)))
+			},
+			state => *state,
+		};
+
+		Preset {
+			name: preset.name.clone(),
+			includes: preset.includes.clone(),
+			nodes: (preset.nodes.iter())
+				.map(|(node, state)| (self.node(*node), *state))
+				.collect(),
+			blocks: (preset.blocks.iter())
+				.map(|(block, state)| (*block, block_state(state)))
+				.collect(),
+		}
+	}
+}
@@ -0,0 +1,864 @@
+use super::*;
+
+use std::fmt::Write as _;
+
+/// A synthetic aerodrome of square blocks laid out in a grid.
+///
+/// Router nodes sit on each border between blocks in the same row, and between
+/// the first blocks of adjacent rows, so that exactly one route joins any two
+/// nodes. Unlike the arbitrary generators, the layout is regular and fully
+/// routable, so it is suited to measuring how work scales with its size.
+#[derive(Clone, Copy, Debug)]
+pub struct Grid {
+	/// blocks along each side, at least two
+	pub size: usize,
+	/// child nodes of each router node
+	pub children: usize,
+	/// points in each path drawn on the geo map
+	pub points: usize,
+}
+
+impl Default for Grid {
+	fn default() -> Self {
+		Self {
+			size: 8,
+			children: 0,
+			points: 8,
+		}
+	}
+}
+
+/// spacing between blocks in degrees
+const SPACING: f32 = 0.001;
+
+/// Returns a polygon of `points` points about the centre, with a radius of
+/// `scale` times the spacing between blocks.
+fn circle(centre: Geo, scale: f32, points: usize) -> Vec<GeoPoint> {
+	(0..points)
+		.map(|i| {
+			let angle = i as f32 / points as f32 * std::f32::consts::TAU;
+			GeoPoint {
+				geo: Geo {
+					lat: centre.lat + angle.sin() * scale * SPACING,
+					lon: centre.lon + angle.cos() * scale * SPACING,
+				},
+				offset: Point::default(),
+			}
+		})
+		.collect()
+}
+
+/// Returns the styles of synthetic maps, being red and then green lines.
+fn styles() -> Vec<Style> {
+	let style = |color: Color| Style {
+		stroke_style: StrokeStyle::Dash(0),
+		stroke_width: 2.0.into(),
+		stroke_cap: StrokeCap(0),
+		stroke_join: StrokeJoin(0),
+		stroke_color: color,
+		fill_style: FillStyle::None,
+		fill_color: color,
+		blink: None,
+	};
+
+	vec![
+		style(Color {
+			r: 0xff,
+			g: 0,
+			b: 0,
+			a: 0xff,
+		}),
+		style(Color {
+			r: 0,
+			g: 0xff,
+			b: 0,
+			a: 0xff,
+		}),
+	]
+}
+
+impl Grid {
+	/// Returns the number of router nodes, which come before any children.
+	pub fn parents(&self) -> usize {
+		(self.size + 1) * (self.size - 1)
+	}
+
+	/// Returns router nodes near the ends of the first and last rows, between
+	/// which the route runs along the first row, down the first column and back
+	/// along the last row.
+	pub fn corners(&self) -> (usize, usize) {
+		let end = self.size - 2;
+		(self.horizontal(end, 0), self.horizontal(end, self.size - 1))
+	}
+
+	fn horizontal(&self, x: usize, y: usize) -> usize {
+		y * (self.size - 1) + x
+	}
+
+	fn vertical(&self, y: usize) -> usize {
+		self.size * (self.size - 1) + y
+	}
+
+	fn children_of(&self, node: usize) -> impl Iterator<Item = usize> {
+		let first = self.parents() + node * self.children;
+		first..first + self.children
+	}
+
+	fn block_nodes(&self, x: usize, y: usize) -> Vec<usize> {
+		let mut nodes = Vec::new();
+		if x > 0 {
+			nodes.push(self.horizontal(x - 1, y));
+		}
+		if x + 1 < self.size {
+			nodes.push(self.horizontal(x, y));
+		}
+		if x == 0 && y > 0 {
+			nodes.push(self.vertical(y - 1));
+		}
+		if x == 0 && y + 1 < self.size {
+			nodes.push(self.vertical(y));
+		}
+		nodes
+	}
+
+	fn blocks(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+		(0..self.size).flat_map(|y| (0..self.size).map(move |x| (x, y)))
+	}
+
+	/// Returns the position of each parent node, at the middle of its border.
+	fn node_positions(&self) -> Vec<Geo> {
+		let at = |x: f32, y: f32| Geo {
+			lat: 51.0 + y * SPACING,
+			lon: -0.5 + x * SPACING,
+		};
+
+		let mut positions = Vec::with_capacity(self.parents());
+		for y in 0..self.size {
+			for x in 0..self.size - 1 {
+				positions.push(at(x as f32 + 1.0, y as f32 + 0.5));
+			}
+		}
+		for y in 0..self.size - 1 {
+			positions.push(at(0.5, y as f32 + 1.0));
+		}
+		positions
+	}
+
+	/// Returns the pairs of parent nodes through each block, with each pair of
+	/// nodes which may be routed between.
+	fn routes(&self) -> Vec<(usize, Vec<BlockRoute>)> {
+		let mut routes = Vec::new();
+		for (i, (x, y)) in self.blocks().enumerate() {
+			let nodes = self.block_nodes(x, y);
+			for (j, a) in nodes.iter().enumerate() {
+				for b in &nodes[j + 1..] {
+					let mut pairs = Vec::new();
+					for a in self.leaves(*a) {
+						for b in self.leaves(*b) {
+							pairs.push(BlockRoute {
+								from: a.into(),
+								to: b.into(),
+							});
+							pairs.push(BlockRoute {
+								from: b.into(),
+								to: a.into(),
+							});
+						}
+					}
+					routes.push((i, pairs));
+				}
+			}
+		}
+		routes
+	}
+
+	/// Returns the nodes lit for a parent node, being its children if any.
+	fn leaves(&self, node: usize) -> Vec<usize> {
+		if self.children == 0 {
+			vec![node]
+		} else {
+			self.children_of(node).collect()
+		}
+	}
+
+	fn path(&self, centre: Geo, scale: f32) -> Vec<GeoPoint> {
+		circle(centre, scale, self.points)
+	}
+
+	/// Builds the aerodrome, with one profile routing every node and a geo map
+	/// drawing each node, edge and block.
+	pub fn aerodrome(&self) -> Aerodrome {
+		assert!(self.size >= 2, "grid must have at least two blocks a side");
+
+		let parents = self.parents();
+		let positions = self.node_positions();
+		let routes = self.routes();
+
+		let mut nodes = (0..parents)
+			.map(|i| Node {
+				id: format!("N{i}"),
+				scratchpad: None,
+				parent: None,
+				input_order: None,
+			})
+			.collect::<Vec<_>>();
+		for parent in 0..parents {
+			for (k, _) in self.children_of(parent).enumerate() {
+				nodes.push(Node {
+					id: format!("N{parent}.{k}"),
+					scratchpad: None,
+					parent: Some(parent.into()),
+					input_order: None,
+				});
+			}
+		}
+
+		let blocks = self
+			.blocks()
+			.map(|(x, y)| Block {
+				id: format!("B{x}.{y}"),
+				nodes: self.block_nodes(x, y).into_iter().map(Ref::from).collect(),
+				edges: Vec::new(),
+				non_routes: Vec::new(),
+				stands: Vec::new(),
+				input_order: None,
+			})
+			.collect::<Vec<_>>();
+
+		let edges = (0..routes.len())
+			.map(|i| Edge {
+				id: format!("X{i}"),
+				name: None,
+			})
+			.collect::<Vec<_>>();
+
+		let elements = (0..parents)
+			.map(|i| Element {
+				id: format!("S{i}"),
+				condition: ElementCondition::Node(i.into()),
+			})
+			.chain((0..edges.len()).map(|i| Element {
+				id: format!("L{i}"),
+				condition: ElementCondition::Edge(i.into()),
+			}))
+			.collect();
+
+		let profile = Profile {
+			id: "default".into(),
+			name: "Default".into(),
+			tags: Vec::new(),
+			nodes: vec![NodeCondition::Router { sticky: false }; nodes.len()],
+			edges: routes
+				.iter()
+				.map(|(block, routes)| EdgeCondition::Router {
+					block: (*block).into(),
+					routes: routes.clone(),
+				})
+				.collect(),
+			blocks: vec![
+				BlockCondition {
+					reset: ResetCondition::None,
+				};
+				blocks.len()
+			],
+			presets: Vec::new(),
+		};
+
+		let position = |node: usize| {
+			positions[nodes[node].parent.map_or(node, |parent| parent.0)]
+		};
+
+		let geo_map = GeoMap {
+			nodes: (0..nodes.len())
+				.map(|i| NodeDisplay {
+					off: vec![Path {
+						points: self.path(position(i), 0.1),
+						style: 0.into(),
+						level: None,
+					}],
+					on: vec![Path {
+						points: self.path(position(i), 0.1),
+						style: 1.into(),
+						level: None,
+					}],
+					selected: Vec::new(),
+					target: Target {
+						polygons: vec![self.path(position(i), 0.2)],
+						hover_style: None,
+						active_style: None,
+						level: None,
+					},
+					tooltip: None,
+				})
+				.collect(),
+			edges: routes
+				.iter()
+				.map(|(_, routes)| {
+					let from = position(routes[0].from.0);
+					let to = position(routes[0].to.0);
+					let centre = Geo {
+						lat: (from.lat + to.lat) / 2.0,
+						lon: (from.lon + to.lon) / 2.0,
+					};
+
+					EdgeDisplay {
+						off: Vec::new(),
+						on: vec![Path {
+							points: self.path(centre, 0.3),
+							style: 1.into(),
+							level: None,
+						}],
+						pending: Vec::new(),
+					}
+				})
+				.collect(),
+			blocks: self
+				.blocks()
+				.map(|(x, y)| BlockDisplay {
+					target: Target {
+						polygons: vec![self.path(
+							Geo {
+								lat: 51.0 + (y as f32 + 0.5) * SPACING,
+								lon: -0.5 + (x as f32 + 0.5) * SPACING,
+							},
+							0.4,
+						)],
+						hover_style: None,
+						active_style: None,
+						level: None,
+					},
+					tooltip: None,
+				})
+				.collect(),
+			widgets: Vec::new(),
+		};
+
+		Aerodrome {
+			icao: "ZZZZ".into(),
+			elements,
+			nodes,
+			edges,
+			blocks,
+			holding_points: Vec::new(),
+			profiles: vec![profile],
+			strings: None,
+			geo_map: Some(geo_map),
+			maps: Vec::new(),
+			styles: styles(),
+		}
+	}
+
+	/// Writes the geo map of the aerodrome as topsky maps text.
+	pub fn topsky(&self) -> String {
+		fn path(text: &mut String, command: &str, points: &[GeoPoint]) {
+			for GeoPoint { geo, .. } in points {
+				writeln!(text, "COORD:{}:{}", geo.lat, geo.lon).unwrap();
+			}
+			writeln!(text, "{command}").unwrap();
+		}
+
+		let aerodrome = self.aerodrome();
+		let geo_map = aerodrome.geo_map.as_ref().unwrap();
+
+		let mut text = String::new();
+		text.push_str("COLORDEF:off:255:0:0\n");
+		text.push_str("COLORDEF:on:0:255:0\n");
+		text.push_str("GEO\n");
+		text.push_str("STYLE:solid:2\n");
+
+		for (node, display) in aerodrome.nodes.iter().zip(&geo_map.nodes) {
+			for (group, paths) in [("OFF", &display.off), ("ON", &display.on)] {
+				writeln!(text, "NODE:{}:{group}", node.id).unwrap();
+				writeln!(text, "COLOR:{}", group.to_lowercase()).unwrap();
+				for p in paths {
+					path(&mut text, "COORDLINE", &p.points);
+				}
+			}
+
+			writeln!(text, "NODE:{}:TARGET", node.id).unwrap();
+			for polygon in &display.target.polygons {
+				path(&mut text, "COORDTARGET", polygon);
+			}
+		}
+
+		text.push_str("COLOR:on\n");
+		for (edge, display) in aerodrome.edges.iter().zip(&geo_map.edges) {
+			writeln!(text, "EDGE:{}:ON", edge.id).unwrap();
+			for p in &display.on {
+				path(&mut text, "COORDLINE", &p.points);
+			}
+		}
+
+		for (block, display) in aerodrome.blocks.iter().zip(&geo_map.blocks) {
+			writeln!(text, "BLOCK:{}:TARGET", block.id).unwrap();
+			for polygon in &display.target.polygons {
+				path(&mut text, "COORDTARGET", polygon);
+			}
+		}
+
+		text
+	}
+}
+
+/// A small deterministic random number generator (SplitMix64), so that
+/// synthetic data is the same on every run and platform.
+#[derive(Clone, Debug)]
+pub struct SyntheticRng(u64);
+
+impl SyntheticRng {
+	pub fn new(seed: u64) -> Self {
+		Self(seed)
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = self.0;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	/// Returns an integer in `0..n`, which must not be empty.
+	pub fn below(&mut self, n: usize) -> usize {
+		(self.next_u64() % n as u64) as usize
+	}
+
+	/// Returns true with probability `p`.
+	pub fn chance(&mut self, p: f32) -> bool {
+		((self.next_u64() >> 40) as f32 / (1u64 << 24) as f32) < p
+	}
+}
+
+/// A synthetic aerodrome of blocks in a lattice, with a mix of conditions,
+/// for stress and soak testing.
+///
+/// Router nodes sit on every border between adjacent blocks, so that many
+/// routes join most pairs of nodes. The first profile routes every node but
+/// the transparent ones, and later profiles mix fixed, direct and router
+/// conditions. Everything chosen at random is drawn from the seed, so the same
+/// parameters always build the same aerodrome.
+#[derive(Clone, Copy, Debug)]
+pub struct Lattice {
+	/// blocks in each row
+	pub columns: usize,
+	/// blocks in each column
+	pub rows: usize,
+	/// child nodes of each router node
+	pub children: usize,
+	/// fraction of router nodes fixed off in every profile, which routes pass
+	/// through
+	pub transparent: f32,
+	/// profiles, at least one
+	pub profiles: usize,
+	/// presets of each profile
+	pub presets: usize,
+	/// points in each path drawn on the geo map, or no geo map if none
+	pub points: Option<usize>,
+	pub seed: u64,
+}
+
+impl Default for Lattice {
+	fn default() -> Self {
+		Self {
+			columns: 8,
+			rows: 8,
+			children: 1,
+			transparent: 0.1,
+			profiles: 3,
+			presets: 4,
+			points: Some(8),
+			seed: 0,
+		}
+	}
+}
+
+impl Lattice {
+	/// Returns the number of parent nodes, which come before any children.
+	pub fn parents(&self) -> usize {
+		self.rows * (self.columns - 1) + (self.rows - 1) * self.columns
+	}
+
+	/// Returns router nodes at opposite corners of the lattice, between which
+	/// there are many routes.
+	pub fn corners(&self) -> (usize, usize) {
+		(0, self.east(self.columns - 2, self.rows - 1))
+	}
+
+	/// Returns the node on the border east of block (x, y).
+	fn east(&self, x: usize, y: usize) -> usize {
+		y * (self.columns - 1) + x
+	}
+
+	/// Returns the node on the border north of block (x, y).
+	fn north(&self, x: usize, y: usize) -> usize {
+		self.rows * (self.columns - 1) + y * self.columns + x
+	}
+
+	fn block_nodes(&self, x: usize, y: usize) -> Vec<usize> {
+		let mut nodes = Vec::new();
+		if x > 0 {
+			nodes.push(self.east(x - 1, y));
+		}
+		if x + 1 < self.columns {
+			nodes.push(self.east(x, y));
+		}
+		if y > 0 {
+			nodes.push(self.north(x, y - 1));
+		}
+		if y + 1 < self.rows {
+			nodes.push(self.north(x, y));
+		}
+		nodes
+	}
+
+	fn blocks(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+		(0..self.rows).flat_map(|y| (0..self.columns).map(move |x| (x, y)))
+	}
+
+	fn leaves(&self, node: usize) -> Vec<usize> {
+		if self.children == 0 {
+			vec![node]
+		} else {
+			let first = self.parents() + node * self.children;
+			(first..first + self.children).collect()
+		}
+	}
+
+	/// Returns the position of each parent node, at the middle of its border.
+	fn node_positions(&self) -> Vec<Geo> {
+		let at = |x: f32, y: f32| Geo {
+			lat: 51.0 + y * SPACING,
+			lon: -0.5 + x * SPACING,
+		};
+
+		let mut positions = Vec::with_capacity(self.parents());
+		for y in 0..self.rows {
+			for x in 0..self.columns - 1 {
+				positions.push(at(x as f32 + 1.0, y as f32 + 0.5));
+			}
+		}
+		for y in 0..self.rows - 1 {
+			for x in 0..self.columns {
+				positions.push(at(x as f32 + 0.5, y as f32 + 1.0));
+			}
+		}
+		positions
+	}
+
+	/// Builds the aerodrome.
+	pub fn generate(&self) -> Aerodrome {
+		assert!(
+			self.columns >= 1 && self.rows >= 1 && self.columns * self.rows >= 2,
+			"lattice must have at least two blocks",
+		);
+		assert!(self.profiles >= 1, "lattice must have a profile");
+
+		let mut rng = SyntheticRng::new(self.seed);
+		let parents = self.parents();
+
+		let mut nodes = (0..parents)
+			.map(|i| Node {
+				id: format!("N{i}"),
+				scratchpad: None,
+				parent: None,
+				input_order: None,
+			})
+			.collect::<Vec<_>>();
+		for parent in 0..parents {
+			for k in 0..self.children {
+				nodes.push(Node {
+					id: format!("N{parent}.{k}"),
+					scratchpad: None,
+					parent: Some(parent.into()),
+					input_order: None,
+				});
+			}
+		}
+
+		let blocks = (self.blocks())
+			.map(|(x, y)| Block {
+				id: format!("B{x}.{y}"),
+				nodes: self.block_nodes(x, y).into_iter().map(Ref::from).collect(),
+				edges: Vec::new(),
+				non_routes: Vec::new(),
+				stands: Vec::new(),
+				input_order: None,
+			})
+			.collect::<Vec<_>>();
+
+		// an edge for each pair of nodes of each block
+		let mut pairs = Vec::new();
+		for (i, block) in blocks.iter().enumerate() {
+			for (j, a) in block.nodes.iter().enumerate() {
+				for b in &block.nodes[j + 1..] {
+					pairs.push((i, a.0, b.0));
+				}
+			}
+		}
+
+		let edges = (0..pairs.len())
+			.map(|i| Edge {
+				id: format!("X{i}"),
+				name: None,
+			})
+			.collect::<Vec<_>>();
+
+		let elements = (0..parents)
+			.map(|i| Element {
+				id: format!("S{i}"),
+				condition: ElementCondition::Node(i.into()),
+			})
+			.chain((0..edges.len()).map(|i| Element {
+				id: format!("L{i}"),
+				condition: ElementCondition::Edge(i.into()),
+			}))
+			.collect();
+
+		let transparent = (0..parents)
+			.map(|_| rng.chance(self.transparent))
+			.collect::<Vec<_>>();
+
+		let profiles = (0..self.profiles)
+			.map(|p| self.profile(p, &transparent, &pairs, &blocks, &mut rng))
+			.collect();
+
+		let geo_map = self
+			.points
+			.map(|points| self.geo_map(points, &nodes, &pairs));
+
+		Aerodrome {
+			icao: "ZZZZ".into(),
+			elements,
+			nodes,
+			edges,
+			blocks,
+			holding_points: Vec::new(),
+			profiles,
+			strings: None,
+			geo_map,
+			maps: Vec::new(),
+			styles: styles(),
+		}
+	}
+
+	fn profile(
+		&self,
+		p: usize,
+		transparent: &[bool],
+		pairs: &[(usize, usize, usize)],
+		blocks: &[Block],
+		rng: &mut SyntheticRng,
+	) -> Profile {
+		let reset = |rng: &mut SyntheticRng| {
+			if rng.chance(0.5) {
+				ResetCondition::None
+			} else {
+				ResetCondition::TimeSecs(30 + rng.below(270) as u32)
+			}
+		};
+
+		// the first profile routes everything, and later ones mix conditions
+		let mut parents = (0..self.parents())
+			.map(|i| {
+				if transparent[i] {
+					return NodeCondition::Fixed {
+						state: NodeState::Off,
+					}
+				}
+
+				match if p == 0 { 0 } else { rng.below(10) } {
+					0..=4 => NodeCondition::Router {
+						sticky: rng.chance(0.2),
+					},
+					5..=8 => NodeCondition::Direct { reset: reset(rng) },
+					_ => NodeCondition::Fixed {
+						state: NodeState::On,
+					},
+				}
+			})
+			.collect::<Vec<_>>();
+
+		// children share the conditions of their parents
+		let children = (0..self.parents())
+			.flat_map(|i| std::iter::repeat_n(parents[i], self.children))
+			.collect::<Vec<_>>();
+		let router = |i: usize, parents: &[NodeCondition]| {
+			matches!(parents[i], NodeCondition::Router { .. })
+		};
+
+		let edges = (pairs.iter())
+			.map(|(block, a, b)| {
+				if router(*a, &parents) || router(*b, &parents) {
+					let mut routes = Vec::new();
+					for a in self.leaves(*a) {
+						for b in self.leaves(*b) {
+							routes.push(BlockRoute {
+								from: a.into(),
+								to: b.into(),
+							});
+							routes.push(BlockRoute {
+								from: b.into(),
+								to: a.into(),
+							});
+						}
+					}
+
+					EdgeCondition::Router {
+						block: (*block).into(),
+						routes,
+					}
+				} else if rng.chance(0.1) {
+					EdgeCondition::Fixed {
+						state: EdgeState::Off,
+					}
+				} else {
+					// lit while either stop bar is dropped
+					EdgeCondition::Direct {
+						nodes: NodeExpression {
+							disjunction: [a, b]
+								.map(|node| NodeConjunction {
+									positive: Vec::new(),
+									negative: vec![(*node).into()],
+								})
+								.into(),
+						},
+					}
+				}
+			})
+			.collect();
+
+		let block_conditions = (0..blocks.len())
+			.map(|_| BlockCondition {
+				reset: if p == 0 {
+					ResetCondition::None
+				} else {
+					reset(rng)
+				},
+			})
+			.collect();
+
+		let presets = (0..self.presets)
+			.map(|k| {
+				let mut preset = Preset {
+					name: format!("Preset {k}"),
+					includes: Vec::new(),
+					nodes: Vec::new(),
+					blocks: Vec::new(),
+				};
+
+				if k > 0 && rng.chance(0.3) {
+					preset.includes.push(rng.below(k).into());
+				}
+
+				for (i, condition) in parents.iter().enumerate() {
+					if matches!(condition, NodeCondition::Direct { .. })
+						&& rng.chance(0.3)
+					{
+						let state = if rng.chance(0.5) {
+							NodeState::On
+						} else {
+							NodeState::Off
+						};
+						preset.nodes.push((i.into(), state));
+					}
+				}
+
+				for (i, block) in blocks.iter().enumerate() {
+					if !rng.chance(0.2) {
+						continue
+					}
+
+					let state = match rng.below(3) {
+						0 => BlockState::Clear,
+						1 => BlockState::Relax,
+						_ if block.nodes.len() >= 2 => {
+							let a = rng.below(block.nodes.len());
+							let b =
+								(a + 1 + rng.below(block.nodes.len() - 1)) % block.nodes.len();
+							BlockState::Route((block.nodes[a], block.nodes[b]))
+						},
+						_ => BlockState::Relax,
+					};
+					preset.blocks.push((i.into(), state));
+				}
+
+				preset
+			})
+			.collect();
+
+		parents.extend(children);
+
+		Profile {
+			id: format!("profile{p}"),
+			name: format!("Profile {p}"),
+			tags: Vec::new(),
+			nodes: parents,
+			edges,
+			blocks: block_conditions,
+			presets,
+		}
+	}
+
+	fn geo_map(
+		&self,
+		points: usize,
+		nodes: &[Node],
+		pairs: &[(usize, usize, usize)],
+	) -> GeoMap {
+		let positions = self.node_positions();
+		let position = |node: usize| {
+			positions[nodes[node].parent.map_or(node, |parent| parent.0)]
+		};
+		let path = |centre: Geo, scale: f32, style: usize| Path {
+			points: circle(centre, scale, points),
+			style: style.into(),
+			level: None,
+		};
+		let target = |centre: Geo, scale: f32| Target {
+			polygons: vec![circle(centre, scale, points)],
+			hover_style: None,
+			active_style: None,
+			level: None,
+		};
+
+		GeoMap {
+			nodes: (0..nodes.len())
+				.map(|i| NodeDisplay {
+					off: vec![path(position(i), 0.1, 0)],
+					on: vec![path(position(i), 0.1, 1)],
+					selected: Vec::new(),
+					target: target(position(i), 0.2),
+					tooltip: None,
+				})
+				.collect(),
+			edges: (pairs.iter())
+				.map(|(_, a, b)| {
+					let (a, b) = (positions[*a], positions[*b]);
+					let centre = Geo {
+						lat: (a.lat + b.lat) / 2.0,
+						lon: (a.lon + b.lon) / 2.0,
+					};
+
+					EdgeDisplay {
+						off: vec![path(centre, 0.3, 0)],
+						on: vec![path(centre, 0.3, 1)],
+						pending: Vec::new(),
+					}
+				})
+				.collect(),
+			blocks: (self.blocks())
+				.map(|(x, y)| BlockDisplay {
+					target: target(
+						Geo {
+							lat: 51.0 + (y as f32 + 0.5) * SPACING,
+							lon: -0.5 + (x as f32 + 0.5) * SPACING,
+						},
+						0.4,
+					),
+					tooltip: None,
+				})
+				.collect(),
+			widgets: Vec::new(),
+		}
+	}
+}
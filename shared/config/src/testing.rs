@@ -0,0 +1,773 @@
+//! Generators for random but structurally valid configs.
+//!
+//! Generated aerodromes keep every ref in range, size profile conditions and
+//! map displays to match the aerodrome, and never nest child nodes, so they
+//! pass validation without errors. The proptest strategies generate from a
+//! byte string, so shrinking only ever produces smaller valid values.
+
+use super::*;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use proptest::prelude::{any, BoxedStrategy, Strategy};
+
+/// most items of any one kind generated in a list
+const MAX_ITEMS: usize = 8;
+
+fn list<'a, T>(
+	u: &mut Unstructured<'a>,
+	mut item: impl FnMut(&mut Unstructured<'a>) -> Result<T>,
+) -> Result<Vec<T>> {
+	let len = u.int_in_range(0..=MAX_ITEMS)?;
+	(0..len).map(|_| item(u)).collect()
+}
+
+fn subset<T>(u: &mut Unstructured, from: &[usize]) -> Result<Vec<Ref<T>>> {
+	let mut refs = Vec::new();
+	for i in from {
+		if u.arbitrary()? {
+			refs.push((*i).into());
+		}
+	}
+
+	Ok(refs)
+}
+
+fn pick<T>(u: &mut Unstructured, len: usize) -> Result<Option<Ref<T>>> {
+	if len == 0 {
+		Ok(None)
+	} else {
+		Ok(Some(u.choose_index(len)?.into()))
+	}
+}
+
+fn coordinate(u: &mut Unstructured) -> Result<f32> {
+	Ok(u.int_in_range(-0x800..=0x800)? as f32 / 16.0)
+}
+
+/// Lengths of the lists which refs in maps point into.
+#[derive(Clone, Copy)]
+struct Counts {
+	nodes: usize,
+	edges: usize,
+	blocks: usize,
+	holding_points: usize,
+	styles: usize,
+}
+
+impl Counts {
+	fn arbitrary(u: &mut Unstructured) -> Result<Self> {
+		let nodes = u.int_in_range(0..=MAX_ITEMS)?;
+		Ok(Self {
+			nodes,
+			edges: u.int_in_range(0..=MAX_ITEMS)?,
+			blocks: u.int_in_range(0..=MAX_ITEMS)?,
+			// holding points are located by nodes if there are no edges
+			holding_points: if nodes > 0 {
+				u.int_in_range(0..=MAX_ITEMS)?
+			} else {
+				0
+			},
+			styles: u.int_in_range(0..=MAX_ITEMS)?,
+		})
+	}
+
+	fn paths<'a, T: Projectable + Arbitrary<'a>>(
+		self,
+		u: &mut Unstructured<'a>,
+	) -> Result<Vec<Path<T>>> {
+		if self.styles == 0 {
+			return Ok(Vec::new())
+		}
+
+		list(u, |u| {
+			Ok(Path {
+				points: list(u, T::arbitrary)?,
+				style: u.choose_index(self.styles)?.into(),
+				level: u.arbitrary()?,
+			})
+		})
+	}
+
+	fn target<'a, T: Projectable + Arbitrary<'a>>(
+		self,
+		u: &mut Unstructured<'a>,
+	) -> Result<Target<T>> {
+		Ok(Target {
+			polygons: list(u, |u| list(u, T::arbitrary))?,
+			hover_style: pick(u, self.styles)?,
+			active_style: pick(u, self.styles)?,
+			level: u.arbitrary()?,
+		})
+	}
+
+	fn nodes<'a, T: Projectable + Arbitrary<'a>>(
+		self,
+		u: &mut Unstructured<'a>,
+	) -> Result<Vec<NodeDisplay<T>>> {
+		(0..self.nodes)
+			.map(|_| {
+				Ok(NodeDisplay {
+					off: self.paths(u)?,
+					on: self.paths(u)?,
+					selected: self.paths(u)?,
+					target: self.target(u)?,
+					tooltip: u.arbitrary()?,
+				})
+			})
+			.collect()
+	}
+
+	fn edges<'a, T: Projectable + Arbitrary<'a>>(
+		self,
+		u: &mut Unstructured<'a>,
+	) -> Result<Vec<EdgeDisplay<T>>> {
+		(0..self.edges)
+			.map(|_| {
+				Ok(EdgeDisplay {
+					off: self.paths(u)?,
+					on: self.paths(u)?,
+					pending: self.paths(u)?,
+				})
+			})
+			.collect()
+	}
+
+	fn blocks<'a, T: Projectable + Arbitrary<'a>>(
+		self,
+		u: &mut Unstructured<'a>,
+	) -> Result<Vec<BlockDisplay<T>>> {
+		(0..self.blocks)
+			.map(|_| {
+				Ok(BlockDisplay {
+					target: self.target(u)?,
+					tooltip: u.arbitrary()?,
+				})
+			})
+			.collect()
+	}
+
+	fn widgets<'a, T: Projectable + Arbitrary<'a>>(
+		self,
+		u: &mut Unstructured<'a>,
+	) -> Result<Vec<Widget<T>>> {
+		let widgets = list(u, |u| {
+			if u.ratio(1, 4)? {
+				let Some(holding_point) = pick(u, self.holding_points)? else {
+					return Ok(None)
+				};
+
+				return Ok(Some(Widget::Label {
+					position: T::arbitrary(u)?,
+					size: u.int_in_range(1..=64)? as f32 / 4.0,
+					holding_point,
+				}))
+			}
+
+			let condition = if u.arbitrary()? {
+				pick(u, self.nodes)?.map(CountdownCondition::Node)
+			} else {
+				pick(u, self.blocks)?.map(CountdownCondition::Block)
+			};
+
+			if u.ratio(1, 3)? {
+				return Ok(Some(Widget::Text {
+					position: T::arbitrary(u)?,
+					size: u.int_in_range(1..=64)? as f32 / 4.0,
+					anchor: *u.choose(&[
+						TextAnchor::Centre,
+						TextAnchor::Left,
+						TextAnchor::Right,
+						TextAnchor::Top,
+						TextAnchor::Bottom,
+						TextAnchor::TopLeft,
+						TextAnchor::TopRight,
+						TextAnchor::BottomLeft,
+						TextAnchor::BottomRight,
+					])?,
+					text: u.arbitrary()?,
+					color: u.arbitrary()?,
+					condition,
+				}))
+			}
+
+			let Some(condition) = condition else {
+				return Ok(None)
+			};
+
+			Ok(Some(Widget::Countdown {
+				position: T::arbitrary(u)?,
+				size: u.int_in_range(1..=64)? as f32 / 4.0,
+				condition,
+				format: *u.choose(&[
+					CountdownFormat::MinutesSeconds,
+					CountdownFormat::Seconds,
+					CountdownFormat::Bar,
+				])?,
+				warn_below_secs: u.arbitrary()?,
+				normal_style: pick(u, self.styles)?,
+				warn_style: pick(u, self.styles)?,
+			}))
+		})?;
+
+		Ok(widgets.into_iter().flatten().collect())
+	}
+
+	fn geo_map<'a>(self, u: &mut Unstructured<'a>) -> Result<GeoMap> {
+		Ok(GeoMap {
+			nodes: self.nodes(u)?,
+			edges: self.edges(u)?,
+			blocks: self.blocks(u)?,
+			widgets: self.widgets(u)?,
+		})
+	}
+
+	fn map<'a>(self, u: &mut Unstructured<'a>) -> Result<Map> {
+		Ok(Map {
+			background: u.arbitrary()?,
+			base: self.paths(u)?,
+			nodes: self.nodes(u)?,
+			edges: self.edges(u)?,
+			blocks: self.blocks(u)?,
+			widgets: self.widgets(u)?,
+			views: list(u, View::arbitrary)?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Config {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			name: u.arbitrary()?,
+			version: u.arbitrary()?,
+			aerodromes: list(u, Aerodrome::arbitrary)?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Aerodrome {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let counts = Counts::arbitrary(u)?;
+
+		let icao = (0..4)
+			.map(|_| Ok(char::from(u.int_in_range(b'A'..=b'Z')?)))
+			.collect::<Result<_>>()?;
+
+		let mut nodes = Vec::<Node>::new();
+		for i in 0..counts.nodes {
+			// children may only have parents which are not children themselves
+			let parents = (0..i)
+				.filter(|j| nodes[*j].parent.is_none())
+				.collect::<Vec<_>>();
+
+			let parent = if !parents.is_empty() && u.ratio(1, 3)? {
+				Some(Ref::from(*u.choose(&parents)?))
+			} else {
+				None
+			};
+
+			nodes.push(Node {
+				id: format!("N{i}"),
+				scratchpad: u.arbitrary()?,
+				parent,
+				input_order: u.arbitrary()?,
+			});
+		}
+
+		let parents = (0..nodes.len())
+			.filter(|i| nodes[*i].parent.is_none())
+			.collect::<Vec<_>>();
+		let root = |node: usize| nodes[node].parent.map_or(node, |p| p.0);
+
+		let routes = |u: &mut Unstructured<'a>, block: &Block| {
+			let nodes = (0..nodes.len())
+				.filter(|i| block.nodes.contains(&root(*i).into()))
+				.collect::<Vec<_>>();
+
+			if nodes.is_empty() {
+				return Ok(Vec::new())
+			}
+
+			list(u, |u| {
+				Ok(BlockRoute {
+					from: (*u.choose(&nodes)?).into(),
+					to: (*u.choose(&nodes)?).into(),
+				})
+			})
+		};
+
+		let edge_ids = (0..counts.edges).collect::<Vec<_>>();
+		let mut blocks = Vec::new();
+		for i in 0..counts.blocks {
+			let mut block = Block {
+				id: format!("B{i}"),
+				nodes: subset(u, &parents)?,
+				edges: subset(u, &edge_ids)?,
+				non_routes: Vec::new(),
+				stands: list(u, |u| u.arbitrary())?,
+				input_order: u.arbitrary()?,
+			};
+
+			block.non_routes = routes(u, &block)?;
+			blocks.push(block);
+		}
+
+		// edge terms are only made for elements, as edge expressions may not
+		// refer to edges driven by expressions
+		let expression = |u: &mut Unstructured<'a>, edges: bool| {
+			let kinds = [
+				counts.nodes > 0,
+				edges && counts.edges > 0,
+				counts.blocks > 0,
+			]
+			.into_iter()
+			.enumerate()
+			.filter(|(_, present)| *present)
+			.map(|(kind, _)| kind)
+			.collect::<Vec<_>>();
+
+			let term = |u: &mut Unstructured<'a>| {
+				Ok(match u.choose(&kinds)? {
+					0 => ConditionTerm::Node(u.choose_index(counts.nodes)?.into()),
+					1 => ConditionTerm::Edge(u.choose_index(counts.edges)?.into()),
+					_ => {
+						let block = u.choose_index(counts.blocks)?;
+						let predicate = match u.int_in_range(0..=3)? {
+							0 => BlockPredicate::Clear,
+							1 => BlockPredicate::Relax,
+							2 => match routes(u, &blocks[block])?.first() {
+								Some(route) => BlockPredicate::Route((route.from, route.to)),
+								None => BlockPredicate::AnyRoute,
+							},
+							_ => BlockPredicate::AnyRoute,
+						};
+						ConditionTerm::Block(block.into(), predicate)
+					},
+				})
+			};
+			let terms = |u: &mut Unstructured<'a>| {
+				if kinds.is_empty() {
+					Ok(Vec::new())
+				} else {
+					list(u, term)
+				}
+			};
+
+			Ok(ConditionExpression {
+				disjunction: list(u, |u| {
+					Ok(ConditionConjunction {
+						positive: terms(u)?,
+						negative: terms(u)?,
+					})
+				})?,
+			})
+		};
+
+		let elements = list(u, |u| {
+			let condition = match u.int_in_range(0..=3)? {
+				1 => pick(u, counts.nodes)?.map(ElementCondition::Node),
+				2 => pick(u, counts.edges)?.map(ElementCondition::Edge),
+				3 => Some(ElementCondition::Expression(expression(u, true)?)),
+				_ => None,
+			};
+
+			Ok(condition.unwrap_or(ElementCondition::Fixed(u.arbitrary()?)))
+		})?
+		.into_iter()
+		.enumerate()
+		.map(|(i, condition)| Element {
+			id: format!("E{i}"),
+			condition,
+		})
+		.collect();
+
+		let mut holding_points = Vec::new();
+		for i in 0..counts.holding_points {
+			let location = match pick(u, counts.edges)? {
+				Some(edge) if u.arbitrary()? => HoldingPointLocation::Edge(edge),
+				_ => HoldingPointLocation::Nodes(
+					u.choose_index(counts.nodes)?.into(),
+					u.choose_index(counts.nodes)?.into(),
+				),
+			};
+
+			holding_points.push(HoldingPoint {
+				id: format!("H{i}"),
+				label: u.arbitrary()?,
+				location,
+				position: if u.arbitrary()? {
+					Some(GeoPoint::arbitrary(u)?.geo)
+				} else {
+					None
+				},
+			});
+		}
+
+		let edge_condition = |u: &mut Unstructured<'a>| {
+			Ok(match u.int_in_range(0..=3)? {
+				1 if counts.nodes > 0 => EdgeCondition::Direct {
+					nodes: NodeExpression {
+						disjunction: list(u, |u| {
+							let all = (0..counts.nodes).collect::<Vec<_>>();
+							Ok(NodeConjunction {
+								positive: subset(u, &all)?,
+								negative: subset(u, &all)?,
+							})
+						})?,
+					},
+				},
+				2 if counts.blocks > 0 => {
+					let block = u.choose_index(counts.blocks)?;
+					EdgeCondition::Router {
+						block: block.into(),
+						routes: routes(u, &blocks[block])?,
+					}
+				},
+				3 => EdgeCondition::Expression {
+					expression: expression(u, false)?,
+				},
+				_ => EdgeCondition::Fixed {
+					state: u.arbitrary()?,
+				},
+			})
+		};
+
+		let block_state = |u: &mut Unstructured<'a>| {
+			Ok(match u.int_in_range(0..=2)? {
+				1 => BlockState::Relax,
+				2 if !parents.is_empty() => BlockState::Route((
+					(*u.choose(&parents)?).into(),
+					(*u.choose(&parents)?).into(),
+				)),
+				_ => BlockState::Clear,
+			})
+		};
+
+		let mut profiles = Vec::new();
+		for i in 0..u.int_in_range(0..=MAX_ITEMS)? {
+			let presets = u.int_in_range(0..=MAX_ITEMS)?;
+			profiles.push(Profile {
+				id: format!("P{i}"),
+				name: u.arbitrary()?,
+				tags: list(u, |u| Ok(u.choose(&["lvp", "night"])?.to_string()))?,
+				nodes: (0..counts.nodes)
+					.map(|_| u.arbitrary())
+					.collect::<Result<_>>()?,
+				edges: (0..counts.edges)
+					.map(|_| edge_condition(u))
+					.collect::<Result<_>>()?,
+				blocks: (0..counts.blocks)
+					.map(|_| u.arbitrary())
+					.collect::<Result<_>>()?,
+				presets: (0..presets)
+					.map(|j| {
+						Ok(Preset {
+							name: u.arbitrary()?,
+							// only earlier presets, so that includes never form a cycle
+							includes: subset(u, &(0..j).collect::<Vec<_>>())?,
+							nodes: list(u, |u| {
+								Ok(pick(u, counts.nodes)?.map(|n| (n, NodeState::Off)))
+							})?
+							.into_iter()
+							.flatten()
+							.map(|(node, _)| Ok((node, u.arbitrary()?)))
+							.collect::<Result<_>>()?,
+							blocks: list(u, |u| pick(u, counts.blocks))?
+								.into_iter()
+								.flatten()
+								.map(|block| Ok((block, block_state(u)?)))
+								.collect::<Result<_>>()?,
+						})
+					})
+					.collect::<Result<_>>()?,
+			});
+		}
+
+		// only keys of existing entities, so that none are dangling
+		let keys = (nodes.iter().map(|node| StringKind::Node.key(&node.id)))
+			.chain(profiles.iter().map(|p| StringKind::Profile.key(&p.id)))
+			.chain(
+				holding_points
+					.iter()
+					.map(|h| StringKind::HoldingPoint.key(&h.id)),
+			)
+			.collect::<Vec<_>>();
+		let strings = if !keys.is_empty() && u.arbitrary()? {
+			let mut table = LocaleTable::default();
+			for _ in 0..u.int_in_range(0..=MAX_ITEMS)? {
+				table
+					.strings
+					.entry(u.choose(&keys)?.clone())
+					.or_default()
+					.insert(
+						u.choose(&["en", "fr", "fr-CA"])?.to_string(),
+						u.arbitrary()?,
+					);
+			}
+
+			Some(table)
+		} else {
+			None
+		};
+
+		Ok(Self {
+			icao,
+			elements,
+			nodes,
+			edges: edge_ids
+				.into_iter()
+				.map(|i| {
+					Ok(Edge {
+						id: format!("X{i}"),
+						name: u.arbitrary()?,
+					})
+				})
+				.collect::<Result<_>>()?,
+			blocks,
+			holding_points,
+			profiles,
+			strings,
+			geo_map: if u.arbitrary()? {
+				Some(counts.geo_map(u)?)
+			} else {
+				None
+			},
+			maps: list(u, |u| counts.map(u))?,
+			styles: (0..counts.styles)
+				.map(|_| u.arbitrary())
+				.collect::<Result<_>>()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Maps {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let counts = Counts::arbitrary(u)?;
+
+		Ok(Self {
+			nodes: (0..counts.nodes).map(|i| format!("N{i}")).collect(),
+			edges: (0..counts.edges).map(|i| format!("X{i}")).collect(),
+			blocks: (0..counts.blocks).map(|i| format!("B{i}")).collect(),
+			holding_points: (0..counts.holding_points)
+				.map(|i| format!("H{i}"))
+				.collect(),
+			geo_map: if u.arbitrary()? {
+				Some(counts.geo_map(u)?)
+			} else {
+				None
+			},
+			maps: list(u, |u| counts.map(u))?,
+			styles: (0..counts.styles)
+				.map(|_| u.arbitrary())
+				.collect::<Result<_>>()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for NodeState {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(if u.arbitrary()? { Self::On } else { Self::Off })
+	}
+}
+
+impl<'a> Arbitrary<'a> for EdgeState {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(if u.arbitrary()? { Self::On } else { Self::Off })
+	}
+}
+
+impl<'a> Arbitrary<'a> for ResetCondition {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(match u.arbitrary()? {
+			Some(secs) => Self::TimeSecs(secs),
+			None => Self::None,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for NodeCondition {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(match u.int_in_range(0..=2)? {
+			0 => Self::Fixed {
+				state: u.arbitrary()?,
+			},
+			1 => Self::Direct {
+				reset: u.arbitrary()?,
+			},
+			_ => Self::Router {
+				sticky: u.arbitrary()?,
+			},
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for BlockCondition {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			reset: u.arbitrary()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for View {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let corner = Point::arbitrary(u)?;
+		let size = Point::arbitrary(u)?;
+
+		Ok(Self {
+			name: u.arbitrary()?,
+			bounds: Box {
+				min: corner,
+				max: Point {
+					x: corner.x + size.x.abs() + 1.0,
+					y: corner.y + size.y.abs() + 1.0,
+				},
+			},
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Point {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			x: coordinate(u)?,
+			y: coordinate(u)?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for GeoPoint {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			geo: Geo {
+				lat: u.int_in_range(-90_000..=90_000)? as f32 / 1000.0,
+				lon: u.int_in_range(-180_000..=180_000)? as f32 / 1000.0,
+			},
+			offset: u.arbitrary()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Style {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			stroke_style: if u.arbitrary()? {
+				StrokeStyle::Dash(u.int_in_range(0..=4)?)
+			} else {
+				StrokeStyle::None
+			},
+			stroke_width: (u.arbitrary::<u16>()? as f32 / 16.0).into(),
+			stroke_cap: StrokeCap(u.int_in_range(0..=2)?),
+			stroke_join: StrokeJoin(u.int_in_range(0..=2)?),
+			stroke_color: u.arbitrary()?,
+			fill_style: match u.int_in_range(0..=2)? {
+				0 => FillStyle::None,
+				1 => FillStyle::Fill,
+				_ => FillStyle::Hatch(u.int_in_range(0..=52)?),
+			},
+			fill_color: u.arbitrary()?,
+			blink: if u.arbitrary()? {
+				Some(Blink {
+					period_ms: u.arbitrary()?,
+					duty_percent: u.int_in_range(0..=100)?,
+					phase_ms: u.arbitrary()?,
+				})
+			} else {
+				None
+			},
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Color {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			r: u.arbitrary()?,
+			g: u.arbitrary()?,
+			b: u.arbitrary()?,
+			a: u.arbitrary()?,
+		})
+	}
+}
+
+/// Builds a strategy which generates values from random bytes.
+fn from_bytes<T: for<'a> Arbitrary<'a> + Debug>() -> BoxedStrategy<T> {
+	proptest::collection::vec(any::<u8>(), 0..0x400)
+		.prop_map(|bytes| {
+			Unstructured::new(&bytes)
+				.arbitrary()
+				.expect("generators only fail on empty choices")
+		})
+		.boxed()
+}
+
+macro_rules! proptest_arbitrary {
+	( $( $ty:ty ),* $(,)? ) => {
+		$(
+			impl proptest::arbitrary::Arbitrary for $ty {
+				type Parameters = ();
+				type Strategy = BoxedStrategy<Self>;
+
+				fn arbitrary_with((): ()) -> Self::Strategy {
+					from_bytes()
+				}
+			}
+		)*
+	};
+}
+
+proptest_arbitrary!(Config, Aerodrome, Maps, GeoMap, Map, Style);
+
+impl<'a> Arbitrary<'a> for GeoMap {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Counts::arbitrary(u)?.geo_map(u)
+	}
+}
+
+impl<'a> Arbitrary<'a> for Map {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Counts::arbitrary(u)?.map(u)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use proptest::prelude::*;
+
+	fn saved(value: &impl Loadable) -> Vec<u8> {
+		let mut data = Vec::new();
+		value.save(&mut data).unwrap();
+		data
+	}
+
+	proptest! {
+		#[test]
+		fn configs_round_trip(config: Config) {
+			let data = saved(&config);
+			let loaded = Config::load(data.as_slice()).unwrap();
+
+			prop_assert_eq!(saved(&loaded), data);
+			prop_assert_eq!(loaded.content_hash(), config.content_hash());
+		}
+
+		#[test]
+		fn maps_round_trip(maps: Maps) {
+			let data = saved(&maps);
+			let loaded = Maps::load(data.as_slice()).unwrap();
+
+			prop_assert_eq!(saved(&loaded), data);
+		}
+
+		#[test]
+		fn generated_configs_validate(config: Config) {
+			let errors = (config.validate().into_iter())
+				.filter(|issue| issue.severity() == Severity::Error)
+				.map(|issue| issue.to_string())
+				.collect::<Vec<_>>();
+
+			prop_assert!(errors.is_empty(), "{errors:?}");
+		}
+	}
+}
@@ -1,16 +1,28 @@
 use crate::*;
 
+use std::borrow::Cow;
 use std::error::Error;
+use std::f32::consts::TAU;
 use std::fmt::{self, Display, Formatter};
 
+/// segments of circles drawn without a number given
+const CIRCLE_SEGMENTS: u16 = 32;
+
 #[derive(Debug)]
 pub struct MapsLoadTopskyError {
 	pub message: String,
+	/// name of the file, when loaded from several
+	pub file: Option<String>,
+	/// of the file, from 1
 	pub line: usize,
 }
 
 impl Display for MapsLoadTopskyError {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if let Some(file) = &self.file {
+			write!(f, "{file}: ")?;
+		}
+
 		write!(f, "line {}: {}", self.line, self.message)
 	}
 }
@@ -57,7 +69,8 @@ impl<'a, T, U> Indexer<'a, T, U> {
 }
 
 impl<'a, T: From<U>, U: Hash + Eq + Clone> Indexer<'a, T, U> {
-	fn index(&mut self, value: U) -> usize {
+	fn index(&mut self, value: impl Into<U>) -> usize {
+		let value = value.into();
 		*self.map.entry(value.clone()).or_insert_with(|| {
 			self.list.push(value.into());
 			self.list.len() - 1
@@ -78,8 +91,106 @@ impl<T: Default> Expand<T> for Vec<T> {
 	}
 }
 
+/// A position named by `COORDDEF` or `POINTDEF`, for reference as `@name`.
+enum Definition {
+	Coord(GeoPoint),
+	Point(Point),
+}
+
+/// An affine transform of points in maps, as the rows of its matrix.
+#[derive(Clone, Copy)]
+struct Transform([[f32; 3]; 2]);
+
+impl Transform {
+	const IDENTITY: Self = Self([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+
+	/// Returns a rotation about the centre, turning the x axis towards the y.
+	fn rotation(degrees: f32, centre: Point) -> Self {
+		// exact for quarter turns, the most common by far
+		let (sin, cos) = match degrees.rem_euclid(360.0) {
+			0.0 => (0.0, 1.0),
+			90.0 => (1.0, 0.0),
+			180.0 => (0.0, -1.0),
+			270.0 => (-1.0, 0.0),
+			_ => degrees.to_radians().sin_cos(),
+		};
+		Self([
+			[cos, -sin, centre.x - cos * centre.x + sin * centre.y],
+			[sin, cos, centre.y - sin * centre.x - cos * centre.y],
+		])
+	}
+
+	/// Returns the transform applying this one and then the other.
+	fn then(self, other: Self) -> Self {
+		let [a, b] = other.0;
+		let [c, d] = self.0;
+		let row = |[x, y, z]: [f32; 3]| {
+			[
+				x * c[0] + y * d[0],
+				x * c[1] + y * d[1],
+				x * c[2] + y * d[2] + z,
+			]
+		};
+
+		Self([row(a), row(b)])
+	}
+
+	fn apply(self, point: Point) -> Point {
+		let [[a, b, c], [d, e, f]] = self.0;
+		Point {
+			x: a * point.x + b * point.y + c,
+			y: d * point.x + e * point.y + f,
+		}
+	}
+}
+
 impl Maps {
 	pub fn load_topsky(text: &str) -> Result<Self, MapsLoadTopskyError> {
+		Self::load_topsky_with([(None, text)], Err)
+	}
+
+	/// Loads maps, skipping lines with errors rather than failing on the first.
+	pub fn load_topsky_lenient(
+		text: &str,
+	) -> Result<(Self, Vec<MapsLoadTopskyError>), MapsLoadTopskyError> {
+		Self::load_topsky_lenient_with([(None, text)])
+	}
+
+	/// Loads maps from several files, given by name and contents, read in
+	/// order as if concatenated, so that colours, styles, definitions and ids
+	/// are shared between them. Errors name the file, and the line within it.
+	pub fn load_topsky_files<'a>(
+		files: impl IntoIterator<Item = (&'a str, &'a str)>,
+	) -> Result<Self, MapsLoadTopskyError> {
+		let files = files.into_iter().map(|(name, text)| (Some(name), text));
+		Self::load_topsky_with(files, Err)
+	}
+
+	/// Loads maps from several files as for [`Maps::load_topsky_files`],
+	/// skipping lines with errors rather than failing on the first.
+	pub fn load_topsky_files_lenient<'a>(
+		files: impl IntoIterator<Item = (&'a str, &'a str)>,
+	) -> Result<(Self, Vec<MapsLoadTopskyError>), MapsLoadTopskyError> {
+		let files = files.into_iter().map(|(name, text)| (Some(name), text));
+		Self::load_topsky_lenient_with(files)
+	}
+
+	fn load_topsky_lenient_with<'a>(
+		files: impl IntoIterator<Item = (Option<&'a str>, &'a str)>,
+	) -> Result<(Self, Vec<MapsLoadTopskyError>), MapsLoadTopskyError> {
+		let mut errors = Vec::new();
+		let maps = Self::load_topsky_with(files, |error| {
+			errors.push(error);
+			Ok(())
+		})?;
+
+		Ok((maps, errors))
+	}
+
+	fn load_topsky_with<'a>(
+		files: impl IntoIterator<Item = (Option<&'a str>, &'a str)>,
+		mut on_error: impl FnMut(MapsLoadTopskyError) -> Result<(), MapsLoadTopskyError>,
+	) -> Result<Self, MapsLoadTopskyError> {
 		const DEFAULT_COLOR: Color = Color {
 			r: 0,
 			g: 0,
@@ -91,19 +202,24 @@ impl Maps {
 			nodes: Vec::new(),
 			edges: Vec::new(),
 			blocks: Vec::new(),
+			holding_points: Vec::new(),
 			geo_map: None,
 			maps: Vec::new(),
 			styles: Vec::new(),
 		};
 
-		let mut nodes = Indexer::new(&mut maps.nodes);
-		let mut edges = Indexer::new(&mut maps.edges);
-		let mut blocks = Indexer::new(&mut maps.blocks);
-		let mut styles = Indexer::new(&mut maps.styles);
+		let mut nodes = Indexer::<_, String>::new(&mut maps.nodes);
+		let mut edges = Indexer::<_, String>::new(&mut maps.edges);
+		let mut blocks = Indexer::<_, String>::new(&mut maps.blocks);
+		let mut holding_points =
+			Indexer::<_, String>::new(&mut maps.holding_points);
+		let mut styles = Indexer::<_, Style>::new(&mut maps.styles);
 
 		let mut colors = HashMap::<String, Color>::new();
+		let mut definitions = HashMap::<String, Definition>::new();
 
 		let mut geo = None;
+		let mut geo_defined = false;
 		let mut map = None;
 
 		let mut coord_list = Vec::new();
@@ -114,391 +230,2128 @@ impl Maps {
 		let mut stroke_color = DEFAULT_COLOR;
 		let mut stroke_style = StrokeStyle::None;
 		let mut stroke_width = StrokeWidth::from(1.0);
+		let mut stroke_cap = StrokeCap::ROUND;
+		let mut stroke_join = StrokeJoin::ROUND;
 		let mut fill_color = DEFAULT_COLOR;
+		let mut blink = None;
+		let mut level = None;
+		// percentage of the alpha of the colours kept when drawing paths
+		let mut alpha = 100u8;
+		// added to the points of paths and targets, as x and y in maps or as
+		// latitude and longitude in the geo map, rather than to offsets in pixels
+		let mut translation = [0.0f32; 2];
+		// applied to the points of paths and targets in maps before translation
+		let mut transform = Transform::IDENTITY;
 
-		let lines = text
-			.lines()
-			.map(|line| {
-				line
-					.split_once("//")
-					.map(|(line, _)| line)
-					.unwrap_or(line)
-					.trim()
-			})
-			.filter(|line| !line.is_empty())
-			.map(|line| line.split(':').collect::<Vec<_>>())
-			.enumerate()
-			.map(|(i, line)| (i + 1, line));
-
-		for (line, parts) in lines {
-			let command = parts[0];
-			let args = &parts[1..];
-
-			macro_rules! bail {
-				( $( $arg:tt )+ ) => {
-					return Err(error!($($arg)+))
-				};
-			}
+		let lines = files.into_iter().flat_map(|(file, text)| {
+			text
+				.lines()
+				.map(|line| {
+					line
+						.split_once("//")
+						.map(|(line, _)| line)
+						.unwrap_or(line)
+						.trim()
+				})
+				.enumerate()
+				.map(move |(i, line)| (file, i + 1, line))
+				.filter(|(_, _, line)| !line.is_empty())
+				.map(|(file, i, line)| (file, i, line.split(':').collect::<Vec<_>>()))
+		});
 
-			macro_rules! error {
-				( $( $arg:tt )+ ) => {
-					MapsLoadTopskyError {
-						message: format!($($arg)+),
-						line,
+		for (file, line, parts) in lines {
+			let result = 'line: {
+				// text may contain anything, so is never taken as references
+				let parts = if parts[0] == "TOOLTIP" {
+					parts.iter().map(|part| Cow::Borrowed(*part)).collect()
+				} else {
+					match expand_references(&parts, &definitions) {
+						Ok(parts) => parts,
+						Err(message) => {
+							break 'line Err(MapsLoadTopskyError {
+								message,
+								file: file.map(Into::into),
+								line,
+							})
+						},
 					}
+				};
+				let parts = parts.iter().map(Cow::as_ref).collect::<Vec<_>>();
+
+				let command = parts[0];
+				let args = &parts[1..];
+
+				macro_rules! bail {
+					( $( $arg:tt )+ ) => {
+						break 'line Err(error!($($arg)+))
+					};
 				}
-			}
 
-			macro_rules! check_args {
-				( $expected:pat ) => {
-					if !matches!(args.len(), $expected) {
-						bail!(
-							"incorrect number of arguments to {} (expected {}, got {})",
-							command,
-							stringify!($expected),
-							args.len(),
-						)
+				macro_rules! attempt {
+					( $result:expr ) => {
+						match $result {
+							Ok(value) => value,
+							Err(err) => break 'line Err(err),
+						}
+					};
+				}
+
+				macro_rules! error {
+					( $( $arg:tt )+ ) => {
+						MapsLoadTopskyError {
+							message: format!($($arg)+),
+							file: file.map(Into::into),
+							line,
+						}
 					}
+				}
+
+				macro_rules! check_args {
+					( $expected:pat ) => {
+						if !matches!(args.len(), $expected) {
+							bail!(
+								"incorrect number of arguments to {} (expected {}, got {})",
+								command,
+								stringify!($expected),
+								args.len(),
+							)
+						}
+					};
+				}
+
+				macro_rules! unwrap {
+					( $result:expr ) => {
+						attempt!($result.map_err(|err| error!("{err}")))
+					};
+				}
+
+				// a style of a defined colour alone, for text and highlights
+				macro_rules! color_style {
+					( $color:expr ) => {{
+						let color = $color;
+						let color = *attempt!(colors
+							.get(color)
+							.ok_or_else(|| error!("{color} undefined")));
+
+						Ref::from(styles.index(Style {
+							stroke_style: StrokeStyle::Dash(0),
+							stroke_width: StrokeWidth::from(1.0),
+							stroke_cap: StrokeCap(0),
+							stroke_join: StrokeJoin(0),
+							stroke_color: color,
+							fill_style: FillStyle::None,
+							fill_color: color,
+							blink: None,
+						}))
+					}};
+				}
+
+				let parse_float =
+					|part: &str| part.parse::<f32>().map_err(|err| error!("{err}"));
+				let parse_point = |parts: &[&str]| {
+					Ok(Point {
+						x: parse_float(parts[0])?,
+						y: parse_float(parts[1])?,
+					})
 				};
-			}
+				let parse_blink = |spec: &str| {
+					let parts = spec.split('/').collect::<Vec<_>>();
+					let [period, duty, phase @ ..] = parts.as_slice() else {
+						return Err(error!("expected blink=PERIOD/DUTY[/PHASE]"))
+					};
+					if phase.len() > 1 {
+						return Err(error!("expected blink=PERIOD/DUTY[/PHASE]"))
+					}
 
-			macro_rules! unwrap {
-				( $result:expr ) => {
-					$result.map_err(|err| error!("{err}"))?
+					let duty_percent =
+						duty.parse::<u8>().map_err(|err| error!("{err}"))?;
+					if duty_percent > 100 {
+						return Err(error!("blink duty {duty_percent} above 100%"))
+					}
+
+					Ok(Blink {
+						period_ms: period.parse().map_err(|err| error!("{err}"))?,
+						duty_percent,
+						phase_ms: match phase.first() {
+							Some(phase) => phase.parse().map_err(|err| error!("{err}"))?,
+							None => 0,
+						},
+					})
+				};
+				let parse_coord = |parts: &[&str]| {
+					Ok(GeoPoint {
+						geo: Geo {
+							lat: parse_angle(parts[0], 'N', 'S')
+								.map_err(|message| error!("{message}"))?,
+							lon: parse_angle(parts[1], 'E', 'W')
+								.map_err(|message| error!("{message}"))?,
+						},
+						offset: if parts.len() > 2 {
+							parse_point(&parts[2..])?
+						} else {
+							Point::default()
+						},
+					})
 				};
-			}
 
-			let parse_point = |parts: &[&str]| {
-				Ok(Point {
-					x: unwrap!(parts[0].parse::<f32>()),
-					y: unwrap!(parts[1].parse::<f32>()),
-				})
-			};
-			let parse_coord = |parts: &[&str]| {
-				Ok(GeoPoint {
-					geo: Geo {
-						lat: unwrap!(parts[0].parse::<f32>()),
-						lon: unwrap!(parts[1].parse::<f32>()),
-					},
-					offset: if parts.len() > 2 {
-						parse_point(&parts[2..])?
-					} else {
-						Point::default()
-					},
-				})
-			};
+				match command {
+					"GEO" => {
+						check_args!(0);
 
-			match command {
-				"GEO" => {
-					check_args!(0);
+						if geo_defined {
+							bail!("geo map already defined")
+						}
 
-					if maps.geo_map.is_some() {
-						bail!("geo map already defined")
-					}
+						geo_defined = true;
 
-					map = None;
-					maps.geo_map = Some(GeoMap::default());
-					geo = maps.geo_map.as_mut();
-				},
-				"MAP" => {
-					check_args!(0..=1);
+						map = None;
+						maps.geo_map = Some(GeoMap::default());
+						geo = maps.geo_map.as_mut();
+						alpha = 100;
+						translation = [0.0; 2];
+						transform = Transform::IDENTITY;
+					},
+					"MAP" => {
+						check_args!(0..=1);
 
-					geo = None;
-					maps.maps.push(Map {
-						background: if let Some(color) = args.get(0) {
-							*colors
+						let background = if let Some(color) = args.first() {
+							*attempt!(colors
 								.get(*color)
-								.ok_or_else(|| error!("{color} undefined"))?
+								.ok_or_else(|| error!("{color} undefined")))
 						} else {
 							DEFAULT_COLOR
-						},
-						..Map::default()
-					});
-					map = maps.maps.last_mut();
-				},
-				"VIEW" => {
-					check_args!(5);
-
-					if let Some(map) = &mut map {
-						map.views.push(View {
-							name: args[0].into(),
-							bounds: Box {
-								min: parse_point(&args[1..3])?,
-								max: parse_point(&args[3..5])?,
-							},
+						};
+
+						geo = None;
+						maps.maps.push(Map {
+							background,
+							..Map::default()
 						});
-					} else {
-						bail!("VIEW outside map context")
-					}
-				},
-				"COLORDEF" => {
-					check_args!(4);
-
-					colors.insert(
-						args[0].into(),
-						Color {
-							r: unwrap!(args[1].parse()),
-							g: unwrap!(args[2].parse()),
-							b: unwrap!(args[3].parse()),
-							a: u8::MAX,
-						},
-					);
-				},
-				"COLOR" => {
-					check_args!(1..=3);
-
-					stroke_color = *colors
-						.get(args[0])
-						.ok_or_else(|| error!("{} undefined", args[0]))?;
-					fill_color = *args
-						.get(1)
-						.map(|color| {
-							colors
-								.get(*color)
-								.ok_or_else(|| error!("{color} undefined"))
-						})
-						.transpose()?
+						alpha = 100;
+						translation = [0.0; 2];
+						transform = Transform::IDENTITY;
+						map = maps.maps.last_mut();
+					},
+					"VIEW" => {
+						check_args!(5);
+
+						if let Some(map) = &mut map {
+							map.views.push(View {
+								name: args[0].into(),
+								bounds: Box {
+									min: attempt!(parse_point(&args[1..3])),
+									max: attempt!(parse_point(&args[3..5])),
+								},
+							});
+						} else {
+							bail!("VIEW outside map context")
+						}
+					},
+					"COLORDEF" => {
+						check_args!(4);
+
+						colors.insert(
+							args[0].into(),
+							Color {
+								r: unwrap!(args[1].parse()),
+								g: unwrap!(args[2].parse()),
+								b: unwrap!(args[3].parse()),
+								a: u8::MAX,
+							},
+						);
+					},
+					"COORDDEF" => {
+						check_args!(3 | 5);
+
+						// replaced by a later definition, for the lines after it
+						let coord = attempt!(parse_coord(&args[1..]));
+						definitions.insert(args[0].into(), Definition::Coord(coord));
+					},
+					"POINTDEF" => {
+						check_args!(3);
+
+						let point = attempt!(parse_point(&args[1..]));
+						definitions.insert(args[0].into(), Definition::Point(point));
+					},
+					"COLOR" => {
+						check_args!(1..=3);
+
+						stroke_color = *attempt!(colors
+							.get(args[0])
+							.ok_or_else(|| error!("{} undefined", args[0])));
+						fill_color = *attempt!(args
+							.get(1)
+							.map(|color| {
+								colors
+									.get(*color)
+									.ok_or_else(|| error!("{color} undefined"))
+							})
+							.transpose())
 						.unwrap_or(&stroke_color);
-				},
-				"STYLE" => {
-					check_args!(1..=2);
-
-					stroke_style = match args[0].to_ascii_lowercase().as_str() {
-						"null" => StrokeStyle::None,
-						"solid" => StrokeStyle::Dash(0),
-						"dash" => StrokeStyle::Dash(1),
-						"dot" | "alternate" => StrokeStyle::Dash(2),
-						"dashdot" => StrokeStyle::Dash(3),
-						"dashdotdot" => StrokeStyle::Dash(4),
-						other => bail!("unknown stroke style {other}"),
-					};
+					},
+					"STYLE" => {
+						check_args!(1..=5);
+
+						stroke_style = match args[0].to_ascii_lowercase().as_str() {
+							"null" => StrokeStyle::None,
+							"solid" => StrokeStyle::Dash(0),
+							"dash" => StrokeStyle::Dash(1),
+							"dot" | "alternate" => StrokeStyle::Dash(2),
+							"dashdot" => StrokeStyle::Dash(3),
+							"dashdotdot" => StrokeStyle::Dash(4),
+							other => bail!("unknown stroke style {other}"),
+						};
+
+						// the width may be given, then options in any order
+						let mut rest = &args[1..];
+						stroke_cap = StrokeCap::ROUND;
+						stroke_join = StrokeJoin::ROUND;
+						blink = None;
+
+						if let Some(width) = rest.first().filter(|w| !w.contains('=')) {
+							let parsed = unwrap!(width.parse::<f32>());
+							if !(0.0..=f32::from(StrokeWidth::MAX)).contains(&parsed) {
+								bail!("stroke width {width} out of range")
+							}
+
+							stroke_width = parsed.into();
+							if stroke_width == 0f32.into() {
+								stroke_style = StrokeStyle::None;
+							}
 
-					if let Some(width) = args.get(1) {
-						stroke_width = unwrap!(width.parse::<f32>()).into();
-						if stroke_width == 0f32.into() {
-							stroke_style = StrokeStyle::None;
+							rest = &rest[1..];
 						}
-					}
-				},
-				"NODE" => {
-					check_args!(2);
-
-					group = Group::Node(
-						nodes.index(args[0]),
-						match args[1] {
-							"OFF" => NodeGroup::Off,
-							"ON" => NodeGroup::On,
-							"SELECTED" => NodeGroup::Selected,
-							"TARGET" => NodeGroup::Target,
-							other => bail!("unknown node group {other}"),
-						},
-					);
-				},
-				"EDGE" => {
-					check_args!(2);
-
-					group = Group::Edge(
-						edges.index(args[0]),
-						match args[1] {
-							"OFF" => EdgeGroup::Off,
-							"ON" => EdgeGroup::On,
-							"PENDING" => EdgeGroup::Pending,
-							other => bail!("unknown edge group {other}"),
-						},
-					);
-				},
-				"BLOCK" => {
-					check_args!(2);
-
-					group = Group::Block(
-						blocks.index(args[0]),
-						match args[1] {
-							"TARGET" => BlockGroup::Target,
-							other => bail!("unknown block group {other}"),
-						},
-					);
-				},
-				"BASE" => {
-					check_args!(0);
 
-					group = Group::Base;
-				},
-				"COORD" | "POINT" => {
-					if geo.is_some() {
-						check_args!(2 | 4);
+						for option in rest {
+							match option.split_once('=') {
+								Some(("blink", spec)) => {
+									blink = Some(attempt!(parse_blink(spec)))
+								},
+								Some(("cap", cap)) => {
+									stroke_cap = match cap.to_ascii_lowercase().as_str() {
+										"butt" => StrokeCap::BUTT,
+										"round" => StrokeCap::ROUND,
+										"square" => StrokeCap::SQUARE,
+										other => bail!("unknown stroke cap {other}"),
+									}
+								},
+								Some(("join", join)) => {
+									stroke_join = match join.to_ascii_lowercase().as_str() {
+										"miter" => StrokeJoin::MITER,
+										"round" => StrokeJoin::ROUND,
+										"bevel" => StrokeJoin::BEVEL,
+										other => bail!("unknown stroke join {other}"),
+									}
+								},
+								_ => bail!("unknown style option {option}"),
+							}
+						}
+					},
+					"ALPHA" => {
+						check_args!(1);
 
-						coord_list.push(parse_coord(&args)?);
-					} else if map.is_some() {
+						// kept across COLOR and groups until the next MAP or GEO
+						alpha = unwrap!(args[0].parse::<u8>());
+						if alpha > 100 {
+							bail!("alpha {alpha} above 100%")
+						}
+					},
+					"OFFSETSET" | "OFFSETADD" => {
 						check_args!(2);
 
-						point_list.push(parse_point(&args)?);
-					} else {
-						bail!("{command} outside map context")
-					}
-				},
-				"COORDTARGET" | "POINTTARGET" => {
-					check_args!(0);
+						let [a, b] = [args[0], args[1]].map(parse_float);
+						let [a, b] = [attempt!(a), attempt!(b)];
 
-					if let Some(geo) = &mut geo {
-						match group {
-							Group::Node(i, NodeGroup::Target) => {
-								&mut geo.nodes.expand(i).target
-							},
-							Group::Block(i, BlockGroup::Target) => {
-								&mut geo.blocks.expand(i).target
-							},
-							_ => bail!("{command} outside target context"),
+						// kept across groups until cleared or the next MAP or GEO
+						translation = match command {
+							"OFFSETADD" => [translation[0] + a, translation[1] + b],
+							_ => [a, b],
+						};
+					},
+					"OFFSETCLEAR" => {
+						check_args!(0);
+
+						translation = [0.0; 2];
+					},
+					"ROTATE" | "MIRROR" => {
+						// the geo map is transformed by latitude and longitude alone
+						if geo.is_some() {
+							bail!("{command} unsupported in the geo map")
 						}
-						.polygons
-						.push(std::mem::take(&mut coord_list));
-					} else if let Some(map) = &mut map {
-						match group {
-							Group::Node(i, NodeGroup::Target) => {
-								&mut map.nodes.expand(i).target
+
+						let next = if command == "ROTATE" {
+							check_args!(1 | 3);
+
+							let degrees = attempt!(parse_float(args[0]));
+							let centre = if args.len() > 1 {
+								attempt!(parse_point(&args[1..]))
+							} else {
+								Point::default()
+							};
+
+							Transform::rotation(degrees, centre)
+						} else {
+							check_args!(1 | 2);
+
+							let axis = match args.get(1) {
+								Some(axis) => attempt!(parse_float(axis)),
+								None => 0.0,
+							};
+
+							// across the line of the coordinate on the axis given
+							match args[0] {
+								"X" => Transform([[-1.0, 0.0, 2.0 * axis], [0.0, 1.0, 0.0]]),
+								"Y" => Transform([[1.0, 0.0, 0.0], [0.0, -1.0, 2.0 * axis]]),
+								other => bail!("unknown mirror axis {other}"),
+							}
+						};
+
+						// composed in order until cleared or the next MAP or GEO
+						transform = transform.then(next);
+					},
+					"TRANSFORMCLEAR" => {
+						check_args!(0);
+
+						transform = Transform::IDENTITY;
+					},
+					"LEVEL" => {
+						check_args!(1);
+
+						level = match args[0] {
+							"ALL" => None,
+							n => Some(unwrap!(n.parse::<i8>())),
+						};
+					},
+					"NODE" => {
+						check_args!(2);
+
+						group = Group::Node(
+							nodes.index(args[0]),
+							match args[1] {
+								"OFF" => NodeGroup::Off,
+								"ON" => NodeGroup::On,
+								"SELECTED" => NodeGroup::Selected,
+								"TARGET" => NodeGroup::Target,
+								other => bail!("unknown node group {other}"),
 							},
-							Group::Block(i, BlockGroup::Target) => {
-								&mut map.blocks.expand(i).target
+						);
+					},
+					"EDGE" => {
+						check_args!(2);
+
+						group = Group::Edge(
+							edges.index(args[0]),
+							match args[1] {
+								"OFF" => EdgeGroup::Off,
+								"ON" => EdgeGroup::On,
+								"PENDING" => EdgeGroup::Pending,
+								other => bail!("unknown edge group {other}"),
 							},
-							_ => bail!("{command} outside target context"),
-						}
-						.polygons
-						.push(std::mem::take(&mut point_list));
-					} else {
-						bail!("{command} outside map context")
-					}
-				},
-				"COORDLINE" | "COORDPOLY" | "POINTLINE" | "POINTPOLY" => {
-					let fill_style = if let "COORDLINE" | "POINTLINE" = command {
+						);
+					},
+					"BLOCK" => {
+						check_args!(2);
+
+						group = Group::Block(
+							blocks.index(args[0]),
+							match args[1] {
+								"TARGET" => BlockGroup::Target,
+								other => bail!("unknown block group {other}"),
+							},
+						);
+					},
+					"BASE" => {
 						check_args!(0);
 
-						FillStyle::None
-					} else {
-						check_args!(1);
+						group = Group::Base;
+					},
+					"COORD" | "POINT" => {
+						if geo.is_some() {
+							check_args!(2 | 4);
 
-						let fill = args[0];
-						if fill.starts_with('E') {
-							let n = unwrap!(fill[1..].parse::<i32>());
-							if 0 <= n && n <= 52 {
-								FillStyle::Hatch(n)
-							} else {
-								bail!("hatch enum variant out of range [0, 52]")
+							let mut coord = attempt!(parse_coord(args));
+							coord.geo.lat += translation[0];
+							coord.geo.lon += translation[1];
+							coord_list.push(coord);
+						} else if map.is_some() {
+							check_args!(2);
+
+							let mut point = transform.apply(attempt!(parse_point(args)));
+							point.x += translation[0];
+							point.y += translation[1];
+							point_list.push(point);
+						} else {
+							bail!("{command} outside map context")
+						}
+					},
+					"COORDTARGET" | "POINTTARGET" => {
+						check_args!(0..=2);
+
+						// styles apply to the whole target, and are kept if not given
+						let mut hover_style = None;
+						let mut active_style = None;
+						for arg in args {
+							match arg.split_once('=') {
+								Some(("HOVERSTYLE", color)) => {
+									hover_style = Some(color_style!(color))
+								},
+								Some(("ACTIVESTYLE", color)) => {
+									active_style = Some(color_style!(color))
+								},
+								_ => bail!("unknown target option {arg}"),
 							}
+						}
+
+						if let Some(geo) = &mut geo {
+							let target = match group {
+								Group::Node(i, NodeGroup::Target) => {
+									&mut geo.nodes.expand(i).target
+								},
+								Group::Block(i, BlockGroup::Target) => {
+									&mut geo.blocks.expand(i).target
+								},
+								_ => bail!("{command} outside target context"),
+							};
+
+							target.polygons.push(std::mem::take(&mut coord_list));
+							target.hover_style = hover_style.or(target.hover_style);
+							target.active_style = active_style.or(target.active_style);
+							target.level = level;
+						} else if let Some(map) = &mut map {
+							let target = match group {
+								Group::Node(i, NodeGroup::Target) => {
+									&mut map.nodes.expand(i).target
+								},
+								Group::Block(i, BlockGroup::Target) => {
+									&mut map.blocks.expand(i).target
+								},
+								_ => bail!("{command} outside target context"),
+							};
+
+							target.polygons.push(std::mem::take(&mut point_list));
+							target.hover_style = hover_style.or(target.hover_style);
+							target.active_style = active_style.or(target.active_style);
+							target.level = level;
 						} else {
-							match fill {
-								"0" => FillStyle::None,
-								"5" => FillStyle::Hatch(6),
-								"10" => FillStyle::Hatch(7),
-								"20" => FillStyle::Hatch(8),
-								"25" => FillStyle::Hatch(9),
-								"30" => FillStyle::Hatch(10),
-								"40" => FillStyle::Hatch(11),
-								"50" => FillStyle::Hatch(12),
-								"60" => FillStyle::Hatch(13),
-								"70" => FillStyle::Hatch(14),
-								"75" => FillStyle::Hatch(15),
-								"80" => FillStyle::Hatch(16),
-								"90" => FillStyle::Hatch(17),
-								"100" => FillStyle::Fill,
-								_ => bail!("invalid hatch style {fill}"),
+							bail!("{command} outside map context")
+						}
+					},
+					"TOOLTIP" => {
+						check_args!(1..);
+
+						// the text may itself contain colons
+						let tooltip = args.join(":");
+
+						if let Some(geo) = &mut geo {
+							match group {
+								Group::Node(i, NodeGroup::Target) => {
+									&mut geo.nodes.expand(i).tooltip
+								},
+								Group::Block(i, BlockGroup::Target) => {
+									&mut geo.blocks.expand(i).tooltip
+								},
+								_ => bail!("{command} outside target context"),
 							}
+							.replace(tooltip);
+						} else if let Some(map) = &mut map {
+							match group {
+								Group::Node(i, NodeGroup::Target) => {
+									&mut map.nodes.expand(i).tooltip
+								},
+								Group::Block(i, BlockGroup::Target) => {
+									&mut map.blocks.expand(i).tooltip
+								},
+								_ => bail!("{command} outside target context"),
+							}
+							.replace(tooltip);
+						} else {
+							bail!("{command} outside map context")
 						}
-					};
+					},
+					"COORDLINE" | "COORDPOLY" | "POINTLINE" | "POINTPOLY"
+					| "COORDCIRCLE" | "POINTCIRCLE" => {
+						let (fill, circle) = match command {
+							"COORDLINE" | "POINTLINE" => {
+								check_args!(0);
 
-					let style = Ref::from(styles.index(Style {
-						stroke_style,
-						stroke_width,
-						stroke_cap: StrokeCap(0),
-						stroke_join: StrokeJoin(0),
-						stroke_color,
-						fill_style,
-						fill_color,
-					}));
-
-					if let Some(geo) = &mut geo {
-						match group {
-							Group::Node(i, NodeGroup::Off) => &mut geo.nodes.expand(i).off,
-							Group::Node(i, NodeGroup::On) => &mut geo.nodes.expand(i).on,
-							Group::Node(i, NodeGroup::Selected) => {
-								&mut geo.nodes.expand(i).selected
+								(None, None)
 							},
-							Group::Edge(i, EdgeGroup::Off) => &mut geo.edges.expand(i).off,
-							Group::Edge(i, EdgeGroup::On) => &mut geo.edges.expand(i).on,
-							Group::Edge(i, EdgeGroup::Pending) => {
-								&mut geo.edges.expand(i).pending
-							},
-							_ => bail!("{command} outside draw context"),
-						}
-						.push(Path {
-							points: std::mem::take(&mut coord_list),
-							style,
-						});
-					} else if let Some(map) = &mut map {
-						match group {
-							Group::Node(i, NodeGroup::Off) => &mut map.nodes.expand(i).off,
-							Group::Node(i, NodeGroup::On) => &mut map.nodes.expand(i).on,
-							Group::Node(i, NodeGroup::Selected) => {
-								&mut map.nodes.expand(i).selected
+							"COORDPOLY" | "POINTPOLY" => {
+								check_args!(1);
+
+								(Some(args[0]), None)
 							},
-							Group::Edge(i, EdgeGroup::Off) => &mut map.edges.expand(i).off,
-							Group::Edge(i, EdgeGroup::On) => &mut map.edges.expand(i).on,
-							Group::Edge(i, EdgeGroup::Pending) => {
-								&mut map.edges.expand(i).pending
+							_ => {
+								// the centre, the radius, the fill and then the segments, the
+								// centre taking an offset in the geo map as for COORD
+								if geo.is_some() {
+									check_args!(4..=7);
+								} else if map.is_some() {
+									check_args!(4 | 5);
+								} else {
+									bail!("{command} outside map context")
+								}
+
+								let centre = if args.len() > 5 { 4 } else { 2 };
+								let radius = unwrap!(args[centre].parse::<f32>());
+								if !radius.is_finite() || radius <= 0.0 {
+									bail!("circle radius {radius} not positive")
+								}
+
+								let segments = match args.get(centre + 2) {
+									Some(segments) => unwrap!(segments.parse::<u16>()),
+									None => CIRCLE_SEGMENTS,
+								};
+								if segments < 3 {
+									bail!("circle of {segments} segments, fewer than 3")
+								}
+
+								(
+									Some(args[centre + 1]),
+									Some((&args[..centre], radius, segments)),
+								)
 							},
-							_ => bail!("{command} outside draw context"),
-						}
-						.push(Path {
-							points: std::mem::take(&mut point_list),
-							style,
-						});
-					} else {
-						bail!("{command} outside map context")
-					}
-				},
-				"WIDGET" => {
-					check_args!(1..);
+						};
 
-					if geo.is_none() && map.is_none() {
-						bail!("WIDGET outside map context")
-					}
+						let fill_style = if let Some(fill) = fill {
+							if let Some(n) = fill.strip_prefix('E') {
+								let n = unwrap!(n.parse::<i32>());
+								if (0..=52).contains(&n) {
+									FillStyle::Hatch(n)
+								} else {
+									bail!("hatch enum variant out of range [0, 52]")
+								}
+							} else {
+								match fill {
+									"0" => FillStyle::None,
+									"5" => FillStyle::Hatch(6),
+									"10" => FillStyle::Hatch(7),
+									"20" => FillStyle::Hatch(8),
+									"25" => FillStyle::Hatch(9),
+									"30" => FillStyle::Hatch(10),
+									"40" => FillStyle::Hatch(11),
+									"50" => FillStyle::Hatch(12),
+									"60" => FillStyle::Hatch(13),
+									"70" => FillStyle::Hatch(14),
+									"75" => FillStyle::Hatch(15),
+									"80" => FillStyle::Hatch(16),
+									"90" => FillStyle::Hatch(17),
+									"100" => FillStyle::Fill,
+									_ => bail!("invalid hatch style {fill}"),
+								}
+							}
+						} else {
+							FillStyle::None
+						};
+
+						let fade = |color: Color| Color {
+							a: ((color.a as u16 * alpha as u16 + 50) / 100) as u8,
+							..color
+						};
+
+						let style = Ref::from(styles.index(Style {
+							stroke_style,
+							stroke_width,
+							stroke_cap,
+							stroke_join,
+							stroke_color: fade(stroke_color),
+							fill_style,
+							fill_color: fade(fill_color),
+							blink,
+						}));
+
+						if let Some(geo) = &mut geo {
+							let points = match circle {
+								Some((centre, radius, segments)) => {
+									let centre = attempt!(parse_coord(centre));
+									let lat = centre.geo.lat + translation[0];
+									let lon = centre.geo.lon + translation[1];
+									let scale = [
+										radius / (METRES_PER_DEGREE * lat.to_radians().cos()),
+										radius / METRES_PER_DEGREE,
+									];
 
-					match args[0] {
-						"COUNTDOWN" => {
-							check_args!(6..);
+									unit_circle(segments)
+										.map(|[x, y]| GeoPoint {
+											geo: Geo {
+												lat: lat + y * scale[1],
+												lon: lon + x * scale[0],
+											},
+											offset: centre.offset,
+										})
+										.collect()
+								},
+								None => std::mem::take(&mut coord_list),
+							};
 
-							let size = unwrap!(args[3].parse());
-							let condition = match args[1] {
-								"NODE" => CountdownCondition::Node(nodes.index(args[2]).into()),
-								"BLOCK" => {
-									CountdownCondition::Block(blocks.index(args[2]).into())
+							match group {
+								Group::Node(i, NodeGroup::Off) => &mut geo.nodes.expand(i).off,
+								Group::Node(i, NodeGroup::On) => &mut geo.nodes.expand(i).on,
+								Group::Node(i, NodeGroup::Selected) => {
+									&mut geo.nodes.expand(i).selected
 								},
-								other => bail!("invalid counter condition {other}"),
+								Group::Edge(i, EdgeGroup::Off) => &mut geo.edges.expand(i).off,
+								Group::Edge(i, EdgeGroup::On) => &mut geo.edges.expand(i).on,
+								Group::Edge(i, EdgeGroup::Pending) => {
+									&mut geo.edges.expand(i).pending
+								},
+								_ => bail!("{command} outside draw context"),
+							}
+							.push(Path {
+								points,
+								style,
+								level,
+							});
+						} else if let Some(map) = &mut map {
+							let points = match circle {
+								Some((centre, radius, segments)) => {
+									let centre = attempt!(parse_point(centre));
+
+									unit_circle(segments)
+										.map(|[x, y]| {
+											let mut point = transform.apply(Point {
+												x: centre.x + x * radius,
+												y: centre.y + y * radius,
+											});
+											point.x += translation[0];
+											point.y += translation[1];
+											point
+										})
+										.collect()
+								},
+								None => std::mem::take(&mut point_list),
 							};
 
-							if let Some(geo) = &mut geo {
-								check_args!(6 | 8);
-
-								geo.widgets.push(Widget::Countdown {
-									position: parse_coord(&args[4..])?,
-									size,
-									condition,
-								});
-							} else if let Some(map) = &mut map {
-								check_args!(6);
-
-								map.widgets.push(Widget::Countdown {
-									position: parse_point(&args[4..])?,
-									size,
-									condition,
-								});
+							match group {
+								Group::Node(i, NodeGroup::Off) => &mut map.nodes.expand(i).off,
+								Group::Node(i, NodeGroup::On) => &mut map.nodes.expand(i).on,
+								Group::Node(i, NodeGroup::Selected) => {
+									&mut map.nodes.expand(i).selected
+								},
+								Group::Edge(i, EdgeGroup::Off) => &mut map.edges.expand(i).off,
+								Group::Edge(i, EdgeGroup::On) => &mut map.edges.expand(i).on,
+								Group::Edge(i, EdgeGroup::Pending) => {
+									&mut map.edges.expand(i).pending
+								},
+								_ => bail!("{command} outside draw context"),
 							}
-						},
-						other => bail!("unknown widget type {other}"),
-					}
-				},
-				_ => bail!("unknown command {command}"),
+							.push(Path {
+								points,
+								style,
+								level,
+							});
+						} else {
+							bail!("{command} outside map context")
+						}
+					},
+					"REPEAT" => {
+						let count = unwrap!(args.first().unwrap_or(&"").parse::<u16>());
+
+						let repeated = if let Some(geo) = &mut geo {
+							check_args!(3 | 5);
+
+							let [dlat, dlon] = [args[1], args[2]].map(parse_float);
+							let (dlat, dlon) = (attempt!(dlat), attempt!(dlon));
+							let step = if args.len() > 3 {
+								attempt!(parse_point(&args[3..]))
+							} else {
+								Point::default()
+							};
+
+							let paths = match group {
+								Group::Node(i, NodeGroup::Off) => &mut geo.nodes.expand(i).off,
+								Group::Node(i, NodeGroup::On) => &mut geo.nodes.expand(i).on,
+								Group::Node(i, NodeGroup::Selected) => {
+									&mut geo.nodes.expand(i).selected
+								},
+								Group::Edge(i, EdgeGroup::Off) => &mut geo.edges.expand(i).off,
+								Group::Edge(i, EdgeGroup::On) => &mut geo.edges.expand(i).on,
+								Group::Edge(i, EdgeGroup::Pending) => {
+									&mut geo.edges.expand(i).pending
+								},
+								_ => bail!("{command} outside draw context"),
+							};
+
+							repeat_last(paths, count, |point, n| GeoPoint {
+								geo: Geo {
+									lat: point.geo.lat + dlat * n,
+									lon: point.geo.lon + dlon * n,
+								},
+								offset: Point {
+									x: point.offset.x + step.x * n,
+									y: point.offset.y + step.y * n,
+								},
+							})
+						} else if let Some(map) = &mut map {
+							check_args!(3);
+
+							let step = attempt!(parse_point(&args[1..]));
+
+							let paths = match group {
+								Group::Node(i, NodeGroup::Off) => &mut map.nodes.expand(i).off,
+								Group::Node(i, NodeGroup::On) => &mut map.nodes.expand(i).on,
+								Group::Node(i, NodeGroup::Selected) => {
+									&mut map.nodes.expand(i).selected
+								},
+								Group::Edge(i, EdgeGroup::Off) => &mut map.edges.expand(i).off,
+								Group::Edge(i, EdgeGroup::On) => &mut map.edges.expand(i).on,
+								Group::Edge(i, EdgeGroup::Pending) => {
+									&mut map.edges.expand(i).pending
+								},
+								_ => bail!("{command} outside draw context"),
+							};
+
+							repeat_last(paths, count, |point, n| Point {
+								x: point.x + step.x * n,
+								y: point.y + step.y * n,
+							})
+						} else {
+							bail!("{command} outside map context")
+						};
+
+						if !repeated {
+							bail!("{command} without a preceding path in the group")
+						}
+					},
+					"WIDGET" => {
+						check_args!(1..);
+
+						if geo.is_none() && map.is_none() {
+							bail!("WIDGET outside map context")
+						}
+
+						match args[0] {
+							"COUNTDOWN" => {
+								check_args!(6..);
+
+								// any options follow the position, each as KEY=VALUE
+								let (positional, options) = args.split_at(
+									(args.iter().position(|arg| arg.contains('=')))
+										.unwrap_or(args.len()),
+								);
+
+								let expected: &[usize] =
+									if geo.is_some() { &[6, 8] } else { &[6] };
+								if !expected.contains(&positional.len()) {
+									bail!(
+										"incorrect number of position arguments to {command} {} (expected {expected:?}, got {})",
+										args[0],
+										positional.len(),
+									)
+								}
+
+								let size = unwrap!(args[3].parse());
+								let condition = match args[1] {
+									"NODE" => {
+										CountdownCondition::Node(nodes.index(args[2]).into())
+									},
+									"BLOCK" => {
+										CountdownCondition::Block(blocks.index(args[2]).into())
+									},
+									other => bail!("invalid counter condition {other}"),
+								};
+
+								let mut format = CountdownFormat::default();
+								let mut warn_below_secs = None;
+								let mut normal_style = None;
+								let mut warn_style = None;
+
+								for option in options {
+									match option.split_once('=') {
+										Some(("format", value)) => {
+											format = match value {
+												"mmss" => CountdownFormat::MinutesSeconds,
+												"secs" => CountdownFormat::Seconds,
+												"bar" => CountdownFormat::Bar,
+												other => bail!("unknown countdown format {other}"),
+											}
+										},
+										Some(("warn", value)) => {
+											warn_below_secs = Some(unwrap!(value.parse()))
+										},
+										Some(("style", color)) => {
+											normal_style = Some(color_style!(color))
+										},
+										Some(("warn_style", color)) => {
+											warn_style = Some(color_style!(color))
+										},
+										_ => bail!("unknown countdown option {option}"),
+									}
+								}
+
+								if let Some(geo) = &mut geo {
+									geo.widgets.push(Widget::Countdown {
+										position: attempt!(parse_coord(&positional[4..])),
+										size,
+										condition,
+										format,
+										warn_below_secs,
+										normal_style,
+										warn_style,
+									});
+								} else if let Some(map) = &mut map {
+									map.widgets.push(Widget::Countdown {
+										position: attempt!(parse_point(&positional[4..])),
+										size,
+										condition,
+										format,
+										warn_below_secs,
+										normal_style,
+										warn_style,
+									});
+								}
+							},
+							"LABEL" => {
+								check_args!(5..);
+
+								let holding_point = holding_points.index(args[1]).into();
+								let size = unwrap!(args[2].parse());
+
+								if let Some(geo) = &mut geo {
+									check_args!(5 | 7);
+
+									geo.widgets.push(Widget::Label {
+										position: attempt!(parse_coord(&args[3..])),
+										size,
+										holding_point,
+									});
+								} else if let Some(map) = &mut map {
+									check_args!(5);
+
+									map.widgets.push(Widget::Label {
+										position: attempt!(parse_point(&args[3..])),
+										size,
+										holding_point,
+									});
+								}
+							},
+							"TEXT" => {
+								check_args!(2..);
+
+								// the condition, if any, leads, as for countdowns
+								let (condition, rest) = match args[1] {
+									"NODE" | "BLOCK" => {
+										let Some(id) = args.get(2) else {
+											bail!("no id for {} condition", args[1])
+										};
+
+										let condition = if args[1] == "NODE" {
+											CountdownCondition::Node(nodes.index(*id).into())
+										} else {
+											CountdownCondition::Block(blocks.index(*id).into())
+										};
+
+										(Some(condition), &args[3..])
+									},
+									_ => (None, &args[1..]),
+								};
+
+								let expected: &[usize] =
+									if geo.is_some() { &[5, 7] } else { &[5] };
+								if !expected.contains(&rest.len()) {
+									bail!(
+										"incorrect number of arguments to {command} {} after any condition (expected {expected:?}, got {})",
+										args[0],
+										rest.len(),
+									)
+								}
+
+								let size = unwrap!(rest[0].parse());
+								let anchor = match rest[1] {
+									"C" => TextAnchor::Centre,
+									"L" => TextAnchor::Left,
+									"R" => TextAnchor::Right,
+									"T" => TextAnchor::Top,
+									"B" => TextAnchor::Bottom,
+									"TL" => TextAnchor::TopLeft,
+									"TR" => TextAnchor::TopRight,
+									"BL" => TextAnchor::BottomLeft,
+									"BR" => TextAnchor::BottomRight,
+									other => bail!("unknown text anchor {other}"),
+								};
+
+								let (position, text) = rest[2..].split_at(rest.len() - 3);
+								let text = text[0].to_string();
+
+								if let Some(geo) = &mut geo {
+									geo.widgets.push(Widget::Text {
+										position: attempt!(parse_coord(position)),
+										size,
+										anchor,
+										text,
+										color: stroke_color,
+										condition,
+									});
+								} else if let Some(map) = &mut map {
+									map.widgets.push(Widget::Text {
+										position: attempt!(parse_point(position)),
+										size,
+										anchor,
+										text,
+										color: stroke_color,
+										condition,
+									});
+								}
+							},
+							other => bail!("unknown widget type {other}"),
+						}
+					},
+					_ => bail!("unknown command {command}"),
+				}
+
+				Ok(())
+			};
+
+			if let Err(error) = result {
+				on_error(error)?;
 			}
 		}
 
 		Ok(maps)
 	}
 }
+
+/// Returns the points of a closed path around the unit circle, anticlockwise
+/// from the positive x axis, with the first point repeated at the end.
+fn unit_circle(segments: u16) -> impl Iterator<Item = [f32; 2]> {
+	(0..=segments).map(move |i| {
+		let angle = TAU * f32::from(i % segments) / f32::from(segments);
+		let (sin, cos) = angle.sin_cos();
+		[cos, sin]
+	})
+}
+
+/// Appends copies of the last of the paths, each moved one step further than
+/// the last, returning whether there was a path to copy.
+fn repeat_last<T: Projectable>(
+	paths: &mut Vec<Path<T>>,
+	count: u16,
+	step: impl Fn(&T, f32) -> T,
+) -> bool {
+	let Some(last) = paths.last().cloned() else {
+		return false
+	};
+
+	for n in 1..=count {
+		paths.push(Path {
+			points: (last.points.iter())
+				.map(|point| step(point, n as f32))
+				.collect(),
+			..last.clone()
+		});
+	}
+
+	true
+}
+
+/// Expands references to defined positions, as `@name` or `@name+DX,DY`, into
+/// the parts of their coordinates, the offset being added to points or to the
+/// offsets in pixels of coordinates.
+fn expand_references<'a>(
+	parts: &[&'a str],
+	definitions: &HashMap<String, Definition>,
+) -> Result<Vec<Cow<'a, str>>, String> {
+	let mut expanded = Vec::with_capacity(parts.len());
+
+	for part in parts {
+		let Some(reference) = part.strip_prefix('@') else {
+			expanded.push(Cow::Borrowed(*part));
+			continue
+		};
+
+		let (name, offset) = match reference.split_once('+') {
+			Some((name, offset)) => {
+				let (x, y) = (offset.split_once(','))
+					.ok_or_else(|| format!("expected @{name}+DX,DY, not {part}"))?;
+				let parse =
+					|part: &str| part.parse::<f32>().map_err(|err| err.to_string());
+				(
+					name,
+					Point {
+						x: parse(x)?,
+						y: parse(y)?,
+					},
+				)
+			},
+			None => (reference, Point::default()),
+		};
+
+		let coordinates = match definitions.get(name) {
+			Some(Definition::Coord(coord)) => vec![
+				coord.geo.lat,
+				coord.geo.lon,
+				coord.offset.x + offset.x,
+				coord.offset.y + offset.y,
+			],
+			Some(Definition::Point(point)) => {
+				vec![point.x + offset.x, point.y + offset.y]
+			},
+			None => return Err(format!("position {name} undefined")),
+		};
+
+		expanded.extend(coordinates.into_iter().map(|c| Cow::Owned(c.to_string())));
+	}
+
+	Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Returns the style of each path drawn when the node is off in the first
+	/// map, in order.
+	fn off_styles(maps: &Maps) -> Vec<&Style> {
+		(maps.maps[0].nodes[0].off.iter())
+			.map(|path| &maps.styles[path.style.0])
+			.collect()
+	}
+
+	/// Returns the message of the error loading the text.
+	fn error(text: &str) -> String {
+		Maps::load_topsky(text).unwrap_err().message
+	}
+
+	#[test]
+	fn styles_blink_when_given() {
+		let maps = Maps::load_topsky(
+			"COLORDEF:red:255:0:0
+			MAP
+			NODE:N1:OFF
+			STYLE:solid:2:blink=500/50
+			POINT:0:0
+			POINTLINE
+			STYLE:solid:2:cap=butt:blink=500/50/250
+			POINT:0:0
+			POINTLINE
+			STYLE:solid:2
+			POINT:0:0
+			POINTLINE
+			STYLE:dash:blink=500/50
+			POINT:0:0
+			POINTLINE",
+		)
+		.unwrap();
+
+		let blinks = off_styles(&maps).into_iter().map(|style| style.blink);
+		assert_eq!(
+			blinks.collect::<Vec<_>>(),
+			[
+				Some(Blink {
+					period_ms: 500,
+					duty_percent: 50,
+					phase_ms: 0,
+				}),
+				Some(Blink {
+					period_ms: 500,
+					duty_percent: 50,
+					phase_ms: 250,
+				}),
+				// cleared by the next style
+				None,
+				Some(Blink {
+					period_ms: 500,
+					duty_percent: 50,
+					phase_ms: 0,
+				}),
+			],
+		);
+
+		// styles alike but for blinking are kept apart
+		assert_eq!(maps.styles.len(), 4);
+		assert_eq!(maps.styles[0].stroke_width, maps.styles[2].stroke_width);
+	}
+
+	#[test]
+	fn blinks_must_be_whole() {
+		for (spec, message) in [
+			("500", "expected blink=PERIOD/DUTY[/PHASE]"),
+			("500/50/250/0", "expected blink=PERIOD/DUTY[/PHASE]"),
+			("500/101", "blink duty 101 above 100%"),
+			("500/x", "invalid digit found in string"),
+			("70000/50", "number too large to fit in target type"),
+		] {
+			let text = format!("STYLE:solid:blink={spec}");
+			assert_eq!(error(&text), message, "{spec}");
+		}
+	}
+
+	#[test]
+	fn countdowns_take_old_and_new_arguments() {
+		let maps = Maps::load_topsky(
+			"COLORDEF:white:255:255:255
+			COLORDEF:red:255:0:0
+			GEO
+			WIDGET:COUNTDOWN:NODE:N1:12:N051.00.00.000:W001.00.00.000
+			WIDGET:COUNTDOWN:BLOCK:B1:12:N051.00.00.000:W001.00.00.000:4:-2
+			WIDGET:COUNTDOWN:BLOCK:B1:12:51:-1:format=bar:warn=10:warn_style=red
+			MAP
+			WIDGET:COUNTDOWN:NODE:N1:14:10:20
+			WIDGET:COUNTDOWN:NODE:N2:14:10:20:format=secs:style=white:warn=5",
+		)
+		.unwrap();
+
+		let geo = &maps.geo_map.as_ref().unwrap().widgets;
+		let map = &maps.maps[0].widgets;
+		assert_eq!((geo.len(), map.len()), (3, 2));
+
+		let Widget::Countdown {
+			position,
+			size,
+			condition,
+			format,
+			warn_below_secs,
+			normal_style,
+			warn_style,
+		} = &geo[0]
+		else {
+			panic!("{:?} is not a countdown", geo[0]);
+		};
+		assert_eq!(position.geo.lat, 51.0);
+		assert_eq!(position.geo.lon, -1.0);
+		assert_eq!(position.offset, Point::default());
+		assert_eq!(*size, 12.0);
+		assert_eq!(*condition, CountdownCondition::Node(0.into()));
+		// those of old files are the defaults
+		assert_eq!(*format, CountdownFormat::MinutesSeconds);
+		assert_eq!(*warn_below_secs, None);
+		assert_eq!((*normal_style, *warn_style), (None, None));
+
+		let Widget::Countdown { position, .. } = &geo[1] else {
+			panic!("{:?} is not a countdown", geo[1]);
+		};
+		assert_eq!(position.offset, Point { x: 4.0, y: -2.0 });
+
+		let Widget::Countdown {
+			condition,
+			format,
+			warn_below_secs,
+			normal_style,
+			warn_style,
+			..
+		} = &geo[2]
+		else {
+			panic!("{:?} is not a countdown", geo[2]);
+		};
+		assert_eq!(*condition, CountdownCondition::Block(0.into()));
+		assert_eq!(*format, CountdownFormat::Bar);
+		assert_eq!(*warn_below_secs, Some(10));
+		assert_eq!(*normal_style, None);
+		let red = warn_style.unwrap();
+		assert_eq!(maps.styles[red.0].stroke_color.r, 255);
+		assert_eq!(maps.styles[red.0].stroke_color.g, 0);
+
+		let Widget::Countdown {
+			position,
+			condition,
+			format,
+			warn_below_secs,
+			normal_style,
+			warn_style,
+			..
+		} = &map[1]
+		else {
+			panic!("{:?} is not a countdown", map[1]);
+		};
+		assert_eq!(*position, Point { x: 10.0, y: 20.0 });
+		assert_eq!(*condition, CountdownCondition::Node(1.into()));
+		assert_eq!(*format, CountdownFormat::Seconds);
+		assert_eq!(*warn_below_secs, Some(5));
+		assert_eq!(maps.styles[normal_style.unwrap().0].stroke_color.g, 255);
+		assert_eq!(*warn_style, None);
+	}
+
+	#[test]
+	fn countdowns_reject_other_arguments() {
+		for (text, message) in [
+			(
+				"MAP\nWIDGET:COUNTDOWN:NODE:N1:14:10:20:4:-2",
+				"incorrect number of position arguments to WIDGET COUNTDOWN (expected [6], got 8)",
+			),
+			(
+				"GEO\nWIDGET:COUNTDOWN:NODE:N1:14:51:-1:4",
+				"incorrect number of position arguments to WIDGET COUNTDOWN (expected [6, 8], got 7)",
+			),
+			(
+				"MAP\nWIDGET:COUNTDOWN:NODE:N1:14:10",
+				"incorrect number of arguments to WIDGET (expected 6.., got 5)",
+			),
+			(
+				"MAP\nWIDGET:COUNTDOWN:NODE:N1:14:10:20:format=hours",
+				"unknown countdown format hours",
+			),
+			(
+				"MAP\nWIDGET:COUNTDOWN:NODE:N1:14:10:20:colour=red",
+				"unknown countdown option colour=red",
+			),
+			(
+				"MAP\nWIDGET:COUNTDOWN:NODE:N1:14:10:20:style=red",
+				"red undefined",
+			),
+			(
+				"MAP\nWIDGET:COUNTDOWN:EDGE:E1:14:10:20",
+				"invalid counter condition EDGE",
+			),
+		] {
+			assert_eq!(error(text), message, "{text}");
+		}
+	}
+
+	#[test]
+	fn tooltips_are_taken_whole() {
+		let maps = Maps::load_topsky(
+			"COORDDEF:home:51:-1
+			GEO
+			NODE:N1:TARGET
+			TOOLTIP:N1 CAT III stopbar: resets {reset} // not the text
+			BLOCK:B1:TARGET
+			TOOLTIP:@home:{state}
+			MAP
+			NODE:N2:TARGET
+			TOOLTIP:first
+			TOOLTIP:{label} ({state})",
+		)
+		.unwrap();
+
+		let geo = maps.geo_map.as_ref().unwrap();
+		assert_eq!(
+			geo.nodes[0].tooltip.as_deref(),
+			Some("N1 CAT III stopbar: resets {reset}"),
+		);
+		// references are not expanded in text
+		assert_eq!(geo.blocks[0].tooltip.as_deref(), Some("@home:{state}"));
+
+		// the last of a node's tooltips replaces the others
+		let map = &maps.maps[0];
+		assert_eq!(map.nodes[1].tooltip.as_deref(), Some("{label} ({state})"));
+		assert_eq!(map.nodes[0].tooltip, None);
+
+		for (text, message) in [
+			("TOOLTIP:text", "TOOLTIP outside map context"),
+			("MAP\nTOOLTIP:text", "TOOLTIP outside target context"),
+			(
+				"GEO\nNODE:N1:ON\nTOOLTIP:text",
+				"TOOLTIP outside target context",
+			),
+			(
+				"MAP\nNODE:N1:TARGET\nTOOLTIP",
+				"incorrect number of arguments to TOOLTIP (expected 1.., got 0)",
+			),
+		] {
+			assert_eq!(error(text), message, "{text}");
+		}
+	}
+
+	#[test]
+	fn tooltips_survive_attaching() {
+		use crate::fixture::{aerodrome, block};
+
+		// listed in another order than in the aerodrome, and with an unknown id
+		let maps = Maps::load_topsky(
+			"MAP
+			NODE:N2:TARGET
+			TOOLTIP:two
+			NODE:N9:TARGET
+			TOOLTIP:unknown
+			NODE:N0:TARGET
+			TOOLTIP:zero
+			BLOCK:B1:TARGET
+			TOOLTIP:block one",
+		)
+		.unwrap();
+
+		let mut aerodrome =
+			aerodrome(3, vec![block("B0", &[0], &[]), block("B1", &[1, 2], &[])]);
+		aerodrome.attach_maps(maps).unwrap();
+
+		let map = &aerodrome.maps[0];
+		let nodes = map.nodes.iter().map(|node| node.tooltip.as_deref());
+		assert_eq!(nodes.collect::<Vec<_>>(), [Some("zero"), None, Some("two")]);
+		let blocks = map.blocks.iter().map(|block| block.tooltip.as_deref());
+		assert_eq!(blocks.collect::<Vec<_>>(), [None, Some("block one")]);
+
+		// and decoding what was encoded
+		let encoded = aerodrome.encode().unwrap();
+		let decoded = Aerodrome::decode(&encoded).unwrap();
+		assert_eq!(decoded.maps[0].nodes[2].tooltip.as_deref(), Some("two"));
+		assert_eq!(
+			decoded.maps[0].blocks[1].tooltip.as_deref(),
+			Some("block one"),
+		);
+	}
+
+	#[test]
+	fn target_styles_survive_attaching() {
+		use crate::fixture::{aerodrome, block};
+
+		let mut aerodrome = aerodrome(2, vec![block("B0", &[0, 1], &[])]);
+
+		// styles already attached are kept before those of the maps
+		let base = Maps::load_topsky(
+			"COLORDEF:grey:128:128:128
+			MAP
+			NODE:N0:OFF
+			COLOR:grey
+			POINT:0:0
+			POINT:1:1
+			POINTLINE",
+		)
+		.unwrap();
+		aerodrome.attach_maps(base).unwrap();
+		let offset = aerodrome.styles.len();
+		assert_eq!(offset, 1);
+
+		let maps = Maps::load_topsky(
+			"COLORDEF:yellow:255:255:0
+			COLORDEF:green:0:255:0
+			GEO
+			NODE:N1:TARGET
+			COORD:51:-1
+			COORD:51.1:-1
+			COORD:51.1:-1.1
+			COORDTARGET:HOVERSTYLE=yellow:ACTIVESTYLE=green
+			MAP
+			BLOCK:B0:TARGET
+			POINT:0:0
+			POINT:0:1
+			POINT:1:1
+			POINTTARGET:ACTIVESTYLE=green
+			POINT:2:2
+			POINT:2:3
+			POINT:3:3
+			POINTTARGET:HOVERSTYLE=yellow
+			WIDGET:COUNTDOWN:NODE:N1:12:0:0:style=green:warn_style=yellow",
+		)
+		.unwrap();
+		let (yellow, green) = (maps.styles[0].clone(), maps.styles[1].clone());
+		aerodrome.attach_maps(maps).unwrap();
+
+		let style = |style: Option<Ref<Style>>| &aerodrome.styles[style.unwrap().0];
+
+		let geo = aerodrome.geo_map.as_ref().unwrap();
+		let target = &geo.nodes[1].target;
+		assert_eq!(target.hover_style, Some(offset.into()));
+		assert_eq!(target.active_style, Some((offset + 1).into()));
+		assert_eq!(style(target.hover_style), &yellow);
+		assert_eq!(style(target.active_style), &green);
+		assert_eq!(geo.nodes[0].target.styles().count(), 0);
+
+		// styles given for part of a target are kept for the rest
+		let target = &aerodrome.maps[1].blocks[0].target;
+		assert_eq!(target.polygons.len(), 2);
+		assert_eq!(style(target.hover_style), &yellow);
+		assert_eq!(style(target.active_style), &green);
+
+		let Widget::Countdown {
+			normal_style,
+			warn_style,
+			..
+		} = &aerodrome.maps[1].widgets[0]
+		else {
+			panic!("{:?} is not a countdown", aerodrome.maps[1].widgets[0]);
+		};
+		assert_eq!(style(*normal_style), &green);
+		assert_eq!(style(*warn_style), &yellow);
+
+		// all of which is valid
+		let issues = aerodrome.validate();
+		assert!(issues.is_empty(), "{issues:?}");
+	}
+
+	#[test]
+	fn attaching_twice_replaces_the_geo_map_and_repeats_maps() {
+		use crate::fixture::{aerodrome, block};
+
+		let maps = Maps::load_topsky(
+			"COLORDEF:grey:128:128:128
+			GEO
+			NODE:N0:OFF
+			COLOR:grey
+			COORD:51:-1
+			COORD:51.1:-1
+			COORDLINE
+			MAP
+			NODE:N1:OFF
+			COLOR:grey
+			POINT:0:0
+			POINT:1:1
+			POINTLINE",
+		)
+		.unwrap();
+
+		let mut aerodrome = aerodrome(2, vec![block("B0", &[0, 1], &[])]);
+		let first = aerodrome.attach_maps(maps.clone()).unwrap();
+		let points = |aerodrome: &Aerodrome| {
+			let geo_map = aerodrome.geo_map.as_ref().unwrap();
+			format!("{:?}", geo_map.nodes[0].off[0].points)
+		};
+		let drawn = points(&aerodrome);
+		let second = aerodrome.attach_maps(maps).unwrap();
+
+		// each attachment reports the same
+		assert_eq!(first.describe(&aerodrome), second.describe(&aerodrome));
+		assert!(second.is_empty(), "{:?}", second.describe(&aerodrome));
+
+		// the styles are appended again, and what was attached second uses them
+		assert_eq!(aerodrome.styles.len(), 2);
+		assert_eq!(aerodrome.styles[0], aerodrome.styles[1]);
+
+		// the geo map is replaced by one drawn the same, in the second styles
+		let geo_map = aerodrome.geo_map.as_ref().unwrap();
+		assert_eq!(geo_map.nodes[0].off[0].style, 1.into());
+		assert_eq!(points(&aerodrome), drawn);
+
+		// and each map is repeated
+		assert_eq!(aerodrome.maps.len(), 2);
+		let styles = aerodrome.maps.iter().map(|map| map.nodes[1].off[0].style);
+		assert_eq!(styles.collect::<Vec<_>>(), [0.into(), 1.into()]);
+
+		// maps without a geo map leave the one attached
+		let plain = Maps::load_topsky("MAP\nNODE:N0:TARGET").unwrap();
+		aerodrome.attach_maps(plain).unwrap();
+		assert_eq!(aerodrome.maps.len(), 3);
+		assert_eq!(aerodrome.geo_map.as_ref().unwrap().nodes[0].off.len(), 1);
+	}
+
+	/// A taxiway on the surface beneath a bridge, each drawn and targeted over
+	/// the same square, with a lead-on drawn on every level.
+	const STACKED: &str = "MAP
+		EDGE:E0:ON
+		LEVEL:0
+		POINT:0:0
+		POINT:10:0
+		POINTLINE
+		LEVEL:1
+		POINT:0:0
+		POINT:10:0
+		POINTLINE
+		LEVEL:ALL
+		POINT:0:5
+		POINT:10:5
+		POINTLINE
+		NODE:N0:TARGET
+		LEVEL:0
+		POINT:0:0
+		POINT:10:0
+		POINT:10:10
+		POINT:0:10
+		POINTTARGET
+		NODE:N1:TARGET
+		LEVEL:1
+		POINT:0:0
+		POINT:10:0
+		POINT:10:10
+		POINT:0:10
+		POINTTARGET
+		LEVEL:ALL
+		NODE:N2:TARGET
+		POINT:20:0
+		POINT:30:0
+		POINT:30:10
+		POINT:20:10
+		POINTTARGET";
+
+	#[test]
+	fn paths_are_filtered_by_level() {
+		let maps = Maps::load_topsky(STACKED).unwrap();
+		let map = &maps.maps[0];
+		assert_eq!(map.levels().into_iter().collect::<Vec<_>>(), [0, 1]);
+
+		let levels = |selected| {
+			(map.edges[0].paths_on_level(selected))
+				.map(|path| path.level)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(levels(None), [Some(0), Some(1), None]);
+		assert_eq!(levels(Some(0)), [Some(0), None]);
+		assert_eq!(levels(Some(1)), [Some(1), None]);
+		assert_eq!(levels(Some(2)), [None]);
+
+		let targets = map.nodes.iter().map(|node| node.target.level);
+		assert_eq!(targets.collect::<Vec<_>>(), [Some(0), Some(1), None]);
+
+		// levels are kept through saving
+		let mut saved = Vec::new();
+		maps.save(&mut saved).unwrap();
+		let loaded = Maps::load(&saved[..]).unwrap();
+		assert_eq!(loaded.maps[0].levels(), map.levels());
+	}
+
+	#[test]
+	fn hits_prefer_the_level_selected() {
+		let maps = Maps::load_topsky(STACKED).unwrap();
+		let map = &maps.maps[0];
+		let index = map.build_index();
+		let hit = |x, level| map.hit_on_level(&index, Point { x, y: 5.0 }, level);
+		let node = |i: usize| Some(HitTarget::Node(i.into()));
+
+		assert_eq!(hit(5.0, Some(0)), node(0));
+		assert_eq!(hit(5.0, Some(1)), node(1));
+		// either, without a level or on another, rather than nothing
+		assert!([node(0), node(1)].contains(&hit(5.0, None)));
+		assert_eq!(hit(5.0, None), map.hit(&index, Point { x: 5.0, y: 5.0 }));
+		assert!([node(0), node(1)].contains(&hit(5.0, Some(2))));
+
+		// and targets on every level are hit on each
+		for level in [None, Some(0), Some(1)] {
+			assert_eq!(hit(25.0, level), node(2));
+			assert_eq!(hit(40.0, level), None);
+		}
+	}
+
+	#[test]
+	fn alpha_fades_later_paths() {
+		let maps = Maps::load_topsky(
+			"COLORDEF:red:255:0:0
+			COLORDEF:blue:0:0:255
+			MAP
+			NODE:N1:OFF
+			COLOR:red:blue
+			POINT:0:0
+			POINTLINE
+			ALPHA:50
+			POINT:0:0
+			POINTLINE
+			NODE:N1:ON
+			COLOR:red:blue
+			POINT:0:0
+			POINTLINE
+			ALPHA:0
+			POINT:0:0
+			POINTLINE
+			MAP
+			NODE:N1:OFF
+			COLOR:red:blue
+			POINT:0:0
+			POINTLINE",
+		)
+		.unwrap();
+
+		let alphas = (maps.styles.iter())
+			.map(|style| (style.stroke_color.a, style.fill_color.a))
+			.collect::<Vec<_>>();
+		assert_eq!(alphas, [(255, 255), (128, 128), (0, 0)]);
+		assert_eq!(maps.styles[1].stroke_color.r, 255);
+		assert_eq!(maps.styles[1].fill_color.b, 255);
+
+		// kept across groups and colours, until the next map
+		let styles = |paths: &[Path<Point>]| {
+			paths.iter().map(|path| path.style.0).collect::<Vec<_>>()
+		};
+		assert_eq!(styles(&maps.maps[0].nodes[0].off), [0, 1]);
+		assert_eq!(styles(&maps.maps[0].nodes[0].on), [1, 2]);
+		assert_eq!(styles(&maps.maps[1].nodes[0].off), [0]);
+
+		assert_eq!(error("ALPHA:101"), "alpha 101 above 100%");
+	}
+
+	#[test]
+	fn repeats_copy_the_last_path() {
+		let maps = Maps::load_topsky(
+			"COLORDEF:white:255:255:255
+			MAP
+			EDGE:E0:ON
+			COLOR:white
+			POINT:0:0
+			POINT:1:0
+			POINTLINE
+			STYLE:dash
+			POINT:0:0
+			POINT:0:1
+			POINTLINE
+			REPEAT:3:10:-5
+			REPEAT:0:99:99
+			GEO
+			EDGE:E0:ON
+			COORD:51:-1
+			COORD:51:-1:2:2
+			COORDLINE
+			REPEAT:2:0.5:-0.25
+			REPEAT:1:1:1:4:-4",
+		)
+		.unwrap();
+
+		// the path before the last is not repeated
+		let paths = &maps.maps[0].edges[0].on;
+		assert_eq!(paths.len(), 5);
+		let style = paths[1].style;
+		assert_ne!(paths[0].style, style);
+
+		for (n, path) in paths[1..].iter().enumerate() {
+			let n = n as f32;
+			let expected = [
+				Point {
+					x: 10.0 * n,
+					y: -5.0 * n,
+				},
+				Point {
+					x: 10.0 * n,
+					y: 1.0 - 5.0 * n,
+				},
+			];
+			assert_eq!(path.points, expected, "copy {n}");
+			assert_eq!(path.style, style);
+		}
+
+		// copies of copies, in latitude and longitude and then in pixels
+		let paths = &maps.geo_map.as_ref().unwrap().edges[0].on;
+		let firsts = (paths.iter())
+			.map(|path| (path.points[0].geo, path.points[1].offset))
+			.collect::<Vec<_>>();
+		let geo = |lat, lon| Geo { lat, lon };
+		assert_eq!(
+			firsts,
+			[
+				(geo(51.0, -1.0), Point { x: 2.0, y: 2.0 }),
+				(geo(51.5, -1.25), Point { x: 2.0, y: 2.0 }),
+				(geo(52.0, -1.5), Point { x: 2.0, y: 2.0 }),
+				(geo(53.0, -0.5), Point { x: 6.0, y: -2.0 }),
+			],
+		);
+		assert!(paths.iter().all(|path| path.style == paths[0].style));
+
+		for (text, message) in [
+			(
+				"MAP\nEDGE:E0:ON\nREPEAT:2:1:1",
+				"REPEAT without a preceding path in the group",
+			),
+			(
+				"MAP\nEDGE:E0:ON\nPOINT:0:0\nPOINTLINE\nEDGE:E0:OFF\nREPEAT:2:1:1",
+				"REPEAT without a preceding path in the group",
+			),
+			("MAP\nREPEAT:2:1:1", "REPEAT outside draw context"),
+			("REPEAT:2:1:1", "REPEAT outside map context"),
+			(
+				"MAP\nEDGE:E0:ON\nREPEAT:2:1:1:1:1",
+				"incorrect number of arguments to REPEAT (expected 3, got 5)",
+			),
+			(
+				"GEO\nEDGE:E0:ON\nREPEAT:2:1:1:1",
+				"incorrect number of arguments to REPEAT (expected 3 | 5, got 4)",
+			),
+		] {
+			assert_eq!(error(text), message, "{text}");
+		}
+	}
+
+	#[test]
+	fn offsets_move_later_points() {
+		let maps = Maps::load_topsky(
+			"MAP
+			OFFSETSET:10:20
+			EDGE:E0:ON
+			POINT:1:1
+			OFFSETSET:100:200
+			POINT:1:1
+			OFFSETADD:1:2
+			POINT:1:1
+			POINTLINE
+			NODE:N0:TARGET
+			POINT:1:1
+			OFFSETCLEAR
+			POINT:1:1
+			POINTTARGET
+			OFFSETSET:5:5
+			MAP
+			EDGE:E0:ON
+			POINT:1:1
+			POINTLINE
+			GEO
+			OFFSETSET:0.5:-0.25
+			EDGE:E0:ON
+			COORD:51:-1
+			COORD:51:-1:3:4
+			COORDLINE
+			OFFSETADD:1:1
+			COORD:51:-1
+			COORDLINE",
+		)
+		.unwrap();
+
+		let point = |x, y| Point { x, y };
+		let map = &maps.maps[0];
+		// set, set again replacing it, and then added to
+		assert_eq!(
+			map.edges[0].on[0].points,
+			[point(11.0, 21.0), point(101.0, 201.0), point(102.0, 203.0)],
+		);
+		// kept across groups until cleared
+		assert_eq!(
+			map.nodes[0].target.polygons[0],
+			[point(102.0, 203.0), point(1.0, 1.0)],
+		);
+		// and until the next map
+		assert_eq!(maps.maps[1].edges[0].on[0].points, [point(1.0, 1.0)]);
+
+		// in the geo map, to latitude and longitude, leaving offsets in pixels
+		let paths = &maps.geo_map.as_ref().unwrap().edges[0].on;
+		let points = (paths.iter().flat_map(|path| &path.points))
+			.map(|point| (point.geo.lat, point.geo.lon, point.offset))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			points,
+			[
+				(51.5, -1.25, point(0.0, 0.0)),
+				(51.5, -1.25, point(3.0, 4.0)),
+				(52.5, -0.25, point(0.0, 0.0)),
+			],
+		);
+	}
+
+	/// Returns the points of the path drawn for each line of commands after
+	/// the first, in the first map, when the edge is on.
+	fn transformed(commands: &[&str]) -> Vec<Vec<Point>> {
+		let mut text = String::from("MAP\nEDGE:E0:ON\n");
+		for line in commands {
+			text.push_str(line);
+			text.push_str("\nPOINT:1:0\nPOINT:3:2\nPOINTLINE\n");
+		}
+
+		let maps = Maps::load_topsky(&text).unwrap();
+		(maps.maps[0].edges[0].on.iter())
+			.map(|path| path.points.clone())
+			.collect()
+	}
+
+	#[test]
+	fn transforms_turn_and_mirror_later_points() {
+		let point = |x, y| Point { x, y };
+		let table: &[(&[&str], [Point; 2])] = &[
+			(&["ROTATE:90"], [point(0.0, 1.0), point(-2.0, 3.0)]),
+			(&["ROTATE:-270"], [point(0.0, 1.0), point(-2.0, 3.0)]),
+			(&["ROTATE:180"], [point(-1.0, 0.0), point(-3.0, -2.0)]),
+			(&["ROTATE:180:2:1"], [point(3.0, 2.0), point(1.0, 0.0)]),
+			(&["ROTATE:90:1:0"], [point(1.0, 0.0), point(-1.0, 2.0)]),
+			(&["MIRROR:X"], [point(-1.0, 0.0), point(-3.0, 2.0)]),
+			(&["MIRROR:X:5"], [point(9.0, 0.0), point(7.0, 2.0)]),
+			(&["MIRROR:Y:-1.5"], [point(1.0, -3.0), point(3.0, -5.0)]),
+			// composed in order, so turned and then mirrored
+			(
+				&["ROTATE:90\nMIRROR:Y:1"],
+				[point(0.0, 1.0), point(-2.0, -1.0)],
+			),
+			(
+				&["MIRROR:Y:1\nROTATE:90"],
+				[point(-2.0, 1.0), point(0.0, 3.0)],
+			),
+			// two mirrors across parallel axes move by twice their distance
+			(
+				&["MIRROR:X:1\nMIRROR:X:4"],
+				[point(7.0, 0.0), point(9.0, 2.0)],
+			),
+			// offsets are added once transformed
+			(
+				&["OFFSETSET:10:0\nROTATE:90"],
+				[point(10.0, 1.0), point(8.0, 3.0)],
+			),
+			(
+				&["ROTATE:90\nTRANSFORMCLEAR"],
+				[point(1.0, 0.0), point(3.0, 2.0)],
+			),
+		];
+
+		for (commands, expected) in table {
+			let paths = transformed(commands);
+			assert_eq!(paths.last().unwrap(), expected, "{commands:?}");
+		}
+
+		// kept for later paths, and across groups, until cleared
+		let paths = transformed(&["ROTATE:180", "", "TRANSFORMCLEAR"]);
+		assert_eq!(paths[1], [point(-1.0, 0.0), point(-3.0, -2.0)]);
+		assert_eq!(paths[2], [point(1.0, 0.0), point(3.0, 2.0)]);
+
+		// circles are turned about their centres with the rest
+		let maps = Maps::load_topsky(
+			"MAP
+			EDGE:E0:ON
+			MIRROR:X:10
+			POINTCIRCLE:2:0:1:0:4",
+		)
+		.unwrap();
+		let points = &maps.maps[0].edges[0].on[0].points;
+		let expected = [(17.0, 0.0), (18.0, 1.0), (19.0, 0.0), (18.0, -1.0)];
+		for (point, (x, y)) in points.iter().zip(expected) {
+			assert!((point.x - x).abs() < 1e-5, "{point:?}");
+			assert!((point.y - y).abs() < 1e-5, "{point:?}");
+		}
+
+		for (text, message) in [
+			("GEO\nROTATE:90", "ROTATE unsupported in the geo map"),
+			("GEO\nMIRROR:X", "MIRROR unsupported in the geo map"),
+			("MAP\nMIRROR:Z", "unknown mirror axis Z"),
+			(
+				"MAP\nROTATE:90:1",
+				"incorrect number of arguments to ROTATE (expected 1 | 3, got 2)",
+			),
+		] {
+			assert_eq!(error(text), message, "{text}");
+		}
+	}
+
+	#[test]
+	fn definitions_are_referenced_by_name() {
+		let maps = Maps::load_topsky(
+			"COORDDEF:thr:N051.00.00.000:W001.00.00.000:1:1
+			POINTDEF:stand:10:20
+			GEO
+			EDGE:E0:ON
+			COORD:@thr
+			COORD:@thr+2,-3
+			COORDDEF:thr:52:-2
+			COORD:@thr
+			COORDLINE
+			WIDGET:COUNTDOWN:NODE:N1:12:@thr+0,5
+			MAP
+			EDGE:E0:ON
+			POINT:@stand
+			POINT:@stand+-0.5,1
+			POINTLINE
+			WIDGET:LABEL:A1:10:@stand",
+		)
+		.unwrap();
+
+		let point = |x, y| Point { x, y };
+		let geo = maps.geo_map.as_ref().unwrap();
+		let points = (geo.edges[0].on[0].points.iter())
+			.map(|point| (point.geo.lat, point.geo.lon, point.offset))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			points,
+			[
+				(51.0, -1.0, point(1.0, 1.0)),
+				// offsets are added to those in pixels
+				(51.0, -1.0, point(3.0, -2.0)),
+				// and definitions replaced for the lines after
+				(52.0, -2.0, point(0.0, 0.0)),
+			],
+		);
+		let position = geo.widgets[0].position();
+		assert_eq!((position.geo.lat, position.geo.lon), (52.0, -2.0));
+		assert_eq!(position.offset, point(0.0, 5.0));
+
+		let map = &maps.maps[0];
+		assert_eq!(
+			map.edges[0].on[0].points,
+			[point(10.0, 20.0), point(9.5, 21.0)],
+		);
+		assert_eq!(*map.widgets[0].position(), point(10.0, 20.0));
+
+		// and shared with later files
+		let maps = Maps::load_topsky_files([
+			("defs.txt", "POINTDEF:stand:10:20"),
+			("map.txt", "MAP\nEDGE:E0:ON\nPOINT:@stand\nPOINTLINE"),
+		])
+		.unwrap();
+		assert_eq!(maps.maps[0].edges[0].on[0].points, [point(10.0, 20.0)]);
+
+		for (text, line, message) in [
+			("MAP\nEDGE:E0:ON\nPOINT:@gate", 3, "position gate undefined"),
+			(
+				"POINTDEF:gate:1:1\nMAP\nPOINT:@gate+1",
+				3,
+				"expected @gate+DX,DY, not @gate+1",
+			),
+			(
+				"POINTDEF:gate:1:1\nMAP\nPOINT:@gate+x,1",
+				3,
+				"invalid float literal",
+			),
+			// defined after use
+			(
+				"MAP\nPOINT:@gate\nPOINTDEF:gate:1:1",
+				2,
+				"position gate undefined",
+			),
+		] {
+			let error = Maps::load_topsky(text).unwrap_err();
+			assert_eq!((error.line, error.message.as_str()), (line, message));
+		}
+	}
+
+	#[test]
+	fn text_widgets_are_placed_and_conditional() {
+		use crate::fixture::{aerodrome, block};
+
+		let maps = Maps::load_topsky(
+			"COLORDEF:white:255:255:255
+			COLORDEF:amber:255:191:0
+			GEO
+			COLOR:amber
+			WIDGET:TEXT:10:TL:51:-1:Stand 5
+			WIDGET:TEXT:NODE:N1:8:C:51:-1:0:-12:{label} {state}
+			MAP
+			COLOR:white
+			WIDGET:TEXT:BLOCK:B0:12:BR:100:50:{label}
+			WIDGET:TEXT:NODE:N9:12:R:0:0:unknown",
+		)
+		.unwrap();
+
+		let geo = &maps.geo_map.as_ref().unwrap().widgets;
+		let Widget::Text {
+			position,
+			size,
+			anchor,
+			text,
+			color,
+			condition,
+		} = &geo[0]
+		else {
+			panic!("{:?} is not text", geo[0]);
+		};
+		assert_eq!((position.geo.lat, position.geo.lon), (51.0, -1.0));
+		assert_eq!(*size, 10.0);
+		assert_eq!(*anchor, TextAnchor::TopLeft);
+		assert_eq!(text, "Stand 5");
+		assert_eq!((color.r, color.g, color.b), (255, 191, 0));
+		assert_eq!(*condition, None);
+
+		let Widget::Text {
+			position,
+			anchor,
+			text,
+			condition,
+			..
+		} = &geo[1]
+		else {
+			panic!("{:?} is not text", geo[1]);
+		};
+		assert_eq!(position.offset, Point { x: 0.0, y: -12.0 });
+		assert_eq!(*anchor, TextAnchor::Centre);
+		assert_eq!(text, "{label} {state}");
+		assert_eq!(*condition, Some(CountdownCondition::Node(0.into())));
+
+		// conditions are rebased onto the aerodrome, and text of those not in
+		// it dropped
+		let mut aerodrome =
+			aerodrome(3, vec![block("B9", &[], &[]), block("B0", &[0], &[])]);
+		aerodrome.attach_maps(maps).unwrap();
+
+		let widgets = &aerodrome.maps[0].widgets;
+		assert_eq!(widgets.len(), 1);
+		let Widget::Text {
+			position,
+			anchor,
+			color,
+			condition,
+			..
+		} = &widgets[0]
+		else {
+			panic!("{:?} is not text", widgets[0]);
+		};
+		assert_eq!(*position, Point { x: 100.0, y: 50.0 });
+		assert_eq!(*anchor, TextAnchor::BottomRight);
+		assert_eq!(color.g, 255);
+		assert_eq!(*condition, Some(CountdownCondition::Block(1.into())));
+
+		let geo = &aerodrome.geo_map.as_ref().unwrap().widgets;
+		assert_eq!(geo.len(), 2);
+		let Widget::Text { condition, .. } = &geo[1] else {
+			panic!("{:?} is not text", geo[1]);
+		};
+		assert_eq!(*condition, Some(CountdownCondition::Node(1.into())));
+
+		for (text, message) in [
+			("MAP\nWIDGET:TEXT:12:X:0:0:text", "unknown text anchor X"),
+			("MAP\nWIDGET:TEXT:NODE", "no id for NODE condition"),
+			(
+				"MAP\nWIDGET:TEXT:12:C:0:0:0:0:text",
+				"incorrect number of arguments to WIDGET TEXT after any condition (expected [5], got 7)",
+			),
+			(
+				"GEO\nWIDGET:TEXT:BLOCK:B0:12:C:51:-1:0:text",
+				"incorrect number of arguments to WIDGET TEXT after any condition (expected [5, 7], got 6)",
+			),
+			("WIDGET:TEXT:12:C:0:0:text", "WIDGET outside map context"),
+		] {
+			assert_eq!(error(text), message, "{text}");
+		}
+	}
+
+	#[test]
+	fn files_are_merged_in_order() {
+		let colours = "COLORDEF:white:255:255:255\nCOLORDEF:red:255:0:0";
+		let north = "MAP
+			COLOR:white
+			NODE:N1:ON
+			POINT:0:0
+			POINTLINE
+			EDGE:E1:ON
+			POINT:0:0
+			POINTLINE";
+		let south = "// the same colour, node and edge as the north
+			MAP
+			COLOR:white
+			NODE:N2:ON
+			POINT:0:0
+			POINTLINE
+			NODE:N1:OFF
+			COLOR:red
+			POINT:0:0
+			POINTLINE
+			GEO
+			EDGE:E1:OFF
+			COORD:51:-1
+			COORDLINE";
+		let files = [
+			("colours.txt", colours),
+			("north.txt", north),
+			("south.txt", south),
+		];
+		let maps = Maps::load_topsky_files(files).unwrap();
+
+		assert_eq!(maps.maps.len(), 2);
+		assert!(maps.geo_map.is_some());
+		assert_eq!(maps.nodes, ["N1", "N2"]);
+		assert_eq!(maps.edges, ["E1"]);
+		assert_eq!(maps.styles.len(), 2);
+
+		let second = &maps.maps[1];
+		assert_eq!(
+			second.nodes[1].on[0].style,
+			maps.maps[0].nodes[0].on[0].style,
+		);
+		assert_eq!(
+			maps.styles[second.nodes[0].off[0].style.0].stroke_color.g,
+			0,
+		);
+
+		// which is as if the files had been one
+		let whole = Maps::load_topsky(&[colours, north, south].join("\n")).unwrap();
+		assert_eq!((whole.nodes, whole.styles), (maps.nodes, maps.styles));
+
+		// errors name the file, counting lines within it
+		let geo = "GEO\nEDGE:E1:ON";
+		let error = Maps::load_topsky_files([
+			("colours.txt", colours),
+			("first.txt", geo),
+			("second.txt", geo),
+		])
+		.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"second.txt: line 1: geo map already defined"
+		);
+		assert_eq!(
+			Maps::load_topsky("MAP\nCOLOR:blue")
+				.unwrap_err()
+				.to_string(),
+			"line 2: blue undefined",
+		);
+
+		let (maps, errors) = Maps::load_topsky_files_lenient([
+			("a.txt", "MAP\nCOLOR:red\nNODE:N1:ON"),
+			("b.txt", colours),
+			("c.txt", "\n\nCOLOR:red\nFOO"),
+		])
+		.unwrap();
+		assert_eq!(maps.nodes, ["N1"]);
+		let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+		assert_eq!(
+			errors,
+			[
+				"a.txt: line 2: red undefined",
+				"c.txt: line 4: unknown command FOO"
+			],
+		);
+	}
+}
@@ -0,0 +1,984 @@
+use super::*;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+impl Display for Severity {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		match self {
+			Self::Warning => write!(f, "warning"),
+			Self::Error => write!(f, "error"),
+		}
+	}
+}
+
+/// A problem found in an aerodrome by validation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+	pub icao: String,
+	/// path to the offending item, such as `profiles[0].nodes[3]`
+	pub location: String,
+	pub kind: IssueKind,
+}
+
+impl Issue {
+	pub fn severity(&self) -> Severity {
+		self.kind.severity()
+	}
+
+	pub fn code(&self) -> &'static str {
+		self.kind.code()
+	}
+}
+
+impl Display for Issue {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		write!(
+			f,
+			"{}: {}: {}: {}",
+			self.severity(),
+			self.icao,
+			self.location,
+			self.kind,
+		)
+	}
+}
+
+impl Error for Issue {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+	RefOutOfBounds {
+		target: &'static str,
+		index: usize,
+		len: usize,
+	},
+	ConditionCount {
+		target: &'static str,
+		expected: usize,
+		found: usize,
+	},
+	DisplayCount {
+		target: &'static str,
+		expected: usize,
+		found: usize,
+	},
+	DuplicateId {
+		target: &'static str,
+		id: String,
+	},
+	ChildInBlock {
+		node: String,
+	},
+	ParentCycle {
+		node: String,
+	},
+	PresetCycle {
+		presets: Vec<String>,
+	},
+	/// an edge expression referring to an edge driven by an expression
+	NestedExpression {
+		edge: String,
+	},
+	NonRouteOutsideBlock {
+		node: String,
+	},
+	UnblockedNode {
+		node: String,
+	},
+	EmptyBlock,
+	AmbiguousStand {
+		stand: String,
+		blocks: Vec<String>,
+	},
+	UnusedEdge {
+		edge: String,
+	},
+	HoldingPointOutsideBlock {
+		holding_point: String,
+	},
+	ProviderConditionMismatch {
+		id: String,
+	},
+	DanglingString {
+		key: String,
+	},
+	EmptyView,
+}
+
+impl IssueKind {
+	pub fn severity(&self) -> Severity {
+		match self {
+			Self::RefOutOfBounds { .. }
+			| Self::ConditionCount { .. }
+			| Self::DisplayCount { .. }
+			| Self::DuplicateId { .. }
+			| Self::ChildInBlock { .. }
+			| Self::ParentCycle { .. }
+			| Self::PresetCycle { .. }
+			| Self::NestedExpression { .. } => Severity::Error,
+			Self::NonRouteOutsideBlock { .. }
+			| Self::UnblockedNode { .. }
+			| Self::EmptyBlock
+			| Self::AmbiguousStand { .. }
+			| Self::UnusedEdge { .. }
+			| Self::HoldingPointOutsideBlock { .. }
+			| Self::ProviderConditionMismatch { .. }
+			| Self::DanglingString { .. }
+			| Self::EmptyView => Severity::Warning,
+		}
+	}
+
+	/// Returns a stable identifier for the kind of issue.
+	pub fn code(&self) -> &'static str {
+		match self {
+			Self::RefOutOfBounds { .. } => "ref-bounds",
+			Self::ConditionCount { .. } => "condition-count",
+			Self::DisplayCount { .. } => "display-count",
+			Self::DuplicateId { .. } => "duplicate-id",
+			Self::ChildInBlock { .. } => "block-child",
+			Self::ParentCycle { .. } => "parent-cycle",
+			Self::PresetCycle { .. } => "preset-cycle",
+			Self::NestedExpression { .. } => "nested-expression",
+			Self::NonRouteOutsideBlock { .. } => "non-route-outside-block",
+			Self::UnblockedNode { .. } => "unblocked-node",
+			Self::EmptyBlock => "empty-block",
+			Self::AmbiguousStand { .. } => "ambiguous-stand",
+			Self::UnusedEdge { .. } => "unused-edge",
+			Self::HoldingPointOutsideBlock { .. } => "holding-point-outside-block",
+			Self::ProviderConditionMismatch { .. } => "provider-condition-mismatch",
+			Self::DanglingString { .. } => "dangling-string",
+			Self::EmptyView => "empty-view",
+		}
+	}
+}
+
+impl Display for IssueKind {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		match self {
+			Self::RefOutOfBounds { target, index, len } => {
+				write!(f, "{target} {index} out of bounds (of {len})")
+			},
+			Self::ConditionCount {
+				target,
+				expected,
+				found,
+			} => write!(f, "expected {expected} {target} conditions, found {found}"),
+			Self::DisplayCount {
+				target,
+				expected,
+				found,
+			} => write!(f, "expected {expected} {target} displays, found {found}"),
+			Self::DuplicateId { target, id } => {
+				write!(f, "duplicate {target} id {id}")
+			},
+			Self::ChildInBlock { node } => write!(f, "child node {node} in block"),
+			Self::ParentCycle { node } => {
+				write!(f, "node {node} is its own ancestor")
+			},
+			Self::PresetCycle { presets } => {
+				write!(f, "preset includes itself via {}", presets.join(", "))
+			},
+			Self::NestedExpression { edge } => {
+				write!(f, "expression refers to edge {edge}, itself an expression")
+			},
+			Self::NonRouteOutsideBlock { node } => {
+				write!(f, "non-route node {node} not in block")
+			},
+			Self::UnblockedNode { node } => write!(f, "node {node} not in any block"),
+			Self::EmptyBlock => write!(f, "block has no nodes"),
+			Self::AmbiguousStand { stand, blocks } => {
+				write!(f, "stand {stand} matched equally by {}", blocks.join(", "))
+			},
+			Self::UnusedEdge { edge } => write!(f, "edge {edge} has no elements"),
+			Self::HoldingPointOutsideBlock { holding_point } => {
+				write!(f, "holding point {holding_point} not within a block")
+			},
+			Self::ProviderConditionMismatch { id } => {
+				write!(f, "elements for {id} differ in condition between providers")
+			},
+			Self::DanglingString { key } => {
+				write!(f, "string {key} is not for any entity")
+			},
+			Self::EmptyView => write!(f, "view has empty bounds"),
+		}
+	}
+}
+
+impl Config {
+	/// Checks every aerodrome for problems, returning all found.
+	pub fn validate(&self) -> Vec<Issue> {
+		self
+			.aerodromes
+			.iter()
+			.flat_map(|aerodrome| aerodrome.validate())
+			.collect()
+	}
+}
+
+impl Aerodrome {
+	/// Checks the aerodrome and its maps for problems, returning all found.
+	pub fn validate(&self) -> Vec<Issue> {
+		let mut validator = Validator {
+			aerodrome: self,
+			issues: Vec::new(),
+		};
+
+		validator.validate();
+		validator.issues
+	}
+}
+
+struct Validator<'a> {
+	aerodrome: &'a Aerodrome,
+	issues: Vec<Issue>,
+}
+
+impl Validator<'_> {
+	fn push(&mut self, location: impl Into<String>, kind: IssueKind) {
+		self.issues.push(Issue {
+			icao: self.aerodrome.icao.clone(),
+			location: location.into(),
+			kind,
+		});
+	}
+
+	fn check_ref<T>(
+		&mut self,
+		location: impl Into<String>,
+		target: &'static str,
+		index: Ref<T>,
+		len: usize,
+	) -> bool {
+		if index.0 < len {
+			return true
+		}
+
+		let kind = IssueKind::RefOutOfBounds {
+			target,
+			index: index.0,
+			len,
+		};
+
+		self.push(location, kind);
+		false
+	}
+
+	fn check_node(
+		&mut self,
+		location: impl Into<String>,
+		node: Ref<Node>,
+	) -> bool {
+		self.check_ref(location, "node", node, self.aerodrome.nodes.len())
+	}
+
+	/// Checks the refs of the terms of an expression, and the nodes of routes
+	/// against their blocks.
+	fn check_expression(
+		&mut self,
+		location: String,
+		expression: &ConditionExpression,
+	) {
+		let aerodrome = self.aerodrome;
+
+		for (i, conjunction) in expression.disjunction.iter().enumerate() {
+			let location = format!("{location}.disjunction[{i}]");
+			for term in conjunction.positive.iter().chain(&conjunction.negative) {
+				match term {
+					ConditionTerm::Node(node) => {
+						self.check_node(location.clone(), *node);
+					},
+					ConditionTerm::Edge(edge) => {
+						let len = aerodrome.edges.len();
+						self.check_ref(location.clone(), "edge", *edge, len);
+					},
+					ConditionTerm::Block(block, predicate) => {
+						let len = aerodrome.blocks.len();
+						if !self.check_ref(location.clone(), "block", *block, len) {
+							continue
+						}
+
+						if let BlockPredicate::Route((from, to)) = predicate {
+							let route = BlockRoute {
+								from: *from,
+								to: *to,
+							};
+							let block = &aerodrome.blocks[block.0];
+							self.validate_route(location.clone(), block, &route);
+						}
+					},
+				}
+			}
+		}
+	}
+
+	fn check_ids<'b>(
+		&mut self,
+		target: &'static str,
+		ids: impl Iterator<Item = &'b String>,
+	) {
+		let mut seen = HashSet::new();
+		for (i, id) in ids.enumerate() {
+			if !seen.insert(id) {
+				let kind = IssueKind::DuplicateId {
+					target,
+					id: id.clone(),
+				};
+
+				self.push(format!("{target}s[{i}]"), kind);
+			}
+		}
+	}
+
+	fn check_count(
+		&mut self,
+		location: impl Into<String>,
+		kind: impl Fn(usize, usize) -> IssueKind,
+		expected: usize,
+		found: usize,
+	) {
+		if expected != found {
+			self.push(location, kind(expected, found));
+		}
+	}
+
+	fn validate(&mut self) {
+		let aerodrome = self.aerodrome;
+
+		self.check_ids("element", aerodrome.elements.iter().map(|e| &e.id));
+		self.check_ids("node", aerodrome.nodes.iter().map(|n| &n.id));
+		self.check_ids("edge", aerodrome.edges.iter().map(|e| &e.id));
+		self.check_ids("block", aerodrome.blocks.iter().map(|b| &b.id));
+		self.check_ids(
+			"holding_point",
+			aerodrome.holding_points.iter().map(|h| &h.id),
+		);
+		self.check_ids("profile", aerodrome.profiles.iter().map(|p| &p.id));
+
+		self.validate_elements();
+		self.validate_nodes();
+		self.validate_blocks();
+		self.validate_stands();
+		self.validate_holding_points();
+		self.validate_profiles();
+		self.validate_strings();
+		self.validate_maps();
+	}
+
+	fn validate_elements(&mut self) {
+		let aerodrome = self.aerodrome;
+		let mut used_edges = HashSet::new();
+
+		for (i, element) in aerodrome.elements.iter().enumerate() {
+			let location = format!("elements[{i}].condition");
+			match &element.condition {
+				ElementCondition::Fixed(_) => (),
+				ElementCondition::Node(node) => {
+					self.check_node(location, *node);
+				},
+				ElementCondition::Edge(edge) => {
+					self.check_ref(location, "edge", *edge, aerodrome.edges.len());
+					used_edges.insert(edge.0);
+				},
+				ElementCondition::Expression(expression) => {
+					self.check_expression(location, expression);
+					for term in expression.terms() {
+						if let ConditionTerm::Edge(edge) = term {
+							used_edges.insert(edge.0);
+						}
+					}
+				},
+			}
+		}
+
+		// elements sharing an id between providers are sent as one, so must
+		// agree on their state
+		let mut conditions = HashMap::new();
+		for (i, element) in aerodrome.elements.iter().enumerate() {
+			let id = element.local_id();
+			match conditions.get(id) {
+				None => {
+					conditions.insert(id, &element.condition);
+				},
+				Some(condition) if **condition != element.condition => {
+					let kind = IssueKind::ProviderConditionMismatch { id: id.into() };
+					self.push(format!("elements[{i}]"), kind);
+				},
+				Some(_) => (),
+			}
+		}
+
+		for (i, edge) in aerodrome.edges.iter().enumerate() {
+			if !used_edges.contains(&i) {
+				let kind = IssueKind::UnusedEdge {
+					edge: edge.id.clone(),
+				};
+
+				self.push(format!("edges[{i}]"), kind);
+			}
+		}
+	}
+
+	fn validate_nodes(&mut self) {
+		let aerodrome = self.aerodrome;
+
+		for (i, node) in aerodrome.nodes.iter().enumerate() {
+			let Some(mut parent) = node.parent else {
+				continue
+			};
+
+			let location = format!("nodes[{i}].parent");
+			if !self.check_node(location.clone(), parent) {
+				continue
+			}
+
+			let mut seen = HashSet::from([i]);
+			loop {
+				if !seen.insert(parent.0) {
+					let kind = IssueKind::ParentCycle {
+						node: node.id.clone(),
+					};
+
+					self.push(location, kind);
+					break
+				}
+
+				match aerodrome.nodes[parent.0].parent {
+					Some(next) if next.0 < aerodrome.nodes.len() => parent = next,
+					_ => break,
+				}
+			}
+		}
+	}
+
+	fn validate_blocks(&mut self) {
+		let aerodrome = self.aerodrome;
+		let mut blocked = HashSet::new();
+
+		for (i, block) in aerodrome.blocks.iter().enumerate() {
+			if block.nodes.is_empty() {
+				self.push(format!("blocks[{i}]"), IssueKind::EmptyBlock);
+			}
+
+			for (j, node) in block.nodes.iter().enumerate() {
+				let location = format!("blocks[{i}].nodes[{j}]");
+				if !self.check_node(location.clone(), *node) {
+					continue
+				}
+
+				blocked.insert(node.0);
+
+				if aerodrome.nodes[node.0].parent.is_some() {
+					let kind = IssueKind::ChildInBlock {
+						node: aerodrome.nodes[node.0].id.clone(),
+					};
+
+					self.push(location, kind);
+				}
+			}
+
+			for (j, edge) in block.edges.iter().enumerate() {
+				let location = format!("blocks[{i}].edges[{j}]");
+				self.check_ref(location, "edge", *edge, aerodrome.edges.len());
+			}
+
+			for (j, route) in block.non_routes.iter().enumerate() {
+				let location = format!("blocks[{i}].non_routes[{j}]");
+				self.validate_route(location, block, route);
+			}
+		}
+
+		for (i, node) in aerodrome.nodes.iter().enumerate() {
+			if node.parent.is_none() && !blocked.contains(&i) {
+				let kind = IssueKind::UnblockedNode {
+					node: node.id.clone(),
+				};
+
+				self.push(format!("nodes[{i}]"), kind);
+			}
+		}
+	}
+
+	/// Checks that the stands named by each block belong to one block only.
+	fn validate_stands(&mut self) {
+		let aerodrome = self.aerodrome;
+		let mut checked = HashSet::new();
+
+		for (i, block) in aerodrome.blocks.iter().enumerate() {
+			for (j, entry) in block.stands.iter().enumerate() {
+				for stand in stand_examples(entry) {
+					if !checked.insert(stand.clone()) {
+						continue
+					}
+
+					let matches = aerodrome
+						.blocks
+						.iter()
+						.filter_map(|block| Some((block.stand_match(&stand)?, block)))
+						.collect::<Vec<_>>();
+					let Some(best) = matches.iter().map(|(rank, _)| *rank).max() else {
+						continue
+					};
+
+					let blocks = matches
+						.iter()
+						.filter(|(rank, _)| *rank == best)
+						.map(|(_, block)| block.id.clone())
+						.collect::<Vec<_>>();
+
+					if blocks.len() > 1 {
+						let kind = IssueKind::AmbiguousStand { stand, blocks };
+						self.push(format!("blocks[{i}].stands[{j}]"), kind);
+					}
+				}
+			}
+		}
+	}
+
+	/// Checks that each holding point lies within a block, so that routes
+	/// through the block can be said to pass it.
+	fn validate_strings(&mut self) {
+		let aerodrome = self.aerodrome;
+		let Some(strings) = &aerodrome.strings else {
+			return
+		};
+
+		for key in strings.strings.keys() {
+			let known = StringKind::parse_key(key)
+				.is_some_and(|(kind, id)| aerodrome.default_name(kind, id).is_some());
+
+			if !known {
+				let kind = IssueKind::DanglingString { key: key.clone() };
+				self.push(format!("strings[{key:?}]"), kind);
+			}
+		}
+	}
+
+	fn validate_holding_points(&mut self) {
+		let aerodrome = self.aerodrome;
+
+		for (i, holding_point) in aerodrome.holding_points.iter().enumerate() {
+			let location = format!("holding_points[{i}].location");
+			let within = match holding_point.location {
+				HoldingPointLocation::Edge(edge) => {
+					let len = aerodrome.edges.len();
+					if !self.check_ref(location.clone(), "edge", edge, len) {
+						continue
+					}
+
+					aerodrome
+						.blocks
+						.iter()
+						.any(|block| block.edges.contains(&edge))
+				},
+				HoldingPointLocation::Nodes(a, b) => {
+					let valid =
+						[a, b].map(|node| self.check_node(location.clone(), node));
+					if valid.contains(&false) {
+						continue
+					}
+
+					let (a, b) = (aerodrome.root_node(a), aerodrome.root_node(b));
+					(aerodrome.blocks.iter())
+						.any(|block| block.nodes.contains(&a) && block.nodes.contains(&b))
+				},
+			};
+
+			if !within {
+				let kind = IssueKind::HoldingPointOutsideBlock {
+					holding_point: holding_point.id.clone(),
+				};
+
+				self.push(location, kind);
+			}
+		}
+	}
+
+	fn validate_route(
+		&mut self,
+		location: String,
+		block: &Block,
+		route: &BlockRoute,
+	) {
+		for node in [route.from, route.to] {
+			if !self.check_node(location.clone(), node) {
+				continue
+			}
+
+			if !block.nodes.contains(&self.aerodrome.root_node(node)) {
+				let kind = IssueKind::NonRouteOutsideBlock {
+					node: self.aerodrome.nodes[node.0].id.clone(),
+				};
+
+				self.push(location.clone(), kind);
+			}
+		}
+	}
+
+	fn validate_profiles(&mut self) {
+		let aerodrome = self.aerodrome;
+
+		for (i, profile) in aerodrome.profiles.iter().enumerate() {
+			let location = format!("profiles[{i}]");
+
+			for (target, expected, found) in [
+				("node", aerodrome.nodes.len(), profile.nodes.len()),
+				("edge", aerodrome.edges.len(), profile.edges.len()),
+				("block", aerodrome.blocks.len(), profile.blocks.len()),
+			] {
+				self.check_count(
+					format!("{location}.{target}s"),
+					|expected, found| IssueKind::ConditionCount {
+						target,
+						expected,
+						found,
+					},
+					expected,
+					found,
+				);
+			}
+
+			for (j, condition) in profile.edges.iter().enumerate() {
+				let location = format!("{location}.edges[{j}]");
+				match condition {
+					EdgeCondition::Fixed { .. } => (),
+					EdgeCondition::Direct { nodes } => {
+						for (k, conjunction) in nodes.disjunction.iter().enumerate() {
+							let nodes =
+								conjunction.positive.iter().chain(&conjunction.negative);
+							for node in nodes {
+								self.check_node(format!("{location}.disjunction[{k}]"), *node);
+							}
+						}
+					},
+					EdgeCondition::Router { block, routes } => {
+						let len = aerodrome.blocks.len();
+						if self.check_ref(location.clone(), "block", *block, len) {
+							let block = &aerodrome.blocks[block.0];
+							for (k, route) in routes.iter().enumerate() {
+								self.validate_route(
+									format!("{location}.routes[{k}]"),
+									block,
+									route,
+								);
+							}
+						}
+					},
+					EdgeCondition::Expression { expression } => {
+						self.check_expression(location.clone(), expression);
+
+						// evaluated in one pass, so expressions may not nest
+						for term in expression.terms() {
+							let ConditionTerm::Edge(edge) = term else {
+								continue
+							};
+
+							if let Some(EdgeCondition::Expression { .. }) =
+								profile.edges.get(edge.0)
+							{
+								let kind = IssueKind::NestedExpression {
+									edge: (aerodrome.edges.get(edge.0))
+										.map_or("?".into(), |edge| edge.id.clone()),
+								};
+								self.push(location.clone(), kind);
+							}
+						}
+					},
+				}
+			}
+
+			for (j, preset) in profile.presets.iter().enumerate() {
+				let location = format!("{location}.presets[{j}]");
+
+				for (k, include) in preset.includes.iter().enumerate() {
+					let location = format!("{location}.includes[{k}]");
+					self.check_ref(location, "preset", *include, profile.presets.len());
+				}
+
+				if let Some(cycle) = profile.preset_cycle(j) {
+					let kind = IssueKind::PresetCycle {
+						presets: cycle
+							.into_iter()
+							.map(|k| profile.presets[k].name.clone())
+							.collect(),
+					};
+
+					self.push(format!("{location}.includes"), kind);
+				}
+
+				for (k, (node, _)) in preset.nodes.iter().enumerate() {
+					self.check_node(format!("{location}.nodes[{k}]"), *node);
+				}
+
+				for (k, (block, state)) in preset.blocks.iter().enumerate() {
+					let location = format!("{location}.blocks[{k}]");
+					self.check_ref(
+						location.clone(),
+						"block",
+						*block,
+						aerodrome.blocks.len(),
+					);
+
+					if let BlockState::Route((from, to)) = state {
+						self.check_node(location.clone(), *from);
+						self.check_node(location, *to);
+					}
+				}
+			}
+		}
+	}
+
+	fn validate_maps(&mut self) {
+		let aerodrome = self.aerodrome;
+
+		if let Some(geo_map) = &aerodrome.geo_map {
+			self.validate_map(
+				"geo_map".into(),
+				[
+					geo_map.nodes.len(),
+					geo_map.edges.len(),
+					geo_map.blocks.len(),
+				],
+				geo_map.nodes.iter().flat_map(NodeDisplay::paths),
+				geo_map.edges.iter().flat_map(EdgeDisplay::paths),
+				(geo_map.nodes.iter().map(|node| &node.target))
+					.chain(geo_map.blocks.iter().map(|block| &block.target)),
+				&geo_map.widgets,
+			);
+		}
+
+		for (i, map) in aerodrome.maps.iter().enumerate() {
+			let location = format!("maps[{i}]");
+
+			for (j, view) in map.views.iter().enumerate() {
+				let Box { min, max } = view.bounds;
+				if !(min.x < max.x && min.y < max.y) {
+					self.push(format!("{location}.views[{j}]"), IssueKind::EmptyView);
+				}
+			}
+
+			self.validate_map(
+				location,
+				[map.nodes.len(), map.edges.len(), map.blocks.len()],
+				map
+					.base
+					.iter()
+					.chain(map.nodes.iter().flat_map(NodeDisplay::paths)),
+				map.edges.iter().flat_map(EdgeDisplay::paths),
+				(map.nodes.iter().map(|node| &node.target))
+					.chain(map.blocks.iter().map(|block| &block.target)),
+				&map.widgets,
+			);
+		}
+	}
+
+	fn validate_map<'b, T: Projectable + 'b>(
+		&mut self,
+		location: String,
+		counts: [usize; 3],
+		node_paths: impl Iterator<Item = &'b Path<T>>,
+		edge_paths: impl Iterator<Item = &'b Path<T>>,
+		targets: impl Iterator<Item = &'b Target<T>>,
+		widgets: &[Widget<T>],
+	) {
+		let aerodrome = self.aerodrome;
+
+		for (target, expected, found) in [
+			("node", aerodrome.nodes.len(), counts[0]),
+			("edge", aerodrome.edges.len(), counts[1]),
+			("block", aerodrome.blocks.len(), counts[2]),
+		] {
+			self.check_count(
+				format!("{location}.{target}s"),
+				|expected, found| IssueKind::DisplayCount {
+					target,
+					expected,
+					found,
+				},
+				expected,
+				found,
+			);
+		}
+
+		let styles = (node_paths.chain(edge_paths).map(|path| path.style))
+			.chain(targets.flat_map(Target::styles));
+		for style in styles {
+			let len = aerodrome.styles.len();
+			if !self.check_ref(location.clone(), "style", style, len) {
+				break
+			}
+		}
+
+		for (i, widget) in widgets.iter().enumerate() {
+			let location = format!("{location}.widgets[{i}]");
+			match widget {
+				Widget::Countdown {
+					condition,
+					normal_style,
+					warn_style,
+					..
+				} => {
+					match condition {
+						CountdownCondition::Node(node) => {
+							self.check_node(location.clone(), *node);
+						},
+						CountdownCondition::Block(block) => {
+							let len = aerodrome.blocks.len();
+							self.check_ref(location.clone(), "block", *block, len);
+						},
+					}
+
+					for style in [normal_style, warn_style].into_iter().flatten() {
+						let len = aerodrome.styles.len();
+						self.check_ref(location.clone(), "style", *style, len);
+					}
+				},
+				Widget::Label { holding_point, .. } => {
+					let len = aerodrome.holding_points.len();
+					self.check_ref(location, "holding point", *holding_point, len);
+				},
+				Widget::Text { condition, .. } => match condition {
+					Some(CountdownCondition::Node(node)) => {
+						self.check_node(location, *node);
+					},
+					Some(CountdownCondition::Block(block)) => {
+						let len = aerodrome.blocks.len();
+						self.check_ref(location, "block", *block, len);
+					},
+					None => (),
+				},
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::fixture::{aerodrome, block};
+
+	/// Builds an aerodrome of a block of two nodes, with an edge `X0`, `X1` and
+	/// so on of each expression, and a last edge lit by `N0`.
+	fn with_expressions(expressions: &[&str]) -> Aerodrome {
+		let mut aerodrome = aerodrome(2, vec![block("B0", &[0, 1], &[])]);
+		aerodrome.edges = (0..=expressions.len())
+			.map(|i| Edge {
+				id: format!("X{i}"),
+				name: None,
+			})
+			.collect();
+
+		fn position<T>(ids: impl IntoIterator<Item = String>, id: &str) -> Ref<T> {
+			ids
+				.into_iter()
+				.position(|other| other == id)
+				.unwrap()
+				.into()
+		}
+
+		let nodes = aerodrome.nodes.iter().map(|node| node.id.clone());
+		let edges = aerodrome.edges.iter().map(|edge| edge.id.clone());
+		let mut conditions = expressions
+			.iter()
+			.map(|text| {
+				let expression = ConditionExpression::parse(text, |term| match term {
+					TermText::Node(id) => {
+						ConditionTerm::Node(position(nodes.clone(), id))
+					},
+					TermText::Edge(id) => {
+						ConditionTerm::Edge(position(edges.clone(), id))
+					},
+					TermText::Block(..) => unreachable!(),
+				});
+				EdgeCondition::Expression {
+					expression: expression.unwrap(),
+				}
+			})
+			.collect::<Vec<_>>();
+		conditions.push(EdgeCondition::Direct {
+			nodes: NodeExpression {
+				disjunction: vec![NodeConjunction {
+					positive: vec![0.into()],
+					negative: Vec::new(),
+				}],
+			},
+		});
+
+		aerodrome.profiles[0].edges = conditions;
+		aerodrome
+	}
+
+	fn nested(aerodrome: &Aerodrome) -> Vec<(String, String)> {
+		(aerodrome.validate().into_iter())
+			.filter_map(|issue| match issue.kind {
+				IssueKind::NestedExpression { edge } => Some((issue.location, edge)),
+				_ => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn expressions_may_not_refer_to_expressions() {
+		// a cycle, each referring to the other
+		let cycle = with_expressions(&["N0 & edge:X1", "!edge:X0 | N1"]);
+		assert_eq!(
+			nested(&cycle),
+			[
+				("profiles[0].edges[0]".into(), "X1".into()),
+				("profiles[0].edges[1]".into(), "X0".into()),
+			],
+		);
+		assert!(cycle.validate().iter().any(|issue| {
+			issue.kind.severity() == Severity::Error
+				&& issue.kind.code() == "nested-expression"
+		}));
+
+		// one referring to itself
+		let own = with_expressions(&["edge:X0 | N1"]);
+		assert_eq!(nested(&own), [("profiles[0].edges[0]".into(), "X0".into())],);
+
+		// referring to edges of other conditions is fine
+		let flat = with_expressions(&["N0 & edge:X2", "!edge:X2 | N1"]);
+		assert_eq!(nested(&flat), []);
+	}
+
+	#[test]
+	fn duplicate_edge_ids_are_rejected() {
+		let mut aerodrome = with_expressions(&["N0", "N1"]);
+		let duplicates = |aerodrome: &Aerodrome| {
+			(aerodrome.validate().into_iter())
+				.filter(|issue| matches!(issue.kind, IssueKind::DuplicateId { .. }))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(duplicates(&aerodrome), []);
+
+		// the edge listed later is the duplicate, whatever its name
+		aerodrome.edges[2].id = "X0".into();
+		aerodrome.edges[2].name = Some("Alpha".into());
+		let issues = duplicates(&aerodrome);
+		assert_eq!(
+			issues,
+			[Issue {
+				icao: "ZZZZ".into(),
+				location: "edges[2]".into(),
+				kind: IssueKind::DuplicateId {
+					target: "edge",
+					id: "X0".into(),
+				},
+			}],
+		);
+		assert_eq!(issues[0].kind.severity(), Severity::Error);
+
+		// edges may share ids with other kinds of entity
+		aerodrome.edges[2].id = "N0".into();
+		assert_eq!(duplicates(&aerodrome), []);
+	}
+}
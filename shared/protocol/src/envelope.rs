@@ -0,0 +1,60 @@
+//! Forward-compatible message envelopes.
+//!
+//! Each message is preceded by a header holding a tag, identifying the kind of
+//! message, and the length of its body. A receiver which does not recognise a
+//! tag can therefore skip over the body and continue with the next message,
+//! rather than losing its place in the stream.
+//!
+//! Bodies may be extended by appending fields to the end. Receivers ignore any
+//! trailing data they do not understand, so appended fields must be optional,
+//! and only relied upon once both peers are known to support them.
+
+pub const ENVELOPE_HEADER_SIZE: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Envelope<'a> {
+	pub tag: u16,
+	pub body: &'a [u8],
+}
+
+impl Envelope<'_> {
+	pub fn encoded_len(&self) -> usize {
+		ENVELOPE_HEADER_SIZE + self.body.len()
+	}
+}
+
+/// Encodes `body` into an envelope with the given tag.
+///
+/// Returns `None` if the body is too large to be represented.
+pub fn encode_envelope(tag: u16, body: &[u8]) -> Option<Vec<u8>> {
+	let size = u32::try_from(body.len()).ok()?;
+
+	let mut data = Vec::with_capacity(ENVELOPE_HEADER_SIZE + body.len());
+	data.extend_from_slice(&tag.to_le_bytes());
+	data.extend_from_slice(&size.to_le_bytes());
+	data.extend_from_slice(body);
+
+	Some(data)
+}
+
+/// Decodes the tag and body length from the header at the start of `data`.
+///
+/// Returns `None` if `data` is too short to contain a complete header.
+pub fn decode_envelope_header(data: &[u8]) -> Option<(u16, usize)> {
+	let header = data.first_chunk::<ENVELOPE_HEADER_SIZE>()?;
+	let tag = u16::from_le_bytes([header[0], header[1]]);
+	let size = u32::from_le_bytes([header[2], header[3], header[4], header[5]]);
+
+	Some((tag, size as usize))
+}
+
+/// Decodes the envelope at the start of `data`.
+///
+/// Returns `None` if `data` does not yet contain the complete envelope. The
+/// number of bytes occupied by the envelope is given by [`Envelope::encoded_len`].
+pub fn decode_envelope(data: &[u8]) -> Option<Envelope<'_>> {
+	let (tag, size) = decode_envelope_header(data)?;
+	let body = data.get(ENVELOPE_HEADER_SIZE..)?.get(..size)?;
+
+	Some(Envelope { tag, body })
+}
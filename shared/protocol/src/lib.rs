@@ -1,9 +1,24 @@
+mod envelope;
+
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
 
+pub use envelope::*;
+
 pub type NodeState = bool;
 
+/// duration for which a block lock is held before expiring
+pub const LOCK_DURATION: Duration = Duration::from_secs(10);
+
+pub fn epoch_millis() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|time| time.as_millis() as u64)
+		.unwrap_or_default()
+}
+
 #[derive(
 	Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize,
 )]
@@ -19,6 +34,8 @@ pub struct Aerodrome {
 	pub profile: String,
 	pub nodes: HashMap<String, NodeState>,
 	pub blocks: HashMap<String, BlockState>,
+	/// edges forced on or off regardless of their conditions
+	pub edges: HashMap<String, bool>,
 	patch: Option<Patch>,
 }
 
@@ -28,6 +45,7 @@ impl Aerodrome {
 			profile,
 			nodes: HashMap::new(),
 			blocks: HashMap::new(),
+			edges: HashMap::new(),
 			patch: None,
 		}
 	}
@@ -39,6 +57,7 @@ impl Aerodrome {
 	pub fn set_profile(&mut self, profile: String) {
 		self.patch().profile = Some(profile.clone());
 		self.profile = profile;
+		self.edges.clear();
 	}
 
 	pub fn set_node(&mut self, id: String, state: NodeState) {
@@ -51,6 +70,14 @@ impl Aerodrome {
 		self.blocks.insert(id, state);
 	}
 
+	pub fn set_edge_override(&mut self, id: String, state: Option<bool>) {
+		self.patch().edges.insert(id.clone(), state);
+		match state {
+			Some(state) => self.edges.insert(id, state),
+			None => self.edges.remove(&id),
+		};
+	}
+
 	pub fn take_patch(&mut self) -> Option<Patch> {
 		std::mem::take(&mut self.patch)
 	}
@@ -58,43 +85,53 @@ impl Aerodrome {
 	pub fn apply_patch(&mut self, patch: Patch) {
 		if let Some(profile) = patch.profile {
 			self.profile = profile;
+			self.edges.clear();
 		}
 
-		self.nodes.extend(patch.nodes.into_iter());
-		self.blocks.extend(patch.blocks.into_iter());
+		self.nodes.extend(patch.nodes);
+		self.blocks.extend(patch.blocks);
+		for (id, state) in patch.edges {
+			match state {
+				Some(state) => self.edges.insert(id, state),
+				None => self.edges.remove(&id),
+			};
+		}
 	}
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Patch {
 	pub profile: Option<String>,
 	pub nodes: HashMap<String, NodeState>,
 	pub blocks: HashMap<String, BlockState>,
+	/// reset deadlines in server epoch milliseconds
+	pub node_deadlines: HashMap<String, Option<u64>>,
+	pub block_deadlines: HashMap<String, Option<u64>>,
+	/// manual overrides of edges, each cleared by none, which are themselves
+	/// cleared by a change of profile
+	pub edges: HashMap<String, Option<bool>>,
 }
 
 impl Patch {
 	pub fn apply_patch(&mut self, patch: Patch) {
 		if let Some(profile) = patch.profile {
 			self.profile = Some(profile);
+			self.edges.clear();
 		}
 
-		self.nodes.extend(patch.nodes.into_iter());
-		self.blocks.extend(patch.blocks.into_iter());
+		self.nodes.extend(patch.nodes);
+		self.blocks.extend(patch.blocks);
+		self.node_deadlines.extend(patch.node_deadlines);
+		self.block_deadlines.extend(patch.block_deadlines);
+		self.edges.extend(patch.edges);
 	}
 
 	pub fn is_empty(&self) -> bool {
-		self.profile.is_none() && self.nodes.is_empty() && self.blocks.is_empty()
-	}
-}
-
-impl Default for Patch {
-	fn default() -> Self {
-		Self {
-			profile: None,
-			nodes: HashMap::new(),
-			blocks: HashMap::new(),
-		}
+		self.profile.is_none()
+			&& self.nodes.is_empty()
+			&& self.blocks.is_empty()
+			&& self.edges.is_empty()
 	}
 }
 
@@ -104,6 +141,10 @@ impl From<Aerodrome> for Patch {
 			profile: Some(from.profile),
 			nodes: from.nodes,
 			blocks: from.blocks,
+			edges: (from.edges.into_iter())
+				.map(|(id, state)| (id, Some(state)))
+				.collect(),
+			..Default::default()
 		}
 	}
 }
@@ -127,6 +168,12 @@ pub enum Upstream<P = Patch> {
 		#[serde(rename = "sharedStatePatch")]
 		patch: P,
 	},
+	Acquire {
+		block: String,
+	},
+	Release {
+		block: String,
+	},
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -155,6 +202,9 @@ pub enum Downstream<P = Patch> {
 		scenery: Vec<SceneryObject>,
 		#[serde(rename = "sharedState")]
 		patch: P,
+		/// config hash of the aerodrome, from servers which know it, to be
+		/// compared with that of the client's config
+		config_hash: Option<String>,
 	},
 	StateUpdate {
 		object_id: String,
@@ -166,6 +216,23 @@ pub enum Downstream<P = Patch> {
 		patch: P,
 		controller_id: String,
 	},
+	Time {
+		server_epoch_millis: u64,
+	},
+	Lock {
+		block: String,
+		owner: String,
+		granted: bool,
+	},
+	/// current conditions, sent by servers which know them
+	Weather {
+		visibility_m: Option<u32>,
+		rvr_m: Option<u32>,
+		/// whether low visibility procedures are in force
+		lvp: Option<bool>,
+		/// the report the conditions are from, such as a METAR
+		raw: Option<String>,
+	},
 	#[serde(other)]
 	Other,
 }
@@ -181,6 +248,41 @@ pub struct SceneryObject {
 pub struct State {
 	pub airport: String,
 	pub controllers: Vec<String>,
-	pub pilots: Vec<String>,
+	pub pilots: Vec<Pilot>,
 	pub offline: bool,
 }
+
+/// A pilot at an aerodrome, as listed in its [`State`].
+///
+/// Older servers list only callsigns, which are read as pilots of unknown
+/// stands.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "PilotEntry")]
+pub struct Pilot {
+	pub callsign: String,
+	/// stand the aircraft is assigned to or parked on, if known
+	pub stand: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PilotEntry {
+	Callsign(String),
+	Pilot {
+		callsign: String,
+		#[serde(default)]
+		stand: Option<String>,
+	},
+}
+
+impl From<PilotEntry> for Pilot {
+	fn from(entry: PilotEntry) -> Self {
+		match entry {
+			PilotEntry::Callsign(callsign) => Self {
+				callsign,
+				stand: None,
+			},
+			PilotEntry::Pilot { callsign, stand } => Self { callsign, stand },
+		}
+	}
+}
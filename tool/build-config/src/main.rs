@@ -0,0 +1,187 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bars_config::{
+	Aerodrome, AerodromeSource, Config, Loadable, Maps, Severity,
+};
+
+use anyhow::{bail, Result};
+
+use clap::Parser;
+
+use serde::Deserialize;
+
+/// Build a BARS config from a project manifest.
+///
+/// The manifest is a TOML file giving the config name and version, and an
+/// `aerodromes` array of tables, each with the path to an aerodrome source
+/// file and optionally a `maps` array of paths to topsky map files. Paths are
+/// relative to the manifest.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// build only aerodrome ICAO
+	#[arg(long = "only", value_name = "ICAO")]
+	only: Vec<String>,
+
+	/// do not attach maps
+	#[arg(long)]
+	no_maps: bool,
+
+	/// write to FILE rather than stdout
+	#[arg(short, long = "out", value_name = "FILE")]
+	output: Option<PathBuf>,
+
+	/// project manifest
+	#[arg(value_name = "MANIFEST")]
+	manifest: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+	name: Option<String>,
+	version: Option<String>,
+
+	#[serde(default)]
+	aerodromes: Vec<AerodromeEntry>,
+}
+
+#[derive(Deserialize)]
+struct AerodromeEntry {
+	source: PathBuf,
+	#[serde(default)]
+	maps: Vec<PathBuf>,
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+
+	let manifest = std::fs::read_to_string(&args.manifest)?;
+	let manifest = toml::from_str::<Manifest>(&manifest)
+		.map_err(|err| anyhow::anyhow!("{}: {err}", args.manifest.display()))?;
+
+	let dir = args.manifest.parent().unwrap_or(Path::new(""));
+
+	let mut aerodromes = Vec::new();
+	let mut errors = Vec::new();
+
+	for entry in &manifest.aerodromes {
+		match build(dir, entry, &args) {
+			Ok(Some(aerodrome)) => aerodromes.push(aerodrome),
+			Ok(None) => (),
+			Err(mut errs) => errors.append(&mut errs),
+		}
+	}
+
+	for icao in &args.only {
+		if !aerodromes.iter().any(|a: &Aerodrome| &a.icao == icao)
+			&& errors.is_empty()
+		{
+			errors.push(format!("{icao}: not in manifest"));
+		}
+	}
+
+	if !errors.is_empty() {
+		for error in &errors {
+			eprintln!("error: {error}");
+		}
+
+		bail!("failed with {} errors", errors.len())
+	}
+
+	for aerodrome in &aerodromes {
+		eprintln!(
+			"{}: {} elements, {} nodes, {} edges, {} blocks, {} profiles, {} maps{}",
+			aerodrome.icao,
+			aerodrome.elements.len(),
+			aerodrome.nodes.len(),
+			aerodrome.edges.len(),
+			aerodrome.blocks.len(),
+			aerodrome.profiles.len(),
+			aerodrome.maps.len(),
+			if aerodrome.geo_map.is_some() {
+				" and geo map"
+			} else {
+				""
+			},
+		);
+	}
+
+	let config = Config {
+		name: manifest.name,
+		version: manifest.version,
+		aerodromes,
+	};
+
+	let output: Box<dyn Write> = match &args.output {
+		Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+		None => Box::new(std::io::stdout().lock()),
+	};
+
+	config.save(output)?;
+
+	Ok(())
+}
+
+fn build(
+	dir: &Path,
+	entry: &AerodromeEntry,
+	args: &Args,
+) -> Result<Option<Aerodrome>, Vec<String>> {
+	let path = dir.join(&entry.source);
+	let name = path.display();
+
+	let source = std::fs::read_to_string(&path)
+		.map_err(|err| vec![format!("{name}: {err}")])?;
+	let source = toml::from_str::<AerodromeSource>(&source)
+		.map_err(|err| vec![format!("{name}: {err}")])?;
+
+	if !args.only.is_empty() && !args.only.contains(&source.icao) {
+		return Ok(None)
+	}
+
+	let mut aerodrome = source.compile().map_err(|errs| {
+		errs
+			.into_iter()
+			.map(|err| format!("{name}: {err}"))
+			.collect::<Vec<_>>()
+	})?;
+
+	if !args.no_maps && !entry.maps.is_empty() {
+		let mut files = Vec::new();
+		for maps_path in &entry.maps {
+			let path = dir.join(maps_path);
+			let text = std::fs::read_to_string(&path)
+				.map_err(|err| vec![format!("{}: {err}", path.display())])?;
+			files.push((path.display().to_string(), text));
+		}
+
+		let maps = Maps::load_topsky_files(
+			(files.iter()).map(|(name, text)| (name.as_str(), text.as_str())),
+		)
+		.map_err(|err| vec![err.to_string()])?;
+
+		let report = aerodrome
+			.attach_maps(maps)
+			.map_err(|err| vec![format!("{name}: {err}")])?;
+		for problem in report.describe(&aerodrome) {
+			eprintln!("warning: {name}: {problem}");
+		}
+	}
+
+	let mut errors = Vec::new();
+	for issue in aerodrome.validate() {
+		let message = format!("{name}: {}: {}", issue.location, issue.kind);
+		match issue.severity() {
+			Severity::Error => errors.push(message),
+			Severity::Warning => eprintln!("warning: {message}"),
+		}
+	}
+
+	if !errors.is_empty() {
+		return Err(errors)
+	}
+
+	Ok(Some(aerodrome))
+}
@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use bars_config::{Config, Loadable};
+
+fn project() -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/project/bars.toml")
+}
+
+/// Builds the project of the manifest to a file of the name, returning the
+/// output of the build.
+fn build(manifest: &Path, out: &str, args: &[&str]) -> (Output, PathBuf) {
+	let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(out);
+	let output = Command::new(env!("CARGO_BIN_EXE_bars-build-config"))
+		.args(args)
+		.arg("--out")
+		.arg(&path)
+		.arg(manifest)
+		.output()
+		.unwrap();
+
+	(output, path)
+}
+
+fn stderr(output: &Output) -> String {
+	String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn projects_build_and_load_back() {
+	let (output, path) = build(&project(), "project.bars", &[]);
+	assert!(output.status.success(), "{}", stderr(&output));
+	assert_eq!(
+		stderr(&output),
+		"EGAA: 2 elements, 2 nodes, 1 edges, 1 blocks, 1 profiles, 1 maps and geo map\n\
+		 EGBB: 1 elements, 1 nodes, 0 edges, 1 blocks, 1 profiles, 0 maps\n",
+	);
+
+	let config = Config::load(File::open(path).unwrap()).unwrap();
+	assert_eq!(config.name.as_deref(), Some("Fixture"));
+	assert_eq!(config.version.as_deref(), Some("2025/01"));
+	assert_eq!(config.validate(), []);
+
+	let [egaa, egbb] = config.aerodromes.as_slice() else {
+		panic!("{} aerodromes built", config.aerodromes.len());
+	};
+	assert_eq!(egaa.icao, "EGAA");
+	assert_eq!(egbb.icao, "EGBB");
+
+	// the maps of both files are attached, sharing their colours
+	let geo_map = egaa.geo_map.as_ref().unwrap();
+	assert_eq!(geo_map.nodes[0].on.len(), 1);
+	assert_eq!(egaa.maps.len(), 1);
+	assert_eq!(egaa.maps[0].views[0].name, "Ground");
+	assert_eq!(egaa.maps[0].nodes[0].on.len(), 1);
+	assert_eq!(egaa.maps[0].edges[0].on.len(), 1);
+	assert!(egbb.geo_map.is_none() && egbb.maps.is_empty());
+}
+
+#[test]
+fn selected_aerodromes_build_without_maps() {
+	let args = ["--only", "EGAA", "--no-maps"];
+	let (output, path) = build(&project(), "selected.bars", &args);
+	assert!(output.status.success(), "{}", stderr(&output));
+
+	let config = Config::load(File::open(path).unwrap()).unwrap();
+	let [egaa] = config.aerodromes.as_slice() else {
+		panic!("{} aerodromes built", config.aerodromes.len());
+	};
+	assert_eq!(egaa.icao, "EGAA");
+	assert!(egaa.geo_map.is_none() && egaa.maps.is_empty());
+
+	let (output, _) = build(&project(), "unknown.bars", &["--only", "EGCC"]);
+	assert!(!output.status.success());
+	assert!(stderr(&output).contains("error: EGCC: not in manifest"));
+}
+
+#[test]
+fn errors_name_their_files() {
+	let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("broken");
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(
+		dir.join("bars.toml"),
+		"[[aerodromes]]\nsource = \"egxx.toml\"\n",
+	)
+	.unwrap();
+	std::fs::write(
+		dir.join("egxx.toml"),
+		"icao = \"EGXX\"\n\n[[blocks]]\nid = \"B1\"\nnodes = [\"N1\"]\n",
+	)
+	.unwrap();
+
+	let (output, _) = build(&dir.join("bars.toml"), "broken.bars", &[]);
+	assert!(!output.status.success());
+
+	let stderr = stderr(&output);
+	let source = dir.join("egxx.toml");
+	let prefix = format!("error: {}: ", source.display());
+	assert!(
+		stderr.lines().any(|line| line.starts_with(&prefix)),
+		"{stderr}"
+	);
+	assert!(stderr.contains("failed with 1 errors"), "{stderr}");
+}
@@ -0,0 +1,110 @@
+mod manifest;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use bars_config::{Config, Loadable};
+
+use anyhow::{bail, Result};
+
+use clap::Parser;
+
+use manifest::Manifest;
+
+/// Cross-check the elements of a BARS config against a scenery manifest.
+///
+/// The manifest lists one scenery object id per line, optionally preceded by
+/// the ICAO of its aerodrome and a comma. Ids may contain the wildcards `*`
+/// and `?`. Blank lines and lines starting with `#` are ignored.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// check only aerodrome ICAO
+	#[arg(short, long = "aerodrome", value_name = "ICAO")]
+	aerodromes: Vec<String>,
+
+	/// do not report objects which no element drives
+	#[arg(long)]
+	allow_undriven: bool,
+
+	/// do not report elements with no matching object
+	#[arg(long)]
+	allow_unimplemented: bool,
+
+	/// config file to check
+	#[arg(value_name = "CONFIG")]
+	config: PathBuf,
+
+	/// manifest of scenery objects
+	#[arg(value_name = "MANIFEST")]
+	manifest: PathBuf,
+}
+
+fn main() -> Result<ExitCode> {
+	let args = Args::parse();
+
+	let mut config = Config::load(BufReader::new(File::open(&args.config)?))?;
+	let manifest = Manifest::read(BufReader::new(File::open(&args.manifest)?))?;
+
+	for icao in &args.aerodromes {
+		if !config.aerodromes.iter().any(|a| &a.icao == icao) {
+			bail!("unknown aerodrome {icao}");
+		}
+	}
+
+	if !args.aerodromes.is_empty() {
+		config
+			.aerodromes
+			.retain(|aerodrome| args.aerodromes.contains(&aerodrome.icao));
+	}
+
+	let mut matched = vec![false; manifest.entries.len()];
+	let mut unimplemented = 0;
+
+	for aerodrome in &config.aerodromes {
+		for element in &aerodrome.elements {
+			let mut found = false;
+			for (i, entry) in manifest.entries.iter().enumerate() {
+				if entry.matches(&aerodrome.icao, &element.id) {
+					matched[i] = true;
+					found = true;
+				}
+			}
+
+			if !found && !args.allow_unimplemented {
+				println!("{}: element {} has no object", aerodrome.icao, element.id);
+				unimplemented += 1;
+			}
+		}
+	}
+
+	let mut undriven = 0;
+
+	for (entry, matched) in manifest.entries.iter().zip(matched) {
+		let checked = entry.icao.as_ref().is_none_or(|icao| {
+			config
+				.aerodromes
+				.iter()
+				.any(|aerodrome| &aerodrome.icao == icao)
+		});
+
+		if !matched && checked && !args.allow_undriven {
+			match &entry.icao {
+				Some(icao) => println!("{icao}: object {} has no element", entry.id),
+				None => println!("object {} has no element", entry.id),
+			}
+
+			undriven += 1;
+		}
+	}
+
+	println!("{unimplemented} elements without objects, {undriven} objects without elements");
+
+	Ok(if unimplemented + undriven > 0 {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	})
+}
@@ -0,0 +1,130 @@
+use std::io::BufRead;
+
+use anyhow::{bail, Result};
+
+pub struct Manifest {
+	pub entries: Vec<Entry>,
+}
+
+pub struct Entry {
+	pub icao: Option<String>,
+	pub id: String,
+}
+
+impl Manifest {
+	pub fn read(reader: impl BufRead) -> Result<Self> {
+		let mut entries = Vec::new();
+
+		for (i, line) in reader.lines().enumerate() {
+			let line = line?;
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue
+			}
+
+			let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+			let (icao, id) = match fields[..] {
+				[id] => (None, id),
+				[icao, id] => (Some(icao.into()), id),
+				_ => bail!("line {}: expected at most two fields", i + 1),
+			};
+
+			// allow a header row, as is common for csv
+			if i == 0 && id.eq_ignore_ascii_case("id") {
+				continue
+			}
+
+			if id.is_empty() {
+				bail!("line {}: empty id", i + 1);
+			}
+
+			entries.push(Entry {
+				icao,
+				id: id.into(),
+			});
+		}
+
+		Ok(Self { entries })
+	}
+}
+
+impl Entry {
+	pub fn matches(&self, icao: &str, id: &str) -> bool {
+		self.icao.as_ref().is_none_or(|i| i == icao) && glob(&self.id, id)
+	}
+}
+
+fn glob(pattern: &str, s: &str) -> bool {
+	let pattern = pattern.chars().collect::<Vec<_>>();
+	let s = s.chars().collect::<Vec<_>>();
+
+	// position to resume from after the most recent star
+	let mut star = None;
+	let (mut p, mut i) = (0, 0);
+
+	while i < s.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				star = Some((p, i));
+				p += 1;
+			},
+			Some('?') => (p, i) = (p + 1, i + 1),
+			Some(c) if *c == s[i] => (p, i) = (p + 1, i + 1),
+			_ => match star {
+				Some((sp, si)) => {
+					star = Some((sp, si + 1));
+					(p, i) = (sp + 1, si + 1);
+				},
+				None => return false,
+			},
+		}
+	}
+
+	pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn manifests_read_ids_with_or_without_icaos() {
+		let manifest = Manifest::read(
+			"icao,id\n# comment\n\nEGXX, STOP_A1\nLEAD_B1\n".as_bytes(),
+		)
+		.unwrap();
+
+		let entries = (manifest.entries.iter())
+			.map(|entry| (entry.icao.as_deref(), entry.id.as_str()))
+			.collect::<Vec<_>>();
+		assert_eq!(entries, [(Some("EGXX"), "STOP_A1"), (None, "LEAD_B1")]);
+
+		assert!(Manifest::read("EGXX,STOP_A1,extra".as_bytes()).is_err());
+		assert!(Manifest::read("EGXX,".as_bytes()).is_err());
+	}
+
+	#[test]
+	fn entries_match_globs_at_their_aerodrome() {
+		let entry = |icao: Option<&str>, id: &str| Entry {
+			icao: icao.map(Into::into),
+			id: id.into(),
+		};
+
+		for (pattern, id, matches) in [
+			("STOP_A1", "STOP_A1", true),
+			("STOP_A1", "STOP_A10", false),
+			("STOP_A?", "STOP_A1", true),
+			("STOP_A?", "STOP_A10", false),
+			("STOP_*", "STOP_A10", true),
+			("*_A1", "STOP_A1", true),
+			("*_A*1", "STOP_A101", true),
+			("*_A*1", "STOP_A10", false),
+		] {
+			let found = entry(None, pattern).matches("EGXX", id);
+			assert_eq!(found, matches, "{pattern} against {id}");
+		}
+
+		assert!(entry(Some("EGXX"), "STOP_*").matches("EGXX", "STOP_A1"));
+		assert!(!entry(Some("EGYY"), "STOP_*").matches("EGXX", "STOP_A1"));
+	}
+}
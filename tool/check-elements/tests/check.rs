@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+use bars_config::{AerodromeSource, Config, Loadable};
+
+fn fixture(name: &str) -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/fixtures")
+		.join(name)
+}
+
+/// Writes a config of the aerodrome source in `tests/fixtures`, returning its
+/// path.
+fn write_config() -> PathBuf {
+	let source = std::fs::read_to_string(fixture("egxx.toml")).unwrap();
+	let source = toml::from_str::<AerodromeSource>(&source).unwrap();
+	let config = Config {
+		name: Some("fixture".into()),
+		version: None,
+		aerodromes: vec![source.compile().unwrap()],
+	};
+
+	let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("egxx.bars");
+	config.save(File::create(&path).unwrap()).unwrap();
+	path
+}
+
+/// Returns the path of the config, written once for every test.
+fn config() -> &'static Path {
+	static CONFIG: OnceLock<PathBuf> = OnceLock::new();
+	CONFIG.get_or_init(write_config)
+}
+
+fn check(manifest: &str, args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_bars-check-elements"))
+		.args(args)
+		.arg(config())
+		.arg(fixture(manifest))
+		.output()
+		.unwrap()
+}
+
+#[test]
+fn complete_manifests_pass() {
+	let output = check("complete.csv", &[]);
+	assert!(output.status.success());
+	assert_eq!(
+		String::from_utf8(output.stdout).unwrap(),
+		"0 elements without objects, 0 objects without elements\n",
+	);
+}
+
+#[test]
+fn mismatches_are_reported_both_ways() {
+	let output = check("mismatched.csv", &[]);
+	assert!(!output.status.success());
+	assert_eq!(
+		String::from_utf8(output.stdout).unwrap(),
+		"EGXX: element EGXX_LEAD_B2 has no object\n\
+		 object EGXX_APRON_? has no element\n\
+		 1 elements without objects, 1 objects without elements\n",
+	);
+
+	let output = check("mismatched.csv", &["--allow-undriven"]);
+	assert!(!output.status.success());
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	assert!(stdout
+		.ends_with("1 elements without objects, 0 objects without elements\n"));
+
+	let output = check(
+		"mismatched.csv",
+		&["--allow-undriven", "--allow-unimplemented"],
+	);
+	assert!(output.status.success());
+}
+
+#[test]
+fn unknown_aerodromes_are_refused() {
+	let output = check("complete.csv", &["--aerodrome", "EGYY"]);
+	assert!(!output.status.success());
+	assert!(
+		String::from_utf8_lossy(&output.stderr).contains("unknown aerodrome EGYY")
+	);
+}
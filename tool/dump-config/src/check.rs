@@ -0,0 +1,53 @@
+use std::io::Write;
+use std::process::ExitCode;
+
+use bars_config::{Config, Issue, Severity};
+
+use anyhow::Result;
+
+use serde_json::{json, Value};
+
+/// Writes the issues found in `config`, returning the exit code for them.
+///
+/// Issues with codes in `allow` are ignored. Warnings only fail the check if
+/// `strict` is set.
+pub fn check(
+	output: &mut impl Write,
+	config: &Config,
+	allow: &[String],
+	strict: bool,
+	json: bool,
+) -> Result<ExitCode> {
+	let issues = config
+		.validate()
+		.into_iter()
+		.filter(|issue| !allow.iter().any(|code| code == issue.code()))
+		.collect::<Vec<_>>();
+
+	if json {
+		let issues = issues.iter().map(issue_json).collect::<Vec<_>>();
+		serde_json::to_writer_pretty(&mut *output, &issues)?;
+		writeln!(output)?;
+	} else {
+		for issue in &issues {
+			writeln!(output, "{issue}")?;
+		}
+	}
+
+	let severity = issues.iter().map(Issue::severity).max();
+	Ok(match severity {
+		Some(Severity::Error) => ExitCode::from(2),
+		Some(Severity::Warning) if strict => ExitCode::from(1),
+		_ => ExitCode::SUCCESS,
+	})
+}
+
+fn issue_json(issue: &Issue) -> Value {
+	json!({
+		"severity": issue.severity().to_string(),
+		"code": issue.code(),
+		"icao": issue.icao,
+		"location": issue.location,
+		"message": issue.kind.to_string(),
+	})
+}
@@ -0,0 +1,74 @@
+use std::io::Write;
+use std::process::ExitCode;
+
+use bars_config::Config;
+
+use anyhow::Result;
+
+use serde_json::{json, Value};
+
+/// Writes the gaps in the maps of each aerodrome of `config`, grouped by map,
+/// returning the exit code for them.
+///
+/// Nodes and edges fixed in every profile are left out if `skip_fixed` is
+/// set. Gaps only fail the report if `strict` is set.
+pub fn coverage(
+	output: &mut impl Write,
+	config: &Config,
+	skip_fixed: bool,
+	strict: bool,
+	json: bool,
+) -> Result<ExitCode> {
+	let mut total = 0;
+	let mut values = Vec::new();
+
+	for aerodrome in &config.aerodromes {
+		let mut report = aerodrome.display_coverage();
+		if skip_fixed {
+			report.exclude_fixed(aerodrome);
+		}
+
+		total += report.gaps();
+
+		if report.maps.is_empty() && !json {
+			writeln!(output, "{}: no maps", aerodrome.icao)?;
+		}
+
+		for map in &report.maps {
+			let gaps = (map.gaps.iter())
+				.map(|gap| gap.describe(aerodrome))
+				.collect::<Vec<_>>();
+
+			if json {
+				values.push(json!({
+					"icao": aerodrome.icao,
+					"map": map.map,
+					"gaps": gaps,
+				}));
+				continue
+			}
+
+			writeln!(
+				output,
+				"{}: {}: {} gaps",
+				aerodrome.icao,
+				map.map,
+				gaps.len()
+			)?;
+			for gap in &gaps {
+				writeln!(output, "\t{gap}")?;
+			}
+		}
+	}
+
+	if json {
+		serde_json::to_writer_pretty(&mut *output, &Value::from(values))?;
+		writeln!(output)?;
+	}
+
+	Ok(if strict && total > 0 {
+		ExitCode::from(1)
+	} else {
+		ExitCode::SUCCESS
+	})
+}
@@ -0,0 +1,104 @@
+use std::io::{Result, Write};
+
+use bars_config::{Aerodrome, Config, NodeCondition, Profile};
+
+/// Writes the routing graph of each aerodrome as a Graphviz digraph.
+///
+/// Nodes are coloured by their condition in `profile`, if given.
+pub fn write_dot(
+	output: &mut impl Write,
+	config: &Config,
+	profile: Option<&str>,
+) -> Result<()> {
+	for aerodrome in &config.aerodromes {
+		let profile = profile.and_then(|id| {
+			aerodrome.profiles.iter().find(|profile| profile.id == id)
+		});
+
+		write_aerodrome(output, aerodrome, profile)?;
+	}
+
+	Ok(())
+}
+
+fn write_aerodrome(
+	output: &mut impl Write,
+	aerodrome: &Aerodrome,
+	profile: Option<&Profile>,
+) -> Result<()> {
+	writeln!(output, "digraph {} {{", quote(&aerodrome.icao))?;
+	writeln!(output, "\tnode [shape=box];")?;
+
+	let node = |i: usize| {
+		let mut attrs = format!("label={}", quote(&aerodrome.nodes[i].id));
+
+		if aerodrome.nodes[i].parent.is_some() {
+			attrs += ", shape=ellipse";
+		}
+
+		if let Some(condition) = profile.and_then(|p| p.nodes.get(i)) {
+			let color = match condition {
+				NodeCondition::Fixed { .. } => "lightgrey",
+				NodeCondition::Direct { .. } => "lightblue",
+				NodeCondition::Router { .. } => "palegreen",
+			};
+
+			attrs += &format!(", style=filled, fillcolor={color}");
+		}
+
+		format!("n{i} [{attrs}];")
+	};
+
+	for (i, parent) in aerodrome.nodes.iter().enumerate() {
+		if parent.parent.is_some() {
+			continue
+		}
+
+		let children = aerodrome
+			.nodes
+			.iter()
+			.enumerate()
+			.filter(|(_, child)| child.parent.is_some_and(|p| p.0 == i))
+			.map(|(j, _)| j)
+			.collect::<Vec<_>>();
+
+		if children.is_empty() {
+			writeln!(output, "\t{}", node(i))?;
+			continue
+		}
+
+		writeln!(output, "\tsubgraph cluster_n{i} {{")?;
+		writeln!(output, "\t\tstyle=dashed;")?;
+		writeln!(output, "\t\t{}", node(i))?;
+		for j in children {
+			writeln!(output, "\t\t{}", node(j))?;
+		}
+		writeln!(output, "\t}}")?;
+	}
+
+	for (i, block) in aerodrome.blocks.iter().enumerate() {
+		writeln!(
+			output,
+			"\tb{i} [label={}, shape=diamond];",
+			quote(&block.id)
+		)?;
+
+		for node in &block.nodes {
+			writeln!(output, "\tn{} -> b{i} [dir=none];", node.0)?;
+		}
+
+		for route in &block.non_routes {
+			writeln!(
+				output,
+				"\tn{} -> n{} [style=dashed, color=red];",
+				route.from.0, route.to.0,
+			)?;
+		}
+	}
+
+	writeln!(output, "}}")
+}
+
+fn quote(s: &str) -> String {
+	format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
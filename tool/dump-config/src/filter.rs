@@ -0,0 +1,166 @@
+use bars_config::Config;
+
+use anyhow::{bail, Result};
+
+use clap::ValueEnum;
+
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Section {
+	Nodes,
+	Edges,
+	Blocks,
+	HoldingPoints,
+	Elements,
+	Profiles,
+	Presets,
+	Strings,
+	Maps,
+}
+
+pub struct Filter {
+	pub aerodromes: Vec<String>,
+	pub sections: Vec<Section>,
+	pub profile: Option<String>,
+	pub maps: bool,
+}
+
+impl Filter {
+	/// Removes aerodromes and profiles which were not selected.
+	pub fn select(&self, config: &mut Config) -> Result<()> {
+		for icao in &self.aerodromes {
+			if !config.aerodromes.iter().any(|a| &a.icao == icao) {
+				bail!("unknown aerodrome {icao}");
+			}
+		}
+
+		if !self.aerodromes.is_empty() {
+			config
+				.aerodromes
+				.retain(|aerodrome| self.aerodromes.contains(&aerodrome.icao));
+		}
+
+		if let Some(id) = &self.profile {
+			let mut found = false;
+			for aerodrome in &mut config.aerodromes {
+				aerodrome.profiles.retain(|profile| &profile.id == id);
+				found |= !aerodrome.profiles.is_empty();
+			}
+
+			if !found {
+				bail!("unknown profile {id}");
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Empties the sections which were not selected.
+	pub fn strip(&self, config: &mut Config) {
+		for aerodrome in &mut config.aerodromes {
+			if !self.includes(Section::Nodes) {
+				aerodrome.nodes.clear();
+			}
+
+			if !self.includes(Section::Edges) {
+				aerodrome.edges.clear();
+			}
+
+			if !self.includes(Section::Blocks) {
+				aerodrome.blocks.clear();
+			}
+
+			if !self.includes(Section::HoldingPoints) {
+				aerodrome.holding_points.clear();
+			}
+
+			if !self.includes(Section::Elements) {
+				aerodrome.elements.clear();
+			}
+
+			for profile in &mut aerodrome.profiles {
+				if !self.includes(Section::Profiles) {
+					profile.nodes.clear();
+					profile.edges.clear();
+					profile.blocks.clear();
+				}
+
+				if !self.includes(Section::Presets) {
+					profile.presets.clear();
+				}
+			}
+
+			if !self.includes(Section::Profiles) && !self.includes(Section::Presets) {
+				aerodrome.profiles.clear();
+			}
+
+			if !self.includes(Section::Strings) {
+				aerodrome.strings = None;
+			}
+
+			if !self.includes(Section::Maps) {
+				aerodrome.geo_map = None;
+				aerodrome.maps.clear();
+				aerodrome.styles.clear();
+			}
+		}
+	}
+
+	/// Removes the sections which were not selected from a serialised config.
+	pub fn strip_value(&self, value: &mut Value) {
+		let Some(aerodromes) =
+			value.get_mut("aerodromes").and_then(Value::as_array_mut)
+		else {
+			return
+		};
+
+		for aerodrome in aerodromes.iter_mut().filter_map(Value::as_object_mut) {
+			for (section, key) in [
+				(Section::Nodes, "nodes"),
+				(Section::Edges, "edges"),
+				(Section::Blocks, "blocks"),
+				(Section::HoldingPoints, "holding_points"),
+				(Section::Elements, "elements"),
+				(Section::Strings, "strings"),
+				(Section::Maps, "geo_map"),
+				(Section::Maps, "maps"),
+				(Section::Maps, "styles"),
+			] {
+				if !self.includes(section) {
+					aerodrome.remove(key);
+				}
+			}
+
+			if !self.includes(Section::Profiles) && !self.includes(Section::Presets) {
+				aerodrome.remove("profiles");
+				continue
+			}
+
+			let profiles = aerodrome
+				.get_mut("profiles")
+				.and_then(Value::as_array_mut)
+				.into_iter()
+				.flatten()
+				.filter_map(Value::as_object_mut);
+
+			for profile in profiles {
+				for (section, key) in [
+					(Section::Profiles, "nodes"),
+					(Section::Profiles, "edges"),
+					(Section::Profiles, "blocks"),
+					(Section::Presets, "presets"),
+				] {
+					if !self.includes(section) {
+						profile.remove(key);
+					}
+				}
+			}
+		}
+	}
+
+	fn includes(&self, section: Section) -> bool {
+		(self.sections.is_empty() || self.sections.contains(&section))
+			&& (section != Section::Maps || self.maps)
+	}
+}
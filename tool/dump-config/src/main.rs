@@ -1,6 +1,342 @@
-use bars_config::Loadable;
+mod check;
+mod coverage;
+mod dot;
+mod filter;
+mod resolve;
+mod stats;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use bars_config::{Config, Loadable, Maps};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use clap::{ArgGroup, Parser, ValueEnum};
+
+use filter::{Filter, Section};
+
+/// Print the contents of a BARS config read from FILE, or stdin if none.
+///
+/// Exits with 2 if the config or maps cannot be loaded.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+#[command(group(ArgGroup::new("report").args(["check", "coverage"])))]
+#[command(group(
+	ArgGroup::new("selection")
+		.args(["aerodromes", "icaos", "sections", "profile", "no_maps"])
+		.multiple(true)
+))]
+struct Args {
+	/// config file to read rather than stdin
+	#[arg(value_name = "FILE")]
+	file: Option<PathBuf>,
+
+	/// print the maps file FILE rather than a config
+	#[arg(
+		long,
+		value_name = "FILE",
+		conflicts_with_all = ["file", "selection", "check", "coverage", "stats", "summary", "resolve_refs"],
+	)]
+	maps: Option<PathBuf>,
+
+	/// print in FORMAT
+	#[arg(short, long, value_enum, default_value_t, value_name = "FORMAT")]
+	format: Format,
+
+	/// print over multiple indented lines (default)
+	#[arg(long, overrides_with = "compact")]
+	pretty: bool,
+
+	/// print on a single line
+	#[arg(long, overrides_with = "pretty")]
+	compact: bool,
+
+	/// write to FILE rather than stdout
+	#[arg(short, long, value_name = "FILE")]
+	output: Option<PathBuf>,
+
+	/// print referenced ids rather than indices (JSON only)
+	#[arg(long)]
+	resolve_refs: bool,
+
+	/// print only aerodrome ICAO
+	#[arg(short, long = "aerodrome", value_name = "ICAO")]
+	aerodromes: Vec<String>,
+
+	/// load only aerodrome ICAO, without decoding the others if the config was
+	/// saved with an index
+	#[arg(long = "icao", value_name = "ICAO", conflicts_with = "aerodromes")]
+	icaos: Vec<String>,
+
+	/// print only SECTION of each aerodrome
+	#[arg(short, long = "section", value_enum, value_name = "SECTION")]
+	sections: Vec<Section>,
+
+	/// print only profile ID of each aerodrome, and colour nodes by their
+	/// conditions in it when printing DOT
+	#[arg(short, long, value_name = "ID")]
+	profile: Option<String>,
+
+	/// omit map display data
+	#[arg(long)]
+	no_maps: bool,
+
+	/// print problems with the config rather than its contents
+	#[arg(long, conflicts_with = "stats")]
+	check: bool,
+
+	/// print entities missing drawings or targets in each map rather than the
+	/// contents
+	#[arg(long, conflicts_with = "stats")]
+	coverage: bool,
+
+	/// leave out nodes and edges fixed in every profile from the coverage
+	#[arg(long, requires = "coverage")]
+	skip_fixed: bool,
+
+	/// fail the check on warnings as well as errors, or the coverage on any
+	/// gaps
+	#[arg(long, requires = "report")]
+	strict: bool,
+
+	/// ignore problems with CODE when checking
+	#[arg(long = "allow", value_name = "CODE", requires = "check")]
+	allowed: Vec<String>,
+
+	/// print entity counts, encoded sizes and content hashes rather than
+	/// contents
+	#[arg(long)]
+	stats: bool,
+
+	/// print a table of the entity counts and encoded size of each aerodrome,
+	/// counting the geo map among the maps, rather than contents
+	#[arg(long, conflicts_with_all = ["stats", "check", "coverage"])]
+	summary: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum Format {
+	#[default]
+	Debug,
+	Json,
+	Dot,
+	/// TOML aerodrome source, as read by build-config, without maps
+	Source,
+}
+
+fn main() -> Result<ExitCode> {
+	let args = Args::parse();
+
+	if args.resolve_refs && !matches!(args.format, Format::Json) {
+		bail!("--resolve-refs requires JSON output");
+	}
+
+	if let Some(path) = &args.maps {
+		let maps = match load_maps(path) {
+			Ok(maps) => maps,
+			Err(err) => {
+				eprintln!("error: {err:#}");
+				return Ok(ExitCode::from(2))
+			},
+		};
+
+		let mut output = open_output(args.output.as_deref())?;
+		write_maps(&mut output, &maps, args.format, args.compact)?;
+		output.flush()?;
+		return Ok(ExitCode::SUCCESS)
+	}
+
+	let filter = Filter {
+		aerodromes: args.aerodromes,
+		sections: args.sections,
+		profile: args.profile,
+		maps: !args.no_maps,
+	};
+
+	let loaded = match &args.file {
+		Some(path) => File::open(path)
+			.map_err(anyhow::Error::from)
+			.and_then(|file| load_config(BufReader::new(file), &args.icaos))
+			.with_context(|| path.display().to_string()),
+		None => load_config_stdin(&args.icaos),
+	};
+	let mut config = match loaded {
+		Ok(config) => config,
+		Err(err) => {
+			eprintln!("error: {err:#}");
+			return Ok(ExitCode::from(2))
+		},
+	};
+	filter.select(&mut config)?;
+
+	let mut output = open_output(args.output.as_deref())?;
+
+	if args.check {
+		let json = matches!(args.format, Format::Json);
+		let code =
+			check::check(&mut output, &config, &args.allowed, args.strict, json)?;
+
+		output.flush()?;
+		return Ok(code)
+	}
+
+	// problems are reported without failing, so broken configs can be inspected
+	for issue in config.validate() {
+		eprintln!("{issue}");
+	}
+
+	if args.coverage {
+		let json = matches!(args.format, Format::Json);
+		let code = coverage::coverage(
+			&mut output,
+			&config,
+			args.skip_fixed,
+			args.strict,
+			json,
+		)?;
+
+		output.flush()?;
+		return Ok(code)
+	}
+
+	if args.stats {
+		stats::write_stats(&mut output, &config)?;
+		output.flush()?;
+		return Ok(ExitCode::SUCCESS)
+	}
+
+	if args.summary {
+		let json = matches!(args.format, Format::Json);
+		stats::write_summary(&mut output, &config, json)?;
+		output.flush()?;
+		return Ok(ExitCode::SUCCESS)
+	}
+
+	match args.format {
+		Format::Debug => {
+			filter.strip(&mut config);
+			if args.compact {
+				writeln!(output, "{config:?}")?;
+			} else {
+				writeln!(output, "{config:#?}")?;
+			}
+		},
+		Format::Json => {
+			let mut value = serde_json::to_value(&config)?;
+			if args.resolve_refs {
+				resolve::resolve_refs(&config, &mut value);
+			}
+
+			filter.strip_value(&mut value);
+
+			if args.compact {
+				serde_json::to_writer(&mut output, &value)?;
+			} else {
+				serde_json::to_writer_pretty(&mut output, &value)?;
+			}
+
+			writeln!(output)?;
+		},
+		Format::Dot => {
+			dot::write_dot(&mut output, &config, filter.profile.as_deref())?;
+		},
+		Format::Source => {
+			let [aerodrome] = config.aerodromes.as_slice() else {
+				bail!("source holds one aerodrome, so select one with --aerodrome");
+			};
+
+			if aerodrome.geo_map.is_some() || !aerodrome.maps.is_empty() {
+				eprintln!("warning: maps are not written to source");
+			}
+
+			let source = aerodrome.to_source().map_err(|errors| {
+				for error in &errors {
+					eprintln!("error: {}: {error}", aerodrome.icao);
+				}
+				anyhow!("{} errors writing source", errors.len())
+			})?;
+
+			write!(output, "{}", toml::to_string(&source)?)?;
+		},
+	}
+
+	output.flush()?;
+
+	Ok(ExitCode::SUCCESS)
+}
+
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+	Ok(match path {
+		Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+		None => Box::new(std::io::stdout().lock()),
+	})
+}
+
+fn load_config_stdin(icaos: &[String]) -> Result<Config> {
+	if icaos.is_empty() {
+		return Ok(Config::load(std::io::stdin())?)
+	}
+
+	// stdin cannot seek, so is read in full, though only the index is decoded
+	let mut data = Vec::new();
+	std::io::stdin().read_to_end(&mut data)?;
+	load_config(Cursor::new(data), icaos)
+}
+
+/// Loads the config, or if aerodromes are given, only those, decoding no
+/// others if the config has an index.
+fn load_config(reader: impl Read + Seek, icaos: &[String]) -> Result<Config> {
+	if icaos.is_empty() {
+		return Ok(Config::load(reader)?)
+	}
+
+	let mut handle = Config::open(reader)?;
+	let mut aerodromes = Vec::new();
+	for icao in icaos {
+		let Some(aerodrome) = handle.load_aerodrome(icao)? else {
+			bail!("unknown aerodrome {icao}");
+		};
+
+		aerodromes.push(aerodrome);
+	}
+
+	Ok(Config {
+		name: handle.name().map(Into::into),
+		version: handle.version().map(Into::into),
+		aerodromes,
+	})
+}
+
+fn load_maps(path: &Path) -> Result<Maps> {
+	let file = File::open(path).with_context(|| path.display().to_string())?;
+	Maps::load(BufReader::new(file)).with_context(|| path.display().to_string())
+}
+
+fn write_maps(
+	output: &mut impl Write,
+	maps: &Maps,
+	format: Format,
+	compact: bool,
+) -> Result<()> {
+	match format {
+		Format::Debug if compact => writeln!(output, "{maps:?}")?,
+		Format::Debug => writeln!(output, "{maps:#?}")?,
+		Format::Json => {
+			if compact {
+				serde_json::to_writer(&mut *output, maps)?;
+			} else {
+				serde_json::to_writer_pretty(&mut *output, maps)?;
+			}
+
+			writeln!(output)?;
+		},
+		Format::Dot | Format::Source => {
+			bail!("maps can be printed only as debug or JSON")
+		},
+	}
 
-fn main() -> anyhow::Result<()> {
-	println!("{:#?}", bars_config::Config::load(std::io::stdin())?);
 	Ok(())
 }
@@ -0,0 +1,161 @@
+use bars_config::{Aerodrome, Config};
+
+use serde_json::Value;
+
+struct Ids<'a> {
+	nodes: Vec<&'a str>,
+	edges: Vec<&'a str>,
+	blocks: Vec<&'a str>,
+	holding_points: Vec<&'a str>,
+}
+
+/// Replaces node, edge, block, holding point, and preset refs in the serialised `config` with the
+/// ids of their targets.
+pub fn resolve_refs(config: &Config, value: &mut Value) {
+	for (aerodrome, value) in
+		config.aerodromes.iter().zip(items(value, "aerodromes"))
+	{
+		resolve_aerodrome(aerodrome, value);
+	}
+}
+
+fn resolve_aerodrome(aerodrome: &Aerodrome, value: &mut Value) {
+	let ids = Ids {
+		nodes: aerodrome
+			.nodes
+			.iter()
+			.map(|node| node.id.as_str())
+			.collect(),
+		edges: aerodrome
+			.edges
+			.iter()
+			.map(|edge| edge.id.as_str())
+			.collect(),
+		blocks: aerodrome
+			.blocks
+			.iter()
+			.map(|block| block.id.as_str())
+			.collect(),
+		holding_points: aerodrome
+			.holding_points
+			.iter()
+			.map(|holding_point| holding_point.id.as_str())
+			.collect(),
+	};
+
+	for element in items(value, "elements") {
+		if let Some(condition) = element.get_mut("condition") {
+			resolve(condition.get_mut("Node"), &ids.nodes);
+			resolve(condition.get_mut("Edge"), &ids.edges);
+		}
+	}
+
+	for node in items(value, "nodes") {
+		resolve(node.get_mut("parent"), &ids.nodes);
+	}
+
+	for block in items(value, "blocks") {
+		resolve_all(block, "nodes", &ids.nodes);
+		resolve_all(block, "edges", &ids.edges);
+		items(block, "non_routes").for_each(|route| resolve_route(route, &ids));
+	}
+
+	for holding_point in items(value, "holding_points") {
+		if let Some(location) = holding_point.get_mut("location") {
+			resolve(location.get_mut("Edge"), &ids.edges);
+			if let Some(nodes) = location.get_mut("Nodes") {
+				resolve(nodes.get_mut(0), &ids.nodes);
+				resolve(nodes.get_mut(1), &ids.nodes);
+			}
+		}
+	}
+
+	let profiles = aerodrome.profiles.iter().map(|profile| {
+		(profile.presets.iter())
+			.map(|preset| preset.name.as_str())
+			.collect::<Vec<_>>()
+	});
+
+	for (presets, profile) in profiles.zip(items(value, "profiles")) {
+		for edge in items(profile, "edges") {
+			if let Some(nodes) = edge.pointer_mut("/Direct/nodes") {
+				for conjunction in items(nodes, "disjunction") {
+					resolve_all(conjunction, "positive", &ids.nodes);
+					resolve_all(conjunction, "negative", &ids.nodes);
+				}
+			}
+
+			if let Some(router) = edge.get_mut("Router") {
+				resolve(router.get_mut("block"), &ids.blocks);
+				items(router, "routes").for_each(|route| resolve_route(route, &ids));
+			}
+		}
+
+		for preset in items(profile, "presets") {
+			resolve_all(preset, "includes", &presets);
+
+			for node in items(preset, "nodes") {
+				resolve(node.get_mut(0), &ids.nodes);
+			}
+
+			for block in items(preset, "blocks") {
+				resolve(block.get_mut(0), &ids.blocks);
+				if let Some(route) = block.pointer_mut("/1/Route") {
+					resolve(route.get_mut(0), &ids.nodes);
+					resolve(route.get_mut(1), &ids.nodes);
+				}
+			}
+		}
+	}
+
+	for map in items(value, "maps") {
+		resolve_widgets(map, &ids);
+	}
+
+	if let Some(geo_map) = value.get_mut("geo_map") {
+		resolve_widgets(geo_map, &ids);
+	}
+}
+
+fn resolve_widgets(map: &mut Value, ids: &Ids) {
+	for widget in items(map, "widgets") {
+		for kind in ["/Countdown/condition", "/Text/condition"] {
+			if let Some(condition) = widget.pointer_mut(kind) {
+				resolve(condition.get_mut("Node"), &ids.nodes);
+				resolve(condition.get_mut("Block"), &ids.blocks);
+			}
+		}
+
+		if let Some(label) = widget.get_mut("Label") {
+			resolve(label.get_mut("holding_point"), &ids.holding_points);
+		}
+	}
+}
+
+fn resolve_route(route: &mut Value, ids: &Ids) {
+	resolve(route.get_mut("from"), &ids.nodes);
+	resolve(route.get_mut("to"), &ids.nodes);
+}
+
+fn resolve_all(value: &mut Value, key: &str, ids: &[&str]) {
+	items(value, key).for_each(|value| resolve(Some(value), ids));
+}
+
+fn resolve(value: Option<&mut Value>, ids: &[&str]) {
+	if let Some(value) = value {
+		if let Some(id) = value.as_u64().and_then(|i| ids.get(i as usize)) {
+			*value = Value::from(*id);
+		}
+	}
+}
+
+fn items<'a>(
+	value: &'a mut Value,
+	key: &str,
+) -> impl Iterator<Item = &'a mut Value> {
+	value
+		.get_mut(key)
+		.and_then(Value::as_array_mut)
+		.into_iter()
+		.flatten()
+}
@@ -0,0 +1,246 @@
+use std::io::Write;
+
+use bars_config::{
+	Aerodrome, Config, EdgeDisplay, NodeDisplay, Path, Projectable, Target,
+};
+
+use anyhow::Result;
+
+use serde_json::json;
+
+#[derive(Default)]
+struct MapStats {
+	paths: usize,
+	points: usize,
+}
+
+impl MapStats {
+	fn add_paths<'a, T: Projectable + 'a>(
+		&mut self,
+		paths: impl Iterator<Item = &'a Path<T>>,
+	) {
+		for path in paths {
+			self.paths += 1;
+			self.points += path.points.len();
+		}
+	}
+
+	fn add_target<T: Projectable>(&mut self, target: &Target<T>) {
+		self.points += target.polygons.iter().map(Vec::len).sum::<usize>();
+	}
+
+	fn add_displays<'a, T: Projectable + 'a>(
+		&mut self,
+		nodes: &'a [NodeDisplay<T>],
+		edges: &'a [EdgeDisplay<T>],
+	) {
+		for node in nodes {
+			self.add_paths(node.paths());
+			self.add_target(&node.target);
+		}
+
+		for edge in edges {
+			self.add_paths(edge.paths());
+		}
+	}
+}
+
+/// Writes entity counts, encoded sizes and content hashes for each aerodrome.
+pub fn write_stats(output: &mut impl Write, config: &Config) -> Result<()> {
+	let mut totals = Vec::new();
+
+	for aerodrome in &config.aerodromes {
+		writeln!(output, "{}", aerodrome.icao)?;
+		writeln!(output, "  content hash: {}", hex(&aerodrome.content_hash()))?;
+		write_counts(output, aerodrome)?;
+
+		let mut sizes = aerodrome.encoded_sizes()?;
+		sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+
+		let total = sizes.iter().map(|(_, size)| size).sum::<usize>();
+		let compressed = aerodrome.compressed_size()?;
+
+		writeln!(output, "  encoded bytes:")?;
+		for (section, size) in sizes {
+			writeln!(output, "    {section:<10} {size:>10}")?;
+		}
+		writeln!(output, "    {:<10} {total:>10}", "total")?;
+		writeln!(output, "    {:<10} {compressed:>10}", "compressed")?;
+		writeln!(output)?;
+
+		totals.push((aerodrome, total, compressed));
+	}
+
+	totals.sort_by_key(|(_, total, _)| std::cmp::Reverse(*total));
+
+	// with a dictionary shared between aerodromes, if there is more than one
+	let dict = config.build_dictionary()?;
+	write!(
+		output,
+		"{:<8} {:>10} {:>10}",
+		"icao", "encoded", "compressed"
+	)?;
+	if !dict.is_empty() {
+		write!(output, " {:>10}", "shared")?;
+	}
+	writeln!(output)?;
+
+	for (aerodrome, total, compressed) in totals {
+		let icao = &aerodrome.icao;
+		write!(output, "{icao:<8} {total:>10} {compressed:>10}")?;
+		if !dict.is_empty() {
+			let shared = aerodrome.encode_with_dict(&dict)?.len();
+			write!(output, " {shared:>10}")?;
+		}
+		writeln!(output)?;
+	}
+
+	if !dict.is_empty() {
+		writeln!(output)?;
+		writeln!(output, "shared dictionary: {} bytes", dict.len())?;
+	}
+
+	writeln!(output)?;
+	writeln!(output, "content hash: {}", hex(&config.content_hash()))?;
+
+	Ok(())
+}
+
+/// Writes a table of the entity counts and encoded size of each aerodrome, or
+/// a list of them as JSON.
+pub fn write_summary(
+	output: &mut impl Write,
+	config: &Config,
+	json: bool,
+) -> Result<()> {
+	let mut rows = Vec::new();
+	for aerodrome in &config.aerodromes {
+		rows.push((aerodrome, aerodrome.encode()?.len()));
+	}
+
+	let maps = |aerodrome: &Aerodrome| {
+		aerodrome.maps.len() + usize::from(aerodrome.geo_map.is_some())
+	};
+
+	if json {
+		let rows = (rows.iter())
+			.map(|(aerodrome, size)| {
+				json!({
+					"icao": aerodrome.icao,
+					"elements": aerodrome.elements.len(),
+					"nodes": aerodrome.nodes.len(),
+					"edges": aerodrome.edges.len(),
+					"blocks": aerodrome.blocks.len(),
+					"profiles": aerodrome.profiles.len(),
+					"maps": maps(aerodrome),
+					"encoded": size,
+				})
+			})
+			.collect::<Vec<_>>();
+
+		serde_json::to_writer_pretty(&mut *output, &rows)?;
+		writeln!(output)?;
+		return Ok(())
+	}
+
+	writeln!(
+		output,
+		"{:<8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>10}",
+		"icao",
+		"elements",
+		"nodes",
+		"edges",
+		"blocks",
+		"profiles",
+		"maps",
+		"encoded",
+	)?;
+	for (aerodrome, size) in rows {
+		writeln!(
+			output,
+			"{:<8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {size:>10}",
+			aerodrome.icao,
+			aerodrome.elements.len(),
+			aerodrome.nodes.len(),
+			aerodrome.edges.len(),
+			aerodrome.blocks.len(),
+			aerodrome.profiles.len(),
+			maps(aerodrome),
+		)?;
+	}
+
+	Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn write_counts(output: &mut impl Write, aerodrome: &Aerodrome) -> Result<()> {
+	let presets = aerodrome
+		.profiles
+		.iter()
+		.map(|p| p.presets.len())
+		.sum::<usize>();
+
+	writeln!(output, "  elements: {}", aerodrome.elements.len())?;
+	writeln!(output, "  nodes: {}", aerodrome.nodes.len())?;
+	writeln!(output, "  edges: {}", aerodrome.edges.len())?;
+	writeln!(output, "  blocks: {}", aerodrome.blocks.len())?;
+	writeln!(
+		output,
+		"  holding points: {}",
+		aerodrome.holding_points.len()
+	)?;
+	writeln!(output, "  profiles: {}", aerodrome.profiles.len())?;
+	writeln!(output, "  presets: {presets}")?;
+	if let Some(strings) = &aerodrome.strings {
+		writeln!(
+			output,
+			"  strings: {} in {} locales",
+			strings.strings.len(),
+			strings.locales().len()
+		)?;
+	}
+	writeln!(output, "  styles: {}", aerodrome.styles.len())?;
+
+	if let Some(geo_map) = &aerodrome.geo_map {
+		let mut stats = MapStats::default();
+		stats.add_displays(&geo_map.nodes, &geo_map.edges);
+		geo_map
+			.blocks
+			.iter()
+			.for_each(|block| stats.add_target(&block.target));
+
+		writeln!(
+			output,
+			"  geo map: {} paths, {} points",
+			stats.paths, stats.points
+		)?;
+	}
+
+	for map in &aerodrome.maps {
+		let mut stats = MapStats::default();
+		stats.add_paths(map.base.iter());
+		stats.add_displays(&map.nodes, &map.edges);
+		map
+			.blocks
+			.iter()
+			.for_each(|block| stats.add_target(&block.target));
+
+		let views = map
+			.views
+			.iter()
+			.map(|view| view.name.as_str())
+			.collect::<Vec<_>>();
+		writeln!(
+			output,
+			"  map ({}): {} paths, {} points",
+			views.join(", "),
+			stats.paths,
+			stats.points,
+		)?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use bars_config::{
+	Aerodrome, AerodromeSource, Block, BlockCondition, Config, Loadable,
+	ResetCondition,
+};
+
+use serde_json::Value;
+
+fn fixture(name: &str) -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/fixtures")
+		.join(name)
+}
+
+/// Compiles the aerodrome source in `tests/fixtures` of the ICAO.
+fn aerodrome(icao: &str) -> Aerodrome {
+	let path = fixture(&format!("{}.toml", icao.to_lowercase()));
+	let source = std::fs::read_to_string(path).unwrap();
+	let source = toml::from_str::<AerodromeSource>(&source).unwrap();
+	source.compile().unwrap()
+}
+
+/// Writes a config of the aerodromes, returning its path.
+fn config(name: &str, aerodromes: Vec<Aerodrome>) -> PathBuf {
+	let config = Config {
+		name: Some("fixture".into()),
+		version: None,
+		aerodromes,
+	};
+
+	let path =
+		Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.bars"));
+	config.save(File::create(&path).unwrap()).unwrap();
+	path
+}
+
+fn dump(config: &Path, args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_bars-dump-config"))
+		.args(args)
+		.arg(config)
+		.output()
+		.unwrap()
+}
+
+/// Dumps the config, checking that it succeeded, and returns what was printed.
+fn dumped(config: &Path, args: &[&str]) -> String {
+	let output = dump(config, args);
+	assert!(
+		output.status.success(),
+		"{}",
+		String::from_utf8_lossy(&output.stderr),
+	);
+
+	String::from_utf8(output.stdout).unwrap()
+}
+
+/// Checks the output against the file of the name in `tests/fixtures`.
+fn check(output: &str, name: &str) {
+	let expected = std::fs::read_to_string(fixture(name)).unwrap();
+	assert_eq!(output, expected, "output differs from {name}");
+}
+
+#[test]
+fn formats_match_snapshots() {
+	let path = config("formats", vec![aerodrome("ZZZZ")]);
+
+	check(&dumped(&path, &[]), "zzzz.debug");
+	check(&dumped(&path, &["--format", "json"]), "zzzz.json");
+	check(
+		&dumped(&path, &["--format", "json", "--resolve-refs"]),
+		"zzzz.resolved.json",
+	);
+
+	// compact JSON is the same value on one line
+	let compact = dumped(&path, &["--format", "json", "--compact"]);
+	assert_eq!(compact.lines().count(), 1);
+	assert_eq!(
+		serde_json::from_str::<Value>(&compact).unwrap(),
+		serde_json::from_str::<Value>(&dumped(&path, &["--format", "json"]))
+			.unwrap(),
+	);
+}
+
+#[test]
+fn dot_matches_golden() {
+	let path = config("dot", vec![aerodrome("ZZZZ")]);
+
+	check(&dumped(&path, &["--format", "dot"]), "zzzz.dot");
+	check(
+		&dumped(&path, &["--format", "dot", "--profile", "default"]),
+		"zzzz.default.dot",
+	);
+}
+
+#[test]
+fn aerodrome_and_section_filters_combine() {
+	let path = config("filters", vec![aerodrome("YYYY"), aerodrome("ZZZZ")]);
+	let args = ["--format", "json", "--aerodrome", "ZZZZ", "--section"];
+
+	let value = dumped(
+		&path,
+		&[&args[..], &["blocks", "--section", "presets"]].concat(),
+	);
+	let value = serde_json::from_str::<Value>(&value).unwrap();
+
+	let aerodromes = value["aerodromes"].as_array().unwrap();
+	assert_eq!(aerodromes.len(), 1);
+
+	let aerodrome = aerodromes[0].as_object().unwrap();
+	let mut keys = aerodrome.keys().map(String::as_str).collect::<Vec<_>>();
+	keys.sort();
+	assert_eq!(keys, ["blocks", "icao", "profiles"]);
+	assert_eq!(aerodrome["icao"], "ZZZZ");
+	assert_eq!(aerodrome["blocks"][0]["id"], "B1");
+
+	// profiles keep only their presets
+	let profile = aerodrome["profiles"][0].as_object().unwrap();
+	assert_eq!(profile["presets"][0]["name"], "Open");
+	assert!(!profile.contains_key("nodes"));
+
+	// the debug format is filtered the same
+	let debug = dumped(&path, &["--aerodrome", "ZZZZ", "--section", "nodes"]);
+	assert!(debug.contains("\"N1A\""));
+	assert!(!debug.contains("\"YYYY\""));
+	assert!(!debug.contains("\"B1\""));
+
+	let unknown = dump(&path, &["--aerodrome", "XXXX"]);
+	assert!(!unknown.status.success());
+	let stderr = String::from_utf8(unknown.stderr).unwrap();
+	assert!(stderr.contains("unknown aerodrome XXXX"), "{stderr}");
+
+	let unknown = dump(&path, &["--section", "runways"]);
+	assert!(!unknown.status.success());
+}
+
+/// Adds a block without nodes, which is only a warning.
+fn add_empty_block(aerodrome: &mut Aerodrome) {
+	aerodrome.blocks.push(Block {
+		id: "B2".into(),
+		nodes: Vec::new(),
+		edges: Vec::new(),
+		non_routes: Vec::new(),
+		stands: Vec::new(),
+		input_order: None,
+	});
+
+	for profile in &mut aerodrome.profiles {
+		profile.blocks.push(BlockCondition {
+			reset: ResetCondition::None,
+		});
+	}
+}
+
+#[test]
+fn checks_exit_by_severity() {
+	let code = |path: &Path, args: &[&str]| {
+		let args = [&["--check"], args].concat();
+		dump(path, &args).status.code().unwrap()
+	};
+
+	let clean = config("clean", vec![aerodrome("ZZZZ")]);
+	assert_eq!(dumped(&clean, &["--check"]), "");
+	assert_eq!(code(&clean, &["--strict"]), 0);
+
+	let mut warned = aerodrome("ZZZZ");
+	add_empty_block(&mut warned);
+	let warned = config("warned", vec![warned]);
+	assert_eq!(
+		dumped(&warned, &["--check"]),
+		"warning: ZZZZ: blocks[1]: block has no nodes\n",
+	);
+	assert_eq!(code(&warned, &["--strict"]), 1);
+	assert_eq!(code(&warned, &["--strict", "--allow", "empty-block"]), 0);
+
+	let findings = dumped(&warned, &["--check", "--format", "json"]);
+	let findings = serde_json::from_str::<Value>(&findings).unwrap();
+	assert_eq!(findings[0]["severity"], "warning");
+	assert_eq!(findings[0]["code"], "empty-block");
+
+	let mut broken = aerodrome("ZZZZ");
+	add_empty_block(&mut broken);
+	broken.nodes[3].id = "N1".into();
+	let broken = config("broken", vec![broken]);
+	assert_eq!(code(&broken, &[]), 2);
+	assert_eq!(code(&broken, &["--allow", "empty-block"]), 2);
+	assert_eq!(code(&broken, &["--allow", "duplicate-id"]), 0);
+}
+
+#[test]
+fn stats_count_entities_and_sizes() {
+	let zzzz = aerodrome("ZZZZ");
+	let path = config("stats", vec![zzzz.clone()]);
+	let stats = dumped(&path, &["--stats"]);
+
+	for count in [
+		"  elements: 2",
+		"  nodes: 4",
+		"  edges: 1",
+		"  blocks: 1",
+		"  holding points: 0",
+		"  profiles: 1",
+		"  presets: 1",
+		"  styles: 0",
+	] {
+		assert!(stats.lines().any(|line| line == count), "no {count:?}");
+	}
+
+	// the encoded size of each section, then their total
+	let sizes = (stats.lines())
+		.skip_while(|line| *line != "  encoded bytes:")
+		.skip(1)
+		.map_while(|line| {
+			let (section, size) = line.trim().split_once(' ')?;
+			Some((section, size.trim().parse::<usize>().ok()?))
+		})
+		.collect::<Vec<_>>();
+
+	let (sections, rest) = sizes.split_at(sizes.len() - 2);
+	let [("total", total), ("compressed", _)] = rest else {
+		panic!("no totals in {sizes:?}");
+	};
+
+	let sum = sections.iter().map(|(_, size)| size).sum::<usize>();
+	assert_eq!(sum, *total);
+
+	let whole = zzzz.encode().unwrap().len();
+	assert!(sum.abs_diff(whole) <= 8, "{sum} bytes of {whole}");
+}
+
+#[test]
+fn summaries_count_each_aerodrome() {
+	let path = config("summary", vec![aerodrome("YYYY"), aerodrome("ZZZZ")]);
+
+	let summary = dumped(&path, &["--summary", "--format", "json"]);
+	let summary = serde_json::from_str::<Value>(&summary).unwrap();
+	let counts = |i: usize| {
+		[
+			"icao", "elements", "nodes", "edges", "blocks", "profiles", "maps",
+		]
+		.map(|key| summary[i][key].to_string())
+	};
+	assert_eq!(counts(0), ["\"YYYY\"", "0", "1", "0", "1", "1", "0"]);
+	assert_eq!(counts(1), ["\"ZZZZ\"", "2", "4", "1", "1", "1", "0"]);
+
+	let encoded = aerodrome("ZZZZ").encode().unwrap().len();
+	assert_eq!(summary[1]["encoded"], encoded);
+
+	// only the aerodrome asked for is loaded
+	let summary = dumped(&path, &["--summary", "--icao", "ZZZZ"]);
+	let rows = summary.lines().skip(1).collect::<Vec<_>>();
+	assert_eq!(rows.len(), 1);
+	assert!(rows[0].starts_with("ZZZZ "), "{}", rows[0]);
+}
+
+#[test]
+fn unreadable_configs_exit_with_a_message() {
+	let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("garbage.bars");
+	std::fs::write(&path, b"not a config").unwrap();
+
+	let output = dump(&path, &[]);
+	assert_eq!(output.status.code(), Some(2));
+
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert_eq!(stderr.lines().count(), 1, "{stderr}");
+	assert!(stderr.starts_with("error: "), "{stderr}");
+	assert!(stderr.contains("garbage.bars"), "{stderr}");
+}
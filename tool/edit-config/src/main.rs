@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use bars_config::{Config, Loadable, Severity};
+
+use anyhow::{anyhow, bail, Result};
+
+use clap::{Parser, Subcommand};
+
+/// Move aerodromes between BARS configs.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about = None)]
+struct Args {
+	#[command(subcommand)]
+	command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// Copy an aerodrome into a config of its own.
+	///
+	/// The new config has the name and version of the original. With
+	/// --profile, only the profiles given are kept, and with --prune-fixed,
+	/// nodes and edges which are fixed in all of them and otherwise unused are
+	/// removed too.
+	Extract {
+		/// aerodrome to extract
+		#[arg(short, long, value_name = "ICAO")]
+		aerodrome: String,
+
+		/// keep only the profile with ID, which may be repeated
+		#[arg(short, long = "profile", value_name = "ID")]
+		profiles: Vec<String>,
+
+		/// remove nodes and edges fixed in every profile kept
+		#[arg(long, requires = "profiles")]
+		prune_fixed: bool,
+
+		/// config to extract from
+		#[arg(long, value_name = "FILE")]
+		from: PathBuf,
+
+		/// write to FILE
+		#[arg(short, long, value_name = "FILE")]
+		out: PathBuf,
+	},
+	/// Replace aerodromes in a config with those from another.
+	///
+	/// Each aerodrome keeps its position, and the name and version of the
+	/// target config are kept, ignoring those of the source.
+	Insert {
+		/// add aerodromes which are not already present, at the end
+		#[arg(long)]
+		add: bool,
+
+		/// config to take aerodromes from
+		#[arg(long, value_name = "FILE")]
+		from: PathBuf,
+
+		/// config to insert aerodromes into
+		#[arg(long, value_name = "FILE")]
+		into: PathBuf,
+
+		/// write to FILE
+		#[arg(short, long, value_name = "FILE")]
+		out: PathBuf,
+	},
+}
+
+fn main() -> Result<()> {
+	match Args::parse().command {
+		Command::Extract {
+			aerodrome,
+			profiles,
+			prune_fixed,
+			from,
+			out,
+		} => {
+			let mut config = load(&from)?;
+			config.aerodromes.retain(|a| a.icao == aerodrome);
+
+			if config.aerodromes.is_empty() {
+				bail!("unknown aerodrome {aerodrome}");
+			}
+
+			if !profiles.is_empty() {
+				let profiles = profiles.iter().map(String::as_str).collect::<Vec<_>>();
+				for aerodrome in &mut config.aerodromes {
+					*aerodrome = aerodrome
+						.subset(&profiles, prune_fixed)
+						.map_err(|err| anyhow!("{}: {err}", aerodrome.icao))?;
+				}
+			}
+
+			save(&config, &out)
+		},
+		Command::Insert {
+			add,
+			from,
+			into,
+			out,
+		} => {
+			let source = load(&from)?;
+			let mut config = load(&into)?;
+
+			for aerodrome in source.aerodromes {
+				let mut errors = 0;
+				for issue in aerodrome.validate() {
+					eprintln!("{issue}");
+					errors += (issue.severity() == Severity::Error) as usize;
+				}
+
+				if errors > 0 {
+					bail!("{}: failed validation with {errors} errors", aerodrome.icao);
+				}
+
+				let existing = config
+					.aerodromes
+					.iter_mut()
+					.find(|existing| existing.icao == aerodrome.icao);
+
+				match existing {
+					Some(existing) => *existing = aerodrome,
+					None if add => config.aerodromes.push(aerodrome),
+					None => bail!("{} not in config (use --add)", aerodrome.icao),
+				}
+			}
+
+			save(&config, &out)
+		},
+	}
+}
+
+fn load(path: &Path) -> Result<Config> {
+	let file = File::open(path)?;
+	Config::load(BufReader::new(file))
+		.map_err(|err| anyhow!("{}: {err}", path.display()))
+}
+
+fn save(config: &Config, path: &Path) -> Result<()> {
+	config.save(BufWriter::new(File::create(path)?))?;
+	Ok(())
+}
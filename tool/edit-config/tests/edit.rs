@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use bars_config::{Aerodrome, Config, Grid, Loadable};
+
+fn path(name: &str) -> PathBuf {
+	Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// Returns a synthetic aerodrome of the ICAO, with maps.
+fn grid(icao: &str, size: usize) -> Aerodrome {
+	Aerodrome {
+		icao: icao.into(),
+		..Grid {
+			size,
+			..Grid::default()
+		}
+		.aerodrome()
+	}
+}
+
+/// Writes a config of the aerodromes, returning its path.
+fn save(name: &str, version: &str, aerodromes: Vec<Aerodrome>) -> PathBuf {
+	let config = Config {
+		name: Some(name.into()),
+		version: Some(version.into()),
+		aerodromes,
+	};
+
+	let path = path(&format!("{name}.bars"));
+	config.save(File::create(&path).unwrap()).unwrap();
+	path
+}
+
+fn load(path: &Path) -> Config {
+	Config::load(File::open(path).unwrap()).unwrap()
+}
+
+fn edit(args: &[&str], paths: &[(&str, &Path)]) -> Output {
+	let mut command = Command::new(env!("CARGO_BIN_EXE_bars-edit-config"));
+	command.args(args);
+	for (flag, path) in paths {
+		command.arg(flag).arg(path);
+	}
+
+	command.output().unwrap()
+}
+
+fn succeeded(output: Output) {
+	assert!(
+		output.status.success(),
+		"{}",
+		String::from_utf8_lossy(&output.stderr),
+	);
+}
+
+#[test]
+fn extracting_then_inserting_changes_nothing() {
+	let aerodromes = vec![grid("EGAA", 2), grid("EGBB", 3), grid("EGCC", 2)];
+	let all = save("round", "2025/01", aerodromes);
+
+	let extracted = path("round-egbb.bars");
+	succeeded(edit(
+		&["extract", "--aerodrome", "EGBB"],
+		&[("--from", &all), ("--out", &extracted)],
+	));
+
+	let config = load(&extracted);
+	assert_eq!(config.name.as_deref(), Some("round"));
+	assert_eq!(config.version.as_deref(), Some("2025/01"));
+	let [egbb] = config.aerodromes.as_slice() else {
+		panic!("{} aerodromes extracted", config.aerodromes.len());
+	};
+	assert_eq!(egbb.encode().unwrap(), grid("EGBB", 3).encode().unwrap());
+
+	let inserted = path("round-new.bars");
+	succeeded(edit(
+		&["insert"],
+		&[
+			("--from", &extracted),
+			("--into", &all),
+			("--out", &inserted),
+		],
+	));
+
+	assert_eq!(
+		std::fs::read(&inserted).unwrap(),
+		std::fs::read(&all).unwrap(),
+	);
+}
+
+#[test]
+fn inserting_replaces_in_place_keeping_the_target_metadata() {
+	let all = save("replace", "2025/01", vec![grid("EGAA", 2), grid("EGBB", 2)]);
+	let update = save("update", "2025/02", vec![grid("EGAA", 4)]);
+
+	let inserted = path("replace-new.bars");
+	succeeded(edit(
+		&["insert"],
+		&[("--from", &update), ("--into", &all), ("--out", &inserted)],
+	));
+
+	let config = load(&inserted);
+	assert_eq!(config.name.as_deref(), Some("replace"));
+	assert_eq!(config.version.as_deref(), Some("2025/01"));
+
+	let encoded = (config.aerodromes.iter())
+		.map(|aerodrome| aerodrome.encode().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		encoded,
+		[
+			grid("EGAA", 4).encode().unwrap(),
+			grid("EGBB", 2).encode().unwrap()
+		],
+	);
+}
+
+#[test]
+fn new_aerodromes_are_only_added_when_asked() {
+	let all = save("add", "2025/01", vec![grid("EGAA", 2)]);
+	let new = save("new", "2025/01", vec![grid("EGDD", 2)]);
+	let inserted = path("add-new.bars");
+	let paths = [("--from", &*new), ("--into", &*all), ("--out", &*inserted)];
+
+	let output = edit(&["insert"], &paths);
+	assert!(!output.status.success());
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(
+		stderr.contains("EGDD not in config (use --add)"),
+		"{stderr}"
+	);
+
+	succeeded(edit(&["insert", "--add"], &paths));
+	let icaos = (load(&inserted).aerodromes.into_iter())
+		.map(|aerodrome| aerodrome.icao)
+		.collect::<Vec<_>>();
+	assert_eq!(icaos, ["EGAA", "EGDD"]);
+}
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use bars_config::{Config, Loadable, Maps};
+
+use anyhow::{anyhow, bail, Result};
+
+use clap::Parser;
+
+/// Export the geo map of a BARS config or maps file as KML.
+///
+/// Paths, targets and widgets are placed in folders by kind, for checking
+/// against imagery in Google Earth or similar.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// export the map of aerodrome ICAO (configs only)
+	#[arg(short, long, value_name = "ICAO")]
+	aerodrome: Option<String>,
+
+	/// write to FILE rather than stdout
+	#[arg(short, long, value_name = "FILE")]
+	output: Option<PathBuf>,
+
+	/// config or maps file
+	#[arg(value_name = "FILE")]
+	input: PathBuf,
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+
+	let data = std::fs::read(&args.input)?;
+	let kml = match Config::load(data.as_slice()) {
+		Ok(config) => {
+			let aerodrome = match &args.aerodrome {
+				Some(icao) => config
+					.aerodromes
+					.iter()
+					.find(|aerodrome| &aerodrome.icao == icao)
+					.ok_or_else(|| anyhow!("unknown aerodrome {icao}"))?,
+				None if config.aerodromes.len() == 1 => &config.aerodromes[0],
+				None => bail!("config has multiple aerodromes, so one must be given"),
+			};
+
+			aerodrome.to_kml()
+		},
+		Err(_) => {
+			let name = (args.input.file_stem())
+				.map(|stem| stem.to_string_lossy())
+				.unwrap_or_default();
+			Maps::load(data.as_slice())?.to_kml(&name)
+		},
+	};
+
+	let kml = kml.ok_or_else(|| anyhow!("no geo map"))?;
+
+	match &args.output {
+		Some(path) => std::fs::write(path, kml)?,
+		None => print!("{kml}"),
+	}
+
+	Ok(())
+}
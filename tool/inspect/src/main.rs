@@ -0,0 +1,248 @@
+mod model;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use bars_config::{Config, Loadable};
+
+use anyhow::Result;
+
+use clap::Parser;
+
+use ratatui::crossterm::event::{
+	self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListState, Paragraph, Tabs, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use model::{Model, Pane, Section};
+
+/// Browse the contents of a BARS config in the terminal.
+///
+/// Tab switches between the aerodrome and item panes, the arrow keys move and
+/// change section, `p` cycles the profile used to resolve conditions, `/`
+/// searches ids and `q` quits.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// config file to inspect
+	#[arg(value_name = "FILE")]
+	file: PathBuf,
+}
+
+struct App {
+	/// the model, or the error loading the config
+	model: Result<Model, String>,
+	searching: bool,
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+
+	let model = load(&args.file)
+		.map(Model::new)
+		.map_err(|err| format!("failed to load {}: {err}", args.file.display()));
+
+	let mut app = App {
+		model,
+		searching: false,
+	};
+
+	let terminal = ratatui::init();
+	let result = run(terminal, &mut app);
+	ratatui::restore();
+
+	result
+}
+
+fn load(path: &Path) -> Result<Config> {
+	Ok(Config::load(BufReader::new(File::open(path)?))?)
+}
+
+fn run(mut terminal: DefaultTerminal, app: &mut App) -> Result<()> {
+	loop {
+		terminal.draw(|frame| draw(frame, app))?;
+
+		if let Event::Key(key) = event::read()? {
+			if key.kind == KeyEventKind::Press && !handle(app, key) {
+				return Ok(())
+			}
+		}
+	}
+}
+
+/// Handles a key press, returning whether to continue.
+fn handle(app: &mut App, key: KeyEvent) -> bool {
+	if key.modifiers.contains(KeyModifiers::CONTROL)
+		&& key.code == KeyCode::Char('c')
+	{
+		return false
+	}
+
+	let Ok(model) = &mut app.model else {
+		return key.code != KeyCode::Char('q')
+	};
+
+	if app.searching {
+		let mut search = model.search().to_owned();
+		match key.code {
+			KeyCode::Enter => app.searching = false,
+			KeyCode::Esc => {
+				app.searching = false;
+				search.clear();
+			},
+			KeyCode::Backspace => {
+				search.pop();
+			},
+			KeyCode::Char(c) => search.push(c),
+			_ => (),
+		}
+
+		model.set_search(search);
+		model.pane = Pane::Items;
+		return true
+	}
+
+	match key.code {
+		KeyCode::Char('q') => return false,
+		KeyCode::Tab | KeyCode::BackTab => {
+			model.pane = match model.pane {
+				Pane::Aerodromes => Pane::Items,
+				Pane::Items => Pane::Aerodromes,
+			};
+		},
+		KeyCode::Up | KeyCode::Char('k') => model.move_by(-1),
+		KeyCode::Down | KeyCode::Char('j') => model.move_by(1),
+		KeyCode::PageUp => model.move_by(-10),
+		KeyCode::PageDown => model.move_by(10),
+		KeyCode::Home => model.move_by(isize::MIN),
+		KeyCode::End => model.move_by(isize::MAX),
+		KeyCode::Left | KeyCode::Char('h') => model.cycle_section(-1),
+		KeyCode::Right | KeyCode::Char('l') => model.cycle_section(1),
+		KeyCode::Char(c @ '1'..='6') => {
+			model.set_section(Section::ALL[c as usize - '1' as usize]);
+		},
+		KeyCode::Char('p') => model.cycle_profile(1),
+		KeyCode::Char('P') => model.cycle_profile(-1),
+		KeyCode::Char('/') => app.searching = true,
+		KeyCode::Esc => model.set_search(String::new()),
+		_ => (),
+	}
+
+	true
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+	let model = match &app.model {
+		Ok(model) => model,
+		Err(err) => {
+			let text = vec![
+				Line::styled(err.as_str(), Style::new().fg(Color::Red)),
+				Line::default(),
+				Line::raw("press q to quit"),
+			];
+
+			let error = Paragraph::new(text)
+				.block(Block::bordered().title("error"))
+				.wrap(Wrap { trim: false });
+			frame.render_widget(error, frame.area());
+			return
+		},
+	};
+
+	let [main, status] =
+		Layout::vertical([Constraint::Min(0), Constraint::Length(1)])
+			.areas(frame.area());
+	let [aerodromes, items, detail] = Layout::horizontal([
+		Constraint::Length(10),
+		Constraint::Percentage(35),
+		Constraint::Min(0),
+	])
+	.areas(main);
+
+	let focused = |pane| {
+		if model.pane == pane {
+			Style::new().fg(Color::Yellow)
+		} else {
+			Style::new()
+		}
+	};
+	let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+	let list = List::new(model.aerodromes())
+		.block(
+			Block::bordered()
+				.title("aerodromes")
+				.border_style(focused(Pane::Aerodromes)),
+		)
+		.highlight_style(highlight);
+	let mut state =
+		ListState::default().with_selected(Some(model.aerodrome_index()));
+	frame.render_stateful_widget(list, aerodromes, &mut state);
+
+	draw_items(frame, model, items, focused(Pane::Items), highlight);
+
+	let detail_text = model
+		.detail()
+		.into_iter()
+		.map(Line::from)
+		.collect::<Vec<_>>();
+	let detail_widget = Paragraph::new(detail_text)
+		.block(Block::bordered().title("detail"))
+		.wrap(Wrap { trim: false });
+	frame.render_widget(detail_widget, detail);
+
+	let status_text = if app.searching {
+		format!("/{}_", model.search())
+	} else {
+		format!(
+			"profile: {}  search: {}  [tab] pane  [←→] section  [p] profile  [/] \
+			 search  [q] quit",
+			model.profile().unwrap_or("none"),
+			if model.search().is_empty() {
+				"none"
+			} else {
+				model.search()
+			},
+		)
+	};
+	frame.render_widget(Paragraph::new(status_text), status);
+}
+
+fn draw_items(
+	frame: &mut Frame,
+	model: &Model,
+	area: Rect,
+	border: Style,
+	highlight: Style,
+) {
+	let block = Block::bordered().border_style(border);
+	let inner = block.inner(area);
+	frame.render_widget(block, area);
+
+	let [tabs, list] =
+		Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+
+	let selected = Section::ALL
+		.iter()
+		.position(|s| *s == model.section())
+		.unwrap();
+	let titles = Section::ALL.iter().map(|s| s.name());
+	frame.render_widget(
+		Tabs::new(titles)
+			.select(selected)
+			.highlight_style(highlight),
+		tabs,
+	);
+
+	let items = model.items();
+	let mut state = ListState::default()
+		.with_selected((!items.is_empty()).then_some(model.item_index()));
+	let widget =
+		List::new(items.into_iter().map(|(_, id)| id)).highlight_style(highlight);
+	frame.render_stateful_widget(widget, list, &mut state);
+}
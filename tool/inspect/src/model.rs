@@ -0,0 +1,673 @@
+use bars_config::{
+	Aerodrome, Block, BlockRoute, BlockState, Config, EdgeCondition,
+	ElementCondition, HoldingPointLocation, Node, NodeCondition, NodeExpression,
+	Ref, ResetCondition,
+};
+
+/// The kinds of entity which can be browsed within an aerodrome.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+	Nodes,
+	Edges,
+	Blocks,
+	HoldingPoints,
+	Elements,
+	Profiles,
+}
+
+impl Section {
+	pub const ALL: [Self; 6] = [
+		Self::Nodes,
+		Self::Edges,
+		Self::Blocks,
+		Self::HoldingPoints,
+		Self::Elements,
+		Self::Profiles,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Nodes => "nodes",
+			Self::Edges => "edges",
+			Self::Blocks => "blocks",
+			Self::HoldingPoints => "holding points",
+			Self::Elements => "elements",
+			Self::Profiles => "profiles",
+		}
+	}
+}
+
+/// The pane which takes movement keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pane {
+	Aerodromes,
+	Items,
+}
+
+/// Navigation and search state, independent of the terminal.
+pub struct Model {
+	config: Config,
+
+	pub pane: Pane,
+	aerodrome: usize,
+	section: Section,
+	/// index into the filtered items
+	item: usize,
+	profile: usize,
+
+	search: String,
+}
+
+impl Model {
+	pub fn new(config: Config) -> Self {
+		Self {
+			config,
+			pane: Pane::Aerodromes,
+			aerodrome: 0,
+			section: Section::Nodes,
+			item: 0,
+			profile: 0,
+			search: String::new(),
+		}
+	}
+
+	pub fn aerodromes(&self) -> impl Iterator<Item = &str> {
+		self.config.aerodromes.iter().map(|a| a.icao.as_str())
+	}
+
+	pub fn aerodrome_index(&self) -> usize {
+		self.aerodrome
+	}
+
+	pub fn aerodrome(&self) -> Option<&Aerodrome> {
+		self.config.aerodromes.get(self.aerodrome)
+	}
+
+	pub fn section(&self) -> Section {
+		self.section
+	}
+
+	pub fn search(&self) -> &str {
+		&self.search
+	}
+
+	/// Returns the profile used to resolve conditions, if there is one.
+	pub fn profile(&self) -> Option<&str> {
+		let profile = self.aerodrome()?.profiles.get(self.profile)?;
+		Some(&profile.id)
+	}
+
+	fn ids(&self) -> Vec<&str> {
+		let Some(aerodrome) = self.aerodrome() else {
+			return Vec::new()
+		};
+
+		match self.section {
+			Section::Nodes => aerodrome.nodes.iter().map(|n| n.id.as_str()).collect(),
+			Section::Edges => aerodrome.edges.iter().map(|e| e.id.as_str()).collect(),
+			Section::Blocks => {
+				aerodrome.blocks.iter().map(|b| b.id.as_str()).collect()
+			},
+			Section::HoldingPoints => (aerodrome.holding_points.iter())
+				.map(|h| h.id.as_str())
+				.collect(),
+			Section::Elements => {
+				aerodrome.elements.iter().map(|e| e.id.as_str()).collect()
+			},
+			Section::Profiles => {
+				aerodrome.profiles.iter().map(|p| p.id.as_str()).collect()
+			},
+		}
+	}
+
+	/// Returns the index and id of each item in the section matching the
+	/// search, which is a case-insensitive substring of the id.
+	pub fn items(&self) -> Vec<(usize, &str)> {
+		let search = self.search.to_lowercase();
+		self
+			.ids()
+			.into_iter()
+			.enumerate()
+			.filter(|(_, id)| id.to_lowercase().contains(&search))
+			.collect()
+	}
+
+	/// Returns the position of the selection within the filtered items.
+	pub fn item_index(&self) -> usize {
+		self.item
+	}
+
+	fn selected(&self) -> Option<usize> {
+		self.items().get(self.item).map(|(i, _)| *i)
+	}
+
+	pub fn move_by(&mut self, delta: isize) {
+		let len = match self.pane {
+			Pane::Aerodromes => self.config.aerodromes.len(),
+			Pane::Items => self.items().len(),
+		};
+		let index = match self.pane {
+			Pane::Aerodromes => &mut self.aerodrome,
+			Pane::Items => &mut self.item,
+		};
+
+		if len == 0 {
+			return
+		}
+
+		*index = index.saturating_add_signed(delta).min(len - 1);
+
+		if self.pane == Pane::Aerodromes {
+			self.item = 0;
+			self.profile = 0;
+		}
+	}
+
+	pub fn set_section(&mut self, section: Section) {
+		self.section = section;
+		self.item = 0;
+	}
+
+	pub fn cycle_section(&mut self, delta: isize) {
+		let len = Section::ALL.len() as isize;
+		let i = Section::ALL
+			.iter()
+			.position(|s| *s == self.section)
+			.unwrap();
+		self
+			.set_section(Section::ALL[(i as isize + delta).rem_euclid(len) as usize]);
+	}
+
+	pub fn cycle_profile(&mut self, delta: isize) {
+		let Some(aerodrome) = self.aerodrome() else {
+			return
+		};
+
+		let len = aerodrome.profiles.len() as isize;
+		if len > 0 {
+			self.profile = (self.profile as isize + delta).rem_euclid(len) as usize;
+		}
+	}
+
+	pub fn set_search(&mut self, search: String) {
+		self.search = search;
+		self.item = 0;
+	}
+
+	/// Describes the selected item, resolving refs to ids and conditions
+	/// through the current profile.
+	pub fn detail(&self) -> Vec<String> {
+		let (Some(aerodrome), Some(i)) = (self.aerodrome(), self.selected()) else {
+			return Vec::new()
+		};
+
+		let names = Names(aerodrome);
+		let profile = aerodrome.profiles.get(self.profile);
+		let mut lines = Vec::new();
+
+		match self.section {
+			Section::Nodes => {
+				let node = &aerodrome.nodes[i];
+				lines.push(format!("node {}", node.id));
+
+				if let Some(scratchpad) = &node.scratchpad {
+					lines.push(format!("scratchpad: {scratchpad}"));
+				}
+
+				if let Some(parent) = node.parent {
+					lines.push(format!("parent: {}", names.node(parent)));
+				}
+
+				if let Some(order) = node.input_order {
+					lines.push(format!("input order: {order}"));
+				}
+
+				let children = aerodrome
+					.nodes
+					.iter()
+					.filter(|n| n.parent.is_some_and(|p| p.0 == i))
+					.map(|n| n.id.as_str())
+					.collect::<Vec<_>>();
+				if !children.is_empty() {
+					lines.push(format!("children: {}", children.join(", ")));
+				}
+
+				let blocks = aerodrome
+					.blocks
+					.iter()
+					.filter(|b| b.nodes.iter().any(|n| n.0 == i))
+					.map(|b| b.id.as_str())
+					.collect::<Vec<_>>();
+				lines.push(format!("blocks: {}", blocks.join(", ")));
+
+				if let Some(condition) = profile.and_then(|p| p.nodes.get(i)) {
+					lines.push(String::new());
+					lines.push(format!("condition: {}", node_condition(condition)));
+				}
+			},
+			Section::Edges => {
+				let edge = &aerodrome.edges[i];
+				lines.push(format!("edge {}", edge.id));
+				if let Some(name) = &edge.name {
+					lines.push(format!("name: {name}"));
+				}
+
+				let elements = aerodrome
+					.elements
+					.iter()
+					.filter(|e| e.condition == ElementCondition::Edge(i.into()))
+					.map(|e| e.id.as_str())
+					.collect::<Vec<_>>();
+				lines.push(format!("elements: {}", elements.join(", ")));
+
+				if let Some(condition) = profile.and_then(|p| p.edges.get(i)) {
+					lines.push(String::new());
+					lines.extend(names.edge_condition(condition));
+				}
+			},
+			Section::Blocks => {
+				let block = &aerodrome.blocks[i];
+				lines.extend(names.block(block));
+
+				if let Some(order) = block.input_order {
+					lines.push(format!("input order: {order}"));
+				}
+
+				if let Some(condition) = profile.and_then(|p| p.blocks.get(i)) {
+					lines.push(String::new());
+					lines.push(format!("reset: {}", reset(condition.reset)));
+				}
+			},
+			Section::HoldingPoints => {
+				let holding_point = &aerodrome.holding_points[i];
+				lines.push(format!("holding point {}", holding_point.id));
+				lines.push(format!("label: {}", holding_point.label));
+				lines.push(format!(
+					"location: {}",
+					match holding_point.location {
+						HoldingPointLocation::Edge(edge) =>
+							format!("edge {}", names.edge(edge)),
+						HoldingPointLocation::Nodes(a, b) =>
+							format!("between {} and {}", names.node(a), names.node(b)),
+					},
+				));
+
+				if let Some(position) = holding_point.position {
+					lines.push(format!("position: {}, {}", position.lat, position.lon));
+				}
+			},
+			Section::Elements => {
+				let element = &aerodrome.elements[i];
+				lines.push(format!("element {}", element.id));
+				lines.push(format!(
+					"condition: {}",
+					match &element.condition {
+						ElementCondition::Fixed(true) => "fixed on".into(),
+						ElementCondition::Fixed(false) => "fixed off".into(),
+						ElementCondition::Node(node) =>
+							format!("node {}", names.node(*node)),
+						ElementCondition::Edge(edge) =>
+							format!("edge {}", names.edge(*edge)),
+						ElementCondition::Expression(expression) =>
+							format!("expression, {}", expression.to_text(aerodrome)),
+					},
+				));
+			},
+			Section::Profiles => {
+				let profile = &aerodrome.profiles[i];
+				lines.push(format!("profile {} ({})", profile.id, profile.name));
+
+				if !profile.tags.is_empty() {
+					lines.push(format!("tags: {}", profile.tags.join(", ")));
+				}
+
+				for preset in &profile.presets {
+					lines.push(String::new());
+					lines.push(format!("preset {}", preset.name));
+
+					if !preset.includes.is_empty() {
+						let includes = (preset.includes.iter())
+							.map(|i| {
+								profile.presets.get(i.0).map_or("?", |p| p.name.as_str())
+							})
+							.collect::<Vec<_>>();
+						lines.push(format!("  includes: {}", includes.join(", ")));
+					}
+
+					for (node, state) in &preset.nodes {
+						lines.push(format!("  node {}: {state:?}", names.node(*node)));
+					}
+
+					for (block, state) in &preset.blocks {
+						let state = match state {
+							BlockState::Clear => "clear".into(),
+							BlockState::Relax => "relax".into(),
+							BlockState::Route((from, to)) => {
+								format!("route {} to {}", names.node(*from), names.node(*to))
+							},
+						};
+
+						lines.push(format!("  block {}: {state}", names.block_id(*block)));
+					}
+				}
+			},
+		}
+
+		lines
+	}
+}
+
+fn reset(reset: ResetCondition) -> String {
+	match reset {
+		ResetCondition::None => "never".into(),
+		ResetCondition::TimeSecs(secs) => format!("after {secs}s"),
+	}
+}
+
+fn node_condition(condition: &NodeCondition) -> String {
+	match condition {
+		NodeCondition::Fixed { state } => format!("fixed {state:?}"),
+		NodeCondition::Direct { reset: r } => {
+			format!("direct, reset {}", reset(*r))
+		},
+		NodeCondition::Router { sticky } => format!("router, sticky {sticky}"),
+	}
+}
+
+/// Resolves refs to ids, tolerating refs out of range.
+struct Names<'a>(&'a Aerodrome);
+
+impl<'a> Names<'a> {
+	fn node(&self, node: Ref<Node>) -> &'a str {
+		self.0.nodes.get(node.0).map_or("?", |n| n.id.as_str())
+	}
+
+	fn edge(&self, edge: Ref<bars_config::Edge>) -> &'a str {
+		self.0.edges.get(edge.0).map_or("?", |e| e.id.as_str())
+	}
+
+	fn block_id(&self, block: Ref<Block>) -> &'a str {
+		self.0.blocks.get(block.0).map_or("?", |b| b.id.as_str())
+	}
+
+	fn route(&self, route: &BlockRoute) -> String {
+		format!("{} to {}", self.node(route.from), self.node(route.to))
+	}
+
+	fn expression(&self, expression: &NodeExpression) -> String {
+		let conjunctions = expression
+			.disjunction
+			.iter()
+			.map(|conjunction| {
+				let positive =
+					conjunction.positive.iter().map(|n| self.node(*n).into());
+				let negative = conjunction
+					.negative
+					.iter()
+					.map(|n| format!("!{}", self.node(*n)));
+				positive
+					.chain(negative)
+					.collect::<Vec<String>>()
+					.join(" & ")
+			})
+			.collect::<Vec<_>>();
+
+		if conjunctions.is_empty() {
+			"never".into()
+		} else {
+			conjunctions.join(" | ")
+		}
+	}
+
+	fn edge_condition(&self, condition: &EdgeCondition) -> Vec<String> {
+		match condition {
+			EdgeCondition::Fixed { state } => {
+				vec![format!("condition: fixed {state:?}")]
+			},
+			EdgeCondition::Direct { nodes } => {
+				vec![format!("condition: direct, {}", self.expression(nodes))]
+			},
+			EdgeCondition::Router { block, routes } => {
+				let mut lines = vec![format!(
+					"condition: router, block {}",
+					self.block_id(*block)
+				)];
+				lines
+					.extend(routes.iter().map(|r| format!("  route {}", self.route(r))));
+				lines
+			},
+			EdgeCondition::Expression { expression } => vec![format!(
+				"condition: expression, {}",
+				expression.to_text(self.0)
+			)],
+		}
+	}
+
+	fn block(&self, block: &Block) -> Vec<String> {
+		let list = |ids: Vec<&str>| ids.join(", ");
+
+		let mut lines = vec![
+			format!("block {}", block.id),
+			format!(
+				"nodes: {}",
+				list(block.nodes.iter().map(|n| self.node(*n)).collect()),
+			),
+			format!(
+				"edges: {}",
+				list(block.edges.iter().map(|e| self.edge(*e)).collect()),
+			),
+		];
+
+		if !block.stands.is_empty() {
+			lines.push(format!("stands: {}", block.stands.join(", ")));
+		}
+
+		for route in &block.non_routes {
+			lines.push(format!("  non-route {}", self.route(route)));
+		}
+
+		lines
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bars_config::AerodromeSource;
+
+	const EGAA: &str = r#"
+		icao = "EGAA"
+
+		[[elements]]
+		id = "STOP_A1"
+		node = "A1"
+
+		[[nodes]]
+		id = "A1"
+
+		[[nodes]]
+		id = "A2"
+
+		[[nodes]]
+		id = "B1"
+
+		[[edges]]
+		id = "TWY_A"
+
+		[[blocks]]
+		id = "K1"
+		nodes = ["A1", "A2", "B1"]
+		edges = ["TWY_A"]
+
+		[[profiles]]
+		id = "normal"
+		name = "Normal"
+
+		[profiles.nodes]
+		A1 = { direct = { reset = 60 } }
+		A2 = { router = {} }
+		B1 = { router = {} }
+
+		[profiles.edges]
+		TWY_A = { router = { block = "K1", routes = [["A1", "A2"]] } }
+
+		[[profiles.presets]]
+		name = "Open"
+		nodes = { A1 = true }
+		blocks = { K1 = { route = ["A1", "A2"] } }
+
+		[[profiles.presets]]
+		name = "All"
+		includes = ["Open"]
+
+		[[profiles]]
+		id = "lvp"
+		name = "Low visibility"
+
+		[profiles.nodes]
+		A1 = { fixed = false }
+		A2 = { router = { sticky = true } }
+		B1 = { router = {} }
+
+		[profiles.edges]
+		TWY_A = { fixed = false }
+	"#;
+
+	const EGBB: &str = r#"
+		icao = "EGBB"
+
+		[[nodes]]
+		id = "N1"
+	"#;
+
+	fn model() -> Model {
+		let aerodrome = |source| {
+			let source = toml::from_str::<AerodromeSource>(source).unwrap();
+			source.compile().unwrap()
+		};
+
+		Model::new(Config {
+			name: None,
+			version: None,
+			aerodromes: vec![aerodrome(EGAA), aerodrome(EGBB)],
+		})
+	}
+
+	fn ids(model: &Model) -> Vec<&str> {
+		model.items().into_iter().map(|(_, id)| id).collect()
+	}
+
+	#[test]
+	fn moving_stays_within_the_pane() {
+		let mut model = model();
+		assert_eq!(model.aerodromes().collect::<Vec<_>>(), ["EGAA", "EGBB"]);
+
+		model.move_by(-1);
+		assert_eq!(model.aerodrome_index(), 0);
+		model.move_by(5);
+		assert_eq!(model.aerodrome().unwrap().icao, "EGBB");
+
+		model.move_by(-1);
+		model.pane = Pane::Items;
+		model.move_by(1);
+		assert_eq!(model.item_index(), 1);
+		model.move_by(10);
+		assert_eq!(model.item_index(), 2);
+
+		// choosing another aerodrome starts its items from the top
+		model.pane = Pane::Aerodromes;
+		model.move_by(1);
+		assert_eq!(model.item_index(), 0);
+		assert_eq!(ids(&model), ["N1"]);
+	}
+
+	#[test]
+	fn sections_cycle_and_reset_the_selection() {
+		let mut model = model();
+		model.pane = Pane::Items;
+		model.move_by(2);
+
+		model.cycle_section(-1);
+		assert_eq!(model.section(), Section::Profiles);
+		assert_eq!(model.item_index(), 0);
+		assert_eq!(ids(&model), ["normal", "lvp"]);
+
+		model.cycle_section(2);
+		assert_eq!(model.section(), Section::Edges);
+		assert_eq!(ids(&model), ["TWY_A"]);
+	}
+
+	#[test]
+	fn searches_filter_ids_ignoring_case() {
+		let mut model = model();
+		model.pane = Pane::Items;
+		model.move_by(1);
+
+		model.set_search("a".into());
+		assert_eq!(model.item_index(), 0);
+		assert_eq!(model.items(), [(0, "A1"), (1, "A2")]);
+
+		// the selection is of the filtered items
+		model.move_by(1);
+		assert_eq!(model.detail()[0], "node A2");
+
+		model.set_search("Z".into());
+		assert_eq!(ids(&model), Vec::<&str>::new());
+		assert_eq!(model.detail(), Vec::<String>::new());
+		model.move_by(1);
+		assert_eq!(model.item_index(), 0);
+	}
+
+	#[test]
+	fn details_resolve_conditions_through_the_profile() {
+		let mut model = model();
+		assert_eq!(
+			model.detail(),
+			[
+				"node A1",
+				"blocks: K1",
+				"",
+				"condition: direct, reset after 60s",
+			],
+		);
+
+		model.cycle_profile(1);
+		assert_eq!(model.profile(), Some("lvp"));
+		assert_eq!(model.detail()[3], "condition: fixed Off");
+
+		model.cycle_profile(1);
+		assert_eq!(model.profile(), Some("normal"));
+		model.set_section(Section::Edges);
+		assert_eq!(
+			model.detail(),
+			[
+				"edge TWY_A",
+				"elements: ",
+				"",
+				"condition: router, block K1",
+				"  route A1 to A2",
+			],
+		);
+	}
+
+	#[test]
+	fn presets_are_shown_with_ids() {
+		let mut model = model();
+		model.set_section(Section::Profiles);
+
+		assert_eq!(
+			model.detail(),
+			[
+				"profile normal (Normal)",
+				"",
+				"preset Open",
+				"  node A1: On",
+				"  block K1: route A1 to A2",
+				"",
+				"preset All",
+				"  includes: Open",
+			],
+		);
+	}
+}
@@ -0,0 +1,223 @@
+mod passes;
+
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use bars_config::{Aerodrome, Config, Loadable, Severity};
+
+use anyhow::{anyhow, bail, Result};
+
+use clap::Parser;
+
+/// Shrink a BARS config for distribution.
+///
+/// Passes run in the order of the options below, and the output is always
+/// written with the best compression. Without any options, this only
+/// recompresses the config.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// remove the profile with ID from every aerodrome
+	#[arg(long, value_name = "ID")]
+	strip_profile: Vec<String>,
+
+	/// remove all maps
+	#[arg(long)]
+	no_maps: bool,
+
+	/// remove the geo map
+	#[arg(long)]
+	no_geo_map: bool,
+
+	/// remove points within TOL of their neighbours' line, in map units for
+	/// maps and metres for geo maps
+	#[arg(long, value_name = "TOL")]
+	simplify: Option<f32>,
+
+	/// sort and deduplicate unordered lists
+	#[arg(long)]
+	canonicalize: bool,
+
+	/// merge identical styles and remove unused ones
+	#[arg(long)]
+	dedup_styles: bool,
+
+	/// check that only the requested lossy passes changed the config, writing
+	/// nothing if not
+	#[arg(long)]
+	verify: bool,
+
+	/// config file to optimise
+	#[arg(value_name = "FILE")]
+	input: PathBuf,
+
+	/// write to FILE
+	#[arg(short, long, value_name = "FILE")]
+	out: PathBuf,
+}
+
+impl Args {
+	/// Applies the passes which change what the config means.
+	fn lossy(&self, aerodrome: &mut Aerodrome) -> Result<()> {
+		if !self.strip_profile.is_empty() {
+			passes::strip_profiles(aerodrome, &self.strip_profile);
+
+			if aerodrome.profiles.is_empty() {
+				bail!("{}: cannot strip every profile", aerodrome.icao);
+			}
+		}
+
+		if self.no_maps {
+			passes::drop_maps(aerodrome);
+		}
+
+		if self.no_geo_map {
+			passes::drop_geo_map(aerodrome);
+		}
+
+		if let Some(tolerance) = self.simplify {
+			passes::simplify(aerodrome, tolerance);
+		}
+
+		if self.canonicalize {
+			passes::canonicalize(aerodrome);
+		}
+
+		Ok(())
+	}
+
+	/// Applies the passes which only change how the config is encoded.
+	fn lossless(&self, aerodrome: &mut Aerodrome) {
+		if self.dedup_styles {
+			passes::dedup_styles(aerodrome);
+		}
+	}
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+
+	if args
+		.simplify
+		.is_some_and(|tolerance| tolerance.is_nan() || tolerance < 0.0)
+	{
+		bail!("simplify tolerance must not be negative");
+	}
+
+	let original = load(&args.input)?;
+
+	let mut errors = 0;
+	for issue in original.validate() {
+		if issue.severity() == Severity::Error {
+			eprintln!("{issue}");
+			errors += 1;
+		}
+	}
+
+	if errors > 0 {
+		bail!("failed validation with {errors} errors");
+	}
+
+	for id in &args.strip_profile {
+		let known = original
+			.aerodromes
+			.iter()
+			.any(|a| a.profiles.iter().any(|p| &p.id == id));
+
+		if !known {
+			bail!("unknown profile {id}");
+		}
+	}
+
+	let mut config = original.clone();
+	for aerodrome in &mut config.aerodromes {
+		args.lossy(aerodrome)?;
+		args.lossless(aerodrome);
+	}
+
+	let mut output = Vec::new();
+	config.save(&mut output)?;
+
+	if args.verify {
+		verify(&args, &original, &output)?;
+	}
+
+	let stdout = &mut std::io::stdout().lock();
+	writeln!(
+		stdout,
+		"{:<8} {:>10} {:>10} {:>7}",
+		"icao", "before", "after", "change"
+	)?;
+	for (before, after) in original.aerodromes.iter().zip(&config.aerodromes) {
+		write_row(
+			stdout,
+			&before.icao,
+			before.compressed_size()?,
+			after.compressed_size()?,
+		)?;
+	}
+	write_row(
+		stdout,
+		"file",
+		std::fs::metadata(&args.input)?.len() as usize,
+		output.len(),
+	)?;
+
+	File::create(&args.out)?.write_all(&output)?;
+
+	Ok(())
+}
+
+fn write_row(
+	output: &mut impl Write,
+	name: &str,
+	before: usize,
+	after: usize,
+) -> Result<()> {
+	let change = if before == 0 {
+		0.0
+	} else {
+		(after as f64 / before as f64 - 1.0) * 100.0
+	};
+
+	writeln!(output, "{name:<8} {before:>10} {after:>10} {change:>+6.1}%")?;
+	Ok(())
+}
+
+/// Checks that the output differs from the original only by the lossy passes.
+///
+/// Both sides have their styles expanded before comparing, so that merging
+/// and renumbering styles is not reported as a change.
+fn verify(args: &Args, original: &Config, output: &[u8]) -> Result<()> {
+	let output = Config::load(output)?;
+
+	if (&output.name, &output.version) != (&original.name, &original.version) {
+		bail!("verification failed: name or version changed");
+	}
+
+	if output.aerodromes.len() != original.aerodromes.len() {
+		bail!("verification failed: aerodromes were added or removed");
+	}
+
+	for (expected, actual) in original.aerodromes.iter().zip(output.aerodromes) {
+		let mut expected = expected.clone();
+		args.lossy(&mut expected)?;
+		passes::expand_styles(&mut expected);
+
+		let mut actual = actual;
+		passes::expand_styles(&mut actual);
+
+		if expected.encode()? != actual.encode()? {
+			bail!("verification failed: {} changed unexpectedly", actual.icao);
+		}
+	}
+
+	Ok(())
+}
+
+fn load(path: &Path) -> Result<Config> {
+	let file = File::open(path)?;
+	Config::load(BufReader::new(file))
+		.map_err(|err| anyhow!("{}: {err}", path.display()))
+}
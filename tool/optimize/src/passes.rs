@@ -0,0 +1,529 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use bars_config::{
+	Aerodrome, ConditionExpression, EdgeCondition, EdgeDisplay, ElementCondition,
+	GeoPoint, NodeDisplay, Path, Point, Projectable, Ref, Style, Target, Widget,
+};
+
+/// metres per degree of latitude
+const METRES_PER_DEGREE: f32 = 111_320.0;
+
+/// Calls `f` on every path in the maps of the aerodrome.
+fn for_each_path(
+	aerodrome: &mut Aerodrome,
+	mut point: impl FnMut(&mut Path<Point>),
+	mut geo: impl FnMut(&mut Path<GeoPoint>),
+) {
+	fn displays<T: Projectable>(
+		nodes: &mut [NodeDisplay<T>],
+		edges: &mut [EdgeDisplay<T>],
+		f: &mut impl FnMut(&mut Path<T>),
+	) {
+		for node in nodes {
+			node.off.iter_mut().for_each(&mut *f);
+			node.on.iter_mut().for_each(&mut *f);
+			node.selected.iter_mut().for_each(&mut *f);
+		}
+
+		for edge in edges {
+			edge.off.iter_mut().for_each(&mut *f);
+			edge.on.iter_mut().for_each(&mut *f);
+			edge.pending.iter_mut().for_each(&mut *f);
+		}
+	}
+
+	if let Some(geo_map) = &mut aerodrome.geo_map {
+		displays(&mut geo_map.nodes, &mut geo_map.edges, &mut geo);
+	}
+
+	for map in &mut aerodrome.maps {
+		map.base.iter_mut().for_each(&mut point);
+		displays(&mut map.nodes, &mut map.edges, &mut point);
+	}
+}
+
+/// Calls `f` on every style ref in the maps of the aerodrome, being those of
+/// paths, then those of targets, then those of widgets.
+fn for_each_style(aerodrome: &mut Aerodrome, f: impl FnMut(&mut Ref<Style>)) {
+	fn targets<'a, T: Projectable + 'a>(
+		targets: impl Iterator<Item = &'a mut Target<T>>,
+		f: &mut impl FnMut(&mut Ref<Style>),
+	) {
+		for target in targets {
+			[&mut target.hover_style, &mut target.active_style]
+				.into_iter()
+				.flatten()
+				.for_each(&mut *f);
+		}
+	}
+
+	fn widgets<T: Projectable>(
+		widgets: &mut [Widget<T>],
+		f: &mut impl FnMut(&mut Ref<Style>),
+	) {
+		for widget in widgets {
+			if let Widget::Countdown {
+				normal_style,
+				warn_style,
+				..
+			} = widget
+			{
+				[normal_style, warn_style]
+					.into_iter()
+					.flatten()
+					.for_each(&mut *f);
+			}
+		}
+	}
+
+	let f = RefCell::new(f);
+	for_each_path(
+		aerodrome,
+		|path| f.borrow_mut()(&mut path.style),
+		|path| f.borrow_mut()(&mut path.style),
+	);
+
+	let mut f = f.into_inner();
+	if let Some(geo_map) = &mut aerodrome.geo_map {
+		let nodes = geo_map.nodes.iter_mut().map(|n| &mut n.target);
+		let blocks = geo_map.blocks.iter_mut().map(|b| &mut b.target);
+		targets(nodes.chain(blocks), &mut f);
+		widgets(&mut geo_map.widgets, &mut f);
+	}
+
+	for map in &mut aerodrome.maps {
+		let nodes = map.nodes.iter_mut().map(|n| &mut n.target);
+		let blocks = map.blocks.iter_mut().map(|b| &mut b.target);
+		targets(nodes.chain(blocks), &mut f);
+		widgets(&mut map.widgets, &mut f);
+	}
+}
+
+/// Calls `f` on every target polygon in the maps of the aerodrome.
+fn for_each_polygon(
+	aerodrome: &mut Aerodrome,
+	mut point: impl FnMut(&mut Vec<Point>),
+	mut geo: impl FnMut(&mut Vec<GeoPoint>),
+) {
+	fn targets<'a, T: Projectable + 'a>(
+		targets: impl Iterator<Item = &'a mut Target<T>>,
+		f: &mut impl FnMut(&mut Vec<T>),
+	) {
+		for target in targets {
+			target.polygons.iter_mut().for_each(&mut *f);
+		}
+	}
+
+	if let Some(geo_map) = &mut aerodrome.geo_map {
+		let nodes = geo_map.nodes.iter_mut().map(|n| &mut n.target);
+		let blocks = geo_map.blocks.iter_mut().map(|b| &mut b.target);
+		targets(nodes.chain(blocks), &mut geo);
+	}
+
+	for map in &mut aerodrome.maps {
+		let nodes = map.nodes.iter_mut().map(|n| &mut n.target);
+		let blocks = map.blocks.iter_mut().map(|b| &mut b.target);
+		targets(nodes.chain(blocks), &mut point);
+	}
+}
+
+/// Removes the profiles with the given ids, returning how many were removed.
+pub fn strip_profiles(aerodrome: &mut Aerodrome, ids: &[String]) -> usize {
+	let len = aerodrome.profiles.len();
+	aerodrome
+		.profiles
+		.retain(|profile| !ids.contains(&profile.id));
+	len - aerodrome.profiles.len()
+}
+
+pub fn drop_maps(aerodrome: &mut Aerodrome) {
+	aerodrome.maps.clear();
+}
+
+pub fn drop_geo_map(aerodrome: &mut Aerodrome) {
+	aerodrome.geo_map = None;
+}
+
+/// Simplifies paths and target polygons, removing points which lie within
+/// `tolerance` of the line through their neighbours.
+///
+/// The tolerance is in map units for maps and metres for geo maps. Geo paths
+/// with pixel offsets are left alone, as offsets cannot be measured in metres.
+pub fn simplify(aerodrome: &mut Aerodrome, tolerance: f32) {
+	let point = |p: &Point| (p.x, p.y);
+	let geo = |p: &GeoPoint| {
+		let scale = p.geo.lat.to_radians().cos();
+		(
+			p.geo.lon * scale * METRES_PER_DEGREE,
+			p.geo.lat * METRES_PER_DEGREE,
+		)
+	};
+	let has_offset =
+		|points: &[GeoPoint]| points.iter().any(|p| p.offset != Point::default());
+
+	for_each_path(
+		aerodrome,
+		|path| path.points = douglas_peucker(&path.points, tolerance, point),
+		|path| {
+			if !has_offset(&path.points) {
+				path.points = douglas_peucker(&path.points, tolerance, geo);
+			}
+		},
+	);
+
+	for_each_polygon(
+		aerodrome,
+		|polygon| *polygon = douglas_peucker(polygon, tolerance, point),
+		|polygon| {
+			if !has_offset(polygon) {
+				*polygon = douglas_peucker(polygon, tolerance, geo);
+			}
+		},
+	);
+}
+
+fn douglas_peucker<T: Clone>(
+	points: &[T],
+	tolerance: f32,
+	position: impl Fn(&T) -> (f32, f32),
+) -> Vec<T> {
+	if points.len() <= 2 {
+		return points.to_vec()
+	}
+
+	let positions = points.iter().map(position).collect::<Vec<_>>();
+	let mut keep = vec![false; points.len()];
+	keep[0] = true;
+	keep[points.len() - 1] = true;
+
+	let mut spans = vec![(0, points.len() - 1)];
+	while let Some((start, end)) = spans.pop() {
+		let (a, b) = (positions[start], positions[end]);
+
+		let farthest = (start + 1..end)
+			.map(|i| (i, distance(positions[i], a, b)))
+			.max_by(|(_, x), (_, y)| x.total_cmp(y));
+
+		if let Some((i, distance)) = farthest {
+			if distance > tolerance {
+				keep[i] = true;
+				spans.push((start, i));
+				spans.push((i, end));
+			}
+		}
+	}
+
+	points
+		.iter()
+		.zip(keep)
+		.filter(|(_, keep)| *keep)
+		.map(|(point, _)| point.clone())
+		.collect()
+}
+
+/// Returns the distance from `p` to the segment from `a` to `b`.
+fn distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+	let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+	let len = dx * dx + dy * dy;
+
+	let t = if len == 0.0 {
+		0.0
+	} else {
+		(((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len).clamp(0.0, 1.0)
+	};
+
+	let (x, y) = (a.0 + t * dx, a.1 + t * dy);
+	((p.0 - x).powi(2) + (p.1 - y).powi(2)).sqrt()
+}
+
+/// Sorts and deduplicates lists whose order has no meaning.
+pub fn canonicalize(aerodrome: &mut Aerodrome) {
+	fn set<T: Ord>(list: &mut Vec<T>) {
+		list.sort();
+		list.dedup();
+	}
+
+	fn expression(expression: &mut ConditionExpression) {
+		for conjunction in &mut expression.disjunction {
+			set(&mut conjunction.positive);
+			set(&mut conjunction.negative);
+		}
+
+		set(&mut expression.disjunction);
+	}
+
+	for element in &mut aerodrome.elements {
+		if let ElementCondition::Expression(condition) = &mut element.condition {
+			expression(condition);
+		}
+	}
+
+	for block in &mut aerodrome.blocks {
+		set(&mut block.nodes);
+		set(&mut block.edges);
+		set(&mut block.non_routes);
+		set(&mut block.stands);
+	}
+
+	for profile in &mut aerodrome.profiles {
+		for condition in &mut profile.edges {
+			match condition {
+				EdgeCondition::Fixed { .. } => (),
+				EdgeCondition::Direct { nodes } => {
+					for conjunction in &mut nodes.disjunction {
+						set(&mut conjunction.positive);
+						set(&mut conjunction.negative);
+					}
+
+					nodes.disjunction.sort_by(|a, b| {
+						(&a.positive, &a.negative).cmp(&(&b.positive, &b.negative))
+					});
+					nodes.disjunction.dedup_by(|a, b| {
+						a.positive == b.positive && a.negative == b.negative
+					});
+				},
+				EdgeCondition::Router { routes, .. } => set(routes),
+				EdgeCondition::Expression {
+					expression: condition,
+				} => expression(condition),
+			}
+		}
+	}
+}
+
+/// Merges identical styles and removes those not used by any path, target or
+/// widget.
+///
+/// Styles are renumbered in order of first use, so the result does not depend
+/// on the original order of the styles.
+pub fn dedup_styles(aerodrome: &mut Aerodrome) {
+	let old = std::mem::take(&mut aerodrome.styles);
+	let mut styles = Vec::new();
+	let mut indices = HashMap::<Style, usize>::new();
+
+	for_each_style(aerodrome, |style| {
+		let value = old[style.0].clone();
+		let i = *indices.entry(value.clone()).or_insert_with(|| {
+			styles.push(value);
+			styles.len() - 1
+		});
+		*style = i.into();
+	});
+
+	aerodrome.styles = styles;
+}
+
+/// Gives every style ref its own copy of its style, so that aerodromes compare
+/// equal however their styles are shared or ordered.
+pub fn expand_styles(aerodrome: &mut Aerodrome) {
+	let old = std::mem::take(&mut aerodrome.styles);
+	let mut styles = Vec::new();
+
+	for_each_style(aerodrome, |style| {
+		styles.push(old[style.0].clone());
+		*style = (styles.len() - 1).into();
+	});
+
+	aerodrome.styles = styles;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bars_config::{Config, Lattice, Loadable, Map, Projection, Severity};
+
+	use proptest::prelude::*;
+
+	fn saved(aerodrome: &Aerodrome) -> Vec<u8> {
+		let config = Config {
+			name: None,
+			version: None,
+			aerodromes: vec![aerodrome.clone()],
+		};
+
+		let mut data = Vec::new();
+		config.save(&mut data).unwrap();
+		data
+	}
+
+	/// Builds a lattice drawn in finely divided circles, both on its geo map
+	/// and on a map projected from it in metres, with its styles listed twice
+	/// and both copies used.
+	fn fixture() -> Aerodrome {
+		let mut aerodrome = Lattice {
+			columns: 4,
+			rows: 4,
+			points: Some(64),
+			seed: 1,
+			..Lattice::default()
+		}
+		.generate();
+
+		let geo_map = aerodrome.geo_map.as_mut().unwrap();
+		let origin = geo_map.nodes[0].off[0].points[0].geo;
+		aerodrome
+			.maps
+			.push(geo_map.project(&Projection::new(origin, 1.0)));
+
+		let styles = aerodrome.styles.len();
+		aerodrome.styles.extend_from_within(..);
+		for edge in &mut geo_map.edges {
+			for path in edge.off.iter_mut().chain(&mut edge.on) {
+				path.style = (path.style.0 + styles).into();
+			}
+		}
+
+		aerodrome
+	}
+
+	/// Returns the aerodrome without its maps and styles.
+	fn undrawn(aerodrome: &Aerodrome) -> Vec<u8> {
+		let mut aerodrome = aerodrome.clone();
+		aerodrome.geo_map = None;
+		aerodrome.maps.clear();
+		aerodrome.styles.clear();
+		aerodrome.encode().unwrap()
+	}
+
+	fn map_paths(map: &Map) -> Vec<&Path<Point>> {
+		let nodes =
+			(map.nodes.iter()).flat_map(|node| node.off.iter().chain(&node.on));
+		let edges =
+			(map.edges.iter()).flat_map(|edge| edge.off.iter().chain(&edge.on));
+		nodes.chain(edges).collect()
+	}
+
+	#[test]
+	fn fixture_is_valid() {
+		let issues = fixture().validate();
+		assert!(
+			!issues
+				.iter()
+				.any(|issue| issue.severity() == Severity::Error),
+			"{issues:?}",
+		);
+	}
+
+	#[test]
+	fn deduplicated_styles_draw_the_same() {
+		let original = fixture();
+		let mut aerodrome = original.clone();
+		dedup_styles(&mut aerodrome);
+
+		assert_eq!(aerodrome.styles.len(), 2);
+		assert!(saved(&aerodrome).len() < saved(&original).len());
+
+		// expanded, each ref has its own copy of the same style
+		let mut expected = original;
+		expand_styles(&mut expected);
+		expand_styles(&mut aerodrome);
+		assert_eq!(aerodrome.encode().unwrap(), expected.encode().unwrap());
+	}
+
+	#[test]
+	fn simplified_paths_stay_within_the_tolerance() {
+		let tolerance = 1.0;
+		let original = fixture();
+		let mut aerodrome = original.clone();
+		simplify(&mut aerodrome, tolerance);
+
+		assert!(saved(&aerodrome).len() < saved(&original).len() / 2);
+		assert_eq!(undrawn(&aerodrome), undrawn(&original));
+
+		let before = map_paths(&original.maps[0]);
+		let after = map_paths(&aerodrome.maps[0]);
+		for (before, after) in before.iter().zip(after) {
+			assert!(after.points.len() < before.points.len());
+			assert_eq!(after.points.first(), before.points.first());
+			assert_eq!(after.points.last(), before.points.last());
+			assert_eq!(after.style, before.style);
+
+			// every point removed is near the path which remains
+			let position = |p: &Point| (p.x, p.y);
+			for point in &before.points {
+				let nearest = (after.points.windows(2))
+					.map(|ends| {
+						let (a, b) = (position(&ends[0]), position(&ends[1]));
+						distance(position(point), a, b)
+					})
+					.fold(f32::INFINITY, f32::min);
+				assert!(nearest <= tolerance + 1e-3, "{point:?} is {nearest} away");
+			}
+		}
+
+		// geo paths are simplified in metres, so by about as much
+		let points = |aerodrome: &Aerodrome| {
+			let geo_map = aerodrome.geo_map.as_ref().unwrap();
+			(geo_map.nodes.iter())
+				.flat_map(|node| node.off.iter().chain(&node.on))
+				.map(|path| path.points.len())
+				.sum::<usize>()
+		};
+		let projected = |aerodrome: &Aerodrome| {
+			(map_paths(&aerodrome.maps[0]).iter())
+				.take(2 * aerodrome.nodes.len())
+				.map(|path| path.points.len())
+				.sum::<usize>()
+		};
+		assert_eq!(points(&original), projected(&original));
+		assert!(
+			points(&aerodrome).abs_diff(projected(&aerodrome)) * 10
+				< points(&aerodrome)
+		);
+	}
+
+	#[test]
+	fn lossy_passes_shrink_the_fixture_and_keep_the_rest() {
+		let original = fixture();
+		let size = |aerodrome: &Aerodrome| saved(aerodrome).len();
+
+		let mut stripped = original.clone();
+		assert_eq!(strip_profiles(&mut stripped, &["profile1".into()]), 1);
+		assert!(size(&stripped) < size(&original));
+		assert_eq!(stripped.maps.len(), 1);
+
+		let mut unmapped = original.clone();
+		drop_maps(&mut unmapped);
+		assert!(size(&unmapped) < size(&original));
+		assert_eq!(undrawn(&unmapped), undrawn(&original));
+		assert!(unmapped.geo_map.is_some());
+
+		drop_geo_map(&mut unmapped);
+		assert!(size(&unmapped) < size(&original));
+		assert_eq!(undrawn(&unmapped), undrawn(&original));
+
+		// the passes which keep the meaning keep the hash, and with the rest
+		// still give a valid config
+		let mut aerodrome = original.clone();
+		canonicalize(&mut aerodrome);
+		dedup_styles(&mut aerodrome);
+		assert_eq!(aerodrome.content_hash(), original.content_hash());
+
+		simplify(&mut aerodrome, 1.0);
+		assert!(size(&aerodrome) < size(&original));
+
+		let issues = aerodrome.validate();
+		assert!(
+			!issues
+				.iter()
+				.any(|issue| issue.severity() == Severity::Error),
+			"{issues:?}",
+		);
+	}
+
+	proptest! {
+		#[test]
+		fn canonicalize_is_idempotent(mut aerodrome: Aerodrome) {
+			let hash = aerodrome.content_hash();
+
+			canonicalize(&mut aerodrome);
+			let once = saved(&aerodrome);
+			prop_assert_eq!(aerodrome.content_hash(), hash);
+
+			canonicalize(&mut aerodrome);
+			prop_assert_eq!(saved(&aerodrome), once);
+		}
+	}
+}
@@ -0,0 +1,243 @@
+mod render;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use bars_config::{
+	Config, EdgeCondition, EdgeState, Loadable, Map, Maps, NodeCondition,
+	NodeState, Style,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use clap::Parser;
+
+use render::{EdgeRender, NodeRender, RenderOptions};
+
+/// Render a view of a BARS map as SVG.
+///
+/// The input may be a config or a maps file. A state file lists one node or
+/// edge per line, as `node ID on|off|selected` or `edge ID on|off`.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// render a map of aerodrome ICAO (configs only)
+	#[arg(short, long, value_name = "ICAO")]
+	aerodrome: Option<String>,
+
+	/// render view NAME, rather than the first, where maps without views have
+	/// one named All of all they draw
+	#[arg(short, long, value_name = "NAME")]
+	view: Option<String>,
+
+	/// hide nodes and edges as the client would in profile ID (configs only)
+	#[arg(short, long, value_name = "ID")]
+	profile: Option<String>,
+
+	/// render everything on, rather than off
+	#[arg(long)]
+	all_on: bool,
+
+	/// render states from FILE, with others off (or on with --all-on)
+	#[arg(short, long, value_name = "FILE")]
+	state: Option<PathBuf>,
+
+	/// outline node and block targets
+	#[arg(long)]
+	targets: bool,
+
+	/// render only level N, with paths on every level
+	#[arg(short, long, value_name = "N", allow_negative_numbers = true)]
+	level: Option<i8>,
+
+	/// width of the image in pixels
+	#[arg(long, default_value_t = 1000.0)]
+	width: f32,
+
+	/// write to FILE rather than stdout
+	#[arg(short, long, value_name = "FILE")]
+	output: Option<PathBuf>,
+
+	/// config or maps file
+	#[arg(value_name = "FILE")]
+	input: PathBuf,
+}
+
+struct Source {
+	maps: Vec<Map>,
+	styles: Vec<Style>,
+	node_ids: Vec<String>,
+	edge_ids: Vec<String>,
+	nodes: Vec<NodeRender>,
+	edges: Vec<EdgeRender>,
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+
+	let data = std::fs::read(&args.input)?;
+	let mut source = match Config::load(data.as_slice()) {
+		Ok(config) => from_config(config, &args)?,
+		Err(_) => from_maps(Maps::load(data.as_slice())?, &args)?,
+	};
+
+	for node in &mut source.nodes {
+		node.on = args.all_on;
+	}
+
+	for edge in &mut source.edges {
+		edge.on = args.all_on;
+	}
+
+	if let Some(path) = &args.state {
+		apply_state(&mut source, &std::fs::read_to_string(path)?)?;
+	}
+
+	let (map, view) = source
+		.maps
+		.iter()
+		.flat_map(|map| {
+			(map.views_or_default().into_owned().into_iter())
+				.map(move |view| (map, view))
+		})
+		.find(|(_, view)| args.view.as_ref().is_none_or(|name| &view.name == name))
+		.ok_or_else(|| anyhow!("no such view"))?;
+
+	let options = RenderOptions {
+		width: args.width,
+		targets: args.targets,
+		level: args.level,
+	};
+
+	let svg = render::render_svg(
+		map,
+		&view,
+		&source.styles,
+		&source.nodes,
+		&source.edges,
+		&options,
+	);
+
+	match &args.output {
+		Some(path) => std::fs::write(path, svg)?,
+		None => print!("{svg}"),
+	}
+
+	Ok(())
+}
+
+fn from_config(mut config: Config, args: &Args) -> Result<Source> {
+	let i = match &args.aerodrome {
+		Some(icao) => config
+			.aerodromes
+			.iter()
+			.position(|aerodrome| &aerodrome.icao == icao)
+			.ok_or_else(|| anyhow!("unknown aerodrome {icao}"))?,
+		None if config.aerodromes.len() == 1 => 0,
+		None => bail!("config has multiple aerodromes, so one must be given"),
+	};
+
+	let aerodrome = config.aerodromes.swap_remove(i);
+
+	let mut nodes = aerodrome
+		.nodes
+		.iter()
+		.map(|node| NodeRender {
+			hidden: node.parent.is_some(),
+			..Default::default()
+		})
+		.collect::<Vec<_>>();
+	let mut edges = vec![EdgeRender::default(); aerodrome.edges.len()];
+
+	if let Some(id) = &args.profile {
+		let profile = aerodrome
+			.profiles
+			.iter()
+			.find(|profile| &profile.id == id)
+			.ok_or_else(|| anyhow!("unknown profile {id}"))?;
+
+		for (node, condition) in nodes.iter_mut().zip(&profile.nodes) {
+			let off = NodeCondition::Fixed {
+				state: NodeState::Off,
+			};
+
+			node.hidden |= *condition == off;
+		}
+
+		for (edge, condition) in edges.iter_mut().zip(&profile.edges) {
+			edge.hidden = matches!(
+				condition,
+				EdgeCondition::Fixed {
+					state: EdgeState::Off
+				}
+			);
+		}
+	}
+
+	Ok(Source {
+		maps: aerodrome.maps,
+		styles: aerodrome.styles,
+		node_ids: aerodrome.nodes.into_iter().map(|node| node.id).collect(),
+		edge_ids: aerodrome.edges.into_iter().map(|edge| edge.id).collect(),
+		nodes,
+		edges,
+	})
+}
+
+fn from_maps(maps: Maps, args: &Args) -> Result<Source> {
+	if args.aerodrome.is_some() || args.profile.is_some() {
+		bail!("aerodromes and profiles only apply to configs");
+	}
+
+	Ok(Source {
+		nodes: vec![NodeRender::default(); maps.nodes.len()],
+		edges: vec![EdgeRender::default(); maps.edges.len()],
+		maps: maps.maps,
+		styles: maps.styles,
+		node_ids: maps.nodes,
+		edge_ids: maps.edges,
+	})
+}
+
+fn apply_state(source: &mut Source, text: &str) -> Result<()> {
+	let index = |ids: &[String]| {
+		ids
+			.iter()
+			.enumerate()
+			.map(|(i, id)| (id.clone(), i))
+			.collect::<HashMap<_, _>>()
+	};
+
+	let nodes = index(&source.node_ids);
+	let edges = index(&source.edge_ids);
+
+	for (i, line) in text.lines().enumerate() {
+		let words = line.split_whitespace().collect::<Vec<_>>();
+		let error = |message| anyhow!("state line {}: {message}", i + 1);
+
+		match words[..] {
+			[] => (),
+			["node", id, state] => {
+				let node = nodes.get(id).ok_or_else(|| error("unknown node"))?;
+				let node = &mut source.nodes[*node];
+				match state {
+					"on" => node.on = true,
+					"off" => node.on = false,
+					"selected" => node.selected = true,
+					_ => return Err(error("invalid node state")),
+				}
+			},
+			["edge", id, state] => {
+				let edge = edges.get(id).ok_or_else(|| error("unknown edge"))?;
+				source.edges[*edge].on = match state {
+					"on" => true,
+					"off" => false,
+					_ => return Err(error("invalid edge state")),
+				};
+			},
+			_ => return Err(error("expected node or edge, id, and state")),
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,252 @@
+use std::fmt::Write;
+
+use bars_config::{
+	Color, FillStyle, Map, Path, Point, StrokeStyle, Style, View,
+};
+
+/// State of a node display when rendering.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NodeRender {
+	pub hidden: bool,
+	pub on: bool,
+	pub selected: bool,
+}
+
+/// State of an edge display when rendering.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EdgeRender {
+	pub hidden: bool,
+	pub on: bool,
+}
+
+pub struct RenderOptions {
+	/// width of the image in pixels
+	pub width: f32,
+	/// whether to outline node and block targets
+	pub targets: bool,
+	/// level to render, or every level if none
+	pub level: Option<i8>,
+}
+
+/// Renders `view` of `map` as an SVG document, mirroring the client.
+///
+/// Stroke widths are in pixels, regardless of the scale of the view.
+pub fn render_svg(
+	map: &Map,
+	view: &View,
+	styles: &[Style],
+	nodes: &[NodeRender],
+	edges: &[EdgeRender],
+	options: &RenderOptions,
+) -> String {
+	let bounds = view.bounds;
+	let (w, h) = (bounds.max.x - bounds.min.x, bounds.max.y - bounds.min.y);
+	let scale = options.width / w;
+
+	let mut svg = String::new();
+	let mut hatches = Vec::new();
+	let mut body = String::new();
+
+	let mut draw = |path: &Path<Point>, body: &mut String| {
+		if !path.on_level(options.level) {
+			return
+		}
+
+		let Some(style) = styles.get(path.style.0) else {
+			return
+		};
+
+		let fill = match style.fill_style {
+			FillStyle::None => "none".into(),
+			FillStyle::Fill => color(style.fill_color),
+			FillStyle::Hatch(hatch) => {
+				let key = (hatch_kind(hatch), style.fill_color);
+				let i = match hatches.iter().position(|k| *k == key) {
+					Some(i) => i,
+					None => {
+						hatches.push(key);
+						hatches.len() - 1
+					},
+				};
+
+				format!("url(#hatch{i})")
+			},
+		};
+
+		let stroke = match style.stroke_style {
+			StrokeStyle::None => "stroke=\"none\"".into(),
+			StrokeStyle::Dash(dash) => {
+				// svg draws sub-pixel widths, unlike the plugin
+				let width = f32::from(style.stroke_width);
+				let mut stroke = format!(
+					"stroke=\"{}\" stroke-width=\"{width}\"",
+					color(style.stroke_color),
+				);
+
+				if let Some(pattern) = dash_pattern(dash) {
+					let pattern = pattern
+						.iter()
+						.map(|n| (n * width.max(1.0)).to_string())
+						.collect::<Vec<_>>();
+					let _ = write!(stroke, " stroke-dasharray=\"{}\"", pattern.join(" "));
+				}
+
+				stroke
+			},
+		};
+
+		let element = if style.fill_style == FillStyle::None {
+			"polyline"
+		} else {
+			"polygon"
+		};
+
+		let _ = writeln!(
+			body,
+			"<{element} points=\"{}\" fill=\"{fill}\" {stroke} \
+			 vector-effect=\"non-scaling-stroke\"/>",
+			points(&path.points),
+		);
+	};
+
+	for path in &map.base {
+		draw(path, &mut body);
+	}
+
+	for (edge, state) in map.edges.iter().zip(edges) {
+		if state.hidden {
+			continue
+		}
+
+		let display = if state.on { &edge.on } else { &edge.off };
+		display.iter().for_each(|path| draw(path, &mut body));
+	}
+
+	for (node, state) in map.nodes.iter().zip(nodes) {
+		if state.hidden {
+			continue
+		}
+
+		let display = if state.on { &node.on } else { &node.off };
+		display.iter().for_each(|path| draw(path, &mut body));
+
+		if state.selected {
+			node.selected.iter().for_each(|path| draw(path, &mut body));
+		}
+	}
+
+	if options.targets {
+		let targets = map
+			.nodes
+			.iter()
+			.map(|node| &node.target)
+			.chain(map.blocks.iter().map(|block| &block.target))
+			.filter(|target| target.on_level(options.level));
+
+		for polygon in targets.flat_map(|target| &target.polygons) {
+			let _ = writeln!(
+				body,
+				"<polygon points=\"{}\" fill=\"none\" stroke=\"#ff00ff\" \
+				 stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"/>",
+				points(polygon),
+			);
+		}
+	}
+
+	let _ = writeln!(
+		svg,
+		"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+		 viewBox=\"{} {} {w} {h}\">",
+		options.width,
+		h * scale,
+		bounds.min.x,
+		bounds.min.y,
+	);
+
+	if !hatches.is_empty() {
+		let _ = writeln!(svg, "<defs>");
+		for (i, (kind, fill)) in hatches.iter().enumerate() {
+			let size = 8.0 / scale;
+			let _ = writeln!(
+				svg,
+				"<pattern id=\"hatch{i}\" patternUnits=\"userSpaceOnUse\" \
+				 width=\"{size}\" height=\"{size}\">",
+			);
+
+			for line in hatch_lines(*kind) {
+				let [x1, y1, x2, y2] = line.map(|n| n * size);
+				let _ = writeln!(
+					svg,
+					"<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"{}\" \
+					 stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"/>",
+					color(*fill),
+				);
+			}
+
+			let _ = writeln!(svg, "</pattern>");
+		}
+		let _ = writeln!(svg, "</defs>");
+	}
+
+	let _ = writeln!(
+		svg,
+		"<rect x=\"{}\" y=\"{}\" width=\"{w}\" height=\"{h}\" fill=\"{}\"/>",
+		bounds.min.x,
+		bounds.min.y,
+		color(map.background),
+	);
+
+	svg += &body;
+	svg += "</svg>\n";
+
+	svg
+}
+
+fn color(color: Color) -> String {
+	// the client draws with gdi, which ignores alpha
+	format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn points(points: &[Point]) -> String {
+	points
+		.iter()
+		.map(|point| format!("{},{}", point.x, point.y))
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Returns the dash pattern of a gdi pen style, in multiples of its width.
+fn dash_pattern(dash: i32) -> Option<&'static [f32]> {
+	match dash {
+		1 => Some(&[6.0, 2.0]),
+		2 => Some(&[1.0, 1.0]),
+		3 => Some(&[3.0, 2.0, 1.0, 2.0]),
+		4 => Some(&[3.0, 1.0, 1.0, 1.0, 1.0, 1.0]),
+		_ => None,
+	}
+}
+
+/// Maps a hatch style to the gdi hatch brush the client uses.
+fn hatch_kind(hatch: i32) -> i32 {
+	match hatch {
+		0..=5 => hatch,
+		_ => 2,
+	}
+}
+
+/// Returns the lines of a hatch pattern tile, in units of the tile size.
+fn hatch_lines(kind: i32) -> Vec<[f32; 4]> {
+	let horizontal = [0.0, 0.5, 1.0, 0.5];
+	let vertical = [0.5, 0.0, 0.5, 1.0];
+	let forward = [0.0, 0.0, 1.0, 1.0];
+	let backward = [0.0, 1.0, 1.0, 0.0];
+
+	match kind {
+		0 => vec![horizontal],
+		1 => vec![vertical],
+		3 => vec![backward],
+		4 => vec![horizontal, vertical],
+		5 => vec![forward, backward],
+		_ => vec![forward],
+	}
+}
@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use bars_config::{Loadable, Maps};
+
+fn fixture(name: &str) -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/fixtures")
+		.join(name)
+}
+
+/// Returns the path of the maps of the topsky fixture, written once for every
+/// test.
+fn maps() -> &'static Path {
+	static MAPS: OnceLock<PathBuf> = OnceLock::new();
+	MAPS.get_or_init(|| {
+		let text = std::fs::read_to_string(fixture("ground.txt")).unwrap();
+		let maps = Maps::load_topsky(&text).unwrap();
+
+		let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ground.bars");
+		maps.save(File::create(&path).unwrap()).unwrap();
+		path
+	})
+}
+
+/// Renders the maps, checking the SVG against the file of the name in
+/// `tests/fixtures`.
+fn check(args: &[&str], golden: &str) {
+	let output = Command::new(env!("CARGO_BIN_EXE_bars-render-map"))
+		.args(["--width", "200"])
+		.args(args)
+		.arg(maps())
+		.output()
+		.unwrap();
+
+	assert!(
+		output.status.success(),
+		"{}",
+		String::from_utf8_lossy(&output.stderr),
+	);
+
+	let expected = std::fs::read_to_string(fixture(golden)).unwrap();
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn everything_off_matches_golden() {
+	check(&[], "off.svg");
+}
+
+#[test]
+fn states_and_targets_match_golden() {
+	let state = fixture("state.txt");
+	let state = state.to_str().unwrap();
+	check(&["--state", state, "--targets"], "state.svg");
+}
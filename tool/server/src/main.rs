@@ -1,9 +1,12 @@
 use std::collections::{HashMap, HashSet};
 use std::io::stderr;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use bars_protocol::SceneryObject;
+use bars_config::Loadable;
+use bars_protocol::{epoch_millis, SceneryObject, LOCK_DURATION};
 
 use anyhow::Result;
 
@@ -38,6 +41,8 @@ use tracing_subscriber::FmtSubscriber;
 type Downstream = bars_protocol::Downstream<Value>;
 type Upstream = bars_protocol::Upstream<Value>;
 
+const TIME_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Serve a local version of the BARS server.
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
@@ -50,6 +55,11 @@ struct Args {
 	#[arg(short = 'o', long = "observer", value_name = "KEY")]
 	observer_keys: Vec<String>,
 
+	/// advertise the config hashes of the aerodromes in FILE, so that clients
+	/// can check that their configs match
+	#[arg(long = "config", value_name = "FILE")]
+	configs: Vec<PathBuf>,
+
 	/// bind server to ADDRESS
 	#[arg(value_name = "ADDRESS")]
 	bind: SocketAddr,
@@ -58,6 +68,8 @@ struct Args {
 struct Config {
 	controller_keys: HashSet<String>,
 	observer_keys: HashSet<String>,
+	/// config hashes by aerodrome
+	config_hashes: HashMap<String, String>,
 }
 
 type State = HashMap<String, StateEntry>;
@@ -82,9 +94,35 @@ struct Aerodrome {
 	controllers: HashSet<String>,
 	objects: HashMap<String, bool>,
 	state: Value,
+	locks: HashMap<String, (String, Instant)>,
 }
 
 impl Aerodrome {
+	fn acquire(&mut self, block: &str, controller: &str) -> &String {
+		let now = Instant::now();
+		let (owner, expiry) = self
+			.locks
+			.entry(block.into())
+			.or_insert_with(|| (controller.into(), now));
+
+		if owner == controller || *expiry <= now {
+			*owner = controller.into();
+			*expiry = now + LOCK_DURATION;
+		}
+
+		owner
+	}
+
+	fn release(&mut self, block: &str, controller: &str) {
+		if self
+			.locks
+			.get(block)
+			.is_some_and(|(owner, _)| owner == controller)
+		{
+			self.locks.remove(block);
+		}
+	}
+
 	fn merge_state(&mut self, state: Value) {
 		fn merge(target: &mut Value, source: Value) {
 			if target.is_object() && source.is_object() {
@@ -129,9 +167,20 @@ async fn main() -> Result<()> {
 
 	let listener = TcpListener::bind(args.bind).await?;
 
+	let mut config_hashes = HashMap::new();
+	for path in &args.configs {
+		let file = std::fs::File::open(path)?;
+		for aerodrome in bars_config::Config::load(file)?.aerodromes {
+			let hash = aerodrome.config_hash();
+			info!("advertising {} as {hash}", aerodrome.icao);
+			config_hashes.insert(aerodrome.icao, hash);
+		}
+	}
+
 	let config: &'static _ = Box::leak(Box::new(Config {
 		controller_keys: HashSet::from_iter(args.controller_keys),
 		observer_keys: HashSet::from_iter(args.observer_keys),
+		config_hashes,
 	}));
 	let state = Arc::new(Mutex::new(State::new()));
 
@@ -196,6 +245,7 @@ async fn handle(
 				if controller || observer {
 					let state = state.clone();
 					let icao = icao.to_string();
+					let config_hash = config.config_hashes.get(&icao).cloned();
 
 					tokio::spawn(async move {
 						match hyper::upgrade::on(req).await {
@@ -224,7 +274,9 @@ async fn handle(
 
 								let id_opt = controller.then_some(&id);
 
-								if let Err(err) = handle_socket(conn, id_opt, entry).await {
+								if let Err(err) =
+									handle_socket(conn, id_opt, config_hash, entry).await
+								{
 									error!("handling error: {err}");
 								}
 
@@ -240,6 +292,8 @@ async fn handle(
 										aerodrome.state = Value::Null;
 									}
 
+									aerodrome.locks.retain(|_, (owner, _)| owner != &id);
+
 									let _ =
 										state.broadcast.send(Downstream::ControllerDisconnect {
 											controller_id: id.clone(),
@@ -357,6 +411,7 @@ fn get_websocket_request(req: &Request<Incoming>) -> Option<String> {
 async fn handle_socket<S>(
 	mut conn: WebSocketStream<S>,
 	controller: Option<&String>,
+	config_hash: Option<String>,
 	state: StateEntry,
 ) -> Result<()>
 where
@@ -403,13 +458,21 @@ where
 					})
 					.collect(),
 				patch: aerodrome.state.clone(),
+				config_hash,
 			},
 		)
 		.await?;
 	}
 
+	let mut time_interval = tokio::time::interval(TIME_INTERVAL);
+
 	loop {
 		tokio::select! {
+			_ = time_interval.tick() => {
+				send(&mut conn, &Downstream::Time {
+					server_epoch_millis: epoch_millis(),
+				}).await?;
+			},
 			Ok(message) = rx.recv() => {
 				send(&mut conn, &message).await?;
 			},
@@ -445,6 +508,22 @@ where
 									controller_id: id.clone(),
 								});
 							},
+							(Upstream::Acquire { block }, Some(id)) => {
+								let owner = {
+									let mut aerodrome = state.aerodrome.lock().await;
+									aerodrome.acquire(&block, id).clone()
+								};
+
+								send(&mut conn, &Downstream::Lock {
+									granted: &owner == id,
+									block,
+									owner,
+								}).await?;
+							},
+							(Upstream::Release { block }, Some(id)) => {
+								let mut aerodrome = state.aerodrome.lock().await;
+								aerodrome.release(&block, id);
+							},
 							(Upstream::SharedStateUpdate { patch }, Some(id)) => {
 								let mut aerodrome = state.aerodrome.lock().await;
 								aerodrome.merge_state(patch.clone());
@@ -0,0 +1,91 @@
+mod sim;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use clap::Parser;
+
+use sim::Sim;
+
+/// Simulate the lighting of an aerodrome without a server or simulator.
+///
+/// Commands are read from standard input, one per line; enter `help` for a
+/// list. With a script, the commands are echoed as they run, the first error
+/// stops the script, and the final state is printed at the end.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// run the commands in FILE rather than reading standard input
+	#[arg(short, long, value_name = "FILE")]
+	script: Option<PathBuf>,
+
+	/// config file to load first
+	#[arg(value_name = "CONFIG")]
+	config: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+
+	let mut sim = Sim::default();
+	let stdout = &mut std::io::stdout().lock();
+
+	if let Some(config) = &args.config {
+		sim.load(config)?;
+	}
+
+	match &args.script {
+		Some(script) => {
+			let reader = BufReader::new(File::open(script)?);
+			for (i, line) in reader.lines().enumerate() {
+				let line = line?;
+				let command = strip_comment(&line);
+				if command.is_empty() {
+					continue
+				}
+
+				writeln!(stdout, "> {command}")?;
+				sim
+					.execute(command, stdout)
+					.map_err(|err| anyhow!("{}:{}: {err}", script.display(), i + 1))?;
+			}
+
+			writeln!(stdout)?;
+			sim.write_state(stdout)?;
+		},
+		None => {
+			let stdin = std::io::stdin();
+			let interactive = stdin.is_terminal();
+
+			loop {
+				if interactive {
+					write!(stdout, "> ")?;
+					stdout.flush()?;
+				}
+
+				let mut line = String::new();
+				if stdin.read_line(&mut line)? == 0 {
+					break
+				}
+
+				let command = strip_comment(&line);
+				if matches!(command, "quit" | "exit") {
+					break
+				}
+
+				if let Err(err) = sim.execute(command, stdout) {
+					eprintln!("error: {err}");
+				}
+			}
+		},
+	}
+
+	Ok(())
+}
+
+fn strip_comment(line: &str) -> &str {
+	line.split('#').next().unwrap_or_default().trim()
+}
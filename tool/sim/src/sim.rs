@@ -0,0 +1,457 @@
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use bars_client::{Aerodrome, Client, RouteActivation};
+use bars_config::{BlockState, Config, Loadable, NodeCondition, Severity};
+
+use anyhow::{anyhow, bail, Result};
+
+pub const HELP: &str = "\
+load FILE            load a config
+aerodrome ICAO       select an aerodrome, resetting its state
+profile ID           select a profile, resetting the state
+profiles             list profiles and their names
+preset NAME          apply a preset of the profile
+presets              list presets of the profile and their names
+capture NAME         save the state as a preset of the profile
+nodes                list nodes and their states
+blocks               list blocks and their states
+set NODE on|off [S]  set a directly controlled node, resetting in S seconds
+route FROM TO [N]    route between two router nodes, N blocks at a time, or
+                     clear the route if already set
+routes               list routes and their set and planned blocks
+advance ID           set the next planned block of a route
+cancel ID            discard the planned blocks of a route
+undo                 undo the last change to nodes, blocks, routes or presets
+release on|off       clear routes behind blocks as they are cleared
+holds                list holding points passed by routes
+tick SECS            advance the clock, firing any resets
+state                print the states of nodes, edges and blocks
+providers [NAME...]  send scenery for providers, or all if none
+locale [TAG]         print names in a locale, or the defaults if none
+scenery              print the states of elements sent as scenery
+help                 print this message";
+
+/// An aerodrome driven offline by text commands.
+pub struct Sim {
+	config: Option<Config>,
+	client: Client,
+	/// of the aerodrome selected, which is the only one loaded in the client
+	icao: Option<String>,
+	elapsed: Duration,
+	providers: Vec<String>,
+	locale: String,
+	release_trailing: bool,
+}
+
+impl Default for Sim {
+	fn default() -> Self {
+		Self {
+			config: None,
+			client: Client::new_offline(),
+			icao: None,
+			elapsed: Duration::ZERO,
+			providers: Vec::new(),
+			locale: String::new(),
+			release_trailing: false,
+		}
+	}
+}
+
+impl Sim {
+	/// Runs one command, writing any output.
+	pub fn execute(&mut self, line: &str, output: &mut impl Write) -> Result<()> {
+		let words = line.split_whitespace().collect::<Vec<_>>();
+
+		match words.as_slice() {
+			[] => (),
+			["help"] => writeln!(output, "{HELP}")?,
+			["load", path] => self.load(Path::new(path))?,
+			["aerodrome", icao] => self.select(icao)?,
+			["profile", id] => {
+				let aerodrome = self.aerodrome()?;
+				let i = aerodrome
+					.config()
+					.profiles
+					.iter()
+					.position(|p| p.id == *id)
+					.ok_or_else(|| anyhow!("unknown profile {id}"))?;
+
+				aerodrome.set_profile(i)?;
+			},
+			["preset", name] => {
+				let aerodrome = self.aerodrome()?;
+				let profile = &aerodrome.config().profiles[aerodrome.profile()];
+				// those of the config by name, captured ones by the name given
+				let presets = aerodrome.presets();
+				let i = (0..presets.len())
+					.position(|i| match profile.presets.get(i) {
+						Some(preset) => preset.name == *name,
+						None => presets[i].name == *name,
+					})
+					.ok_or_else(|| anyhow!("unknown preset {name}"))?;
+
+				aerodrome.apply_preset(i)?;
+			},
+			["capture", name] => {
+				self.aerodrome()?.save_preset(name);
+			},
+			["profiles"] => {
+				let aerodrome = self.aerodrome()?;
+				for (i, profile) in aerodrome.config().profiles.iter().enumerate() {
+					writeln!(output, "{:<12} {}", profile.id, aerodrome.profile_name(i))?;
+				}
+			},
+			["presets"] => {
+				let aerodrome = self.aerodrome()?;
+				let profile = &aerodrome.config().profiles[aerodrome.profile()];
+				for (i, preset) in aerodrome.presets().into_iter().enumerate() {
+					match profile.presets.get(i) {
+						Some(config) => {
+							writeln!(output, "{:<12} {}", config.name, preset.name)?
+						},
+						None => writeln!(output, "{:<12} (local)", preset.name)?,
+					}
+				}
+			},
+			["nodes"] => self.write_nodes(output)?,
+			["blocks"] => self.write_blocks(output)?,
+			["set", node, state, secs @ ..] if secs.len() <= 1 => {
+				let state = match *state {
+					"on" => true,
+					"off" => false,
+					_ => bail!("expected on or off, not {state}"),
+				};
+				let secs = match secs.first() {
+					Some(secs) => Some(
+						secs
+							.parse::<u32>()
+							.map_err(|_| anyhow!("invalid duration {secs}"))?,
+					),
+					None => None,
+				};
+
+				let aerodrome = self.aerodrome()?;
+				let i = node_index(aerodrome, node)?;
+				match condition(aerodrome, i) {
+					NodeCondition::Direct { .. } => {
+						aerodrome.set_node_timed(i, state, secs)?
+					},
+					_ => bail!("node {node} is not directly controlled"),
+				}
+			},
+			["route", from, to, ahead @ ..] if ahead.len() <= 1 => {
+				let activation = match ahead.first() {
+					Some(ahead) => RouteActivation::Progressive {
+						ahead: ahead
+							.parse()
+							.map_err(|_| anyhow!("invalid number of blocks {ahead}"))?,
+					},
+					None => RouteActivation::Immediate,
+				};
+
+				let aerodrome = self.aerodrome()?;
+				let from = node_index(aerodrome, from)?;
+				let to = node_index(aerodrome, to)?;
+
+				for i in [from, to] {
+					if !matches!(condition(aerodrome, i), NodeCondition::Router { .. }) {
+						bail!(
+							"node {} is not a router node",
+							aerodrome.config().nodes[i].id
+						);
+					}
+				}
+
+				// selecting the ends of a route again clears it
+				let blocks = aerodrome.config().blocks.len();
+				let before = (0..blocks)
+					.map(|i| aerodrome.block_state(i))
+					.collect::<Vec<_>>();
+
+				match aerodrome.set_route_with((from, to), activation)? {
+					Some(id) => writeln!(output, "route {id}")?,
+					None
+						if (0..blocks).any(|i| aerodrome.block_state(i) != before[i]) =>
+					{
+						writeln!(output, "route cleared")?
+					},
+					None => bail!("no route found"),
+				}
+			},
+			["routes"] => {
+				let aerodrome = self.aerodrome()?;
+				let ids = |blocks: &[usize]| {
+					(blocks.iter())
+						.map(|i| aerodrome.config().blocks[*i].id.as_str())
+						.collect::<Vec<_>>()
+						.join(" ")
+				};
+
+				for route in aerodrome.active_routes() {
+					writeln!(
+						output,
+						"{:<4} set {} planned {}",
+						route.id,
+						ids(&route.active),
+						ids(&route.planned),
+					)?;
+				}
+			},
+			["advance", id] => {
+				let id = route_id(id)?;
+				if !self.aerodrome()?.advance_route(id)? {
+					bail!("route {id} has no planned blocks");
+				}
+			},
+			["cancel", id] => {
+				let id = route_id(id)?;
+				if !self.aerodrome()?.cancel_route(id)? {
+					bail!("route {id} has no planned blocks");
+				}
+			},
+			["undo"] => {
+				if !self.aerodrome()?.undo()? {
+					bail!("nothing to undo");
+				}
+			},
+			["release", release] => {
+				let release = match *release {
+					"on" => true,
+					"off" => false,
+					_ => bail!("expected on or off, not {release}"),
+				};
+				self.release_trailing = release;
+				if let Some(aerodrome) = self.loaded() {
+					aerodrome.set_release_trailing(release);
+				}
+			},
+			["holds"] => {
+				let aerodrome = self.aerodrome()?;
+				for i in aerodrome.route_holding_points() {
+					let id = &aerodrome.config().holding_points[i].id;
+					writeln!(output, "{id} {}", aerodrome.holding_point_label(i))?;
+				}
+			},
+			["tick", secs] => {
+				let secs = secs
+					.parse::<f64>()
+					.ok()
+					.filter(|secs| *secs >= 0.0)
+					.ok_or_else(|| anyhow!("invalid duration {secs}"))?;
+				let duration = Duration::from_secs_f64(secs);
+
+				self.aerodrome()?.advance(duration);
+				self.elapsed += duration;
+			},
+			["providers", providers @ ..] => {
+				self.providers = providers.iter().map(|p| p.to_string()).collect();
+				self.client.set_providers(providers);
+			},
+			["locale"] => self.set_locale(""),
+			["locale", locale] => self.set_locale(locale),
+			["state"] => self.write_state(output)?,
+			["scenery"] => self.write_scenery(output)?,
+			[command, ..] => bail!("unknown command or arguments for {command}"),
+		}
+
+		// as the plugin would each frame, so that changes are confirmed and reach
+		// the scenery
+		for message in self.client.tick()? {
+			writeln!(output, "{message}")?;
+		}
+
+		Ok(())
+	}
+
+	pub fn load(&mut self, path: &Path) -> Result<()> {
+		let config = Config::load(BufReader::new(File::open(path)?))
+			.map_err(|err| anyhow!("{}: {err}", path.display()))?;
+
+		let issues = config.validate();
+		if let Some(issue) = issues
+			.iter()
+			.find(|issue| issue.severity() == Severity::Error)
+		{
+			bail!("{}: invalid config: {issue}", path.display());
+		}
+
+		let only = match config.aerodromes.as_slice() {
+			[aerodrome] => Some(aerodrome.icao.clone()),
+			_ => None,
+		};
+
+		self.config = Some(config);
+		self.unload();
+
+		if let Some(icao) = only {
+			self.select(&icao)?;
+		}
+
+		Ok(())
+	}
+
+	fn select(&mut self, icao: &str) -> Result<()> {
+		let config = self.config.as_ref().ok_or_else(|| anyhow!("no config"))?;
+		let aerodrome = config
+			.aerodromes
+			.iter()
+			.find(|a| a.icao == icao)
+			.ok_or_else(|| anyhow!("unknown aerodrome {icao}"))?;
+
+		// there is no server to give control, so the client is offline
+		let aerodrome = aerodrome.clone();
+		self.unload();
+		self.icao = Some(aerodrome.icao.clone());
+		self.client.load_local(aerodrome)?;
+
+		let release_trailing = self.release_trailing;
+		self.aerodrome()?.set_release_trailing(release_trailing);
+
+		self.elapsed = Duration::ZERO;
+
+		Ok(())
+	}
+
+	fn set_locale(&mut self, locale: &str) {
+		self.locale = locale.into();
+		self.client.set_locale(locale);
+	}
+
+	/// Replaces the client with one with nothing of the last aerodrome left.
+	fn unload(&mut self) {
+		let providers = (self.providers.iter())
+			.map(String::as_str)
+			.collect::<Vec<_>>();
+
+		self.client = Client::new_offline();
+		self.client.set_providers(&providers);
+		self.client.set_locale(&self.locale);
+		self.icao = None;
+	}
+
+	fn loaded(&mut self) -> Option<&mut Aerodrome> {
+		self.client.aerodrome_mut(self.icao.as_ref()?)
+	}
+
+	fn aerodrome(&mut self) -> Result<&mut Aerodrome> {
+		self.loaded().ok_or_else(|| anyhow!("no aerodrome"))
+	}
+
+	fn write_nodes(&mut self, output: &mut impl Write) -> Result<()> {
+		let aerodrome = self.aerodrome()?;
+		for (i, node) in aerodrome.config().nodes.iter().enumerate() {
+			let kind = match condition(aerodrome, i) {
+				NodeCondition::Fixed { .. } => "fixed",
+				NodeCondition::Direct { .. } => "direct",
+				NodeCondition::Router { .. } => "router",
+			};
+
+			writeln!(
+				output,
+				"{:<12} {:<6} {}",
+				node.id,
+				kind,
+				on_off(aerodrome.node_state(i)),
+			)?;
+		}
+
+		Ok(())
+	}
+
+	fn write_blocks(&mut self, output: &mut impl Write) -> Result<()> {
+		let aerodrome = self.aerodrome()?;
+		for (i, block) in aerodrome.config().blocks.iter().enumerate() {
+			writeln!(output, "{:<12} {}", block.id, block_state(aerodrome, i))?;
+		}
+
+		Ok(())
+	}
+
+	/// Writes the states of everything, for comparing the outcomes of scripts.
+	pub fn write_state(&mut self, output: &mut impl Write) -> Result<()> {
+		let elapsed = self.elapsed;
+		let aerodrome = self.aerodrome()?;
+		let config = aerodrome.config();
+
+		writeln!(
+			output,
+			"{} profile {} at {}s",
+			config.icao,
+			config.profiles[aerodrome.profile()].id,
+			elapsed.as_secs_f64(),
+		)?;
+
+		for (i, node) in config.nodes.iter().enumerate() {
+			writeln!(
+				output,
+				"node {} {}",
+				node.id,
+				on_off(aerodrome.node_state(i))
+			)?;
+		}
+
+		for (i, edge) in config.edges.iter().enumerate() {
+			writeln!(
+				output,
+				"edge {} {}",
+				edge.id,
+				on_off(aerodrome.edge_state(i))
+			)?;
+		}
+
+		for (i, block) in config.blocks.iter().enumerate() {
+			writeln!(output, "block {} {}", block.id, block_state(aerodrome, i))?;
+		}
+
+		Ok(())
+	}
+
+	fn write_scenery(&mut self, output: &mut impl Write) -> Result<()> {
+		let providers = self.providers.clone();
+		let aerodrome = self.aerodrome()?;
+		for (i, id) in aerodrome.config().provider_elements(&providers) {
+			writeln!(output, "{id} {}", on_off(aerodrome.element_state(i)))?;
+		}
+
+		Ok(())
+	}
+}
+
+fn node_index(aerodrome: &Aerodrome, id: &str) -> Result<usize> {
+	aerodrome
+		.config()
+		.nodes
+		.iter()
+		.position(|node| node.id == id)
+		.ok_or_else(|| anyhow!("unknown node {id}"))
+}
+
+fn route_id(id: &str) -> Result<usize> {
+	id.parse().map_err(|_| anyhow!("invalid route {id}"))
+}
+
+fn condition(aerodrome: &Aerodrome, node: usize) -> NodeCondition {
+	aerodrome.config().profiles[aerodrome.profile()].nodes[node]
+}
+
+fn block_state(aerodrome: &Aerodrome, block: usize) -> String {
+	let nodes = &aerodrome.config().nodes;
+	match aerodrome.block_state(block) {
+		BlockState::Clear => "clear".into(),
+		BlockState::Relax => "relax".into(),
+		BlockState::Route((from, to)) => {
+			format!("route {} to {}", nodes[from.0].id, nodes[to.0].id)
+		},
+	}
+}
+
+fn on_off(state: bool) -> &'static str {
+	if state {
+		"on"
+	} else {
+		"off"
+	}
+}
@@ -0,0 +1,52 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use bars_config::{Config, Grid, Loadable};
+
+/// Writes a config of one synthetic aerodrome, returning its path.
+fn grid_config(size: usize) -> PathBuf {
+	let config = Config {
+		name: Some("grid".into()),
+		version: None,
+		aerodromes: vec![Grid {
+			size,
+			..Grid::default()
+		}
+		.aerodrome()],
+	};
+
+	let path =
+		Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("grid{size}.bin"));
+	config.save(File::create(&path).unwrap()).unwrap();
+	path
+}
+
+/// Runs a script in `tests/scripts`, checking its output against that of the
+/// same name with the extension `out`.
+fn check(config: &Path, script: &str) {
+	let script = Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/scripts")
+		.join(script);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_bars-sim"))
+		.arg("--script")
+		.arg(&script)
+		.arg(config)
+		.output()
+		.unwrap();
+
+	assert!(
+		output.status.success(),
+		"{}",
+		String::from_utf8_lossy(&output.stderr),
+	);
+
+	let expected = std::fs::read_to_string(script.with_extension("out")).unwrap();
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn progressive_route() {
+	check(&grid_config(3), "grid.txt");
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use bars_config::{GroundNetwork, SkeletonNote};
+
+use anyhow::{bail, Result};
+
+use clap::Parser;
+
+/// Generate a skeleton aerodrome source from EuroScope ESE ground network data.
+///
+/// Nodes are derived from holding points and runway entries, edges from the
+/// taxiways between them, and blocks from runway crossings. The source is
+/// written as TOML for build-config, with a `# TODO` comment on everything
+/// which needs checking. A topsky geo map of the derived entities may also be
+/// written, as a starting point for the map.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// aerodrome to generate
+	#[arg(short, long, value_name = "ICAO")]
+	aerodrome: String,
+
+	/// also write a topsky geo map to FILE
+	#[arg(long, value_name = "FILE")]
+	topsky: Option<PathBuf>,
+
+	/// write to FILE rather than stdout
+	#[arg(short, long, value_name = "FILE")]
+	output: Option<PathBuf>,
+
+	/// ESE file
+	#[arg(value_name = "FILE")]
+	input: PathBuf,
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+
+	let bytes = std::fs::read(&args.input)?;
+	// sector files are often not UTF-8, but the lines read are ASCII
+	let text = String::from_utf8_lossy(&bytes);
+
+	let network = GroundNetwork::load_ese(&text, &args.aerodrome)
+		.map_err(|err| anyhow::anyhow!("{}: {err}", args.input.display()))?;
+	if network.runways.is_empty() && network.holding_points.is_empty() {
+		bail!("no ground network for {}", args.aerodrome)
+	}
+
+	let skeleton = network.skeleton(&args.aerodrome);
+	let toml = annotate(&toml::to_string(&skeleton.source)?, &skeleton.notes);
+
+	eprintln!(
+		"{}: {} nodes, {} edges, {} blocks, {} to check",
+		args.aerodrome,
+		skeleton.source.nodes.len(),
+		skeleton.source.edges.len(),
+		skeleton.source.blocks.len(),
+		skeleton.notes.len(),
+	);
+
+	match &args.output {
+		Some(path) => std::fs::write(path, toml)?,
+		None => print!("{toml}"),
+	}
+
+	if let Some(path) = &args.topsky {
+		std::fs::write(path, skeleton.to_topsky())?;
+	}
+
+	Ok(())
+}
+
+/// Adds each note as a comment after the id of the entity it is on, or at the
+/// top if there is none.
+fn annotate(toml: &str, notes: &[SkeletonNote]) -> String {
+	let mut placed = vec![false; notes.len()];
+	let mut body = String::new();
+	let mut table = "";
+
+	for line in toml.lines() {
+		body += line;
+		body += "\n";
+
+		if let Some(name) =
+			line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]"))
+		{
+			table = name;
+		} else if let Some(id) = line.strip_prefix("id = ") {
+			let location = format!("{table}.{}", id.trim_matches('"'));
+			for (note, placed) in notes.iter().zip(&mut placed) {
+				if note.location == location {
+					body += &format!("# TODO: {}\n", note.message);
+					*placed = true;
+				}
+			}
+		}
+	}
+
+	let mut header = String::from("# generated by bars-skeleton\n");
+	for (note, placed) in notes.iter().zip(placed) {
+		if !placed {
+			header += &format!("# TODO: {note}\n");
+		}
+	}
+
+	header + "\n" + &body
+}
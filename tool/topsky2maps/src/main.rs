@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use bars_config::{EdgeDisplay, Loadable, Maps, NodeDisplay, Projectable};
+
+use anyhow::Result;
+
+use clap::Parser;
+
+/// Convert topsky map files into a BARS maps file.
+///
+/// Files are read in order into one set of maps, so that colours defined in one
+/// file can be used in those after it. Exits with 2 if any file fails to parse,
+/// or 1 if only warnings were found.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// skip lines with errors rather than stopping at the first
+	#[arg(long)]
+	lenient: bool,
+
+	/// print counts of the entities found
+	#[arg(long)]
+	summary: bool,
+
+	/// write maps to FILE
+	#[arg(short, long, value_name = "FILE")]
+	output: Option<PathBuf>,
+
+	/// topsky map files
+	#[arg(value_name = "FILE", required = true)]
+	files: Vec<PathBuf>,
+}
+
+fn main() -> Result<ExitCode> {
+	let args = Args::parse();
+
+	let mut files = Vec::new();
+	for path in &args.files {
+		files.push((path.display().to_string(), std::fs::read_to_string(path)?));
+	}
+
+	let files = (files.iter()).map(|(name, text)| (name.as_str(), text.as_str()));
+	let loaded = if args.lenient {
+		Maps::load_topsky_files_lenient(files)
+	} else {
+		Maps::load_topsky_files(files).map(|maps| (maps, Vec::new()))
+	};
+	let (maps, errors) = match loaded {
+		Ok(loaded) => loaded,
+		Err(error) => {
+			eprintln!("error: {error}");
+			return Ok(ExitCode::from(2))
+		},
+	};
+
+	for error in &errors {
+		eprintln!("error: {error}");
+	}
+
+	let warnings = lint(&maps);
+	for warning in &warnings {
+		eprintln!("warning: {warning}");
+	}
+
+	if args.summary {
+		let widgets = maps.maps.iter().map(|map| map.widgets.len()).sum::<usize>()
+			+ maps.geo_map.as_ref().map_or(0, |geo| geo.widgets.len());
+
+		println!("nodes: {}", maps.nodes.len());
+		println!("edges: {}", maps.edges.len());
+		println!("blocks: {}", maps.blocks.len());
+		println!("styles: {}", maps.styles.len());
+		println!("widgets: {widgets}");
+		println!("maps: {}", maps.maps.len());
+		println!(
+			"geo map: {}",
+			if maps.geo_map.is_some() { "yes" } else { "no" }
+		);
+	}
+
+	if let Some(path) = &args.output {
+		maps.save(BufWriter::new(File::create(path)?))?;
+	}
+
+	Ok(if !errors.is_empty() {
+		ExitCode::from(2)
+	} else if !warnings.is_empty() {
+		ExitCode::from(1)
+	} else {
+		ExitCode::SUCCESS
+	})
+}
+
+fn lint(maps: &Maps) -> Vec<String> {
+	let mut warnings = Vec::new();
+
+	if let Some(geo) = &maps.geo_map {
+		lint_displays(maps, "geo map", &geo.nodes, &geo.edges, &mut warnings);
+	}
+
+	for (i, map) in maps.maps.iter().enumerate() {
+		let name = format!("map {}", i + 1);
+		lint_displays(maps, &name, &map.nodes, &map.edges, &mut warnings);
+
+		for view in &map.views {
+			let (min, max) = (view.bounds.min, view.bounds.max);
+			if !(min.x < max.x && min.y < max.y) {
+				warnings.push(format!("{name}: view {} has empty bounds", view.name));
+			}
+		}
+	}
+
+	warnings
+}
+
+fn lint_displays<T: Projectable>(
+	maps: &Maps,
+	name: &str,
+	nodes: &[NodeDisplay<T>],
+	edges: &[EdgeDisplay<T>],
+	warnings: &mut Vec<String>,
+) {
+	for (id, node) in maps.nodes.iter().zip(nodes) {
+		if node.paths().next().is_none() {
+			continue
+		}
+
+		if node.on.is_empty() || node.off.is_empty() {
+			warnings.push(format!("{name}: node {id} lacks on or off drawings"));
+		}
+
+		if node.target.polygons.is_empty() {
+			warnings.push(format!("{name}: node {id} has no target"));
+		}
+	}
+
+	for (id, edge) in maps.edges.iter().zip(edges) {
+		if edge.paths().next().is_some()
+			&& (edge.on.is_empty() || edge.off.is_empty())
+		{
+			warnings.push(format!("{name}: edge {id} lacks on or off drawings"));
+		}
+	}
+}
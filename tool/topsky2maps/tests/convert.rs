@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use bars_config::{Loadable, Maps};
+
+/// Converts files in `tests/fixtures`, writing the maps to a file of the name.
+fn convert(files: &[&str], out: &str, args: &[&str]) -> (Output, PathBuf) {
+	let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(out);
+	let _ = std::fs::remove_file(&path);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_bars-topsky2maps"))
+		.current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"))
+		.args(args)
+		.arg("--output")
+		.arg(&path)
+		.args(files)
+		.output()
+		.unwrap();
+
+	(output, path)
+}
+
+fn stderr(output: &Output) -> String {
+	String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn clean_files_convert() {
+	let files = ["colors.txt", "clean.txt"];
+	let (output, path) = convert(&files, "clean.bars", &["--summary"]);
+	assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+	assert_eq!(stderr(&output), "");
+	assert_eq!(
+		String::from_utf8(output.stdout).unwrap(),
+		"nodes: 1\nedges: 1\nblocks: 0\nstyles: 2\nwidgets: 1\nmaps: 1\ngeo map: no\n",
+	);
+
+	let maps = Maps::load(File::open(path).unwrap()).unwrap();
+	assert_eq!(maps.nodes, ["N1"]);
+	assert_eq!(maps.edges, ["X1"]);
+	assert_eq!(maps.maps[0].views[0].name, "Ground");
+	assert_eq!(maps.maps[0].nodes[0].target.polygons[0].len(), 4);
+}
+
+#[test]
+fn warnings_exit_with_1() {
+	let files = ["colors.txt", "warned.txt"];
+	let (output, path) = convert(&files, "warned.bars", &[]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(
+		stderr(&output),
+		"warning: map 1: node N2 lacks on or off drawings\n\
+		 warning: map 1: node N2 has no target\n\
+		 warning: map 1: view Empty has empty bounds\n",
+	);
+
+	// the maps are written all the same
+	assert!(path.exists());
+}
+
+#[test]
+fn errors_exit_with_2() {
+	// colours are defined only for the files after them
+	let (output, path) = convert(&["clean.txt"], "undefined.bars", &[]);
+	assert_eq!(output.status.code(), Some(2));
+	assert_eq!(
+		stderr(&output),
+		"error: clean.txt: line 1: back undefined\n"
+	);
+	assert!(!path.exists());
+
+	let (output, path) = convert(&["broken.txt"], "broken.bars", &["--lenient"]);
+	assert_eq!(output.status.code(), Some(2));
+	let stderr = stderr(&output);
+	let errors = stderr
+		.lines()
+		.filter(|line| line.starts_with("error: "))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		errors,
+		[
+			"error: broken.txt: line 3: missing undefined",
+			"error: broken.txt: line 7: unknown command FROB",
+		],
+	);
+
+	// the lines without errors are still converted
+	let maps = Maps::load(File::open(path).unwrap()).unwrap();
+	assert_eq!(maps.maps[0].nodes[0].on.len(), 1);
+}
@@ -0,0 +1,285 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use bars_config::{
+	read_version, Config, Loadable, Maps, Severity, INDEXED_FLAG, MAPS_FLAG,
+};
+
+use anyhow::{bail, Result};
+
+use clap::Parser;
+
+/// Upgrade BARS config and maps files to the current version.
+///
+/// Each file given, and each `.bars` file under each directory given, is
+/// reported with its version, then migrated and validated. Nothing is written
+/// without --apply, which rewrites each file in place, keeping the original
+/// with `.bak` appended. Files which fail are left untouched, and the exit
+/// code is 1 if any did.
+#[derive(Debug, Parser)]
+#[command(version, about, long_about)]
+struct Args {
+	/// rewrite files, rather than only reporting what would change
+	#[arg(long, conflicts_with = "dry_run")]
+	apply: bool,
+
+	/// only report what would change, which is the default
+	#[arg(long)]
+	dry_run: bool,
+
+	/// also rewrite files already at the current version, compressing them as
+	/// well as possible
+	#[arg(long)]
+	recompress: bool,
+
+	/// files, or directories to search for .bars files
+	#[arg(value_name = "PATH", required = true)]
+	paths: Vec<PathBuf>,
+}
+
+/// A file decoded at the current version.
+enum Loaded {
+	Config(Config),
+	Maps(Maps),
+}
+
+impl Loaded {
+	/// Encodes the file, with an index if it is a config and `indexed`.
+	fn encode(&self, indexed: bool) -> Result<Vec<u8>> {
+		let mut data = Vec::new();
+		match self {
+			Self::Config(config) if indexed => config.save_indexed(&mut data)?,
+			Self::Config(config) => config.save(&mut data)?,
+			Self::Maps(maps) => maps.save(&mut data)?,
+		}
+		Ok(data)
+	}
+}
+
+fn main() -> Result<ExitCode> {
+	let args = Args::parse();
+
+	let mut files = Vec::new();
+	for path in &args.paths {
+		collect(path, &mut files)?;
+	}
+
+	let mut failures = 0;
+	for path in &files {
+		match upgrade(path, &args) {
+			Ok(outcome) => println!("{}: {outcome}", path.display()),
+			Err(err) => {
+				eprintln!("error: {}: {err}", path.display());
+				failures += 1;
+			},
+		}
+	}
+
+	if failures > 0 {
+		eprintln!(
+			"{failures} of {} files failed and were left untouched",
+			files.len()
+		);
+		Ok(ExitCode::FAILURE)
+	} else {
+		Ok(ExitCode::SUCCESS)
+	}
+}
+
+fn collect(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+	if !path.is_dir() {
+		files.push(path.into());
+		return Ok(())
+	}
+
+	let mut entries = std::fs::read_dir(path)?
+		.map(|entry| entry.map(|entry| entry.path()))
+		.collect::<Result<Vec<_>, _>>()?;
+	entries.sort();
+
+	for entry in entries {
+		if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "bars") {
+			collect(&entry, files)?;
+		}
+	}
+
+	Ok(())
+}
+
+fn upgrade(path: &Path, args: &Args) -> Result<String> {
+	let data = std::fs::read(path)?;
+	let version = read_version(data.as_slice())?;
+
+	// indexed configs stay indexed, so are current at the indexed version
+	let indexed = version & MAPS_FLAG == 0 && version & INDEXED_FLAG != 0;
+	let (kind, current) = if indexed {
+		("indexed config", Config::VERSION | INDEXED_FLAG)
+	} else if version & MAPS_FLAG == 0 {
+		("config", Config::VERSION)
+	} else {
+		("maps", Maps::VERSION)
+	};
+
+	let summary = format!("{kind} version {version:#06x}");
+	if version == current && !args.recompress {
+		return Ok(format!("{summary}, up to date"))
+	}
+
+	let loaded = migrate(&data, version)?;
+	validate(&loaded)?;
+	let encoded = loaded.encode(indexed)?;
+
+	let (action, done) = if version == current {
+		("recompress".into(), "recompressed".into())
+	} else {
+		(
+			format!("upgrade to {current:#06x}"),
+			format!("upgraded to {current:#06x}"),
+		)
+	};
+	let sizes = format!("{} to {} bytes", data.len(), encoded.len());
+
+	if !args.apply {
+		return Ok(format!("{summary}, would {action} ({sizes})"))
+	}
+
+	write(path, &encoded)?;
+	Ok(format!("{summary}, {done} ({sizes})"))
+}
+
+/// Decodes a file at any version which can be migrated to the current one,
+/// being any which can still be loaded.
+fn migrate(data: &[u8], version: u16) -> Result<Loaded> {
+	Ok(if version & MAPS_FLAG == 0 {
+		Loaded::Config(Config::load(data)?)
+	} else {
+		Loaded::Maps(Maps::load(data)?)
+	})
+}
+
+fn validate(loaded: &Loaded) -> Result<()> {
+	let Loaded::Config(config) = loaded else {
+		return Ok(())
+	};
+
+	for aerodrome in &config.aerodromes {
+		let issues = aerodrome.validate();
+		if let Some(issue) =
+			(issues.iter()).find(|issue| issue.severity() == Severity::Error)
+		{
+			bail!("{}: {issue}", aerodrome.icao)
+		}
+	}
+
+	Ok(())
+}
+
+/// Replaces the file with the data, keeping the original with `.bak`
+/// appended, so that the file is never left partly written.
+fn write(path: &Path, data: &[u8]) -> Result<()> {
+	let append = |suffix: &str| {
+		let mut path = path.as_os_str().to_owned();
+		path.push(suffix);
+		PathBuf::from(path)
+	};
+
+	let temp = append(".tmp");
+	std::fs::copy(path, append(".bak"))?;
+
+	let res =
+		std::fs::write(&temp, data).and_then(|()| std::fs::rename(&temp, path));
+	if res.is_err() {
+		let _ = std::fs::remove_file(&temp);
+	}
+
+	Ok(res?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bars_config::Grid;
+
+	fn config() -> Config {
+		Config {
+			name: Some("grid".into()),
+			version: None,
+			aerodromes: vec![Grid::default().aerodrome()],
+		}
+	}
+
+	fn maps() -> Maps {
+		let aerodrome = Grid::default().aerodrome();
+		Maps {
+			nodes: aerodrome.nodes.iter().map(|n| n.id.clone()).collect(),
+			edges: aerodrome.edges.iter().map(|e| e.id.clone()).collect(),
+			blocks: aerodrome.blocks.iter().map(|b| b.id.clone()).collect(),
+			holding_points: Vec::new(),
+			geo_map: aerodrome.geo_map,
+			maps: aerodrome.maps,
+			styles: aerodrome.styles,
+		}
+	}
+
+	/// Migrates a file as written at an older version, checking that it is
+	/// reencoded at the current version.
+	fn check(data: &[u8], version: u16, current: u16) {
+		assert_eq!(read_version(data).unwrap(), version);
+
+		let loaded = migrate(data, version).unwrap();
+		validate(&loaded).unwrap();
+		let encoded = loaded.encode(false).unwrap();
+		assert_eq!(read_version(encoded.as_slice()).unwrap(), current);
+
+		match loaded {
+			Loaded::Config(_) => drop(Config::load(encoded.as_slice()).unwrap()),
+			Loaded::Maps(_) => drop(Maps::load(encoded.as_slice()).unwrap()),
+		}
+	}
+
+	#[test]
+	fn migrates_configs_from_every_version() {
+		let config = config();
+		for version in Config::OLDEST_VERSION..=Config::VERSION {
+			let mut data = Vec::new();
+			config.save_at(version, &mut data).unwrap();
+			check(&data, version, Config::VERSION);
+		}
+	}
+
+	#[test]
+	fn migrates_maps_from_every_version() {
+		let maps = maps();
+		for version in Maps::OLDEST_VERSION..=Maps::VERSION {
+			let mut data = Vec::new();
+			maps.save_at(version, &mut data).unwrap();
+			check(&data, version, Maps::VERSION);
+		}
+	}
+
+	#[test]
+	fn upgrades_files_in_place() {
+		let dir =
+			std::env::temp_dir().join(format!("bars-upgrade-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let path = dir.join("old.bars");
+		let mut data = Vec::new();
+		config().save_at(Config::OLDEST_VERSION, &mut data).unwrap();
+		std::fs::write(&path, &data).unwrap();
+
+		let args = Args::parse_from(["bars-upgrade", "--apply", "old.bars"]);
+		let outcome = upgrade(&path, &args).unwrap();
+		assert!(outcome.contains("upgraded"), "{outcome}");
+
+		let upgraded = std::fs::read(&path).unwrap();
+		assert_eq!(read_version(upgraded.as_slice()).unwrap(), Config::VERSION);
+		assert_eq!(std::fs::read(dir.join("old.bars.bak")).unwrap(), data);
+
+		let outcome = upgrade(&path, &args).unwrap();
+		assert!(outcome.contains("up to date"), "{outcome}");
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}